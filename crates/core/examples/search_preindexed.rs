@@ -38,6 +38,7 @@ pub async fn main() {
         widgets: WidgetsConfig {
             thesaurus_paths: vec!["data/english-wordnet-2022-subset.ttl".to_string()],
             calculator_fetch_currencies_exchange: false,
+            calculator_exchange_rates_path: None,
         },
         spell_check: Some(stract::config::ApiSpellCheck {
             path: "data/web_spell".to_string(),
@@ -45,6 +46,10 @@ pub async fn main() {
         }),
         max_concurrent_searches: defaults::Api::max_concurrent_searches(),
         max_similar_hosts: defaults::Api::max_similar_hosts(),
+        max_batch_queries: defaults::Api::max_batch_queries(),
+        threat_list: None,
+        disable_outbound_requests: false,
+        region_defaults: Default::default(),
     };
 
     let mut queries = stract::autosuggest::Autosuggest::load_csv(&config.queries_csv_path)
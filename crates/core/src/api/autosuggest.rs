@@ -16,14 +16,27 @@
 
 use std::{collections::HashMap, sync::Arc};
 
-use axum::{extract, response::IntoResponse, Json};
+use axum::{
+    extract,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::Serialize;
 use utoipa::{IntoParams, ToSchema};
 
-use crate::highlighted::HighlightedFragment;
+use crate::{
+    autosuggest::{Autosuggest, SuggestionScore},
+    highlighted::HighlightedFragment,
+    search_prettifier::DisplayedWebpage,
+    searcher::{SearchQuery, SearchResult},
+};
 
 use super::State;
 
+/// Shortest prefix that a result is previewed for.
+const MIN_PREVIEW_PREFIX: usize = 3;
+
 fn highlight(query: &str, suggestion: &str) -> Vec<HighlightedFragment> {
     let idx = suggestion
         .chars()
@@ -47,6 +60,9 @@ fn highlight(query: &str, suggestion: &str) -> Vec<HighlightedFragment> {
 pub struct Suggestion {
     highlighted: Vec<HighlightedFragment>,
     raw: String,
+    /// Only included when the request has `debug=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<SuggestionScore>,
 }
 
 #[derive(
@@ -55,6 +71,79 @@ pub struct Suggestion {
 #[serde(rename_all = "camelCase")]
 pub struct AutosuggestQuery {
     q: String,
+    /// Hide adult suggestions.
+    safe_search: Option<bool>,
+    /// Include the score of each suggestion.
+    debug: Option<bool>,
+    /// Also return the top result of the best suggestion when it is likely
+    /// the site the user is typing. The response is then an [`AutosuggestWithPreview`].
+    preview: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultPreview {
+    title: String,
+    url: String,
+    favicon: String,
+}
+
+impl From<DisplayedWebpage> for ResultPreview {
+    fn from(webpage: DisplayedWebpage) -> Self {
+        Self {
+            favicon: format!("https://{}/favicon.ico", webpage.site),
+            title: webpage.title,
+            url: webpage.url,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AutosuggestWithPreview {
+    suggestions: Vec<Suggestion>,
+    preview: Option<ResultPreview>,
+}
+
+/// Whether the prefix looks like the name of the domain, e.g. `githu` for
+/// github.com, so pressing enter can take the user directly to the site.
+fn is_navigational(prefix: &str, domain: &str) -> bool {
+    let prefix: String = prefix
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let name = domain.split('.').next().unwrap_or_default().to_lowercase();
+
+    prefix.chars().count() >= MIN_PREVIEW_PREFIX
+        && name.starts_with(&prefix)
+        && 2 * prefix.len() >= name.len()
+}
+
+/// The top result for the best suggestion if it is navigational for the prefix.
+async fn preview(state: &State, prefix: &str, suggestion: &str) -> Option<ResultPreview> {
+    let query = SearchQuery {
+        query: suggestion.to_string(),
+        num_results: 1,
+        ..Default::default()
+    };
+
+    let webpage = match state.searcher.search(&query).await {
+        Ok(SearchResult::Websites(websites)) => websites.webpages.into_iter().next()?,
+        Ok(SearchResult::Bang(_)) => return None,
+        Err(err) => {
+            tracing::warn!("failed to search for autosuggest preview: {}", err);
+            return None;
+        }
+    };
+
+    is_navigational(prefix, &webpage.domain).then(|| webpage.into())
+}
+
+fn safe_search(params: &HashMap<String, String>) -> bool {
+    params
+        .get("safeSearch")
+        .is_some_and(|safe_search| safe_search == "true")
 }
 
 #[utoipa::path(
@@ -62,28 +151,56 @@ pub struct AutosuggestQuery {
     path = "/beta/api/autosuggest",
     params(AutosuggestQuery),
     responses(
-        (status = 200, description = "Autosuggest", body = Vec<Suggestion>),
+        (status = 200, description = "Autosuggest. With `preview=true` the suggestions are returned in an `AutosuggestWithPreview`.", body = Vec<Suggestion>),
     )
 )]
 
 pub async fn route(
     extract::State(state): extract::State<Arc<State>>,
     extract::Query(params): extract::Query<HashMap<String, String>>,
-) -> impl IntoResponse {
-    if let Some(query) = params.get("q") {
+) -> Response {
+    let suggestions = suggestions(&state, &params);
+
+    if !params
+        .get("preview")
+        .is_some_and(|preview| preview == "true")
+    {
+        return Json(suggestions).into_response();
+    }
+
+    let preview = match (params.get("q"), suggestions.first()) {
+        (Some(prefix), Some(top)) => preview(&state, prefix, &top.raw).await,
+        _ => None,
+    };
+
+    Json(AutosuggestWithPreview {
+        suggestions,
+        preview,
+    })
+    .into_response()
+}
+
+fn suggestions(state: &State, params: &HashMap<String, String>) -> Vec<Suggestion> {
+    if let (Some(query), Some(autosuggest)) = (params.get("q"), state.autosuggest.get()) {
         let mut suggestions = Vec::new();
 
-        for suggestion in state.autosuggest.suggestions(query).unwrap() {
-            let highlighted = highlight(query, &suggestion);
+        let debug = params.get("debug").is_some_and(|debug| debug == "true");
+
+        for scored in autosuggest
+            .scored_suggestions(query, safe_search(params))
+            .unwrap()
+        {
+            let highlighted = highlight(query, &scored.suggestion);
             suggestions.push(Suggestion {
                 highlighted,
-                raw: suggestion,
+                raw: scored.suggestion,
+                score: debug.then_some(scored.score),
             });
         }
 
-        Json(suggestions)
+        suggestions
     } else {
-        Json(Vec::new())
+        Vec::new()
     }
 }
 
@@ -91,13 +208,75 @@ pub async fn browser(
     extract::State(state): extract::State<Arc<State>>,
     extract::Query(params): extract::Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
-    if let Some(query) = params.get("q") {
-        Json((query.clone(), state.autosuggest.suggestions(query).unwrap()))
+    if let (Some(query), Some(autosuggest)) = (params.get("q"), state.autosuggest.get()) {
+        Json((
+            query.clone(),
+            autosuggest
+                .suggestions(query, safe_search(&params))
+                .unwrap(),
+        ))
     } else {
         Json((String::new(), Vec::new()))
     }
 }
 
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadRequest {
+    /// Csv of queries or fst built with the `autosuggest-build` command.
+    /// Defaults to the queries csv of the config.
+    path: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReloadResponse {
+    path: String,
+    num_queries: usize,
+}
+
+/// Load the suggestions from a file and swap them in once they have loaded. Requests
+/// that are in progress finish with the suggestions they started with.
+pub async fn reload(
+    extract::State(state): extract::State<Arc<State>>,
+    headers: HeaderMap,
+    request: Option<Json<ReloadRequest>>,
+) -> Result<Json<ReloadResponse>, (StatusCode, String)> {
+    super::authorize_admin(&state.config, &headers)?;
+
+    let Json(request) = request.unwrap_or_default();
+    let path = request
+        .path
+        .unwrap_or_else(|| state.config.queries_csv_path.clone());
+
+    let autosuggest = {
+        let path = path.clone();
+        let filter = state.autosuggest_filter.clone();
+        let ranking = state.config.autosuggest_ranking.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let autosuggest = Autosuggest::open(path)?.with_ranking(ranking);
+
+            match filter {
+                Some(filter) => autosuggest.with_filter(filter),
+                None => Ok(autosuggest),
+            }
+        })
+        .await
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+        .map_err(|err| {
+            tracing::warn!("failed to load autosuggest from {}: {}", path, err);
+            (StatusCode::BAD_REQUEST, err.to_string())
+        })?
+    };
+
+    let num_queries = autosuggest.len();
+    state.autosuggest.swap(autosuggest, path.clone());
+    tracing::info!("reloaded {} autosuggest queries from {}", num_queries, path);
+
+    Ok(Json(ReloadResponse { path, num_queries }))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::highlighted::HighlightedKind;
@@ -124,6 +303,17 @@ mod tests {
             .collect()
     }
 
+    #[test]
+    fn navigational_prefix() {
+        assert!(is_navigational("githu", "github.com"));
+        assert!(is_navigational("GitHub", "github.com"));
+        assert!(is_navigational("bbc", "bbc.co.uk"));
+
+        assert!(!is_navigational("gi", "github.com"));
+        assert!(!is_navigational("wiki", "wikipedia.org"));
+        assert!(!is_navigational("hub", "github.com"));
+    }
+
     #[test]
     fn suffix_highlight() {
         assert_eq!(
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use super::{autosuggest, explore, hosts, search, webgraph};
+use super::{autosuggest, explore, hosts, page, search, site, summarize, webgraph};
 use axum::Router;
 use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
@@ -23,11 +23,14 @@ use utoipa_swagger_ui::SwaggerUi;
 #[openapi(
         paths(
             search::search,
+            search::batch_search,
             search::widget,
             search::sidebar,
             search::spellcheck,
             webgraph::host::similar,
+            webgraph::host::similar_sites,
             webgraph::host::knows,
+            webgraph::host::backlink_set,
             webgraph::host::ingoing_hosts,
             webgraph::host::outgoing_hosts,
             webgraph::page::ingoing_pages,
@@ -35,6 +38,9 @@ use utoipa_swagger_ui::SwaggerUi;
             autosuggest::route,
             hosts::hosts_export_optic,
             explore::explore_export_optic,
+            site::site_info,
+            page::signals,
+            summarize::summarize,
         ),
         components(
             schemas(
@@ -42,13 +48,20 @@ use utoipa_swagger_ui::SwaggerUi;
                 optics::HostRankings,
                 search::ApiSearchQuery,
                 search::ApiSearchResult,
+                search::ApiBatchSearchQuery,
+                search::ApiBatchSearchResult,
                 search::WidgetQuery,
                 search::SidebarQuery,
                 search::SpellcheckQuery,
                 search::ReturnBody,
+                crate::searcher::lens::Lens,
                 crate::searcher::WebsitesResult,
+                crate::searcher::rewrite::QueryRewrite,
+                crate::searcher::rewrite::QueryRewriteKind,
                 crate::search_prettifier::HighlightedSpellCorrection,
                 crate::search_prettifier::DisplayedWebpage,
+                crate::webpage::homograph::Homograph,
+                crate::threat_list::Threat,
                 crate::search_prettifier::DisplayedEntity,
                 crate::search_prettifier::DisplayedAnswer,
                 crate::search_prettifier::DisplayedSidebar,
@@ -57,6 +70,12 @@ use utoipa_swagger_ui::SwaggerUi;
                 crate::search_prettifier::StackOverflowAnswer,
                 crate::search_prettifier::StackOverflowQuestion,
                 crate::search_prettifier::CodeOrText,
+                crate::search_prettifier::PaperMetadata,
+                crate::search_prettifier::DisplayedProduct,
+                crate::search_prettifier::Availability,
+                crate::search_prettifier::ProductFilter,
+                crate::search_prettifier::DisplayedRecipe,
+                crate::search_prettifier::GroupedRecipe,
 
                 crate::snippet::TextSnippet,
                 crate::highlighted::HighlightedFragment,
@@ -84,10 +103,29 @@ use utoipa_swagger_ui::SwaggerUi;
                 crate::bangs::Bang,
 
                 webgraph::host::SimilarHostsParams,
+                webgraph::host::SimilarSitesResponse,
+                webgraph::host::SimilarSite,
+                webgraph::host::SimilarityExplanation,
+                webgraph::host::BacklinkSetParams,
+                webgraph::host::BacklinkSetHost,
+                webgraph::host::BacklinkSet,
+                crate::webgraph::LinkSetOperation,
                 webgraph::KnowsHost,
+                site::SiteInfo,
+                site::AnchorText,
+                site::LinkingHost,
+                site::TopPage,
+                site::SpamFlags,
+                page::PageSignals,
+                crate::host_about::HostAbout,
+                summarize::SummarizeQuery,
+                crate::summarizer::SummaryChunk,
                 crate::entrypoint::webgraph_server::ScoredHost,
 
                 autosuggest::Suggestion,
+                autosuggest::AutosuggestWithPreview,
+                autosuggest::ResultPreview,
+                crate::autosuggest::SuggestionScore,
 
                 hosts::HostsExportOpticParams,
                 explore::ExploreExportOpticParams,
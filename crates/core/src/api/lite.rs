@@ -0,0 +1,381 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A minimal results page rendered on the server for text browsers, screen readers
+//! and clients on slow connections. The page has no javascript, images or external
+//! resources, and all navigation is done with plain links and a GET form.
+
+use std::{fmt::Write, sync::Arc};
+
+use axum::{
+    extract,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use http::StatusCode;
+use url::form_urlencoded;
+
+use crate::{
+    config::defaults,
+    highlighted::HighlightedKind,
+    search_prettifier::DisplayedWebpage,
+    searcher::{SearchQuery, SearchResult, WebsitesResult, NUM_RESULTS_PER_PAGE},
+    threat_list::Threat,
+};
+
+use super::{search::ApiSearchQuery, State};
+
+const STYLE: &str =
+    "body{max-width:42em;margin:0 auto;padding:0.5em;font-family:sans-serif;line-height:1.4}\
+li{margin-bottom:1em}h2{font-size:1.1em;margin:0}.url{color:#006621;margin:0;word-break:break-all}\
+.warning{color:#b00020;font-weight:bold}p{margin:0.2em 0}";
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct LiteQuery {
+    #[serde(default)]
+    q: String,
+    /// Page number starting from 1.
+    p: Option<usize>,
+    gl: Option<String>,
+    hl: Option<String>,
+    /// `nfpr=1` searches for the query as it was typed, without spelling corrections.
+    nfpr: Option<u8>,
+}
+
+impl LiteQuery {
+    fn page(&self) -> usize {
+        self.p.unwrap_or(1).max(1) - 1
+    }
+
+    fn disable_rewrites(&self) -> bool {
+        self.nfpr == Some(1)
+    }
+
+    fn api_query(&self) -> ApiSearchQuery {
+        ApiSearchQuery {
+            query: self.q.clone(),
+            page: Some(self.page()),
+            num_results: None,
+            selected_region: None,
+            optic: None,
+            lens: None,
+            host_rankings: None,
+            safe_search: None,
+            include_parked: None,
+            diversify: None,
+            exclude_hosts: None,
+            product_filter: None,
+            gl: self.gl.clone(),
+            hl: self.hl.clone(),
+            signal_coefficients: None,
+            return_ranking_signals: false,
+            flatten_response: defaults::SearchQuery::flatten_response(),
+            count_results_exact: false,
+            return_structured_data: false,
+            disable_rewrites: self.disable_rewrites(),
+            within_results: None,
+            snapshot: None,
+            #[cfg(feature = "return_body")]
+            return_body: None,
+        }
+    }
+
+    /// Link to another page of results for the same query.
+    fn page_href(&self, page: usize) -> String {
+        self.href(page, self.disable_rewrites())
+    }
+
+    /// Link to the first page of results for the query exactly as it was typed.
+    fn original_query_href(&self) -> String {
+        self.href(0, true)
+    }
+
+    fn href(&self, page: usize, disable_rewrites: bool) -> String {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        serializer.append_pair("q", &self.q);
+        serializer.append_pair("p", &(page + 1).to_string());
+
+        if disable_rewrites {
+            serializer.append_pair("nfpr", "1");
+        }
+
+        if let Some(gl) = &self.gl {
+            serializer.append_pair("gl", gl);
+        }
+
+        if let Some(hl) = &self.hl {
+            serializer.append_pair("hl", hl);
+        }
+
+        format!("/lite?{}", serializer.finish())
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&#39;"),
+            _ => res.push(c),
+        }
+    }
+
+    res
+}
+
+fn render_page(query: &LiteQuery, main: &str) -> String {
+    let lang = query
+        .hl
+        .as_deref()
+        .and_then(crate::localization::Language::from_hl)
+        .unwrap_or_default()
+        .hl();
+
+    let title = if query.q.is_empty() {
+        "Stract".to_string()
+    } else {
+        format!("{} - Stract", escape(&query.q))
+    };
+
+    let mut hidden = String::new();
+    for (name, value) in [("gl", &query.gl), ("hl", &query.hl)] {
+        if let Some(value) = value {
+            let _ = write!(
+                hidden,
+                r#"<input type="hidden" name="{name}" value="{}">"#,
+                escape(value)
+            );
+        }
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="referrer" content="no-referrer">
+<title>{title}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<header>
+<form action="/lite" method="get" role="search">
+<label for="q">Search</label>
+<input id="q" type="search" name="q" value="{query}">{hidden}
+<button type="submit">Search</button>
+</form>
+</header>
+<main>
+{main}
+</main>
+</body>
+</html>
+"#,
+        query = escape(&query.q),
+    )
+}
+
+fn render_webpage(out: &mut String, webpage: &DisplayedWebpage) {
+    let _ = write!(
+        out,
+        r#"<li><h2><a href="{}">{}</a></h2><p class="url">{}</p>"#,
+        escape(&webpage.url),
+        escape(&webpage.title),
+        escape(&webpage.pretty_url),
+    );
+
+    if let Some(threat) = webpage.security_threat {
+        let threat = match threat {
+            Threat::Malware => "malware",
+            Threat::Phishing => "phishing",
+        };
+
+        let _ = write!(
+            out,
+            r#"<p class="warning">Warning: this page is known to host {threat}.</p>"#
+        );
+    }
+
+    if let Some(homograph) = &webpage.homograph_warning {
+        let _ = write!(
+            out,
+            r#"<p class="warning">Warning: {} looks like {}.</p>"#,
+            escape(&homograph.unicode_host),
+            escape(&homograph.lookalike),
+        );
+    }
+
+    out.push_str("<p>");
+
+    if let Some(date) = &webpage.snippet.date {
+        let _ = write!(out, "{} — ", escape(date));
+    }
+
+    for fragment in &webpage.snippet.text.fragments {
+        match fragment.kind {
+            HighlightedKind::Normal => out.push_str(&escape(&fragment.text)),
+            HighlightedKind::Highlighted => {
+                let _ = write!(out, "<b>{}</b>", escape(&fragment.text));
+            }
+        }
+    }
+
+    out.push_str("</p></li>\n");
+}
+
+fn render_results(query: &LiteQuery, result: &WebsitesResult) -> String {
+    if result.webpages.is_empty() {
+        return "<p>No results found.</p>".to_string();
+    }
+
+    let page = query.page();
+    let searched = result
+        .rewrites
+        .last()
+        .map_or(query.q.as_str(), |rewrite| rewrite.to.as_str());
+
+    let mut out = format!("<h1>Results for {}</h1>\n", escape(searched));
+
+    if !result.rewrites.is_empty() {
+        let _ = writeln!(
+            out,
+            r#"<p>Search instead for <a href="{}">{}</a></p>"#,
+            escape(&query.original_query_href()),
+            escape(&query.q)
+        );
+    }
+
+    let _ = writeln!(out, "<ol start=\"{}\">", page * NUM_RESULTS_PER_PAGE + 1);
+
+    for webpage in &result.webpages {
+        render_webpage(&mut out, webpage);
+    }
+
+    out.push_str("</ol>\n<nav aria-label=\"Pages\">");
+
+    if page > 0 {
+        let _ = write!(
+            out,
+            r#"<a rel="prev" href="{}">Previous page</a> "#,
+            escape(&query.page_href(page - 1))
+        );
+    }
+
+    if result.has_more_results {
+        let _ = write!(
+            out,
+            r#"<a rel="next" href="{}">Next page</a>"#,
+            escape(&query.page_href(page + 1))
+        );
+    }
+
+    out.push_str("</nav>");
+
+    out
+}
+
+pub async fn search(
+    extract::State(state): extract::State<Arc<State>>,
+    extract::Query(query): extract::Query<LiteQuery>,
+) -> Response {
+    if query.q.trim().is_empty() {
+        return Html(render_page(&query, "")).into_response();
+    }
+
+    let mut api_query = query.api_query();
+    api_query.apply_region_defaults(&state.region_defaults);
+
+    let search_query = match SearchQuery::try_from(api_query) {
+        Ok(search_query) => search_query,
+        Err(err) => {
+            tracing::debug!("invalid lite query: {:?}", err);
+            return (
+                StatusCode::BAD_REQUEST,
+                Html(render_page(&query, "<p>Invalid search.</p>")),
+            )
+                .into_response();
+        }
+    };
+
+    match state.searcher.search(&search_query).await {
+        Ok(SearchResult::Websites(result)) => {
+            Html(render_page(&query, &render_results(&query, &result))).into_response()
+        }
+        Ok(SearchResult::Bang(hit)) => Redirect::to(hit.redirect_to.as_str()).into_response(),
+        Err(err) => {
+            tracing::error!("{:?}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Html(render_page(
+                    &query,
+                    "<p>Something went wrong. Please try again.</p>",
+                )),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape(r#"<script>alert("x" & 'y')</script>"#),
+            "&lt;script&gt;alert(&quot;x&quot; &amp; &#39;y&#39;)&lt;/script&gt;"
+        );
+    }
+
+    #[test]
+    fn page_links_keep_parameters() {
+        let query = LiteQuery {
+            q: "rust & c".to_string(),
+            p: Some(2),
+            gl: Some("dk".to_string()),
+            hl: None,
+            nfpr: None,
+        };
+
+        assert_eq!(query.page(), 1);
+        assert_eq!(query.page_href(2), "/lite?q=rust+%26+c&p=3&gl=dk");
+        assert_eq!(
+            query.original_query_href(),
+            "/lite?q=rust+%26+c&p=1&nfpr=1&gl=dk"
+        );
+    }
+
+    #[test]
+    fn renders_without_scripts() {
+        let query = LiteQuery {
+            q: "<b>".to_string(),
+            hl: Some("da".to_string()),
+            ..Default::default()
+        };
+
+        let page = render_page(&query, "");
+
+        assert!(page.contains(r#"<html lang="da">"#));
+        assert!(page.contains(r#"value="&lt;b&gt;""#));
+        assert!(!page.contains("<script"));
+    }
+}
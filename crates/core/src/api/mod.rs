@@ -23,20 +23,28 @@ use tower::limit::ConcurrencyLimitLayer;
 use tower_http::compression::CompressionLayer;
 
 use crate::{
-    autosuggest::Autosuggest,
+    autosuggest::{Autosuggest, SuggestionFilter},
     bangs::Bangs,
     config::ApiConfig,
     distributed::{
         cluster::Cluster,
         member::{Member, Service},
     },
+    host_about::HostAboutStore,
     improvement::{store_improvements_loop, ImprovementEvent},
     leaky_queue::LeakyQueue,
-    models::dual_encoder::DualEncoder,
+    localization::RegionDefaults,
+    models::{
+        accelerator::Accelerator,
+        dual_encoder::DualEncoder,
+        registry::{Model, ModelHandle, ModelRegistry},
+    },
     ranking::models::lambdamart::LambdaMART,
     searcher::{api::ApiSearcher, live::LiveSearcher, DistributedSearcher},
     similar_hosts::SimilarHostsFinder,
+    summarizer::ExtractiveSummarizer,
     webgraph::remote::RemoteWebgraph,
+    webpage::safety_classifier,
 };
 
 use crate::ranking::models::cross_encoder::CrossEncoderModel;
@@ -44,7 +52,9 @@ use crate::ranking::models::cross_encoder::CrossEncoderModel;
 use anyhow::Result;
 use std::{
     net::{IpAddr, SocketAddr},
+    path::Path,
     sync::Arc,
+    time::Duration,
 };
 
 use axum::{
@@ -59,15 +69,27 @@ mod docs;
 mod explore;
 mod hosts;
 pub mod improvement;
+mod lite;
 mod metrics;
+mod page;
 pub mod search;
+mod site;
+mod summarize;
 pub mod user_count;
 mod webgraph;
 
+/// Number of passages the summarizer extracts from each page.
+const TOP_SUMMARY_PASSAGES: usize = 3;
+
 pub struct Counters {
     pub search_counter_success: crate::metrics::Counter,
     pub search_counter_fail: crate::metrics::Counter,
     pub explore_counter: crate::metrics::Counter,
+    pub similar_sites_counter: crate::metrics::Counter,
+    pub site_info_counter: crate::metrics::Counter,
+    pub page_signals_counter: crate::metrics::Counter,
+    pub result_cache_hits: crate::metrics::Counter,
+    pub result_cache_misses: crate::metrics::Counter,
     pub daily_active_users: user_count::UserCount<user_count::Daily>,
 }
 
@@ -76,11 +98,15 @@ pub struct State {
     pub searcher: Arc<ApiSearcher<DistributedSearcher, LiveSearcher, Arc<RemoteWebgraph>>>,
     pub page_webgraph: Arc<RemoteWebgraph>,
     pub host_webgraph: Arc<RemoteWebgraph>,
-    pub autosuggest: Autosuggest,
+    pub autosuggest: ModelHandle<Autosuggest>,
+    pub autosuggest_filter: Option<Arc<SuggestionFilter>>,
     pub counters: Counters,
     pub improvement_queue: Option<Arc<Mutex<LeakyQueue<ImprovementEvent>>>>,
     pub _cluster: Arc<Cluster>,
     pub similar_hosts: SimilarHostsFinder,
+    pub region_defaults: RegionDefaults,
+    pub summarizer: ModelHandle<ExtractiveSummarizer>,
+    pub host_about: Option<HostAboutStore>,
 }
 
 pub async fn favicon() -> impl IntoResponse {
@@ -95,6 +121,8 @@ pub async fn favicon() -> impl IntoResponse {
 fn build_router(state: Arc<State>) -> Router {
     let mut search = Router::new()
         .route("/beta/api/search", post(search::search))
+        .route("/beta/api/search/batch", post(search::batch_search))
+        .route("/lite", get(lite::search))
         .route_layer(middleware::from_fn_with_state(state.clone(), search_metric))
         .layer(cors_layer());
 
@@ -105,6 +133,11 @@ fn build_router(state: Arc<State>) -> Router {
     Router::new()
         .merge(search)
         .route("/favicon.ico", get(favicon))
+        .merge(
+            Router::new()
+                .route("/api/v1/similar_sites", get(webgraph::host::similar_sites))
+                .layer(cors_layer()),
+        )
         .merge(
             Router::new()
                 .route("/improvement/click", post(improvement::click))
@@ -123,6 +156,10 @@ fn build_router(state: Arc<State>) -> Router {
                 .route("/api/autosuggest/browser", get(autosuggest::browser))
                 .route("/api/webgraph/host/similar", post(webgraph::host::similar))
                 .route("/api/webgraph/host/knows", post(webgraph::host::knows))
+                .route(
+                    "/api/webgraph/host/backlinks",
+                    post(webgraph::host::backlink_set),
+                )
                 .route(
                     "/api/webgraph/host/ingoing",
                     post(webgraph::host::ingoing_hosts),
@@ -141,25 +178,111 @@ fn build_router(state: Arc<State>) -> Router {
                 )
                 .route("/api/hosts/export", post(hosts::hosts_export_optic))
                 .route("/api/explore/export", post(explore::explore_export_optic))
+                .route("/api/site/:host", get(site::site_info))
+                .route("/api/page/signals", get(page::signals))
+                .route("/api/summarize", post(summarize::summarize))
                 .route("/api/entity_image", get(search::entity_image))
                 .layer(cors_layer()),
         )
+        .route("/admin/autosuggest/reload", post(autosuggest::reload))
         .with_state(state)
 }
 
-pub async fn router(config: &ApiConfig, counters: Counters) -> Result<Router> {
-    let autosuggest = Autosuggest::load_csv(&config.queries_csv_path)?;
-
-    let lambda_model = match &config.lambda_model_path {
-        Some(path) => Some(LambdaMART::open(path)?),
-        None => None,
+/// Admin endpoints are only enabled when an admin token is configured, and the
+/// token must be sent as a bearer token in the `Authorization` header.
+fn authorize_admin(
+    config: &ApiConfig,
+    headers: &axum::http::HeaderMap,
+) -> std::result::Result<(), (StatusCode, String)> {
+    let Some(token) = &config.admin_token else {
+        return Err((StatusCode::NOT_FOUND, String::new()));
     };
 
-    let dual_encoder_model = match &config.dual_encoder_model_path {
-        Some(path) => Some(DualEncoder::open(path)?),
+    let bearer = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    if bearer == Some(token.as_str()) {
+        Ok(())
+    } else {
+        Err((StatusCode::UNAUTHORIZED, "invalid admin token".to_string()))
+    }
+}
+
+pub async fn router(config: &ApiConfig, counters: Counters) -> Result<Router> {
+    let autosuggest_filter = match &config.autosuggest_filter {
+        Some(filter) => Some(Arc::new(SuggestionFilter::open(filter)?)),
         None => None,
     };
 
+    let mut autosuggest = Autosuggest::open(&config.queries_csv_path)?
+        .with_ranking(config.autosuggest_ranking.clone());
+    if let Some(filter) = &autosuggest_filter {
+        autosuggest = autosuggest.with_filter(Arc::clone(filter))?;
+    }
+    let autosuggest = ModelHandle::new(autosuggest);
+
+    let dual_encoder_accelerator = Accelerator::new(&config.dual_encoder_accelerator);
+    let cross_encoder_accelerator = Accelerator::new(&config.cross_encoder_accelerator);
+
+    let summarizer_accelerator = Accelerator::new(&config.summarizer_accelerator);
+    let verifier_accelerator = config
+        .citation_verification
+        .is_some()
+        .then(|| Accelerator::new(&config.cross_encoder_accelerator));
+
+    let (lambda_model, dual_encoder_model, cross_encoder, summarizer, safety_classifier) =
+        match &config.model_registry {
+            Some(registry_config) => {
+                let mut registry = ModelRegistry::new(registry_config);
+                let models = (
+                    registry.register::<LambdaMART>("lambda"),
+                    registry.register_with("dual_encoder", move |path| {
+                        DualEncoder::open_with_accelerator(path, &dual_encoder_accelerator)
+                    }),
+                    registry.register_with("cross_encoder", move |path| {
+                        CrossEncoderModel::open_with_accelerator(path, &cross_encoder_accelerator)
+                    }),
+                    registry.register_with("summarizer", move |path| {
+                        open_summarizer(
+                            path,
+                            &summarizer_accelerator,
+                            verifier_accelerator.as_ref(),
+                        )
+                    }),
+                    registry.register::<safety_classifier::Model>("safety_classifier"),
+                );
+
+                let registry = Arc::new(registry);
+                let initial = Arc::clone(&registry);
+                tokio::task::spawn_blocking(move || initial.reload()).await??;
+
+                tokio::spawn(reload_models_loop(
+                    registry,
+                    Duration::from_secs(registry_config.reload_interval_secs),
+                ));
+
+                models
+            }
+            None => (
+                open_model(&config.lambda_model_path, LambdaMART::load)?,
+                open_model(&config.dual_encoder_model_path, |path| {
+                    DualEncoder::open_with_accelerator(path, &dual_encoder_accelerator)
+                })?,
+                open_model(&config.crossencoder_model_path, |path| {
+                    CrossEncoderModel::open_with_accelerator(path, &cross_encoder_accelerator)
+                })?,
+                open_model(&config.summarizer_path, |path| {
+                    open_summarizer(path, &summarizer_accelerator, verifier_accelerator.as_ref())
+                })?,
+                open_model(
+                    &config.safety_classifier_path,
+                    safety_classifier::Model::load,
+                )?,
+            ),
+        };
+
     let query_store_queue = config.query_store_db_host.clone().map(|db_host| {
         let query_store_queue = Arc::new(Mutex::new(LeakyQueue::new(10_000)));
         tokio::spawn(store_improvements_loop(query_store_queue.clone(), db_host));
@@ -167,6 +290,7 @@ pub async fn router(config: &ApiConfig, counters: Counters) -> Result<Router> {
     });
 
     let bangs = Bangs::from_path(&config.bangs_path);
+    let region_defaults = RegionDefaults::open(&config.region_defaults)?;
 
     let cluster = Arc::new(
         Cluster::join(
@@ -188,27 +312,22 @@ pub async fn router(config: &ApiConfig, counters: Counters) -> Result<Router> {
     let dist_searcher = DistributedSearcher::new(Arc::clone(&cluster)).await;
     let live_searcher = LiveSearcher::new(Arc::clone(&cluster));
 
-    let state = {
-        let mut cross_encoder = None;
-
-        if let Some(path) = config.crossencoder_model_path.as_ref() {
-            cross_encoder = Some(CrossEncoderModel::open(path)?);
-        }
-
-        let mut searcher =
-            ApiSearcher::new(dist_searcher, bangs, config.clone()).with_live(live_searcher);
-
-        if let Some(cross_encoder) = cross_encoder {
-            searcher = searcher.with_cross_encoder(cross_encoder);
-        }
-
-        if let Some(lambda) = lambda_model {
-            searcher = searcher.with_lambda_model(lambda);
-        }
+    let host_about = match &config.host_about_path {
+        Some(path) => Some(HostAboutStore::open(path)?),
+        None => None,
+    };
 
-        if let Some(dual_encoder_model) = dual_encoder_model {
-            searcher = searcher.with_dual_encoder(dual_encoder_model);
-        }
+    let state = {
+        let searcher = ApiSearcher::new(dist_searcher, bangs, config.clone())
+            .with_live(live_searcher)
+            .with_cross_encoder(cross_encoder)
+            .with_lambda_model(lambda_model)
+            .with_dual_encoder(dual_encoder_model)
+            .with_safety_classifier(safety_classifier)
+            .with_result_cache_counters(
+                counters.result_cache_hits.clone(),
+                counters.result_cache_misses.clone(),
+            );
 
         let host_webgraph = Arc::new(host_webgraph);
         let page_webgraph = Arc::new(page_webgraph);
@@ -220,18 +339,79 @@ pub async fn router(config: &ApiConfig, counters: Counters) -> Result<Router> {
             config: config.clone(),
             searcher: Arc::new(searcher),
             autosuggest,
+            autosuggest_filter,
             counters,
             host_webgraph,
             page_webgraph,
             improvement_queue: query_store_queue,
             _cluster: cluster,
             similar_hosts,
+            region_defaults,
+            summarizer,
+            host_about,
         })
     };
 
     Ok(build_router(state))
 }
 
+/// The summarizer folder contains the dual encoder that scores the passages, and
+/// the cross encoder that verifies the citations if citation verification is enabled,
+/// i.e. if there is a `verifier_accelerator` to run it on.
+fn open_summarizer(
+    path: &Path,
+    accelerator: &Accelerator,
+    verifier_accelerator: Option<&Accelerator>,
+) -> Result<ExtractiveSummarizer> {
+    let summarizer = ExtractiveSummarizer::open_with_accelerator(
+        path.join("dual_encoder"),
+        TOP_SUMMARY_PASSAGES,
+        accelerator,
+    )?;
+
+    let Some(verifier_accelerator) = verifier_accelerator else {
+        return Ok(summarizer);
+    };
+
+    let verifier = path.join("cross_encoder");
+    if !verifier.exists() {
+        anyhow::bail!(
+            "citation verification requires a cross encoder in {}",
+            verifier.display()
+        );
+    }
+
+    Ok(
+        summarizer.with_verifier(CrossEncoderModel::open_with_accelerator(
+            verifier,
+            verifier_accelerator,
+        )?),
+    )
+}
+
+fn open_model<T>(
+    path: &Option<String>,
+    load: impl FnOnce(&Path) -> Result<T>,
+) -> Result<ModelHandle<T>> {
+    match path {
+        Some(path) => Ok(ModelHandle::new(load(Path::new(path))?)),
+        None => Ok(ModelHandle::default()),
+    }
+}
+
+async fn reload_models_loop(registry: Arc<ModelRegistry>, interval: Duration) {
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let registry = Arc::clone(&registry);
+        match tokio::task::spawn_blocking(move || registry.reload()).await {
+            Ok(Err(err)) => tracing::warn!("failed to reload models: {}", err),
+            Err(err) => tracing::error!("model reload panicked: {}", err),
+            Ok(Ok(_)) => {}
+        }
+    }
+}
+
 /// Enables CORS for development where the API and frontend are on
 /// different hosts.
 fn cors_layer() -> tower_http::cors::CorsLayer {
@@ -247,6 +427,11 @@ pub fn metrics_router(registry: crate::metrics::PrometheusRegistry) -> Router {
         .with_state(Arc::new(registry))
 }
 
+/// Marks responses to requests with several queries, where the handler has already
+/// counted each query in the search metrics.
+#[derive(Clone, Copy)]
+struct QueriesCounted;
+
 async fn search_metric(
     extract::State(state): extract::State<Arc<State>>,
     extract::ConnectInfo(addr): extract::ConnectInfo<SocketAddr>,
@@ -271,6 +456,10 @@ async fn search_metric(
 
     let response = next.run(request).await;
 
+    if response.extensions().get::<QueriesCounted>().is_some() {
+        return response;
+    }
+
     if response.status().is_success() {
         state.counters.search_counter_success.inc();
     } else if response.status().is_server_error() {
@@ -0,0 +1,115 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The stored ranking signals of a single page, to inspect why
+//! the page ranks the way it does.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract, response::IntoResponse, Json};
+use chrono::NaiveDateTime;
+use http::StatusCode;
+use url::Url;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    ranking::SignalEnumDiscriminants,
+    webgraph::{EdgeLimit, Node},
+    webpage::region::Region,
+};
+
+use super::{
+    site::{top_anchor_texts, AnchorText},
+    State,
+};
+
+const NUM_ANCHOR_TEXTS_SAMPLE: usize = 1024;
+
+#[derive(serde::Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct PageSignalsParams {
+    pub url: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PageSignals {
+    pub url: String,
+    /// The query independent signals stored in the index for the page,
+    /// e.g. centrality, spam and quality scores and freshness.
+    pub signals: HashMap<SignalEnumDiscriminants, f64>,
+    pub region: Region,
+    #[schema(value_type = Option<String>)]
+    pub updated_time: Option<NaiveDateTime>,
+    pub likely_has_ads: bool,
+    pub likely_has_paywall: bool,
+    pub num_ingoing_links: usize,
+    pub top_anchor_texts: Vec<AnchorText>,
+}
+
+#[utoipa::path(get,
+    path = "/beta/api/page/signals",
+    params(PageSignalsParams),
+    responses(
+        (status = 200, description = "The stored ranking signals of the page", body = PageSignals),
+        (status = 404, description = "The page is not in the index"),
+    )
+)]
+pub async fn signals(
+    extract::State(state): extract::State<Arc<State>>,
+    extract::Query(params): extract::Query<PageSignalsParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    state.counters.page_signals_counter.inc();
+
+    let url = Url::parse(&params.url).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let id = Node::from(&url).id();
+
+    let (stored, in_degree, anchors) = tokio::join!(
+        state.searcher.get_stored_signals(url.as_str()),
+        state.page_webgraph.in_degrees(vec![id]),
+        state
+            .page_webgraph
+            .raw_ingoing_edges_with_labels(id, EdgeLimit::Limit(NUM_ANCHOR_TEXTS_SAMPLE)),
+    );
+
+    let stored = match stored {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(err) => {
+            tracing::error!("Failed to get stored signals: {}", err);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let (in_degree, anchors) = match (in_degree, anchors) {
+        (Ok(in_degree), Ok(anchors)) => (in_degree, anchors),
+        _ => {
+            tracing::error!("Failed to send request to webgraph");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok(Json(PageSignals {
+        url: stored.url,
+        signals: stored.signals,
+        region: stored.region,
+        updated_time: stored.updated_time,
+        likely_has_ads: stored.likely_has_ads,
+        likely_has_paywall: stored.likely_has_paywall,
+        num_ingoing_links: in_degree.into_iter().next().unwrap_or_default(),
+        top_anchor_texts: top_anchor_texts(anchors.into_iter().map(|edge| edge.label)),
+    }))
+}
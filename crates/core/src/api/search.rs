@@ -24,16 +24,19 @@ use optics::{HostRankings, Optic};
 use std::{collections::HashMap, sync::Arc};
 use utoipa::ToSchema;
 
-use axum::Json;
+use axum::{Extension, Json};
 use axum_macros::debug_handler;
 
 use crate::{
     bangs::BangHit,
-    searcher::{self, SearchQuery, SearchResult, WebsitesResult},
+    distributed::member::SnapshotDate,
+    localization::{Language, RegionDefaults},
+    search_prettifier::ProductFilter,
+    searcher::{self, lens::Lens, SearchQuery, SearchResult, WebsitesResult},
     webpage::region::Region,
 };
 
-use super::State;
+use super::{QueriesCounted, State};
 
 use axum::{extract, response::IntoResponse};
 
@@ -64,8 +67,21 @@ pub struct ApiSearchQuery {
     pub num_results: Option<usize>,
     pub selected_region: Option<Region>,
     pub optic: Option<String>,
+    pub lens: Option<Lens>,
     pub host_rankings: Option<HostRankings>,
     pub safe_search: Option<bool>,
+    pub include_parked: Option<bool>,
+    pub diversify: Option<bool>,
+    pub exclude_hosts: Option<Vec<String>>,
+    /// Filter the product results of queries with shopping intent, like "buy running shoes".
+    /// The filter is ignored for other queries.
+    pub product_filter: Option<ProductFilter>,
+
+    /// Region code (e.g. `dk`) used when `selectedRegion` is not set. It also
+    /// selects the default optic and safe search setting configured for the region.
+    pub gl: Option<String>,
+    /// Language of the strings in the response (e.g. `da` or `de-DE`). Defaults to english.
+    pub hl: Option<String>,
 
     pub signal_coefficients: Option<HashMap<SignalEnumDiscriminants, f64>>,
 
@@ -81,6 +97,20 @@ pub struct ApiSearchQuery {
     #[serde(default = "defaults::SearchQuery::return_structured_data")]
     pub return_structured_data: bool,
 
+    /// Search for the query exactly as it was typed. The rewrites that would otherwise
+    /// have been applied, like spelling corrections, are listed in the `rewrites` of the result.
+    #[serde(default = "defaults::SearchQuery::disable_rewrites")]
+    pub disable_rewrites: bool,
+
+    /// The `resultSet` token of an earlier response. Only the results of that response
+    /// are searched.
+    pub within_results: Option<String>,
+
+    /// Search the frozen index snapshot taken on this date (`YYYY-MM-DD`) instead of the
+    /// current index, so the results can be reproduced while the snapshot is kept.
+    #[schema(value_type = Option<String>)]
+    pub snapshot: Option<SnapshotDate>,
+
     #[cfg(feature = "return_body")]
     pub return_body: Option<ReturnBody>,
 }
@@ -89,14 +119,62 @@ impl TryFrom<ApiSearchQuery> for SearchQuery {
     type Error = anyhow::Error;
 
     fn try_from(api: ApiSearchQuery) -> Result<Self, Self::Error> {
-        let optic = if let Some(optic) = &api.optic {
-            Some(Optic::parse(optic)?)
+        api.into_search_query(&mut HashMap::new())
+    }
+}
+
+impl ApiSearchQuery {
+    fn region(&self) -> Result<Option<Region>, anyhow::Error> {
+        match (self.selected_region, &self.gl) {
+            (Some(region), _) => Ok(Some(region)),
+            (None, Some(gl)) => Ok(Some(Region::from_gl(&gl.to_lowercase())?)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Use the defaults of the region for the settings the client did not specify.
+    pub(super) fn apply_region_defaults(&mut self, defaults: &RegionDefaults) {
+        let Ok(Some(region)) = self.region() else {
+            return;
+        };
+
+        if self.safe_search.is_none() {
+            self.safe_search = defaults.safe_search(region);
+        }
+
+        if self.optic.is_none() {
+            self.optic = defaults.optic(region).map(|optic| optic.to_string());
+        }
+    }
+
+    /// Convert into a `SearchQuery` where optics that have already been
+    /// parsed are taken from `parsed_optics`.
+    fn into_search_query(
+        self,
+        parsed_optics: &mut HashMap<String, Optic>,
+    ) -> Result<SearchQuery, anyhow::Error> {
+        let selected_region = self.region()?;
+
+        let optic = if let Some(optic) = &self.optic {
+            match parsed_optics.get(optic) {
+                Some(parsed) => Some(parsed.clone()),
+                None => {
+                    let parsed = Optic::parse(optic)?;
+                    parsed_optics.insert(optic.clone(), parsed.clone());
+                    Some(parsed)
+                }
+            }
         } else {
             None
         };
 
+        let optic = match self.lens {
+            Some(lens) => Some(lens.apply(optic)),
+            None => optic,
+        };
+
         let signal_coefficients: Option<SignalCoefficient> =
-            api.signal_coefficients.map(|coefficients| {
+            self.signal_coefficients.map(|coefficients| {
                 coefficients
                     .into_iter()
                     .map(|(signal, coefficient)| (signal.into(), coefficient))
@@ -105,23 +183,36 @@ impl TryFrom<ApiSearchQuery> for SearchQuery {
             });
 
         let default = SearchQuery::default();
+        let language = self
+            .hl
+            .as_deref()
+            .and_then(Language::from_hl)
+            .unwrap_or(default.language);
 
         Ok(SearchQuery {
-            query: api.query,
-            page: api.page.unwrap_or(default.page),
-            num_results: api.num_results.unwrap_or(default.num_results),
-            selected_region: api.selected_region,
+            query: self.query,
+            page: self.page.unwrap_or(default.page),
+            num_results: self.num_results.unwrap_or(default.num_results),
+            selected_region,
             optic,
-            host_rankings: api.host_rankings,
-            return_ranking_signals: api.return_ranking_signals,
-            safe_search: api.safe_search.unwrap_or(default.safe_search),
-            count_results_exact: api.count_results_exact,
+            host_rankings: self.host_rankings,
+            return_ranking_signals: self.return_ranking_signals,
+            safe_search: self.safe_search.unwrap_or(default.safe_search),
+            include_parked: self.include_parked.unwrap_or(default.include_parked),
+            diversify: self.diversify.unwrap_or(default.diversify),
+            exclude_hosts: self.exclude_hosts.unwrap_or(default.exclude_hosts),
+            count_results_exact: self.count_results_exact,
             signal_coefficients: signal_coefficients.unwrap_or(default.signal_coefficients),
             #[cfg(feature = "return_body")]
-            return_body: api.return_body,
+            return_body: self.return_body,
             #[cfg(not(feature = "return_body"))]
             return_body: None,
-            return_structured_data: api.return_structured_data,
+            return_structured_data: self.return_structured_data,
+            product_filter: self.product_filter,
+            language,
+            disable_rewrites: self.disable_rewrites,
+            within_urls: None,
+            snapshot: self.snapshot,
         })
     }
 }
@@ -151,14 +242,18 @@ impl From<SearchResult> for ApiSearchResult {
     request_body(content = ApiSearchQuery),
     responses(
         (status = 200, description = "Search results", body = ApiSearchResult),
+        (status = 404, description = "No searchers serve the index snapshot of `snapshot`"),
+        (status = 410, description = "The result set of `withinResults` is unknown or has expired"),
     )
 )]
 pub async fn search(
     extract::State(state): extract::State<Arc<State>>,
-    extract::Json(query): extract::Json<ApiSearchQuery>,
+    extract::Json(mut query): extract::Json<ApiSearchQuery>,
 ) -> Result<impl IntoResponse, StatusCode> {
     tracing::debug!(?query);
+    query.apply_region_defaults(&state.region_defaults);
     let flatten_result = query.flatten_response;
+    let within_results = query.within_results.take();
     let query = SearchQuery::try_from(query);
 
     if let Err(err) = query {
@@ -167,6 +262,13 @@ pub async fn search(
     }
     let mut query = query.unwrap();
 
+    if let Some(token) = within_results {
+        if let Err(err) = restrict_to_result_set(&state, &mut query, &token) {
+            tracing::debug!("{:?}", err);
+            return Err(StatusCode::GONE);
+        }
+    }
+
     query.num_results = query.num_results.min(100);
 
     match state.searcher.search(&query).await {
@@ -184,6 +286,8 @@ pub async fn search(
                     .to_string()
                     .into_response())
             }
+            Some(searcher::distributed::Error::UnknownSnapshot) => Err(StatusCode::NOT_FOUND),
+            Some(searcher::distributed::Error::PageOutOfRange) => Err(StatusCode::BAD_REQUEST),
             _ => {
                 tracing::error!("{:?}", err);
                 Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -192,11 +296,143 @@ pub async fn search(
     }
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+#[schema(example = json!({"queries": [{"query": "hello world"}, {"query": "rust"}]}))]
+pub struct ApiBatchSearchQuery {
+    pub queries: Vec<ApiSearchQuery>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(tag = "_type", rename_all = "camelCase")]
+pub enum ApiBatchSearchResult {
+    Websites(WebsitesResult),
+    Bang(Box<BangHit>),
+    Error { message: String },
+}
+
+impl From<SearchResult> for ApiBatchSearchResult {
+    fn from(result: SearchResult) -> Self {
+        match result {
+            SearchResult::Websites(result) => ApiBatchSearchResult::Websites(result),
+            SearchResult::Bang(result) => ApiBatchSearchResult::Bang(result),
+        }
+    }
+}
+
+/// Restrict the query to the urls of the result set with the token. Fails if the
+/// result set is unknown or has expired.
+fn restrict_to_result_set(
+    state: &State,
+    query: &mut SearchQuery,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let urls = state
+        .searcher
+        .result_set(token)
+        .ok_or_else(|| anyhow::anyhow!("the result set {token} is unknown or has expired"))?;
+
+    query.within_urls = Some(urls.to_vec());
+
+    Ok(())
+}
+
+#[debug_handler]
+#[utoipa::path(
+    post,
+    path = "/beta/api/search/batch",
+    request_body(content = ApiBatchSearchQuery),
+    responses(
+        (status = 200, description = "Search results for each query in the same order as the queries", body = Vec<ApiBatchSearchResult>),
+    )
+)]
+pub async fn batch_search(
+    extract::State(state): extract::State<Arc<State>>,
+    extract::Json(batch): extract::Json<ApiBatchSearchQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if batch.queries.len() > state.config.max_batch_queries {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // queries in a batch often use the same optic, so we only parse each optic once
+    let mut parsed_optics = HashMap::new();
+    let queries: Vec<_> = batch
+        .queries
+        .into_iter()
+        .map(|mut query| {
+            query.apply_region_defaults(&state.region_defaults);
+            let within_results = query.within_results.take();
+
+            query
+                .into_search_query(&mut parsed_optics)
+                .and_then(|mut query| {
+                    if let Some(token) = within_results {
+                        restrict_to_result_set(&state, &mut query, &token)?;
+                    }
+
+                    query.num_results = query.num_results.min(100);
+                    Ok(query)
+                })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(queries.len());
+    let mut valid = Vec::new();
+
+    for query in queries {
+        match query {
+            Ok(query) => {
+                valid.push(query);
+                results.push(None);
+            }
+            Err(err) => {
+                results.push(Some(ApiBatchSearchResult::Error {
+                    message: err.to_string(),
+                }));
+            }
+        }
+    }
+
+    // the valid queries are sent to each searcher in a single request
+    let mut searched = state
+        .searcher
+        .search_batch(&valid)
+        .await
+        .into_iter()
+        .map(|result| match result {
+            Ok(result) => {
+                state.counters.search_counter_success.inc();
+                ApiBatchSearchResult::from(result)
+            }
+            Err(err) => {
+                // errors caused by the query itself are not counted, as for single searches
+                if err.downcast_ref::<searcher::distributed::Error>().is_none() {
+                    state.counters.search_counter_fail.inc();
+                    tracing::error!("{:?}", err);
+                }
+
+                ApiBatchSearchResult::Error {
+                    message: err.to_string(),
+                }
+            }
+        });
+
+    let results: Vec<_> = results
+        .into_iter()
+        .map(|result| result.unwrap_or_else(|| searched.next().unwrap()))
+        .collect();
+
+    // each query of the batch has been counted above
+    Ok((Extension(QueriesCounted), Json(results)))
+}
+
 #[derive(
     Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, ToSchema,
 )]
 pub struct WidgetQuery {
     pub query: String,
+    /// Language used to format numbers in the widget. Defaults to english.
+    pub hl: Option<String>,
 }
 
 #[debug_handler]
@@ -212,7 +448,13 @@ pub async fn widget(
     extract::State(state): extract::State<Arc<State>>,
     extract::Json(req): extract::Json<WidgetQuery>,
 ) -> impl IntoResponse {
-    Json(state.searcher.widget(&req.query).await)
+    let language = req
+        .hl
+        .as_deref()
+        .and_then(Language::from_hl)
+        .unwrap_or_default();
+
+    Json(state.searcher.widget(&req.query, language).await)
 }
 
 #[derive(
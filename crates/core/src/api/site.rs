@@ -0,0 +1,370 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Aggregated information about a single host, combining data from
+//! the host webgraph and the search index.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{extract, response::IntoResponse, Json};
+use http::StatusCode;
+use url::Url;
+use utoipa::ToSchema;
+
+use crate::{
+    collector::approx_count::Count,
+    host_about::HostAbout,
+    ranking::{SignalCoefficient, SignalEnumDiscriminants, SignalScore},
+    searcher::{SearchQuery, SearchResult},
+    webgraph::{self, AnchorCount, Node},
+    webpage::region::Region,
+};
+
+use super::State;
+
+const TOP_ANCHOR_TEXTS: usize = 10;
+const TOP_LINKING_HOSTS: usize = 10;
+const TOP_PAGES: usize = 10;
+
+#[derive(serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AnchorText {
+    pub text: String,
+    pub count: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TopPage {
+    pub url: String,
+    /// Number of other hosts with a link to the page.
+    pub num_ingoing_links: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkingHost {
+    pub host: String,
+    pub num_links: usize,
+}
+
+/// The spam signals of the host. A flag is `None` if the signal is not available for the host.
+#[derive(
+    Default, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct SpamFlags {
+    /// The host serves different content to the crawler than to users.
+    pub cloaking: Option<bool>,
+    pub affiliate_links: Option<bool>,
+    /// The host sends its outgoing links through redirect scripts or url shorteners.
+    pub link_rotator: Option<bool>,
+    /// The outgoing links of the host have the footprint of a private blog network.
+    pub pbn: Option<bool>,
+    pub parked: Option<bool>,
+}
+
+impl SpamFlags {
+    /// The spam signals score 0.0 when the host is flagged and 1.0 otherwise.
+    fn from_signals(signals: Option<&HashMap<SignalEnumDiscriminants, SignalScore>>) -> Self {
+        let flag = |signal| {
+            signals
+                .and_then(|signals| signals.get(&signal))
+                .map(|score| score.value == 0.0)
+        };
+
+        Self {
+            cloaking: flag(SignalEnumDiscriminants::HostCloaking),
+            affiliate_links: flag(SignalEnumDiscriminants::HostAffiliateLinks),
+            link_rotator: flag(SignalEnumDiscriminants::HostLinkRotator),
+            pbn: flag(SignalEnumDiscriminants::HostPbn),
+            parked: flag(SignalEnumDiscriminants::ParkedDomain),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SiteInfo {
+    pub host: String,
+    pub host_centrality: Option<f64>,
+    /// Position of the host when all hosts are sorted by centrality (0 is the most central).
+    pub host_centrality_rank: Option<u64>,
+    /// Fraction of the hosts that are less central than the host.
+    pub host_centrality_percentile: Option<f64>,
+    pub num_ingoing_hosts: usize,
+    pub num_outgoing_hosts: usize,
+    pub top_anchor_texts: Vec<AnchorText>,
+    /// The hosts with the most links to the host.
+    pub top_linking_hosts: Vec<LinkingHost>,
+    /// The pages from the host that are linked from the most other hosts.
+    pub top_pages: Vec<TopPage>,
+    pub region: Option<Region>,
+    /// ISO 639-3 code of the language detected on the homepage when it was indexed.
+    pub language: Option<String>,
+    pub likely_has_ads: bool,
+    pub likely_has_paywall: bool,
+    /// Ratio of the crawled outlinks from the host that are broken.
+    pub broken_outlink_ratio: Option<f64>,
+    /// Number of pages from the host in the search index.
+    pub num_indexed_pages: Count,
+    /// Descriptors of the site from the analysis of its homepage.
+    pub about: Option<HostAbout>,
+    pub spam_flags: SpamFlags,
+}
+
+impl From<AnchorCount> for AnchorText {
+    fn from(anchor: AnchorCount) -> Self {
+        Self {
+            text: anchor.text,
+            count: anchor.count,
+        }
+    }
+}
+
+pub(super) fn top_anchor_texts(labels: impl Iterator<Item = String>) -> Vec<AnchorText> {
+    webgraph::top_anchor_texts(labels, TOP_ANCHOR_TEXTS)
+        .into_iter()
+        .map(AnchorText::from)
+        .collect()
+}
+
+/// The number of hosts is summed over the shards of the host graph and can count a
+/// host more than once, so the percentile is clamped to stay within `[0, 1]`.
+fn centrality_percentile(rank: u64, num_hosts: u64) -> f64 {
+    if num_hosts == 0 {
+        return 0.0;
+    }
+
+    (1.0 - (rank + 1) as f64 / num_hosts as f64).clamp(0.0, 1.0)
+}
+
+#[utoipa::path(get,
+    path = "/beta/api/site/{host}",
+    params(
+        ("host" = String, Path, description = "The host to get information about"),
+    ),
+    responses(
+        (status = 200, description = "Aggregated information about the host", body = SiteInfo),
+    )
+)]
+pub async fn site_info(
+    extract::State(state): extract::State<Arc<State>>,
+    extract::Path(host): extract::Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    state.counters.site_info_counter.inc();
+
+    let url = Url::parse(&("http://".to_string() + host.as_str()))
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let node = Node::from(url).into_host();
+    let id = node.id();
+
+    let (summary, top_pages, num_hosts) = tokio::join!(
+        state.host_webgraph.host_summary(id),
+        state.page_webgraph.top_pages_for_host(id, TOP_PAGES),
+        state.host_webgraph.num_nodes(),
+    );
+
+    let (mut summary, top_pages, num_hosts) = match (summary, top_pages, num_hosts) {
+        (Ok(summary), Ok(top_pages), Ok(num_hosts)) => (summary, top_pages, num_hosts),
+        _ => {
+            tracing::error!("Failed to send request to webgraph");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    summary.top_linking_hosts.truncate(TOP_LINKING_HOSTS);
+    let linking_host_nodes = match state
+        .host_webgraph
+        .batch_get_node(
+            &summary
+                .top_linking_hosts
+                .iter()
+                .map(|host| host.node)
+                .collect::<Vec<_>>(),
+        )
+        .await
+    {
+        Ok(nodes) => nodes,
+        Err(err) => {
+            tracing::error!("Failed to get linking hosts from webgraph: {}", err);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let top_linking_hosts: Vec<_> = summary
+        .top_linking_hosts
+        .iter()
+        .zip(linking_host_nodes)
+        .filter_map(|(host, node)| {
+            node.map(|node| LinkingHost {
+                host: node.as_str().to_string(),
+                num_links: host.degree,
+            })
+        })
+        .collect();
+
+    let top_page_nodes = match state
+        .page_webgraph
+        .batch_get_node(&top_pages.iter().map(|page| page.node).collect::<Vec<_>>())
+        .await
+    {
+        Ok(nodes) => nodes,
+        Err(err) => {
+            tracing::error!("Failed to get top pages from webgraph: {}", err);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let top_pages: Vec<_> = top_pages
+        .into_iter()
+        .zip(top_page_nodes)
+        .filter_map(|(page, node)| {
+            node.map(|node| TopPage {
+                url: node.as_str().to_string(),
+                num_ingoing_links: page.degree,
+            })
+        })
+        .collect();
+
+    // the centrality rank is not used for ranking by default, so it will only
+    // be computed and returned if it has a non-zero coefficient.
+    let mut signal_coefficients = SignalCoefficient::default();
+    signal_coefficients.merge_add(SignalCoefficient::new(
+        [(SignalEnumDiscriminants::HostCentralityRank.into(), 1.0)].into_iter(),
+    ));
+
+    let query = SearchQuery {
+        query: format!("site:{}", node.as_str()),
+        num_results: 1,
+        count_results_exact: true,
+        return_ranking_signals: true,
+        signal_coefficients,
+        ..Default::default()
+    };
+
+    let websites = match state.searcher.search(&query).await {
+        Ok(SearchResult::Websites(websites)) => websites,
+        Ok(SearchResult::Bang(_)) => return Err(StatusCode::BAD_REQUEST),
+        Err(err) => {
+            tracing::error!("Failed to search for site: {}", err);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let signals = websites
+        .webpages
+        .first()
+        .and_then(|webpage| webpage.ranking_signals.as_ref());
+
+    let host_centrality = signals
+        .and_then(|signals| signals.get(&SignalEnumDiscriminants::HostCentrality))
+        .map(|score| score.value);
+
+    // the rank signal is stored as 1 / (rank + 1)
+    let host_centrality_rank = signals
+        .and_then(|signals| signals.get(&SignalEnumDiscriminants::HostCentralityRank))
+        .filter(|score| score.value > 0.0)
+        .map(|score| (1.0 / score.value - 1.0).round().max(0.0) as u64);
+
+    let host_centrality_percentile = host_centrality_rank
+        .zip(num_hosts)
+        .map(|(rank, num_hosts)| centrality_percentile(rank, num_hosts));
+
+    // the signal is scored as 1 - ratio
+    let broken_outlink_ratio = signals
+        .and_then(|signals| signals.get(&SignalEnumDiscriminants::HostBrokenOutlinkRatio))
+        .map(|score| (1.0 - score.value).max(0.0));
+
+    let spam_flags = SpamFlags::from_signals(signals);
+
+    let about = match state.host_about.as_ref().map(|store| store.get(&id)) {
+        Some(Ok(about)) => about,
+        Some(Err(err)) => {
+            tracing::error!("Failed to get host descriptors: {}", err);
+            None
+        }
+        None => None,
+    };
+
+    let homepage = state
+        .searcher
+        .get_webpage(&format!("https://{}/", node.as_str()))
+        .await
+        .ok()
+        .flatten();
+
+    Ok(Json(SiteInfo {
+        host: node.as_str().to_string(),
+        host_centrality,
+        host_centrality_rank,
+        host_centrality_percentile,
+        num_ingoing_hosts: summary.num_ingoing,
+        num_outgoing_hosts: summary.num_outgoing,
+        top_anchor_texts: summary
+            .top_anchors
+            .into_iter()
+            .take(TOP_ANCHOR_TEXTS)
+            .map(AnchorText::from)
+            .collect(),
+        top_linking_hosts,
+        top_pages,
+        region: homepage.as_ref().map(|page| page.region),
+        language: homepage.as_ref().and_then(|page| page.language.clone()),
+        likely_has_ads: homepage
+            .as_ref()
+            .map(|page| page.likely_has_ads)
+            .unwrap_or_default(),
+        likely_has_paywall: homepage
+            .as_ref()
+            .map(|page| page.likely_has_paywall)
+            .unwrap_or_default(),
+        broken_outlink_ratio,
+        num_indexed_pages: websites.num_hits,
+        about,
+        spam_flags,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchor_texts_are_counted() {
+        let anchors = top_anchor_texts(
+            vec!["Example", "example ", "", "Other", "another", "another"]
+                .into_iter()
+                .map(String::from),
+        );
+
+        assert_eq!(anchors.len(), 3);
+        assert_eq!(anchors[0].text, "another");
+        assert_eq!(anchors[0].count, 2);
+        assert_eq!(anchors[1].text, "example");
+        assert_eq!(anchors[1].count, 2);
+        assert_eq!(anchors[2].text, "other");
+        assert_eq!(anchors[2].count, 1);
+    }
+
+    #[test]
+    fn percentile() {
+        assert_eq!(centrality_percentile(0, 4), 0.75);
+        assert_eq!(centrality_percentile(3, 4), 0.0);
+        assert_eq!(centrality_percentile(10, 4), 0.0);
+        assert_eq!(centrality_percentile(0, 0), 0.0);
+    }
+}
@@ -0,0 +1,124 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Streams an extractive answer to a query. The answer is built from the most
+//! relevant passages of the given pages and is sent as server-sent events, so
+//! clients can render the answer token by token while it is being computed.
+//! If citation verification is enabled, sentences that are not supported by
+//! the page they cite are dropped or flagged before they are sent.
+
+use std::sync::Arc;
+
+use axum::{
+    extract,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{Stream, StreamExt};
+use http::StatusCode;
+use utoipa::ToSchema;
+
+use crate::summarizer::{segment_chunks, CheckedAnswer, SummaryChunk};
+
+use super::State;
+
+/// Pages after the first `MAX_SOURCES` urls are not summarized.
+const MAX_SOURCES: usize = 10;
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SummarizeQuery {
+    pub query: String,
+    /// Urls of the pages to summarize, e.g. the top results of a search.
+    /// Each segment of the answer cites the page it was extracted from.
+    pub urls: Vec<String>,
+}
+
+#[utoipa::path(post,
+    path = "/beta/api/summarize",
+    request_body(content = SummarizeQuery),
+    responses(
+        (status = 200, description = "Server-sent events where each event is a json encoded chunk of the answer", body = SummaryChunk),
+        (status = 501, description = "The api is not configured with a summarizer"),
+    )
+)]
+pub async fn summarize(
+    extract::State(state): extract::State<Arc<State>>,
+    extract::Json(params): extract::Json<SummarizeQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+    let summarizer = state.summarizer.get().ok_or(StatusCode::NOT_IMPLEMENTED)?;
+
+    let chunks = async_stream::stream! {
+        let mut segment = 0;
+
+        for url in params.urls.into_iter().take(MAX_SOURCES) {
+            let webpage = match state.searcher.get_webpage(&url).await {
+                Ok(Some(webpage)) => webpage,
+                Ok(None) => continue,
+                Err(err) => {
+                    tracing::warn!("failed to retrieve {} for summary: {}", url, err);
+                    continue;
+                }
+            };
+
+            let summarizer = Arc::clone(&summarizer);
+            let query = params.query.clone();
+            let verification = state.config.citation_verification.clone();
+            let answer = tokio::task::spawn_blocking(move || {
+                let summary = summarizer.cited_summary(&query, &webpage.body)?;
+
+                match verification {
+                    Some(config) => match summarizer.verify(&summary) {
+                        Ok(sentences) => Some(CheckedAnswer::new(&sentences, &config)),
+                        Err(err) => {
+                            tracing::warn!("failed to verify summary: {}", err);
+                            None
+                        }
+                    },
+                    None => Some(CheckedAnswer {
+                        text: summary.text,
+                        flagged: Vec::new(),
+                    }),
+                }
+            })
+            .await;
+
+            let answer = match answer {
+                Ok(Some(answer)) if !answer.text.is_empty() => answer,
+                Ok(_) => continue,
+                Err(err) => {
+                    tracing::error!("summarizer panicked: {}", err);
+                    continue;
+                }
+            };
+
+            for chunk in segment_chunks(segment, vec![url], &answer.text) {
+                yield chunk;
+            }
+
+            for claim in answer.flagged {
+                yield SummaryChunk::Unsupported { segment, claim };
+            }
+
+            segment += 1;
+        }
+
+        yield SummaryChunk::Done;
+    };
+
+    let events = chunks.map(|chunk| Event::default().json_data(chunk));
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use axum::{extract, response::IntoResponse, Json};
 use http::StatusCode;
@@ -22,7 +22,9 @@ use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     config::WebgraphGranularity,
-    webgraph::{EdgeLimit, FullEdge, Node},
+    webgraph::{
+        EdgeCursor, EdgeFilter, EdgeLimit, EdgeQueryResult, FullEdge, LinkSetOperation, Node,
+    },
 };
 
 use super::State;
@@ -51,6 +53,35 @@ pub mod host {
     #[serde(rename_all = "camelCase")]
     pub struct HostLinksParams {
         pub host: String,
+        /// Continue after the edge with this cursor.
+        #[param(value_type = Option<String>)]
+        pub cursor: Option<EdgeCursor>,
+    }
+
+    #[derive(serde::Deserialize, ToSchema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BacklinkSetParams {
+        pub hosts: Vec<String>,
+        pub operation: LinkSetOperation,
+        /// Number of hosts to return. At most 1024 hosts are returned.
+        pub top_n: usize,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, ToSchema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BacklinkSetHost {
+        pub host: String,
+        /// Number of the requested hosts this host links to.
+        pub count: usize,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, ToSchema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BacklinkSet {
+        pub hosts: Vec<BacklinkSetHost>,
+        /// Whether some of the requested hosts have more backlinks than are read for
+        /// each host. The result is then computed from a sample of their backlinks.
+        pub truncated: bool,
     }
 
     #[utoipa::path(post,
@@ -83,6 +114,116 @@ pub mod host {
         ))
     }
 
+    #[derive(serde::Deserialize, IntoParams)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SimilarSitesParams {
+        pub host: String,
+        /// Page of the results, starting at 0.
+        #[serde(default)]
+        pub page: usize,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, ToSchema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SimilarSitesResponse {
+        pub sites: Vec<SimilarSite>,
+        /// The page after this one, if there are more results.
+        pub next_page: Option<usize>,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, ToSchema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SimilarSite {
+        pub host: String,
+        pub score: f64,
+        pub explanation: SimilarityExplanation,
+    }
+
+    /// The score of a site is the number of backlinks it shares with the queried host,
+    /// divided by the geometric mean of their numbers of backlinks.
+    #[derive(serde::Serialize, serde::Deserialize, ToSchema)]
+    #[serde(rename_all = "camelCase")]
+    pub struct SimilarityExplanation {
+        /// Number of the sampled backlinks of the queried host that also link to the site.
+        pub shared_backlinks: usize,
+        pub host_backlinks: usize,
+        pub site_backlinks: usize,
+    }
+
+    #[utoipa::path(get,
+        path = "/api/v1/similar_sites",
+        params(SimilarSitesParams),
+        responses(
+            (status = 200, description = "Sites that are linked to from the same sites as the host", body = SimilarSitesResponse),
+        )
+    )]
+    pub async fn similar_sites(
+        extract::State(state): extract::State<Arc<State>>,
+        extract::Query(params): extract::Query<SimilarSitesParams>,
+    ) -> std::result::Result<impl IntoResponse, StatusCode> {
+        state.counters.similar_sites_counter.inc();
+
+        if params.page >= MAX_SIMILAR_SITES_PAGES {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let url = Url::parse(&("http://".to_string() + params.host.as_str()))
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+        let node = Node::from(url).into_host();
+
+        let offset = params.page * SIMILAR_SITES_PER_PAGE;
+        // one more than the page, to know whether there is a next page
+        let top_k = offset + SIMILAR_SITES_PER_PAGE + 1;
+
+        let similar = state
+            .host_webgraph
+            .similar_nodes(node.id(), top_k)
+            .await
+            .map_err(|_| {
+                tracing::error!("Failed to send request to webgraph");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        let has_next = similar.len() > offset + SIMILAR_SITES_PER_PAGE;
+        let similar: Vec<_> = similar
+            .into_iter()
+            .skip(offset)
+            .take(SIMILAR_SITES_PER_PAGE)
+            .collect();
+
+        let ids: Vec<_> = similar.iter().map(|s| s.node).collect();
+        let nodes = state
+            .host_webgraph
+            .batch_get_node(&ids)
+            .await
+            .map_err(|_| {
+                tracing::error!("Failed to send request to webgraph");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        let sites = similar
+            .into_iter()
+            .zip(nodes)
+            .filter_map(|(similar, node)| {
+                Some(SimilarSite {
+                    host: node?.as_str().to_string(),
+                    score: similar.score,
+                    explanation: SimilarityExplanation {
+                        shared_backlinks: similar.shared_backlinks,
+                        host_backlinks: similar.node_backlinks,
+                        site_backlinks: similar.candidate_backlinks,
+                    },
+                })
+            })
+            .collect();
+
+        Ok(Json(SimilarSitesResponse {
+            sites,
+            next_page: (has_next && params.page + 1 < MAX_SIMILAR_SITES_PAGES)
+                .then_some(params.page + 1),
+        }))
+    }
+
     #[utoipa::path(post,
         path = "/beta/api/webgraph/host/knows",
         params(KnowsHostParams),
@@ -106,6 +247,70 @@ pub mod host {
         }
     }
 
+    #[utoipa::path(post,
+        path = "/beta/api/webgraph/host/backlinks",
+        request_body(content = BacklinkSetParams),
+        responses(
+            (status = 200, description = "Set operation over the backlinks of the hosts", body = BacklinkSet),
+        )
+    )]
+    pub async fn backlink_set(
+        extract::State(state): extract::State<Arc<State>>,
+        extract::Json(params): extract::Json<BacklinkSetParams>,
+    ) -> std::result::Result<impl IntoResponse, StatusCode> {
+        state.counters.explore_counter.inc();
+
+        let nodes = params
+            .hosts
+            .iter()
+            .take(MAX_BACKLINK_SET_HOSTS)
+            .map(|host| {
+                Url::parse(&("http://".to_string() + host.as_str()))
+                    .map(|url| Node::from(url).into_host().id())
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+        let set = state
+            .host_webgraph
+            .backlink_set(&nodes, params.operation)
+            .await
+            .map_err(|_| {
+                tracing::error!("Failed to send request to webgraph");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        let entries: Vec<_> = set
+            .entries
+            .into_iter()
+            .take(params.top_n.min(MAX_BACKLINK_SET_RESULTS))
+            .collect();
+        let ids: Vec<_> = entries.iter().map(|entry| entry.node).collect();
+
+        let hosts = state
+            .host_webgraph
+            .batch_get_node(&ids)
+            .await
+            .map_err(|_| {
+                tracing::error!("Failed to send request to webgraph");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        Ok(Json(BacklinkSet {
+            hosts: entries
+                .into_iter()
+                .zip(hosts)
+                .filter_map(|(entry, host)| {
+                    host.map(|host| BacklinkSetHost {
+                        host: host.as_str().to_string(),
+                        count: entry.count,
+                    })
+                })
+                .collect(),
+            truncated: set.truncated,
+        }))
+    }
+
     #[utoipa::path(post,
         path = "/beta/api/webgraph/host/ingoing",
         params(HostLinksParams),
@@ -120,14 +325,14 @@ pub mod host {
         let url = Url::parse(&("http://".to_string() + params.host.as_str()))
             .map_err(|_| StatusCode::BAD_REQUEST)?;
         let node = Node::from(url).into_host();
-        let links = ingoing_links(state, node, WebgraphGranularity::Host)
+        let links = ingoing_links(state, node, WebgraphGranularity::Host, params.cursor)
             .await
             .map_err(|_| {
                 tracing::error!("Failed to send request to webgraph");
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
-        Ok(Json(links))
+        Ok(edges_response(links))
     }
 
     #[utoipa::path(post,
@@ -144,14 +349,14 @@ pub mod host {
         let url = Url::parse(&("http://".to_string() + params.host.as_str()))
             .map_err(|_| StatusCode::BAD_REQUEST)?;
         let node = Node::from(url).into_host();
-        let links = outgoing_links(state, node, WebgraphGranularity::Host)
+        let links = outgoing_links(state, node, WebgraphGranularity::Host, params.cursor)
             .await
             .map_err(|_| {
                 tracing::error!("Failed to send request to webgraph");
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
-        Ok(Json(links))
+        Ok(edges_response(links))
     }
 }
 
@@ -162,6 +367,9 @@ pub mod page {
     #[serde(rename_all = "camelCase")]
     pub struct PageLinksParams {
         pub page: String,
+        /// Continue after the edge with this cursor.
+        #[param(value_type = Option<String>)]
+        pub cursor: Option<EdgeCursor>,
     }
 
     #[utoipa::path(post,
@@ -176,14 +384,14 @@ pub mod page {
         extract::Query(params): extract::Query<PageLinksParams>,
     ) -> std::result::Result<impl IntoResponse, StatusCode> {
         let node = Node::from(params.page);
-        let links = ingoing_links(state, node, WebgraphGranularity::Page)
+        let links = ingoing_links(state, node, WebgraphGranularity::Page, params.cursor)
             .await
             .map_err(|_| {
                 tracing::error!("Failed to send request to webgraph");
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
-        Ok(Json(links))
+        Ok(edges_response(links))
     }
 
     #[utoipa::path(post,
@@ -198,14 +406,51 @@ pub mod page {
         extract::Query(params): extract::Query<PageLinksParams>,
     ) -> std::result::Result<impl IntoResponse, StatusCode> {
         let node = Node::from(params.page);
-        let links = outgoing_links(state, node, WebgraphGranularity::Page)
+        let links = outgoing_links(state, node, WebgraphGranularity::Page, params.cursor)
             .await
             .map_err(|_| {
                 tracing::error!("Failed to send request to webgraph");
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
-        Ok(Json(links))
+        Ok(edges_response(links))
+    }
+}
+
+const SIMILAR_SITES_PER_PAGE: usize = 20;
+
+/// The candidates of the co-citation similarity are limited, so only the first pages
+/// of similar sites are meaningful.
+const MAX_SIMILAR_SITES_PAGES: usize = 10;
+
+/// Maximum number of links returned per request.
+const MAX_LINKS: usize = 1024;
+
+/// Maximum number of hosts in a backlink set operation.
+const MAX_BACKLINK_SET_HOSTS: usize = 8;
+
+/// Maximum number of hosts returned by a backlink set operation.
+const MAX_BACKLINK_SET_RESULTS: usize = 1024;
+
+/// Edges of hub nodes can take long to read, so the webgraph returns the edges it has
+/// read when this time has passed.
+const EDGE_QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Header that is `true` when the edges were truncated by [`EDGE_QUERY_TIMEOUT`].
+const EDGES_TRUNCATED_HEADER: &str = "x-edges-truncated";
+
+fn edges_response(result: EdgeQueryResult) -> impl IntoResponse {
+    let truncated = if result.truncated { "true" } else { "false" };
+    ([(EDGES_TRUNCATED_HEADER, truncated)], Json(result.edges))
+}
+
+fn edge_limit(cursor: Option<EdgeCursor>) -> EdgeLimit {
+    match cursor {
+        Some(cursor) => EdgeLimit::After {
+            cursor,
+            limit: MAX_LINKS,
+        },
+        None => EdgeLimit::Limit(MAX_LINKS),
     }
 }
 
@@ -213,26 +458,44 @@ async fn ingoing_links(
     state: Arc<State>,
     node: Node,
     level: WebgraphGranularity,
-) -> anyhow::Result<Vec<FullEdge>> {
+    cursor: Option<EdgeCursor>,
+) -> anyhow::Result<EdgeQueryResult> {
     let graph = match level {
         WebgraphGranularity::Host => &state.host_webgraph,
         WebgraphGranularity::Page => &state.page_webgraph,
+        WebgraphGranularity::Domain => anyhow::bail!("the api does not query the domain graph"),
     };
 
-    graph.ingoing_edges(node, EdgeLimit::Limit(1024)).await
+    graph
+        .ingoing_edges(
+            node,
+            edge_limit(cursor),
+            EdgeFilter::all(),
+            Some(EDGE_QUERY_TIMEOUT),
+        )
+        .await
 }
 
 async fn outgoing_links(
     state: Arc<State>,
     node: Node,
     level: WebgraphGranularity,
-) -> anyhow::Result<Vec<FullEdge>> {
+    cursor: Option<EdgeCursor>,
+) -> anyhow::Result<EdgeQueryResult> {
     let graph = match level {
         WebgraphGranularity::Host => &state.host_webgraph,
         WebgraphGranularity::Page => &state.page_webgraph,
+        WebgraphGranularity::Domain => anyhow::bail!("the api does not query the domain graph"),
     };
 
-    graph.outgoing_edges(node, EdgeLimit::Limit(1024)).await
+    graph
+        .outgoing_edges(
+            node,
+            edge_limit(cursor),
+            EdgeFilter::all(),
+            Some(EDGE_QUERY_TIMEOUT),
+        )
+        .await
 }
 
 #[derive(serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, ToSchema)]
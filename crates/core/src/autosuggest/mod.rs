@@ -0,0 +1,629 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2023 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Autosuggest provides the functionality for the little dropdown that appears
+//! when you type something into the search bar and queries are suggested.
+//! It uses a finite state transducer (fst) to store popular queries
+//! and performs a prefix search on the fst to find suggestions.
+//!
+//! The value of each query in the fst holds how often the query has been searched
+//! and how often it has been searched recently. The queries with the prefix are
+//! ranked by a blend of these and how much of the query has already been typed.
+//!
+//! The fst can be built offline from a csv of queries with [`Autosuggest::save`],
+//! so a running api only has to read the file when the suggestions are reloaded.
+//!
+//! A [`SuggestionFilter`] removes the queries that should never be suggested from
+//! the fst, and hides adult suggestions at query time when safe search is on.
+//!
+//! The queries are also found when the prefix is typed without their diacritics or
+//! with the wrong keyboard layout, e.g. `munchen` or `ьгтсрут` for `münchen`.
+
+mod transliterate;
+
+use fst::{automaton::Str, Automaton, IntoStreamer, Streamer};
+use regex::RegexSet;
+use utoipa::ToSchema;
+
+use crate::{
+    config::{AutosuggestFilterConfig, AutosuggestRankingConfig},
+    Result,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+    sync::Arc,
+};
+
+/// Separates the folded query from the query in the keys of the folded fst.
+const FOLDED_SEPARATOR: u8 = 0;
+
+const NUM_SUGGESTIONS: usize = 10;
+
+/// The searches of a query count half as much towards its trend per week
+/// they are older than the newest search in the csv.
+const TREND_HALF_LIFE_SECS: f64 = 7.0 * 24.0 * 60.0 * 60.0;
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Terms read from a file with a term per line. A term can consist of
+/// several words, which must then appear next to each other.
+#[derive(Default)]
+struct Terms(Vec<Vec<String>>);
+
+impl Terms {
+    fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let terms = fs::read_to_string(path)?
+            .lines()
+            .map(words)
+            .filter(|term| !term.is_empty())
+            .collect();
+
+        Ok(Self(terms))
+    }
+
+    fn matches(&self, words: &[String]) -> bool {
+        self.0.iter().any(|term| {
+            words
+                .windows(term.len())
+                .any(|window| window == term.as_slice())
+        })
+    }
+}
+
+/// Removes slurs, operator configured patterns and, with safe search,
+/// adult terms from the suggestions.
+#[derive(Default)]
+pub struct SuggestionFilter {
+    blocked: Terms,
+    adult: Terms,
+    patterns: Option<RegexSet>,
+}
+
+impl SuggestionFilter {
+    pub fn open(config: &AutosuggestFilterConfig) -> Result<Self> {
+        let blocked = match &config.blocklist_path {
+            Some(path) => Terms::open(path)?,
+            None => Terms::default(),
+        };
+
+        let adult = match &config.adult_terms_path {
+            Some(path) => Terms::open(path)?,
+            None => Terms::default(),
+        };
+
+        let patterns = if config.patterns.is_empty() {
+            None
+        } else {
+            Some(RegexSet::new(&config.patterns)?)
+        };
+
+        Ok(Self {
+            blocked,
+            adult,
+            patterns,
+        })
+    }
+
+    /// Whether the suggestion is blocked no matter the safe search setting.
+    fn is_blocked(&self, suggestion: &str) -> bool {
+        self.blocked.matches(&words(suggestion))
+            || self
+                .patterns
+                .as_ref()
+                .is_some_and(|patterns| patterns.is_match(suggestion))
+    }
+
+    pub fn is_allowed(&self, suggestion: &str, safe_search: bool) -> bool {
+        !self.is_blocked(suggestion) && !(safe_search && self.adult.matches(&words(suggestion)))
+    }
+}
+
+/// Popularity of a query, packed into its value in the fst.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct QueryStats {
+    frequency: u32,
+    trend: f32,
+}
+
+impl QueryStats {
+    fn pack(self) -> u64 {
+        ((self.frequency as u64) << 32) | self.trend.to_bits() as u64
+    }
+
+    fn unpack(value: u64) -> Self {
+        Self {
+            frequency: (value >> 32) as u32,
+            trend: f32::from_bits(value as u32),
+        }
+    }
+}
+
+/// How a suggestion was scored.
+#[derive(Debug, Clone, serde::Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestionScore {
+    pub total: f64,
+    pub frequency: f64,
+    pub trend: f64,
+    pub prefix_match: f64,
+}
+
+impl SuggestionScore {
+    fn new(
+        query: &str,
+        suggestion: &str,
+        stats: QueryStats,
+        config: &AutosuggestRankingConfig,
+    ) -> Self {
+        let frequency = (stats.frequency as f64).ln_1p();
+        let trend = (stats.trend as f64).ln_1p();
+        let prefix_match = query.chars().count() as f64 / suggestion.chars().count().max(1) as f64;
+
+        Self {
+            total: config.frequency_weight * frequency
+                + config.trend_weight * trend
+                + config.prefix_match_weight * prefix_match,
+            frequency,
+            trend,
+            prefix_match,
+        }
+    }
+}
+
+pub struct ScoredSuggestion {
+    pub suggestion: String,
+    pub score: SuggestionScore,
+}
+
+pub struct Autosuggest {
+    queries: fst::Map<Vec<u8>>,
+    /// The queries that have diacritics, keyed by the query without its diacritics
+    /// followed by [`FOLDED_SEPARATOR`] and the query.
+    folded: fst::Map<Vec<u8>>,
+    filter: Option<Arc<SuggestionFilter>>,
+    ranking: AutosuggestRankingConfig,
+}
+
+impl Autosuggest {
+    fn new(queries: fst::Map<Vec<u8>>) -> Result<Self> {
+        let mut folded = Vec::new();
+
+        let mut stream = queries.stream();
+        while let Some((query, stats)) = stream.next() {
+            let query = std::str::from_utf8(query)?;
+            let folded_query = transliterate::fold_diacritics(query);
+
+            if folded_query != query {
+                let mut key = folded_query.into_bytes();
+                key.push(FOLDED_SEPARATOR);
+                key.extend_from_slice(query.as_bytes());
+
+                folded.push((key, stats));
+            }
+        }
+
+        folded.sort();
+
+        Ok(Self {
+            queries,
+            folded: fst::Map::from_iter(folded)?,
+            filter: None,
+            ranking: AutosuggestRankingConfig::default(),
+        })
+    }
+
+    /// Open the suggestions at `path`, which is either a csv of queries or an fst
+    /// written by [`Autosuggest::save`].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Self::load_csv(path),
+            _ => Self::new(fst::Map::new(fs::read(path)?)?),
+        }
+    }
+
+    /// Each row of the csv is a query, optionally followed by the number of times
+    /// it was searched and the unix timestamp of the searches. A query can have
+    /// several rows, e.g. one for each day it was searched.
+    pub fn load_csv<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut rows = Vec::new();
+
+        let mut rdr = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+        for result in rdr.records() {
+            let record = result?;
+            let Some(query) = record.get(0) else {
+                continue;
+            };
+
+            let count = record
+                .get(1)
+                .and_then(|count| count.trim().parse::<u64>().ok())
+                .unwrap_or(1);
+            let timestamp = record
+                .get(2)
+                .and_then(|timestamp| timestamp.trim().parse::<u64>().ok());
+
+            rows.push((query.to_string(), count, timestamp));
+        }
+
+        let newest = rows.iter().filter_map(|(_, _, timestamp)| *timestamp).max();
+
+        let mut queries: BTreeMap<String, (u64, f64)> = BTreeMap::new();
+        for (query, count, timestamp) in rows {
+            let decay = match (timestamp, newest) {
+                (Some(timestamp), Some(newest)) => {
+                    0.5_f64.powf(newest.saturating_sub(timestamp) as f64 / TREND_HALF_LIFE_SECS)
+                }
+                _ => 1.0,
+            };
+
+            let (frequency, trend) = queries.entry(query).or_default();
+            *frequency += count;
+            *trend += count as f64 * decay;
+        }
+
+        let queries =
+            fst::Map::from_iter(queries.into_iter().map(|(query, (frequency, trend))| {
+                let stats = QueryStats {
+                    frequency: frequency.min(u32::MAX as u64) as u32,
+                    trend: trend as f32,
+                };
+
+                (query, stats.pack())
+            }))?;
+
+        Self::new(queries)
+    }
+
+    pub fn with_ranking(mut self, ranking: AutosuggestRankingConfig) -> Self {
+        self.ranking = ranking;
+        self
+    }
+
+    /// Remove the blocked queries and filter the suggestions with `filter`. The
+    /// adult queries are kept, as they are only hidden when safe search is on.
+    pub fn with_filter(self, filter: Arc<SuggestionFilter>) -> Result<Self> {
+        let mut allowed = fst::MapBuilder::memory();
+
+        let mut stream = self.queries.stream();
+        while let Some((query, stats)) = stream.next() {
+            if !filter.is_blocked(std::str::from_utf8(query)?) {
+                allowed.insert(query, stats)?;
+            }
+        }
+
+        Ok(Self {
+            filter: Some(filter),
+            ranking: self.ranking,
+            ..Self::new(fst::Map::new(allowed.into_inner()?)?)?
+        })
+    }
+
+    /// Write the fst to `path`. The file is replaced atomically, so an api that
+    /// reloads the suggestions never reads a partially written file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+
+        fs::write(&tmp_path, self.queries.as_fst().as_bytes())?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.queries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    pub fn suggestions(&self, query: &str, safe_search: bool) -> Result<Vec<String>> {
+        Ok(self
+            .scored_suggestions(query, safe_search)?
+            .into_iter()
+            .map(|scored| scored.suggestion)
+            .collect())
+    }
+
+    /// The best suggestions for the query, ranked by their score.
+    pub fn scored_suggestions(
+        &self,
+        query: &str,
+        safe_search: bool,
+    ) -> Result<Vec<ScoredSuggestion>> {
+        let query = query.to_lowercase();
+
+        let mut prefixes = vec![query.clone()];
+        for prefix in [
+            transliterate::to_cyrillic_layout(&query),
+            transliterate::to_latin_layout(&query),
+        ] {
+            if !prefixes.contains(&prefix) {
+                prefixes.push(prefix);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+
+        for prefix in &prefixes {
+            let folded_prefix = transliterate::fold_diacritics(prefix);
+
+            for (map, prefix, is_folded) in [
+                (&self.queries, prefix.as_str(), false),
+                (&self.folded, folded_prefix.as_str(), true),
+            ] {
+                let mut stream = map.search(Str::new(prefix).starts_with()).into_stream();
+
+                while let Some((key, stats)) = stream.next() {
+                    if candidates.len() == self.ranking.max_candidates {
+                        break;
+                    }
+
+                    let suggestion = if is_folded {
+                        let separator = key
+                            .iter()
+                            .position(|b| *b == FOLDED_SEPARATOR)
+                            .unwrap_or_default();
+                        &key[separator + 1..]
+                    } else {
+                        key
+                    };
+                    let suggestion = std::str::from_utf8(suggestion)?;
+
+                    if !seen.insert(suggestion.to_string()) {
+                        continue;
+                    }
+
+                    if self
+                        .filter
+                        .as_ref()
+                        .map_or(true, |filter| filter.is_allowed(suggestion, safe_search))
+                    {
+                        candidates.push(ScoredSuggestion {
+                            suggestion: suggestion.to_string(),
+                            score: SuggestionScore::new(
+                                &query,
+                                suggestion,
+                                QueryStats::unpack(stats),
+                                &self.ranking,
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| {
+            b.score
+                .total
+                .total_cmp(&a.score.total)
+                .then_with(|| a.suggestion.cmp(&b.suggestion))
+        });
+        candidates.truncate(NUM_SUGGESTIONS);
+
+        Ok(candidates)
+    }
+
+    pub fn all(&self) -> Result<Vec<String>> {
+        Ok(self.queries.keys().into_strs()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_fst_has_same_suggestions() {
+        let dir = crate::gen_temp_path();
+        fs::create_dir_all(&dir).unwrap();
+
+        let csv_path = dir.join("queries.csv");
+        fs::write(
+            &csv_path,
+            "query\nbest pizza\nbest pasta\nbest pizza\nworst pizza\n",
+        )
+        .unwrap();
+
+        let autosuggest = Autosuggest::open(&csv_path).unwrap();
+        assert_eq!(autosuggest.len(), 3);
+
+        let fst_path = dir.join("queries.fst");
+        autosuggest.save(&fst_path).unwrap();
+
+        let reopened = Autosuggest::open(&fst_path).unwrap();
+        assert_eq!(reopened.all().unwrap(), autosuggest.all().unwrap());
+        assert_eq!(
+            reopened.suggestions("Best", false).unwrap(),
+            vec!["best pizza".to_string(), "best pasta".to_string()]
+        );
+    }
+
+    #[test]
+    fn ranked_by_popularity_and_prefix_match() {
+        let dir = crate::gen_temp_path();
+        fs::create_dir_all(&dir).unwrap();
+
+        let week = TREND_HALF_LIFE_SECS as u64;
+        let csv_path = dir.join("queries.csv");
+        fs::write(
+            &csv_path,
+            format!(
+                "query,count,timestamp\n\
+                 rust book,10,0\n\
+                 rust belt,4,{week}\n\
+                 rust belt,4,{}\n\
+                 rusty,2,{}\n",
+                4 * week,
+                4 * week
+            ),
+        )
+        .unwrap();
+
+        let autosuggest = Autosuggest::load_csv(&csv_path).unwrap();
+
+        let frequency_only = AutosuggestRankingConfig {
+            frequency_weight: 1.0,
+            trend_weight: 0.0,
+            prefix_match_weight: 0.0,
+            max_candidates: 100,
+        };
+        let autosuggest = autosuggest.with_ranking(frequency_only);
+        assert_eq!(
+            autosuggest.suggestions("rust", false).unwrap(),
+            vec!["rust book", "rust belt", "rusty"]
+        );
+
+        let trend_only = AutosuggestRankingConfig {
+            frequency_weight: 0.0,
+            trend_weight: 1.0,
+            ..autosuggest.ranking.clone()
+        };
+        let autosuggest = autosuggest.with_ranking(trend_only);
+        assert_eq!(
+            autosuggest.suggestions("rust", false).unwrap(),
+            vec!["rust belt", "rusty", "rust book"]
+        );
+
+        let scored = autosuggest.scored_suggestions("rust", false).unwrap();
+        assert_eq!(scored[0].score.frequency, 8.0_f64.ln_1p());
+        assert_eq!(scored[2].score.trend, (10.0 / 16.0_f64).ln_1p());
+
+        let prefix_match_only = AutosuggestRankingConfig {
+            trend_weight: 0.0,
+            prefix_match_weight: 1.0,
+            ..autosuggest.ranking.clone()
+        };
+        let autosuggest = autosuggest.with_ranking(prefix_match_only);
+        assert_eq!(
+            autosuggest.suggestions("rust", false).unwrap(),
+            vec!["rusty", "rust belt", "rust book"]
+        );
+    }
+
+    #[test]
+    fn transliterated_prefixes() {
+        let dir = crate::gen_temp_path();
+        fs::create_dir_all(&dir).unwrap();
+
+        let csv_path = dir.join("queries.csv");
+        fs::write(
+            &csv_path,
+            "query\nmünchen hotel\nmunich\nпривет мир\nstraße\n",
+        )
+        .unwrap();
+
+        let autosuggest = Autosuggest::open(&csv_path).unwrap();
+        assert_eq!(autosuggest.len(), 4);
+
+        assert_eq!(
+            autosuggest.suggestions("munchen", false).unwrap(),
+            vec!["münchen hotel"]
+        );
+        assert_eq!(
+            autosuggest.suggestions("Mün", false).unwrap(),
+            vec!["münchen hotel"]
+        );
+        assert_eq!(
+            autosuggest.suggestions("mun", false).unwrap(),
+            vec!["munich", "münchen hotel"]
+        );
+        assert_eq!(
+            autosuggest.suggestions("strass", false).unwrap(),
+            vec!["straße"]
+        );
+
+        // typed on a QWERTY layout while meaning to type russian, and the other way around
+        assert_eq!(
+            autosuggest.suggestions("ghbd", false).unwrap(),
+            vec!["привет мир"]
+        );
+        assert_eq!(
+            autosuggest.suggestions("ьгт", false).unwrap(),
+            vec!["munich", "münchen hotel"]
+        );
+    }
+
+    #[test]
+    fn filtered_suggestions() {
+        let dir = crate::gen_temp_path();
+        fs::create_dir_all(&dir).unwrap();
+
+        let csv_path = dir.join("queries.csv");
+        fs::write(
+            &csv_path,
+            "query
+best pizza
+best badword
+best adult movies
+best adultery books
+best casino bonus
+",
+        )
+        .unwrap();
+
+        let blocklist_path = dir.join("blocklist.txt");
+        fs::write(
+            &blocklist_path,
+            "badword
+",
+        )
+        .unwrap();
+
+        let adult_path = dir.join("adult.txt");
+        fs::write(
+            &adult_path,
+            "adult movies
+",
+        )
+        .unwrap();
+
+        let filter = SuggestionFilter::open(&AutosuggestFilterConfig {
+            blocklist_path: Some(blocklist_path.to_str().unwrap().to_string()),
+            adult_terms_path: Some(adult_path.to_str().unwrap().to_string()),
+            patterns: vec!["casino".to_string()],
+        })
+        .unwrap();
+
+        let autosuggest = Autosuggest::open(&csv_path)
+            .unwrap()
+            .with_filter(Arc::new(filter))
+            .unwrap();
+        assert_eq!(autosuggest.len(), 3);
+
+        assert_eq!(
+            autosuggest.suggestions("best", false).unwrap(),
+            vec![
+                "best pizza".to_string(),
+                "best adult movies".to_string(),
+                "best adultery books".to_string()
+            ]
+        );
+        assert_eq!(
+            autosuggest.suggestions("best", true).unwrap(),
+            vec!["best pizza".to_string(), "best adultery books".to_string()]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Alternative spellings of what the user typed, so suggestions are still found
+//! when diacritics are left out or the query is typed with the wrong keyboard layout.
+
+/// Keys of the russian ЙЦУКЕН layout and the keys at the same position on a QWERTY keyboard.
+const LAYOUT: [(char, char); 33] = [
+    ('q', 'й'),
+    ('w', 'ц'),
+    ('e', 'у'),
+    ('r', 'к'),
+    ('t', 'е'),
+    ('y', 'н'),
+    ('u', 'г'),
+    ('i', 'ш'),
+    ('o', 'щ'),
+    ('p', 'з'),
+    ('[', 'х'),
+    (']', 'ъ'),
+    ('a', 'ф'),
+    ('s', 'ы'),
+    ('d', 'в'),
+    ('f', 'а'),
+    ('g', 'п'),
+    ('h', 'р'),
+    ('j', 'о'),
+    ('k', 'л'),
+    ('l', 'д'),
+    (';', 'ж'),
+    ('\'', 'э'),
+    ('z', 'я'),
+    ('x', 'ч'),
+    ('c', 'с'),
+    ('v', 'м'),
+    ('b', 'и'),
+    ('n', 'т'),
+    ('m', 'ь'),
+    (',', 'б'),
+    ('.', 'ю'),
+    ('`', 'ё'),
+];
+
+/// The text as if it had been typed with the same keys on a russian keyboard.
+pub fn to_cyrillic_layout(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            LAYOUT
+                .iter()
+                .find(|(latin, _)| *latin == c)
+                .map_or(c, |(_, cyrillic)| *cyrillic)
+        })
+        .collect()
+}
+
+/// The text as if it had been typed with the same keys on a QWERTY keyboard.
+pub fn to_latin_layout(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            LAYOUT
+                .iter()
+                .find(|(_, cyrillic)| *cyrillic == c)
+                .map_or(c, |(latin, _)| *latin)
+        })
+        .collect()
+}
+
+/// The text with the diacritics of latin letters removed, e.g. `münchen` becomes `munchen`.
+pub fn fold_diacritics(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        let replacement = match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+            'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+            'ď' | 'đ' | 'ð' => "d",
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+            'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+            'ĥ' | 'ħ' => "h",
+            'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+            'ĵ' => "j",
+            'ķ' => "k",
+            'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+            'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+            'ŕ' | 'ŗ' | 'ř' => "r",
+            'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+            'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+            'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+            'ŵ' => "w",
+            'ý' | 'ÿ' | 'ŷ' => "y",
+            'ź' | 'ż' | 'ž' => "z",
+            'ß' => "ss",
+            'æ' => "ae",
+            'œ' => "oe",
+            'þ' => "th",
+            _ => {
+                folded.push(c);
+                continue;
+            }
+        };
+
+        folded.push_str(replacement);
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyboard_layouts() {
+        assert_eq!(to_cyrillic_layout("ghbdtn"), "привет");
+        assert_eq!(to_latin_layout("руддщ"), "hello");
+        assert_eq!(to_latin_layout(&to_cyrillic_layout("stract 1")), "stract 1");
+    }
+
+    #[test]
+    fn diacritics() {
+        assert_eq!(fold_diacritics("münchen"), "munchen");
+        assert_eq!(fold_diacritics("crème brûlée"), "creme brulee");
+        assert_eq!(fold_diacritics("straße"), "strasse");
+        assert_eq!(fold_diacritics("москва"), "москва");
+    }
+}
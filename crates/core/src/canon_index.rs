@@ -55,6 +55,17 @@ impl CanonicalIndex {
             .map(|stored_url| stored_url.0))
     }
 
+    /// The https version of an http url if it has been seen during crawling.
+    pub fn https_version(&self, url: &Url) -> Result<Option<Url>> {
+        if url.scheme() != "http" {
+            return Ok(None);
+        }
+
+        Ok(self
+            .get(url)?
+            .filter(|canonical| canonical.scheme() == "https"))
+    }
+
     pub fn commit(&mut self) -> Result<()> {
         self.inner.commit()
     }
@@ -68,3 +79,27 @@ impl CanonicalIndex {
         self.optimize_read()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn https_version() {
+        let mut index = CanonicalIndex::open(crate::gen_temp_path()).unwrap();
+
+        let http = Url::parse("http://example.com/page").unwrap();
+        let https = Url::parse("https://example.com/page").unwrap();
+        let other = Url::parse("http://example.com/other").unwrap();
+
+        index.insert(http.clone(), https.clone()).unwrap();
+        index
+            .insert(other.clone(), Url::parse("http://example.com/").unwrap())
+            .unwrap();
+        index.commit().unwrap();
+
+        assert_eq!(index.https_version(&http).unwrap(), Some(https.clone()));
+        assert_eq!(index.https_version(&https).unwrap(), None);
+        assert_eq!(index.https_version(&other).unwrap(), None);
+    }
+}
@@ -0,0 +1,239 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Cloaking is when a site serves different content to search engine crawlers
+//! than to regular users, typically to rank for content the users never see.
+//! We detect it by fetching the same page twice, once with a browser-like user agent
+//! and once with the crawler user agent, and comparing the extracted text.
+
+use std::{collections::HashSet, path::Path, time::Duration};
+
+use url::Url;
+
+use crate::{
+    config::CloakingAuditConfig,
+    webgraph::{Node, NodeID},
+    webpage::Html,
+    Result,
+};
+
+/// Hosts need at least this many checked pages before they can be flagged.
+const MIN_CHECKED_PAGES: u64 = 2;
+
+/// A host is flagged if at least this ratio of its checked pages differ.
+const MIN_DIFFERENT_RATIO: f64 = 0.5;
+
+/// Jaccard similarity of the words in the two texts.
+pub fn content_similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<String> = a.split_whitespace().map(|w| w.to_lowercase()).collect();
+    let b: HashSet<String> = b.split_whitespace().map(|w| w.to_lowercase()).collect();
+
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    let intersection = a.intersection(&b).count();
+    let union = a.union(&b).count();
+
+    intersection as f64 / union as f64
+}
+
+fn extract_text(body: &str, url: &Url) -> Option<String> {
+    let html = Html::parse(body, url.as_str()).ok()?;
+
+    html.clean_text().cloned()
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct CloakingStats {
+    pub num_checked: u64,
+    pub num_different: u64,
+}
+
+impl CloakingStats {
+    pub fn add(&mut self, similarity: f64, threshold: f64) {
+        self.num_checked += 1;
+
+        if similarity < threshold {
+            self.num_different += 1;
+        }
+    }
+
+    pub fn different_ratio(&self) -> f64 {
+        if self.num_checked == 0 {
+            return 0.0;
+        }
+
+        self.num_different as f64 / self.num_checked as f64
+    }
+
+    pub fn is_cloaking(&self) -> bool {
+        self.num_checked >= MIN_CHECKED_PAGES && self.different_ratio() >= MIN_DIFFERENT_RATIO
+    }
+}
+
+/// Fetches pages with both a browser-like and the crawler user agent.
+pub struct DualFetcher {
+    browser: reqwest::Client,
+    crawler: reqwest::Client,
+}
+
+impl DualFetcher {
+    pub fn new(config: &CloakingAuditConfig) -> Result<Self> {
+        let timeout = Duration::from_secs(config.timeout_seconds);
+
+        let client = |user_agent: &str| {
+            reqwest::Client::builder()
+                .timeout(timeout)
+                .connect_timeout(timeout)
+                .user_agent(user_agent)
+                .build()
+        };
+
+        Ok(Self {
+            browser: client(&config.browser_user_agent)?,
+            crawler: client(&config.crawler_user_agent.full)?,
+        })
+    }
+
+    async fn fetch_text(client: &reqwest::Client, url: &Url) -> Result<Option<String>> {
+        let res = client.get(url.as_str()).send().await?;
+
+        if !res.status().is_success() {
+            return Ok(None);
+        }
+
+        let url = res.url().clone();
+        let body = res.text().await?;
+
+        Ok(extract_text(&body, &url))
+    }
+
+    /// Similarity of the content served to the two user agents.
+    /// Returns `None` if either of the fetches didn't return any content.
+    pub async fn similarity(&self, url: &Url) -> Result<Option<f64>> {
+        let (browser, crawler) = tokio::join!(
+            Self::fetch_text(&self.browser, url),
+            Self::fetch_text(&self.crawler, url)
+        );
+
+        match (browser?, crawler?) {
+            (Some(browser), Some(crawler)) => Ok(Some(content_similarity(&browser, &crawler))),
+            _ => Ok(None),
+        }
+    }
+}
+
+pub struct CloakingStore {
+    inner: speedy_kv::Db<NodeID, CloakingStats>,
+}
+
+impl CloakingStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(path)?,
+        })
+    }
+
+    pub fn insert(&mut self, host: &Node, stats: CloakingStats) -> Result<()> {
+        self.inner.insert(host.clone().into_host().id(), stats)
+    }
+
+    pub fn get(&self, host: &NodeID) -> Result<Option<CloakingStats>> {
+        self.inner.get(host)
+    }
+
+    pub fn is_cloaking(&self, host: &NodeID) -> Result<bool> {
+        Ok(self
+            .get(host)?
+            .map(|stats| stats.is_cloaking())
+            .unwrap_or(false))
+    }
+
+    pub fn commit(&mut self) -> Result<()> {
+        self.inner.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity() {
+        assert_eq!(content_similarity("", ""), 1.0);
+        assert_eq!(content_similarity("the cat sat", "The cat SAT"), 1.0);
+        assert_eq!(content_similarity("the cat sat", "buy cheap pills"), 0.0);
+        assert!((content_similarity("the cat sat", "the cat ran") - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stats() {
+        let mut stats = CloakingStats::default();
+
+        stats.add(0.1, 0.5);
+        assert!(!stats.is_cloaking());
+
+        stats.add(0.9, 0.5);
+        assert!(stats.is_cloaking());
+
+        stats.add(0.9, 0.5);
+        assert!(!stats.is_cloaking());
+        assert!((stats.different_ratio() - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn store() {
+        let mut store = CloakingStore::open(crate::gen_temp_path()).unwrap();
+
+        store
+            .insert(
+                &Node::from("https://spam.com/"),
+                CloakingStats {
+                    num_checked: 4,
+                    num_different: 3,
+                },
+            )
+            .unwrap();
+        store
+            .insert(
+                &Node::from("https://example.com/"),
+                CloakingStats {
+                    num_checked: 4,
+                    num_different: 0,
+                },
+            )
+            .unwrap();
+        store.commit().unwrap();
+
+        let host = |url: &str| Node::from(url).into_host().id();
+
+        assert!(store.is_cloaking(&host("https://spam.com/page")).unwrap());
+        assert!(!store.is_cloaking(&host("https://example.com/")).unwrap());
+        assert!(!store.is_cloaking(&host("https://other.com/")).unwrap());
+    }
+}
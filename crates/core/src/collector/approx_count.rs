@@ -57,6 +57,12 @@ impl Count {
         }
     }
 
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            Count::Exact(count) | Count::Approximate(count) => *count,
+        }
+    }
+
     pub fn compose(&self, other: &Self) -> Self {
         match (self, other) {
             (Count::Exact(a), Count::Exact(b)) => Count::Exact(a + b),
@@ -14,6 +14,26 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub struct Autosuggest;
+
+impl Autosuggest {
+    pub fn frequency_weight() -> f64 {
+        1.0
+    }
+
+    pub fn trend_weight() -> f64 {
+        1.0
+    }
+
+    pub fn prefix_match_weight() -> f64 {
+        2.0
+    }
+
+    pub fn max_candidates() -> usize {
+        1_000
+    }
+}
+
 pub struct Collector;
 
 impl Collector {
@@ -56,6 +76,10 @@ impl Api {
     pub fn max_similar_hosts() -> usize {
         1_000
     }
+
+    pub fn max_batch_queries() -> usize {
+        32
+    }
 }
 
 pub struct Snippet;
@@ -141,6 +165,14 @@ impl SearchQuery {
         false
     }
 
+    pub fn include_parked() -> bool {
+        false
+    }
+
+    pub fn diversify() -> bool {
+        true
+    }
+
     pub fn count_results_exact() -> bool {
         false
     }
@@ -148,6 +180,10 @@ impl SearchQuery {
     pub fn return_structured_data() -> bool {
         false
     }
+
+    pub fn disable_rewrites() -> bool {
+        false
+    }
 }
 
 pub struct Correction;
@@ -207,3 +243,256 @@ impl Webgraph {
         true
     }
 }
+
+pub struct Compaction;
+impl Compaction {
+    pub fn segments_per_merge() -> usize {
+        8
+    }
+
+    pub fn size_ratio() -> f64 {
+        4.0
+    }
+
+    pub fn min_segment_size_bytes() -> u64 {
+        64 * 1024 * 1024
+    }
+
+    pub fn interval_secs() -> u64 {
+        60
+    }
+}
+
+pub struct CloakingAudit;
+impl CloakingAudit {
+    pub fn browser_user_agent() -> String {
+        "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36".to_string()
+    }
+
+    pub fn pages_per_host() -> usize {
+        3
+    }
+
+    pub fn similarity_threshold() -> f64 {
+        0.5
+    }
+
+    pub fn timeout_seconds() -> u64 {
+        30
+    }
+
+    pub fn num_concurrent_hosts() -> usize {
+        64
+    }
+}
+
+pub struct HostClusters;
+impl HostClusters {
+    pub fn max_iterations() -> usize {
+        20
+    }
+}
+
+pub struct Evaluation;
+
+impl Evaluation {
+    pub fn run_name() -> String {
+        "stract".to_string()
+    }
+
+    pub fn num_results() -> usize {
+        100
+    }
+
+    pub fn cutoff() -> usize {
+        10
+    }
+}
+
+pub struct AllInOne;
+
+impl AllInOne {
+    pub fn host() -> std::net::SocketAddr {
+        std::net::SocketAddr::from(([0, 0, 0, 0], 3000))
+    }
+}
+
+pub struct QueryLog;
+
+impl QueryLog {
+    pub fn k_anonymity() -> usize {
+        10
+    }
+
+    pub fn num_evaluation_queries() -> usize {
+        1000
+    }
+
+    pub fn max_related() -> usize {
+        5
+    }
+}
+
+pub struct PageFeatures;
+
+impl PageFeatures {
+    pub fn row_group_size() -> usize {
+        10_000
+    }
+}
+
+pub struct ModelRegistry;
+
+impl ModelRegistry {
+    pub fn reload_interval_secs() -> u64 {
+        60
+    }
+}
+
+pub struct Accelerator;
+
+impl Accelerator {
+    pub fn cpu_batch_size() -> usize {
+        32
+    }
+
+    pub fn cuda_batch_size() -> usize {
+        256
+    }
+
+    pub fn metal_batch_size() -> usize {
+        128
+    }
+}
+
+pub struct CitationVerification;
+
+impl CitationVerification {
+    pub fn min_support() -> f32 {
+        0.5
+    }
+}
+
+pub struct SlowQueryLog;
+
+impl SlowQueryLog {
+    pub fn threshold_ms() -> u64 {
+        1_000
+    }
+}
+
+pub struct PersonalizedPageRank;
+
+impl PersonalizedPageRank {
+    pub fn alpha() -> f64 {
+        0.15
+    }
+
+    pub fn iterations() -> usize {
+        20
+    }
+}
+
+pub struct StageLimit;
+
+impl StageLimit {
+    pub fn failure_threshold() -> usize {
+        5
+    }
+
+    pub fn cooldown_ms() -> u64 {
+        30_000
+    }
+}
+
+pub struct TrustRank;
+
+impl TrustRank {
+    pub fn decay() -> f64 {
+        0.85
+    }
+
+    pub fn iterations() -> usize {
+        20
+    }
+}
+
+pub struct Betweenness;
+
+impl Betweenness {
+    pub fn num_pivots() -> usize {
+        10_000
+    }
+}
+
+pub struct ResultCache;
+
+impl ResultCache {
+    pub fn ttl_secs() -> u64 {
+        5 * 60
+    }
+
+    pub fn max_entries() -> usize {
+        10_000
+    }
+}
+
+pub struct ResultSet;
+
+impl ResultSet {
+    pub fn ttl_secs() -> u64 {
+        15 * 60
+    }
+
+    pub fn max_entries() -> usize {
+        100_000
+    }
+}
+
+pub struct IncrementalHarmonic;
+
+impl IncrementalHarmonic {
+    pub fn max_hops() -> u8 {
+        4
+    }
+
+    pub fn max_affected_fraction() -> f64 {
+        0.01
+    }
+
+    pub fn full_rebuild_interval() -> usize {
+        10
+    }
+}
+
+pub struct Standby;
+
+impl Standby {
+    pub fn sync_interval_secs() -> u64 {
+        30
+    }
+
+    pub fn chunk_size_bytes() -> u64 {
+        8 * 1024 * 1024
+    }
+}
+
+pub struct NeighborhoodFunction;
+
+impl NeighborhoodFunction {
+    pub fn max_hops() -> u8 {
+        8
+    }
+}
+
+pub struct TieredStorage;
+
+impl TieredStorage {
+    pub fn cache_size_bytes() -> u64 {
+        64 * 1024 * 1024 * 1024
+    }
+
+    pub fn block_size_bytes() -> u64 {
+        1024 * 1024
+    }
+}
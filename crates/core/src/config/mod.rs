@@ -18,7 +18,7 @@ pub mod defaults;
 
 use super::Result;
 use crate::ampc::dht;
-use crate::distributed::member::ShardId;
+use crate::distributed::member::{ShardId, SnapshotDate};
 use crate::feed::scheduler::SplitId;
 
 use std::fs::File;
@@ -38,6 +38,12 @@ pub struct IndexingLocalConfig {
     pub page_centrality_store_path: Option<String>,
     pub safety_classifier_path: Option<String>,
     pub minimum_clean_words: Option<usize>,
+    pub outlink_audit_path: Option<String>,
+    pub outlink_profile_path: Option<String>,
+    pub cloaking_audit_path: Option<String>,
+    pub host_about_path: Option<String>,
+    pub host_clusters_path: Option<String>,
+    pub canonical_index_path: Option<String>,
 
     #[serde(default = "defaults::Indexing::batch_size")]
     pub batch_size: usize,
@@ -68,6 +74,10 @@ pub struct IndexingDualEncoderConfig {
 pub struct WebgraphConstructConfig {
     pub host_graph_base_path: String,
     pub page_graph_base_path: String,
+    /// Also build a graph where the hosts are collapsed to their registrable domain.
+    /// Links between subdomains of the same domain are left out, so centrality computed
+    /// on it can not be inflated by linking from many subdomains.
+    pub domain_graph_base_path: Option<String>,
     pub warc_source: WarcSource,
     pub limit_warc_files: Option<usize>,
     pub skip_warc_files: Option<usize>,
@@ -77,6 +87,121 @@ pub struct WebgraphConstructConfig {
 
     #[serde(default = "defaults::Webgraph::merge_all_segments")]
     pub merge_all_segments: bool,
+
+    #[serde(default)]
+    pub insert_policy: WebgraphInsertPolicy,
+
+    /// How the urls of the links are turned into nodes. Graphs built with a
+    /// non-default normalization must be opened with the same normalization.
+    #[serde(default)]
+    pub normalization: WebgraphNormalizationConfig,
+
+    /// Codecs of the adjacency and label stores of the segments.
+    #[serde(default)]
+    pub compression: crate::webgraph::Compression,
+
+    /// Log the edges of the workers to a write-ahead log, so a restarted build
+    /// keeps the edges of the crashed run and skips the jobs it completed.
+    #[serde(default)]
+    pub write_ahead_log: bool,
+
+    /// Compact the segments of the graphs in the background while the graphs
+    /// of the workers are merged, and once more when all of them are merged.
+    pub compaction: Option<CompactionConfig>,
+}
+
+/// Size-tiered compaction of webgraph segments. Segments are grouped into tiers
+/// where each tier holds segments up to `size_ratio` times larger than the tier
+/// below it, and a merge combines the smallest segments within a single tier.
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct CompactionConfig {
+    /// Number of segments that are merged at a time. A tier is compacted once
+    /// it holds at least this many segments.
+    #[serde(default = "defaults::Compaction::segments_per_merge")]
+    pub segments_per_merge: usize,
+
+    #[serde(default = "defaults::Compaction::size_ratio")]
+    pub size_ratio: f64,
+
+    /// Segments up to this size are all in the lowest tier.
+    #[serde(default = "defaults::Compaction::min_segment_size_bytes")]
+    pub min_segment_size_bytes: u64,
+
+    /// Segments larger than this are never compacted.
+    pub max_segment_size_bytes: Option<u64>,
+
+    /// How long the background compaction waits before it looks for segments
+    /// to merge again, when there was nothing to merge.
+    #[serde(default = "defaults::Compaction::interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for CompactionConfig {
+    fn default() -> Self {
+        Self {
+            segments_per_merge: defaults::Compaction::segments_per_merge(),
+            size_ratio: defaults::Compaction::size_ratio(),
+            min_segment_size_bytes: defaults::Compaction::min_segment_size_bytes(),
+            max_segment_size_bytes: None,
+            interval_secs: defaults::Compaction::interval_secs(),
+        }
+    }
+}
+
+/// Which links the webgraph writers keep. By default all links are kept.
+#[derive(Debug, Default, serde::Deserialize, Clone)]
+pub struct WebgraphInsertPolicy {
+    /// Keep at most this many outgoing links per node.
+    pub max_outlinks_per_node: Option<usize>,
+
+    /// Drop links to hosts under these top level domains, e.g. `["zip", "co.cc"]`.
+    #[serde(default)]
+    pub blocked_tlds: Vec<String>,
+
+    /// Collapse footer and navigation links from the pages of a host to the same
+    /// destination into a single edge, weighted by the number of collapsed links.
+    #[serde(default)]
+    pub collapse_boilerplate: bool,
+}
+
+impl WebgraphInsertPolicy {
+    pub fn is_blocked_host(&self, host: &str) -> bool {
+        self.blocked_tlds.iter().any(|tld| {
+            let tld = tld.trim_start_matches('.');
+
+            host.strip_suffix(tld)
+                .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+        })
+    }
+}
+
+/// What is kept of the query string of a url when it is turned into a node.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryNormalization {
+    /// Remove tracking parameters such as `utm_source`.
+    #[default]
+    Clean,
+    /// Keep the query string as it is.
+    Keep,
+    /// Remove the query string.
+    Strip,
+}
+
+/// Normalization of the urls that are inserted into the webgraph. The default
+/// gives the same nodes as `Node::from`.
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+pub struct WebgraphNormalizationConfig {
+    #[serde(default)]
+    pub query: QueryNormalization,
+
+    /// Lowercase the path of the url. The case of the path is kept by default.
+    #[serde(default)]
+    pub lowercase_path: bool,
+
+    /// Hosts that are replaced by another host, e.g. `{ "m.example.com" = "example.com" }`.
+    #[serde(default)]
+    pub host_aliases: std::collections::HashMap<String, String>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Clone)]
@@ -85,6 +210,9 @@ pub enum WarcSource {
     HTTP(HttpConfig),
     Local(LocalConfig),
     S3(S3Config),
+    /// Stream gzipped warc records from stdin, e.g. `cat *.warc.gz | stract ...`.
+    /// The records are processed as they arrive, so the stream is never stored on disk.
+    Stdin,
 }
 
 impl WarcSource {
@@ -100,6 +228,9 @@ impl WarcSource {
             WarcSource::Local(config) => {
                 warc_paths.clone_from(&config.names);
             }
+            WarcSource::Stdin => {
+                warc_paths.push(crate::warc::STDIN_PATH.to_string());
+            }
             WarcSource::S3(config) => {
                 let bucket = s3::Bucket::new(
                     &config.bucket,
@@ -167,6 +298,39 @@ pub struct S3Config {
     pub endpoint: String,
 }
 
+/// Weights of the signals that suggestions are ranked by.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct AutosuggestRankingConfig {
+    /// Weight of the log of how often the query has been searched.
+    #[serde(default = "defaults::Autosuggest::frequency_weight")]
+    pub frequency_weight: f64,
+
+    /// Weight of the log of the searches of the query, where each search is
+    /// weighted down with a half-life of a week before the newest search.
+    #[serde(default = "defaults::Autosuggest::trend_weight")]
+    pub trend_weight: f64,
+
+    /// Weight of the fraction of the suggestion that has already been typed.
+    #[serde(default = "defaults::Autosuggest::prefix_match_weight")]
+    pub prefix_match_weight: f64,
+
+    /// Number of queries with the prefix that are ranked. The fst is read in
+    /// lexicographic order, so later queries with the prefix are never suggested.
+    #[serde(default = "defaults::Autosuggest::max_candidates")]
+    pub max_candidates: usize,
+}
+
+impl Default for AutosuggestRankingConfig {
+    fn default() -> Self {
+        Self {
+            frequency_weight: defaults::Autosuggest::frequency_weight(),
+            trend_weight: defaults::Autosuggest::trend_weight(),
+            prefix_match_weight: defaults::Autosuggest::prefix_match_weight(),
+            max_candidates: defaults::Autosuggest::max_candidates(),
+        }
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
 pub struct CollectorConfig {
     #[serde(default = "defaults::Collector::site_penalty")]
@@ -221,6 +385,11 @@ pub struct ApiSpellCheck {
 
     #[serde(default)]
     pub correction_config: CorrectionConfig,
+
+    /// Search for the corrected query instead of only suggesting the correction.
+    /// The correction is returned with the results and can be disabled per query.
+    #[serde(default)]
+    pub auto_correct: bool,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -231,6 +400,16 @@ pub struct ApiConfig {
     pub crossencoder_model_path: Option<String>,
     pub lambda_model_path: Option<String>,
     pub dual_encoder_model_path: Option<String>,
+    /// Folder with the models of the summarizer. The summarize endpoint is
+    /// disabled when no summarizer is configured.
+    pub summarizer_path: Option<String>,
+    /// Safety classifier that drops nsfw results when safe search is enabled.
+    pub safety_classifier_path: Option<String>,
+    /// Check that each sentence of a summary is supported by the passages it cites.
+    /// The summarizer folder must contain a `cross_encoder` model.
+    pub citation_verification: Option<CitationVerificationConfig>,
+    /// Store with the descriptors of each host that are shown in the site info panel.
+    pub host_about_path: Option<String>,
     pub bangs_path: String,
     pub query_store_db_host: Option<String>,
     pub cluster_id: String,
@@ -240,6 +419,10 @@ pub struct ApiConfig {
     #[serde(default = "defaults::Api::max_similar_hosts")]
     pub max_similar_hosts: usize,
 
+    /// Maximum number of queries in a single batch search request.
+    #[serde(default = "defaults::Api::max_batch_queries")]
+    pub max_batch_queries: usize,
+
     pub spell_check: Option<ApiSpellCheck>,
 
     #[serde(default)]
@@ -252,6 +435,91 @@ pub struct ApiConfig {
 
     #[serde(default = "defaults::Api::max_concurrent_searches")]
     pub max_concurrent_searches: Option<usize>,
+
+    pub threat_list: Option<ApiThreatList>,
+    /// Log the queries that exceed a latency threshold together with their cost and ranking signals.
+    pub slow_query_log: Option<SlowQueryLogConfig>,
+
+    #[serde(default)]
+    pub stage_limits: StageLimitsConfig,
+
+    /// Cache the results of queries. Queries that only differ in case, whitespace
+    /// or stopwords share the cached results.
+    pub result_cache: Option<ResultCacheConfig>,
+
+    /// Keep the urls of each response for a while, so a follow-up query can search
+    /// within the results of the response.
+    pub result_sets: Option<ResultSetConfig>,
+
+    /// Never contact third parties while serving requests, e.g. when the instance runs
+    /// as an onion service. Data that would otherwise be fetched, like exchange rates,
+    /// is only read from what has been stored locally.
+    #[serde(default)]
+    pub disable_outbound_requests: bool,
+
+    /// Defaults for queries from each region, keyed by the `gl` code of the region.
+    #[serde(default)]
+    pub region_defaults: std::collections::HashMap<String, ApiRegionDefaults>,
+
+    /// Load the ranking models from a model registry instead of the model paths above.
+    pub model_registry: Option<ModelRegistryConfig>,
+
+    #[serde(default)]
+    pub cross_encoder_accelerator: AcceleratorConfig,
+
+    #[serde(default)]
+    pub dual_encoder_accelerator: AcceleratorConfig,
+
+    /// The summarizer runs on this device. The cross encoder that verifies the citations
+    /// of the summaries runs on the device of `cross_encoder_accelerator`.
+    #[serde(default)]
+    pub summarizer_accelerator: AcceleratorConfig,
+
+    /// Token for the admin endpoints, e.g. to reload the autosuggest queries.
+    /// The admin endpoints are disabled without a token.
+    pub admin_token: Option<String>,
+
+    /// Suggestions that are removed before they are returned by autosuggest.
+    pub autosuggest_filter: Option<AutosuggestFilterConfig>,
+
+    #[serde(default)]
+    pub autosuggest_ranking: AutosuggestRankingConfig,
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize, Clone)]
+pub struct AutosuggestFilterConfig {
+    /// File with a term per line, e.g. slurs, that are never suggested.
+    pub blocklist_path: Option<String>,
+    /// File with a term per line that are not suggested when safe search is on.
+    pub adult_terms_path: Option<String>,
+    /// Regular expressions of suggestions that are never shown.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct ApiRegionDefaults {
+    pub safe_search: Option<bool>,
+    pub optic_path: Option<String>,
+}
+
+/// What to do with results that are on a malware or phishing list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreatPolicy {
+    /// Show the results with a warning.
+    #[default]
+    Annotate,
+    /// Remove the results.
+    Filter,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
+pub struct ApiThreatList {
+    pub path: String,
+
+    #[serde(default)]
+    pub policy: ThreatPolicy,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -311,6 +579,16 @@ pub struct SearchServerConfig {
 
     #[serde(default)]
     pub snippet: SnippetConfig,
+
+    /// Run as a warm standby for the shard. The index is replicated from an active
+    /// searcher of the shard into `index_path` until the standby is promoted.
+    pub standby: Option<StandbyConfig>,
+    /// Read the index files that have been offloaded to object storage
+    /// through a local block cache.
+    pub tiered_storage: Option<TieredStorageConfig>,
+    /// Serve `index_path` as the frozen snapshot of the index taken on this date
+    /// (`YYYY-MM-DD`). Snapshot searchers only get the queries that ask for their date.
+    pub snapshot: Option<SnapshotDate>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -398,6 +676,8 @@ pub enum AcceleratorDevice {
 pub enum WebgraphGranularity {
     Host,
     Page,
+    /// Hosts collapsed to their registrable domain.
+    Domain,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -407,6 +687,25 @@ pub struct WebgraphServerConfig {
     pub graph_path: String,
     pub granularity: WebgraphGranularity,
 
+    /// Lock the edge ranges of the graph in memory, so edge lookups never wait for the disk.
+    /// The process needs a large enough `RLIMIT_MEMLOCK`.
+    #[serde(default)]
+    pub lock_index_in_memory: bool,
+
+    /// Memory budget for the cache of decoded edge and label blocks. Without a cache,
+    /// every query decompresses the blocks it reads from the page cache.
+    #[serde(default)]
+    pub cache_bytes: Option<u64>,
+
+    /// Number of nodes to keep in memory when resolving the node ids of edges.
+    #[serde(default)]
+    pub node_cache_size: Option<usize>,
+
+    /// Address to serve the prometheus metrics of the graph on, e.g. edge query latencies,
+    /// segments touched per query and the hit rate of the block cache.
+    #[serde(default)]
+    pub prometheus_host: Option<SocketAddr>,
+
     pub cluster_id: String,
     pub gossip_seed_nodes: Option<Vec<SocketAddr>>,
     pub gossip_addr: SocketAddr,
@@ -418,6 +717,10 @@ pub struct WidgetsConfig {
 
     #[serde(default = "defaults::Widgets::calculator_fetch_currencies_exchange")]
     pub calculator_fetch_currencies_exchange: bool,
+
+    /// Where the exchange rates are stored. The rates are read from here at startup and
+    /// updated when new rates are fetched.
+    pub calculator_exchange_rates_path: Option<String>,
 }
 
 impl Default for WidgetsConfig {
@@ -426,6 +729,7 @@ impl Default for WidgetsConfig {
             thesaurus_paths: Vec::new(),
             calculator_fetch_currencies_exchange:
                 defaults::Widgets::calculator_fetch_currencies_exchange(),
+            calculator_exchange_rates_path: None,
         }
     }
 }
@@ -483,6 +787,12 @@ pub struct LiveIndexConfig {
     pub safety_classifier_path: Option<String>,
     pub host_centrality_threshold: Option<f64>,
     pub minimum_clean_words: Option<usize>,
+    pub outlink_audit_path: Option<String>,
+    pub outlink_profile_path: Option<String>,
+    pub cloaking_audit_path: Option<String>,
+    pub host_about_path: Option<String>,
+    pub host_clusters_path: Option<String>,
+    pub canonical_index_path: Option<String>,
 
     // search
     pub cluster_id: String,
@@ -613,3 +923,347 @@ pub struct CanonicalIndexConfig {
     pub limit_warc_files: Option<usize>,
     pub skip_warc_files: Option<usize>,
 }
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct OutlinkAuditConfig {
+    /// Path to the page webgraph.
+    pub webgraph_path: String,
+    /// Crawl logs where each line has the format `<status code> <url>`.
+    pub crawl_log_paths: Vec<String>,
+    pub output_path: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct OutlinkProfileConfig {
+    /// Path to the page webgraph.
+    pub webgraph_path: String,
+    pub output_path: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct CloakingAuditConfig {
+    pub output_path: String,
+    /// The pages to check are sampled from these warc files.
+    pub warc_source: WarcSource,
+    pub limit_warc_files: Option<usize>,
+    pub skip_warc_files: Option<usize>,
+    pub crawler_user_agent: UserAgent,
+
+    #[serde(default = "defaults::CloakingAudit::browser_user_agent")]
+    pub browser_user_agent: String,
+
+    #[serde(default = "defaults::CloakingAudit::pages_per_host")]
+    pub pages_per_host: usize,
+
+    /// Pages where the content served to the two user agents is less similar
+    /// than this threshold are considered different.
+    #[serde(default = "defaults::CloakingAudit::similarity_threshold")]
+    pub similarity_threshold: f64,
+
+    #[serde(default = "defaults::CloakingAudit::timeout_seconds")]
+    pub timeout_seconds: u64,
+
+    #[serde(default = "defaults::CloakingAudit::num_concurrent_hosts")]
+    pub num_concurrent_hosts: usize,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct EvaluationConfig {
+    pub index_path: String,
+    pub topics_path: String,
+    pub qrels_path: Option<String>,
+    pub run_path: String,
+    pub optic_path: Option<String>,
+    pub linear_model_path: Option<String>,
+    pub lambda_model_path: Option<String>,
+
+    #[serde(default = "defaults::Evaluation::run_name")]
+    pub run_name: String,
+
+    /// Number of results to include in the run for each query.
+    #[serde(default = "defaults::Evaluation::num_results")]
+    pub num_results: usize,
+
+    /// nDCG and MRR are computed for the top `cutoff` results.
+    #[serde(default = "defaults::Evaluation::cutoff")]
+    pub cutoff: usize,
+
+    #[serde(default)]
+    pub collector: CollectorConfig,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct QueryLogConfig {
+    /// Raw query events with one json object per line.
+    pub input_paths: Vec<String>,
+    pub output_path: String,
+
+    /// Queries (and result urls for a query) must be seen by at least this many distinct users
+    /// to be included.
+    #[serde(default = "defaults::QueryLog::k_anonymity")]
+    pub k_anonymity: usize,
+
+    #[serde(default = "defaults::QueryLog::num_evaluation_queries")]
+    pub num_evaluation_queries: usize,
+
+    #[serde(default = "defaults::QueryLog::max_related")]
+    pub max_related: usize,
+
+    #[serde(default)]
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct FileIndexerConfig {
+    /// Directory or zip file with the html and markdown files to index.
+    pub input_path: String,
+    pub output_path: String,
+
+    /// Url where the files are served, e.g. `https://intranet.example.com/docs/`.
+    /// The files are indexed with `file://` urls if not specified.
+    pub base_url: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct AllInOneConfig {
+    /// Directory with the index, webgraphs etc. It is populated on the first run.
+    pub data_dir: String,
+
+    #[serde(default = "defaults::AllInOne::host")]
+    pub host: SocketAddr,
+
+    /// Warc file to build the index from on the first run. A few bundled pages are used if not specified.
+    pub warc_path: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ThreatListSource {
+    /// Local path or http(s) url of the list.
+    pub path: String,
+    pub threat: crate::threat_list::Threat,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ThreatListsConfig {
+    pub lists: Vec<ThreatListSource>,
+    pub output_path: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct HostClustersConfig {
+    pub host_graph_path: String,
+    pub output_path: String,
+
+    /// Label propagation stops after this many iterations, even if the clusters have not converged.
+    #[serde(default = "defaults::HostClusters::max_iterations")]
+    pub max_iterations: usize,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct PageFeaturesConfig {
+    /// Directory for the parquet files. One file is written for each warc file.
+    pub output_path: String,
+    pub warc_source: WarcSource,
+    pub limit_warc_files: Option<usize>,
+    pub skip_warc_files: Option<usize>,
+
+    /// Number of rows in each row group of the parquet files.
+    #[serde(default = "defaults::PageFeatures::row_group_size")]
+    pub row_group_size: usize,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct HostAboutConfig {
+    /// Directory of the store with the descriptors of each host.
+    pub output_path: String,
+    pub warc_source: WarcSource,
+    pub limit_warc_files: Option<usize>,
+    pub skip_warc_files: Option<usize>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct CoverageReportConfig {
+    pub host_graph_path: String,
+    /// Indexed pages are only included in the report if an index is given.
+    pub index_path: Option<String>,
+    /// Directory for the json and csv files of the report.
+    pub output_path: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SlowQueryLogConfig {
+    /// File the slow queries are appended to, one json object per line.
+    pub path: String,
+    /// Queries that take at least this long are logged.
+    #[serde(default = "defaults::SlowQueryLog::threshold_ms")]
+    pub threshold_ms: u64,
+}
+
+/// Deadlines and circuit breakers for the optional stages of a search.
+/// Stages without a limit are always waited for.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct StageLimitsConfig {
+    /// Widgets like the calculator and thesaurus.
+    pub widgets: Option<StageLimitConfig>,
+    /// Reranking of the top results, e.g. with the cross encoder.
+    /// The results keep the order from the recall stage when it is skipped.
+    pub reranker: Option<StageLimitConfig>,
+    /// Lookups in the host webgraph for the inbound similarity of the results.
+    pub webgraph: Option<StageLimitConfig>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StageLimitConfig {
+    /// The stage is skipped if it has not finished within this time.
+    pub timeout_ms: u64,
+    /// Consecutive failures or timeouts before the stage is skipped without being called.
+    #[serde(default = "defaults::StageLimit::failure_threshold")]
+    pub failure_threshold: usize,
+    /// How long the stage is skipped before it is tried again.
+    #[serde(default = "defaults::StageLimit::cooldown_ms")]
+    pub cooldown_ms: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ResultCacheConfig {
+    #[serde(default = "defaults::ResultCache::ttl_secs")]
+    pub ttl_secs: u64,
+    #[serde(default = "defaults::ResultCache::max_entries")]
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ResultSetConfig {
+    /// How long a result set can be searched within after the response it was created for.
+    #[serde(default = "defaults::ResultSet::ttl_secs")]
+    pub ttl_secs: u64,
+    #[serde(default = "defaults::ResultSet::max_entries")]
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StandbyConfig {
+    /// How often the index is compared with the active searcher.
+    #[serde(default = "defaults::Standby::sync_interval_secs")]
+    pub sync_interval_secs: u64,
+    /// Size of the chunks that files are copied in.
+    #[serde(default = "defaults::Standby::chunk_size_bytes")]
+    pub chunk_size_bytes: u64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TieredStorageConfig {
+    /// Bucket and folder that the cold files of the index are stored in.
+    /// Each index needs its own folder.
+    pub s3: S3Config,
+    /// Folder for the cached blocks of the cold files.
+    pub cache_path: String,
+    /// Maximum size of the cached blocks on disk.
+    #[serde(default = "defaults::TieredStorage::cache_size_bytes")]
+    pub cache_size_bytes: u64,
+    /// Size of the blocks that are fetched from object storage. Changing it
+    /// requires the files to be offloaded again.
+    #[serde(default = "defaults::TieredStorage::block_size_bytes")]
+    pub block_size_bytes: u64,
+}
+
+/// A version of a model in the model registry manifest.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ModelSource {
+    /// Local path or http(s) url of the model. Models that are stored as a folder,
+    /// like the dual encoder and cross encoder, are downloaded as a zip archive.
+    pub location: String,
+    pub version: String,
+    /// Hex encoded md5 checksum of the model file, or of all files if the model is a folder.
+    pub checksum: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct ModelRegistryConfig {
+    /// Toml file that maps model names to a [`ModelSource`]. The file is read periodically,
+    /// and a model is swapped at runtime when its version changes.
+    pub manifest_path: String,
+    /// Folder where models that are downloaded from a url are stored.
+    pub cache_path: String,
+
+    #[serde(default = "defaults::ModelRegistry::reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+/// Device that a model runs on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcceleratorDevice {
+    #[default]
+    Cpu,
+    /// Requires the `cuda` feature.
+    Cuda,
+    /// Requires the `metal` feature.
+    Metal,
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct AcceleratorConfig {
+    #[serde(default)]
+    pub device: AcceleratorDevice,
+    /// Index of the gpu when the machine has more than one.
+    #[serde(default)]
+    pub ordinal: usize,
+    #[serde(default)]
+    pub batch_sizes: AcceleratorBatchSizes,
+}
+
+/// Number of inputs that are passed through a model at once on each device.
+/// The model falls back to the cpu if the configured device is unavailable,
+/// in which case the cpu batch size is used.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct AcceleratorBatchSizes {
+    #[serde(default = "defaults::Accelerator::cpu_batch_size")]
+    pub cpu: usize,
+    #[serde(default = "defaults::Accelerator::cuda_batch_size")]
+    pub cuda: usize,
+    #[serde(default = "defaults::Accelerator::metal_batch_size")]
+    pub metal: usize,
+}
+
+impl Default for AcceleratorBatchSizes {
+    fn default() -> Self {
+        Self {
+            cpu: defaults::Accelerator::cpu_batch_size(),
+            cuda: defaults::Accelerator::cuda_batch_size(),
+            metal: defaults::Accelerator::metal_batch_size(),
+        }
+    }
+}
+
+impl AcceleratorBatchSizes {
+    pub fn get(&self, device: AcceleratorDevice) -> usize {
+        match device {
+            AcceleratorDevice::Cpu => self.cpu,
+            AcceleratorDevice::Cuda => self.cuda,
+            AcceleratorDevice::Metal => self.metal,
+        }
+    }
+}
+
+/// What to do with sentences of an answer that are not supported by the source they cite.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsupportedClaimPolicy {
+    /// Remove the sentences from the answer.
+    #[default]
+    Drop,
+    /// Keep the sentences, but tell the client that they are unsupported.
+    Flag,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct CitationVerificationConfig {
+    /// Minimum score from the cross encoder of the summarizer for a sentence to be
+    /// supported by the passages it was extracted from.
+    #[serde(default = "defaults::CitationVerification::min_support")]
+    pub min_support: f32,
+
+    #[serde(default)]
+    pub policy: UnsupportedClaimPolicy,
+}
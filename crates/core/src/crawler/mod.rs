@@ -261,6 +261,8 @@ pub struct CrawlDatum {
     pub payload_type: warc::PayloadType,
     pub body: String,
     pub fetch_time_ms: u64,
+    /// The urls that redirected to `url`, in the order they were followed.
+    pub redirects: Vec<Url>,
 }
 
 pub struct Crawler {
@@ -305,6 +307,20 @@ pub trait DatumStream: Send + Sync {
 }
 
 pub fn reqwest_client(config: &CrawlerConfig) -> Result<reqwest::Client> {
+    Ok(client_builder(config)
+        .redirect(reqwest::redirect::Policy::limited(config.max_redirects))
+        .build()?)
+}
+
+/// Client for the pages of a job. It does not follow redirects, as the job follows
+/// them itself to record the urls that redirected to each page.
+pub fn page_client(config: &CrawlerConfig) -> Result<reqwest::Client> {
+    Ok(client_builder(config)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?)
+}
+
+fn client_builder(config: &CrawlerConfig) -> reqwest::ClientBuilder {
     let timeout = Duration::from_secs(config.timeout_seconds);
 
     let mut headers = reqwest::header::HeaderMap::default();
@@ -317,12 +333,10 @@ pub fn reqwest_client(config: &CrawlerConfig) -> Result<reqwest::Client> {
         reqwest::header::HeaderValue::from_static("en-US,en;q=0.9,*;q=0.8"),
     );
 
-    Ok(reqwest::Client::builder()
+    reqwest::Client::builder()
         .timeout(timeout)
         .connect_timeout(timeout)
         .http2_keep_alive_interval(None)
         .default_headers(headers)
-        .redirect(reqwest::redirect::Policy::limited(config.max_redirects))
         .user_agent(&config.user_agent.full)
-        .build()?)
 }
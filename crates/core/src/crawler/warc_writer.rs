@@ -113,6 +113,11 @@ async fn writer_task(mut rx: tokio::sync::mpsc::Receiver<WarcWriterMessage>, s3:
                             },
                             metadata: warc::Metadata {
                                 fetch_time_ms: datum.fetch_time_ms,
+                                redirects: datum
+                                    .redirects
+                                    .iter()
+                                    .map(|url| url.to_string())
+                                    .collect(),
                             },
                         };
 
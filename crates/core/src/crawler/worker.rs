@@ -40,9 +40,9 @@ use crate::{
 };
 
 use super::{
-    reqwest_client, robots_txt::RobotsTxtManager, wander_prirotiser::WanderPrioritiser, CrawlDatum,
-    DatumStream, Domain, Error, Result, RetrieableUrl, Site, UrlResponse, WarcWriter, WeightedUrl,
-    WorkerJob,
+    page_client, reqwest_client, robots_txt::RobotsTxtManager,
+    wander_prirotiser::WanderPrioritiser, CrawlDatum, DatumStream, Domain, Error, Result,
+    RetrieableUrl, Site, UrlResponse, WarcWriter, WeightedUrl, WorkerJob,
 };
 
 const MAX_CONTENT_LENGTH: usize = 32 * 1024 * 1024; // 32 MB
@@ -55,12 +55,15 @@ const IGNORED_EXTENSIONS: [&str; 27] = [
 
 struct ProcessedUrl {
     new_urls: Vec<Url>,
+    /// Content quality of the page the urls were found on.
+    content_quality: f64,
     response: UrlResponse,
 }
 
 pub struct WorkerThread {
     writer: Arc<WarcWriter>,
     client: reqwest::Client,
+    page_client: reqwest::Client,
     config: Arc<CrawlerConfig>,
     router_hosts: Vec<SocketAddr>,
 }
@@ -72,10 +75,12 @@ impl WorkerThread {
         router_hosts: Vec<SocketAddr>,
     ) -> Result<Self> {
         let client = reqwest_client(&config)?;
+        let page_client = page_client(&config)?;
 
         Ok(Self {
             writer,
             client,
+            page_client,
             config: Arc::new(config),
             router_hosts,
         })
@@ -106,6 +111,7 @@ impl WorkerThread {
                     let executor = JobExecutor::new(
                         job.into(),
                         self.client.clone(),
+                        self.page_client.clone(),
                         self.config.clone(),
                         self.writer.clone(),
                     );
@@ -126,6 +132,7 @@ impl WorkerThread {
 pub struct JobExecutor<S: DatumStream> {
     writer: Arc<S>,
     client: reqwest::Client,
+    page_client: reqwest::Client,
     politeness_factor: f32,
     robotstxt: RobotsTxtManager,
     crawled_urls: HashSet<Url>,
@@ -140,6 +147,7 @@ impl<S: DatumStream> JobExecutor<S> {
     pub fn new(
         job: WorkerJob,
         client: reqwest::Client,
+        page_client: reqwest::Client,
         config: Arc<CrawlerConfig>,
         writer: Arc<S>,
     ) -> Self {
@@ -151,6 +159,7 @@ impl<S: DatumStream> JobExecutor<S> {
                 Duration::from_secs(config.robots_txt_cache_sec),
             ),
             client,
+            page_client,
             crawled_urls: HashSet::new(),
             crawled_sitemaps: HashSet::new(),
             sitemap_urls: HashSet::new(),
@@ -251,7 +260,8 @@ impl<S: DatumStream> JobExecutor<S> {
 
             match res.response {
                 UrlResponse::Success { url: _ } => {
-                    let weight = retryable_url.weighted_url.weight;
+                    // links found on high quality pages are prioritised when wandering
+                    let weight = retryable_url.weighted_url.weight * res.content_quality;
 
                     for new_url in res.new_urls {
                         if new_url.host_str().is_none() {
@@ -295,6 +305,10 @@ impl<S: DatumStream> JobExecutor<S> {
         html.all_links()
             .into_iter()
             .map(|link| link.destination)
+            .map(|mut url| {
+                url.normalize();
+                url
+            })
             .filter(|url| url.as_str().len() <= MAX_URL_LEN_BYTES)
             .filter(|url| {
                 IGNORED_EXTENSIONS
@@ -318,11 +332,13 @@ impl<S: DatumStream> JobExecutor<S> {
                             let url_res = UrlResponse::Success { url: datum.url };
                             ProcessedUrl {
                                 new_urls,
+                                content_quality: html.content_quality(),
                                 response: url_res,
                             }
                         }
                         Err(_) => ProcessedUrl {
                             new_urls: Vec::new(),
+                            content_quality: 0.0,
                             response: UrlResponse::Failed {
                                 url,
                                 status_code: None,
@@ -330,7 +346,7 @@ impl<S: DatumStream> JobExecutor<S> {
                         },
                     }
                 }
-                301 | 302 => {
+                301 | 302 | 303 | 307 | 308 => {
                     let url_res = UrlResponse::Redirected {
                         url,
                         new_url: datum.url,
@@ -338,6 +354,7 @@ impl<S: DatumStream> JobExecutor<S> {
 
                     ProcessedUrl {
                         new_urls: Vec::new(),
+                        content_quality: 0.0,
                         response: url_res,
                     }
                 }
@@ -350,6 +367,7 @@ impl<S: DatumStream> JobExecutor<S> {
                     tracing::debug!("failed to fetch url ({}): {}", &url, datum.status_code);
                     ProcessedUrl {
                         new_urls: Vec::new(),
+                        content_quality: 0.0,
                         response: UrlResponse::Failed {
                             url,
                             status_code: Some(datum.status_code),
@@ -362,6 +380,7 @@ impl<S: DatumStream> JobExecutor<S> {
 
                 ProcessedUrl {
                     new_urls: Vec::new(),
+                    content_quality: 0.0,
                     response: UrlResponse::Failed {
                         url,
                         status_code: None,
@@ -380,12 +399,21 @@ impl<S: DatumStream> JobExecutor<S> {
     }
 
     async fn fetch(&self, url: Url) -> Result<reqwest::Response> {
+        self.send(&self.client, url).await
+    }
+
+    /// Fetch a page without following redirects.
+    async fn fetch_page(&self, url: Url) -> Result<reqwest::Response> {
+        self.send(&self.page_client, url).await
+    }
+
+    async fn send(&self, client: &reqwest::Client, url: Url) -> Result<reqwest::Response> {
         if self.config.dry_run {
             tracing::debug!("dry run: {}", url);
             return Err(Error::FetchFailed(reqwest::StatusCode::IM_A_TEAPOT).into());
         }
 
-        self.client
+        client
             .get(url.to_string())
             .send()
             .await
@@ -399,15 +427,15 @@ impl<S: DatumStream> JobExecutor<S> {
                 .set_scheme("https")
                 .map_err(|_| anyhow!("set scheme on url failed"))?;
 
-            match self.fetch(https).await {
+            match self.fetch_page(https).await {
                 Ok(res) => Ok(res),
                 Err(_) => {
                     tokio::time::sleep(Duration::from_millis(self.config.min_crawl_delay_ms)).await;
-                    self.fetch(url.clone()).await
+                    self.fetch_page(url.clone()).await
                 }
             }
         } else {
-            self.fetch(url.clone()).await
+            self.fetch_page(url.clone()).await
         }
     }
 
@@ -453,37 +481,24 @@ impl<S: DatumStream> JobExecutor<S> {
         }
     }
 
-    fn redirect_datum(
-        &self,
-        res: &reqwest::Response,
-        url: &Url,
-        payload_type: warc::PayloadType,
-        fetch_time: Duration,
-    ) -> Result<Option<CrawlDatum>> {
-        let status_code = res.status().as_u16();
-
-        if status_code == 301 || status_code == 302 {
-            let location = res
-                .headers()
-                .get("location")
-                .ok_or(Error::InvalidRedirect)?;
-
-            let location = location.to_str().map_err(|_| Error::InvalidRedirect)?;
-
-            let url = Url::parse(location)
-                .or_else(|_| url.join(location))
-                .map_err(|_| Error::InvalidRedirect)?;
-
-            Ok(Some(CrawlDatum {
-                url,
-                status_code,
-                payload_type,
-                body: String::new(),
-                fetch_time_ms: fetch_time.as_millis() as u64,
-            }))
-        } else {
-            Ok(None)
+    /// The url the response redirects to, if it is a redirect.
+    fn redirect_location(res: &reqwest::Response) -> Result<Option<Url>> {
+        if !matches!(res.status().as_u16(), 301 | 302 | 303 | 307 | 308) {
+            return Ok(None);
         }
+
+        let location = res
+            .headers()
+            .get("location")
+            .ok_or(Error::InvalidRedirect)?;
+
+        let location = location.to_str().map_err(|_| Error::InvalidRedirect)?;
+
+        let url = Url::parse(location)
+            .or_else(|_| res.url().join(location))
+            .map_err(|_| Error::InvalidRedirect)?;
+
+        Ok(Some(url))
     }
 
     async fn encoded_body(&self, res: reqwest::Response) -> Result<String> {
@@ -519,33 +534,58 @@ impl<S: DatumStream> JobExecutor<S> {
         Ok(text.to_string())
     }
 
+    /// Fetch the url and follow its redirects. The urls that redirected are part of
+    /// the datum, so they can be stored as aliases of the page they redirect to.
     async fn crawl_url(&self, url: Url) -> Result<CrawlDatum> {
-        let start = Instant::now();
-        let res = self.fetch_with_https_priority(url.clone()).await;
-        let fetch_time = start.elapsed();
-        self.politeness_delay(fetch_time).await;
-
-        // we want to delay before returning the error
-        let res = res?;
+        let mut url = url;
+        let mut redirects = Vec::new();
 
-        let payload_type = self.check_headers(&res)?;
+        loop {
+            let start = Instant::now();
+            let res = if redirects.is_empty() {
+                self.fetch_with_https_priority(url.clone()).await
+            } else {
+                self.fetch_page(url.clone()).await
+            };
+            let fetch_time = start.elapsed();
+            self.politeness_delay(fetch_time).await;
+
+            // we want to delay before returning the error
+            let res = res?;
+
+            if let Some(location) = Self::redirect_location(&res)? {
+                if redirects.len() < self.config.max_redirects {
+                    redirects.push(res.url().clone());
+                    url = location;
+                    continue;
+                }
 
-        if let Some(datum) = self.redirect_datum(&res, &url, payload_type, fetch_time)? {
-            return Ok(datum);
-        }
+                // the url the last redirect points to is crawled later
+                return Ok(CrawlDatum {
+                    url: location,
+                    status_code: res.status().as_u16(),
+                    payload_type: warc::PayloadType::Html,
+                    body: String::new(),
+                    fetch_time_ms: fetch_time.as_millis() as u64,
+                    redirects,
+                });
+            }
 
-        let status_code = res.status().as_u16();
+            let payload_type = self.check_headers(&res)?;
+            let status_code = res.status().as_u16();
 
-        let res_url = res.url().clone();
-        let body = self.encoded_body(res).await?;
+            let res_url = res.url().clone();
+            let body = self.encoded_body(res).await?;
 
-        Ok(CrawlDatum {
-            url: res_url,
-            status_code,
-            body,
-            payload_type,
-            fetch_time_ms: fetch_time.as_millis() as u64,
-        })
+            return Ok(CrawlDatum {
+                url: res_url,
+                status_code,
+                body,
+                payload_type,
+                fetch_time_ms: fetch_time.as_millis() as u64,
+                redirects,
+            });
+        }
     }
 
     async fn urls_from_sitemap(&self, sitemap: Url, max_depth: usize) -> Vec<Url> {
@@ -81,6 +81,7 @@ fn alive_nodes_updater(chitchat: Arc<Mutex<Chitchat>>) -> Arc<RwLock<HashSet<Mem
 
 pub struct Cluster {
     alive_nodes: Arc<RwLock<HashSet<Member>>>,
+    chitchat: Arc<Mutex<Chitchat>>,
     // dropping the handle leaves the cluster
     _chitchat_handle: ChitchatHandle,
 }
@@ -166,14 +167,28 @@ impl Cluster {
         let chitchat_handle = spawn_chitchat(config, key_values, &transport).await?;
         let chitchat = chitchat_handle.chitchat();
 
-        let alive_nodes = alive_nodes_updater(chitchat);
+        let alive_nodes = alive_nodes_updater(chitchat.clone());
 
         Ok(Self {
             alive_nodes,
+            chitchat,
             _chitchat_handle: chitchat_handle,
         })
     }
 
+    /// Announce a new service for this node, e.g. when a standby is promoted.
+    /// The other members see the change after the next gossip round.
+    pub async fn set_service(&self, service: &Service) -> Result<()> {
+        let service = serde_json::to_string(service)?;
+        self.chitchat
+            .lock()
+            .await
+            .self_node_state()
+            .set(SERVICE_KEY, service);
+
+        Ok(())
+    }
+
     pub async fn members(&self) -> Vec<Member> {
         let lock = self.alive_nodes.read().await;
         let mut res = Vec::with_capacity(lock.len());
@@ -14,7 +14,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::net::SocketAddr;
+use std::{fmt::Display, net::SocketAddr, str::FromStr};
+
+use chrono::{Datelike, NaiveDate};
 
 use crate::config::WebgraphGranularity;
 
@@ -56,6 +58,68 @@ impl From<ShardId> for u64 {
     }
 }
 
+/// The day a frozen snapshot of the index was taken. It is written as `YYYY-MM-DD`
+/// in configs and queries.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    Debug,
+    PartialOrd,
+    Ord,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct SnapshotDate {
+    /// Days since the first of January in year 1.
+    days: i32,
+}
+
+impl SnapshotDate {
+    pub fn new(date: NaiveDate) -> Self {
+        Self {
+            days: date.num_days_from_ce(),
+        }
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        NaiveDate::from_num_days_from_ce_opt(self.days).expect("snapshot dates are valid dates")
+    }
+}
+
+impl Display for SnapshotDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.date().format("%Y-%m-%d"))
+    }
+}
+
+impl FromStr for SnapshotDate {
+    type Err = chrono::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map(Self::new)
+    }
+}
+
+impl TryFrom<String> for SnapshotDate {
+    type Error = chrono::ParseError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<SnapshotDate> for String {
+    fn from(date: SnapshotDate) -> String {
+        date.to_string()
+    }
+}
+
 #[derive(
     serde::Serialize,
     serde::Deserialize,
@@ -72,6 +136,19 @@ pub enum Service {
         host: SocketAddr,
         shard: ShardId,
     },
+    /// A searcher that replicates the index of an active searcher for the shard
+    /// and does not serve queries until it is promoted.
+    StandbySearcher {
+        host: SocketAddr,
+        shard: ShardId,
+    },
+    /// A searcher for the shard of a frozen index snapshot. It only serves the
+    /// queries that ask for the snapshot of its date.
+    SnapshotSearcher {
+        host: SocketAddr,
+        shard: ShardId,
+        date: SnapshotDate,
+    },
     EntitySearcher {
         host: SocketAddr,
     },
@@ -112,3 +189,23 @@ pub struct Member {
     pub id: String,
     pub service: Service,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_dates() {
+        let date: SnapshotDate = "2024-03-01".parse().unwrap();
+
+        assert_eq!(date.to_string(), "2024-03-01");
+        assert_eq!(date.date(), NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        assert!(date < "2024-03-02".parse().unwrap());
+        assert!("2024-13-01".parse::<SnapshotDate>().is_err());
+        assert!("01-03-2024".parse::<SnapshotDate>().is_err());
+
+        let json = serde_json::to_string(&date).unwrap();
+        assert_eq!(json, r#""2024-03-01""#);
+        assert_eq!(serde_json::from_str::<SnapshotDate>(&json).unwrap(), date);
+    }
+}
@@ -332,6 +332,10 @@ where
     pub fn new(id: Id, replicas: ReplicatedClient<S>) -> Self {
         Self { replicas, id }
     }
+
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
 }
 
 pub struct ShardedClient<S: sonic::service::Service, Id: ShardIdentifier> {
@@ -351,6 +355,15 @@ where
         self.shards.is_empty()
     }
 
+    /// The ids of the shards that are selected by the selector.
+    pub fn selected_shards<Sel: ShardSelector<S, Id>>(&self, selector: &Sel) -> Vec<Id> {
+        selector
+            .select(&self.shards)
+            .into_iter()
+            .map(|shard| shard.id.clone())
+            .collect()
+    }
+
     async fn send_single<Req, Sel>(
         &self,
         req: Req,
@@ -48,9 +48,14 @@ pub struct Server<S: Service> {
 
 impl<S: Service> Server<S> {
     pub async fn bind(service: S, addr: impl ToSocketAddrs) -> Result<Self> {
+        Self::bind_shared(Arc::new(service), addr).await
+    }
+
+    /// Bind a service that is also used outside of the server, e.g. by background tasks.
+    pub async fn bind_shared(service: Arc<S>, addr: impl ToSocketAddrs) -> Result<Self> {
         Ok(Server {
             inner: super::Server::bind(addr).await?,
-            service: Arc::new(service),
+            service,
         })
     }
     pub async fn accept(&self) -> Result<()> {
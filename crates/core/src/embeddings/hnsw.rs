@@ -0,0 +1,248 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Hierarchical navigable small world graph (Malkov & Yashunin, 2016).
+//! Each node is assigned a random top layer, and the graph of each layer connects
+//! every node to its nearest neighbours in that layer. A search starts in the
+//! sparse top layer and greedily descends towards the query.
+
+use std::collections::{BinaryHeap, HashSet};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::Vectors;
+
+#[derive(Debug, Clone, Copy, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct HnswParams {
+    /// Number of neighbours of each node in the layers above the bottom layer.
+    /// The bottom layer has twice as many.
+    pub m: usize,
+    /// Size of the candidate list when a node is inserted.
+    pub ef_construction: usize,
+    /// Size of the candidate list when searching.
+    pub ef_search: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    pub distance: f32,
+    pub id: u32,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct Hnsw {
+    params: HnswParams,
+    /// `neighbours[node][layer]` are the neighbours of the node in the layer.
+    neighbours: Vec<Vec<Vec<u32>>>,
+    entry_point: Option<u32>,
+}
+
+impl Hnsw {
+    pub fn new(params: HnswParams) -> Self {
+        Self {
+            params,
+            neighbours: Vec::new(),
+            entry_point: None,
+        }
+    }
+
+    pub fn params(&self) -> &HnswParams {
+        &self.params
+    }
+
+    fn max_neighbours(&self, layer: usize) -> usize {
+        if layer == 0 {
+            2 * self.params.m
+        } else {
+            self.params.m
+        }
+    }
+
+    fn top_layer(&self, node: u32) -> usize {
+        self.neighbours[node as usize].len() - 1
+    }
+
+    /// The layer is derived from the id, so rebuilding the graph
+    /// from the same vectors gives the same graph.
+    fn random_layer(&self, id: u32) -> usize {
+        let ml = 1.0 / (self.params.m.max(2) as f64).ln();
+        let uniform: f64 = StdRng::seed_from_u64(id as u64).gen_range(f64::EPSILON..1.0);
+
+        (-uniform.ln() * ml).floor() as usize
+    }
+
+    /// Nodes closest to the query in a single layer, sorted by distance.
+    fn search_layer(
+        &self,
+        vectors: &Vectors,
+        query: &[f32],
+        entry_points: &[u32],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<u32> = entry_points.iter().copied().collect();
+
+        // min-heap of nodes to expand and max-heap of the best nodes found so far
+        let mut candidates = BinaryHeap::new();
+        let mut best = BinaryHeap::new();
+
+        for &id in entry_points {
+            let candidate = Candidate {
+                distance: vectors.distance(id, query),
+                id,
+            };
+            candidates.push(std::cmp::Reverse(candidate));
+            best.push(candidate);
+        }
+
+        while let Some(std::cmp::Reverse(current)) = candidates.pop() {
+            let furthest = best.peek().map(|c: &Candidate| c.distance).unwrap();
+            if current.distance > furthest && best.len() >= ef {
+                break;
+            }
+
+            for &neighbour in &self.neighbours[current.id as usize][layer] {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+
+                let candidate = Candidate {
+                    distance: vectors.distance(neighbour, query),
+                    id: neighbour,
+                };
+
+                let furthest = best.peek().map(|c: &Candidate| c.distance).unwrap();
+                if best.len() < ef || candidate.distance < furthest {
+                    candidates.push(std::cmp::Reverse(candidate));
+                    best.push(candidate);
+
+                    if best.len() > ef {
+                        best.pop();
+                    }
+                }
+            }
+        }
+
+        best.into_sorted_vec()
+    }
+
+    /// Walk greedily from the entry point down to `layer`.
+    fn descend(&self, vectors: &Vectors, query: &[f32], layer: usize) -> Option<u32> {
+        let mut entry_point = self.entry_point?;
+
+        for l in (layer + 1..=self.top_layer(entry_point)).rev() {
+            entry_point = self.search_layer(vectors, query, &[entry_point], 1, l)[0].id;
+        }
+
+        Some(entry_point)
+    }
+
+    /// Keep the `max` neighbours closest to the node.
+    fn prune(&mut self, vectors: &Vectors, node: u32, layer: usize, max: usize) {
+        let query = vectors.get(node);
+
+        let mut neighbours: Vec<_> = self.neighbours[node as usize][layer]
+            .iter()
+            .map(|&id| Candidate {
+                distance: vectors.distance(id, query),
+                id,
+            })
+            .collect();
+        neighbours.sort();
+        neighbours.truncate(max);
+
+        self.neighbours[node as usize][layer] = neighbours.into_iter().map(|c| c.id).collect();
+    }
+
+    /// Insert the vector with the given id. Ids must be inserted in order starting from 0.
+    pub fn insert(&mut self, vectors: &Vectors, id: u32) {
+        debug_assert_eq!(id as usize, self.neighbours.len());
+
+        let layer = self.random_layer(id);
+        self.neighbours.push(vec![Vec::new(); layer + 1]);
+
+        let query = vectors.get(id);
+
+        let Some(entry_point) = self.descend(vectors, query, layer) else {
+            self.entry_point = Some(id);
+            return;
+        };
+
+        let top_layer = self.top_layer(entry_point);
+        let mut entry_points = vec![entry_point];
+
+        for l in (0..=layer.min(top_layer)).rev() {
+            let candidates = self.search_layer(
+                vectors,
+                query,
+                &entry_points,
+                self.params.ef_construction,
+                l,
+            );
+            let max = self.max_neighbours(l);
+
+            self.neighbours[id as usize][l] = candidates.iter().take(max).map(|c| c.id).collect();
+
+            for c in candidates.iter().take(max) {
+                self.neighbours[c.id as usize][l].push(id);
+
+                if self.neighbours[c.id as usize][l].len() > max {
+                    self.prune(vectors, c.id, l, max);
+                }
+            }
+
+            entry_points = candidates.into_iter().map(|c| c.id).collect();
+        }
+
+        if layer > top_layer {
+            self.entry_point = Some(id);
+        }
+    }
+
+    /// The `ef` nodes closest to the query, sorted by distance.
+    pub fn search(&self, vectors: &Vectors, query: &[f32], ef: usize) -> Vec<Candidate> {
+        match self.descend(vectors, query, 0) {
+            Some(entry_point) => self.search_layer(vectors, query, &[entry_point], ef, 0),
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,494 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Store for dense vectors with approximate nearest neighbour search.
+//! The vectors are normalized when they are inserted, so the similarity
+//! between two vectors is their cosine similarity.
+//!
+//! The store is kept in memory and written to disk on [`EmbeddingStore::commit`].
+//! The vectors and keys are only appended to their files, so a commit writes the
+//! new vectors and the graph, which is replaced as a whole.
+//!
+//! The entity index uses the store to find related entities.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    hash::Hash,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::Result;
+
+mod hnsw;
+
+use hnsw::Hnsw;
+pub use hnsw::HnswParams;
+
+const GRAPH_FILE: &str = "graph.bin";
+const VECTORS_FILE: &str = "vectors.bin";
+const KEYS_FILE: &str = "keys.bin";
+
+/// Normalized vectors stored back to back.
+#[derive(Debug)]
+pub struct Vectors {
+    dim: usize,
+    data: Vec<f32>,
+}
+
+impl Vectors {
+    fn new(dim: usize) -> Self {
+        Self {
+            dim,
+            data: Vec::new(),
+        }
+    }
+
+    fn get(&self, id: u32) -> &[f32] {
+        let start = id as usize * self.dim;
+        &self.data[start..start + self.dim]
+    }
+
+    fn push(&mut self, vector: &[f32]) -> u32 {
+        let id = (self.data.len() / self.dim) as u32;
+        self.data.extend_from_slice(vector);
+        id
+    }
+
+    fn distance(&self, id: u32, query: &[f32]) -> f32 {
+        1.0 - dot(self.get(id), query)
+    }
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+fn normalize(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = dot(vector, vector).sqrt();
+
+    if norm == 0.0 || !norm.is_finite() {
+        return None;
+    }
+
+    Some(vector.iter().map(|x| x / norm).collect())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbour<K> {
+    pub key: K,
+    /// Cosine similarity to the query.
+    pub similarity: f32,
+}
+
+/// Everything but the vectors and keys, which are appended to their own files.
+/// Only the first `num_vectors` vectors and the first `keys_len` bytes of keys
+/// belong to the store, so data from an interrupted commit is ignored.
+#[derive(bincode::Decode)]
+struct StoredGraph {
+    dim: usize,
+    num_vectors: usize,
+    keys_len: u64,
+    removed: HashSet<u32>,
+    hnsw: Hnsw,
+}
+
+/// Encodes to the same bytes as [`StoredGraph`] without copying the graph.
+#[derive(bincode::Encode)]
+struct StoredGraphRef<'a> {
+    dim: usize,
+    num_vectors: usize,
+    keys_len: u64,
+    removed: &'a HashSet<u32>,
+    hnsw: &'a Hnsw,
+}
+
+/// Embeddings with a key of type `K`, e.g. an entity name or a node id.
+/// Inserting a key that is already in the store replaces its vector.
+pub struct EmbeddingStore<K> {
+    path: PathBuf,
+    keys: Vec<K>,
+    ids: HashMap<K, u32>,
+    vectors: Vectors,
+    /// Ids of replaced vectors. They are still part of the graph so the graph stays
+    /// connected, but they are never returned from a search.
+    removed: HashSet<u32>,
+    hnsw: Hnsw,
+    /// Number of vectors in the vectors file.
+    num_committed: usize,
+    /// Length of the keys file.
+    keys_len: u64,
+}
+
+impl<K> EmbeddingStore<K>
+where
+    K: Clone + Eq + Hash + bincode::Encode + bincode::Decode,
+{
+    pub fn open<P: AsRef<Path>>(path: P, dim: usize) -> Result<Self> {
+        Self::open_with_params(path, dim, HnswParams::default())
+    }
+
+    /// The params are only used when a new store is created. An existing
+    /// store keeps the params it was created with.
+    pub fn open_with_params<P: AsRef<Path>>(
+        path: P,
+        dim: usize,
+        params: HnswParams,
+    ) -> Result<Self> {
+        if dim == 0 {
+            bail!("embeddings must have at least one dimension");
+        }
+
+        let path = path.as_ref().to_path_buf();
+        std::fs::create_dir_all(&path)?;
+
+        let Some(store) = Self::load(&path)? else {
+            return Ok(Self {
+                path,
+                keys: Vec::new(),
+                ids: HashMap::new(),
+                vectors: Vectors::new(dim),
+                removed: HashSet::new(),
+                hnsw: Hnsw::new(params),
+                num_committed: 0,
+                keys_len: 0,
+            });
+        };
+
+        if store.dim() != dim {
+            bail!(
+                "store has {} dimensions, but {} was expected",
+                store.dim(),
+                dim
+            );
+        }
+
+        Ok(store)
+    }
+
+    /// Open a store that has been committed before, with the dimensions it was created with.
+    pub fn open_existing<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+
+        Self::load(path)?.ok_or_else(|| anyhow!("no embeddings in {}", path.display()))
+    }
+
+    fn load(path: &Path) -> Result<Option<Self>> {
+        let file = path.join(GRAPH_FILE);
+
+        if !file.exists() {
+            return Ok(None);
+        }
+
+        let graph: StoredGraph = bincode::decode_from_std_read(
+            &mut BufReader::new(File::open(&file)?),
+            bincode::config::standard(),
+        )?;
+
+        let mut bytes = vec![0; graph.num_vectors * graph.dim * std::mem::size_of::<f32>()];
+        File::open(path.join(VECTORS_FILE))?.read_exact(&mut bytes)?;
+
+        let mut vectors = Vectors::new(graph.dim);
+        vectors.data = bytes
+            .chunks_exact(std::mem::size_of::<f32>())
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+
+        let mut reader = BufReader::new(File::open(path.join(KEYS_FILE))?.take(graph.keys_len));
+        let keys = (0..graph.num_vectors)
+            .map(|_| bincode::decode_from_std_read(&mut reader, bincode::config::standard()))
+            .collect::<Result<Vec<K>, _>>()?;
+
+        let ids = keys
+            .iter()
+            .enumerate()
+            .filter(|(id, _)| !graph.removed.contains(&(*id as u32)))
+            .map(|(id, key)| (key.clone(), id as u32))
+            .collect();
+
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            keys,
+            ids,
+            vectors,
+            removed: graph.removed,
+            hnsw: graph.hnsw,
+            num_committed: graph.num_vectors,
+            keys_len: graph.keys_len,
+        }))
+    }
+
+    pub fn dim(&self) -> usize {
+        self.vectors.dim
+    }
+
+    /// Number of keys in the store.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.ids.contains_key(key)
+    }
+
+    /// The normalized vector of the key.
+    pub fn get(&self, key: &K) -> Option<&[f32]> {
+        self.ids.get(key).map(|id| self.vectors.get(*id))
+    }
+
+    pub fn insert(&mut self, key: K, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.dim() {
+            bail!(
+                "expected a vector with {} dimensions, got {}",
+                self.dim(),
+                vector.len()
+            );
+        }
+
+        let vector = normalize(vector).ok_or_else(|| anyhow!("cannot normalize vector"))?;
+
+        let id = self.vectors.push(&vector);
+        self.keys.push(key.clone());
+        self.hnsw.insert(&self.vectors, id);
+
+        if let Some(old) = self.ids.insert(key, id) {
+            self.removed.insert(old);
+        }
+
+        Ok(())
+    }
+
+    /// The `k` keys with the most similar vectors, sorted by decreasing similarity.
+    pub fn similar(&self, vector: &[f32], k: usize) -> Vec<Neighbour<K>> {
+        if k == 0 || vector.len() != self.dim() {
+            return Vec::new();
+        }
+
+        let Some(query) = normalize(vector) else {
+            return Vec::new();
+        };
+
+        let ef = self.hnsw.params().ef_search.max(k) + self.removed.len().min(k);
+
+        self.hnsw
+            .search(&self.vectors, &query, ef)
+            .into_iter()
+            .filter(|candidate| !self.removed.contains(&candidate.id))
+            .take(k)
+            .map(|candidate| Neighbour {
+                key: self.keys[candidate.id as usize].clone(),
+                similarity: 1.0 - candidate.distance,
+            })
+            .collect()
+    }
+
+    /// Keys most similar to the vector of `key`, excluding the key itself.
+    pub fn similar_to_key(&self, key: &K, k: usize) -> Vec<Neighbour<K>> {
+        match self.get(key) {
+            Some(vector) => self
+                .similar(vector, k + 1)
+                .into_iter()
+                .filter(|neighbour| &neighbour.key != key)
+                .take(k)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Append the vectors and keys inserted since the last commit and write the graph.
+    /// The previous version stays readable until the new graph file replaces it.
+    pub fn commit(&mut self) -> Result<()> {
+        let dim = self.dim();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(self.path.join(VECTORS_FILE))?;
+        file.set_len((self.num_committed * dim * std::mem::size_of::<f32>()) as u64)?;
+        file.seek(SeekFrom::End(0))?;
+
+        let mut writer = BufWriter::new(file);
+        for x in &self.vectors.data[self.num_committed * dim..] {
+            writer.write_all(&x.to_le_bytes())?;
+        }
+        writer.into_inner()?.sync_all()?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(self.path.join(KEYS_FILE))?;
+        file.set_len(self.keys_len)?;
+        file.seek(SeekFrom::End(0))?;
+
+        let mut writer = BufWriter::new(file);
+        for key in &self.keys[self.num_committed..] {
+            bincode::encode_into_std_write(key, &mut writer, bincode::config::standard())?;
+        }
+        let file = writer.into_inner()?;
+        file.sync_all()?;
+        let keys_len = file.metadata()?.len();
+
+        let graph = StoredGraphRef {
+            dim,
+            num_vectors: self.keys.len(),
+            keys_len,
+            removed: &self.removed,
+            hnsw: &self.hnsw,
+        };
+
+        let tmp = self.path.join(format!("{GRAPH_FILE}.tmp"));
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        bincode::encode_into_std_write(&graph, &mut writer, bincode::config::standard())?;
+        writer.into_inner()?.sync_all()?;
+
+        std::fs::rename(tmp, self.path.join(GRAPH_FILE))?;
+
+        self.num_committed = self.keys.len();
+        self.keys_len = keys_len;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_vectors(n: usize, dim: usize) -> Vec<Vec<f32>> {
+        let mut rng = StdRng::seed_from_u64(42);
+
+        (0..n)
+            .map(|_| (0..dim).map(|_| rng.gen_range(-1.0..1.0)).collect())
+            .collect()
+    }
+
+    fn brute_force(vectors: &[Vec<f32>], query: &[f32], k: usize) -> Vec<u64> {
+        let query = normalize(query).unwrap();
+
+        let mut scored: Vec<_> = vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (dot(&normalize(v).unwrap(), &query), i as u64))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        scored.into_iter().take(k).map(|(_, i)| i).collect()
+    }
+
+    #[test]
+    fn recall() {
+        let vectors = random_vectors(2_000, 16);
+        let mut store = EmbeddingStore::open(crate::gen_temp_path(), 16).unwrap();
+
+        for (i, v) in vectors.iter().enumerate() {
+            store.insert(i as u64, v).unwrap();
+        }
+
+        let queries = random_vectors(50, 16);
+        let mut found = 0;
+
+        for query in &queries {
+            let expected: HashSet<_> = brute_force(&vectors, query, 10).into_iter().collect();
+            let res = store.similar(query, 10);
+
+            assert_eq!(res.len(), 10);
+            assert!(res.windows(2).all(|w| w[0].similarity >= w[1].similarity));
+
+            found += res.iter().filter(|n| expected.contains(&n.key)).count();
+        }
+
+        let recall = found as f64 / (queries.len() * 10) as f64;
+        assert!(recall > 0.9, "recall {recall}");
+    }
+
+    #[test]
+    fn persistence() {
+        let path = crate::gen_temp_path();
+        let vectors = random_vectors(100, 8);
+
+        let mut store = EmbeddingStore::open(&path, 8).unwrap();
+        for (i, v) in vectors.iter().enumerate().take(50) {
+            store.insert(format!("key{i}"), v).unwrap();
+        }
+        store.commit().unwrap();
+
+        let expected = store.similar(&vectors[0], 5);
+
+        // vectors inserted after the commit are lost
+        store.insert("extra".to_string(), &vectors[99]).unwrap();
+        drop(store);
+
+        let mut store: EmbeddingStore<String> = EmbeddingStore::open(&path, 8).unwrap();
+        assert_eq!(store.len(), 50);
+        assert!(!store.contains(&"extra".to_string()));
+        assert_eq!(store.similar(&vectors[0], 5), expected);
+
+        // inserts continue on the reopened store
+        for (i, v) in vectors.iter().enumerate().skip(50) {
+            store.insert(format!("key{i}"), v).unwrap();
+        }
+        assert_eq!(store.len(), 100);
+        assert_eq!(store.similar(&vectors[80], 1)[0].key, "key80");
+
+        // the second commit appends to the files of the first
+        store.insert("key0".to_string(), &vectors[99]).unwrap();
+        store.commit().unwrap();
+        drop(store);
+
+        let store: EmbeddingStore<String> = EmbeddingStore::open_existing(&path).unwrap();
+        assert_eq!(store.dim(), 8);
+        assert_eq!(store.len(), 100);
+        assert_eq!(
+            store.get(&"key0".to_string()),
+            store.get(&"key99".to_string())
+        );
+        assert_eq!(store.similar(&vectors[80], 1)[0].key, "key80");
+
+        assert!(EmbeddingStore::<String>::open(&path, 4).is_err());
+        assert!(EmbeddingStore::<String>::open_existing(crate::gen_temp_path()).is_err());
+    }
+
+    #[test]
+    fn replace() {
+        let mut store: EmbeddingStore<u64> =
+            EmbeddingStore::open(crate::gen_temp_path(), 2).unwrap();
+
+        store.insert(1, &[1.0, 0.0]).unwrap();
+        store.insert(2, &[0.0, 1.0]).unwrap();
+        store.insert(1, &[0.0, 2.0]).unwrap();
+
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(&1), Some(&[0.0, 1.0][..]));
+
+        let res = store.similar(&[1.0, 0.0], 3);
+        assert_eq!(res.len(), 2);
+        assert!(res.iter().all(|n| n.similarity.abs() < 1e-6));
+
+        assert_eq!(store.similar_to_key(&1, 1)[0].key, 2);
+
+        assert!(store.insert(3, &[0.0, 0.0]).is_err());
+        assert!(store.insert(3, &[1.0]).is_err());
+    }
+}
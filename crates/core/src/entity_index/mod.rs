@@ -14,7 +14,12 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{collections::HashSet, fs, path::Path, sync::Arc};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use base64::{prelude::BASE64_STANDARD as BASE64_ENGINE, Engine};
 
@@ -27,6 +32,7 @@ use tantivy::{
 };
 
 use crate::{
+    embeddings::EmbeddingStore,
     image_store::{EntityImageStore, Image, ImageStore},
     tokenizer::Normal,
     Result,
@@ -35,6 +41,9 @@ use crate::{
 use self::entity::{Entity, Link, Span};
 pub(crate) mod entity;
 
+/// Folder with the embeddings of the entities, keyed by title.
+const EMBEDDINGS_DIR: &str = "embeddings";
+
 fn schema() -> Schema {
     let mut builder = tantivy::schema::Schema::builder();
 
@@ -127,7 +136,11 @@ pub struct EntityMatch {
 }
 
 pub struct EntityIndex {
+    path: PathBuf,
     image_store: EntityImageStore,
+    /// Only available if the index was built with a dual encoder. The related
+    /// entities are found by text similarity otherwise.
+    embeddings: Option<EmbeddingStore<String>>,
     writer: Option<IndexWriter>,
     reader: IndexReader,
     tv_index: tantivy::Index,
@@ -165,8 +178,16 @@ impl EntityIndex {
 
         let reader = tantivy_index.reader()?;
 
+        let embeddings_path = path.as_ref().join(EMBEDDINGS_DIR);
+        let embeddings = embeddings_path
+            .exists()
+            .then(|| EmbeddingStore::open_existing(&embeddings_path))
+            .transpose()?;
+
         Ok(Self {
+            path: path.as_ref().to_path_buf(),
             image_store,
+            embeddings,
             writer: None,
             reader,
             tv_index: tantivy_index,
@@ -180,6 +201,20 @@ impl EntityIndex {
         self.image_store.prepare_writer();
     }
 
+    /// Store embeddings with `dim` dimensions for the inserted entities.
+    pub fn prepare_embeddings(&mut self, dim: usize) -> Result<()> {
+        self.embeddings = Some(EmbeddingStore::open(self.path.join(EMBEDDINGS_DIR), dim)?);
+
+        Ok(())
+    }
+
+    pub fn insert_embedding(&mut self, title: String, embedding: &[f32]) -> Result<()> {
+        self.embeddings
+            .as_mut()
+            .expect("embeddings not prepared")
+            .insert(title, embedding)
+    }
+
     fn best_info(&self, info: Vec<(String, Span)>) -> Vec<(String, Span)> {
         info.into_iter().take(5).collect()
     }
@@ -200,11 +235,111 @@ impl EntityIndex {
             .commit()
             .unwrap();
         self.image_store.flush();
+
+        if let Some(embeddings) = self.embeddings.as_mut() {
+            embeddings.commit().unwrap();
+        }
+
         self.reader.reload().unwrap();
     }
 
-    fn related_entities(&self, doc: DocAddress, image_id: Option<&String>) -> Vec<EntityMatch> {
+    /// The entity with exactly the title.
+    fn doc_by_title(&self, searcher: &Searcher, title: &str) -> Option<DocAddress> {
+        let field = self.schema.get_field("title").unwrap();
+        let mut tokenizer = self.tv_index.tokenizer_for_field(field).ok()?;
+
+        let mut terms = Vec::new();
+        let mut stream = tokenizer.token_stream(title);
+        while let Some(token) = stream.next() {
+            terms.push((
+                Occur::Must,
+                TermQuery::new(
+                    Term::from_field_text(field, &token.text),
+                    IndexRecordOption::Basic,
+                )
+                .box_clone(),
+            ));
+        }
+
+        if terms.is_empty() {
+            return None;
+        }
+
+        searcher
+            .search(&BooleanQuery::from(terms), &TopDocs::with_limit(10))
+            .ok()?
+            .into_iter()
+            .map(|(_, doc_address)| doc_address)
+            .find(|doc_address| {
+                searcher
+                    .doc::<TantivyDocument>(*doc_address)
+                    .ok()
+                    .and_then(|doc| match doc.get_first(field) {
+                        Some(tantivy::schema::OwnedValue::Str(string)) => Some(string == title),
+                        _ => None,
+                    })
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Entities with the most similar embeddings, or `None` if the entity has no embedding.
+    fn similar_entities(&self, searcher: &Searcher, title: &str) -> Option<Vec<EntityMatch>> {
+        let embeddings = self.embeddings.as_ref()?;
+        let title = title.to_string();
+
+        if !embeddings.contains(&title) {
+            return None;
+        }
+
+        Some(
+            embeddings
+                .similar_to_key(&title, 100)
+                .into_iter()
+                .filter_map(|neighbour| {
+                    let doc_address = self.doc_by_title(searcher, &neighbour.key)?;
+                    let entity =
+                        self.retrieve_stored_entity(searcher, doc_address, false, false, false);
+
+                    Some(EntityMatch {
+                        entity,
+                        score: neighbour.similarity,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn related_entities(
+        &self,
+        doc: DocAddress,
+        title: &str,
+        image_id: Option<&String>,
+    ) -> Vec<EntityMatch> {
         let searcher = self.reader.searcher();
+
+        let mut images = HashSet::new();
+
+        if let Some(image_id) = image_id {
+            images.insert(image_id.clone());
+        }
+
+        // only entities with a new image are shown
+        let unique_image = |entity_match: &EntityMatch| {
+            if let Some(image_id) = &entity_match.entity.image_id {
+                let res = !images.contains(image_id);
+
+                images.insert(image_id.clone());
+
+                res
+            } else {
+                false
+            }
+        };
+
+        if let Some(similar) = self.similar_entities(&searcher, title) {
+            return similar.into_iter().filter(unique_image).take(4).collect();
+        }
+
         let more_like_this_query = MoreLikeThisQuery::builder()
             .with_min_doc_frequency(1)
             .with_min_term_frequency(1)
@@ -222,12 +357,6 @@ impl EntityIndex {
             (Occur::Must, image_query.box_clone()),
         ]);
 
-        let mut images = HashSet::new();
-
-        if let Some(image_id) = image_id {
-            images.insert(image_id.clone());
-        }
-
         match searcher.search(&query, &TopDocs::with_limit(100)) {
             Ok(result) => result
                 .into_iter()
@@ -238,17 +367,7 @@ impl EntityIndex {
 
                     EntityMatch { entity, score }
                 })
-                .filter(|entity_match| {
-                    if let Some(image_id) = &entity_match.entity.image_id {
-                        let res = !images.contains(image_id);
-
-                        images.insert(image_id.clone());
-
-                        res
-                    } else {
-                        false
-                    }
-                })
+                .filter(unique_image)
                 .take(4)
                 .collect(),
             Err(_) => Vec::new(),
@@ -379,7 +498,7 @@ impl EntityIndex {
         };
 
         let related_entities = if get_related {
-            self.related_entities(doc_address, image_id.as_ref())
+            self.related_entities(doc_address, &title, image_id.as_ref())
         } else {
             Vec::new()
         };
@@ -490,4 +609,46 @@ mod tests {
             .retrieve_image(&index.search("ashes").unwrap().entity.image_id.unwrap())
             .is_some());
     }
+
+    #[test]
+    fn related_by_embeddings() {
+        let path = crate::gen_temp_path();
+        let mut index = EntityIndex::open(&path).unwrap();
+        index.prepare_writer();
+        index.prepare_embeddings(2).unwrap();
+
+        for (title, embedding) in [
+            ("rust", [1.0, 0.1]),
+            ("cargo", [1.0, 0.2]),
+            ("jaguar", [0.0, 1.0]),
+        ] {
+            index.insert(Entity {
+                article_url: String::new(),
+                is_disambiguation: false,
+                title: title.to_string(),
+                page_abstract: Span {
+                    text: String::new(),
+                    links: Vec::new(),
+                },
+                info: Vec::new(),
+                image: None,
+            });
+            index
+                .insert_embedding(title.to_string(), &embedding)
+                .unwrap();
+        }
+
+        index.commit();
+        drop(index);
+
+        let index = EntityIndex::open(&path).unwrap();
+        let searcher = index.reader.searcher();
+
+        let similar = index.similar_entities(&searcher, "rust").unwrap();
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0].entity.title, "cargo");
+        assert_eq!(similar[1].entity.title, "jaguar");
+
+        assert!(index.similar_entities(&searcher, "python").is_none());
+    }
 }
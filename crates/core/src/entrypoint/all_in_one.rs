@@ -0,0 +1,409 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Run the api, a search server, the live index and the host and page webgraphs
+//! in a single process. This is meant for self-hosting on a single machine.
+//!
+//! The data directory is populated on the first run. The index and webgraphs are built
+//! from the given warc file, or from a few bundled pages if no warc file is given.
+//! The lite results page is used as the frontend.
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
+
+use axum::{response::Redirect, routing::get, Router};
+use tracing::info;
+
+use crate::{
+    config::{
+        defaults, AllInOneConfig, ApiConfig, CollectorConfig, IndexingGraphConfig, LiveIndexConfig,
+        SearchServerConfig, SnippetConfig, UserAgent, WebgraphGranularity, WebgraphServerConfig,
+        WidgetsConfig,
+    },
+    distributed::member::ShardId,
+    feed::scheduler::Split,
+    warc::{self, WarcRecord, WarcWriter},
+    Result,
+};
+
+use super::file_indexer;
+
+const SEED_PAGES: [(&str, &str); 3] = [
+    (
+        "https://stract.com/about",
+        include_str!("../../seed/about.html"),
+    ),
+    (
+        "https://stract.com/webmasters",
+        include_str!("../../seed/webmasters.html"),
+    ),
+    (
+        "https://github.com/StractOrg/stract",
+        include_str!("../../seed/self_hosting.html"),
+    ),
+];
+
+/// The live index crawls the feeds in its split. The split is empty until the user adds feeds to it.
+const EMPTY_SPLIT: &str = r#"{"id": "00000000-0000-0000-0000-000000000000", "feeds": []}"#;
+
+const USER_AGENT: &str =
+    "Mozilla/5.0 (compatible; StractBot/0.1; open source search engine; +https://stract.com/webmasters)";
+
+struct DataDir {
+    root: PathBuf,
+}
+
+impl DataDir {
+    fn path(&self, name: &str) -> String {
+        self.root.join(name).to_str().unwrap().to_string()
+    }
+}
+
+/// The internal services listen on localhost, on the ports following the api port.
+struct Addresses {
+    api: SocketAddr,
+    base_port: u16,
+}
+
+impl Addresses {
+    fn local(&self, offset: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), self.base_port + offset)
+    }
+
+    fn prometheus(&self) -> SocketAddr {
+        self.local(1)
+    }
+
+    fn search_server(&self) -> SocketAddr {
+        self.local(2)
+    }
+
+    fn host_webgraph(&self) -> SocketAddr {
+        self.local(3)
+    }
+
+    fn page_webgraph(&self) -> SocketAddr {
+        self.local(4)
+    }
+
+    fn live_index(&self) -> SocketAddr {
+        self.local(5)
+    }
+
+    fn api_gossip(&self) -> SocketAddr {
+        self.local(6)
+    }
+
+    fn search_server_gossip(&self) -> SocketAddr {
+        self.local(7)
+    }
+
+    fn host_webgraph_gossip(&self) -> SocketAddr {
+        self.local(8)
+    }
+
+    fn page_webgraph_gossip(&self) -> SocketAddr {
+        self.local(9)
+    }
+
+    fn live_index_gossip(&self) -> SocketAddr {
+        self.local(10)
+    }
+}
+
+fn write_seed_warc(path: &Path) -> Result<()> {
+    let mut writer = WarcWriter::new();
+
+    for (url, body) in SEED_PAGES {
+        writer.write(&WarcRecord {
+            request: warc::Request {
+                url: url.to_string(),
+            },
+            response: warc::Response {
+                body: body.to_string(),
+                payload_type: Some(warc::PayloadType::Html),
+            },
+            metadata: warc::Metadata {
+                fetch_time_ms: 0,
+                redirects: Vec::new(),
+            },
+        })?;
+    }
+
+    std::fs::write(path, writer.finish()?)?;
+
+    Ok(())
+}
+
+/// Create everything that is missing in the data directory.
+fn prepare(config: &AllInOneConfig, data: &DataDir) -> Result<()> {
+    std::fs::create_dir_all(&data.root)?;
+
+    if !Path::new(&data.path("index")).exists() {
+        let warc_path = match &config.warc_path {
+            Some(path) => path.clone(),
+            None => {
+                let path = data.path("seed.warc.gz");
+                write_seed_warc(Path::new(&path))?;
+                path
+            }
+        };
+
+        file_indexer::build_from_warc(&warc_path, &data.root)?;
+    }
+
+    let queries_path = data.path("queries.csv");
+    if !Path::new(&queries_path).exists() {
+        std::fs::write(&queries_path, "query\n")?;
+    }
+
+    let bangs_path = data.path("bangs.json");
+    if !Path::new(&bangs_path).exists() {
+        std::fs::write(&bangs_path, "[]")?;
+    }
+
+    std::fs::create_dir_all(data.path("live"))?;
+    let split_path = data.path("live/split.json");
+    if !Path::new(&split_path).exists() {
+        std::fs::write(&split_path, EMPTY_SPLIT)?;
+    }
+
+    Ok(())
+}
+
+fn search_server_config(data: &DataDir, addrs: &Addresses) -> SearchServerConfig {
+    SearchServerConfig {
+        cluster_id: "all_in_one_search".to_string(),
+        gossip_seed_nodes: Some(vec![addrs.api_gossip()]),
+        gossip_addr: addrs.search_server_gossip(),
+        shard: ShardId::new(0),
+        index_path: data.path("index"),
+        linear_model_path: None,
+        lambda_model_path: None,
+        dual_encoder_model_path: None,
+        host: addrs.search_server(),
+        collector: CollectorConfig::default(),
+        snippet: SnippetConfig::default(),
+        standby: None,
+        tiered_storage: None,
+        snapshot: None,
+    }
+}
+
+fn webgraph_server_config(
+    data: &DataDir,
+    addrs: &Addresses,
+    granularity: WebgraphGranularity,
+) -> WebgraphServerConfig {
+    let (name, host, gossip_addr) = match granularity {
+        WebgraphGranularity::Host => (
+            "webgraph_host",
+            addrs.host_webgraph(),
+            addrs.host_webgraph_gossip(),
+        ),
+        WebgraphGranularity::Page => (
+            "webgraph_page",
+            addrs.page_webgraph(),
+            addrs.page_webgraph_gossip(),
+        ),
+        WebgraphGranularity::Domain => unreachable!("all in one does not serve a domain graph"),
+    };
+
+    WebgraphServerConfig {
+        host,
+        shard: ShardId::new(0),
+        graph_path: data.path(name),
+        granularity,
+        lock_index_in_memory: false,
+        cache_bytes: None,
+        node_cache_size: None,
+        prometheus_host: None,
+        cluster_id: format!("all_in_one_{name}"),
+        gossip_seed_nodes: Some(vec![addrs.api_gossip()]),
+        gossip_addr,
+    }
+}
+
+fn live_index_config(data: &DataDir, addrs: &Addresses) -> Result<LiveIndexConfig> {
+    let split_path = data.path("live/split.json");
+    let split_id = Split::open(&split_path)?.id;
+
+    Ok(LiveIndexConfig {
+        split_path,
+        downloaded_db_path: data.path("live/downloaded"),
+        user_agent: UserAgent {
+            full: USER_AGENT.to_string(),
+            token: "StractBot".to_string(),
+        },
+        robots_txt_cache_sec: defaults::Crawler::robots_txt_cache_sec(),
+        politeness_factor: defaults::Crawler::politeness_factor(),
+        min_crawl_delay_ms: defaults::Crawler::min_crawl_delay_ms(),
+        max_crawl_delay_ms: defaults::Crawler::max_crawl_delay_ms(),
+        max_politeness_factor: defaults::Crawler::max_politeness_factor(),
+        max_url_slowdown_retry: defaults::Crawler::max_url_slowdown_retry(),
+        max_redirects: defaults::Crawler::max_redirects(),
+        timeout_seconds: 60,
+        host_centrality_store_path: data.path("centrality"),
+        page_webgraph: Some(IndexingGraphConfig::Local {
+            path: data.path("webgraph_page"),
+        }),
+        page_centrality_store_path: Some(data.path("centrality_page")),
+        safety_classifier_path: None,
+        host_centrality_threshold: None,
+        minimum_clean_words: None,
+        outlink_audit_path: None,
+        outlink_profile_path: None,
+        cloaking_audit_path: None,
+        host_about_path: None,
+        host_clusters_path: None,
+        canonical_index_path: None,
+        cluster_id: "all_in_one_live".to_string(),
+        gossip_seed_nodes: Some(vec![addrs.api_gossip()]),
+        gossip_addr: addrs.live_index_gossip(),
+        split_id,
+        index_path: data.path("live/index"),
+        linear_model_path: None,
+        lambda_model_path: None,
+        host: addrs.live_index(),
+        collector: CollectorConfig::default(),
+        snippet: SnippetConfig::default(),
+    })
+}
+
+fn api_config(data: &DataDir, addrs: &Addresses) -> ApiConfig {
+    ApiConfig {
+        queries_csv_path: data.path("queries.csv"),
+        host: addrs.api,
+        prometheus_host: addrs.prometheus(),
+        crossencoder_model_path: None,
+        lambda_model_path: None,
+        dual_encoder_model_path: None,
+        summarizer_path: None,
+        safety_classifier_path: None,
+        citation_verification: None,
+        host_about_path: None,
+        bangs_path: data.path("bangs.json"),
+        query_store_db_host: None,
+        cluster_id: "all_in_one_api".to_string(),
+        gossip_seed_nodes: Some(vec![
+            addrs.search_server_gossip(),
+            addrs.host_webgraph_gossip(),
+            addrs.page_webgraph_gossip(),
+            addrs.live_index_gossip(),
+        ]),
+        gossip_addr: addrs.api_gossip(),
+        max_similar_hosts: defaults::Api::max_similar_hosts(),
+        max_batch_queries: defaults::Api::max_batch_queries(),
+        spell_check: None,
+        thresholds: Default::default(),
+        widgets: WidgetsConfig {
+            calculator_exchange_rates_path: Some(data.path("exchange_rates.xml")),
+            ..Default::default()
+        },
+        collector: CollectorConfig::default(),
+        max_concurrent_searches: defaults::Api::max_concurrent_searches(),
+        threat_list: None,
+        slow_query_log: None,
+        stage_limits: Default::default(),
+        result_cache: None,
+        result_sets: None,
+        disable_outbound_requests: false,
+        region_defaults: Default::default(),
+        model_registry: None,
+        cross_encoder_accelerator: Default::default(),
+        dual_encoder_accelerator: Default::default(),
+        summarizer_accelerator: Default::default(),
+        admin_token: None,
+        autosuggest_filter: None,
+        autosuggest_ranking: Default::default(),
+    }
+}
+
+pub fn run(config: AllInOneConfig) -> Result<()> {
+    let data = DataDir {
+        root: PathBuf::from(&config.data_dir),
+    };
+
+    prepare(&config, &data)?;
+
+    let addrs = Addresses {
+        api: config.host,
+        base_port: config.host.port(),
+    };
+
+    let search_server = search_server_config(&data, &addrs);
+    let host_webgraph = webgraph_server_config(&data, &addrs, WebgraphGranularity::Host);
+    let page_webgraph = webgraph_server_config(&data, &addrs, WebgraphGranularity::Page);
+    let live_index = live_index_config(&data, &addrs)?;
+    let api = api_config(&data, &addrs);
+
+    // the lite results page is the frontend
+    let frontend = Router::new().route("/", get(|| async { Redirect::to("/lite") }));
+
+    info!("starting stract on http://{}", config.host);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(async {
+            tokio::try_join!(
+                super::search_server::run(search_server),
+                super::webgraph_server::run(host_webgraph),
+                super::webgraph_server::run(page_webgraph),
+                super::live_index::serve(live_index),
+                super::api::run_with_routes(api, frontend),
+            )
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_warc() {
+        let path = crate::gen_temp_path().join("seed.warc.gz");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        write_seed_warc(&path).unwrap();
+
+        let warc = warc::WarcFile::open(&path).unwrap();
+        let urls: Vec<_> = warc
+            .records()
+            .map(|record| record.unwrap().request.url)
+            .collect();
+
+        assert_eq!(
+            urls,
+            SEED_PAGES
+                .iter()
+                .map(|(url, _)| url.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn empty_split() {
+        let path = crate::gen_temp_path().join("split.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, EMPTY_SPLIT).unwrap();
+
+        assert!(Split::open(&path).unwrap().feeds.is_empty());
+    }
+}
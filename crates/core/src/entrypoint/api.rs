@@ -17,6 +17,7 @@
 use std::{future::IntoFuture, net::SocketAddr};
 
 use anyhow::Result;
+use axum::Router;
 use tokio::net::TcpListener;
 
 use crate::{
@@ -26,9 +27,19 @@ use crate::{
 };
 
 pub async fn run(config: config::ApiConfig) -> Result<()> {
+    run_with_routes(config, Router::new()).await
+}
+
+/// Run the api with additional routes merged into the api router.
+pub async fn run_with_routes(config: config::ApiConfig, routes: Router) -> Result<()> {
     let search_counter_success = crate::metrics::Counter::default();
     let search_counter_fail = crate::metrics::Counter::default();
     let explore_counter = crate::metrics::Counter::default();
+    let similar_sites_counter = crate::metrics::Counter::default();
+    let site_info_counter = crate::metrics::Counter::default();
+    let page_signals_counter = crate::metrics::Counter::default();
+    let result_cache_hits = crate::metrics::Counter::default();
+    let result_cache_misses = crate::metrics::Counter::default();
     let daily_active_users = user_count::UserCount::new()?;
 
     let mut registry = crate::metrics::PrometheusRegistry::default();
@@ -63,6 +74,51 @@ pub async fn run(config: config::ApiConfig) -> Result<()> {
         .unwrap();
     group.register(explore_counter.clone(), vec![]);
 
+    let group = registry
+        .new_group(
+            "stract_similar_sites_requests".to_string(),
+            Some("Total number of incoming requests to the similar sites api.".to_string()),
+        )
+        .unwrap();
+    group.register(similar_sites_counter.clone(), vec![]);
+
+    let group = registry
+        .new_group(
+            "stract_site_info_requests".to_string(),
+            Some("Total number of incoming requests to the site info api.".to_string()),
+        )
+        .unwrap();
+    group.register(site_info_counter.clone(), vec![]);
+
+    let group = registry
+        .new_group(
+            "stract_page_signals_requests".to_string(),
+            Some("Total number of incoming requests to the page signals api.".to_string()),
+        )
+        .unwrap();
+    group.register(page_signals_counter.clone(), vec![]);
+
+    let group = registry
+        .new_group(
+            "stract_result_cache_lookups".to_string(),
+            Some("Total number of search result cache lookups.".to_string()),
+        )
+        .unwrap();
+    group.register(
+        result_cache_hits.clone(),
+        vec![Label {
+            key: "result".to_string(),
+            val: "hit".to_string(),
+        }],
+    );
+    group.register(
+        result_cache_misses.clone(),
+        vec![Label {
+            key: "result".to_string(),
+            val: "miss".to_string(),
+        }],
+    );
+
     let group = registry
         .new_group(
             "stract_daily_active_users".to_string(),
@@ -75,10 +131,15 @@ pub async fn run(config: config::ApiConfig) -> Result<()> {
         search_counter_success,
         search_counter_fail,
         explore_counter,
+        similar_sites_counter,
+        site_info_counter,
+        page_signals_counter,
+        result_cache_hits,
+        result_cache_misses,
         daily_active_users,
     };
 
-    let app = router(&config, counters).await?;
+    let app = router(&config, counters).await?.merge(routes);
     let metrics_app = metrics_router(registry);
 
     let addr = config.host;
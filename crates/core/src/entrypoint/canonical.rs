@@ -34,6 +34,17 @@ pub struct Job {
     pub warc_path: String,
 }
 
+fn http_version(url: &Url) -> Option<Url> {
+    if url.scheme() != "https" {
+        return None;
+    }
+
+    let mut http_url = url.clone();
+    http_url.set_scheme("http").ok()?;
+
+    Some(http_url)
+}
+
 pub struct Worker {
     pub index: CanonicalIndex,
 }
@@ -54,9 +65,17 @@ impl Worker {
                 }
             };
 
-            if let Some(canonical_url) = webpage.canonical_url() {
-                let url = Url::parse(&record.request.url)?;
-                self.index.insert(url, canonical_url)?;
+            let url = Url::parse(&record.request.url)?;
+            let canonical_url = webpage.canonical_url();
+
+            if let Some(canonical_url) = &canonical_url {
+                self.index.insert(url.clone(), canonical_url.clone())?;
+            }
+
+            // the page is available over https, so links to the http
+            // version should point to the same (preferably https) url.
+            if let Some(http_url) = http_version(&url) {
+                self.index.insert(http_url, canonical_url.unwrap_or(url))?;
             }
         }
 
@@ -21,10 +21,16 @@ use crate::{
     external_sort::ExternalSorter,
     webgraph::{
         centrality::{
-            approx_harmonic::ApproxHarmonic, harmonic::HarmonicCentrality, store_csv,
-            store_harmonic, TopNodes,
+            approx_harmonic::ApproxHarmonic,
+            betweenness::ApproxBetweenness,
+            harmonic::HarmonicCentrality,
+            incremental_harmonic::{IncrementalHarmonic, IncrementalState},
+            neighborhood::NeighborhoodFunction,
+            personalized_pagerank, read_seeds, store_csv, store_harmonic,
+            trustrank::TrustRank,
+            TopNodes,
         },
-        WebgraphBuilder,
+        unix_timestamp, DegreeDirection, NodeID, Webgraph, WebgraphBuilder,
     },
     SortableFloat,
 };
@@ -32,13 +38,24 @@ use crate::{
 pub struct Centrality;
 
 impl Centrality {
-    pub fn build_harmonic<P: AsRef<Path>>(webgraph_path: P, base_output: P) {
+    /// Links that have not been seen for `max_link_age_days` are ignored.
+    pub fn build_harmonic<P: AsRef<Path>>(
+        webgraph_path: P,
+        base_output: P,
+        max_link_age_days: Option<u32>,
+    ) {
         tracing::info!(
             "Building harmonic centrality for {}",
             webgraph_path.as_ref().to_str().unwrap()
         );
         let graph = WebgraphBuilder::new(webgraph_path).single_threaded().open();
-        let harmonic_centrality = HarmonicCentrality::calculate(&graph);
+        let harmonic_centrality = match max_link_age_days {
+            Some(days) => HarmonicCentrality::calculate_seen_since(
+                &graph,
+                unix_timestamp().saturating_sub(days.saturating_mul(24 * 60 * 60)),
+            ),
+            None => HarmonicCentrality::calculate(&graph),
+        };
         let store = store_harmonic(
             harmonic_centrality.iter().map(|(n, c)| (*n, c)),
             base_output.as_ref(),
@@ -53,6 +70,106 @@ impl Centrality {
         store_csv(top_harmonics, base_output.as_ref().join("harmonic.csv"));
     }
 
+    /// Update the harmonic centrality in `base_output` with the nodes that gained or lost
+    /// edges since the last update. The centrality is rebuilt from scratch if there is no
+    /// previous centrality, if the update would read more than `max_affected_fraction` of
+    /// the nodes or after `full_rebuild_interval` incremental updates.
+    ///
+    /// The dirty nodes of the graph are cleared afterwards, so no batches should be
+    /// merged into the graph while the centrality is updated.
+    pub fn update_harmonic<P: AsRef<Path>>(
+        webgraph_path: P,
+        base_output: P,
+        max_hops: u8,
+        max_affected_fraction: f64,
+        full_rebuild_interval: usize,
+    ) -> Result<()> {
+        let base_output = base_output.as_ref();
+        let graph = WebgraphBuilder::new(webgraph_path).open();
+        let state_path = IncrementalState::path(base_output);
+
+        let update = match (IncrementalState::open(&state_path), graph.dirty_nodes()) {
+            (Ok(state), Ok(dirty)) if state.incremental_updates < full_rebuild_interval => {
+                let max_affected =
+                    (graph.estimate_num_nodes() as f64 * max_affected_fraction) as usize;
+
+                IncrementalHarmonic::calculate(&graph, &dirty, max_hops, max_affected)
+                    .map(|update| (state, update))
+            }
+            _ => None,
+        };
+
+        let state = match update {
+            Some((state, update)) => {
+                tracing::info!(
+                    "updating harmonic centrality incrementally ({} updates since last rebuild)",
+                    state.incremental_updates + 1
+                );
+
+                let tmp = base_output.join("harmonic_update");
+                if tmp.exists() {
+                    std::fs::remove_dir_all(&tmp)?;
+                }
+
+                {
+                    let previous: speedy_kv::Db<NodeID, f64> =
+                        speedy_kv::Db::open_or_create(base_output.join("harmonic"))?;
+                    store_harmonic(update.apply(previous.iter(), state.num_nodes), &tmp);
+                }
+
+                for name in ["harmonic", "harmonic_rank"] {
+                    std::fs::remove_dir_all(base_output.join(name))?;
+                    std::fs::rename(tmp.join(name), base_output.join(name))?;
+                }
+                std::fs::remove_dir_all(&tmp)?;
+
+                IncrementalState {
+                    num_nodes: update.num_nodes(),
+                    incremental_updates: state.incremental_updates + 1,
+                }
+            }
+            None => {
+                tracing::info!("rebuilding harmonic centrality from scratch");
+
+                for name in ["harmonic", "harmonic_rank"] {
+                    let path = base_output.join(name);
+                    if path.exists() {
+                        std::fs::remove_dir_all(path)?;
+                    }
+                }
+
+                let harmonic_centrality = HarmonicCentrality::calculate(&graph);
+                store_harmonic(
+                    harmonic_centrality.iter().map(|(n, c)| (*n, c)),
+                    base_output,
+                );
+
+                IncrementalState {
+                    num_nodes: graph.nodes().count() as u64,
+                    incremental_updates: 0,
+                }
+            }
+        };
+
+        let store: speedy_kv::Db<NodeID, f64> =
+            speedy_kv::Db::open_or_create(base_output.join("harmonic"))?;
+        Self::store_top_harmonics(&graph, &store, base_output);
+
+        state.save(&state_path)?;
+        graph.clear_dirty_nodes()?;
+
+        Ok(())
+    }
+
+    fn store_top_harmonics(graph: &Webgraph, store: &speedy_kv::Db<NodeID, f64>, output: &Path) {
+        let top_harmonics = crate::webgraph::centrality::top_nodes(store, TopNodes::Top(1_000_000))
+            .into_iter()
+            .filter_map(|(n, c)| graph.id2node(&n).map(|n| (n, c)))
+            .collect();
+
+        store_csv(top_harmonics, output.join("harmonic.csv"));
+    }
+
     pub fn build_approx_harmonic<P: AsRef<Path>>(webgraph_path: P, base_output: P) -> Result<()> {
         tracing::info!(
             "Building approximated harmonic centrality for {}",
@@ -98,4 +215,151 @@ impl Centrality {
 
         Ok(())
     }
+
+    /// Betweenness estimated from `num_pivots` random pivots. Nodes with a high betweenness
+    /// are bridges between otherwise poorly connected parts of the graph.
+    pub fn build_approx_betweenness<P: AsRef<Path>>(
+        webgraph_path: P,
+        base_output: P,
+        num_pivots: usize,
+    ) -> Result<()> {
+        tracing::info!(
+            "Building approximated betweenness centrality for {}",
+            webgraph_path.as_ref().to_str().unwrap()
+        );
+
+        let graph = WebgraphBuilder::new(webgraph_path).open();
+        let betweenness = ApproxBetweenness::calculate(&graph, num_pivots);
+
+        let mut store: speedy_kv::Db<NodeID, f64> =
+            speedy_kv::Db::open_or_create(base_output.as_ref().join("approx_betweenness"))?;
+
+        for (node, score) in betweenness.iter() {
+            store.insert(*node, score)?;
+
+            if store.uncommitted_inserts() >= 100_000_000 {
+                store.commit()?;
+            }
+        }
+
+        store.commit()?;
+        store.merge_all_segments()?;
+
+        let top_nodes = crate::webgraph::centrality::top_nodes(&store, TopNodes::Top(1_000_000))
+            .into_iter()
+            .filter_map(|(n, c)| graph.id2node(&n).map(|n| (n, c)))
+            .collect();
+
+        store_csv(
+            top_nodes,
+            base_output.as_ref().join("approx_betweenness.csv"),
+        );
+
+        Ok(())
+    }
+
+    /// The neighborhood function of every node up to `max_hops` hops, written to
+    /// `neighborhood.csv` in the output folder.
+    pub fn build_neighborhood_function<P: AsRef<Path>>(
+        webgraph_path: P,
+        base_output: P,
+        max_hops: u8,
+        ingoing: bool,
+    ) -> Result<()> {
+        tracing::info!(
+            "Building neighborhood function for {}",
+            webgraph_path.as_ref().to_str().unwrap()
+        );
+
+        let direction = if ingoing {
+            DegreeDirection::In
+        } else {
+            DegreeDirection::Out
+        };
+
+        let graph = WebgraphBuilder::new(webgraph_path).open();
+        let neighborhood = NeighborhoodFunction::calculate(&graph, max_hops, direction);
+
+        for (hops, pairs) in neighborhood.global().into_iter().enumerate() {
+            tracing::info!("pairs within {} hops: {:.0}", hops, pairs);
+        }
+
+        std::fs::create_dir_all(base_output.as_ref())?;
+        neighborhood.export_csv(&graph, base_output.as_ref().join("neighborhood.csv"))?;
+
+        Ok(())
+    }
+
+    /// TrustRank from the hosts in the `whitelist_path`. The scores are stored in the same
+    /// layout as the harmonic centrality, so the output folder can be used as the
+    /// host centrality store when indexing.
+    pub fn build_trustrank<P: AsRef<Path>>(
+        webgraph_path: P,
+        whitelist_path: P,
+        base_output: P,
+        decay: f64,
+        iterations: usize,
+    ) -> Result<()> {
+        tracing::info!(
+            "Building trustrank for {}",
+            webgraph_path.as_ref().to_str().unwrap()
+        );
+
+        let graph = WebgraphBuilder::new(webgraph_path).open();
+        let trust = TrustRank::calculate_from_file(&graph, whitelist_path, decay, iterations)?;
+
+        let store = store_harmonic(trust.iter().map(|(n, t)| (*n, t)), base_output.as_ref());
+
+        let top_nodes = crate::webgraph::centrality::top_nodes(&store, TopNodes::Top(1_000_000))
+            .into_iter()
+            .filter_map(|(n, t)| graph.id2node(&n).map(|n| (n, t)))
+            .collect();
+
+        store_csv(top_nodes, base_output.as_ref().join("trustrank.csv"));
+
+        Ok(())
+    }
+
+    /// Personalized PageRank from the hosts listed in `seeds_path`, one host per line.
+    /// The scores are stored in a `personalized_pagerank` database in the output folder.
+    pub fn build_personalized_pagerank<P: AsRef<Path>>(
+        webgraph_path: P,
+        seeds_path: P,
+        base_output: P,
+        alpha: f64,
+        iterations: usize,
+    ) -> Result<()> {
+        let seeds = read_seeds(seeds_path)?;
+
+        tracing::info!(
+            "Building personalized pagerank for {} from {} seeds",
+            webgraph_path.as_ref().to_str().unwrap(),
+            seeds.len()
+        );
+
+        let graph = WebgraphBuilder::new(webgraph_path).open();
+        let scores = personalized_pagerank(&graph, &seeds, alpha, iterations);
+
+        let mut store: speedy_kv::Db<NodeID, f64> =
+            speedy_kv::Db::open_or_create(base_output.as_ref().join("personalized_pagerank"))?;
+
+        for (node, score) in scores {
+            store.insert(node, score)?;
+        }
+
+        store.commit()?;
+        store.merge_all_segments()?;
+
+        let top_nodes = crate::webgraph::centrality::top_nodes(&store, TopNodes::Top(1_000_000))
+            .into_iter()
+            .filter_map(|(n, c)| graph.id2node(&n).map(|n| (n, c)))
+            .collect();
+
+        store_csv(
+            top_nodes,
+            base_output.as_ref().join("personalized_pagerank.csv"),
+        );
+
+        Ok(())
+    }
 }
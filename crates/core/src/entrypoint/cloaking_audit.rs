@@ -0,0 +1,121 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+use std::collections::HashMap;
+
+use futures::StreamExt;
+use tracing::info;
+use url::Url;
+
+use crate::{
+    cloaking::{CloakingStats, CloakingStore, DualFetcher},
+    config::CloakingAuditConfig,
+    webgraph::Node,
+    Result,
+};
+
+use super::download_all_warc_files;
+
+/// Sample up to `pages_per_host` urls from each host in the warc files.
+fn sample_urls(config: &CloakingAuditConfig) -> Result<HashMap<Node, Vec<Url>>> {
+    let warc_paths: Vec<_> = config
+        .warc_source
+        .paths()?
+        .into_iter()
+        .skip(config.skip_warc_files.unwrap_or(0))
+        .take(config.limit_warc_files.unwrap_or(usize::MAX))
+        .collect();
+
+    let mut hosts: HashMap<Node, Vec<Url>> = HashMap::new();
+
+    for file in download_all_warc_files(&warc_paths, &config.warc_source) {
+        for record in file.records().flatten() {
+            let Ok(url) = Url::parse(&record.request.url) else {
+                continue;
+            };
+
+            let urls = hosts.entry(Node::from(&url).into_host()).or_default();
+
+            if urls.len() < config.pages_per_host {
+                urls.push(url);
+            }
+        }
+    }
+
+    Ok(hosts)
+}
+
+async fn check_host(
+    fetcher: &DualFetcher,
+    urls: Vec<Url>,
+    similarity_threshold: f64,
+) -> CloakingStats {
+    let mut stats = CloakingStats::default();
+
+    // pages from the same host are checked sequentially to stay polite.
+    for url in urls {
+        match fetcher.similarity(&url).await {
+            Ok(Some(similarity)) => stats.add(similarity, similarity_threshold),
+            Ok(None) => {}
+            Err(err) => tracing::debug!("failed to check {}: {}", url, err),
+        }
+    }
+
+    stats
+}
+
+pub fn run(config: CloakingAuditConfig) -> Result<()> {
+    let hosts = sample_urls(&config)?;
+    info!("checking {} hosts for cloaking", hosts.len());
+
+    let fetcher = DualFetcher::new(&config)?;
+    let mut store = CloakingStore::open(&config.output_path)?;
+
+    let results: Vec<_> = crate::block_on(
+        futures::stream::iter(hosts)
+            .map(|(host, urls)| {
+                let fetcher = &fetcher;
+                let similarity_threshold = config.similarity_threshold;
+
+                async move {
+                    let stats = check_host(fetcher, urls, similarity_threshold).await;
+                    (host, stats)
+                }
+            })
+            .buffer_unordered(config.num_concurrent_hosts)
+            .collect(),
+    );
+
+    let mut num_flagged = 0;
+
+    for (host, stats) in results {
+        if stats.num_checked == 0 {
+            continue;
+        }
+
+        if stats.is_cloaking() {
+            num_flagged += 1;
+        }
+
+        store.insert(&host, stats)?;
+    }
+
+    store.commit()?;
+
+    info!("flagged {} hosts for cloaking", num_flagged);
+
+    Ok(())
+}
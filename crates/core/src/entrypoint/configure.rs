@@ -122,8 +122,9 @@ fn create_webgraph() -> Result<()> {
     };
 
     let mut worker = webgraph::WebgraphWorker {
-        host_graph: webgraph::open_host_graph_writer(&out_path_host, None),
-        page_graph: webgraph::open_page_graph_writer(&out_path_page, None),
+        host_graph: webgraph::open_host_graph_writer(&out_path_host, Default::default(), None),
+        page_graph: webgraph::open_page_graph_writer(&out_path_page, Default::default(), None),
+        domain_graph: None,
         canonical_index: None,
     };
 
@@ -141,7 +142,7 @@ fn calculate_centrality() {
     let out_path = Path::new(DATA_PATH).join("centrality");
 
     if !out_path.exists() {
-        Centrality::build_harmonic(&webgraph_path, &out_path);
+        Centrality::build_harmonic(&webgraph_path, &out_path, None);
     }
 
     let webgraph_page = Path::new(DATA_PATH).join("webgraph_page");
@@ -205,6 +206,12 @@ fn create_inverted_index() -> Result<()> {
         warc_source: job.source_config.clone(),
         host_centrality_threshold: None,
         safety_classifier_path: None,
+        outlink_audit_path: None,
+        outlink_profile_path: None,
+        cloaking_audit_path: None,
+        host_about_path: None,
+        host_clusters_path: None,
+        canonical_index_path: None,
         minimum_clean_words: None,
         batch_size: defaults::Indexing::batch_size(),
         autocommit_after_num_inserts: defaults::Indexing::autocommit_after_num_inserts(),
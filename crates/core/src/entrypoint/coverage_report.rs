@@ -0,0 +1,307 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Coverage of the crawl and the search index per top level domain and language.
+//! A host is known if it is in the host webgraph and crawled if it has outgoing
+//! links in the graph, since links are only extracted from pages we have fetched.
+//! The report is written as json and csv files that can be published as is.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufWriter,
+    path::Path,
+};
+
+use rayon::prelude::*;
+use tracing::info;
+use url::Url;
+
+use crate::{
+    config::CoverageReportConfig,
+    index::Index,
+    webgraph::{Node, NodeID, WebgraphBuilder},
+    webpage::{region::Region, url_ext::UrlExt},
+    Result,
+};
+
+const UNKNOWN_TLD: &str = "unknown";
+const UNKNOWN_LANGUAGE: &str = "unknown";
+
+fn tld(url: &Url) -> String {
+    url.tld()
+        .map(|tld| tld.to_lowercase())
+        .unwrap_or_else(|| UNKNOWN_TLD.to_string())
+}
+
+fn language(region: Region) -> String {
+    match region {
+        // pages are assigned to `All` when their language is not one of the supported regions
+        Region::All => UNKNOWN_LANGUAGE.to_string(),
+        region => region.name(),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TldCoverage {
+    pub tld: String,
+    pub known_hosts: u64,
+    pub crawled_hosts: u64,
+    pub indexed_hosts: u64,
+    pub indexed_pages: u64,
+    /// Ratio of the known hosts that have been crawled.
+    pub crawled_ratio: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct LanguageCoverage {
+    pub language: String,
+    pub indexed_hosts: u64,
+    pub indexed_pages: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct CoverageReport {
+    /// Sorted by the number of known hosts.
+    pub tlds: Vec<TldCoverage>,
+    /// Sorted by the number of indexed pages.
+    pub languages: Vec<LanguageCoverage>,
+}
+
+#[derive(Default)]
+struct Counts {
+    hosts: HashSet<NodeID>,
+    pages: u64,
+}
+
+#[derive(Default)]
+struct CoverageCounter {
+    known_hosts: HashMap<String, u64>,
+    crawled_hosts: HashMap<String, u64>,
+    indexed_tlds: HashMap<String, Counts>,
+    indexed_languages: HashMap<String, Counts>,
+}
+
+impl CoverageCounter {
+    fn add_host(&mut self, host: &Node, crawled: bool) {
+        let Ok(url) = Url::parse(&format!("http://{}", host.as_str())) else {
+            return;
+        };
+        let tld = tld(&url);
+
+        if crawled {
+            *self.crawled_hosts.entry(tld.clone()).or_default() += 1;
+        }
+
+        *self.known_hosts.entry(tld).or_default() += 1;
+    }
+
+    fn add_page(&mut self, url: &Url, region: Region) {
+        let host = Node::from(url).into_host().id();
+
+        let counts = self.indexed_tlds.entry(tld(url)).or_default();
+        counts.hosts.insert(host);
+        counts.pages += 1;
+
+        let counts = self.indexed_languages.entry(language(region)).or_default();
+        counts.hosts.insert(host);
+        counts.pages += 1;
+    }
+
+    fn finish(mut self) -> CoverageReport {
+        let tld_names: HashSet<String> = self
+            .known_hosts
+            .keys()
+            .chain(self.indexed_tlds.keys())
+            .cloned()
+            .collect();
+
+        let mut tlds: Vec<_> = tld_names
+            .into_iter()
+            .map(|tld| {
+                let known_hosts = self.known_hosts.get(&tld).copied().unwrap_or_default();
+                let crawled_hosts = self.crawled_hosts.get(&tld).copied().unwrap_or_default();
+                let indexed = self.indexed_tlds.remove(&tld).unwrap_or_default();
+
+                TldCoverage {
+                    tld,
+                    known_hosts,
+                    crawled_hosts,
+                    indexed_hosts: indexed.hosts.len() as u64,
+                    indexed_pages: indexed.pages,
+                    crawled_ratio: if known_hosts == 0 {
+                        0.0
+                    } else {
+                        crawled_hosts as f64 / known_hosts as f64
+                    },
+                }
+            })
+            .collect();
+
+        tlds.sort_by(|a, b| {
+            b.known_hosts
+                .cmp(&a.known_hosts)
+                .then_with(|| b.indexed_pages.cmp(&a.indexed_pages))
+                .then_with(|| a.tld.cmp(&b.tld))
+        });
+
+        let mut languages: Vec<_> = self
+            .indexed_languages
+            .into_iter()
+            .map(|(language, counts)| LanguageCoverage {
+                language,
+                indexed_hosts: counts.hosts.len() as u64,
+                indexed_pages: counts.pages,
+            })
+            .collect();
+
+        languages.sort_by(|a, b| {
+            b.indexed_pages
+                .cmp(&a.indexed_pages)
+                .then_with(|| a.language.cmp(&b.language))
+        });
+
+        CoverageReport { tlds, languages }
+    }
+}
+
+impl CoverageReport {
+    fn save<P: AsRef<Path>>(&self, output: P) -> Result<()> {
+        let output = output.as_ref();
+        std::fs::create_dir_all(output)?;
+
+        serde_json::to_writer_pretty(
+            BufWriter::new(File::create(output.join("coverage.json"))?),
+            self,
+        )?;
+
+        let mut writer = csv::Writer::from_path(output.join("tlds.csv"))?;
+        for tld in &self.tlds {
+            writer.serialize(tld)?;
+        }
+        writer.flush()?;
+
+        let mut writer = csv::Writer::from_path(output.join("languages.csv"))?;
+        for language in &self.languages {
+            writer.serialize(language)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+pub fn run(config: &CoverageReportConfig) -> Result<()> {
+    let mut counter = CoverageCounter::default();
+
+    let graph = WebgraphBuilder::new(&config.host_graph_path).open();
+    let crawled: HashSet<NodeID> = graph.par_edges().map(|edge| edge.from).collect();
+
+    for (host, id) in graph.node_ids() {
+        counter.add_host(&host, crawled.contains(&id));
+    }
+
+    info!("counted {} known hosts", graph.estimate_num_nodes());
+
+    if let Some(index_path) = &config.index_path {
+        let index = Index::open(index_path)?;
+
+        for page in index.inverted_index.pages() {
+            if let Ok(url) = Url::parse(&page.url) {
+                counter.add_page(&url, page.region);
+            }
+        }
+    }
+
+    let report = counter.finish();
+    report.save(&config.output_path)?;
+
+    info!("coverage report written to {}", config.output_path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_per_tld_and_language() {
+        let mut counter = CoverageCounter::default();
+
+        counter.add_host(&Node::from("https://a.dk/").into_host(), true);
+        counter.add_host(&Node::from("https://b.dk/").into_host(), false);
+        counter.add_host(&Node::from("https://c.com/").into_host(), true);
+
+        for url in ["https://a.dk/", "https://a.dk/page", "https://c.com/"] {
+            let url = Url::parse(url).unwrap();
+            let region = if url.tld() == Some("dk") {
+                Region::Denmark
+            } else {
+                Region::All
+            };
+
+            counter.add_page(&url, region);
+        }
+
+        let report = counter.finish();
+
+        assert_eq!(
+            report.tlds[0],
+            TldCoverage {
+                tld: "dk".to_string(),
+                known_hosts: 2,
+                crawled_hosts: 1,
+                indexed_hosts: 1,
+                indexed_pages: 2,
+                crawled_ratio: 0.5,
+            }
+        );
+        assert_eq!(report.tlds[1].tld, "com");
+        assert_eq!(report.tlds[1].crawled_ratio, 1.0);
+
+        assert_eq!(
+            report.languages,
+            vec![
+                LanguageCoverage {
+                    language: "Danish".to_string(),
+                    indexed_hosts: 1,
+                    indexed_pages: 2,
+                },
+                LanguageCoverage {
+                    language: UNKNOWN_LANGUAGE.to_string(),
+                    indexed_hosts: 1,
+                    indexed_pages: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn report_is_saved() {
+        let output = crate::gen_temp_path();
+        let mut counter = CoverageCounter::default();
+        counter.add_host(&Node::from("https://a.dk/").into_host(), true);
+
+        let report = counter.finish();
+        report.save(&output).unwrap();
+
+        let saved: CoverageReport =
+            serde_json::from_reader(File::open(output.join("coverage.json")).unwrap()).unwrap();
+        assert_eq!(saved, report);
+        assert!(output.join("tlds.csv").exists());
+    }
+}
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use candle_core::DType;
 use kuchiki::{traits::TendrilSink, NodeRef};
 use zimba::{Article, ArticleIterator, ZimFile};
 
@@ -23,6 +24,7 @@ use crate::{
         EntityIndex,
     },
     image_store::Image,
+    models::dual_encoder::DualEncoder,
     Result,
 };
 
@@ -188,29 +190,79 @@ fn node_into_span(node: &NodeRef) -> Span {
     span
 }
 
+/// Embed the title and abstract of the entities.
+fn insert_embeddings(
+    index: &mut EntityIndex,
+    dual_encoder: &DualEncoder,
+    entities: Vec<(String, String)>,
+) -> Result<()> {
+    if entities.is_empty() {
+        return Ok(());
+    }
+
+    let (titles, texts): (Vec<_>, Vec<_>) = entities.into_iter().unzip();
+    let embeddings: Vec<Vec<f32>> = dual_encoder
+        .embed(&texts)?
+        .to_dtype(DType::F32)?
+        .to_vec2()?;
+
+    for (title, embedding) in titles.into_iter().zip(embeddings) {
+        index.insert_embedding(title, &embedding)?;
+    }
+
+    Ok(())
+}
+
 pub struct EntityIndexer;
 
 impl EntityIndexer {
-    pub fn run(wikipedia_dump_path: String, output_path: String) -> Result<()> {
+    /// The related entities are found with embeddings from the dual encoder, if one is given.
+    pub fn run(
+        wikipedia_dump_path: String,
+        output_path: String,
+        dual_encoder_path: Option<String>,
+    ) -> Result<()> {
         let zim = ZimFile::open(wikipedia_dump_path)?;
         let mut index = EntityIndex::open(output_path)?;
         index.prepare_writer();
 
+        let dual_encoder = dual_encoder_path.map(DualEncoder::open).transpose()?;
+
+        if let Some(dual_encoder) = &dual_encoder {
+            index.prepare_embeddings(dual_encoder.hidden_size())?;
+        }
+
         let mut inserts = 0;
+        let mut to_embed = Vec::new();
 
         for entity in EntityIterator::new(&zim)?
             .filter(|e| !e.is_disambiguation)
             .filter(|e| !e.article_url.starts_with("Portal:"))
         {
+            if dual_encoder.is_some() {
+                to_embed.push((
+                    entity.title.clone(),
+                    format!("{}. {}", entity.title, entity.page_abstract.text),
+                ));
+            }
+
             index.insert(entity);
             inserts += 1;
 
             if inserts > 10_000 {
+                if let Some(dual_encoder) = &dual_encoder {
+                    insert_embeddings(&mut index, dual_encoder, std::mem::take(&mut to_embed))?;
+                }
+
                 index.commit();
                 inserts = 0;
             }
         }
 
+        if let Some(dual_encoder) = &dual_encoder {
+            insert_embeddings(&mut index, dual_encoder, to_embed)?;
+        }
+
         index.commit();
         inserts = 0;
 
@@ -0,0 +1,103 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+use optics::Optic;
+use tracing::{info, warn};
+
+use crate::{
+    config::EvaluationConfig,
+    index::Index,
+    ranking::models::{lambdamart::LambdaMART, linear::LinearRegression},
+    searcher::{LocalSearcher, SearchQuery},
+    trec::{self, Qrels, Run},
+    Result,
+};
+
+pub fn run(config: EvaluationConfig) -> Result<()> {
+    let topics = trec::read_topics(BufReader::new(File::open(&config.topics_path)?))?;
+
+    let optic = match &config.optic_path {
+        Some(path) => Some(Optic::parse(&std::fs::read_to_string(path)?)?),
+        None => None,
+    };
+
+    let mut searcher = LocalSearcher::new(Index::open(&config.index_path)?);
+
+    if let Some(model_path) = &config.linear_model_path {
+        searcher.set_linear_model(LinearRegression::open(model_path)?);
+    }
+
+    if let Some(model_path) = &config.lambda_model_path {
+        searcher.set_lambda_model(LambdaMART::open(model_path)?);
+    }
+
+    searcher.set_collector_config(config.collector.clone());
+
+    let mut run = Run::new(config.run_name.clone());
+
+    for topic in topics {
+        let query = SearchQuery {
+            query: topic.query.clone(),
+            num_results: config.num_results,
+            optic: optic.clone(),
+            ..Default::default()
+        };
+
+        let webpages = match searcher.search(&query) {
+            Ok(result) => result.webpages,
+            Err(err) => {
+                warn!("query {} ({}) failed: {}", topic.qid, topic.query, err);
+                Vec::new()
+            }
+        };
+
+        // the scores in a run must be decreasing, so we fall back to the rank if the score is unknown
+        let num_webpages = webpages.len();
+        let results = webpages
+            .into_iter()
+            .enumerate()
+            .map(|(rank, webpage)| {
+                let score = webpage.score.unwrap_or((num_webpages - rank) as f64);
+                (webpage.url, score)
+            })
+            .collect();
+
+        run.insert(topic.qid, results);
+    }
+
+    run.write(BufWriter::new(File::create(&config.run_path)?))?;
+    info!("run written to {}", config.run_path);
+
+    if let Some(qrels_path) = &config.qrels_path {
+        let qrels = Qrels::read(BufReader::new(File::open(qrels_path)?))?;
+        let metrics = trec::evaluate(&run, &qrels, config.cutoff);
+
+        info!(
+            "evaluated {} queries: nDCG@{k} = {:.4}, MRR@{k} = {:.4}",
+            metrics.num_queries,
+            metrics.ndcg,
+            metrics.mrr,
+            k = config.cutoff,
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,396 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Index a directory tree or zip file of html and markdown files, e.g. for intranet
+//! or site search. Markdown files are converted to html before they are indexed.
+//!
+//! The files are written to a warc file which is then processed like any other warc file,
+//! so the output directory has the same layout as a regular index build:
+//! `index`, `webgraph_host`, `webgraph_page`, `centrality` and `centrality_page`.
+
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use tracing::info;
+use url::Url;
+
+use crate::{
+    config::{
+        defaults, FileIndexerConfig, IndexingGraphConfig, IndexingLocalConfig, LocalConfig,
+        WarcSource,
+    },
+    warc::{self, WarcRecord, WarcWriter},
+    Result,
+};
+
+use super::{
+    indexer::{self, JobSettings},
+    webgraph, Centrality,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileKind {
+    Html,
+    Markdown,
+}
+
+impl FileKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "html" | "htm" | "xhtml" => Some(FileKind::Html),
+            "md" | "markdown" => Some(FileKind::Markdown),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Document {
+    /// Path relative to the root of the directory or zip file.
+    path: PathBuf,
+    kind: FileKind,
+    content: String,
+}
+
+fn read_dir(root: &Path, dir: &Path, documents: &mut Vec<Document>) -> Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.path());
+
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            read_dir(root, &path, documents)?;
+        } else if file_type.is_file() {
+            let Some(kind) = FileKind::from_path(&path) else {
+                continue;
+            };
+
+            match std::fs::read_to_string(&path) {
+                Ok(content) => documents.push(Document {
+                    path: path.strip_prefix(root)?.to_path_buf(),
+                    kind,
+                    content,
+                }),
+                Err(err) => tracing::warn!("skipping {}: {}", path.display(), err),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn read_zip(path: &Path) -> Result<Vec<Document>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut documents = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+
+        if file.is_dir() {
+            continue;
+        }
+
+        // entries with absolute paths or `..` components are skipped
+        let Some(path) = file.enclosed_name().map(|path| path.to_path_buf()) else {
+            continue;
+        };
+
+        let Some(kind) = FileKind::from_path(&path) else {
+            continue;
+        };
+
+        let mut content = String::new();
+        match file.read_to_string(&mut content) {
+            Ok(_) => documents.push(Document {
+                path,
+                kind,
+                content,
+            }),
+            Err(err) => tracing::warn!("skipping {}: {}", path.display(), err),
+        }
+    }
+
+    documents.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(documents)
+}
+
+fn read_documents(input: &Path) -> Result<Vec<Document>> {
+    if input.is_dir() {
+        let mut documents = Vec::new();
+        read_dir(input, input, &mut documents)?;
+        Ok(documents)
+    } else {
+        read_zip(input)
+    }
+}
+
+fn markdown_to_html(markdown: &str, fallback_title: &str) -> String {
+    let title = markdown
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix("# "))
+        .map(|title| title.trim())
+        .unwrap_or(fallback_title);
+
+    let mut escaped_title = String::new();
+    let _ = pulldown_cmark::escape::escape_html(&mut escaped_title, title);
+
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, pulldown_cmark::Parser::new(markdown));
+
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{escaped_title}</title></head><body>{body}</body></html>"
+    )
+}
+
+/// The url of the document. Relative links between the files resolve to the
+/// urls of the linked files, so they become edges in the webgraph.
+fn document_url(input: &Path, base_url: Option<&Url>, document: &Document) -> Result<Url> {
+    match base_url {
+        Some(base_url) => {
+            let relative = document
+                .path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            Ok(base_url.join(&relative)?)
+        }
+        None => Url::from_file_path(input.join(&document.path))
+            .map_err(|_| anyhow!("could not create file url for {}", document.path.display())),
+    }
+}
+
+fn write_warc(config: &FileIndexerConfig, warc_path: &Path) -> Result<usize> {
+    let input = Path::new(&config.input_path).canonicalize()?;
+
+    let base_url = match &config.base_url {
+        Some(base_url) if base_url.ends_with('/') => Some(Url::parse(base_url)?),
+        Some(base_url) => Some(Url::parse(&format!("{base_url}/"))?),
+        None => None,
+    };
+
+    let documents = read_documents(&input)?;
+    let mut writer = WarcWriter::new();
+
+    for document in &documents {
+        let url = document_url(&input, base_url.as_ref(), document)?;
+
+        let body = match document.kind {
+            FileKind::Html => document.content.clone(),
+            FileKind::Markdown => {
+                let fallback_title = document
+                    .path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                markdown_to_html(&document.content, &fallback_title)
+            }
+        };
+
+        writer.write(&WarcRecord {
+            request: warc::Request {
+                url: url.to_string(),
+            },
+            response: warc::Response {
+                body,
+                payload_type: Some(warc::PayloadType::Html),
+            },
+            metadata: warc::Metadata {
+                fetch_time_ms: 0,
+                redirects: Vec::new(),
+            },
+        })?;
+    }
+
+    std::fs::write(warc_path, writer.finish()?)?;
+
+    Ok(documents.len())
+}
+
+fn build_webgraphs(warc_path: &str, output: &Path) -> Result<()> {
+    info!("building webgraphs");
+
+    let host_path = output.join("webgraph_host");
+    let page_path = output.join("webgraph_page");
+
+    let job = webgraph::Job {
+        config: webgraph::JobConfig::Local(LocalConfig {
+            folder: ".".to_string(),
+            names: vec![warc_path.to_string()],
+        }),
+        warc_paths: vec![warc_path.to_string()],
+    };
+
+    let mut worker = webgraph::WebgraphWorker {
+        host_graph: webgraph::open_host_graph_writer(&host_path, Default::default(), None),
+        page_graph: webgraph::open_page_graph_writer(&page_path, Default::default(), None),
+        domain_graph: None,
+        canonical_index: None,
+    };
+
+    worker.process_job(&job);
+
+    worker.host_graph.finalize();
+    worker.page_graph.finalize();
+
+    Centrality::build_harmonic(&host_path, &output.join("centrality"), None);
+    Centrality::build_approx_harmonic(page_path, output.join("centrality_page"))?;
+
+    Ok(())
+}
+
+fn build_index(warc_path: &str, output: &Path) -> Result<()> {
+    info!("building index");
+
+    let path = |name: &str| output.join(name).to_str().unwrap().to_string();
+    let tmp_path = path("index_tmp");
+
+    if Path::new(&tmp_path).exists() {
+        std::fs::remove_dir_all(&tmp_path)?;
+    }
+
+    let source_config = WarcSource::Local(LocalConfig {
+        folder: ".".to_string(),
+        names: vec![warc_path.to_string()],
+    });
+
+    let job = indexer::Job {
+        source_config: source_config.clone(),
+        warc_path: warc_path.to_string(),
+        base_path: tmp_path.clone(),
+        settings: JobSettings {
+            host_centrality_threshold: None,
+            minimum_clean_words: None,
+            batch_size: defaults::Indexing::batch_size(),
+            autocommit_after_num_inserts: defaults::Indexing::autocommit_after_num_inserts(),
+        },
+    };
+
+    let worker = indexer::IndexingWorker::new(IndexingLocalConfig {
+        host_centrality_store_path: path("centrality"),
+        page_centrality_store_path: Some(path("centrality_page")),
+        page_webgraph: Some(IndexingGraphConfig::Local {
+            path: path("webgraph_page"),
+        }),
+        topics_path: None,
+        output_path: tmp_path.clone(),
+        limit_warc_files: None,
+        skip_warc_files: None,
+        warc_source: source_config,
+        host_centrality_threshold: None,
+        safety_classifier_path: None,
+        outlink_audit_path: None,
+        outlink_profile_path: None,
+        cloaking_audit_path: None,
+        host_about_path: None,
+        host_clusters_path: None,
+        canonical_index_path: None,
+        minimum_clean_words: None,
+        batch_size: defaults::Indexing::batch_size(),
+        autocommit_after_num_inserts: defaults::Indexing::autocommit_after_num_inserts(),
+        dual_encoder: None,
+    });
+
+    let index = job.process(&worker);
+    std::fs::rename(index.path, path("index"))?;
+    std::fs::remove_dir_all(&tmp_path)?;
+
+    Ok(())
+}
+
+/// Build the webgraphs, centralities and index for a single warc file into `output`.
+pub(super) fn build_from_warc(warc_path: &str, output: &Path) -> Result<()> {
+    std::fs::create_dir_all(output)?;
+
+    build_webgraphs(warc_path, output)?;
+    build_index(warc_path, output)
+}
+
+pub fn run(config: &FileIndexerConfig) -> Result<()> {
+    let output = Path::new(&config.output_path);
+    std::fs::create_dir_all(output)?;
+
+    let warc_path = output.join("files.warc.gz");
+    let num_documents = write_warc(config, &warc_path)?;
+    info!("found {} html and markdown files", num_documents);
+
+    build_from_warc(warc_path.to_str().unwrap(), output)?;
+    std::fs::remove_file(&warc_path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_html_and_markdown() {
+        let root = crate::gen_temp_path();
+        std::fs::create_dir_all(root.join("docs")).unwrap();
+        std::fs::write(
+            root.join("index.html"),
+            "<a href=\"docs/intro.md\">intro</a>",
+        )
+        .unwrap();
+        std::fs::write(root.join("docs/intro.md"), "# Intro\n\nHello").unwrap();
+        std::fs::write(root.join("image.png"), [0u8; 4]).unwrap();
+
+        let documents = read_documents(&root).unwrap();
+        let paths: Vec<_> = documents.iter().map(|doc| doc.path.clone()).collect();
+
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("docs/intro.md"), PathBuf::from("index.html")]
+        );
+        assert_eq!(documents[0].kind, FileKind::Markdown);
+
+        let base_url = Url::parse("https://intranet.example.com/wiki/").unwrap();
+        assert_eq!(
+            document_url(&root, Some(&base_url), &documents[0])
+                .unwrap()
+                .as_str(),
+            "https://intranet.example.com/wiki/docs/intro.md"
+        );
+
+        let url = document_url(&root, None, &documents[1]).unwrap();
+        assert_eq!(url.scheme(), "file");
+        assert_eq!(
+            url.join("docs/intro.md").unwrap(),
+            document_url(&root, None, &documents[0]).unwrap()
+        );
+    }
+
+    #[test]
+    fn markdown_title() {
+        let html = markdown_to_html("some text\n\n# Getting <started>\n\n[link](other.md)", "x");
+
+        assert!(html.contains("<title>Getting &lt;started&gt;</title>"));
+        assert!(html.contains("<a href=\"other.md\">link</a>"));
+        assert!(markdown_to_html("no heading", "readme").contains("<title>readme</title>"));
+    }
+}
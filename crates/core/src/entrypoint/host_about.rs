@@ -0,0 +1,134 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Analyze the homepage of each host in the warc files and store the descriptors
+//! of the host (see [`crate::host_about`]).
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+use tracing::info;
+
+use crate::{
+    config::{HostAboutConfig, WarcSource},
+    host_about::{HostAbout, HostAboutStore},
+    warc::{PayloadType, WarcFile},
+    webgraph::Node,
+    webpage::Html,
+    Result,
+};
+
+/// Keeps the most complete descriptors if a host has several homepages (e.g. both http and https).
+fn insert_best(hosts: &mut HashMap<Node, HostAbout>, host: Node, about: HostAbout) {
+    match hosts.get(&host) {
+        Some(existing) if existing.completeness() >= about.completeness() => {}
+        _ => {
+            hosts.insert(host, about);
+        }
+    }
+}
+
+fn process_warc_file(
+    warc_source: &WarcSource,
+    warc_path: &str,
+) -> Result<HashMap<Node, HostAbout>> {
+    let name = warc_path.split('/').last().unwrap();
+    info!("processing {}", name);
+
+    let warc_file = WarcFile::download(warc_source, warc_path)?;
+    let mut hosts = HashMap::new();
+
+    for record in warc_file
+        .records()
+        .flatten()
+        .filter(|record| matches!(record.response.payload_type, Some(PayloadType::Html) | None))
+    {
+        let html = match Html::parse(&record.response.body, &record.request.url) {
+            Ok(html) => html,
+            Err(err) => {
+                tracing::debug!("skipping {}: {}", record.request.url, err);
+                continue;
+            }
+        };
+
+        if !html.is_homepage() {
+            continue;
+        }
+
+        let host = Node::from(html.url()).into_host();
+        insert_best(&mut hosts, host, HostAbout::from_homepage(&html));
+    }
+
+    info!("{} done", name);
+
+    Ok(hosts)
+}
+
+pub fn run(config: &HostAboutConfig) -> Result<()> {
+    let warc_paths: Vec<_> = config
+        .warc_source
+        .paths()?
+        .into_iter()
+        .skip(config.skip_warc_files.unwrap_or(0))
+        .take(config.limit_warc_files.unwrap_or(usize::MAX))
+        .collect();
+
+    let hosts = warc_paths
+        .par_iter()
+        .map(|warc_path| process_warc_file(&config.warc_source, warc_path))
+        .try_reduce(HashMap::new, |mut acc, hosts| {
+            for (host, about) in hosts {
+                insert_best(&mut acc, host, about);
+            }
+
+            Ok(acc)
+        })?;
+
+    let mut store = HostAboutStore::open(&config.output_path)?;
+    let num_hosts = hosts.len();
+
+    for (host, about) in hosts {
+        store.insert(&host, about)?;
+    }
+
+    store.commit()?;
+
+    info!("stored descriptors for {} hosts", num_hosts);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_complete_homepage_is_kept() {
+        let host = Node::from("https://example.com/").into_host();
+        let mut hosts = HashMap::new();
+
+        let complete = HostAbout {
+            title: Some("Example".to_string()),
+            has_contact: true,
+            ..Default::default()
+        };
+
+        insert_best(&mut hosts, host.clone(), complete.clone());
+        insert_best(&mut hosts, host.clone(), HostAbout::default());
+
+        assert_eq!(hosts.get(&host), Some(&complete));
+    }
+}
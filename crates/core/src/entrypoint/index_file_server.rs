@@ -0,0 +1,128 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Serve the output of the indexer so the search servers can pull the segments
+//! that changed since their last copy of the index.
+
+use std::{net::SocketAddr, path::PathBuf};
+
+use anyhow::anyhow;
+use tracing::info;
+
+use crate::{
+    distributed::sonic::{self, replication::RemoteClient, service::sonic_service},
+    index_replication::{self, FileSource, LocalSource, Manifest},
+    Result,
+};
+
+sonic_service!(IndexFileService, [GetManifest, GetFileChunk]);
+
+pub struct IndexFileService {
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetManifest;
+impl sonic::service::Message<IndexFileService> for GetManifest {
+    type Response = Option<Manifest>;
+    async fn handle(self, server: &IndexFileService) -> Self::Response {
+        let root = server.root.clone();
+
+        // the index can be replaced by a new build, so the manifest is checked every time
+        match tokio::task::spawn_blocking(move || Manifest::open_or_build(root)).await {
+            Ok(Ok(manifest)) => Some(manifest),
+            Ok(Err(err)) => {
+                tracing::error!("failed to build manifest: {}", err);
+                None
+            }
+            Err(err) => {
+                tracing::error!("failed to build manifest: {}", err);
+                None
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetFileChunk {
+    pub path: String,
+    pub offset: u64,
+    pub len: u64,
+}
+impl sonic::service::Message<IndexFileService> for GetFileChunk {
+    type Response = Option<Vec<u8>>;
+    async fn handle(self, server: &IndexFileService) -> Self::Response {
+        index_replication::read_chunk(&server.root, &self.path, self.offset, self.len).ok()
+    }
+}
+
+impl FileSource for RemoteClient<IndexFileService> {
+    async fn manifest(&self) -> Result<Manifest> {
+        self.send(GetManifest)
+            .await?
+            .ok_or_else(|| anyhow!("index file server could not list its index"))
+    }
+
+    async fn read_chunk(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        self.send(GetFileChunk {
+            path: path.to_string(),
+            offset,
+            len,
+        })
+        .await?
+        .ok_or_else(|| anyhow!("index file server could not read {}", path))
+    }
+}
+
+pub async fn serve(index_path: String, host: SocketAddr) -> Result<()> {
+    let server = IndexFileService {
+        root: PathBuf::from(index_path),
+    }
+    .bind(host)
+    .await
+    .unwrap();
+
+    info!("index file server is ready to accept requests on {}", host);
+
+    loop {
+        if let Err(e) = server.accept().await {
+            tracing::error!("{:?}", e);
+        }
+    }
+}
+
+/// Pull the files that changed in `source` into the index at `index_path`.
+/// The source is either the address of an index file server or a local folder.
+pub async fn pull(source: String, index_path: String, chunk_size: u64) -> Result<()> {
+    let dest = PathBuf::from(index_path);
+
+    let stats = match source.parse::<SocketAddr>() {
+        Ok(addr) => {
+            let client: RemoteClient<IndexFileService> = RemoteClient::new(addr);
+            index_replication::replicate(&client, &dest, chunk_size).await?
+        }
+        Err(_) => {
+            index_replication::replicate(&LocalSource::new(&source), &dest, chunk_size).await?
+        }
+    };
+
+    info!(
+        "copied {} files ({} bytes) and deleted {} files",
+        stats.copied_files, stats.copied_bytes, stats.deleted_files
+    );
+
+    Ok(())
+}
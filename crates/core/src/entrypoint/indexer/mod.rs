@@ -119,7 +119,15 @@ pub fn merge(indexes: Vec<IndexPointer>) -> Result<()> {
         std::fs::remove_dir_all(other_path).unwrap();
     }
 
+    // the workers are not guaranteed to have disjoint urls, so the same page
+    // can have been indexed by several of them
+    let num_deleted = index.inverted_index.dedup_urls()?;
+    tracing::info!("deleted {} duplicate pages", num_deleted);
+
     index.inverted_index.merge_into_max_segments(1).unwrap();
 
+    // lets the search servers fetch only the segments that changed since their last copy
+    crate::index_replication::Manifest::build_and_save(&index.path)?;
+
     Ok(())
 }
@@ -30,8 +30,14 @@ use crate::models::dual_encoder::DualEncoder as DualEncoderModel;
 use crate::webgraph::remote::RemoteWebgraph;
 use crate::Result;
 
+use crate::canon_index::CanonicalIndex;
+use crate::cloaking::CloakingStore;
+use crate::host_about::HostAboutStore;
+use crate::host_clusters::HostClusters;
 use crate::human_website_annotations;
 use crate::index::Index;
+use crate::outlink_audit::OutlinkAudit;
+use crate::outlink_profile::OutlinkProfiles;
 use crate::rake::RakeModel;
 use crate::ranking::SignalComputer;
 use crate::webgraph::{self, EdgeLimit, Node, NodeID};
@@ -43,6 +49,12 @@ pub struct Config {
     pub page_webgraph: Option<IndexingGraphConfig>,
     pub topics_path: Option<String>,
     pub safety_classifier_path: Option<String>,
+    pub outlink_audit_path: Option<String>,
+    pub outlink_profile_path: Option<String>,
+    pub cloaking_audit_path: Option<String>,
+    pub host_about_path: Option<String>,
+    pub host_clusters_path: Option<String>,
+    pub canonical_index_path: Option<String>,
     pub dual_encoder: Option<IndexingDualEncoderConfig>,
 }
 
@@ -54,6 +66,12 @@ impl From<IndexingLocalConfig> for Config {
             page_webgraph: config.page_webgraph,
             topics_path: config.topics_path,
             safety_classifier_path: config.safety_classifier_path,
+            outlink_audit_path: config.outlink_audit_path,
+            outlink_profile_path: config.outlink_profile_path,
+            cloaking_audit_path: config.cloaking_audit_path,
+            host_about_path: config.host_about_path,
+            host_clusters_path: config.host_clusters_path,
+            canonical_index_path: config.canonical_index_path,
             dual_encoder: config.dual_encoder,
         }
     }
@@ -67,6 +85,12 @@ impl From<LiveIndexConfig> for Config {
             page_webgraph: config.page_webgraph,
             topics_path: None,
             safety_classifier_path: config.safety_classifier_path,
+            outlink_audit_path: config.outlink_audit_path,
+            outlink_profile_path: config.outlink_profile_path,
+            cloaking_audit_path: config.cloaking_audit_path,
+            host_about_path: config.host_about_path,
+            host_clusters_path: config.host_clusters_path,
+            canonical_index_path: config.canonical_index_path,
             dual_encoder: None,
         }
     }
@@ -167,6 +191,12 @@ pub struct IndexingWorker {
     page_webgraph: Option<Webgraph>,
     topics: Option<human_website_annotations::Mapper>,
     safety_classifier: Option<safety_classifier::Model>,
+    outlink_audit: Option<OutlinkAudit>,
+    outlink_profiles: Option<OutlinkProfiles>,
+    cloaking: Option<CloakingStore>,
+    host_about: Option<HostAboutStore>,
+    host_clusters: Option<HostClusters>,
+    canonical_index: Option<CanonicalIndex>,
     job_settings: Option<JobSettings>,
     rake: RakeModel,
     dual_encoder: Option<DualEncoder>,
@@ -203,6 +233,30 @@ impl IndexingWorker {
                 .safety_classifier_path
                 .as_ref()
                 .map(|path| safety_classifier::Model::open(path).unwrap()),
+            outlink_audit: config
+                .outlink_audit_path
+                .as_ref()
+                .map(|path| OutlinkAudit::open(Path::new(path).join("outlinks")).unwrap()),
+            outlink_profiles: config
+                .outlink_profile_path
+                .as_ref()
+                .map(|path| OutlinkProfiles::open(path).unwrap()),
+            cloaking: config
+                .cloaking_audit_path
+                .as_ref()
+                .map(|path| CloakingStore::open(path).unwrap()),
+            host_about: config
+                .host_about_path
+                .as_ref()
+                .map(|path| HostAboutStore::open(path).unwrap()),
+            host_clusters: config
+                .host_clusters_path
+                .as_ref()
+                .map(|path| HostClusters::open(path).unwrap()),
+            canonical_index: config
+                .canonical_index_path
+                .as_ref()
+                .map(|path| CanonicalIndex::open(path).unwrap()),
             job_settings: None,
             rake: RakeModel::default(),
             dual_encoder: config.dual_encoder.as_ref().map(|dual_encoder| {
@@ -247,6 +301,12 @@ impl IndexingWorker {
             return Err(anyhow::anyhow!("noindex"));
         }
 
+        if let Some(index) = self.canonical_index.as_ref() {
+            if index.https_version(html.url())?.is_some() {
+                return Err(anyhow::anyhow!("https version exists"));
+            }
+        }
+
         let title = html.title().unwrap_or_default();
         if title.is_empty() || title.chars().all(|c| c.is_whitespace()) {
             return Err(anyhow::anyhow!("empty title"));
@@ -284,6 +344,34 @@ impl IndexingWorker {
         page.host_centrality = host_centrality;
         page.host_centrality_rank = host_centrality_rank;
 
+        if let Some(audit) = self.outlink_audit.as_ref() {
+            page.host_broken_outlink_ratio = audit
+                .get(&host_node_id)
+                .unwrap()
+                .map(|stats| stats.broken_ratio())
+                .unwrap_or_default();
+        }
+
+        if let Some(profiles) = self.outlink_profiles.as_ref() {
+            page.host_outlink_flags = profiles.flags(&host_node_id).unwrap();
+        }
+
+        if let Some(cloaking) = self.cloaking.as_ref() {
+            page.host_is_cloaking = cloaking.is_cloaking(&host_node_id).unwrap();
+        }
+
+        if let Some(host_about) = self.host_about.as_ref() {
+            page.host_about_completeness = host_about
+                .get(&host_node_id)
+                .unwrap()
+                .map(|about| about.completeness())
+                .unwrap_or_default();
+        }
+
+        if let Some(clusters) = self.host_clusters.as_ref() {
+            page.host_cluster = clusters.get(&host_node_id).unwrap();
+        }
+
         if !page.host_centrality.is_finite() {
             page.host_centrality = 0.0;
         }
@@ -463,6 +551,11 @@ impl IndexingWorker {
                 page_centrality_rank: prepared.page_centrality_rank,
                 host_centrality: prepared.host_centrality,
                 host_centrality_rank: prepared.host_centrality_rank,
+                host_broken_outlink_ratio: prepared.host_broken_outlink_ratio,
+                host_outlink_flags: prepared.host_outlink_flags,
+                host_is_cloaking: prepared.host_is_cloaking,
+                host_about_completeness: prepared.host_about_completeness,
+                host_cluster: prepared.host_cluster,
                 fetch_time_ms: page.fetch_time_ms,
                 pre_computed_score: 0.0,
                 node_id: prepared.node_id,
@@ -501,6 +594,12 @@ mod tests {
             page_webgraph: None,
             topics_path: None,
             safety_classifier_path: None,
+            outlink_audit_path: None,
+            outlink_profile_path: None,
+            cloaking_audit_path: None,
+            host_about_path: None,
+            host_clusters_path: None,
+            canonical_index_path: None,
             dual_encoder: Some(IndexingDualEncoderConfig {
                 model_path: data_path.to_str().unwrap().to_string(),
                 page_centrality_rank_threshold: threshold,
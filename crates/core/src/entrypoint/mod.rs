@@ -15,19 +15,30 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! The entrypoint module contains all entrypoints that runs the executables.
+pub mod all_in_one;
 pub mod ampc;
 pub mod api;
 pub mod autosuggest_scrape;
 pub mod canonical;
 mod centrality;
+pub mod cloaking_audit;
 #[cfg(feature = "dev")]
 pub mod configure;
+pub mod coverage_report;
 pub mod crawler;
 pub mod dmoz_parser;
 mod entity;
 pub mod entity_search_server;
+pub mod evaluation;
 pub mod feed_indexer;
+pub mod file_indexer;
+pub mod host_about;
+pub mod host_clusters;
+pub mod index_file_server;
 pub mod indexer;
+pub mod outlink_audit;
+pub mod outlink_profile;
+pub mod page_features;
 pub mod safety_classifier;
 pub mod search_server;
 pub mod web_spell;
@@ -0,0 +1,57 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use tracing::info;
+
+use crate::{
+    config::OutlinkAuditConfig,
+    outlink_audit::{parse_crawl_log_line, CrawlStatusStore, OutlinkAudit},
+    webgraph::WebgraphBuilder,
+    Result,
+};
+
+pub fn run(config: OutlinkAuditConfig) -> Result<()> {
+    let output_path = Path::new(&config.output_path);
+    let mut statuses = CrawlStatusStore::open(output_path.join("crawl_status"))?;
+
+    for path in &config.crawl_log_paths {
+        info!("reading crawl log {}", path);
+
+        for line in BufReader::new(File::open(path)?).lines() {
+            if let Some((url, status)) = parse_crawl_log_line(&line?) {
+                statuses.insert(&url, status)?;
+            }
+        }
+
+        statuses.commit()?;
+    }
+
+    let graph = WebgraphBuilder::new(&config.webgraph_path)
+        .single_threaded()
+        .open();
+
+    OutlinkAudit::build(&graph, &statuses, output_path.join("outlinks"))?;
+
+    info!("outlink audit done");
+
+    Ok(())
+}
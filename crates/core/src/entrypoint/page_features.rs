@@ -0,0 +1,333 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Extract features from the pages in warc files and write them as parquet files
+//! for offline analysis and model training. The pages are parsed the same way
+//! as during indexing, but no index is built.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Builder, ListBuilder, StringBuilder, UInt64Builder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use rayon::prelude::*;
+use tracing::info;
+
+use crate::{
+    config::{PageFeaturesConfig, WarcSource},
+    warc::{PayloadType, WarcFile, WarcRecord},
+    webpage::Html,
+    Result,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageFeatures {
+    pub url: String,
+    /// Language code (ISO 639-3) of the detected language.
+    pub language: Option<String>,
+    /// Number of bytes in the html.
+    pub html_length: u64,
+    /// Number of characters in the main content of the page.
+    pub text_length: u64,
+    /// The distinct schema.org types on the page.
+    pub schema_types: Vec<String>,
+    pub num_outlinks: u64,
+    pub quality_score: f64,
+    pub fetch_time_ms: u64,
+}
+
+impl PageFeatures {
+    fn extract(record: &WarcRecord) -> Result<Self> {
+        let html = Html::parse(&record.response.body, &record.request.url)?;
+
+        let mut schema_types: Vec<String> = html
+            .schema_org()
+            .into_iter()
+            .filter_map(|item| item.itemtype)
+            .flat_map(|itemtype| itemtype.many())
+            .collect();
+        schema_types.sort();
+        schema_types.dedup();
+
+        Ok(Self {
+            url: record.request.url.clone(),
+            language: html.lang().map(|lang| lang.code().to_string()),
+            html_length: record.response.body.len() as u64,
+            text_length: html
+                .clean_text()
+                .map(|text| text.chars().count() as u64)
+                .unwrap_or_default(),
+            schema_types,
+            num_outlinks: html.anchor_links().len() as u64,
+            quality_score: html.content_quality(),
+            fetch_time_ms: record.metadata.fetch_time_ms,
+        })
+    }
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("url", DataType::Utf8, false),
+            Field::new("language", DataType::Utf8, true),
+            Field::new("html_length", DataType::UInt64, false),
+            Field::new("text_length", DataType::UInt64, false),
+            Field::new(
+                "schema_types",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                false,
+            ),
+            Field::new("num_outlinks", DataType::UInt64, false),
+            Field::new("quality_score", DataType::Float64, false),
+            Field::new("fetch_time_ms", DataType::UInt64, false),
+        ]))
+    }
+
+    fn record_batch(rows: &[PageFeatures]) -> Result<RecordBatch> {
+        let mut url = StringBuilder::new();
+        let mut language = StringBuilder::new();
+        let mut html_length = UInt64Builder::new();
+        let mut text_length = UInt64Builder::new();
+        let mut schema_types = ListBuilder::new(StringBuilder::new());
+        let mut num_outlinks = UInt64Builder::new();
+        let mut quality_score = Float64Builder::new();
+        let mut fetch_time_ms = UInt64Builder::new();
+
+        for row in rows {
+            url.append_value(&row.url);
+            language.append_option(row.language.as_deref());
+            html_length.append_value(row.html_length);
+            text_length.append_value(row.text_length);
+            schema_types.append_value(row.schema_types.iter().map(Some));
+            num_outlinks.append_value(row.num_outlinks);
+            quality_score.append_value(row.quality_score);
+            fetch_time_ms.append_value(row.fetch_time_ms);
+        }
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(url.finish()),
+            Arc::new(language.finish()),
+            Arc::new(html_length.finish()),
+            Arc::new(text_length.finish()),
+            Arc::new(schema_types.finish()),
+            Arc::new(num_outlinks.finish()),
+            Arc::new(quality_score.finish()),
+            Arc::new(fetch_time_ms.finish()),
+        ];
+
+        Ok(RecordBatch::try_new(Self::schema(), columns)?)
+    }
+}
+
+/// Writes the rows in row groups of `row_group_size` rows.
+struct FeatureWriter {
+    writer: ArrowWriter<File>,
+    rows: Vec<PageFeatures>,
+    row_group_size: usize,
+}
+
+impl FeatureWriter {
+    fn create<P: AsRef<Path>>(path: P, row_group_size: usize) -> Result<Self> {
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(row_group_size)
+            .build();
+
+        let writer =
+            ArrowWriter::try_new(File::create(path)?, PageFeatures::schema(), Some(props))?;
+
+        Ok(Self {
+            writer,
+            rows: Vec::with_capacity(row_group_size),
+            row_group_size,
+        })
+    }
+
+    fn write(&mut self, features: PageFeatures) -> Result<()> {
+        self.rows.push(features);
+
+        if self.rows.len() >= self.row_group_size {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.rows.is_empty() {
+            self.writer
+                .write(&PageFeatures::record_batch(&self.rows)?)?;
+            self.rows.clear();
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        self.writer.close()?;
+
+        Ok(())
+    }
+}
+
+fn process_warc_file(
+    warc_source: &WarcSource,
+    warc_path: &str,
+    output: &Path,
+    row_group_size: usize,
+) -> Result<usize> {
+    let name = warc_path.split('/').last().unwrap();
+    info!("processing {}", name);
+
+    let warc_file = WarcFile::download(warc_source, warc_path)?;
+    let mut writer = FeatureWriter::create(output, row_group_size)?;
+    let mut num_rows = 0;
+
+    for record in warc_file
+        .records()
+        .flatten()
+        .filter(|record| matches!(record.response.payload_type, Some(PayloadType::Html) | None))
+    {
+        match PageFeatures::extract(&record) {
+            Ok(features) => {
+                writer.write(features)?;
+                num_rows += 1;
+            }
+            Err(err) => tracing::debug!("skipping {}: {}", record.request.url, err),
+        }
+    }
+
+    writer.finish()?;
+
+    info!("{} done", name);
+
+    Ok(num_rows)
+}
+
+pub fn run(config: &PageFeaturesConfig) -> Result<()> {
+    let output = Path::new(&config.output_path);
+    std::fs::create_dir_all(output)?;
+
+    let warc_paths: Vec<_> = config
+        .warc_source
+        .paths()?
+        .into_iter()
+        .skip(config.skip_warc_files.unwrap_or(0))
+        .take(config.limit_warc_files.unwrap_or(usize::MAX))
+        .collect();
+
+    let num_rows = warc_paths
+        .par_iter()
+        .enumerate()
+        .map(|(i, warc_path)| {
+            process_warc_file(
+                &config.warc_source,
+                warc_path,
+                &output.join(format!("part-{i:05}.parquet")),
+                config.row_group_size,
+            )
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .sum::<usize>();
+
+    info!("extracted features for {} pages", num_rows);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::warc;
+
+    use super::*;
+
+    fn record(url: &str, body: &str) -> WarcRecord {
+        WarcRecord {
+            request: warc::Request {
+                url: url.to_string(),
+            },
+            response: warc::Response {
+                body: body.to_string(),
+                payload_type: Some(PayloadType::Html),
+            },
+            metadata: warc::Metadata {
+                fetch_time_ms: 42,
+                redirects: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn extract_features() {
+        let features = PageFeatures::extract(&record(
+            "https://example.com/recipe",
+            r#"<html><head><title>Pancakes</title>
+            <script type="application/ld+json">{"@context": "https://schema.org", "@type": "Recipe", "name": "Pancakes"}</script>
+            </head><body><article><p>Pancakes are a flat cake prepared from a starch-based batter
+            that may contain eggs, milk and butter, and cooked on a hot surface such as a griddle
+            or frying pan, often frying with oil or butter.</p></article>
+            <a href="https://example.org/">eggs</a><a href="/milk">milk</a></body></html>"#,
+        ))
+        .unwrap();
+
+        assert_eq!(features.language.as_deref(), Some("eng"));
+        assert_eq!(features.schema_types, vec!["Recipe".to_string()]);
+        assert_eq!(features.num_outlinks, 2);
+        assert_eq!(features.fetch_time_ms, 42);
+        assert!(features.text_length > 0);
+        assert!((0.0..=1.0).contains(&features.quality_score));
+    }
+
+    #[test]
+    fn write_parquet() {
+        let path = crate::gen_temp_path().join("part-00000.parquet");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let rows: Vec<_> = (0..5)
+            .map(|i| PageFeatures {
+                url: format!("https://example.com/{i}"),
+                language: (i % 2 == 0).then(|| "eng".to_string()),
+                html_length: i,
+                text_length: i,
+                schema_types: vec!["Article".to_string(); i as usize % 3],
+                num_outlinks: i,
+                quality_score: 0.5,
+                fetch_time_ms: 0,
+            })
+            .collect();
+
+        let mut writer = FeatureWriter::create(&path, 2).unwrap();
+        for row in rows.clone() {
+            writer.write(row).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 3);
+
+        let num_rows: usize = reader
+            .build()
+            .unwrap()
+            .map(|batch| batch.unwrap().num_rows())
+            .sum();
+        assert_eq!(num_rows, rows.len());
+    }
+}
@@ -0,0 +1,449 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2023 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use tracing::info;
+use url::Url;
+
+use crate::{
+    config,
+    distributed::sonic::service::sonic_service,
+    distributed::{
+        cluster::Cluster,
+        member::{Member, Service},
+        sonic::{self, replication::RemoteClient},
+    },
+    index::Index,
+    index_replication::{self, Manifest, SyncStats},
+    inverted_index::{self, RetrievedWebpage, StoredSignals},
+    models::dual_encoder::DualEncoder,
+    ranking::models::{lambdamart::LambdaMART, linear::LinearRegression},
+    searcher::{host_routing::ShardDomains, InitialWebsiteResult, LocalSearcher, SearchQuery},
+    Result,
+};
+
+pub mod standby;
+
+sonic_service!(
+    SearchService,
+    [
+        RetrieveWebsites,
+        Search,
+        SearchBatch,
+        GetWebpage,
+        GetStoredSignals,
+        GetHomepageDescriptions,
+        GetShardDomains,
+        GetIndexManifest,
+        GetIndexFileChunk,
+        Promote,
+    ]
+);
+
+fn open_local_searcher(config: &config::SearchServerConfig) -> Result<LocalSearcher<Index>> {
+    let search_index = match &config.tiered_storage {
+        Some(tiered_storage) => Index::open_tiered(&config.index_path, tiered_storage)?,
+        None => Index::open(&config.index_path)?,
+    };
+
+    let mut local_searcher = LocalSearcher::new(search_index);
+
+    if let Some(model_path) = &config.linear_model_path {
+        local_searcher.set_linear_model(LinearRegression::open(model_path)?);
+    }
+
+    if let Some(model_path) = &config.lambda_model_path {
+        local_searcher.set_lambda_model(LambdaMART::open(model_path)?);
+    }
+
+    if let Some(model_path) = &config.dual_encoder_model_path {
+        local_searcher.set_dual_encoder(DualEncoder::open(model_path)?);
+    }
+
+    local_searcher.set_collector_config(config.collector.clone());
+    local_searcher.set_snippet_config(config.snippet.clone());
+
+    Ok(local_searcher)
+}
+
+pub struct SearchService {
+    /// `None` while the server is a standby that has not been promoted.
+    local_searcher: RwLock<Option<Arc<LocalSearcher<Index>>>>,
+    config: config::SearchServerConfig,
+    promotion: tokio::sync::Mutex<()>,
+    /// The index is not modified while it is served, so the manifest is only built once.
+    manifest: tokio::sync::OnceCell<Manifest>,
+    domains: tokio::sync::OnceCell<ShardDomains>,
+    // dropping the handle leaves the cluster
+    cluster_handle: Cluster,
+}
+
+impl SearchService {
+    async fn new(config: config::SearchServerConfig) -> Result<Self> {
+        let (local_searcher, service) = match (&config.standby, config.snapshot) {
+            // a promoted standby serves live queries, so it cannot hold a frozen snapshot
+            (Some(_), Some(_)) => anyhow::bail!("a snapshot searcher cannot be a standby"),
+            (Some(_), None) => (
+                None,
+                Service::StandbySearcher {
+                    host: config.host,
+                    shard: config.shard,
+                },
+            ),
+            (None, Some(date)) => (
+                Some(Arc::new(open_local_searcher(&config)?)),
+                Service::SnapshotSearcher {
+                    host: config.host,
+                    shard: config.shard,
+                    date,
+                },
+            ),
+            (None, None) => (
+                Some(Arc::new(open_local_searcher(&config)?)),
+                Service::Searcher {
+                    host: config.host,
+                    shard: config.shard,
+                },
+            ),
+        };
+
+        let cluster_handle = Cluster::join(
+            Member {
+                id: config.cluster_id.clone(),
+                service,
+            },
+            config.gossip_addr,
+            config.gossip_seed_nodes.clone().unwrap_or_default(),
+        )
+        .await?;
+
+        Ok(SearchService {
+            local_searcher: RwLock::new(local_searcher),
+            config,
+            promotion: tokio::sync::Mutex::new(()),
+            manifest: tokio::sync::OnceCell::new(),
+            domains: tokio::sync::OnceCell::new(),
+            cluster_handle,
+        })
+    }
+
+    fn local_searcher(&self) -> Option<Arc<LocalSearcher<Index>>> {
+        self.local_searcher.read().unwrap().clone()
+    }
+
+    fn is_standby(&self) -> bool {
+        self.local_searcher.read().unwrap().is_none()
+    }
+
+    /// An active searcher for the same shard to replicate the index from.
+    async fn active_replica(&self) -> Option<RemoteClient<SearchService>> {
+        self.cluster_handle
+            .members()
+            .await
+            .into_iter()
+            .find_map(|member| match member.service {
+                Service::Searcher { host, shard }
+                    if shard == self.config.shard && host != self.config.host =>
+                {
+                    Some(RemoteClient::new(host))
+                }
+                _ => None,
+            })
+    }
+
+    async fn sync_index(&self, chunk_size: u64) -> Result<SyncStats> {
+        let replica = self
+            .active_replica()
+            .await
+            .ok_or_else(|| anyhow::anyhow!("no active searcher for shard"))?;
+
+        index_replication::replicate(&replica, Path::new(&self.config.index_path), chunk_size).await
+    }
+
+    /// Start serving queries from the replicated index and announce the server as an
+    /// active searcher. The index is synced one last time if an active searcher is still
+    /// alive, which only copies the files that changed since the last sync.
+    async fn promote(&self) -> Result<()> {
+        let _guard = self.promotion.lock().await;
+
+        if !self.is_standby() {
+            return Ok(());
+        }
+
+        if let Some(standby) = &self.config.standby {
+            if let Err(err) = self.sync_index(standby.chunk_size_bytes).await {
+                tracing::warn!("final sync before promotion failed: {}", err);
+            }
+        }
+
+        let local_searcher = open_local_searcher(&self.config)?;
+        *self.local_searcher.write().unwrap() = Some(Arc::new(local_searcher));
+
+        self.cluster_handle
+            .set_service(&Service::Searcher {
+                host: self.config.host,
+                shard: self.config.shard,
+            })
+            .await?;
+
+        info!("standby for shard {:?} promoted", self.config.shard);
+
+        Ok(())
+    }
+
+    /// Keep the index in sync with an active searcher until the standby is promoted.
+    async fn replicate(self: Arc<Self>, config: config::StandbyConfig) {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.sync_interval_secs));
+
+        loop {
+            interval.tick().await;
+
+            let _guard = self.promotion.lock().await;
+            if !self.is_standby() {
+                break;
+            }
+
+            match self.sync_index(config.chunk_size_bytes).await {
+                Ok(stats) if stats.is_empty() => {}
+                Ok(stats) => info!(
+                    "replicated {} files ({} bytes) and deleted {} files",
+                    stats.copied_files, stats.copied_bytes, stats.deleted_files
+                ),
+                Err(err) => tracing::warn!("failed to replicate index: {}", err),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct RetrieveWebsites {
+    pub websites: Vec<inverted_index::WebpagePointer>,
+    pub query: String,
+}
+impl sonic::service::Message<SearchService> for RetrieveWebsites {
+    type Response = Option<Vec<inverted_index::RetrievedWebpage>>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        server
+            .local_searcher()?
+            .retrieve_websites(&self.websites, &self.query)
+            .ok()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct Search {
+    pub query: SearchQuery,
+}
+impl sonic::service::Message<SearchService> for Search {
+    type Response = Option<InitialWebsiteResult>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        server
+            .local_searcher()?
+            .search_initial(&self.query, true)
+            .ok()
+    }
+}
+
+/// Several queries searched with a single request. The results are in the order of the queries.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct SearchBatch {
+    pub queries: Vec<SearchQuery>,
+}
+impl sonic::service::Message<SearchService> for SearchBatch {
+    type Response = Vec<Option<InitialWebsiteResult>>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        let Some(searcher) = server.local_searcher() else {
+            return self.queries.iter().map(|_| None).collect();
+        };
+
+        self.queries
+            .iter()
+            .map(|query| searcher.search_initial(query, true).ok())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetWebpage {
+    pub url: String,
+}
+impl sonic::service::Message<SearchService> for GetWebpage {
+    type Response = Option<RetrievedWebpage>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        server.local_searcher()?.get_webpage(&self.url)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetStoredSignals {
+    pub url: String,
+}
+impl sonic::service::Message<SearchService> for GetStoredSignals {
+    type Response = Option<StoredSignals>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        let local_searcher = server.local_searcher()?;
+
+        tokio::task::spawn_blocking(move || local_searcher.stored_signals(&self.url))
+            .await
+            .ok()?
+            .ok()
+            .flatten()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetHomepageDescriptions {
+    #[bincode(with_serde)]
+    pub urls: Vec<Url>,
+}
+impl sonic::service::Message<SearchService> for GetHomepageDescriptions {
+    type Response = crate::bincode_utils::SerdeCompat<HashMap<Url, String>>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        let mut result = HashMap::with_capacity(self.urls.len());
+
+        let Some(local_searcher) = server.local_searcher() else {
+            return crate::bincode_utils::SerdeCompat(result);
+        };
+
+        for url in &self.urls {
+            if let Some(homepage) = local_searcher.get_homepage(url) {
+                if let Some(desc) = homepage.description() {
+                    result.insert(url.clone(), desc.clone());
+                }
+            }
+        }
+
+        crate::bincode_utils::SerdeCompat(result)
+    }
+}
+
+/// The root domains in the index of the shard, so queries for other domains aren't
+/// sent to the shard.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetShardDomains;
+impl sonic::service::Message<SearchService> for GetShardDomains {
+    type Response = Option<ShardDomains>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        let local_searcher = server.local_searcher()?;
+
+        server
+            .domains
+            .get_or_try_init(|| async move {
+                tokio::task::spawn_blocking(move || local_searcher.shard_domains()).await?
+            })
+            .await
+            .cloned()
+            .ok()
+    }
+}
+
+/// The manifest of the index so a standby can replicate it.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetIndexManifest;
+impl sonic::service::Message<SearchService> for GetIndexManifest {
+    type Response = Option<Manifest>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        if server.is_standby() {
+            return None;
+        }
+
+        let index_path = server.config.index_path.clone();
+        server
+            .manifest
+            .get_or_try_init(|| async move {
+                tokio::task::spawn_blocking(move || Manifest::open_or_build(index_path)).await?
+            })
+            .await
+            .cloned()
+            .ok()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct GetIndexFileChunk {
+    pub path: String,
+    pub offset: u64,
+    pub len: u64,
+}
+impl sonic::service::Message<SearchService> for GetIndexFileChunk {
+    type Response = Option<Vec<u8>>;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        if server.is_standby() {
+            return None;
+        }
+
+        index_replication::read_chunk(&server.config.index_path, &self.path, self.offset, self.len)
+            .ok()
+    }
+}
+
+/// Promote a standby to an active searcher. Returns whether the server is active afterwards.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct Promote;
+impl sonic::service::Message<SearchService> for Promote {
+    type Response = bool;
+    async fn handle(self, server: &SearchService) -> Self::Response {
+        match server.promote().await {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::error!("failed to promote standby: {}", err);
+                false
+            }
+        }
+    }
+}
+
+/// Ask the standby at `host` to take over for its shard.
+pub async fn promote(host: std::net::SocketAddr) -> Result<()> {
+    let client: RemoteClient<SearchService> = RemoteClient::new(host);
+
+    if !client.send(Promote).await? {
+        anyhow::bail!("standby at {} could not be promoted", host);
+    }
+
+    info!("{} is now an active searcher", host);
+
+    Ok(())
+}
+
+pub async fn run(config: config::SearchServerConfig) -> Result<()> {
+    let addr = config.host;
+    let standby = config.standby.clone();
+    let service = Arc::new(SearchService::new(config).await?);
+
+    if let Some(standby) = standby {
+        info!("running as a standby until promoted");
+        tokio::spawn(service.clone().replicate(standby));
+    }
+
+    let server = sonic::service::Server::bind_shared(service, addr)
+        .await
+        .unwrap();
+
+    info!("search server is ready to accept requests on {}", addr);
+
+    loop {
+        if let Err(e) = server.accept().await {
+            tracing::error!("{:?}", e);
+        }
+    }
+}
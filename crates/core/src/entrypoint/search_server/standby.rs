@@ -0,0 +1,46 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A warm standby replicates the index of an active searcher for its shard
+//! with [`crate::index_replication`], so only the files that changed since the
+//! last sync have to be copied when it is promoted.
+
+use anyhow::anyhow;
+
+use super::{GetIndexFileChunk, GetIndexManifest, SearchService};
+use crate::{
+    distributed::sonic::replication::RemoteClient,
+    index_replication::{FileSource, Manifest},
+    Result,
+};
+
+impl FileSource for RemoteClient<SearchService> {
+    async fn manifest(&self) -> Result<Manifest> {
+        self.send(GetIndexManifest)
+            .await?
+            .ok_or_else(|| anyhow!("active searcher could not list its index"))
+    }
+
+    async fn read_chunk(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        self.send(GetIndexFileChunk {
+            path: path.to_string(),
+            offset,
+            len,
+        })
+        .await?
+        .ok_or_else(|| anyhow!("active searcher could not read {}", path))
+    }
+}
@@ -15,16 +15,19 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 use crate::{
     canon_index::CanonicalIndex,
-    config::{self, WarcSource, WebgraphConstructConfig},
+    config::{self, CompactionConfig, WarcSource, WebgraphConstructConfig},
     entrypoint::download_all_warc_files,
-    webgraph::{self, Node, NodeID, WebgraphWriter},
-    webpage::{url_ext::UrlExt, Html},
+    webgraph::{self, CompactionThread, NodeID, NormalizationPolicy, WebgraphWriter},
+    webpage::{html::links::RelFlags, url_ext::UrlExt, Html},
     Result,
 };
 use itertools::Itertools;
 use url::Url;
 
-use std::{path::Path, sync::Arc};
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+};
 use tokio::pin;
 use tracing::{info, trace};
 
@@ -38,6 +41,7 @@ pub enum JobConfig {
     Http(config::HttpConfig),
     Local(config::LocalConfig),
     S3(config::S3Config),
+    Stdin,
 }
 
 impl From<config::WarcSource> for JobConfig {
@@ -46,6 +50,7 @@ impl From<config::WarcSource> for JobConfig {
             config::WarcSource::HTTP(config) => JobConfig::Http(config),
             config::WarcSource::Local(config) => JobConfig::Local(config),
             config::WarcSource::S3(config) => JobConfig::S3(config),
+            config::WarcSource::Stdin => JobConfig::Stdin,
         }
     }
 }
@@ -56,6 +61,7 @@ impl From<JobConfig> for config::WarcSource {
             JobConfig::Http(config) => config::WarcSource::HTTP(config),
             JobConfig::Local(config) => config::WarcSource::Local(config),
             JobConfig::S3(config) => WarcSource::S3(config),
+            JobConfig::Stdin => WarcSource::Stdin,
         }
     }
 }
@@ -68,24 +74,26 @@ pub struct Job {
 
 pub fn open_host_graph_writer<P: AsRef<Path>>(
     path: P,
+    compression: webgraph::Compression,
     host_centrality_store: Option<Arc<speedy_kv::Db<NodeID, u64>>>,
 ) -> webgraph::WebgraphWriter {
     WebgraphWriter::new(
         path,
         crate::executor::Executor::single_thread(),
-        webgraph::Compression::Lz4,
+        compression,
         host_centrality_store,
     )
 }
 
 pub fn open_page_graph_writer<P: AsRef<Path>>(
     path: P,
+    compression: webgraph::Compression,
     host_centrality_store: Option<Arc<speedy_kv::Db<NodeID, u64>>>,
 ) -> webgraph::WebgraphWriter {
     WebgraphWriter::new(
         path,
         crate::executor::Executor::single_thread(),
-        webgraph::Compression::Lz4,
+        compression,
         host_centrality_store,
     )
 }
@@ -101,12 +109,25 @@ fn canonical_or_self(index: &CanonicalIndex, url: Url) -> Url {
 pub struct WebgraphWorker {
     pub host_graph: webgraph::WebgraphWriter,
     pub page_graph: webgraph::WebgraphWriter,
+    pub domain_graph: Option<webgraph::WebgraphWriter>,
     pub canonical_index: Option<Arc<CanonicalIndex>>,
 }
 
 impl WebgraphWorker {
     pub fn process_job(&mut self, job: &Job) {
         let name = job.warc_paths.first().unwrap().split('/').last().unwrap();
+        let checkpoint = job.warc_paths.join(",");
+
+        if self.host_graph.has_checkpoint(&checkpoint)
+            && self.page_graph.has_checkpoint(&checkpoint)
+            && self
+                .domain_graph
+                .as_ref()
+                .map_or(true, |graph| graph.has_checkpoint(&checkpoint))
+        {
+            info!("{} was processed before a restart", name);
+            return;
+        }
 
         info!("processing {}", name);
 
@@ -126,10 +147,40 @@ impl WebgraphWorker {
                         }
                     };
 
-                for mut link in webpage
-                    .anchor_links()
-                    .into_iter()
-                    .filter(|link| matches!(link.destination.scheme(), "http" | "https"))
+                // parked domains only link to ads and domain marketplaces. Their links are kept
+                // but flagged, so they are not used as sources when computing centrality.
+                let parked = webpage.is_parked();
+
+                // pages with a canonical link to another url are the same page, so queries
+                // for either of them get the links of both
+                if let Some(canonical) = webpage.canonical_url() {
+                    if &canonical != webpage.url() && matches!(canonical.scheme(), "http" | "https")
+                    {
+                        let alias = self.page_graph.node(webpage.url());
+                        let canonical = self.page_graph.node(&canonical);
+                        self.page_graph.insert_alias(alias, canonical);
+                    }
+                }
+
+                // every url in the redirect chain is the same page as the url it ended at
+                for redirect in &record.metadata.redirects {
+                    if let Ok(redirect) = Url::parse(redirect) {
+                        let alias = self.page_graph.node(&redirect);
+                        let page = self.page_graph.node(webpage.url());
+                        self.page_graph.insert_alias(alias, page);
+                    }
+                }
+
+                for (mut link, metadata) in
+                    webpage
+                        .anchor_links_with_metadata()
+                        .into_iter()
+                        .filter(|(link, _)| {
+                            // links between local files are kept when indexing a directory of files
+                            matches!(link.destination.scheme(), "http" | "https")
+                                || (link.source.scheme() == "file"
+                                    && link.destination.scheme() == "file")
+                        })
                 {
                     let mut source = link.source.clone();
                     let mut destination = link.destination.clone();
@@ -141,16 +192,21 @@ impl WebgraphWorker {
 
                     link.text = link.text.chars().take(128).collect();
 
-                    let mut source = Node::from(source);
+                    if parked {
+                        link.rel |= RelFlags::PARKED;
+                    }
+
+                    let mut source = self.page_graph.node(&source);
 
-                    let mut destination = Node::from(destination);
+                    let mut destination = self.page_graph.node(&destination);
 
                     trace!("inserting link {:?}", link);
-                    self.page_graph.insert(
+                    self.page_graph.insert_with_metadata(
                         source.clone(),
                         destination.clone(),
                         link.text.clone(),
                         link.rel,
+                        metadata,
                     );
 
                     let dest_domain = link.destination.root_domain();
@@ -159,23 +215,71 @@ impl WebgraphWorker {
                         && source_domain.is_some()
                         && dest_domain != source_domain
                     {
+                        if let Some(domain_graph) = &mut self.domain_graph {
+                            domain_graph.insert_with_metadata(
+                                source.clone().into_domain(),
+                                destination.clone().into_domain(),
+                                link.text.clone(),
+                                link.rel,
+                                metadata,
+                            );
+                        }
+
                         source = source.into_host();
                         destination = destination.into_host();
 
-                        self.host_graph
-                            .insert(source, destination, link.text, link.rel);
+                        self.host_graph.insert_with_metadata(
+                            source,
+                            destination,
+                            link.text,
+                            link.rel,
+                            metadata,
+                        );
                     }
                 }
             }
 
             self.host_graph.commit();
             self.page_graph.commit();
+            if let Some(domain_graph) = &mut self.domain_graph {
+                domain_graph.commit();
+            }
+        }
+
+        self.host_graph.checkpoint(checkpoint.clone());
+        if let Some(domain_graph) = &mut self.domain_graph {
+            domain_graph.checkpoint(checkpoint.clone());
         }
+        self.page_graph.checkpoint(checkpoint);
 
         info!("{} done", name);
     }
 }
 
+/// Merge the other graphs into `graph` while its segments are compacted in the background.
+fn merge_with_compaction(
+    graph: webgraph::Webgraph,
+    others: Vec<webgraph::Webgraph>,
+    config: &CompactionConfig,
+) -> Result<webgraph::Webgraph> {
+    let graph = Arc::new(RwLock::new(graph));
+    let compaction = CompactionThread::spawn(Arc::clone(&graph), config.clone());
+
+    for other in others {
+        graph.write().unwrap().merge(other)?;
+    }
+
+    compaction.stop();
+
+    let mut graph = Arc::into_inner(graph)
+        .expect("the compaction thread has stopped")
+        .into_inner()
+        .unwrap();
+    graph.compact(config)?;
+
+    Ok(graph)
+}
+
 pub struct Webgraph {}
 
 impl Webgraph {
@@ -222,6 +326,8 @@ impl Webgraph {
             s.send(())?;
         }
 
+        let normalization: Arc<dyn NormalizationPolicy> = Arc::new(config.normalization.clone());
+
         for i in 0..num_workers {
             let host_path = host_path.clone();
             let host_path = Path::new(&host_path);
@@ -231,9 +337,40 @@ impl Webgraph {
             let page_path = Path::new(&page_path);
             let page_path = page_path.join(format!("worker_{i}"));
 
+            let mut host_graph = open_host_graph_writer(
+                host_path,
+                config.compression,
+                host_centrality_rank_store.clone(),
+            )
+            .with_insert_policy(config.insert_policy.clone())
+            .with_normalization(Arc::clone(&normalization));
+            let mut page_graph = open_page_graph_writer(
+                page_path,
+                config.compression,
+                host_centrality_rank_store.clone(),
+            )
+            .with_insert_policy(config.insert_policy.clone())
+            .with_normalization(Arc::clone(&normalization));
+            let mut domain_graph = config.domain_graph_base_path.as_ref().map(|path| {
+                open_host_graph_writer(
+                    Path::new(path).join(format!("worker_{i}")),
+                    config.compression,
+                    host_centrality_rank_store.clone(),
+                )
+                .with_insert_policy(config.insert_policy.clone())
+                .with_normalization(Arc::clone(&normalization))
+            });
+
+            if config.write_ahead_log {
+                host_graph = host_graph.with_wal();
+                page_graph = page_graph.with_wal();
+                domain_graph = domain_graph.map(|graph| graph.with_wal());
+            }
+
             let mut worker = WebgraphWorker {
-                host_graph: open_host_graph_writer(host_path, host_centrality_rank_store.clone()),
-                page_graph: open_page_graph_writer(page_path, host_centrality_rank_store.clone()),
+                host_graph,
+                page_graph,
+                domain_graph,
                 canonical_index: canonical_index.clone(),
             };
 
@@ -248,9 +385,10 @@ impl Webgraph {
 
                 let host = worker.host_graph.finalize();
                 let page = worker.page_graph.finalize();
+                let domain = worker.domain_graph.map(|graph| graph.finalize());
 
                 s.send(()).unwrap();
-                (host, page)
+                (host, page, domain)
             }));
         }
 
@@ -259,22 +397,55 @@ impl Webgraph {
             graphs.push(handler.join().unwrap());
         }
 
-        let (mut host_graph, mut page_graph) = graphs.pop().unwrap();
+        let (mut host_graph, mut page_graph, mut domain_graph) = graphs.pop().unwrap();
+
+        match &config.compaction {
+            Some(compaction) => {
+                let (mut other_hosts, mut other_pages, mut other_domains) =
+                    (Vec::new(), Vec::new(), Vec::new());
+                for (host, page, domain) in graphs {
+                    other_hosts.push(host);
+                    other_pages.push(page);
+                    other_domains.extend(domain);
+                }
 
-        for (other_host, other_page) in graphs {
-            host_graph.merge(other_host)?;
-            page_graph.merge(other_page)?;
+                host_graph = merge_with_compaction(host_graph, other_hosts, compaction)?;
+                page_graph = merge_with_compaction(page_graph, other_pages, compaction)?;
+                domain_graph = domain_graph
+                    .map(|graph| merge_with_compaction(graph, other_domains, compaction))
+                    .transpose()?;
+            }
+            None => {
+                for (other_host, other_page, other_domain) in graphs {
+                    host_graph.merge(other_host)?;
+                    page_graph.merge(other_page)?;
+
+                    if let (Some(domain_graph), Some(other_domain)) =
+                        (&mut domain_graph, other_domain)
+                    {
+                        domain_graph.merge(other_domain)?;
+                    }
+                }
+            }
         }
 
         if config.merge_all_segments {
             host_graph.optimize_read(); // save space in id2node db
             page_graph.optimize_read(); // save space in id2node db
-            host_graph.merge_all_segments(Default::default())?;
-            page_graph.merge_all_segments(Default::default())?;
+            host_graph.merge_all_segments()?;
+            page_graph.merge_all_segments()?;
+
+            if let Some(domain_graph) = &mut domain_graph {
+                domain_graph.optimize_read();
+                domain_graph.merge_all_segments()?;
+            }
         }
 
         host_graph.optimize_read();
         page_graph.optimize_read();
+        if let Some(domain_graph) = &mut domain_graph {
+            domain_graph.optimize_read();
+        }
 
         Ok(())
     }
@@ -16,22 +16,33 @@
 
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use itertools::Itertools;
+use tokio::net::TcpListener;
 use tracing::info;
 use utoipa::ToSchema;
 
+use crate::api::metrics_router;
 use crate::config;
 use crate::distributed::cluster::Cluster;
 use crate::distributed::member::Member;
 use crate::distributed::member::Service;
 use crate::distributed::sonic::service::sonic_service;
 use crate::distributed::sonic::service::Message;
+use crate::metrics::PrometheusRegistry;
+use crate::webgraph::BlockCache;
+use crate::webgraph::CocitationCandidates;
+use crate::webgraph::Deadline;
 use crate::webgraph::Edge;
+use crate::webgraph::EdgeFilter;
 use crate::webgraph::EdgeLimit;
-use crate::webgraph::FullEdge;
+use crate::webgraph::EdgeQueryResult;
+use crate::webgraph::HostEdgeSummary;
 use crate::webgraph::Node;
+use crate::webgraph::NodeDegree;
 use crate::webgraph::NodeID;
+use crate::webgraph::SimilarityConfig;
 use crate::webgraph::Webgraph;
 use crate::webgraph::WebgraphBuilder;
 use crate::Result;
@@ -58,7 +69,12 @@ sonic_service!(
         RawOutgoingEdges,
         RawIngoingEdgesWithLabels,
         RawOutgoingEdgesWithLabels,
-        PagesByHosts
+        PagesByHosts,
+        TopPagesForHost,
+        HostSummary,
+        SimilarNodeCandidates,
+        InDegrees,
+        NumNodes
     ]
 );
 
@@ -71,7 +87,7 @@ impl Message<WebGraphService> for GetNode {
     type Response = Option<Node>;
 
     async fn handle(self, server: &WebGraphService) -> Self::Response {
-        server.graph.id2node(&self.node)
+        server.graph.id2node_async(self.node).await
     }
 }
 
@@ -79,13 +95,21 @@ impl Message<WebGraphService> for GetNode {
 pub struct IngoingEdges {
     pub node: Node,
     pub limit: EdgeLimit,
+    pub filter: EdgeFilter,
+    /// Return the edges read so far when the query takes longer than this.
+    pub timeout: Option<Duration>,
 }
 
 impl Message<WebGraphService> for IngoingEdges {
-    type Response = Vec<FullEdge>;
+    type Response = EdgeQueryResult;
 
     async fn handle(self, server: &WebGraphService) -> Self::Response {
-        server.graph.ingoing_edges(self.node, self.limit)
+        let deadline = self.timeout.map(Deadline::after).unwrap_or_default();
+
+        server
+            .graph
+            .ingoing_edges_until_async(self.node, self.limit, self.filter, deadline)
+            .await
     }
 }
 
@@ -93,13 +117,21 @@ impl Message<WebGraphService> for IngoingEdges {
 pub struct OutgoingEdges {
     pub node: Node,
     pub limit: EdgeLimit,
+    pub filter: EdgeFilter,
+    /// Return the edges read so far when the query takes longer than this.
+    pub timeout: Option<Duration>,
 }
 
 impl Message<WebGraphService> for OutgoingEdges {
-    type Response = Vec<FullEdge>;
+    type Response = EdgeQueryResult;
 
     async fn handle(self, server: &WebGraphService) -> Self::Response {
-        server.graph.outgoing_edges(self.node, self.limit)
+        let deadline = self.timeout.map(Deadline::after).unwrap_or_default();
+
+        server
+            .graph
+            .outgoing_edges_until_async(self.node, self.limit, self.filter, deadline)
+            .await
     }
 }
 
@@ -113,7 +145,10 @@ impl Message<WebGraphService> for RawIngoingEdges {
     type Response = Vec<Edge<()>>;
 
     async fn handle(self, server: &WebGraphService) -> Self::Response {
-        server.graph.raw_ingoing_edges(&self.node, self.limit)
+        server
+            .graph
+            .raw_ingoing_edges_async(self.node, self.limit)
+            .await
     }
 }
 
@@ -127,7 +162,10 @@ impl Message<WebGraphService> for RawOutgoingEdges {
     type Response = Vec<Edge<()>>;
 
     async fn handle(self, server: &WebGraphService) -> Self::Response {
-        server.graph.raw_outgoing_edges(&self.node, self.limit)
+        server
+            .graph
+            .raw_outgoing_edges_async(self.node, self.limit)
+            .await
     }
 }
 
@@ -143,7 +181,8 @@ impl Message<WebGraphService> for RawIngoingEdgesWithLabels {
     async fn handle(self, server: &WebGraphService) -> Self::Response {
         server
             .graph
-            .raw_ingoing_edges_with_labels(&self.node, self.limit)
+            .raw_ingoing_edges_with_labels_async(self.node, self.limit)
+            .await
     }
 }
 
@@ -159,7 +198,8 @@ impl Message<WebGraphService> for RawOutgoingEdgesWithLabels {
     async fn handle(self, server: &WebGraphService) -> Self::Response {
         server
             .graph
-            .raw_outgoing_edges_with_labels(&self.node, self.limit)
+            .raw_outgoing_edges_with_labels_async(self.node, self.limit)
+            .await
     }
 }
 
@@ -172,14 +212,97 @@ impl Message<WebGraphService> for PagesByHosts {
     type Response = Vec<NodeID>;
 
     async fn handle(self, server: &WebGraphService) -> Self::Response {
-        self.hosts
-            .iter()
-            .flat_map(|host| server.graph.pages_by_host(host))
+        server
+            .graph
+            .pages_by_hosts_async(self.hosts)
+            .await
+            .into_iter()
+            .flatten()
             .unique()
             .collect()
     }
 }
 
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct TopPagesForHost {
+    pub host: NodeID,
+    pub k: usize,
+}
+
+impl Message<WebGraphService> for TopPagesForHost {
+    type Response = Vec<NodeDegree>;
+
+    async fn handle(self, server: &WebGraphService) -> Self::Response {
+        server
+            .graph
+            .top_pages_for_host_async(self.host, self.k)
+            .await
+    }
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct HostSummary {
+    pub node: NodeID,
+}
+
+impl Message<WebGraphService> for HostSummary {
+    type Response = HostEdgeSummary;
+
+    async fn handle(self, server: &WebGraphService) -> Self::Response {
+        server.graph.host_summary_async(self.node).await
+    }
+}
+
+/// The co-citation candidates of the node in the shard. The candidates are scored
+/// by the client once the counts from all shards have been summed.
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct SimilarNodeCandidates {
+    pub node: NodeID,
+}
+
+impl Message<WebGraphService> for SimilarNodeCandidates {
+    type Response = CocitationCandidates;
+
+    async fn handle(self, server: &WebGraphService) -> Self::Response {
+        server
+            .graph
+            .cocitation_candidates_async(self.node, SimilarityConfig::default())
+            .await
+    }
+}
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct InDegrees {
+    pub nodes: Vec<NodeID>,
+}
+
+impl Message<WebGraphService> for InDegrees {
+    type Response = Vec<usize>;
+
+    async fn handle(self, server: &WebGraphService) -> Self::Response {
+        server.graph.in_degrees_async(self.nodes).await
+    }
+}
+
+/// The number of nodes in the shard from the statistics of the graph, or an estimate
+/// if the statistics have not been calculated.
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct NumNodes;
+
+impl Message<WebGraphService> for NumNodes {
+    type Response = Option<u64>;
+
+    async fn handle(self, server: &WebGraphService) -> Self::Response {
+        let num_nodes = server
+            .graph
+            .stats()
+            .map(|stats| stats.num_nodes)
+            .unwrap_or_else(|| server.graph.estimate_num_nodes() as u64);
+
+        Some(num_nodes)
+    }
+}
+
 pub async fn run(config: config::WebgraphServerConfig) -> Result<()> {
     let addr: SocketAddr = config.host;
 
@@ -200,7 +323,51 @@ pub async fn run(config: config::WebgraphServerConfig) -> Result<()> {
         .await?,
     );
 
-    let graph = Arc::new(WebgraphBuilder::new(config.graph_path).open());
+    let mut builder = WebgraphBuilder::new(config.graph_path);
+
+    if let Some(cache_bytes) = config.cache_bytes {
+        let cache = Arc::new(BlockCache::new(cache_bytes));
+        builder = builder.block_cache(Arc::clone(&cache));
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+            loop {
+                interval.tick().await;
+                info!(
+                    "block cache uses {} bytes with a hit rate of {:.2}",
+                    cache.size(),
+                    cache.hit_rate()
+                );
+            }
+        });
+    }
+
+    if let Some(capacity) = config.node_cache_size {
+        builder = builder.node_cache(capacity);
+    }
+
+    let mut graph = builder.open();
+    if config.lock_index_in_memory {
+        graph.optimize_read();
+    }
+    let graph = Arc::new(graph);
+
+    if let Some(prometheus_host) = config.prometheus_host {
+        let mut registry = PrometheusRegistry::default();
+        graph.register_metrics(&mut registry, "stract_webgraph")?;
+
+        let listener = TcpListener::bind(&prometheus_host).await?;
+        info!("prometheus exporter listening on {}", prometheus_host);
+
+        tokio::spawn(async move {
+            if let Err(e) =
+                axum::serve(listener, metrics_router(registry).into_make_service()).await
+            {
+                tracing::error!("prometheus exporter failed: {:?}", e);
+            }
+        });
+    }
 
     let server = WebGraphService { graph }.bind(addr).await.unwrap();
 
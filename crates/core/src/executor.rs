@@ -14,23 +14,146 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{Error, Result};
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use crate::{
+    metrics::{Counter, Label, PrometheusRegistry},
+    Error, Result,
+};
 
 use crossbeam_channel::unbounded;
-use rayon::{ThreadPool, ThreadPoolBuilder};
+use rayon::{ThreadPool, ThreadPoolBuilder, Yield};
+
+/// Priority class of the jobs in a [`Executor::map_with_priority`] call.
+///
+/// Running jobs are never interrupted, but a batch job that is about to start first
+/// runs the interactive jobs that are waiting for a thread in the pool.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Jobs that someone is waiting for, like the edge queries of a search.
+    #[default]
+    Interactive,
+    /// Long running background work, like merges and centrality calculations.
+    Batch,
+}
+
+thread_local! {
+    /// Priority of the job that is running on the thread.
+    static CURRENT_PRIORITY: Cell<Option<Priority>> = const { Cell::new(None) };
+}
+
+impl Priority {
+    /// The priority of the job running on this thread, or interactive outside of jobs.
+    /// Jobs that are started from within a job get the priority of that job.
+    pub fn current() -> Priority {
+        CURRENT_PRIORITY.get().unwrap_or_default()
+    }
+
+    fn run<R>(self, f: impl FnOnce() -> R) -> R {
+        let prev = CURRENT_PRIORITY.replace(Some(self));
+        let res = f();
+        CURRENT_PRIORITY.set(prev);
+
+        res
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Interactive => "interactive",
+            Priority::Batch => "batch",
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct ClassMetrics {
+    /// Number of jobs that have finished.
+    pub jobs: Counter,
+    /// Total time spent running the jobs, summed over all threads.
+    pub busy_micros: Counter,
+}
+
+/// Utilization of the executor for each priority class. The utilization of a class over an
+/// interval is the increase in `busy_micros` divided by the interval and the number of threads.
+#[derive(Default, Clone)]
+pub struct ExecutorMetrics {
+    interactive: ClassMetrics,
+    batch: ClassMetrics,
+}
+
+impl ExecutorMetrics {
+    pub fn class(&self, priority: Priority) -> &ClassMetrics {
+        match priority {
+            Priority::Interactive => &self.interactive,
+            Priority::Batch => &self.batch,
+        }
+    }
+
+    fn record(&self, priority: Priority, start: Instant) {
+        let class = self.class(priority);
+
+        class.jobs.inc();
+        class.busy_micros.add(start.elapsed().as_micros() as u64);
+    }
+
+    /// Register the metrics in groups named `{prefix}_executor_jobs` and
+    /// `{prefix}_executor_busy_micros` with a label for the priority class.
+    pub fn register(
+        &self,
+        registry: &mut PrometheusRegistry,
+        prefix: &str,
+    ) -> std::result::Result<(), crate::metrics::Error> {
+        let classes = [Priority::Interactive, Priority::Batch];
+        let label = |priority: Priority| {
+            vec![Label {
+                key: "class".to_string(),
+                val: priority.as_str().to_string(),
+            }]
+        };
+
+        let group = registry.new_group(
+            format!("{prefix}_executor_jobs"),
+            Some("Total number of jobs run by the executor.".to_string()),
+        )?;
+        for priority in classes {
+            group.register(self.class(priority).jobs.clone(), label(priority));
+        }
+
+        let group = registry.new_group(
+            format!("{prefix}_executor_busy_micros"),
+            Some("Total time spent running jobs on the executor in microseconds.".to_string()),
+        )?;
+        for priority in classes {
+            group.register(self.class(priority).busy_micros.clone(), label(priority));
+        }
+
+        Ok(())
+    }
+}
 
 #[derive(Default)]
-pub enum Executor {
+enum Pool {
     #[default]
-    #[allow(unused)]
     SingleThread,
     ThreadPool(ThreadPool),
 }
 
+#[derive(Default)]
+pub struct Executor {
+    pool: Pool,
+    /// Interactive jobs that have been spawned on the pool but not started yet.
+    waiting_interactive: AtomicUsize,
+    metrics: ExecutorMetrics,
+}
+
 impl Executor {
     #[allow(unused)]
     pub fn single_thread() -> Executor {
-        Executor::SingleThread
+        Executor::default()
     }
 
     pub fn multi_thread(prefix: &'static str) -> Result<Executor> {
@@ -43,19 +166,72 @@ impl Executor {
             .stack_size(160_000_000)
             .thread_name(move |num| format!("{prefix}{num}"))
             .build()?;
-        Ok(Executor::ThreadPool(pool))
+
+        Ok(Executor {
+            pool: Pool::ThreadPool(pool),
+            ..Default::default()
+        })
     }
 
+    pub fn num_threads(&self) -> usize {
+        match &self.pool {
+            Pool::SingleThread => 1,
+            Pool::ThreadPool(pool) => pool.current_num_threads(),
+        }
+    }
+
+    pub fn metrics(&self) -> &ExecutorMetrics {
+        &self.metrics
+    }
+
+    /// Run the jobs with the priority of the job that calls `map`, see [`Priority::current`].
     pub fn map<J: Send, R: Send, JIterator: Iterator<Item = J>, F: Sized + Sync + Fn(J) -> R>(
         &self,
         f: F,
         jobs: JIterator,
     ) -> Result<Vec<R>> {
-        match self {
-            Executor::SingleThread => Ok(jobs.map(f).collect::<_>()),
-            Executor::ThreadPool(pool) => {
+        self.map_with_priority(Priority::current(), f, jobs)
+    }
+
+    /// Let the threads of the pool run the interactive jobs that are waiting
+    /// before a batch job is started.
+    fn yield_to_interactive(&self) {
+        while self.waiting_interactive.load(Ordering::Acquire) > 0 {
+            if rayon::yield_now() != Some(Yield::Executed) {
+                break;
+            }
+        }
+    }
+
+    pub fn map_with_priority<
+        J: Send,
+        R: Send,
+        JIterator: Iterator<Item = J>,
+        F: Sized + Sync + Fn(J) -> R,
+    >(
+        &self,
+        priority: Priority,
+        f: F,
+        jobs: JIterator,
+    ) -> Result<Vec<R>> {
+        match &self.pool {
+            Pool::SingleThread => Ok(jobs
+                .map(|job| {
+                    let start = Instant::now();
+                    let res = priority.run(|| f(job));
+                    self.metrics.record(priority, start);
+                    res
+                })
+                .collect::<_>()),
+            Pool::ThreadPool(pool) => {
                 let jobs: Vec<J> = jobs.collect();
                 let num_jobs = jobs.len();
+
+                if priority == Priority::Interactive {
+                    self.waiting_interactive
+                        .fetch_add(num_jobs, Ordering::AcqRel);
+                }
+
                 let rx = {
                     let (tx, rx) = unbounded();
                     pool.scope(|scope| {
@@ -63,7 +239,17 @@ impl Executor {
                             let tx_ref = &tx;
                             let f_ref = &f;
                             scope.spawn(move |_| {
-                                let res = f_ref(arg);
+                                match priority {
+                                    Priority::Interactive => {
+                                        self.waiting_interactive.fetch_sub(1, Ordering::AcqRel);
+                                    }
+                                    Priority::Batch => self.yield_to_interactive(),
+                                }
+
+                                let start = Instant::now();
+                                let res = priority.run(|| f_ref(arg));
+                                self.metrics.record(priority, start);
+
                                 if let Err(err) = tx_ref.send((idx, res)) {
                                     tracing::error!(
                                         "Failed to execute job. It probably means all executor \
@@ -105,7 +291,12 @@ impl Executor {
 #[cfg(test)]
 mod tests {
 
-    use super::Executor;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Barrier,
+    };
+
+    use super::{Executor, Priority};
 
     #[test]
     #[should_panic(expected = "panic should propagate")]
@@ -155,4 +346,69 @@ mod tests {
             assert_eq!(*r, i * 2);
         }
     }
+
+    #[test]
+    fn test_class_metrics() {
+        let executor = Executor::with_threads(2, "search-test").unwrap();
+
+        executor.map(|i| i * 2, 0..10).unwrap();
+        executor
+            .map_with_priority(Priority::Batch, |i| i * 2, 0..5)
+            .unwrap();
+
+        let metrics = executor.metrics();
+        assert_eq!(metrics.class(Priority::Interactive).jobs.get(), 10);
+        assert_eq!(metrics.class(Priority::Batch).jobs.get(), 5);
+
+        // jobs started from a batch job are batch jobs
+        executor
+            .map_with_priority(
+                Priority::Batch,
+                |_| executor.map(|_| Priority::current(), 0..2).unwrap(),
+                0..1,
+            )
+            .unwrap();
+        assert_eq!(metrics.class(Priority::Interactive).jobs.get(), 10);
+        assert_eq!(metrics.class(Priority::Batch).jobs.get(), 8);
+        assert_eq!(Priority::current(), Priority::Interactive);
+    }
+
+    #[test]
+    fn test_interactive_jobs_run_before_waiting_batch_jobs() {
+        let executor = Executor::with_threads(1, "search-test").unwrap();
+        let interactive_done = AtomicBool::new(false);
+        let first_started = AtomicBool::new(false);
+        let barrier = Barrier::new(2);
+
+        std::thread::scope(|scope| {
+            let batch = scope.spawn(|| {
+                executor
+                    .map_with_priority(
+                        Priority::Batch,
+                        |_| {
+                            if !first_started.swap(true, Ordering::AcqRel) {
+                                // the interactive job is queued while the first batch job runs
+                                barrier.wait();
+                                while executor.waiting_interactive.load(Ordering::Acquire) == 0 {
+                                    std::thread::yield_now();
+                                }
+                            }
+
+                            interactive_done.load(Ordering::Acquire)
+                        },
+                        0..2,
+                    )
+                    .unwrap()
+            });
+
+            barrier.wait();
+            executor
+                .map(|_| interactive_done.store(true, Ordering::Release), 0..1)
+                .unwrap();
+
+            let mut seen = batch.join().unwrap();
+            seen.sort();
+            assert_eq!(seen, vec![false, true]);
+        });
+    }
 }
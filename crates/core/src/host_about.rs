@@ -0,0 +1,246 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Descriptors of the site behind each host, extracted from the homepage of the host.
+//! The descriptors are shown in the site info panel, and whether the site tells the
+//! visitor who runs it and how to reach them is used as a trust signal during ranking.
+
+use std::path::Path;
+
+use crate::{
+    webgraph::{Node, NodeID},
+    webpage::{schema_org::Item, Html},
+    Result,
+};
+
+/// Titles and descriptions are truncated to this number of characters.
+const MAX_TEXT_CHARS: usize = 512;
+
+/// schema.org types that describe the organization behind a site.
+const ORGANIZATION_TYPES: [&str; 6] = [
+    "Organization",
+    "Corporation",
+    "LocalBusiness",
+    "NewsMediaOrganization",
+    "EducationalOrganization",
+    "GovernmentOrganization",
+];
+
+const CONTACT_TERMS: [&str; 4] = ["contact", "kontakt", "contacto", "contatti"];
+
+const IMPRINT_TERMS: [&str; 6] = [
+    "impressum",
+    "imprint",
+    "legal notice",
+    "legal-notice",
+    "mentions legales",
+    "mentions-legales",
+];
+
+fn truncate(text: String) -> String {
+    match text.char_indices().nth(MAX_TEXT_CHARS) {
+        Some((idx, _)) => text[..idx].to_string(),
+        None => text,
+    }
+}
+
+fn non_empty(text: Option<String>) -> Option<String> {
+    text.map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .map(truncate)
+}
+
+fn organization_name(items: &[Item]) -> Option<String> {
+    items
+        .iter()
+        .filter(|item| ORGANIZATION_TYPES.iter().any(|t| item.types_contains(t)))
+        .find_map(|item| {
+            item.properties
+                .get("name")
+                .cloned()
+                .and_then(|name| name.one())
+                .and_then(|name| name.try_into_string())
+        })
+}
+
+fn site_name(html: &Html) -> Option<String> {
+    html.metadata()
+        .into_iter()
+        .find(|meta| meta.get("property").map(|p| p.as_str()) == Some("og:site_name"))
+        .and_then(|meta| meta.get("content").cloned())
+}
+
+fn mentions(text: &str, terms: &[&str]) -> bool {
+    let text = text.to_lowercase().replace('é', "e");
+    terms.iter().any(|term| text.contains(term))
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct HostAbout {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Name of the organization behind the site, either from schema.org or `og:site_name`.
+    pub organization: Option<String>,
+    /// Whether the homepage links to a contact page.
+    pub has_contact: bool,
+    /// Whether the homepage links to an imprint or legal notice.
+    pub has_imprint: bool,
+}
+
+impl HostAbout {
+    pub fn from_homepage(html: &Html) -> Self {
+        let links = html.anchor_links();
+
+        let links_to = |terms: &[&str]| {
+            links
+                .iter()
+                .filter(|link| link.destination.host_str() == html.url().host_str())
+                .any(|link| mentions(&link.text, terms) || mentions(link.destination.path(), terms))
+        };
+
+        Self {
+            title: non_empty(html.title()),
+            description: non_empty(html.description()),
+            organization: non_empty(
+                organization_name(&html.schema_org()).or_else(|| site_name(html)),
+            ),
+            has_contact: links_to(&CONTACT_TERMS),
+            has_imprint: links_to(&IMPRINT_TERMS),
+        }
+    }
+
+    /// Ratio of the descriptors that are present, in the range `[0, 1]`.
+    pub fn completeness(&self) -> f64 {
+        let present = [
+            self.title.is_some(),
+            self.description.is_some(),
+            self.organization.is_some(),
+            self.has_contact,
+            self.has_imprint,
+        ];
+
+        present.iter().filter(|p| **p).count() as f64 / present.len() as f64
+    }
+}
+
+pub struct HostAboutStore {
+    inner: speedy_kv::Db<NodeID, HostAbout>,
+}
+
+impl HostAboutStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(path)?,
+        })
+    }
+
+    pub fn insert(&mut self, host: &Node, about: HostAbout) -> Result<()> {
+        self.inner.insert(host.clone().into_host().id(), about)
+    }
+
+    pub fn get(&self, host: &NodeID) -> Result<Option<HostAbout>> {
+        self.inner.get(host)
+    }
+
+    pub fn commit(&mut self) -> Result<()> {
+        self.inner.commit()?;
+        self.inner.merge_all_segments()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_from_homepage() {
+        let html = Html::parse(
+            r#"<html><head><title>  Example Bakery </title>
+            <meta name="description" content="Fresh bread every morning">
+            <script type="application/ld+json">{"@context": "https://schema.org", "@type": "LocalBusiness", "name": "Example Bakery ApS"}</script>
+            </head><body>
+            <a href="/kontakt">Kontakt</a>
+            <a href="https://example.com/legal">Impressum</a>
+            <a href="https://other.com/contact">Contact them</a>
+            </body></html>"#,
+            "https://example.com/",
+        )
+        .unwrap();
+
+        let about = HostAbout::from_homepage(&html);
+
+        assert_eq!(about.title.as_deref(), Some("Example Bakery"));
+        assert_eq!(
+            about.description.as_deref(),
+            Some("Fresh bread every morning")
+        );
+        assert_eq!(about.organization.as_deref(), Some("Example Bakery ApS"));
+        assert!(about.has_contact);
+        assert!(about.has_imprint);
+        assert_eq!(about.completeness(), 1.0);
+    }
+
+    #[test]
+    fn external_links_are_ignored() {
+        let html = Html::parse(
+            r#"<html><head><meta property="og:site_name" content="Example"></head>
+            <body><a href="https://other.com/contact">Contact</a></body></html>"#,
+            "https://example.com/",
+        )
+        .unwrap();
+
+        let about = HostAbout::from_homepage(&html);
+
+        assert_eq!(about.title, None);
+        assert_eq!(about.organization.as_deref(), Some("Example"));
+        assert!(!about.has_contact);
+        assert!(!about.has_imprint);
+        assert!((about.completeness() - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn store() {
+        let mut store = HostAboutStore::open(crate::gen_temp_path()).unwrap();
+        let about = HostAbout {
+            title: Some("Example".to_string()),
+            has_contact: true,
+            ..Default::default()
+        };
+
+        store
+            .insert(&Node::from("https://example.com/about"), about.clone())
+            .unwrap();
+        store.commit().unwrap();
+
+        assert_eq!(
+            store
+                .get(&Node::from("https://example.com/").into_host().id())
+                .unwrap(),
+            Some(about)
+        );
+    }
+}
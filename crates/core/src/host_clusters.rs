@@ -0,0 +1,313 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Assigns each host in the host webgraph to a cluster of related hosts.
+//! The clusters are found with label propagation, where the vote of each
+//! neighbour is weighted by how similar the content of the two hosts is.
+//! We use the anchor texts pointing to a host as a description of its content.
+//!
+//! Only the labels and a fixed size MinHash sketch of the anchor terms are kept in
+//! memory for each host. The edges are read from the webgraph in every iteration.
+//!
+//! The clusters are used to diversify the search results, so a single
+//! group of related sites cannot take over all the top results for ambiguous queries.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use crate::{
+    webgraph::{EdgeLimit, NodeID, Webgraph},
+    Result,
+};
+
+/// Number of ingoing anchor texts used to describe the content of a host.
+const NUM_ANCHOR_TEXTS: usize = 256;
+
+/// Edges considered for each host in each direction.
+const MAX_NEIGHBOURS: usize = 1024;
+
+/// Number of hashes in the content sketch of a host.
+const SKETCH_SIZE: usize = 32;
+
+fn anchor_terms<'a>(labels: impl Iterator<Item = &'a str>) -> HashSet<String> {
+    labels
+        .flat_map(|label| label.split_whitespace())
+        .map(|term| term.to_lowercase())
+        .filter(|term| term.chars().any(|c| c.is_alphanumeric()))
+        .collect()
+}
+
+/// MinHash sketch of a set of terms. The share of positions where two sketches
+/// agree is an estimate of the jaccard similarity of the two sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TermSketch {
+    mins: [u32; SKETCH_SIZE],
+}
+
+impl TermSketch {
+    /// Returns `None` for an empty set, which is not similar to anything.
+    fn new(terms: &HashSet<String>) -> Option<Self> {
+        if terms.is_empty() {
+            return None;
+        }
+
+        let mut mins = [u32::MAX; SKETCH_SIZE];
+
+        for term in terms {
+            for (seed, min) in mins.iter_mut().enumerate() {
+                let mut hasher = DefaultHasher::default();
+                seed.hash(&mut hasher);
+                term.hash(&mut hasher);
+                *min = (*min).min(hasher.finish() as u32);
+            }
+        }
+
+        Some(Self { mins })
+    }
+
+    fn similarity(&self, other: &Self) -> f64 {
+        let agree = self
+            .mins
+            .iter()
+            .zip(other.mins.iter())
+            .filter(|(a, b)| a == b)
+            .count();
+
+        agree as f64 / SKETCH_SIZE as f64
+    }
+}
+
+/// The hosts of the webgraph with a sketch of the content of each host.
+struct HostGraph<'a> {
+    graph: &'a Webgraph,
+    hosts: Vec<NodeID>,
+    sketches: HashMap<NodeID, TermSketch>,
+}
+
+impl<'a> HostGraph<'a> {
+    fn from_webgraph(graph: &'a Webgraph) -> Self {
+        let mut hosts: Vec<_> = graph.node_ids().map(|(_, id)| id).collect();
+        hosts.sort();
+
+        let mut sketches = HashMap::new();
+
+        for host in &hosts {
+            let ingoing =
+                graph.raw_ingoing_edges_with_labels(host, EdgeLimit::Limit(NUM_ANCHOR_TEXTS));
+            let terms = anchor_terms(ingoing.iter().map(|edge| edge.label.as_str()));
+
+            if let Some(sketch) = TermSketch::new(&terms) {
+                sketches.insert(*host, sketch);
+            }
+        }
+
+        Self {
+            graph,
+            hosts,
+            sketches,
+        }
+    }
+
+    fn neighbours(&self, host: &NodeID) -> Vec<NodeID> {
+        let mut neighbours: Vec<_> = self
+            .graph
+            .raw_ingoing_edges(host, EdgeLimit::Limit(MAX_NEIGHBOURS))
+            .into_iter()
+            .map(|edge| edge.from)
+            .chain(
+                self.graph
+                    .raw_outgoing_edges(host, EdgeLimit::Limit(MAX_NEIGHBOURS))
+                    .into_iter()
+                    .map(|edge| edge.to),
+            )
+            .filter(|n| n != host)
+            .collect();
+        neighbours.sort();
+        neighbours.dedup();
+
+        neighbours
+    }
+
+    fn similarity(&self, a: &NodeID, b: &NodeID) -> f64 {
+        match (self.sketches.get(a), self.sketches.get(b)) {
+            (Some(a), Some(b)) => a.similarity(b),
+            _ => 0.0,
+        }
+    }
+
+    /// Label propagation where each neighbour votes with weight `1 + content similarity`.
+    /// The host also votes for its current label, which prevents labels from oscillating.
+    /// Ties are broken by the smallest label to make the result deterministic.
+    fn label_propagation(&self, max_iterations: usize) -> HashMap<NodeID, NodeID> {
+        let mut labels: HashMap<NodeID, NodeID> = self.hosts.iter().map(|h| (*h, *h)).collect();
+
+        for _ in 0..max_iterations {
+            let mut changed = false;
+
+            for host in &self.hosts {
+                let mut votes: HashMap<NodeID, f64> = HashMap::new();
+                votes.insert(labels[host], 1.0);
+
+                for neighbour in self.neighbours(host) {
+                    if let Some(label) = labels.get(&neighbour) {
+                        *votes.entry(*label).or_default() +=
+                            1.0 + self.similarity(host, &neighbour);
+                    }
+                }
+
+                let (best, _) = votes
+                    .into_iter()
+                    .max_by(|(a_label, a), (b_label, b)| {
+                        a.total_cmp(b).then_with(|| b_label.cmp(a_label))
+                    })
+                    .unwrap();
+
+                if labels[host] != best {
+                    labels.insert(*host, best);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        labels
+    }
+}
+
+/// The cluster of each host. Clusters are identified by the id of one of their hosts.
+pub struct HostClusters {
+    inner: speedy_kv::Db<NodeID, NodeID>,
+}
+
+impl HostClusters {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(path)?,
+        })
+    }
+
+    pub fn build<P: AsRef<Path>>(
+        host_graph: &Webgraph,
+        max_iterations: usize,
+        path: P,
+    ) -> Result<Self> {
+        let graph = HostGraph::from_webgraph(host_graph);
+        let labels = graph.label_propagation(max_iterations);
+
+        let mut clusters = Self::open(path)?;
+
+        for (host, cluster) in labels {
+            clusters.inner.insert(host, cluster)?;
+        }
+
+        clusters.inner.commit()?;
+        clusters.inner.merge_all_segments()?;
+
+        Ok(clusters)
+    }
+
+    pub fn get(&self, host: &NodeID) -> Result<Option<NodeID>> {
+        self.inner.get(host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        executor::Executor,
+        webgraph::{Compression, Node, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    use super::*;
+
+    #[test]
+    fn terms() {
+        let terms = anchor_terms(["Rust Language", "rust - the book"].into_iter());
+
+        assert_eq!(terms.len(), 4);
+        assert!(terms.contains("rust"));
+        assert!(!terms.contains("-"));
+
+        let sketch = TermSketch::new(&terms).unwrap();
+        let other = TermSketch::new(&anchor_terms(["jaguar cars"].into_iter())).unwrap();
+
+        assert_eq!(TermSketch::new(&HashSet::new()), None);
+        assert_eq!(sketch.similarity(&sketch), 1.0);
+        assert!(sketch.similarity(&other) < 0.5);
+    }
+
+    #[test]
+    fn two_communities() {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        let edges = [
+            ("https://a.com/", "https://b.com/", "rust programming"),
+            ("https://b.com/", "https://c.com/", "rust compiler"),
+            ("https://c.com/", "https://a.com/", "rust book"),
+            ("https://a.com/", "https://c.com/", "rust language"),
+            ("https://x.com/", "https://y.com/", "jaguar cars"),
+            ("https://y.com/", "https://z.com/", "jaguar dealer"),
+            ("https://z.com/", "https://x.com/", "jaguar models"),
+            ("https://x.com/", "https://z.com/", "jaguar parts"),
+        ];
+
+        for (from, to, label) in edges {
+            writer.insert(
+                Node::from(from).into_host(),
+                Node::from(to).into_host(),
+                label.to_string(),
+                RelFlags::default(),
+            );
+        }
+
+        writer.commit();
+        let graph = writer.finalize();
+
+        let clusters = HostClusters::build(&graph, 10, crate::gen_temp_path()).unwrap();
+
+        let cluster = |url: &str| {
+            clusters
+                .get(&Node::from(url).into_host().id())
+                .unwrap()
+                .unwrap()
+        };
+
+        assert_eq!(cluster("https://a.com/"), cluster("https://b.com/"));
+        assert_eq!(cluster("https://a.com/"), cluster("https://c.com/"));
+        assert_eq!(cluster("https://x.com/"), cluster("https://y.com/"));
+        assert_eq!(cluster("https://x.com/"), cluster("https://z.com/"));
+        assert_ne!(cluster("https://a.com/"), cluster("https://x.com/"));
+
+        assert_eq!(
+            clusters
+                .get(&Node::from("https://other.com/").into_host().id())
+                .unwrap(),
+            None
+        );
+    }
+}
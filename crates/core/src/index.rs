@@ -23,6 +23,7 @@ use std::time::SystemTime;
 use tantivy::tokenizer::TokenizerManager;
 
 use crate::collector::MainCollector;
+use crate::config::TieredStorageConfig;
 use crate::inverted_index::{self, InvertedIndex};
 use crate::query::Query;
 use crate::search_ctx::Ctx;
@@ -58,6 +59,30 @@ impl Index {
         })
     }
 
+    /// Open an existing index where some of the segment files may have been
+    /// offloaded to object storage.
+    pub fn open_tiered<P: AsRef<Path>>(path: P, config: &TieredStorageConfig) -> Result<Self> {
+        let inverted_index =
+            InvertedIndex::open_tiered(path.as_ref().join(INVERTED_INDEX_SUBFOLDER_NAME), config)?;
+
+        let region_count = RegionCount::open(path.as_ref().join(REGION_COUNT_FILE_NAME));
+
+        Ok(Self {
+            inverted_index,
+            region_count: Mutex::new(region_count),
+            path: path.as_ref().to_str().unwrap().to_string(),
+        })
+    }
+
+    /// Move the segment files of the index at `path` to object storage.
+    /// Returns the number of files that were offloaded.
+    pub fn offload_segments<P: AsRef<Path>>(
+        path: P,
+        config: &TieredStorageConfig,
+    ) -> Result<usize> {
+        InvertedIndex::offload_segments(path.as_ref().join(INVERTED_INDEX_SUBFOLDER_NAME), config)
+    }
+
     pub fn set_auto_merge_policy(&mut self) {
         self.inverted_index.set_auto_merge_policy();
     }
@@ -0,0 +1,509 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Incremental replication of an index folder.
+//!
+//! The source of the replication describes its files in a [`Manifest`] with the
+//! size and checksum of each file. The destination compares the manifest with its own
+//! and only copies the files that are missing or have changed, so a new index build
+//! that keeps most of its segments only transfers the new segments.
+//!
+//! The json metadata files point to the other files, so they are copied after all
+//! other files. Files that are no longer in the source are deleted at the end.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    future::Future,
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::Result;
+
+const MANIFEST_FILE: &str = "replication_manifest.json";
+const PARTIAL_SUFFIX: &str = ".partial";
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct ManifestFile {
+    /// Path relative to the index folder with `/` as separator.
+    pub path: String,
+    pub len: u64,
+    pub checksum: u64,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct Manifest {
+    pub files: Vec<ManifestFile>,
+}
+
+fn is_mutable(path: &str) -> bool {
+    path.ends_with(".json")
+}
+
+fn is_ignored(path: &str) -> bool {
+    path == MANIFEST_FILE || path.ends_with(".lock") || path.ends_with(PARTIAL_SUFFIX)
+}
+
+fn list_dir(root: &Path, dir: &Path, files: &mut Vec<(String, u64)>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_type()?.is_dir() {
+            list_dir(root, &path, files)?;
+            continue;
+        }
+
+        let relative = path
+            .strip_prefix(root)?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if !is_ignored(&relative) {
+            files.push((relative, entry.metadata()?.len()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Paths and sizes of all files in the index folder.
+fn list_files(root: &Path) -> Result<Vec<(String, u64)>> {
+    let mut files = Vec::new();
+
+    if root.exists() {
+        list_dir(root, root, &mut files)?;
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+fn checksum<R: Read>(mut reader: R) -> Result<u64> {
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0; 1 << 20];
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher.digest())
+}
+
+/// Resolve a path from a manifest within the index folder. Paths that could point
+/// outside of the folder are rejected.
+fn resolve(root: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(anyhow!("invalid index file path: {}", path));
+    }
+
+    Ok(root.join(relative))
+}
+
+impl Manifest {
+    pub fn path<P: AsRef<Path>>(root: P) -> PathBuf {
+        root.as_ref().join(MANIFEST_FILE)
+    }
+
+    /// Describe the files in the folder. This reads all files to compute their checksums.
+    pub fn build<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let root = root.as_ref();
+        let mut files = Vec::new();
+
+        for (path, len) in list_files(root)? {
+            let checksum = checksum(BufReader::new(File::open(resolve(root, &path)?)?))?;
+            files.push(ManifestFile {
+                path,
+                len,
+                checksum,
+            });
+        }
+
+        Ok(Self { files })
+    }
+
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let reader = BufReader::new(File::open(Self::path(root))?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, root: P) -> Result<()> {
+        let writer = BufWriter::new(File::create(Self::path(root))?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Build the manifest and store it in the folder, e.g. when an index build is done.
+    pub fn build_and_save<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let manifest = Self::build(&root)?;
+        manifest.save(&root)?;
+        Ok(manifest)
+    }
+
+    /// The stored manifest of the folder if it still lists exactly the files in the
+    /// folder with the same sizes. Otherwise the manifest is rebuilt and stored.
+    /// The metadata files can change without changing size, so their checksums
+    /// are always recomputed.
+    pub fn open_or_build<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let root = root.as_ref();
+
+        if let Ok(mut manifest) = Self::open(root) {
+            let files: Vec<_> = manifest
+                .files
+                .iter()
+                .map(|f| (f.path.clone(), f.len))
+                .collect();
+
+            if files == list_files(root)? {
+                let mut changed = false;
+
+                for file in manifest.files.iter_mut().filter(|f| is_mutable(&f.path)) {
+                    let checksum =
+                        checksum(BufReader::new(File::open(resolve(root, &file.path)?)?))?;
+
+                    if checksum != file.checksum {
+                        file.checksum = checksum;
+                        changed = true;
+                    }
+                }
+
+                if changed {
+                    manifest.save(root)?;
+                }
+
+                return Ok(manifest);
+            }
+        }
+
+        Self::build_and_save(root)
+    }
+
+    pub fn total_len(&self) -> u64 {
+        self.files.iter().map(|f| f.len).sum()
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct SyncPlan {
+    /// Files to copy from the source. The mutable files come last.
+    fetch: Vec<ManifestFile>,
+    /// Local files that are not in the source.
+    delete: Vec<String>,
+}
+
+fn plan(local: &Manifest, remote: &Manifest) -> SyncPlan {
+    let local_files: HashMap<_, _> = local
+        .files
+        .iter()
+        .map(|f| (f.path.as_str(), (f.len, f.checksum)))
+        .collect();
+
+    let (mut fetch, mutable): (Vec<_>, Vec<_>) = remote
+        .files
+        .iter()
+        .filter(|f| local_files.get(f.path.as_str()) != Some(&(f.len, f.checksum)))
+        .cloned()
+        .partition(|f| !is_mutable(&f.path));
+    fetch.extend(mutable);
+
+    let remote_paths: HashSet<_> = remote.files.iter().map(|f| f.path.as_str()).collect();
+    let delete = local
+        .files
+        .iter()
+        .filter(|f| !remote_paths.contains(f.path.as_str()))
+        .map(|f| f.path.clone())
+        .collect();
+
+    SyncPlan { fetch, delete }
+}
+
+/// Somewhere an index can be replicated from.
+pub trait FileSource {
+    fn manifest(&self) -> impl Future<Output = Result<Manifest>> + Send;
+
+    fn read_chunk(
+        &self,
+        path: &str,
+        offset: u64,
+        len: u64,
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+}
+
+/// Read at most `len` bytes from the file at `offset`.
+pub fn read_chunk<P: AsRef<Path>>(root: P, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let mut file = File::open(resolve(root.as_ref(), path)?)?;
+    file.seek(SeekFrom::Start(offset))?;
+
+    let mut buf = Vec::new();
+    file.take(len).read_to_end(&mut buf)?;
+
+    Ok(buf)
+}
+
+/// An index folder on a local or mounted disk, e.g. the output of the indexer.
+pub struct LocalSource {
+    root: PathBuf,
+}
+
+impl LocalSource {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl FileSource for LocalSource {
+    async fn manifest(&self) -> Result<Manifest> {
+        Manifest::open_or_build(&self.root)
+    }
+
+    async fn read_chunk(&self, path: &str, offset: u64, len: u64) -> Result<Vec<u8>> {
+        read_chunk(&self.root, path, offset, len)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyncStats {
+    pub copied_files: usize,
+    pub copied_bytes: u64,
+    pub deleted_files: usize,
+}
+
+impl SyncStats {
+    pub fn is_empty(&self) -> bool {
+        self.copied_files == 0 && self.deleted_files == 0
+    }
+}
+
+async fn fetch_file<S: FileSource>(
+    source: &S,
+    root: &Path,
+    file: &ManifestFile,
+    chunk_size: u64,
+) -> Result<()> {
+    let dest = resolve(root, &file.path)?;
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let partial = PathBuf::from(format!("{}{}", dest.display(), PARTIAL_SUFFIX));
+    let mut out = BufWriter::new(File::create(&partial)?);
+    let mut hasher = Xxh3::new();
+    let mut offset = 0;
+
+    while offset < file.len {
+        let chunk = source
+            .read_chunk(&file.path, offset, chunk_size.min(file.len - offset))
+            .await?;
+
+        if chunk.is_empty() {
+            return Err(anyhow!("{} ended before {} bytes", file.path, file.len));
+        }
+
+        hasher.update(&chunk);
+        out.write_all(&chunk)?;
+        offset += chunk.len() as u64;
+    }
+
+    if hasher.digest() != file.checksum {
+        drop(out);
+        fs::remove_file(&partial)?;
+        return Err(anyhow!("checksum mismatch for {}", file.path));
+    }
+
+    out.into_inner()?.sync_all()?;
+    fs::rename(partial, dest)?;
+
+    Ok(())
+}
+
+/// Bring the index folder at `root` up to date with the source.
+pub async fn replicate<S: FileSource>(
+    source: &S,
+    root: &Path,
+    chunk_size: u64,
+) -> Result<SyncStats> {
+    let remote = source.manifest().await?;
+
+    fs::create_dir_all(root)?;
+    let local = Manifest::open_or_build(root)?;
+
+    let plan = plan(&local, &remote);
+    let mut stats = SyncStats::default();
+
+    for file in &plan.fetch {
+        fetch_file(source, root, file, chunk_size.max(1)).await?;
+        stats.copied_files += 1;
+        stats.copied_bytes += file.len;
+    }
+
+    for path in &plan.delete {
+        fs::remove_file(resolve(root, path)?)?;
+        stats.deleted_files += 1;
+    }
+
+    remote.save(root)?;
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, len: u64, checksum: u64) -> ManifestFile {
+        ManifestFile {
+            path: path.to_string(),
+            len,
+            checksum,
+        }
+    }
+
+    #[test]
+    fn plan_copies_changed_files_and_metadata_last() {
+        let local = Manifest {
+            files: vec![
+                file("inverted_index/a.idx", 10, 1),
+                file("inverted_index/b.idx", 5, 2),
+                file("inverted_index/c.idx", 5, 3),
+                file("inverted_index/old.idx", 3, 4),
+                file("inverted_index/meta.json", 100, 5),
+            ],
+        };
+        let remote = Manifest {
+            files: vec![
+                file("inverted_index/meta.json", 100, 6),
+                file("inverted_index/a.idx", 10, 1),
+                file("inverted_index/b.idx", 7, 7),
+                file("inverted_index/c.idx", 5, 8),
+                file("inverted_index/new.idx", 3, 9),
+                file("region_count.json", 20, 10),
+            ],
+        };
+
+        let plan = plan(&local, &remote);
+
+        assert_eq!(
+            plan.fetch,
+            vec![
+                file("inverted_index/b.idx", 7, 7),
+                file("inverted_index/c.idx", 5, 8),
+                file("inverted_index/new.idx", 3, 9),
+                file("inverted_index/meta.json", 100, 6),
+                file("region_count.json", 20, 10),
+            ]
+        );
+        assert_eq!(plan.delete, vec!["inverted_index/old.idx".to_string()]);
+    }
+
+    #[test]
+    fn paths_stay_within_index() {
+        let root = crate::gen_temp_path();
+        fs::create_dir_all(root.join("inverted_index")).unwrap();
+        fs::write(root.join("inverted_index").join("a.idx"), b"0123456789").unwrap();
+        fs::write(root.join("inverted_index").join(".tantivy-meta.lock"), b"").unwrap();
+
+        let manifest = Manifest::build(&root).unwrap();
+        assert_eq!(manifest.files.len(), 1);
+        assert_eq!(manifest.files[0].path, "inverted_index/a.idx");
+        assert_eq!(manifest.files[0].len, 10);
+
+        assert_eq!(
+            read_chunk(&root, "inverted_index/a.idx", 2, 3).unwrap(),
+            b"234".to_vec()
+        );
+
+        assert!(read_chunk(&root, "../secret", 0, 10).is_err());
+        assert!(read_chunk(&root, "/etc/passwd", 0, 10).is_err());
+        assert!(read_chunk(&root, "", 0, 10).is_err());
+    }
+
+    #[tokio::test]
+    async fn replicate_only_changed_files() {
+        let source = crate::gen_temp_path();
+        let dest = crate::gen_temp_path();
+
+        fs::create_dir_all(source.join("segments")).unwrap();
+        fs::write(source.join("segments").join("a.idx"), b"first segment").unwrap();
+        fs::write(source.join("meta.json"), b"{\"segments\": [\"a\"]}").unwrap();
+
+        let stats = replicate(&LocalSource::new(&source), &dest, 4)
+            .await
+            .unwrap();
+        assert_eq!(stats.copied_files, 2);
+        assert_eq!(
+            fs::read(dest.join("segments").join("a.idx")).unwrap(),
+            b"first segment"
+        );
+
+        fs::remove_file(source.join("segments").join("a.idx")).unwrap();
+        fs::write(source.join("segments").join("b.idx"), b"second segment").unwrap();
+        fs::write(source.join("meta.json"), b"{\"segments\": [\"b\"]}").unwrap();
+
+        let stats = replicate(&LocalSource::new(&source), &dest, 4)
+            .await
+            .unwrap();
+        assert_eq!(stats.copied_files, 2);
+        assert_eq!(stats.deleted_files, 1);
+        assert!(!dest.join("segments").join("a.idx").exists());
+        assert_eq!(
+            fs::read(dest.join("meta.json")).unwrap(),
+            b"{\"segments\": [\"b\"]}"
+        );
+
+        let stats = replicate(&LocalSource::new(&source), &dest, 4)
+            .await
+            .unwrap();
+        assert!(stats.is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>
+
+//! Deduplication of the pages in the index by their url.
+//!
+//! The url of a page is replaced by its canonical url when the page is parsed, so
+//! the same page can end up in the index more than once if it was fetched several
+//! times or under different urls, e.g. by different indexing workers. Only the
+//! latest fetch of each url is kept.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
+
+use bloom::combine_u64s;
+use tantivy::{
+    query::{EnableScoring, Explanation, Query, Scorer, Weight},
+    DocId, DocSet, Score, SegmentId, TERMINATED,
+};
+
+use super::InvertedIndex;
+use crate::{
+    prehashed::Prehashed,
+    schema::fast_field::{FastField, FetchTimeMs, UrlHash1, UrlHash2},
+    Result,
+};
+
+struct Page {
+    segment: SegmentId,
+    doc: DocId,
+    fetch_time_ms: u64,
+}
+
+/// Matches a fixed set of documents in each segment.
+#[derive(Debug, Clone)]
+struct DocsQuery {
+    docs: Arc<HashMap<SegmentId, Vec<DocId>>>,
+}
+
+impl DocsQuery {
+    fn new(mut docs: HashMap<SegmentId, Vec<DocId>>) -> Self {
+        for segment_docs in docs.values_mut() {
+            segment_docs.sort_unstable();
+        }
+
+        Self {
+            docs: Arc::new(docs),
+        }
+    }
+}
+
+impl Query for DocsQuery {
+    fn weight(&self, _: EnableScoring) -> tantivy::Result<Box<dyn Weight>> {
+        Ok(Box::new(DocsWeight {
+            docs: Arc::clone(&self.docs),
+        }))
+    }
+}
+
+struct DocsWeight {
+    docs: Arc<HashMap<SegmentId, Vec<DocId>>>,
+}
+
+impl Weight for DocsWeight {
+    fn scorer(
+        &self,
+        reader: &tantivy::SegmentReader,
+        boost: Score,
+    ) -> tantivy::Result<Box<dyn Scorer>> {
+        Ok(Box::new(DocsScorer {
+            docs: self
+                .docs
+                .get(&reader.segment_id())
+                .cloned()
+                .unwrap_or_default(),
+            cursor: 0,
+            score: boost,
+        }))
+    }
+
+    fn explain(&self, reader: &tantivy::SegmentReader, doc: DocId) -> tantivy::Result<Explanation> {
+        let mut scorer = self.scorer(reader, 1.0)?;
+
+        if scorer.seek(doc) != doc {
+            return Err(tantivy::TantivyError::InvalidArgument(format!(
+                "Document #({doc}) does not match"
+            )));
+        }
+
+        Ok(Explanation::new("Matching document", scorer.score()))
+    }
+}
+
+struct DocsScorer {
+    docs: Vec<DocId>,
+    cursor: usize,
+    score: Score,
+}
+
+impl Scorer for DocsScorer {
+    fn score(&mut self) -> Score {
+        self.score
+    }
+}
+
+impl DocSet for DocsScorer {
+    fn advance(&mut self) -> DocId {
+        self.cursor += 1;
+        self.doc()
+    }
+
+    fn doc(&self) -> DocId {
+        self.docs.get(self.cursor).copied().unwrap_or(TERMINATED)
+    }
+
+    fn size_hint(&self) -> u32 {
+        self.docs.len() as u32
+    }
+}
+
+impl InvertedIndex {
+    /// Delete all but the latest fetch of every url in the index and commit the deletes.
+    /// If a url was fetched more than once at the same time, the page in the first
+    /// segment is kept. Returns the number of deleted pages.
+    pub fn dedup_urls(&mut self) -> Result<u64> {
+        self.prepare_writer()?;
+
+        let tv_searcher = self.reader.searcher();
+
+        let mut latest: HashMap<Prehashed, Page> = HashMap::new();
+        let mut duplicates: HashMap<SegmentId, Vec<DocId>> = HashMap::new();
+
+        for segment in tv_searcher.segment_readers() {
+            let fast_fields = segment.fast_fields();
+            let url_hash1 = fast_fields.u64(UrlHash1.name())?;
+            let url_hash2 = fast_fields.u64(UrlHash2.name())?;
+            let fetch_time_ms = fast_fields.u64(FetchTimeMs.name())?;
+
+            for doc in segment.doc_ids_alive() {
+                let hash: Prehashed =
+                    combine_u64s([url_hash1.values.get_val(doc), url_hash2.values.get_val(doc)])
+                        .into();
+
+                let page = Page {
+                    segment: segment.segment_id(),
+                    doc,
+                    fetch_time_ms: fetch_time_ms.values.get_val(doc),
+                };
+
+                let stale = match latest.entry(hash) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(page);
+                        continue;
+                    }
+                    Entry::Occupied(mut entry) => {
+                        if page.fetch_time_ms > entry.get().fetch_time_ms {
+                            entry.insert(page)
+                        } else {
+                            page
+                        }
+                    }
+                };
+
+                duplicates.entry(stale.segment).or_default().push(stale.doc);
+            }
+        }
+
+        let num_deleted = duplicates.values().map(|docs| docs.len() as u64).sum();
+
+        if num_deleted > 0 {
+            self.delete(Box::new(DocsQuery::new(duplicates)))?;
+            self.commit()?;
+        }
+
+        Ok(num_deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::webpage::Webpage;
+
+    use super::*;
+
+    fn page(title: &str, url: &str, fetch_time_ms: u64) -> Webpage {
+        let mut webpage = Webpage::test_parse(
+            &format!(
+                r#"
+            <html>
+                <head>
+                    <title>{title}</title>
+                </head>
+                <body>
+                    {}
+                </body>
+            </html>
+            "#,
+                crate::rand_words(100)
+            ),
+            url,
+        )
+        .unwrap();
+
+        webpage.fetch_time_ms = fetch_time_ms;
+        webpage
+    }
+
+    #[test]
+    fn keep_latest_fetch() {
+        let index1 = InvertedIndex::temporary().expect("Unable to open index");
+        index1
+            .insert(&page("Old", "https://www.example.com", 1))
+            .unwrap();
+        index1
+            .insert(&page("Other", "https://www.other.com", 1))
+            .unwrap();
+
+        let index2 = InvertedIndex::temporary().expect("Unable to open index");
+        index2
+            .insert(&page("New", "https://www.example.com", 2))
+            .unwrap();
+        index2
+            .insert(&page("Other again", "https://www.other.com", 1))
+            .unwrap();
+
+        let mut index = index1.merge(index2);
+        index.commit().unwrap();
+        assert_eq!(index.pages().count(), 4);
+
+        assert_eq!(index.dedup_urls().unwrap(), 2);
+
+        let mut titles: Vec<_> = index.pages().map(|page| page.title).collect();
+        titles.sort();
+        assert_eq!(titles.len(), 2);
+        assert_eq!(titles[0], "New");
+        assert!(titles[1].starts_with("Other"));
+
+        assert_eq!(index.dedup_urls().unwrap(), 0);
+    }
+}
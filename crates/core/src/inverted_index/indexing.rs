@@ -134,7 +134,7 @@ impl InvertedIndex {
         Ok(())
     }
 
-    fn delete(&self, query: Box<dyn tantivy::query::Query>) -> Result<()> {
+    pub(super) fn delete(&self, query: Box<dyn tantivy::query::Query>) -> Result<()> {
         self.writer
             .as_ref()
             .expect("writer has not been prepared")
@@ -26,6 +26,7 @@
 //! This allows us to perform more advanced queries than just term lookups,
 //! but the principle is the same.
 
+mod dedup;
 mod indexing;
 mod search;
 
@@ -40,14 +41,16 @@ use tantivy::tokenizer::TokenizerManager;
 use tantivy::{IndexReader, IndexWriter, TantivyDocument};
 
 use crate::collector::{approx_count, Hashes};
-use crate::config::SnippetConfig;
+use crate::config::{SnippetConfig, TieredStorageConfig};
 use crate::fastfield_reader::FastFieldReader;
 
 use crate::ranking::initial::Score;
+use crate::ranking::SignalEnumDiscriminants;
 
 use crate::schema::text_field::TextField;
 use crate::schema::{fast_field, text_field, FastFieldEnum, Field, TextFieldEnum};
 use crate::snippet::TextSnippet;
+use crate::tiered_storage;
 use crate::tokenizer::{
     BigramTokenizer, Identity, JsonField, SiteOperatorUrlTokenizer, Stemmed, TrigramTokenizer,
 };
@@ -56,6 +59,7 @@ use crate::webpage::region::Region;
 use crate::webpage::schema_org;
 use crate::Result;
 use crate::{schema::create_schema, tokenizer::Tokenizer};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
@@ -164,6 +168,46 @@ impl InvertedIndex {
             tantivy::Index::create(mmap_directory, schema.clone(), index_settings)?
         };
 
+        Self::from_tantivy_index(path, tantivy_index)
+    }
+
+    /// Open an existing index where some of the segment files may have been
+    /// offloaded to object storage.
+    pub fn open_tiered<P: AsRef<Path>>(path: P, config: &TieredStorageConfig) -> Result<Self> {
+        let directory = tiered_storage::open_directory(&path, config)?;
+        let tantivy_index = tantivy::Index::open(directory)?;
+
+        Self::from_tantivy_index(path, tantivy_index)
+    }
+
+    /// Move the files of all segments in the index to object storage. Returns the
+    /// number of files that were offloaded.
+    pub fn offload_segments<P: AsRef<Path>>(
+        path: P,
+        config: &TieredStorageConfig,
+    ) -> Result<usize> {
+        let directory = tiered_storage::open_directory(&path, config)?;
+        let metas = tantivy::Index::open(directory.clone())?.load_metas()?;
+
+        let mut num_offloaded = 0;
+
+        for segment in metas.segments {
+            for file in segment.list_files() {
+                if directory.is_cold(&file) || !path.as_ref().join(&file).exists() {
+                    continue;
+                }
+
+                directory.offload(&file)?;
+                num_offloaded += 1;
+            }
+        }
+
+        Ok(num_offloaded)
+    }
+
+    fn from_tantivy_index<P: AsRef<Path>>(path: P, tantivy_index: tantivy::Index) -> Result<Self> {
+        let schema = create_schema();
+
         register_tokenizers(tantivy_index.tokenizers());
 
         let reader: IndexReader = tantivy_index.reader_builder().try_into()?;
@@ -244,6 +288,9 @@ pub struct RetrievedWebpage {
     pub likely_has_paywall: bool,
     pub recipe_first_ingredient_tag_id: Option<String>,
     pub keywords: Vec<String>,
+    pub breadcrumb: Vec<String>,
+    /// ISO 639-3 code of the language detected in the text of the page.
+    pub language: Option<String>,
 }
 impl RetrievedWebpage {
     pub fn description(&self) -> Option<&String> {
@@ -251,6 +298,18 @@ impl RetrievedWebpage {
     }
 }
 
+/// The query independent ranking signals that are stored for a page in the index.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
+pub struct StoredSignals {
+    pub url: String,
+    pub signals: HashMap<SignalEnumDiscriminants, f64>,
+    pub region: Region,
+    #[bincode(with_serde)]
+    pub updated_time: Option<NaiveDateTime>,
+    pub likely_has_ads: bool,
+    pub likely_has_paywall: bool,
+}
+
 fn str_value(name: &str, value: &tantivy::schema::FieldValue) -> String {
     value
         .value()
@@ -324,6 +383,20 @@ impl From<TantivyDocument> for RetrievedWebpage {
                     let keywords = str_value(text_field::Keywords.name(), value);
                     webpage.keywords = keywords.split('\n').map(|s| s.to_string()).collect();
                 }
+                Some(Field::Text(TextFieldEnum::Language(_))) => {
+                    let language = str_value(text_field::Language.name(), value);
+                    if !language.is_empty() {
+                        webpage.language = Some(language);
+                    }
+                }
+                Some(Field::Text(TextFieldEnum::Breadcrumb(_))) => {
+                    let breadcrumb = str_value(text_field::Breadcrumb.name(), value);
+                    webpage.breadcrumb = breadcrumb
+                        .split('\n')
+                        .filter(|s| !s.is_empty())
+                        .map(|s| s.to_string())
+                        .collect();
+                }
                 _ => {}
             }
         }
@@ -1045,6 +1118,55 @@ mod tests {
         let webpage = index.get_webpage("https://www.example.com").unwrap();
         assert_eq!(webpage.title, "News website".to_string());
         assert_eq!(webpage.url, "https://www.example.com/".to_string());
+        assert_eq!(webpage.language.as_deref(), Some("eng"));
+    }
+
+    #[test]
+    fn stored_signals() {
+        let mut index = InvertedIndex::temporary().expect("Unable to open index");
+
+        let mut webpage = Webpage::test_parse(
+            &format!(
+                r#"
+                    <html>
+                        <head>
+                            <title>Example website</title>
+                        </head>
+                        <body>
+                            {CONTENT} test
+                        </body>
+                    </html>
+                "#
+            ),
+            "https://www.example.com",
+        )
+        .unwrap();
+        webpage.host_centrality = 0.5;
+
+        index.insert(&webpage).expect("failed to insert webpage");
+        index.commit().expect("failed to commit index");
+
+        assert!(index
+            .stored_signals("https://www.example.com/other")
+            .unwrap()
+            .is_none());
+
+        let stored = index
+            .stored_signals("https://www.example.com")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(stored.url, "https://www.example.com/".to_string());
+        assert!((stored.signals[&SignalEnumDiscriminants::HostCentrality] - 0.5).abs() < 0.0001);
+        assert!(stored
+            .signals
+            .contains_key(&SignalEnumDiscriminants::IsHomepage));
+        assert!(!stored
+            .signals
+            .contains_key(&SignalEnumDiscriminants::Region));
+        assert!(!stored
+            .signals
+            .contains_key(&SignalEnumDiscriminants::Bm25Title));
     }
 
     #[test]
@@ -1379,4 +1501,38 @@ mod tests {
         assert_eq!(webpages[0].title, "C++");
         assert_eq!(webpages[0].url, "https://www.a.com/");
     }
+
+    #[test]
+    fn all_pages() {
+        let mut index = InvertedIndex::temporary().expect("Unable to open index");
+
+        for url in ["https://www.a.com", "https://www.b.com"] {
+            index
+                .insert(
+                    &Webpage::test_parse(
+                        &format!(
+                            r#"
+                            <html>
+                                <head>
+                                    <title>Test website</title>
+                                </head>
+                                <body>
+                                    {CONTENT}
+                                </body>
+                            </html>
+                        "#
+                        ),
+                        url,
+                    )
+                    .unwrap(),
+                )
+                .expect("failed to insert webpage");
+        }
+        index.commit().expect("failed to commit index");
+
+        let mut urls: Vec<_> = index.pages().map(|page| page.url).collect();
+        urls.sort();
+
+        assert_eq!(urls, vec!["https://www.a.com/", "https://www.b.com/"]);
+    }
 }
@@ -14,7 +14,9 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
-use super::{DocAddress, InitialSearchResult, InvertedIndex, RetrievedWebpage, WebpagePointer};
+use super::{
+    DocAddress, InitialSearchResult, InvertedIndex, RetrievedWebpage, StoredSignals, WebpagePointer,
+};
 use itertools::Itertools;
 use tantivy::collector::Count;
 
@@ -31,7 +33,7 @@ use crate::highlighted::HighlightedFragment;
 use crate::query::shortcircuit::ShortCircuitQuery;
 use crate::query::Query;
 use crate::ranking::pipeline::LocalRecallRankingWebpage;
-use crate::ranking::SignalComputer;
+use crate::ranking::{Signal, SignalComputer, SignalEnum};
 use crate::schema::{fast_field, text_field, FastFieldEnum, Field, TextFieldEnum};
 use crate::search_ctx::Ctx;
 use crate::snippet;
@@ -254,9 +256,33 @@ impl InvertedIndex {
         Ok(RetrievedWebpage::from(doc))
     }
 
-    pub(crate) fn get_webpage(&self, url: &str) -> Option<RetrievedWebpage> {
-        let url = Url::parse(url).ok()?;
+    /// All pages in the index. The stored document of every page is read,
+    /// so this is only meant for offline jobs.
+    pub fn pages(&self) -> impl Iterator<Item = RetrievedWebpage> {
         let tv_searcher = self.reader.searcher();
+
+        (0..tv_searcher.segment_readers().len() as u32).flat_map(move |segment_ord| {
+            let tv_searcher = tv_searcher.clone();
+            let docs: Vec<_> = tv_searcher
+                .segment_reader(segment_ord)
+                .doc_ids_alive()
+                .collect();
+
+            docs.into_iter().filter_map(move |doc_id| {
+                tv_searcher
+                    .doc::<TantivyDocument>(tantivy::DocAddress::new(segment_ord, doc_id))
+                    .ok()
+                    .map(RetrievedWebpage::from)
+            })
+        })
+    }
+
+    fn doc_address_by_url(
+        &self,
+        tv_searcher: &tantivy::Searcher,
+        url: &str,
+    ) -> Option<tantivy::DocAddress> {
+        let url = Url::parse(url).ok()?;
         let field = tv_searcher
             .schema()
             .get_field(Field::Text(TextFieldEnum::from(text_field::UrlNoTokenizer)).name())
@@ -270,8 +296,54 @@ impl InvertedIndex {
             .search(&query, &tantivy::collector::TopDocs::with_limit(1))
             .unwrap();
 
-        res.pop()
-            .map(|(_, doc)| self.retrieve_doc(doc.into(), &tv_searcher).unwrap())
+        res.pop().map(|(_, doc)| doc)
+    }
+
+    pub(crate) fn get_webpage(&self, url: &str) -> Option<RetrievedWebpage> {
+        let tv_searcher = self.reader.searcher();
+
+        self.doc_address_by_url(&tv_searcher, url)
+            .map(|doc| self.retrieve_doc(doc.into(), &tv_searcher).unwrap())
+    }
+
+    /// All the stored, query independent signals of the page with the given url.
+    pub(crate) fn stored_signals(&self, url: &str) -> Result<Option<StoredSignals>> {
+        let tv_searcher = self.reader.searcher();
+
+        let Some(doc) = self.doc_address_by_url(&tv_searcher, url) else {
+            return Ok(None);
+        };
+
+        let webpage = self.retrieve_doc(doc.into(), &tv_searcher)?;
+
+        let mut computer = SignalComputer::new(None);
+        computer.set_current_timestamp(chrono::Utc::now().timestamp().max(0) as usize);
+        computer.register_segment(
+            &tv_searcher,
+            tv_searcher.segment_reader(doc.segment_ord),
+            &self.fastfield_reader,
+        )?;
+
+        let signals = SignalEnum::all()
+            // the score of the region signal depends on the region selected in the query
+            .filter(|signal| {
+                signal.as_fastfield().is_some() && !matches!(signal, SignalEnum::Region(_))
+            })
+            .filter_map(|signal| {
+                signal
+                    .compute(doc.doc_id, &computer)
+                    .map(|value| (signal.into(), value))
+            })
+            .collect();
+
+        Ok(Some(StoredSignals {
+            url: webpage.url,
+            signals,
+            region: webpage.region,
+            updated_time: webpage.updated_time,
+            likely_has_ads: webpage.likely_has_ads,
+            likely_has_paywall: webpage.likely_has_paywall,
+        }))
     }
 
     pub(crate) fn get_homepage(&self, url: &Url) -> Option<RetrievedWebpage> {
@@ -46,10 +46,12 @@ pub mod autosuggest;
 pub mod bangs;
 mod bincode_utils;
 pub mod canon_index;
+pub mod cloaking;
 mod collector;
 pub mod config;
 pub mod crawler;
 pub mod distributed;
+pub mod embeddings;
 pub mod entity_index;
 mod enum_map;
 pub mod executor;
@@ -57,20 +59,27 @@ mod external_sort;
 mod fastfield_reader;
 pub mod feed;
 mod highlighted;
+pub mod host_about;
+pub mod host_clusters;
 mod human_website_annotations;
 pub mod hyperloglog;
 pub mod image_store;
 mod improvement;
 pub mod index;
+pub mod index_replication;
 mod intmap;
 mod kahan_sum;
 mod leaky_queue;
 mod live_index;
+pub mod localization;
 mod metrics;
 mod models;
 pub mod naive_bayes;
+pub mod outlink_audit;
+pub mod outlink_profile;
 pub mod prehashed;
 mod query;
+pub mod query_log;
 mod rake;
 pub mod ranking;
 mod schema;
@@ -82,7 +91,10 @@ pub mod similar_hosts;
 mod snippet;
 mod stopwords;
 pub mod summarizer;
+pub mod threat_list;
+pub mod tiered_storage;
 mod tokenizer;
+pub mod trec;
 #[allow(unused)]
 mod ttl_cache;
 pub mod warc;
@@ -22,7 +22,7 @@ use url::Url;
 
 use crate::{
     config::CrawlerConfig,
-    crawler::{reqwest_client, JobExecutor, RetrieableUrl, WeightedUrl, WorkerJob},
+    crawler::{page_client, reqwest_client, JobExecutor, RetrieableUrl, WeightedUrl, WorkerJob},
     feed::{
         self,
         scheduler::{Domain, DomainFeeds, Split},
@@ -42,6 +42,7 @@ pub struct Crawler {
     downloaded_db: DownloadedDb,
     config: Arc<CrawlerConfig>,
     client: reqwest::Client,
+    page_client: reqwest::Client,
 }
 
 impl Crawler {
@@ -52,6 +53,7 @@ impl Crawler {
         config: Arc<CrawlerConfig>,
     ) -> Result<Self> {
         let client = reqwest_client(&config)?;
+        let page_client = page_client(&config)?;
 
         Ok(Self {
             feeds: split.into(),
@@ -59,6 +61,7 @@ impl Crawler {
             downloaded_db,
             config,
             client,
+            page_client,
         })
     }
 
@@ -81,6 +84,7 @@ impl Crawler {
         let executor = JobExecutor::new(
             job,
             self.client.clone(),
+            self.page_client.clone(),
             self.config.clone(),
             self.indexer.clone(),
         );
@@ -0,0 +1,259 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Localization of the strings and numbers we return from the api.
+//! The language is chosen by the client with the `hl` parameter and the
+//! region (used for the default optic and safe search) with the `gl` parameter.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use optics::Optic;
+
+use crate::{config::ApiRegionDefaults, webpage::region::Region, Result};
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Language {
+    #[default]
+    English,
+    Danish,
+    French,
+    German,
+    Spanish,
+}
+
+impl Language {
+    /// Parse a language tag like `da`, `de-DE` or `en_GB`. Only the primary
+    /// language is used, so regional variants share the same translations.
+    pub fn from_hl(hl: &str) -> Option<Self> {
+        let primary = hl.split(['-', '_']).next()?.to_lowercase();
+
+        match primary.as_str() {
+            "en" => Some(Language::English),
+            "da" => Some(Language::Danish),
+            "fr" => Some(Language::French),
+            "de" => Some(Language::German),
+            "es" => Some(Language::Spanish),
+            _ => None,
+        }
+    }
+
+    pub fn hl(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Danish => "da",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Spanish => "es",
+        }
+    }
+
+    fn decimal_separator(&self) -> char {
+        match self {
+            Language::English => '.',
+            Language::Danish | Language::French | Language::German | Language::Spanish => ',',
+        }
+    }
+
+    fn group_separator(&self) -> char {
+        match self {
+            Language::English => ',',
+            Language::Danish | Language::German | Language::Spanish => '.',
+            Language::French => '\u{202f}',
+        }
+    }
+
+    /// Format a number with at most two decimals. English numbers keep the plain
+    /// formatting without grouping. Other languages use their own separators, and
+    /// numbers below 10.000 are not grouped as they are often years or similar.
+    pub fn format_number(&self, num: f64) -> String {
+        let formatted = if num.fract() > 0.01 {
+            format!("{:.2}", num)
+        } else {
+            num.to_string()
+        };
+
+        if matches!(self, Language::English) {
+            return formatted;
+        }
+
+        // very small or large numbers are formatted with an exponent
+        if formatted.contains(['e', 'E']) || !num.is_finite() {
+            return formatted;
+        }
+
+        let (sign, formatted) = match formatted.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", formatted.as_str()),
+        };
+
+        let (integer, fraction) = match formatted.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (formatted, None),
+        };
+
+        let mut res = sign.to_string();
+
+        if integer.len() > 4 {
+            for (i, c) in integer.chars().enumerate() {
+                if i > 0 && (integer.len() - i) % 3 == 0 {
+                    res.push(self.group_separator());
+                }
+                res.push(c);
+            }
+        } else {
+            res.push_str(integer);
+        }
+
+        if let Some(fraction) = fraction {
+            res.push(self.decimal_separator());
+            res.push_str(fraction);
+        }
+
+        res
+    }
+
+    pub fn hours_ago(&self, hours: i64) -> String {
+        match (self, hours <= 1) {
+            (Language::English, true) => "1 hour ago".to_string(),
+            (Language::English, false) => format!("{hours} hours ago"),
+            (Language::Danish, true) => "1 time siden".to_string(),
+            (Language::Danish, false) => format!("{hours} timer siden"),
+            (Language::French, true) => "il y a 1 heure".to_string(),
+            (Language::French, false) => format!("il y a {hours} heures"),
+            (Language::German, true) => "vor 1 Stunde".to_string(),
+            (Language::German, false) => format!("vor {hours} Stunden"),
+            (Language::Spanish, true) => "hace 1 hora".to_string(),
+            (Language::Spanish, false) => format!("hace {hours} horas"),
+        }
+    }
+
+    pub fn days_ago(&self, days: i64) -> String {
+        match (self, days <= 1) {
+            (Language::English, true) => "1 day ago".to_string(),
+            (Language::English, false) => format!("{days} days ago"),
+            (Language::Danish, true) => "1 dag siden".to_string(),
+            (Language::Danish, false) => format!("{days} dage siden"),
+            (Language::French, true) => "il y a 1 jour".to_string(),
+            (Language::French, false) => format!("il y a {days} jours"),
+            (Language::German, true) => "vor 1 Tag".to_string(),
+            (Language::German, false) => format!("vor {days} Tagen"),
+            (Language::Spanish, true) => "hace 1 día".to_string(),
+            (Language::Spanish, false) => format!("hace {days} días"),
+        }
+    }
+
+    pub fn format_date(&self, date: NaiveDateTime) -> String {
+        let format = match self {
+            Language::English => "%d. %b. %Y",
+            Language::Danish | Language::German => "%d.%m.%Y",
+            Language::French | Language::Spanish => "%d/%m/%Y",
+        };
+
+        date.format(format).to_string()
+    }
+}
+
+/// The default optic and safe search setting for each region. These are used
+/// when the client does not specify them in the query.
+#[derive(Default)]
+pub struct RegionDefaults {
+    defaults: HashMap<Region, (Option<bool>, Option<String>)>,
+}
+
+impl RegionDefaults {
+    pub fn open(config: &HashMap<String, ApiRegionDefaults>) -> Result<Self> {
+        let mut defaults = HashMap::new();
+
+        for (gl, region_defaults) in config {
+            let region = Region::from_gl(gl)?;
+
+            let optic = match &region_defaults.optic_path {
+                Some(path) => {
+                    let optic = std::fs::read_to_string(path)?;
+                    // fail at startup instead of on every query
+                    Optic::parse(&optic)?;
+                    Some(optic)
+                }
+                None => None,
+            };
+
+            defaults.insert(region, (region_defaults.safe_search, optic));
+        }
+
+        Ok(Self { defaults })
+    }
+
+    pub fn safe_search(&self, region: Region) -> Option<bool> {
+        self.defaults
+            .get(&region)
+            .and_then(|(safe_search, _)| *safe_search)
+    }
+
+    pub fn optic(&self, region: Region) -> Option<&str> {
+        self.defaults
+            .get(&region)
+            .and_then(|(_, optic)| optic.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hl() {
+        assert_eq!(Language::from_hl("da"), Some(Language::Danish));
+        assert_eq!(Language::from_hl("de-AT"), Some(Language::German));
+        assert_eq!(Language::from_hl("EN_gb"), Some(Language::English));
+        assert_eq!(Language::from_hl("xx"), None);
+        assert_eq!(Language::from_hl(""), None);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Language::English.format_number(4.0), "4");
+        assert_eq!(Language::English.format_number(2024.0), "2024");
+        assert_eq!(Language::English.format_number(1234567.891), "1234567.89");
+        assert_eq!(Language::English.format_number(-3.5), "-3.5");
+        assert_eq!(Language::German.format_number(1234567.891), "1.234.567,89");
+        assert_eq!(Language::Danish.format_number(-3.5), "-3,5");
+        assert_eq!(
+            Language::French.format_number(12345.0),
+            "12\u{202f}345".to_string()
+        );
+    }
+
+    #[test]
+    fn relative_dates() {
+        assert_eq!(Language::English.days_ago(2), "2 days ago");
+        assert_eq!(Language::Danish.hours_ago(1), "1 time siden");
+        assert_eq!(Language::Spanish.days_ago(3), "hace 3 días");
+    }
+}
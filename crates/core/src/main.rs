@@ -17,7 +17,8 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use serde::de::DeserializeOwned;
 use std::fs;
-use std::path::Path;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use stract::config;
 use stract::entrypoint::autosuggest_scrape::{self, Gl};
 
@@ -25,9 +26,12 @@ use stract::entrypoint::autosuggest_scrape::{self, Gl};
 use stract::entrypoint::configure;
 
 use stract::entrypoint::{
-    self, api, entity_search_server, safety_classifier, search_server, webgraph_server,
+    self, api, entity_search_server, index_file_server, safety_classifier, search_server,
+    webgraph_server,
+};
+use stract::webgraph::{
+    DegreeDirection, ExportFormat, ImportFormat, Node, WebgraphBuilder, WebgraphImporter,
 };
-use stract::webgraph::WebgraphBuilder;
 use tracing_subscriber::prelude::*;
 
 #[cfg(not(target_env = "msvc"))]
@@ -43,6 +47,11 @@ static GLOBAL: Jemalloc = Jemalloc;
 struct Args {
     #[clap(subcommand)]
     command: Commands,
+
+    /// Rules file (ClearURLs format) used to strip tracking parameters from urls.
+    /// The bundled rules are used if not specified.
+    #[clap(long, global = true)]
+    url_rules: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -77,6 +86,24 @@ enum Commands {
         config_path: String,
     },
 
+    /// Promote a standby search server to serve queries for its shard, e.g. when the
+    /// active search server of the shard has failed.
+    PromoteSearchServer {
+        host: SocketAddr,
+    },
+
+    /// Replicate an index incrementally, e.g. from the indexer output to the search servers.
+    IndexReplication {
+        #[clap(subcommand)]
+        options: IndexReplicationOptions,
+    },
+
+    /// Move index segments to object storage so search servers only keep the hot parts on local disk.
+    TieredStorage {
+        #[clap(subcommand)]
+        options: TieredStorageOptions,
+    },
+
     /// Deploy the entity search server.
     EntitySearchServer {
         config_path: String,
@@ -96,6 +123,17 @@ enum Commands {
         output_dir: String,
     },
 
+    /// Build the autosuggest fst from a csv of queries. The api can load the fst
+    /// with its admin endpoint without a restart.
+    AutosuggestBuild {
+        queries_csv_path: String,
+        output_path: String,
+
+        /// Toml file with the filter of the api config. The blocked queries are left out of the fst.
+        #[clap(long)]
+        filter_config: Option<String>,
+    },
+
     /// Deploy the crawler.
     Crawler {
         #[clap(subcommand)]
@@ -108,6 +146,39 @@ enum Commands {
         options: SafetyClassifierOptions,
     },
 
+    /// Extract features (language, length, schema.org types, outlinks and quality score)
+    /// for the pages in warc files and write them as parquet files.
+    PageFeatures {
+        config_path: String,
+    },
+
+    /// Count the known, crawled and indexed hosts per top level domain and the indexed
+    /// pages per language, and write the counts as a coverage report.
+    CoverageReport {
+        config_path: String,
+    },
+
+    /// Analyze the homepage of each host in warc files (title, description, organization
+    /// and whether it links to a contact page or imprint) and store the descriptors per host.
+    HostAbout {
+        config_path: String,
+    },
+
+    /// Run the api, search server, live index and webgraphs in a single process.
+    /// The data directory is populated with a small index on the first run.
+    AllInOne {
+        #[clap(long)]
+        data_dir: String,
+
+        /// Address of the api and lite frontend.
+        #[clap(long)]
+        host: Option<SocketAddr>,
+
+        /// Build the index from this warc file instead of the bundled pages.
+        #[clap(long)]
+        warc: Option<String>,
+    },
+
     /// Setup dev environment.
     #[cfg(feature = "dev")]
     Configure {
@@ -126,6 +197,24 @@ enum Commands {
         config_path: String,
     },
 
+    /// Run a set of queries against an index, write the results as a TREC run file
+    /// and compute nDCG and MRR if relevance judgements are provided.
+    Evaluate {
+        config_path: String,
+    },
+
+    /// Anonymize raw query logs and aggregate them into counts for autosuggest,
+    /// related searches and evaluation topics.
+    QueryLog {
+        config_path: String,
+    },
+
+    /// Ingest malware and phishing lists (e.g. URLhaus or OpenPhish) into the store
+    /// that is used to annotate or filter flagged search results.
+    ThreatLists {
+        config_path: String,
+    },
+
     // Commands to compute distributed graph algorithms.
     Ampc {
         #[clap(subcommand)]
@@ -133,6 +222,32 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum TieredStorageOptions {
+    /// Upload the segment files of the index in a search server config to the object
+    /// storage in its `tiered_storage` section and remove them from local disk.
+    Offload { config_path: String },
+}
+
+#[derive(Subcommand)]
+enum IndexReplicationOptions {
+    /// Serve the files of an index so other nodes can pull them.
+    Serve {
+        index_path: String,
+        host: SocketAddr,
+    },
+
+    /// Copy the files that are missing or changed in the source to the local index
+    /// and delete the ones that are no longer in the source. The source is either the
+    /// address of an index file server or a local folder.
+    Pull {
+        source: String,
+        index_path: String,
+        #[clap(long, default_value_t = stract::config::defaults::Standby::chunk_size_bytes())]
+        chunk_size_bytes: u64,
+    },
+}
+
 #[derive(Subcommand)]
 enum AmpcOptions {
     /// Start a node for the distributed hash table (DHT).
@@ -180,6 +295,10 @@ enum Crawler {
 
     /// Create a crawl plan.
     Plan { config_path: String },
+
+    /// Refetch a sample of pages from each host with both a browser and the crawler user agent
+    /// to find hosts that serve different content to the crawler (cloaking).
+    CloakingAudit { config_path: String },
 }
 
 /// Commands to train or run inference on the classifier that predicts if a webpage is NSFW or SFW.
@@ -201,12 +320,74 @@ enum CentralityMode {
     Host {
         webgraph_path: String,
         output_path: String,
+        /// Ignore links that have not been seen in a crawl for this many days.
+        #[clap(long)]
+        max_link_age_days: Option<u32>,
+    },
+    /// Update the harmonic centrality of the host webgraph with the nodes that gained or lost
+    /// edges in the batches merged since the last update. Falls back to a full rebuild when
+    /// too much of the graph has changed or after a number of incremental updates.
+    HostIncremental {
+        webgraph_path: String,
+        output_path: String,
+        /// Recompute the nodes that are at most this many hops from a changed node.
+        #[clap(long, default_value_t = stract::config::defaults::IncrementalHarmonic::max_hops())]
+        max_hops: u8,
+        /// Rebuild from scratch if the update would read more than this fraction of the nodes.
+        #[clap(long, default_value_t = stract::config::defaults::IncrementalHarmonic::max_affected_fraction())]
+        max_affected_fraction: f64,
+        /// Rebuild from scratch after this many incremental updates.
+        #[clap(long, default_value_t = stract::config::defaults::IncrementalHarmonic::full_rebuild_interval())]
+        full_rebuild_interval: usize,
     },
     /// Calculate metrics for the page webgraph.
     Page {
         webgraph_path: String,
         output_path: String,
     },
+    /// Calculate personalized PageRank for the host webgraph, where the random surfer
+    /// restarts from the hosts in `seeds_path` (one host per line).
+    Personalized {
+        webgraph_path: String,
+        seeds_path: String,
+        output_path: String,
+        /// Probability of restarting from a seed in each step.
+        #[clap(long, default_value_t = stract::config::defaults::PersonalizedPageRank::alpha())]
+        alpha: f64,
+        #[clap(long, default_value_t = stract::config::defaults::PersonalizedPageRank::iterations())]
+        iterations: usize,
+    },
+    /// Estimate how many nodes are within each number of hops of every node in a webgraph
+    /// (the neighborhood function) and export it as csv.
+    Neighborhood {
+        webgraph_path: String,
+        output_path: String,
+        #[clap(long, default_value_t = stract::config::defaults::NeighborhoodFunction::max_hops())]
+        max_hops: u8,
+        /// Count the nodes that can reach each node instead of the nodes it can reach.
+        #[clap(long)]
+        ingoing: bool,
+    },
+    /// Estimate the betweenness centrality of the nodes in a webgraph from the shortest paths of
+    /// randomly sampled pivots.
+    Betweenness {
+        webgraph_path: String,
+        output_path: String,
+        #[clap(long, default_value_t = stract::config::defaults::Betweenness::num_pivots())]
+        num_pivots: usize,
+    },
+    /// Calculate TrustRank for the host webgraph from a whitelist of trusted hosts (one host per line).
+    /// The output can be used in place of the harmonic centrality of the hosts.
+    TrustRank {
+        webgraph_path: String,
+        whitelist_path: String,
+        output_path: String,
+        /// Fraction of the trust that is passed on for each hop.
+        #[clap(long, default_value_t = stract::config::defaults::TrustRank::decay())]
+        decay: f64,
+        #[clap(long, default_value_t = stract::config::defaults::TrustRank::iterations())]
+        iterations: usize,
+    },
 }
 
 #[derive(Subcommand)]
@@ -223,9 +404,85 @@ enum WebgraphOptions {
         merge_all_segments: bool,
     },
 
+    /// Check the integrity of a webgraph without modifying it. Reports nodes that are missing
+    /// from the id to node mapping, label blocks that can't be read and segment folders that
+    /// are not in the metadata.
+    Verify { path: String },
+
+    /// Calculate statistics about the shape of the graph, like its degree distributions,
+    /// reciprocity and average path length. The statistics are saved in the graph folder.
+    Stats { path: String },
+
     /// Deploy the webgraph server. The webgraph server is responsible for serving the webgraph to the search servers.
     /// This is e.g. used to find similar sites etc.
     Server { config_path: String },
+
+    /// Cross-reference the destinations of all links in the page webgraph with their crawl status
+    /// to find the ratio of broken and redirected outlinks for each host.
+    OutlinkAudit { config_path: String },
+
+    /// Classify the outgoing links of each host in the page webgraph and flag hosts
+    /// that look like affiliate sites, link rotators or members of private blog networks.
+    OutlinkProfile { config_path: String },
+
+    /// Assign each host in the host webgraph to a cluster of related hosts.
+    /// The clusters are used to diversify the search results.
+    HostClusters { config_path: String },
+
+    /// Detect communities of densely connected nodes with the Louvain method.
+    /// The community of each node is stored in the folder of the graph.
+    Communities { path: String },
+
+    /// Extract the subgraph induced by the nodes within a number of hops from a node,
+    /// e.g. to visualize the ego network of a host. The subgraph is written as graphviz
+    /// if the output path ends with `.dot`, as an edge list if it ends with `.csv` and
+    /// as a new webgraph otherwise.
+    Neighborhood {
+        path: String,
+        node: String,
+        output_path: String,
+
+        #[clap(long, default_value_t = 2)]
+        hops: u8,
+
+        /// Follow the ingoing edges instead of the outgoing edges.
+        #[clap(long)]
+        ingoing: bool,
+
+        #[clap(long, default_value_t = stract::webgraph::DEFAULT_MAX_NEIGHBORHOOD_NODES)]
+        max_nodes: usize,
+    },
+
+    /// Export all edges of a webgraph with their labels and rel flags, so the graph can be
+    /// loaded into other graph tools. The format is one of `graphml`, `tsv` and `parquet`.
+    Export {
+        path: String,
+        output_path: String,
+
+        #[clap(long, default_value_t = ExportFormat::Tsv)]
+        format: ExportFormat,
+    },
+
+    /// Build a webgraph from edge lists, e.g. to compute centrality on the host graph
+    /// published by Common Crawl. The format is one of `tsv`, `parquet` and `common-crawl`.
+    /// Text files are decompressed if their name ends with `.gz`.
+    Import {
+        output_path: String,
+
+        #[clap(required = true)]
+        files: Vec<String>,
+
+        #[clap(long, default_value_t = ImportFormat::Tsv)]
+        format: ImportFormat,
+
+        /// Vertex files that map the ids in the Common Crawl edge files to hosts.
+        #[clap(long)]
+        vertices: Vec<String>,
+
+        /// Number of writers the edges are inserted into in parallel.
+        #[clap(long, default_value_t = 8)]
+        shards: usize,
+    },
 }
 
 #[derive(Subcommand)]
@@ -245,6 +502,9 @@ enum IndexingOptions {
     Entity {
         wikipedia_dump_path: String,
         output_path: String,
+        /// Dual encoder used to find related entities. They are found by text similarity without it.
+        #[clap(long)]
+        dual_encoder: Option<String>,
     },
 
     /// Create the feed index. Used to find feeds to put into the live index.
@@ -256,6 +516,11 @@ enum IndexingOptions {
     Canonical {
         config_path: String,
     },
+
+    /// Create the search index and webgraphs from a directory or zip file of html and markdown files.
+    Files {
+        config_path: String,
+    },
 }
 
 fn load_toml_config<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> T {
@@ -283,6 +548,10 @@ fn main() -> Result<()> {
 
     let args = Args::parse();
 
+    if let Some(path) = &args.url_rules {
+        stract::webpage::url_cleaner::load_rules(path)?;
+    }
+
     match args.command {
         Commands::Indexer { options } => match options {
             IndexingOptions::Search { config_path } => {
@@ -292,7 +561,8 @@ fn main() -> Result<()> {
             IndexingOptions::Entity {
                 wikipedia_dump_path,
                 output_path,
-            } => entrypoint::EntityIndexer::run(wikipedia_dump_path, output_path)?,
+                dual_encoder,
+            } => entrypoint::EntityIndexer::run(wikipedia_dump_path, output_path, dual_encoder)?,
             IndexingOptions::Feed { config_path } => {
                 let config = load_toml_config(config_path);
                 entrypoint::feed_indexer::build(config)?;
@@ -308,19 +578,87 @@ fn main() -> Result<()> {
                 let config: config::CanonicalIndexConfig = load_toml_config(config_path);
                 entrypoint::canonical::create(config)?;
             }
+            IndexingOptions::Files { config_path } => {
+                let config: config::FileIndexerConfig = load_toml_config(config_path);
+                entrypoint::file_indexer::run(&config)?;
+            }
         },
         Commands::Centrality { mode } => {
             match mode {
                 CentralityMode::Host {
                     webgraph_path,
                     output_path,
+                    max_link_age_days,
                 } => {
-                    entrypoint::Centrality::build_harmonic(&webgraph_path, &output_path);
+                    entrypoint::Centrality::build_harmonic(
+                        &webgraph_path,
+                        &output_path,
+                        max_link_age_days,
+                    );
                 }
+                CentralityMode::HostIncremental {
+                    webgraph_path,
+                    output_path,
+                    max_hops,
+                    max_affected_fraction,
+                    full_rebuild_interval,
+                } => entrypoint::Centrality::update_harmonic(
+                    webgraph_path,
+                    output_path,
+                    max_hops,
+                    max_affected_fraction,
+                    full_rebuild_interval,
+                )?,
                 CentralityMode::Page {
                     webgraph_path,
                     output_path,
                 } => entrypoint::Centrality::build_approx_harmonic(webgraph_path, output_path)?,
+                CentralityMode::Personalized {
+                    webgraph_path,
+                    seeds_path,
+                    output_path,
+                    alpha,
+                    iterations,
+                } => entrypoint::Centrality::build_personalized_pagerank(
+                    webgraph_path,
+                    seeds_path,
+                    output_path,
+                    alpha,
+                    iterations,
+                )?,
+                CentralityMode::Betweenness {
+                    webgraph_path,
+                    output_path,
+                    num_pivots,
+                } => entrypoint::Centrality::build_approx_betweenness(
+                    webgraph_path,
+                    output_path,
+                    num_pivots,
+                )?,
+                CentralityMode::Neighborhood {
+                    webgraph_path,
+                    output_path,
+                    max_hops,
+                    ingoing,
+                } => entrypoint::Centrality::build_neighborhood_function(
+                    webgraph_path,
+                    output_path,
+                    max_hops,
+                    ingoing,
+                )?,
+                CentralityMode::TrustRank {
+                    webgraph_path,
+                    whitelist_path,
+                    output_path,
+                    decay,
+                    iterations,
+                } => entrypoint::Centrality::build_trustrank(
+                    webgraph_path,
+                    whitelist_path,
+                    output_path,
+                    decay,
+                    iterations,
+                )?,
             }
             tracing::info!("Done");
         }
@@ -344,11 +682,24 @@ fn main() -> Result<()> {
 
                 if merge_all_segments {
                     webgraph.optimize_read(); // save space in id2node db
-                    webgraph.merge_all_segments(Default::default())?;
+                    webgraph.merge_all_segments()?;
                 }
 
                 webgraph.optimize_read();
             }
+            WebgraphOptions::Verify { path } => {
+                let report = stract::webgraph::verify(path)?;
+                tracing::info!("{report}");
+
+                if !report.is_ok() {
+                    anyhow::bail!("the webgraph has integrity problems");
+                }
+            }
+            WebgraphOptions::Stats { path } => {
+                let webgraph = WebgraphBuilder::new(path).open();
+                let stats = webgraph.update_stats()?;
+                tracing::info!("{stats:#?}");
+            }
             WebgraphOptions::Server { config_path } => {
                 let config: config::WebgraphServerConfig = load_toml_config(config_path);
 
@@ -357,6 +708,83 @@ fn main() -> Result<()> {
                     .build()?
                     .block_on(webgraph_server::run(config))?;
             }
+            WebgraphOptions::OutlinkAudit { config_path } => {
+                let config: config::OutlinkAuditConfig = load_toml_config(config_path);
+                entrypoint::outlink_audit::run(config)?;
+            }
+            WebgraphOptions::OutlinkProfile { config_path } => {
+                let config: config::OutlinkProfileConfig = load_toml_config(config_path);
+                entrypoint::outlink_profile::run(config)?;
+            }
+            WebgraphOptions::HostClusters { config_path } => {
+                let config: config::HostClustersConfig = load_toml_config(config_path);
+                entrypoint::host_clusters::run(config)?;
+            }
+            WebgraphOptions::Communities { path } => {
+                let webgraph = WebgraphBuilder::new(path).open();
+                let communities = webgraph.detect_communities()?;
+                tracing::info!("{} nodes assigned to communities", communities.len());
+            }
+            WebgraphOptions::Neighborhood {
+                path,
+                node,
+                output_path,
+                hops,
+                ingoing,
+                max_nodes,
+            } => {
+                let webgraph = WebgraphBuilder::new(path).open();
+                let direction = if ingoing {
+                    DegreeDirection::In
+                } else {
+                    DegreeDirection::Out
+                };
+
+                let subgraph =
+                    webgraph.neighborhood_with_limit(&Node::from(node), hops, direction, max_nodes);
+                tracing::info!(
+                    "neighborhood has {} nodes and {} edges",
+                    subgraph.len(),
+                    subgraph.edges().len()
+                );
+
+                match Path::new(&output_path).extension().and_then(|e| e.to_str()) {
+                    Some("dot") => fs::write(&output_path, subgraph.to_dot())?,
+                    Some("csv") => subgraph.export_csv(&output_path)?,
+                    _ => {
+                        subgraph.into_webgraph(&output_path);
+                    }
+                }
+            }
+            WebgraphOptions::Export {
+                path,
+                output_path,
+                format,
+            } => {
+                let webgraph = WebgraphBuilder::new(path).open();
+                webgraph.export(format, output_path)?;
+            }
+            WebgraphOptions::Import {
+                output_path,
+                files,
+                format,
+                vertices,
+                shards,
+            } => {
+                let mut importer = WebgraphImporter::new(output_path, shards, Default::default())?;
+
+                if format == ImportFormat::CommonCrawl {
+                    let vertices: Vec<PathBuf> = vertices.into_iter().map(PathBuf::from).collect();
+                    importer.load_vertices(&vertices)?;
+                }
+
+                let files: Vec<PathBuf> = files.into_iter().map(PathBuf::from).collect();
+                let num_edges = importer.import(format, &files)?;
+
+                let mut webgraph = importer.finalize()?;
+                webgraph.optimize_read();
+                tracing::info!("imported {num_edges} edges");
+            }
         },
         Commands::Api { config_path } => {
             let config: config::ApiConfig = load_toml_config(config_path);
@@ -374,6 +802,44 @@ fn main() -> Result<()> {
                 .build()?
                 .block_on(search_server::run(config))?;
         }
+        Commands::PromoteSearchServer { host } => {
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?
+                .block_on(search_server::promote(host))?;
+        }
+        Commands::TieredStorage { options } => match options {
+            TieredStorageOptions::Offload { config_path } => {
+                let config: config::SearchServerConfig = load_toml_config(config_path);
+                let tiered_storage = config
+                    .tiered_storage
+                    .context("the config has no tiered_storage section")?;
+
+                let num_files =
+                    stract::index::Index::offload_segments(&config.index_path, &tiered_storage)?;
+                tracing::info!("offloaded {} files", num_files);
+            }
+        },
+        Commands::IndexReplication { options } => {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()?;
+
+            match options {
+                IndexReplicationOptions::Serve { index_path, host } => {
+                    rt.block_on(index_file_server::serve(index_path, host))?
+                }
+                IndexReplicationOptions::Pull {
+                    source,
+                    index_path,
+                    chunk_size_bytes,
+                } => rt.block_on(index_file_server::pull(
+                    source,
+                    index_path,
+                    chunk_size_bytes,
+                ))?,
+            }
+        }
         Commands::EntitySearchServer { config_path } => {
             let config: config::EntitySearchServerConfig = load_toml_config(config_path);
 
@@ -390,6 +856,21 @@ fn main() -> Result<()> {
         } => {
             autosuggest_scrape::run(queries_to_scrape, gl, ms_sleep_between_req, output_dir)?;
         }
+        Commands::AutosuggestBuild {
+            queries_csv_path,
+            output_path,
+            filter_config,
+        } => {
+            let mut autosuggest = stract::autosuggest::Autosuggest::load_csv(queries_csv_path)?;
+
+            if let Some(filter_config) = filter_config {
+                let filter =
+                    stract::autosuggest::SuggestionFilter::open(&load_toml_config(filter_config))?;
+                autosuggest = autosuggest.with_filter(std::sync::Arc::new(filter))?;
+            }
+
+            autosuggest.save(output_path)?;
+        }
         #[cfg(feature = "dev")]
         Commands::Configure { skip_download } => {
             configure::run(skip_download)?;
@@ -431,6 +912,10 @@ fn main() -> Result<()> {
                     .build()?
                     .block_on(entrypoint::crawler::planner(config))?;
             }
+            Crawler::CloakingAudit { config_path } => {
+                let config: config::CloakingAuditConfig = load_toml_config(config_path);
+                entrypoint::cloaking_audit::run(config)?;
+            }
         },
         Commands::SafetyClassifier { options } => match options {
             SafetyClassifierOptions::Train {
@@ -459,6 +944,41 @@ fn main() -> Result<()> {
             let config: config::WebSpellConfig = load_toml_config(config_path);
             entrypoint::web_spell::run(config)?;
         }
+        Commands::Evaluate { config_path } => {
+            let config: config::EvaluationConfig = load_toml_config(config_path);
+            entrypoint::evaluation::run(config)?;
+        }
+        Commands::PageFeatures { config_path } => {
+            let config: config::PageFeaturesConfig = load_toml_config(config_path);
+            entrypoint::page_features::run(&config)?;
+        }
+        Commands::CoverageReport { config_path } => {
+            let config: config::CoverageReportConfig = load_toml_config(config_path);
+            entrypoint::coverage_report::run(&config)?;
+        }
+        Commands::HostAbout { config_path } => {
+            let config: config::HostAboutConfig = load_toml_config(config_path);
+            entrypoint::host_about::run(&config)?;
+        }
+        Commands::AllInOne {
+            data_dir,
+            host,
+            warc,
+        } => {
+            entrypoint::all_in_one::run(config::AllInOneConfig {
+                data_dir,
+                host: host.unwrap_or_else(config::defaults::AllInOne::host),
+                warc_path: warc,
+            })?;
+        }
+        Commands::QueryLog { config_path } => {
+            let config: config::QueryLogConfig = load_toml_config(config_path);
+            stract::query_log::run(&config)?;
+        }
+        Commands::ThreatLists { config_path } => {
+            let config: config::ThreatListsConfig = load_toml_config(config_path);
+            stract::threat_list::ThreatList::build(&config)?;
+        }
         Commands::Ampc { options } => match options {
             AmpcOptions::Dht { config_path } => {
                 let config: config::DhtConfig = load_toml_config(config_path);
@@ -40,25 +40,101 @@ impl Counter {
         self.0.fetch_add(1, Ordering::SeqCst);
     }
 
+    pub fn add(&self, val: u64) {
+        self.0.fetch_add(val, Ordering::SeqCst);
+    }
+
     pub fn store(&self, val: u64) {
         self.0.store(val, Ordering::SeqCst);
     }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+struct HistogramInner {
+    /// Upper bounds of the buckets in increasing order.
+    bounds: Vec<f64>,
+    /// Number of observations in each bucket, not including the smaller buckets.
+    buckets: Vec<AtomicU64>,
+    /// Bits of the `f64` sum of the observations.
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+/// Distribution of observed values, exported as a prometheus histogram.
+#[derive(Clone)]
+pub struct Histogram(Arc<HistogramInner>);
+
+impl Histogram {
+    pub fn new(mut bounds: Vec<f64>) -> Self {
+        bounds.sort_by(|a, b| a.total_cmp(b));
+        bounds.dedup();
+
+        Self(Arc::new(HistogramInner {
+            buckets: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            bounds,
+            sum: AtomicU64::new(0f64.to_bits()),
+            count: AtomicU64::new(0),
+        }))
+    }
+
+    /// Buckets for durations in seconds, from a millisecond to half a minute.
+    pub fn seconds() -> Self {
+        Self::new(vec![
+            0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+        ])
+    }
+
+    pub fn observe(&self, val: f64) {
+        if let Some(i) = self.0.bounds.iter().position(|bound| val <= *bound) {
+            self.0.buckets[i].fetch_add(1, Ordering::SeqCst);
+        }
+
+        self.0
+            .sum
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |sum| {
+                Some((f64::from_bits(sum) + val).to_bits())
+            })
+            .ok();
+        self.0.count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.0.count.load(Ordering::SeqCst)
+    }
+
+    pub fn sum(&self) -> f64 {
+        f64::from_bits(self.0.sum.load(Ordering::SeqCst))
+    }
+
+    /// The upper bound of each bucket with the number of observations up to that bound.
+    fn cumulative_buckets(&self) -> Vec<(f64, u64)> {
+        let mut total = 0;
+
+        self.0
+            .bounds
+            .iter()
+            .zip(self.0.buckets.iter())
+            .map(|(bound, count)| {
+                total += count.load(Ordering::SeqCst);
+                (*bound, total)
+            })
+            .collect()
+    }
 }
 
 pub enum PrometheusMetric {
     Counter(Counter),
+    Histogram(Histogram),
 }
 
 impl PrometheusMetric {
     fn prom_type(&self) -> &'static str {
         match self {
             PrometheusMetric::Counter(_) => "counter",
-        }
-    }
-
-    fn prom_val(&self) -> String {
-        match self {
-            PrometheusMetric::Counter(counter) => format!("{}", counter.0.load(Ordering::SeqCst)),
+            PrometheusMetric::Histogram(_) => "histogram",
         }
     }
 }
@@ -69,6 +145,12 @@ impl From<Counter> for PrometheusMetric {
     }
 }
 
+impl From<Histogram> for PrometheusMetric {
+    fn from(histogram: Histogram) -> Self {
+        Self::Histogram(histogram)
+    }
+}
+
 type Name = String;
 
 #[derive(Default)]
@@ -124,25 +206,62 @@ struct LabelledMetric {
     metric: PrometheusMetric,
     labels: Vec<Label>,
 }
-impl Display for LabelledMetric {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if !self.labels.is_empty() {
+
+impl LabelledMetric {
+    fn write_labels(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        extra: Option<(&str, String)>,
+    ) -> std::fmt::Result {
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|label| format!("{}=\"{}\"", label.key, label.val))
+            .chain(extra.map(|(key, val)| format!("{key}=\"{val}\"")))
+            .collect();
+
+        if !labels.is_empty() {
             f.write_str("{")?;
+            f.write_str(&labels.join(","))?;
+            f.write_str("}")?;
+        }
 
-            let num_labels = self.labels.len();
-            for (i, label) in self.labels.iter().enumerate() {
-                f.write_str(&format!("{}=\"{}\"", label.key, label.val))?;
+        Ok(())
+    }
 
-                if i < num_labels - 1 {
-                    f.write_str(",")?;
-                }
+    /// Write the samples of the metric, each on a new line.
+    fn write_samples(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        name: &str,
+        timestamp: u128,
+    ) -> std::fmt::Result {
+        match &self.metric {
+            PrometheusMetric::Counter(counter) => {
+                write!(f, "\n{name}")?;
+                self.write_labels(f, None)?;
+                write!(f, " {} {timestamp}", counter.get())?;
             }
+            PrometheusMetric::Histogram(histogram) => {
+                for (bound, count) in histogram.cumulative_buckets() {
+                    write!(f, "\n{name}_bucket")?;
+                    self.write_labels(f, Some(("le", bound.to_string())))?;
+                    write!(f, " {count} {timestamp}")?;
+                }
 
-            f.write_str("}")?;
-        }
+                write!(f, "\n{name}_bucket")?;
+                self.write_labels(f, Some(("le", "+Inf".to_string())))?;
+                write!(f, " {} {timestamp}", histogram.count())?;
+
+                write!(f, "\n{name}_sum")?;
+                self.write_labels(f, None)?;
+                write!(f, " {} {timestamp}", histogram.sum())?;
 
-        f.write_str(" ")?;
-        f.write_str(&self.metric.prom_val())?;
+                write!(f, "\n{name}_count")?;
+                self.write_labels(f, None)?;
+                write!(f, " {} {timestamp}", histogram.count())?;
+            }
+        }
 
         Ok(())
     }
@@ -189,11 +308,7 @@ impl Display for PrometheusGroup {
         }
 
         for m in &self.metrics {
-            f.write_str("\n")?;
-            f.write_str(&self.name)?;
-            f.write_str(&format!("{m}"))?;
-            f.write_str(" ")?;
-            f.write_str(&format!("{timestamp}"))?;
+            m.write_samples(f, &self.name, timestamp)?;
         }
 
         Ok(())
@@ -247,6 +362,41 @@ test_counter{{test_label="123"}} 0 {t}
             r##"# HELP test_counter Test counter help.
 # TYPE test_counter counter
 test_counter{{test_label="123"}} 1 {t}
+"##
+        );
+        assert_eq!(format!("{registry}"), expected);
+    }
+
+    #[test]
+    fn histogram() {
+        let histogram = Histogram::new(vec![1.0, 0.5]);
+        let mut registry = PrometheusRegistry::default();
+
+        registry
+            .new_group("test_histogram".to_string(), None)
+            .unwrap()
+            .register(histogram.clone(), vec![]);
+
+        let t = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+
+        for group in registry.groups.values_mut() {
+            group.forced_timestamp = Some(t);
+        }
+
+        histogram.observe(0.25);
+        histogram.observe(0.75);
+        histogram.observe(2.0);
+
+        let expected = format!(
+            r##"# TYPE test_histogram histogram
+test_histogram_bucket{{le="0.5"}} 1 {t}
+test_histogram_bucket{{le="1"}} 2 {t}
+test_histogram_bucket{{le="+Inf"}} 3 {t}
+test_histogram_sum 3 {t}
+test_histogram_count 3 {t}
 "##
         );
         assert_eq!(format!("{registry}"), expected);
@@ -0,0 +1,149 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Selects the device that a model runs on. Cuda and metal are only available
+//! when the crate is built with the `cuda` or `metal` feature, and models fall
+//! back to the cpu when the configured device can't be used.
+
+use candle_core::Device;
+
+use crate::{
+    config::{AcceleratorConfig, AcceleratorDevice},
+    Result,
+};
+
+#[derive(Debug, Clone)]
+pub struct Accelerator {
+    device: Device,
+    kind: AcceleratorDevice,
+    batch_size: usize,
+    cpu_batch_size: usize,
+}
+
+impl Default for Accelerator {
+    fn default() -> Self {
+        Self::new(&AcceleratorConfig::default())
+    }
+}
+
+impl Accelerator {
+    pub fn new(config: &AcceleratorConfig) -> Self {
+        let cpu_batch_size = config.batch_sizes.cpu;
+
+        let device = match config.device {
+            AcceleratorDevice::Cpu => Ok(Device::Cpu),
+            AcceleratorDevice::Cuda => Device::new_cuda(config.ordinal),
+            AcceleratorDevice::Metal => Device::new_metal(config.ordinal),
+        };
+
+        match device {
+            Ok(device) => Self {
+                device,
+                kind: config.device,
+                batch_size: config.batch_sizes.get(config.device),
+                cpu_batch_size,
+            },
+            Err(err) => {
+                tracing::warn!(
+                    "{:?} device {} is unavailable, falling back to cpu: {}",
+                    config.device,
+                    config.ordinal,
+                    err
+                );
+
+                Self::cpu(cpu_batch_size)
+            }
+        }
+    }
+
+    fn cpu(batch_size: usize) -> Self {
+        Self {
+            device: Device::Cpu,
+            kind: AcceleratorDevice::Cpu,
+            batch_size,
+            cpu_batch_size: batch_size,
+        }
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn kind(&self) -> AcceleratorDevice {
+        self.kind
+    }
+
+    /// Maximum number of inputs to run through the model at once.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Load a model on the device. If loading fails on an accelerator, e.g. because
+    /// the model doesn't fit in its memory, the model is loaded on the cpu instead.
+    pub fn load<T>(&self, mut load: impl FnMut(&Accelerator) -> Result<T>) -> Result<T> {
+        match load(self) {
+            Ok(model) => Ok(model),
+            Err(err) if self.kind != AcceleratorDevice::Cpu => {
+                tracing::warn!(
+                    "failed to load model on {:?}, falling back to cpu: {}",
+                    self.kind,
+                    err
+                );
+
+                load(&Self::cpu(self.cpu_batch_size))
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::AcceleratorBatchSizes;
+
+    use super::*;
+
+    #[test]
+    fn fallback_to_cpu() {
+        let config = AcceleratorConfig {
+            device: AcceleratorDevice::Cuda,
+            ordinal: 0,
+            batch_sizes: AcceleratorBatchSizes {
+                cpu: 4,
+                cuda: 64,
+                metal: 32,
+            },
+        };
+
+        let accelerator = Accelerator::new(&config);
+
+        if accelerator.kind() == AcceleratorDevice::Cpu {
+            assert!(accelerator.device().is_cpu());
+            assert_eq!(accelerator.batch_size(), 4);
+        } else {
+            assert!(accelerator.device().is_cuda());
+            assert_eq!(accelerator.batch_size(), 64);
+        }
+
+        let mut attempts = Vec::new();
+        let res = accelerator.load(|acc| {
+            attempts.push(acc.kind());
+            anyhow::bail!("out of memory")
+        });
+        assert!(res.is_err());
+        assert_eq!(attempts.last(), Some(&AcceleratorDevice::Cpu));
+    }
+}
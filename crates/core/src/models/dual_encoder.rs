@@ -20,7 +20,10 @@ use candle_nn::VarBuilder;
 use std::path::Path;
 
 use crate::{
-    models::bert::{self, BertModel},
+    models::{
+        accelerator::Accelerator,
+        bert::{self, BertModel},
+    },
     Result,
 };
 use tokenizers::{PaddingParams, TruncationParams};
@@ -31,11 +34,23 @@ pub struct DualEncoder {
     device: Device,
     dtype: candle_core::DType,
     config: bert::Config,
+    batch_size: usize,
 }
 
 impl DualEncoder {
     pub fn open<P: AsRef<Path>>(folder: P) -> Result<Self> {
-        let device = Device::Cpu;
+        Self::open_with_accelerator(folder, &Accelerator::default())
+    }
+
+    pub fn open_with_accelerator<P: AsRef<Path>>(
+        folder: P,
+        accelerator: &Accelerator,
+    ) -> Result<Self> {
+        accelerator.load(|accelerator| Self::load_on(folder.as_ref(), accelerator))
+    }
+
+    fn load_on(folder: &Path, accelerator: &Accelerator) -> Result<Self> {
+        let device = accelerator.device().clone();
         let dtype = candle_core::DType::F16;
 
         let truncation = TruncationParams {
@@ -48,24 +63,24 @@ impl DualEncoder {
         };
 
         let mut tokenizer =
-            tokenizers::Tokenizer::from_file(folder.as_ref().join("tokenizer.json")).unwrap();
+            tokenizers::Tokenizer::from_file(folder.join("tokenizer.json")).unwrap();
 
         tokenizer.with_truncation(Some(truncation)).unwrap();
         tokenizer.with_padding(Some(padding));
 
         let vb = unsafe {
             VarBuilder::from_mmaped_safetensors(
-                &[folder.as_ref().join("model.safetensors")],
+                &[folder.join("model.safetensors")],
                 dtype,
                 &device,
             )?
         };
-        let config = std::fs::read_to_string(folder.as_ref().join("config.json"))?;
+        let config = std::fs::read_to_string(folder.join("config.json"))?;
         let mut config: bert::Config = serde_json::from_str(&config)?;
         config.hidden_act = bert::HiddenAct::GeluApproximate;
 
         // all tensors can be loaded with (useful for debugging):
-        // candle_core::safetensors::load(folder.as_ref().join("model.safetensors"), &device)
+        // candle_core::safetensors::load(folder.join("model.safetensors"), &device)
 
         let mut model = BertModel::load(vb, &config)?;
         model.set_pooler(None); // model should use mean pooling
@@ -76,10 +91,27 @@ impl DualEncoder {
             device,
             dtype,
             config,
+            batch_size: accelerator.batch_size(),
         })
     }
 
+    /// Embed the texts in batches. The embeddings are always returned on the cpu,
+    /// so they can be compared with the embeddings stored in the index.
     pub fn embed(&self, texts: &[String]) -> Result<Tensor> {
+        let batches = texts
+            .chunks(self.batch_size.max(1))
+            .map(|batch| self.embed_batch(batch))
+            .collect::<Result<Vec<_>>>()?;
+
+        let emb = match batches.len() {
+            1 => batches.into_iter().next().unwrap(),
+            _ => Tensor::cat(&batches, 0)?,
+        };
+
+        Ok(emb.to_device(&Device::Cpu)?)
+    }
+
+    fn embed_batch(&self, texts: &[String]) -> Result<Tensor> {
         let enc = self
             .tokenizer
             .encode_batch(texts.to_vec(), true)
@@ -14,5 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod accelerator;
 pub mod bert;
 pub mod dual_encoder;
+pub mod registry;
@@ -0,0 +1,515 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The model registry keeps track of which version of each model is in use.
+//! Models are listed in a manifest with their location, version and checksum.
+//! When the version in the manifest changes, the new version is loaded next to
+//! the old one and swapped in once it has loaded, so requests that are in
+//! progress finish with the model they started with.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::bail;
+
+use crate::{
+    config::{ModelRegistryConfig, ModelSource},
+    models::dual_encoder::DualEncoder,
+    ranking::models::{cross_encoder::CrossEncoderModel, lambdamart::LambdaMART},
+    webpage::safety_classifier,
+    Result,
+};
+
+/// A model that can be loaded from a file or folder.
+pub trait Model: Sized + Send + Sync + 'static {
+    fn load(path: &Path) -> Result<Self>;
+}
+
+impl Model for LambdaMART {
+    fn load(path: &Path) -> Result<Self> {
+        Ok(Self::open(path)?)
+    }
+}
+
+impl Model for CrossEncoderModel {
+    fn load(path: &Path) -> Result<Self> {
+        Self::open(path)
+    }
+}
+
+impl Model for DualEncoder {
+    fn load(path: &Path) -> Result<Self> {
+        Self::open(path)
+    }
+}
+
+impl Model for safety_classifier::Model {
+    fn load(path: &Path) -> Result<Self> {
+        Self::open(path)
+    }
+}
+
+struct Loaded<T> {
+    model: Arc<T>,
+    version: Option<String>,
+}
+
+/// Shared handle to the current version of a model. All clones of the
+/// handle see the new version once the model has been swapped.
+pub struct ModelHandle<T> {
+    current: Arc<RwLock<Option<Loaded<T>>>>,
+}
+
+impl<T> Clone for ModelHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: Arc::clone(&self.current),
+        }
+    }
+}
+
+impl<T> Default for ModelHandle<T> {
+    fn default() -> Self {
+        Self {
+            current: Arc::new(RwLock::new(None)),
+        }
+    }
+}
+
+impl<T> ModelHandle<T> {
+    /// Handle to a model that is not managed by a registry.
+    pub fn new(model: T) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Some(Loaded {
+                model: Arc::new(model),
+                version: None,
+            }))),
+        }
+    }
+
+    /// The current model. Callers should keep the returned model for the
+    /// duration of a request instead of calling `get` multiple times.
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.current
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|loaded| Arc::clone(&loaded.model))
+    }
+
+    pub fn version(&self) -> Option<String> {
+        self.current
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|loaded| loaded.version.clone())
+    }
+
+    pub fn swap(&self, model: T, version: String) {
+        *self.current.write().unwrap() = Some(Loaded {
+            model: Arc::new(model),
+            version: Some(version),
+        });
+    }
+}
+
+trait Slot: Send + Sync {
+    fn version(&self) -> Option<String>;
+    fn load(&self, path: &Path, version: &str) -> Result<()>;
+}
+
+type Loader<T> = Box<dyn Fn(&Path) -> Result<T> + Send + Sync>;
+
+struct Registered<T> {
+    handle: ModelHandle<T>,
+    loader: Loader<T>,
+}
+
+impl<T: Send + Sync + 'static> Slot for Registered<T> {
+    fn version(&self) -> Option<String> {
+        self.handle.version()
+    }
+
+    fn load(&self, path: &Path, version: &str) -> Result<()> {
+        let model = (self.loader)(path)?;
+        self.handle.swap(model, version.to_string());
+
+        Ok(())
+    }
+}
+
+pub struct ModelRegistry {
+    manifest_path: PathBuf,
+    cache_path: PathBuf,
+    slots: BTreeMap<String, Box<dyn Slot>>,
+}
+
+impl ModelRegistry {
+    pub fn new(config: &ModelRegistryConfig) -> Self {
+        Self {
+            manifest_path: PathBuf::from(&config.manifest_path),
+            cache_path: PathBuf::from(&config.cache_path),
+            slots: BTreeMap::new(),
+        }
+    }
+
+    /// Register a model with the name it has in the manifest. The handle is
+    /// empty until the model has been loaded by [`ModelRegistry::reload`].
+    pub fn register<T: Model>(&mut self, name: &str) -> ModelHandle<T> {
+        self.register_with(name, T::load)
+    }
+
+    /// Register a model that is loaded with `loader`, e.g. to load it on a specific device.
+    pub fn register_with<T, F>(&mut self, name: &str, loader: F) -> ModelHandle<T>
+    where
+        T: Send + Sync + 'static,
+        F: Fn(&Path) -> Result<T> + Send + Sync + 'static,
+    {
+        let handle = ModelHandle::default();
+        self.slots.insert(
+            name.to_string(),
+            Box::new(Registered {
+                handle: handle.clone(),
+                loader: Box::new(loader),
+            }),
+        );
+
+        handle
+    }
+
+    /// The loaded version of each registered model.
+    pub fn versions(&self) -> BTreeMap<String, Option<String>> {
+        self.slots
+            .iter()
+            .map(|(name, slot)| (name.clone(), slot.version()))
+            .collect()
+    }
+
+    fn manifest(&self) -> Result<BTreeMap<String, ModelSource>> {
+        let manifest = std::fs::read_to_string(&self.manifest_path)?;
+        Ok(toml::from_str(&manifest)?)
+    }
+
+    /// Load the models where the version in the manifest differs from the loaded version
+    /// and return their names. A model that fails to load keeps its current version.
+    pub fn reload(&self) -> Result<Vec<String>> {
+        let manifest = self.manifest()?;
+        let mut swapped = Vec::new();
+
+        for (name, slot) in &self.slots {
+            let Some(source) = manifest.get(name) else {
+                continue;
+            };
+
+            if slot.version().as_deref() == Some(source.version.as_str()) {
+                continue;
+            }
+
+            match self
+                .fetch(name, source)
+                .and_then(|path| slot.load(&path, &source.version))
+            {
+                Ok(()) => {
+                    tracing::info!("loaded version {} of model {}", source.version, name);
+                    swapped.push(name.clone());
+                }
+                Err(err) => tracing::warn!(
+                    "failed to load version {} of model {}: {}",
+                    source.version,
+                    name,
+                    err
+                ),
+            }
+        }
+
+        Ok(swapped)
+    }
+
+    /// Local path of the model. Models with a url are downloaded to the cache
+    /// the first time the version is loaded, and zip archives are unpacked
+    /// to a folder.
+    fn fetch(&self, name: &str, source: &ModelSource) -> Result<PathBuf> {
+        let remote = is_url(&source.location);
+
+        let path = if remote {
+            let path = self.cache_path.join(name).join(&source.version);
+
+            if !path.exists() {
+                download(&source.location, &path)?;
+            }
+
+            path
+        } else {
+            PathBuf::from(&source.location)
+        };
+
+        if let Some(expected) = &source.checksum {
+            let actual = checksum(&path)?;
+
+            if !actual.eq_ignore_ascii_case(expected) {
+                if remote {
+                    if path.is_dir() {
+                        std::fs::remove_dir_all(&path)?;
+                    } else {
+                        std::fs::remove_file(&path)?;
+                    }
+                }
+
+                bail!(
+                    "checksum of {} is {actual}, expected {expected}",
+                    path.display()
+                );
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Models that are stored as a folder, like the dual encoder, are downloaded as a zip archive.
+fn is_archive(url: &str) -> bool {
+    url.split(['?', '#'])
+        .next()
+        .is_some_and(|path| path.ends_with(".zip"))
+}
+
+fn download(url: &str, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut res = reqwest::blocking::get(url)?.error_for_status()?;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".download");
+    let mut file = File::create(&tmp)?;
+    res.copy_to(&mut file)?;
+    file.sync_all()?;
+
+    if is_archive(url) {
+        unpack(Path::new(&tmp), path)?;
+        std::fs::remove_file(tmp)?;
+    } else {
+        std::fs::rename(tmp, path)?;
+    }
+
+    Ok(())
+}
+
+/// Unpack a zip archive to the folder `path`. The archive is unpacked next to
+/// the folder and renamed, so a partially unpacked model is never loaded.
+fn unpack(archive: &Path, path: &Path) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".unpack");
+    let tmp = PathBuf::from(tmp);
+
+    if tmp.exists() {
+        std::fs::remove_dir_all(&tmp)?;
+    }
+
+    zip::ZipArchive::new(File::open(archive)?)?.extract(&tmp)?;
+    std::fs::rename(tmp, path)?;
+
+    Ok(())
+}
+
+/// Md5 checksum of a file. The checksum of a folder includes the relative path
+/// and content of every file in the folder.
+fn checksum(path: &Path) -> Result<String> {
+    let mut context = md5::Context::new();
+
+    if path.is_dir() {
+        let mut files = Vec::new();
+        list_files(path, &mut files)?;
+        files.sort();
+
+        for file in files {
+            let relative = file.strip_prefix(path)?;
+            context.consume(relative.to_string_lossy().as_bytes());
+            consume_file(&mut context, &file)?;
+        }
+    } else {
+        consume_file(&mut context, path)?;
+    }
+
+    Ok(format!("{:x}", context.compute()))
+}
+
+fn consume_file(context: &mut md5::Context, path: &Path) -> Result<()> {
+    let mut file = File::open(path)?;
+    let mut buf = vec![0; 1 << 16];
+
+    loop {
+        let n = file.read(&mut buf)?;
+
+        if n == 0 {
+            break;
+        }
+
+        context.consume(&buf[..n]);
+    }
+
+    Ok(())
+}
+
+fn list_files(folder: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(folder)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Text(String);
+
+    impl Model for Text {
+        fn load(path: &Path) -> Result<Self> {
+            Ok(Self(std::fs::read_to_string(path)?))
+        }
+    }
+
+    fn write_manifest(path: &Path, models: &[(&str, ModelSource)]) {
+        let manifest: BTreeMap<_, _> = models.iter().cloned().collect();
+        std::fs::write(path, toml::to_string(&manifest).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn hot_swap() {
+        let folder = crate::gen_temp_path();
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let manifest_path = folder.join("models.toml");
+        let mut registry = ModelRegistry::new(&ModelRegistryConfig {
+            manifest_path: manifest_path.to_str().unwrap().to_string(),
+            cache_path: folder.join("cache").to_str().unwrap().to_string(),
+            reload_interval_secs: 60,
+        });
+
+        let handle: ModelHandle<Text> = registry.register("text");
+        let reader = handle.clone();
+        assert!(handle.get().is_none());
+
+        let v1 = folder.join("v1.txt");
+        std::fs::write(&v1, "first").unwrap();
+        write_manifest(
+            &manifest_path,
+            &[(
+                "text",
+                ModelSource {
+                    location: v1.to_str().unwrap().to_string(),
+                    version: "1".to_string(),
+                    checksum: None,
+                },
+            )],
+        );
+
+        assert_eq!(registry.reload().unwrap(), vec!["text".to_string()]);
+        let first = reader.get().unwrap();
+        assert_eq!(first.0, "first");
+
+        // unchanged version is not reloaded
+        assert!(registry.reload().unwrap().is_empty());
+
+        let v2 = folder.join("v2.txt");
+        std::fs::write(&v2, "second").unwrap();
+        let mut source = ModelSource {
+            location: v2.to_str().unwrap().to_string(),
+            version: "2".to_string(),
+            checksum: Some("0".repeat(32)),
+        };
+        write_manifest(&manifest_path, &[("text", source.clone())]);
+
+        // wrong checksum keeps the old version
+        assert!(registry.reload().unwrap().is_empty());
+        assert_eq!(reader.get().unwrap().0, "first");
+        assert_eq!(reader.version().as_deref(), Some("1"));
+
+        source.checksum = Some(format!("{:x}", md5::compute("second")));
+        write_manifest(&manifest_path, &[("text", source)]);
+
+        assert_eq!(registry.reload().unwrap(), vec!["text".to_string()]);
+        assert_eq!(reader.get().unwrap().0, "second");
+        assert_eq!(
+            registry.versions(),
+            BTreeMap::from([("text".to_string(), Some("2".to_string()))])
+        );
+
+        // the model that was in use before the swap is still valid
+        assert_eq!(first.0, "first");
+    }
+
+    #[test]
+    fn unpack_archive() {
+        use std::io::Write;
+
+        let folder = crate::gen_temp_path();
+        std::fs::create_dir_all(&folder).unwrap();
+
+        let archive = folder.join("model.zip");
+        let mut writer = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let options = zip::write::FileOptions::default();
+        writer.start_file("config.json", options).unwrap();
+        writer.write_all(b"{}").unwrap();
+        writer.start_file("weights/model.bin", options).unwrap();
+        writer.write_all(b"weights").unwrap();
+        writer.finish().unwrap();
+
+        let path = folder.join("model");
+        unpack(&archive, &path).unwrap();
+
+        assert!(path.is_dir());
+        assert_eq!(
+            std::fs::read_to_string(path.join("weights").join("model.bin")).unwrap(),
+            "weights"
+        );
+
+        assert!(is_archive("https://example.com/model.zip?token=abc"));
+        assert!(!is_archive("https://example.com/model.bin"));
+    }
+
+    #[test]
+    fn folder_checksum() {
+        let folder = crate::gen_temp_path();
+        std::fs::create_dir_all(folder.join("sub")).unwrap();
+        std::fs::write(folder.join("a"), "a").unwrap();
+        std::fs::write(folder.join("sub").join("b"), "b").unwrap();
+
+        let before = checksum(&folder).unwrap();
+        assert_eq!(before, checksum(&folder).unwrap());
+
+        std::fs::write(folder.join("sub").join("b"), "c").unwrap();
+        assert_ne!(before, checksum(&folder).unwrap());
+    }
+}
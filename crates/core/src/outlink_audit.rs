@@ -0,0 +1,289 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Audit of the outgoing links of each host. The destinations of all edges in the page
+//! webgraph are cross-referenced with their crawl status, so we know how many
+//! of the links from a host point to pages that are broken or redirected.
+//! A high ratio of broken outlinks is a sign of a poorly maintained site.
+
+use std::{collections::HashMap, path::Path};
+
+use url::Url;
+
+use crate::{
+    webgraph::{EdgeLimit, Node, NodeID, Webgraph},
+    Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub enum CrawlStatus {
+    Ok,
+    Redirected,
+    Broken,
+}
+
+impl CrawlStatus {
+    pub fn from_status_code(status_code: u16) -> Self {
+        match status_code {
+            200..=299 => CrawlStatus::Ok,
+            300..=399 => CrawlStatus::Redirected,
+            _ => CrawlStatus::Broken,
+        }
+    }
+}
+
+/// Parse a line from a crawl log. Each line has the format `<status code> <url>`.
+pub fn parse_crawl_log_line(line: &str) -> Option<(Url, CrawlStatus)> {
+    let (status_code, url) = line.trim().split_once(char::is_whitespace)?;
+
+    let status_code: u16 = status_code.parse().ok()?;
+    let url = Url::parse(url.trim()).ok()?;
+
+    Some((url, CrawlStatus::from_status_code(status_code)))
+}
+
+pub struct CrawlStatusStore {
+    inner: speedy_kv::Db<NodeID, CrawlStatus>,
+}
+
+impl CrawlStatusStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(path)?,
+        })
+    }
+
+    pub fn insert(&mut self, url: &Url, status: CrawlStatus) -> Result<()> {
+        self.inner.insert(Node::from(url).id(), status)
+    }
+
+    pub fn get(&self, node: &NodeID) -> Result<Option<CrawlStatus>> {
+        self.inner.get(node)
+    }
+
+    pub fn commit(&mut self) -> Result<()> {
+        self.inner.commit()
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct OutlinkStats {
+    pub num_outlinks: u64,
+    pub num_ok: u64,
+    pub num_redirected: u64,
+    pub num_broken: u64,
+}
+
+impl OutlinkStats {
+    fn add(&mut self, status: Option<CrawlStatus>) {
+        self.num_outlinks += 1;
+
+        match status {
+            Some(CrawlStatus::Ok) => self.num_ok += 1,
+            Some(CrawlStatus::Redirected) => self.num_redirected += 1,
+            Some(CrawlStatus::Broken) => self.num_broken += 1,
+            None => {}
+        }
+    }
+
+    /// Number of outlinks where the destination has been crawled.
+    pub fn num_audited(&self) -> u64 {
+        self.num_ok + self.num_redirected + self.num_broken
+    }
+
+    /// Ratio of the audited outlinks that are broken.
+    pub fn broken_ratio(&self) -> f64 {
+        if self.num_audited() == 0 {
+            return 0.0;
+        }
+
+        self.num_broken as f64 / self.num_audited() as f64
+    }
+
+    /// Ratio of the audited outlinks that are redirected.
+    pub fn redirected_ratio(&self) -> f64 {
+        if self.num_audited() == 0 {
+            return 0.0;
+        }
+
+        self.num_redirected as f64 / self.num_audited() as f64
+    }
+}
+
+/// The outlink stats for each host.
+pub struct OutlinkAudit {
+    inner: speedy_kv::Db<NodeID, OutlinkStats>,
+}
+
+impl OutlinkAudit {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(path)?,
+        })
+    }
+
+    pub fn build<P: AsRef<Path>>(
+        graph: &Webgraph,
+        statuses: &CrawlStatusStore,
+        path: P,
+    ) -> Result<Self> {
+        let mut hosts: HashMap<NodeID, OutlinkStats> = HashMap::new();
+
+        for (node, id) in graph.node_ids() {
+            let edges = graph.raw_outgoing_edges(&id, EdgeLimit::Unlimited);
+
+            if edges.is_empty() {
+                continue;
+            }
+
+            let stats = hosts.entry(node.into_host().id()).or_default();
+
+            for edge in edges {
+                stats.add(statuses.get(&edge.to)?);
+            }
+        }
+
+        let mut audit = Self::open(path)?;
+
+        for (host, stats) in hosts {
+            audit.inner.insert(host, stats)?;
+        }
+
+        audit.inner.commit()?;
+        audit.inner.merge_all_segments()?;
+
+        Ok(audit)
+    }
+
+    pub fn get(&self, host: &NodeID) -> Result<Option<OutlinkStats>> {
+        self.inner.get(host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        executor::Executor,
+        webgraph::{Compression, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    use super::*;
+
+    #[test]
+    fn crawl_log() {
+        assert_eq!(
+            parse_crawl_log_line("404 https://a.com/missing"),
+            Some((
+                Url::parse("https://a.com/missing").unwrap(),
+                CrawlStatus::Broken
+            ))
+        );
+        assert_eq!(
+            parse_crawl_log_line("301\thttps://a.com/moved\n").map(|(_, status)| status),
+            Some(CrawlStatus::Redirected)
+        );
+        assert_eq!(
+            parse_crawl_log_line("200 https://a.com/").map(|(_, status)| status),
+            Some(CrawlStatus::Ok)
+        );
+        assert_eq!(parse_crawl_log_line("https://a.com/"), None);
+        assert_eq!(parse_crawl_log_line("200 not a url"), None);
+    }
+
+    #[test]
+    fn audit() {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        for (from, to) in [
+            ("https://a.com/", "https://b.com/ok"),
+            ("https://a.com/", "https://b.com/broken"),
+            ("https://a.com/page", "https://b.com/moved"),
+            ("https://a.com/page", "https://b.com/unknown"),
+            ("https://b.com/ok", "https://a.com/"),
+        ] {
+            writer.insert(
+                Node::from(from),
+                Node::from(to),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        writer.commit();
+        let graph = writer.finalize();
+
+        let mut statuses = CrawlStatusStore::open(crate::gen_temp_path()).unwrap();
+
+        for (url, status) in [
+            ("https://b.com/ok", CrawlStatus::Ok),
+            ("https://b.com/broken", CrawlStatus::Broken),
+            ("https://b.com/moved", CrawlStatus::Redirected),
+            ("https://a.com/", CrawlStatus::Ok),
+        ] {
+            statuses.insert(&Url::parse(url).unwrap(), status).unwrap();
+        }
+        statuses.commit().unwrap();
+
+        let audit = OutlinkAudit::build(&graph, &statuses, crate::gen_temp_path()).unwrap();
+
+        let a = audit
+            .get(&Node::from("https://a.com/").into_host().id())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            a,
+            OutlinkStats {
+                num_outlinks: 4,
+                num_ok: 1,
+                num_redirected: 1,
+                num_broken: 1,
+            }
+        );
+        assert!((a.broken_ratio() - 1.0 / 3.0).abs() < 1e-6);
+        assert!((a.redirected_ratio() - 1.0 / 3.0).abs() < 1e-6);
+
+        let b = audit
+            .get(&Node::from("https://b.com/").into_host().id())
+            .unwrap()
+            .unwrap();
+        assert_eq!(b.broken_ratio(), 0.0);
+
+        assert_eq!(
+            audit
+                .get(&Node::from("https://c.com/").into_host().id())
+                .unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,529 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Classification of the outgoing links of each host in the page webgraph.
+//! Sites that mostly exist to pass on visitors or link juice have recognisable link
+//! profiles, and each profile we recognise becomes a flag on the host:
+//!
+//! - affiliate sites have a high density of links with affiliate parameters,
+//!   links to affiliate networks or links marked as sponsored.
+//! - link rotators send their links through redirect scripts (`/go/...`, `/out/...`)
+//!   or url shorteners, so the final destination can be swapped at any time.
+//! - sites in private blog networks (PBNs) point most of their external links to
+//!   a handful of money sites, with anchors that are keywords rather than names.
+
+use std::{collections::HashMap, path::Path};
+
+use bitflags::bitflags;
+use url::Url;
+
+use crate::{
+    webgraph::{Edge, EdgeLimit, Node, NodeID, Webgraph},
+    webpage::{html::links::RelFlags, url_ext::UrlExt},
+    Result,
+};
+
+/// Hosts need at least this many external outlinks before they can be flagged.
+const MIN_EXTERNAL_OUTLINKS: u64 = 20;
+
+/// Ratio of the external outlinks that must be affiliate links.
+const AFFILIATE_RATIO: f64 = 0.3;
+
+/// Ratio of all outlinks that must go through a redirect script or url shortener.
+const ROTATOR_RATIO: f64 = 0.2;
+
+/// Number of most linked hosts that are considered the money sites of a PBN.
+const PBN_TARGETS: usize = 3;
+
+/// Ratio of the external outlinks that must point to the money sites.
+const PBN_CONCENTRATION: f64 = 0.8;
+
+/// Ratio of the external outlinks that must have keyword anchors.
+const PBN_KEYWORD_ANCHOR_RATIO: f64 = 0.5;
+
+const AFFILIATE_PARAMS: &[&str] = &[
+    "aff",
+    "aff_id",
+    "affid",
+    "affiliate",
+    "affiliate_id",
+    "clickid",
+    "partnerid",
+    "refid",
+    "subid",
+];
+
+const AFFILIATE_HOSTS: &[&str] = &[
+    "amzn.to",
+    "anrdoezrs.net",
+    "avantlink.com",
+    "awin1.com",
+    "click.linksynergy.com",
+    "clickbank.net",
+    "dpbolvw.net",
+    "go.skimresources.com",
+    "jdoqocy.com",
+    "kqzyfj.com",
+    "pjatr.com",
+    "pntra.com",
+    "prf.hn",
+    "shareasale.com",
+    "sjv.io",
+    "tkqlhce.com",
+];
+
+const SHORTENER_HOSTS: &[&str] = &[
+    "bit.ly",
+    "buff.ly",
+    "cutt.ly",
+    "goo.gl",
+    "is.gd",
+    "ow.ly",
+    "rebrand.ly",
+    "shorturl.at",
+    "tinyurl.com",
+];
+
+/// First path segments of redirect scripts.
+const REDIRECT_SEGMENTS: &[&str] = &[
+    "click",
+    "go",
+    "goto",
+    "jump",
+    "out",
+    "recommends",
+    "redir",
+    "redirect",
+    "refer",
+    "rotator",
+    "visit",
+];
+
+/// Anchors that say nothing about the destination.
+const GENERIC_ANCHORS: &[&str] = &[
+    "click here",
+    "here",
+    "homepage",
+    "learn more",
+    "link",
+    "more",
+    "read more",
+    "source",
+    "this",
+    "website",
+];
+
+fn host_matches(host: &str, hosts: &[&str]) -> bool {
+    hosts
+        .iter()
+        .any(|h| host == *h || host.ends_with(&format!(".{h}")))
+}
+
+fn is_affiliate_link(url: &Url, rel: RelFlags) -> bool {
+    rel.contains(RelFlags::SPONSORED)
+        || url
+            .host_str()
+            .is_some_and(|host| host_matches(host, AFFILIATE_HOSTS))
+        || url
+            .query_pairs()
+            .any(|(key, _)| AFFILIATE_PARAMS.contains(&key.to_ascii_lowercase().as_str()))
+}
+
+fn is_rotator_link(url: &Url) -> bool {
+    url.host_str()
+        .is_some_and(|host| host_matches(host, SHORTENER_HOSTS))
+        || url
+            .path_segments()
+            .and_then(|mut segments| segments.next())
+            .is_some_and(|segment| REDIRECT_SEGMENTS.contains(&segment.to_lowercase().as_str()))
+}
+
+/// Whether the anchor describes the destination with keywords, instead of being empty,
+/// generic, a url or the name of the destination.
+fn is_keyword_anchor(anchor: &str, destination: &Url) -> bool {
+    let anchor = anchor.trim().to_lowercase();
+
+    if anchor.is_empty() || anchor.contains("://") || GENERIC_ANCHORS.contains(&anchor.as_str()) {
+        return false;
+    }
+
+    let name = destination
+        .host_str()
+        .map(|host| host.trim_start_matches("www."))
+        .and_then(|host| host.split('.').next())
+        .unwrap_or_default();
+
+    let compact: String = anchor.chars().filter(|c| c.is_alphanumeric()).collect();
+
+    name.is_empty() || !compact.contains(name)
+}
+
+/// Links between subdomains of the same site, like `www.example.com` and
+/// `blog.example.com`, are internal.
+fn is_external(url: &Url, source: &Url) -> bool {
+    let site = |url: &Url| url.root_domain().or(url.host_str()).map(str::to_string);
+
+    site(url) != site(source)
+}
+
+fn node_url(node: &Node) -> Option<Url> {
+    Url::parse(&("http://".to_string() + node.as_str())).ok()
+}
+
+bitflags! {
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct OutlinkFlags: u8 {
+        const AFFILIATE = 1 << 0;
+        const LINK_ROTATOR = 1 << 1;
+        const PBN = 1 << 2;
+    }
+}
+
+impl OutlinkFlags {
+    pub fn as_u64(&self) -> u64 {
+        self.bits() as u64
+    }
+}
+
+impl From<u64> for OutlinkFlags {
+    fn from(value: u64) -> Self {
+        Self::from_bits_truncate(value as u8)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct OutlinkProfile {
+    pub num_outlinks: u64,
+    /// Outlinks to other sites.
+    pub num_external: u64,
+    pub num_affiliate: u64,
+    pub num_rotator: u64,
+    /// External outlinks where the anchor is made of keywords.
+    pub num_keyword_anchors: u64,
+    /// External outlinks to the hosts the host links to the most.
+    pub num_to_top_hosts: u64,
+}
+
+impl OutlinkProfile {
+    fn ratio(count: u64, total: u64) -> f64 {
+        if total == 0 {
+            return 0.0;
+        }
+
+        count as f64 / total as f64
+    }
+
+    pub fn affiliate_ratio(&self) -> f64 {
+        Self::ratio(self.num_affiliate, self.num_external)
+    }
+
+    pub fn rotator_ratio(&self) -> f64 {
+        Self::ratio(self.num_rotator, self.num_outlinks)
+    }
+
+    pub fn top_hosts_ratio(&self) -> f64 {
+        Self::ratio(self.num_to_top_hosts, self.num_external)
+    }
+
+    pub fn keyword_anchor_ratio(&self) -> f64 {
+        Self::ratio(self.num_keyword_anchors, self.num_external)
+    }
+
+    pub fn flags(&self) -> OutlinkFlags {
+        let mut flags = OutlinkFlags::empty();
+
+        if self.num_external < MIN_EXTERNAL_OUTLINKS {
+            return flags;
+        }
+
+        if self.affiliate_ratio() >= AFFILIATE_RATIO {
+            flags |= OutlinkFlags::AFFILIATE;
+        }
+
+        if self.rotator_ratio() >= ROTATOR_RATIO {
+            flags |= OutlinkFlags::LINK_ROTATOR;
+        }
+
+        if self.top_hosts_ratio() >= PBN_CONCENTRATION
+            && self.keyword_anchor_ratio() >= PBN_KEYWORD_ANCHOR_RATIO
+        {
+            flags |= OutlinkFlags::PBN;
+        }
+
+        flags
+    }
+}
+
+#[derive(Default)]
+struct HostOutlinks {
+    profile: OutlinkProfile,
+    /// Number of outlinks to each of the other hosts.
+    targets: HashMap<NodeID, u64>,
+}
+
+impl HostOutlinks {
+    fn add(&mut self, edge: &Edge<String>, source: &Url, destination: &Node) {
+        let Some(url) = node_url(destination) else {
+            return;
+        };
+
+        self.profile.num_outlinks += 1;
+
+        if is_rotator_link(&url) {
+            self.profile.num_rotator += 1;
+        }
+
+        if !is_external(&url, source) {
+            return;
+        }
+
+        self.profile.num_external += 1;
+        *self
+            .targets
+            .entry(destination.clone().into_host().id())
+            .or_default() += 1;
+
+        if is_affiliate_link(&url, edge.rel) {
+            self.profile.num_affiliate += 1;
+        }
+
+        if is_keyword_anchor(&edge.label, &url) {
+            self.profile.num_keyword_anchors += 1;
+        }
+    }
+
+    fn finish(mut self) -> OutlinkProfile {
+        let mut counts: Vec<_> = self.targets.into_values().collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+
+        self.profile.num_to_top_hosts = counts.into_iter().take(PBN_TARGETS).sum();
+
+        self.profile
+    }
+}
+
+/// The outlink profile of each host.
+pub struct OutlinkProfiles {
+    inner: speedy_kv::Db<NodeID, OutlinkProfile>,
+}
+
+impl OutlinkProfiles {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(path)?,
+        })
+    }
+
+    /// Build the profiles from the outgoing edges of all pages in the page webgraph.
+    /// The hosts are built one at a time, so only the outlinks of a single host are
+    /// kept in memory.
+    pub fn build<P: AsRef<Path>>(graph: &Webgraph, path: P) -> Result<Self> {
+        let mut profiles = Self::open(path)?;
+
+        for (host, pages) in graph.pages_with_outlinks_by_host() {
+            let mut outlinks = HostOutlinks::default();
+
+            for (page, node) in pages.iter().zip(graph.id2node_many(&pages)) {
+                let Some(source) = node.as_ref().and_then(node_url) else {
+                    continue;
+                };
+
+                let edges = graph.raw_outgoing_edges_with_labels(page, EdgeLimit::Unlimited);
+                let destinations: Vec<_> = edges.iter().map(|edge| edge.to).collect();
+
+                for (edge, destination) in edges.iter().zip(graph.id2node_many(&destinations)) {
+                    if let Some(destination) = destination {
+                        outlinks.add(edge, &source, &destination);
+                    }
+                }
+            }
+
+            if outlinks.profile.num_outlinks > 0 {
+                profiles.inner.insert(host, outlinks.finish())?;
+            }
+        }
+
+        profiles.inner.commit()?;
+        profiles.inner.merge_all_segments()?;
+
+        Ok(profiles)
+    }
+
+    pub fn get(&self, host: &NodeID) -> Result<Option<OutlinkProfile>> {
+        self.inner.get(host)
+    }
+
+    pub fn flags(&self, host: &NodeID) -> Result<OutlinkFlags> {
+        Ok(self
+            .get(host)?
+            .map(|profile| profile.flags())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        executor::Executor,
+        webgraph::{Compression, WebgraphWriter},
+    };
+
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn links() {
+        assert!(is_affiliate_link(
+            &url("https://shop.com/product?aff_id=site"),
+            RelFlags::default()
+        ));
+        assert!(is_affiliate_link(
+            &url("https://amzn.to/abc"),
+            RelFlags::default()
+        ));
+        assert!(is_affiliate_link(
+            &url("https://shop.com/product"),
+            RelFlags::SPONSORED
+        ));
+        assert!(!is_affiliate_link(
+            &url("https://shop.com/product?page=2"),
+            RelFlags::default()
+        ));
+        assert!(!is_affiliate_link(
+            &url("https://news.com/story?ref=homepage"),
+            RelFlags::default()
+        ));
+
+        assert!(is_external(
+            &url("https://other.com/"),
+            &url("https://www.example.com/")
+        ));
+        assert!(!is_external(
+            &url("https://blog.example.com/"),
+            &url("https://www.example.com/")
+        ));
+
+        assert!(is_rotator_link(&url("https://a.com/go/best-vpn")));
+        assert!(is_rotator_link(&url("https://bit.ly/xyz")));
+        assert!(!is_rotator_link(&url("https://a.com/blog/go")));
+
+        assert!(is_keyword_anchor(
+            "best cheap car insurance",
+            &url("https://insure.com/")
+        ));
+        assert!(!is_keyword_anchor(
+            "Insure.com",
+            &url("https://insure.com/")
+        ));
+        assert!(!is_keyword_anchor(
+            "click here",
+            &url("https://insure.com/")
+        ));
+        assert!(!is_keyword_anchor("", &url("https://insure.com/")));
+    }
+
+    #[test]
+    fn flags() {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        for i in 0..30 {
+            // affiliate links through a redirect script on the same host
+            writer.insert(
+                Node::from(format!("https://deals.com/post-{i}")),
+                Node::from(format!("https://shop{i}.com/item?aff_id=deals")),
+                format!("shop {i}"),
+                RelFlags::default(),
+            );
+            writer.insert(
+                Node::from(format!("https://deals.com/post-{i}")),
+                Node::from(format!("https://deals.com/go/shop-{i}")),
+                "buy now".to_string(),
+                RelFlags::default(),
+            );
+
+            // keyword anchors to a single money site
+            writer.insert(
+                Node::from(format!("https://pbn.com/article-{i}")),
+                Node::from(format!("https://money.com/page-{}", i % 3)),
+                "best online casino bonus".to_string(),
+                RelFlags::default(),
+            );
+
+            // a regular site that links to many different hosts by name
+            writer.insert(
+                Node::from(format!("https://blog.com/post-{i}")),
+                Node::from(format!("https://site{i}.com/")),
+                format!("site{i}"),
+                RelFlags::default(),
+            );
+            writer.insert(
+                Node::from(format!("https://blog.com/post-{i}")),
+                Node::from(format!("https://shop.blog.com/?ref=post-{i}")),
+                "best shop deals".to_string(),
+                RelFlags::default(),
+            );
+        }
+
+        writer.commit();
+        let graph = writer.finalize();
+
+        let profiles = OutlinkProfiles::build(&graph, crate::gen_temp_path()).unwrap();
+        let flags = |host: &str| profiles.flags(&Node::from(host).into_host().id()).unwrap();
+
+        assert_eq!(
+            flags("https://deals.com/"),
+            OutlinkFlags::AFFILIATE | OutlinkFlags::LINK_ROTATOR
+        );
+        assert_eq!(flags("https://pbn.com/"), OutlinkFlags::PBN);
+        assert_eq!(flags("https://blog.com/"), OutlinkFlags::empty());
+        assert_eq!(flags("https://unknown.com/"), OutlinkFlags::empty());
+
+        let deals = profiles
+            .get(&Node::from("https://deals.com/").into_host().id())
+            .unwrap()
+            .unwrap();
+        assert_eq!(deals.num_outlinks, 60);
+        assert_eq!(deals.num_external, 30);
+        assert_eq!(deals.num_rotator, 30);
+
+        // links to a subdomain of the site are internal
+        let blog = profiles
+            .get(&Node::from("https://blog.com/").into_host().id())
+            .unwrap()
+            .unwrap();
+        assert_eq!(blog.num_outlinks, 60);
+        assert_eq!(blog.num_external, 30);
+    }
+}
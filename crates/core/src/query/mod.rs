@@ -18,7 +18,7 @@ use crate::{
     inverted_index::InvertedIndex,
     query::parser::TermCompound,
     ranking::SignalCoefficient,
-    schema::text_field,
+    schema::text_field::{self, TextField},
     search_ctx::Ctx,
     searcher::SearchQuery,
     webpage::{region::Region, safety_classifier},
@@ -27,7 +27,7 @@ use crate::{
 
 use optics::{HostRankings, Optic};
 
-use tantivy::query::{BooleanQuery, Occur, QueryClone};
+use tantivy::query::{BooleanQuery, Occur, QueryClone, TermQuery};
 
 mod const_query;
 pub mod intersection;
@@ -38,10 +38,40 @@ mod plan;
 pub mod shortcircuit;
 pub mod union;
 
-use self::{optic::AsMultipleTantivyQuery, parser::SimpleOrPhrase};
+use self::{const_query::ConstQuery, optic::AsMultipleTantivyQuery, parser::SimpleOrPhrase};
 use parser::Term;
 
 pub const MAX_TERMS_FOR_NGRAM_LOOKUPS: usize = 16;
+pub const MAX_EXCLUDED_HOSTS: usize = 64;
+
+/// Terms that ask for results near the user on their own, e.g. "pizza nearby".
+const LOCAL_INTENT_TERMS: [&str; 1] = ["nearby"];
+
+/// Phrases that ask for results near the user, e.g. "pizza near me". Words like
+/// "near" or "closest" are not enough, as in "closest pair of points".
+const LOCAL_INTENT_PHRASES: [&[&str]; 8] = [
+    &["near", "me"],
+    &["open", "now"],
+    &["nær", "mig"],
+    &["i", "nærheden"],
+    &["åbent", "nu"],
+    &["in", "der", "nähe"],
+    &["jetzt", "geöffnet"],
+    &["près", "de", "moi"],
+];
+
+fn has_local_intent(terms: &[String]) -> bool {
+    let terms: Vec<_> = terms.iter().map(|term| term.to_lowercase()).collect();
+
+    terms
+        .iter()
+        .any(|term| LOCAL_INTENT_TERMS.contains(&term.as_str()))
+        || LOCAL_INTENT_PHRASES.iter().any(|phrase| {
+            terms
+                .windows(phrase.len())
+                .any(|window| window.iter().zip(phrase.iter()).all(|(a, b)| a == b))
+        })
+}
 
 #[derive(Debug)]
 pub struct Query {
@@ -55,6 +85,7 @@ pub struct Query {
     count_results_exact: bool,
     signal_coefficients: SignalCoefficient,
     lang: Option<whatlang::Lang>,
+    local_intent: bool,
 }
 
 impl Clone for Query {
@@ -70,6 +101,7 @@ impl Clone for Query {
             count_results_exact: self.count_results_exact,
             signal_coefficients: self.signal_coefficients.clone(),
             lang: self.lang,
+            local_intent: self.local_intent,
         }
     }
 }
@@ -104,6 +136,8 @@ impl Query {
             })
             .collect();
 
+        let local_intent = has_local_intent(&simple_terms_text);
+
         let mut plan = plan::initial(parsed_terms).expect("terms are not empty and not all bangs");
 
         let schema = index.schema();
@@ -117,11 +151,39 @@ impl Query {
             ))));
         }
 
+        if !query.include_parked {
+            plan = plan.and(plan::Node::Not(Box::new(plan::Node::Term(
+                plan::Term::new(
+                    parser::SimpleTerm::from(text_field::PARKED_TOKEN.to_string()).into(),
+                    text_field::ParkedDomain.into(),
+                ),
+            ))));
+        }
+
+        for host in query
+            .exclude_hosts
+            .iter()
+            .map(|host| host.trim())
+            .filter(|host| !host.is_empty())
+            .take(MAX_EXCLUDED_HOSTS)
+        {
+            plan = plan.and(plan::Node::Not(Box::new(plan::Node::from_term(
+                Term::Site(host.to_string()),
+            ))));
+        }
+
         let mut tantivy_query = plan
             .into_query()
             .as_tantivy(lang.as_ref(), &schema)
             .expect("there should at least be one field in the index");
 
+        if let Some(urls) = &query.within_urls {
+            tantivy_query = Box::new(BooleanQuery::new(vec![
+                (Occur::Must, tantivy_query),
+                (Occur::Must, Self::url_set_query(urls, &schema)),
+            ]));
+        }
+
         let mut optics = Vec::new();
         if let Some(site_rankigns_optic) = query.host_rankings.clone().map(|sr| sr.into_optic()) {
             optics.push(site_rankigns_optic);
@@ -151,9 +213,36 @@ impl Query {
             count_results_exact: query.count_results_exact,
             signal_coefficients: query.signal_coefficients(),
             lang,
+            local_intent,
         })
     }
 
+    /// Matches the documents with one of the urls without affecting their score.
+    fn url_set_query(
+        urls: &[String],
+        schema: &tantivy::schema::Schema,
+    ) -> Box<dyn tantivy::query::Query> {
+        let field = text_field::UrlNoTokenizer
+            .tantivy_field(schema)
+            .expect("the index should have a url field");
+
+        let urls = urls
+            .iter()
+            .filter_map(|url| url::Url::parse(url).ok())
+            .map(|url| {
+                let term = tantivy::Term::from_field_text(field, url.as_str());
+                let query: Box<dyn tantivy::query::Query> = Box::new(TermQuery::new(
+                    term,
+                    tantivy::schema::IndexRecordOption::Basic,
+                ));
+
+                (Occur::Should, query)
+            })
+            .collect();
+
+        Box::new(ConstQuery::new(Box::new(BooleanQuery::new(urls)), 0.0))
+    }
+
     pub fn count_results_exact(&self) -> bool {
         self.count_results_exact
     }
@@ -189,6 +278,11 @@ impl Query {
     pub fn lang(&self) -> Option<whatlang::Lang> {
         self.lang
     }
+
+    /// Whether the query asks for results near the user, like "pizza near me".
+    pub fn local_intent(&self) -> bool {
+        self.local_intent
+    }
 }
 
 impl tantivy::query::Query for Query {
@@ -242,6 +336,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn local_intent() {
+        let terms = |q: &str| {
+            q.split_whitespace()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(has_local_intent(&terms("pizza near me")));
+        assert!(has_local_intent(&terms("Pharmacy open now")));
+        assert!(has_local_intent(&terms("bager i nærheden")));
+        assert!(!has_local_intent(&terms("pizza recipe")));
+        assert!(!has_local_intent(&terms("now open source")));
+        assert!(!has_local_intent(&terms("nearest star")));
+        assert!(!has_local_intent(&terms("closest pair of points")));
+        assert!(!has_local_intent(&terms("near field communication")));
+        assert!(!has_local_intent(&terms("cerca trova")));
+    }
+
     #[test]
     fn parse_trailing_leading_whitespace() {
         let index = empty_index();
@@ -912,6 +1025,190 @@ mod tests {
         assert_eq!(result.webpages[0].url, "https://www.sfw.com/");
     }
 
+    #[test]
+    fn parked_domains_are_excluded() {
+        let mut index = Index::temporary().expect("Unable to open index");
+
+        index
+            .insert(
+                &Webpage::test_parse(
+                    &format!(
+                        r#"
+                    <html>
+                        <head>
+                            <title>Test website</title>
+                        </head>
+                        <body>
+                            This is a test website {}
+                        </body>
+                    </html>
+                "#,
+                        rand_words(100)
+                    ),
+                    "https://www.example.com",
+                )
+                .unwrap(),
+            )
+            .expect("failed to insert webpage");
+
+        index
+            .insert(
+                &Webpage::test_parse(
+                    r#"
+                    <html>
+                        <head>
+                            <title>Test website</title>
+                        </head>
+                        <body>
+                            This test domain is for sale
+                            <a href="https://dan.com/buy-domain/parked.com">Make an offer</a>
+                        </body>
+                    </html>
+                "#,
+                    "https://www.parked.com",
+                )
+                .unwrap(),
+            )
+            .expect("failed to insert webpage");
+
+        index.commit().expect("failed to commit index");
+        let searcher = LocalSearcher::from(index);
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "test".to_string(),
+                ..Default::default()
+            })
+            .expect("Search failed");
+        assert_eq!(result.webpages.len(), 1);
+        assert_eq!(result.webpages[0].url, "https://www.example.com/");
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "test".to_string(),
+                include_parked: true,
+                ..Default::default()
+            })
+            .expect("Search failed");
+        assert_eq!(result.webpages.len(), 2);
+    }
+
+    #[test]
+    fn exclude_hosts() {
+        let mut index = Index::temporary().expect("Unable to open index");
+
+        for url in [
+            "https://www.first.com",
+            "https://blog.second.com",
+            "https://www.third.com",
+        ] {
+            index
+                .insert(
+                    &Webpage::test_parse(
+                        &format!(
+                            r#"
+                            <html>
+                                <head>
+                                    <title>Test website</title>
+                                </head>
+                                <body>
+                                    This is a test website {}
+                                </body>
+                            </html>
+                        "#,
+                            rand_words(100)
+                        ),
+                        url,
+                    )
+                    .unwrap(),
+                )
+                .expect("failed to insert webpage");
+        }
+
+        index.commit().expect("failed to commit index");
+        let searcher = LocalSearcher::from(index);
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "test".to_string(),
+                exclude_hosts: vec!["first.com".to_string(), "second.com".to_string()],
+                num_results: 1,
+                ..Default::default()
+            })
+            .expect("Search failed");
+
+        assert_eq!(result.webpages.len(), 1);
+        assert_eq!(result.webpages[0].url, "https://www.third.com/");
+    }
+
+    #[test]
+    fn within_urls() {
+        let mut index = Index::temporary().expect("Unable to open index");
+
+        for url in [
+            "https://www.first.com",
+            "https://www.second.com",
+            "https://www.third.com",
+        ] {
+            index
+                .insert(
+                    &Webpage::test_parse(
+                        &format!(
+                            r#"
+                            <html>
+                                <head>
+                                    <title>Test website</title>
+                                </head>
+                                <body>
+                                    This is a test website {}
+                                </body>
+                            </html>
+                        "#,
+                            rand_words(100)
+                        ),
+                        url,
+                    )
+                    .unwrap(),
+                )
+                .expect("failed to insert webpage");
+        }
+
+        index.commit().expect("failed to commit index");
+        let searcher = LocalSearcher::from(index);
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "website".to_string(),
+                within_urls: Some(vec![
+                    "https://www.first.com/".to_string(),
+                    "https://www.third.com/".to_string(),
+                ]),
+                ..Default::default()
+            })
+            .expect("Search failed");
+
+        let mut urls: Vec<_> = result
+            .webpages
+            .iter()
+            .map(|page| page.url.as_str())
+            .collect();
+        urls.sort();
+        assert_eq!(
+            urls,
+            vec!["https://www.first.com/", "https://www.third.com/"]
+        );
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "website".to_string(),
+                within_urls: Some(vec![]),
+                ..Default::default()
+            })
+            .expect("Search failed");
+
+        assert!(result.webpages.is_empty());
+    }
+
     #[test]
     fn suffix_domain_prefix_path_site_operator() {
         let mut index = Index::temporary().expect("Unable to open index");
@@ -0,0 +1,368 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Turns raw query events into aggregated counts that are safe to use.
+//!
+//! Only the query, its result urls and the user (or session) id are read from each
+//! event; timestamps and any other fields are dropped. The user id is replaced by a
+//! hash with a salt that only exists while the log is aggregated, and is only used to
+//! count the distinct users of each query. Queries that look like they contain personal
+//! information (emails, phone numbers, ip addresses etc.) are discarded, and a query
+//! is only included in the output if it has been searched by at least `k` distinct users.
+//! Events without a user id are skipped, as they can't be attributed to distinct users.
+//!
+//! The output directory contains:
+//! - `queries.csv`: `query,count` for autosuggest.
+//! - `related.csv`: `query,related,shared_results` for related searches. Two queries
+//!   are related if they share some of their top results.
+//! - `evaluation_topics.tsv`: a sample of the queries weighted by their count,
+//!   in the topic format used by [`crate::trec`].
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use anyhow::anyhow;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use regex::Regex;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::{config::QueryLogConfig, Result};
+
+/// Queries longer than this are usually unique and more likely to identify the user.
+const MAX_QUERY_CHARS: usize = 100;
+
+/// Number of top results used to find related queries.
+const NUM_RESULTS_FOR_RELATED: usize = 5;
+
+static PERSONAL_INFO: once_cell::sync::Lazy<Vec<Regex>> = once_cell::sync::Lazy::new(|| {
+    [
+        // email
+        r"[^\s@]+@[^\s@]+\.[a-z]{2,}",
+        // phone, credit card and id numbers
+        r"\d(?:[\s\-().]?\d){6,}",
+        // ipv4
+        r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b",
+        // uuid
+        r"\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+        // credentials in urls
+        r"[?&][a-z_]*(?:token|session|key|password|sid)=",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).unwrap())
+    .collect()
+});
+
+#[derive(Debug, serde::Deserialize)]
+struct RawQueryEvent {
+    query: String,
+    #[serde(default)]
+    urls: Vec<String>,
+    #[serde(default, alias = "session_id")]
+    user_id: Option<String>,
+}
+
+/// Lowercase the query and collapse whitespace. Returns `None` if the query should not be used.
+fn normalize(query: &str) -> Option<String> {
+    let query = query
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+
+    if query.is_empty() || query.chars().count() > MAX_QUERY_CHARS {
+        return None;
+    }
+
+    if PERSONAL_INFO.iter().any(|pattern| pattern.is_match(&query)) {
+        return None;
+    }
+
+    Some(query)
+}
+
+/// Salted hash of a user id. The salt is never stored, so the hashes can't be linked
+/// to the users once the aggregation is done.
+type UserHash = u64;
+
+#[derive(Default)]
+struct QueryStats {
+    count: usize,
+    users: HashSet<UserHash>,
+    /// The distinct users that got each url in their top results for the query.
+    urls: HashMap<String, HashSet<UserHash>>,
+}
+
+pub struct QueryLogAggregator {
+    queries: HashMap<String, QueryStats>,
+    salt: hmac::Key,
+    num_without_user: usize,
+}
+
+impl QueryLogAggregator {
+    pub fn new() -> Result<Self> {
+        let mut salt = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| anyhow!("failed to generate salt"))?;
+
+        Ok(Self {
+            queries: HashMap::new(),
+            salt: hmac::Key::new(hmac::HMAC_SHA256, &salt),
+            num_without_user: 0,
+        })
+    }
+
+    fn hash_user(&self, user_id: &str) -> UserHash {
+        let tag = hmac::sign(&self.salt, user_id.as_bytes());
+        let mut hash = [0u8; 8];
+        hash.copy_from_slice(&tag.as_ref()[..8]);
+
+        UserHash::from_le_bytes(hash)
+    }
+
+    /// Add the events from a reader with one json event per line.
+    pub fn add_events<R: BufRead>(&mut self, reader: R) -> Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<RawQueryEvent>(&line) {
+                Ok(RawQueryEvent {
+                    query,
+                    urls,
+                    user_id: Some(user_id),
+                }) => self.add(query, urls, &user_id),
+                Ok(_) => self.num_without_user += 1,
+                Err(err) => tracing::debug!("skipping invalid query event: {}", err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add a search of `query` by the user. Only a salted hash of the user id is kept.
+    pub fn add(&mut self, query: String, urls: Vec<String>, user_id: &str) {
+        let Some(query) = normalize(&query) else {
+            return;
+        };
+
+        let user = self.hash_user(user_id);
+        let stats = self.queries.entry(query).or_default();
+        stats.count += 1;
+        stats.users.insert(user);
+
+        for url in urls.into_iter().take(NUM_RESULTS_FOR_RELATED) {
+            stats.urls.entry(url).or_default().insert(user);
+        }
+    }
+
+    /// The queries that have been searched by at least `k` distinct users, with the
+    /// number of times they have been searched.
+    pub fn anonymous_counts(&self, k: usize) -> BTreeMap<String, usize> {
+        self.queries
+            .iter()
+            .filter(|(_, stats)| stats.users.len() >= k)
+            .map(|(query, stats)| (query.clone(), stats.count))
+            .collect()
+    }
+
+    /// Related queries with the number of top results they share. Urls are only
+    /// used if at least `k` distinct users got them in their top results for the query.
+    pub fn related(&self, k: usize, max_related: usize) -> BTreeMap<String, Vec<(String, usize)>> {
+        let anonymous = self.anonymous_counts(k);
+
+        let mut url_queries: HashMap<&str, Vec<&str>> = HashMap::new();
+        for query in anonymous.keys() {
+            for (url, users) in &self.queries[query].urls {
+                if users.len() >= k {
+                    url_queries.entry(url).or_default().push(query);
+                }
+            }
+        }
+
+        let mut res = BTreeMap::new();
+
+        for query in anonymous.keys() {
+            let mut shared: HashMap<&str, usize> = HashMap::new();
+
+            for (url, users) in &self.queries[query].urls {
+                if users.len() < k {
+                    continue;
+                }
+
+                for other in url_queries.get(url.as_str()).into_iter().flatten() {
+                    if *other != query.as_str() {
+                        *shared.entry(*other).or_default() += 1;
+                    }
+                }
+            }
+
+            let mut shared: Vec<_> = shared
+                .into_iter()
+                .map(|(other, count)| (other.to_string(), count))
+                .collect();
+            shared.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+            shared.truncate(max_related);
+
+            if !shared.is_empty() {
+                res.insert(query.clone(), shared);
+            }
+        }
+
+        res
+    }
+
+    /// Sample queries weighted by their count. The sample is deterministic for a given seed.
+    pub fn evaluation_sample(&self, k: usize, num_queries: usize, seed: u64) -> Vec<String> {
+        let queries: Vec<_> = self.anonymous_counts(k).into_iter().collect();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        queries
+            .choose_multiple_weighted(&mut rng, num_queries, |(_, count)| *count as f64)
+            .map(|sample| sample.map(|(query, _)| query.clone()).collect())
+            .unwrap_or_default()
+    }
+}
+
+pub fn run(config: &QueryLogConfig) -> Result<()> {
+    let mut aggregator = QueryLogAggregator::new()?;
+
+    for path in &config.input_paths {
+        let file = std::fs::File::open(path)?;
+        aggregator.add_events(std::io::BufReader::new(file))?;
+    }
+
+    if aggregator.num_without_user > 0 {
+        tracing::warn!(
+            "skipped {} query events without a user id",
+            aggregator.num_without_user
+        );
+    }
+
+    let output = Path::new(&config.output_path);
+    std::fs::create_dir_all(output)?;
+
+    let counts = aggregator.anonymous_counts(config.k_anonymity);
+    let mut writer = csv::Writer::from_path(output.join("queries.csv"))?;
+    writer.write_record(["query", "count"])?;
+    for (query, count) in &counts {
+        writer.write_record([query.as_str(), count.to_string().as_str()])?;
+    }
+    writer.flush()?;
+
+    let mut writer = csv::Writer::from_path(output.join("related.csv"))?;
+    writer.write_record(["query", "related", "shared_results"])?;
+    for (query, related) in aggregator.related(config.k_anonymity, config.max_related) {
+        for (other, shared) in related {
+            writer.write_record([query.as_str(), other.as_str(), shared.to_string().as_str()])?;
+        }
+    }
+    writer.flush()?;
+
+    let sample = aggregator.evaluation_sample(
+        config.k_anonymity,
+        config.num_evaluation_queries,
+        config.seed,
+    );
+    let mut writer =
+        std::io::BufWriter::new(std::fs::File::create(output.join("evaluation_topics.tsv"))?);
+    for (qid, query) in sample.iter().enumerate() {
+        writeln!(writer, "{}\t{}", qid + 1, query)?;
+    }
+    writer.flush()?;
+
+    tracing::info!(
+        "{} queries searched by at least {} users",
+        counts.len(),
+        config.k_anonymity
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn personal_information_is_removed() {
+        assert_eq!(normalize("  Rust   Book "), Some("rust book".to_string()));
+        assert_eq!(normalize("iphone 15"), Some("iphone 15".to_string()));
+        assert_eq!(
+            normalize("best movies 2023"),
+            Some("best movies 2023".to_string())
+        );
+        assert_eq!(
+            normalize("windows 10 2004"),
+            Some("windows 10 2004".to_string())
+        );
+
+        assert_eq!(normalize("john.doe@example.com"), None);
+        assert_eq!(normalize("call +45 12 34 56 78"), None);
+        assert_eq!(normalize("4111 1111 1111 1111"), None);
+        assert_eq!(normalize("ping 192.168.1.1"), None);
+        assert_eq!(normalize("https://example.com/?session=abc"), None);
+        assert_eq!(normalize(&"a".repeat(MAX_QUERY_CHARS + 1)), None);
+        assert_eq!(normalize("   "), None);
+    }
+
+    #[test]
+    fn k_anonymity() {
+        let mut aggregator = QueryLogAggregator::new().unwrap();
+
+        aggregator
+            .add_events(
+                r#"{"qid": "a", "user_id": "1", "query": "Rust", "urls": ["https://rust-lang.org/"], "timestamp": 1}
+{"qid": "b", "user_id": "2", "query": "rust", "urls": ["https://rust-lang.org/"]}
+{"session_id": "3", "query": "rust lang", "urls": ["https://rust-lang.org/"]}
+{"session_id": "4", "query": "rust lang", "urls": ["https://rust-lang.org/"]}
+{"user_id": "5", "query": "my secret query"}
+{"user_id": "5", "query": "my secret query"}
+{"user_id": "5", "query": "my secret query"}
+{"query": "no user"}
+{"query": "no user"}
+not json
+"#
+                .as_bytes(),
+            )
+            .unwrap();
+
+        let counts = aggregator.anonymous_counts(2);
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["rust"], 2);
+        // searched many times, but only by a single user
+        assert!(!counts.contains_key("my secret query"));
+        assert!(!counts.contains_key("no user"));
+        assert_eq!(aggregator.num_without_user, 2);
+
+        let related = aggregator.related(2, 5);
+        assert_eq!(related["rust"], vec![("rust lang".to_string(), 1)]);
+        assert_eq!(related["rust lang"], vec![("rust".to_string(), 1)]);
+
+        let sample = aggregator.evaluation_sample(2, 10, 0);
+        assert_eq!(sample.len(), 2);
+        assert_eq!(sample, aggregator.evaluation_sample(2, 10, 0));
+    }
+}
@@ -469,6 +469,143 @@ mod tests {
         assert_eq!(result.webpages[0].url, "https://www.first.com/");
     }
 
+    #[test]
+    fn parked_domains_are_demoted() {
+        let mut index = Index::temporary().expect("Unable to open index");
+
+        index
+            .insert(&Webpage {
+                html: Html::parse(
+                    r#"
+                    <html>
+                        <head>
+                            <title>Test website</title>
+                        </head>
+                        <body>
+                            This is a test website
+                        </body>
+                    </html>
+                "#,
+                    "https://www.example.com",
+                )
+                .unwrap(),
+                fetch_time_ms: 500,
+                ..Default::default()
+            })
+            .expect("failed to insert webpage");
+        index
+            .insert(&Webpage {
+                html: Html::parse(
+                    r#"
+                    <html>
+                        <head>
+                            <title>Test website</title>
+                        </head>
+                        <body>
+                            This test domain is for sale
+                            <a href="https://dan.com/buy-domain/parked.com">Make an offer</a>
+                        </body>
+                    </html>
+                "#,
+                    "https://www.parked.com",
+                )
+                .unwrap(),
+                host_centrality: 1.0,
+                fetch_time_ms: 500,
+                ..Default::default()
+            })
+            .expect("failed to insert webpage");
+
+        index.commit().expect("failed to commit index");
+        let searcher = LocalSearcher::from(index);
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "test".to_string(),
+                include_parked: true,
+                ..Default::default()
+            })
+            .expect("Search failed");
+
+        assert_eq!(result.webpages.len(), 2);
+        assert_eq!(result.webpages[0].url, "https://www.example.com/");
+        assert_eq!(result.webpages[1].url, "https://www.parked.com/");
+    }
+
+    #[test]
+    fn local_intent_prefers_region() {
+        let mut index = Index::temporary().expect("Unable to open index");
+
+        index
+            .insert(&Webpage {
+                html: Html::parse(
+                    r#"
+                    <html>
+                        <head>
+                            <title>Bakery</title>
+                        </head>
+                        <body>
+                            the best bakery near me, by the harbour
+                            <address>Nyhavn 1, 1051 København, Danmark</address>
+                        </body>
+                    </html>
+                "#,
+                    "https://www.first.com",
+                )
+                .unwrap(),
+                fetch_time_ms: 500,
+                ..Default::default()
+            })
+            .expect("failed to insert webpage");
+        index
+            .insert(&Webpage {
+                html: Html::parse(
+                    r#"
+                    <html>
+                        <head>
+                            <title>Bakery</title>
+                        </head>
+                        <body>
+                            the best bakery near me, by the harbour
+                            <address>1 Harbor St, Boston, USA</address>
+                        </body>
+                    </html>
+                "#,
+                    "https://www.second.com",
+                )
+                .unwrap(),
+                host_centrality: 1.0,
+                fetch_time_ms: 500,
+                ..Default::default()
+            })
+            .expect("failed to insert webpage");
+
+        index.commit().expect("failed to commit index");
+        let searcher = LocalSearcher::from(index);
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "best bakery".to_string(),
+                selected_region: Some(crate::webpage::Region::Denmark),
+                ..Default::default()
+            })
+            .expect("Search failed");
+
+        assert_eq!(result.webpages.len(), 2);
+        assert_eq!(result.webpages[0].url, "https://www.second.com/");
+
+        let result = searcher
+            .search(&SearchQuery {
+                query: "best bakery near me".to_string(),
+                selected_region: Some(crate::webpage::Region::Denmark),
+                ..Default::default()
+            })
+            .expect("Search failed");
+
+        assert_eq!(result.webpages.len(), 2);
+        assert_eq!(result.webpages[0].url, "https://www.first.com/");
+    }
+
     #[test]
     fn custom_signal_aggregation() {
         let mut index = Index::temporary().expect("Unable to open index");
@@ -749,6 +886,12 @@ mod tests {
             page_webgraph: None,
             topics_path: None,
             safety_classifier_path: None,
+            outlink_audit_path: None,
+            outlink_profile_path: None,
+            cloaking_audit_path: None,
+            host_about_path: None,
+            host_clusters_path: None,
+            canonical_index_path: None,
             dual_encoder: Some(IndexingDualEncoderConfig {
                 model_path: data_path.to_str().unwrap().to_string(),
                 page_centrality_rank_threshold: None,
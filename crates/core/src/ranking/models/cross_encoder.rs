@@ -24,6 +24,7 @@ use std::path::Path;
 use tokenizers::PaddingParams;
 use tokenizers::TruncationParams;
 
+use crate::models::accelerator::Accelerator;
 use crate::models::bert;
 use crate::models::bert::BertModel;
 
@@ -35,11 +36,23 @@ pub struct CrossEncoderModel {
     classifier: Linear,
     device: Device,
     dtype: candle_core::DType,
+    batch_size: usize,
 }
 
 impl CrossEncoderModel {
     pub fn open<P: AsRef<Path>>(folder: P) -> Result<Self> {
-        let device = Device::Cpu;
+        Self::open_with_accelerator(folder, &Accelerator::default())
+    }
+
+    pub fn open_with_accelerator<P: AsRef<Path>>(
+        folder: P,
+        accelerator: &Accelerator,
+    ) -> Result<Self> {
+        accelerator.load(|accelerator| Self::load_on(folder.as_ref(), accelerator))
+    }
+
+    fn load_on(folder: &Path, accelerator: &Accelerator) -> Result<Self> {
+        let device = accelerator.device().clone();
         let dtype = candle_core::DType::F16;
 
         let truncation = TruncationParams {
@@ -51,9 +64,8 @@ impl CrossEncoderModel {
             ..Default::default()
         };
 
-        let mut tokenizer =
-            tokenizers::Tokenizer::from_file(folder.as_ref().join("tokenizer.json"))
-                .map_err(|_| anyhow!("couldn't open tokenizer"))?;
+        let mut tokenizer = tokenizers::Tokenizer::from_file(folder.join("tokenizer.json"))
+            .map_err(|_| anyhow!("couldn't open tokenizer"))?;
 
         tokenizer
             .with_truncation(Some(truncation))
@@ -62,19 +74,19 @@ impl CrossEncoderModel {
 
         let vb = unsafe {
             VarBuilder::from_mmaped_safetensors(
-                &[folder.as_ref().join("model.safetensors")],
+                &[folder.join("model.safetensors")],
                 dtype,
                 &device,
             )?
         };
-        let config = std::fs::read_to_string(folder.as_ref().join("config.json"))?;
+        let config = std::fs::read_to_string(folder.join("config.json"))?;
         let mut config: bert::Config = serde_json::from_str(&config)?;
         config.hidden_act = bert::HiddenAct::GeluApproximate;
 
         let classifier: Linear = candle_nn::linear(config.hidden_size, 1, vb.pp("classifier"))?;
 
         // all tensors can be loaded with (useful for debugging):
-        // candle_core::safetensors::load(folder.as_ref().join("model.safetensors"), &device)
+        // candle_core::safetensors::load(folder.join("model.safetensors"), &device)
 
         let encoder = BertModel::load(vb, &config)?;
 
@@ -84,10 +96,25 @@ impl CrossEncoderModel {
             classifier,
             device,
             dtype,
+            batch_size: accelerator.batch_size(),
         })
     }
 
-    fn scores(&self, query: &str, bodies: &[String]) -> Vec<f64> {
+    /// Number of batches the bodies are split into when they are scored.
+    pub fn num_batches(&self, num_bodies: usize) -> usize {
+        num_bodies.div_ceil(self.batch_size.max(1))
+    }
+
+    /// The score between 0 and 1 of each body for the query. [`CrossEncoder::run`]
+    /// turns the scores into ranks.
+    pub fn scores(&self, query: &str, bodies: &[String]) -> Vec<f64> {
+        bodies
+            .chunks(self.batch_size.max(1))
+            .flat_map(|batch| self.batch_scores(query, batch))
+            .collect()
+    }
+
+    fn batch_scores(&self, query: &str, bodies: &[String]) -> Vec<f64> {
         if bodies.is_empty() {
             return Vec::new();
         }
@@ -0,0 +1,168 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Diversification of the results for ambiguous queries. Short queries like "jaguar"
+//! can refer to several unrelated things, so we limit the number of top results
+//! from each cluster of related hosts (see [`crate::host_clusters`]).
+
+use std::collections::HashMap;
+
+use crate::{
+    query::parser::{self, SimpleOrPhrase, Term},
+    searcher::SearchQuery,
+    webgraph::NodeID,
+};
+
+use super::RankableWebpage;
+
+/// Results from a cluster beyond this number are moved below the results from other clusters.
+pub const MAX_RESULTS_PER_CLUSTER: usize = 3;
+
+/// Queries with more terms than this are specific enough that we don't diversify.
+const MAX_AMBIGUOUS_TERMS: usize = 2;
+
+/// Whether the query is short and without any operators, so the user intent can be ambiguous.
+pub fn is_ambiguous(query: &SearchQuery) -> bool {
+    if !query.diversify || query.optic.is_some() {
+        return false;
+    }
+
+    let terms = match parser::parse(&query.query) {
+        Ok(terms) => terms,
+        Err(_) => return false,
+    };
+
+    !terms.is_empty()
+        && terms.len() <= MAX_AMBIGUOUS_TERMS
+        && terms
+            .iter()
+            .all(|term| matches!(term, Term::SimpleOrPhrase(SimpleOrPhrase::Simple(_))))
+}
+
+/// Keep the order of the webpages, but move results that exceed `max_per_cluster`
+/// for their cluster to the end. Webpages without a cluster are never moved.
+pub fn limit_per_cluster<T: RankableWebpage>(webpages: Vec<T>, max_per_cluster: usize) -> Vec<T> {
+    let mut counts: HashMap<NodeID, usize> = HashMap::new();
+    let mut res = Vec::with_capacity(webpages.len());
+    let mut overflow = Vec::new();
+
+    for webpage in webpages {
+        match webpage.host_cluster() {
+            Some(cluster) => {
+                let count = counts.entry(cluster).or_default();
+
+                if *count < max_per_cluster {
+                    *count += 1;
+                    res.push(webpage);
+                } else {
+                    overflow.push(webpage);
+                }
+            }
+            None => res.push(webpage),
+        }
+    }
+
+    res.extend(overflow);
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        collector::{self, Hashes},
+        enum_map::EnumMap,
+        prehashed::Prehashed,
+        ranking::SignalEnum,
+    };
+
+    use super::*;
+
+    struct TestWebpage {
+        id: usize,
+        cluster: Option<NodeID>,
+    }
+
+    impl collector::Doc for TestWebpage {
+        fn score(&self) -> f64 {
+            0.0
+        }
+
+        fn hashes(&self) -> Hashes {
+            Hashes {
+                site: Prehashed(0),
+                title: Prehashed(0),
+                url: Prehashed(0),
+                url_without_tld: Prehashed(0),
+                simhash: 0,
+            }
+        }
+    }
+
+    impl RankableWebpage for TestWebpage {
+        fn set_score(&mut self, _: f64) {}
+
+        fn boost(&self) -> Option<f64> {
+            None
+        }
+
+        fn signals(&self) -> &EnumMap<SignalEnum, f64> {
+            unimplemented!()
+        }
+
+        fn host_cluster(&self) -> Option<NodeID> {
+            self.cluster
+        }
+    }
+
+    #[test]
+    fn ambiguous_queries() {
+        let query = |q: &str| SearchQuery {
+            query: q.to_string(),
+            ..Default::default()
+        };
+
+        assert!(is_ambiguous(&query("jaguar")));
+        assert!(is_ambiguous(&query("python snake")));
+        assert!(!is_ambiguous(&query("")));
+        assert!(!is_ambiguous(&query("jaguar xf engine problems")));
+        assert!(!is_ambiguous(&query("jaguar site:jaguar.com")));
+        assert!(!is_ambiguous(&query("\"jaguar cars\"")));
+        assert!(!is_ambiguous(&SearchQuery {
+            diversify: false,
+            ..query("jaguar")
+        }));
+    }
+
+    #[test]
+    fn limits_results_per_cluster() {
+        let a = Some(NodeID::from(1u64));
+        let b = Some(NodeID::from(2u64));
+
+        let webpages = [a, a, a, None, a, b, a, b]
+            .into_iter()
+            .enumerate()
+            .map(|(id, cluster)| TestWebpage { id, cluster })
+            .collect();
+
+        let res: Vec<_> = limit_per_cluster(webpages, 2)
+            .into_iter()
+            .map(|w| w.id)
+            .collect();
+
+        assert_eq!(res, vec![0, 1, 3, 5, 7, 2, 4, 6]);
+    }
+}
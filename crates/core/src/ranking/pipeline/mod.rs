@@ -21,6 +21,7 @@ use crate::{
     config::CollectorConfig,
     enum_map::EnumMap,
     searcher::SearchQuery,
+    webgraph,
 };
 
 use super::{
@@ -28,6 +29,7 @@ use super::{
     SignalCoefficient, SignalEnum, SignalScore,
 };
 
+mod diversify;
 mod scorers;
 mod stages;
 
@@ -38,6 +40,7 @@ pub trait RankableWebpage: collector::Doc + Send + Sync {
     fn set_score(&mut self, score: f64);
     fn boost(&self) -> Option<f64>;
     fn signals(&self) -> &EnumMap<SignalEnum, f64>;
+    fn host_cluster(&self) -> Option<webgraph::NodeID>;
 
     fn boost_score(&mut self) {
         if let Some(boost) = self.boost() {
@@ -61,6 +64,7 @@ struct RankingStage<T> {
     derank_similar: bool,
     model: Option<Arc<LambdaMART>>,
     coefficients: SignalCoefficient,
+    max_results_per_cluster: Option<usize>,
 }
 
 impl<T: RankableWebpage> RankingStage<T> {
@@ -88,11 +92,13 @@ impl<T: RankableWebpage> RankingStage<T> {
             collector.insert(website);
         }
 
-        collector
-            .into_sorted_vec(self.derank_similar)
-            .into_iter()
-            .take(top_n)
-            .collect()
+        let mut websites = collector.into_sorted_vec(self.derank_similar);
+
+        if let Some(max_per_cluster) = self.max_results_per_cluster {
+            websites = diversify::limit_per_cluster(websites, max_per_cluster);
+        }
+
+        websites.into_iter().take(top_n).collect()
     }
 
     fn calculate_score(&self, signals: &EnumMap<SignalEnum, f64>) -> f64 {
@@ -119,6 +125,8 @@ impl<T: RankableWebpage> RankingStage<T> {
         self.scorer.set_query_info(query);
 
         self.coefficients = query.signal_coefficients();
+        self.max_results_per_cluster =
+            diversify::is_ambiguous(query).then_some(diversify::MAX_RESULTS_PER_CLUSTER);
     }
 }
 
@@ -30,7 +30,7 @@ use crate::{
         SignalEnum,
     },
     searcher::SearchQuery,
-    Result,
+    webgraph, Result,
 };
 
 use super::RecallRankingWebpage;
@@ -77,6 +77,10 @@ impl RankableWebpage for PrecisionRankingWebpage {
     fn signals(&self) -> &EnumMap<SignalEnum, f64> {
         self.ranking.signals()
     }
+
+    fn host_cluster(&self) -> Option<webgraph::NodeID> {
+        self.ranking.host_cluster()
+    }
 }
 
 impl PrecisionRankingWebpage {
@@ -112,6 +116,7 @@ impl RankingPipeline<PrecisionRankingWebpage> {
             derank_similar: true,
             model: lambda,
             coefficients: Default::default(),
+            max_results_per_cluster: None,
         };
 
         Ok(Self {
@@ -119,6 +119,10 @@ impl RankableWebpage for RecallRankingWebpage {
     fn signals(&self) -> &EnumMap<SignalEnum, f64> {
         self.local.signals()
     }
+
+    fn host_cluster(&self) -> Option<webgraph::NodeID> {
+        self.local.host_cluster
+    }
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode)]
@@ -130,6 +134,7 @@ pub struct LocalRecallRankingWebpage {
     keyword_embedding: Option<StoredEmbeddings>,
     score: f64,
     host_id: webgraph::NodeID,
+    host_cluster: Option<webgraph::NodeID>,
 }
 
 impl LocalRecallRankingWebpage {
@@ -147,6 +152,7 @@ impl LocalRecallRankingWebpage {
             keyword_embedding: None,
             score,
             host_id: webgraph::NodeID::from(0u64),
+            host_cluster: None,
         }
     }
 
@@ -175,6 +181,12 @@ impl LocalRecallRankingWebpage {
             .unwrap()
             .into();
 
+        let host_cluster = fastfields
+            .get(fast_field::HostClusterId.into())
+            .and_then(|v| v.as_u64())
+            .filter(|id| *id != u64::MAX)
+            .map(webgraph::NodeID::from);
+
         let mut res = LocalRecallRankingWebpage {
             signals: EnumMap::new(),
             score: pointer.score.total,
@@ -183,6 +195,7 @@ impl LocalRecallRankingWebpage {
             title_embedding: title_embedding.map(StoredEmbeddings),
             keyword_embedding: keyword_embedding.map(StoredEmbeddings),
             host_id,
+            host_cluster,
         };
 
         for computed_signal in computer.compute_signals(pointer.address.doc_id).flatten() {
@@ -246,6 +259,10 @@ impl RankableWebpage for LocalRecallRankingWebpage {
     fn signals(&self) -> &EnumMap<SignalEnum, f64> {
         &self.signals
     }
+
+    fn host_cluster(&self) -> Option<webgraph::NodeID> {
+        self.host_cluster
+    }
 }
 
 impl collector::Doc for LocalRecallRankingWebpage {
@@ -272,6 +289,7 @@ impl RankingPipeline<LocalRecallRankingWebpage> {
             derank_similar: true,
             model: lambdamart,
             coefficients: Default::default(),
+            max_results_per_cluster: None,
         };
 
         Self {
@@ -314,6 +332,7 @@ impl RankingPipeline<api::ScoredWebpagePointer> {
             derank_similar: true,
             model: lambdamart,
             coefficients: Default::default(),
+            max_results_per_cluster: None,
         };
 
         Self {
@@ -79,12 +79,17 @@ pub struct QueryData {
     simple_terms: Vec<String>,
     optic_rules: Vec<optics::Rule>,
     selected_region: Option<crate::webpage::Region>,
+    local_intent: bool,
     lang: Option<whatlang::Lang>,
 }
 impl QueryData {
     pub fn selected_region(&self) -> Option<crate::webpage::Region> {
         self.selected_region
     }
+
+    pub fn local_intent(&self) -> bool {
+        self.local_intent
+    }
 }
 
 pub struct SignalComputer {
@@ -155,6 +160,7 @@ impl SignalComputer {
                 .cloned()
                 .collect(),
             selected_region: q.region().cloned(),
+            local_intent: q.local_intent(),
             lang: q.lang(),
         });
 
@@ -119,6 +119,7 @@ pub struct ComputedSignal {
     Debug,
     Clone,
     Copy,
+    PartialEq,
     serde::Serialize,
     serde::Deserialize,
     bincode::Encode,
@@ -18,8 +18,9 @@ use tantivy::DocId;
 
 use super::{Signal, SignalComputer};
 use crate::{
+    outlink_profile::OutlinkFlags,
     schema::{self, Field, FLOAT_SCALING},
-    webpage::Webpage,
+    webpage::{RegionTags, Webpage},
 };
 
 fn score_timestamp(page_timestamp: usize, signal_computer: &SignalComputer) -> f64 {
@@ -66,6 +67,11 @@ fn score_link_density(link_density: f64) -> f64 {
     }
 }
 
+#[inline]
+fn score_broken_outlinks(broken_ratio: f64) -> f64 {
+    1.0 - broken_ratio.clamp(0.0, 1.0)
+}
+
 fn score_region(webpage_region: crate::webpage::Region, computer: &SignalComputer) -> f64 {
     match computer.region_count() {
         Some(region_count) => {
@@ -671,3 +677,426 @@ impl Signal for LinkDensity {
         Some(score_link_density(val as f64 / FLOAT_SCALING as f64))
     }
 }
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct HostBrokenOutlinkRatio;
+impl Signal for HostBrokenOutlinkRatio {
+    fn default_coefficient(&self) -> f64 {
+        0.05
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(
+            schema::fast_field::HostBrokenOutlinkRatio.into(),
+        ))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(score_broken_outlinks(webpage.host_broken_outlink_ratio))
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+        let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+        let val = fastfield_reader
+            .get(self.as_fastfield().unwrap())
+            .and_then(|v| v.as_u64())
+            .unwrap();
+        Some(score_broken_outlinks(val as f64 / FLOAT_SCALING as f64))
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct HostCloaking;
+impl Signal for HostCloaking {
+    fn default_coefficient(&self) -> f64 {
+        0.1
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::HostIsCloaking.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(if webpage.host_is_cloaking { 0.0 } else { 1.0 })
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+        let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+        let val = fastfield_reader
+            .get(self.as_fastfield().unwrap())
+            .and_then(|v| v.as_u64())
+            .unwrap();
+        Some(if val == 0 { 1.0 } else { 0.0 })
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct HostAbout;
+impl Signal for HostAbout {
+    fn default_coefficient(&self) -> f64 {
+        0.05
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(
+            schema::fast_field::HostAboutCompleteness.into(),
+        ))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(webpage.host_about_completeness)
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+        let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+        let val = fastfield_reader
+            .get(self.as_fastfield().unwrap())
+            .and_then(|v| v.as_u64())
+            .unwrap();
+        Some(val as f64 / FLOAT_SCALING as f64)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct ContentQuality;
+impl Signal for ContentQuality {
+    fn default_coefficient(&self) -> f64 {
+        0.05
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::ContentQuality.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(webpage.html.content_quality())
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+        let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+        let val = fastfield_reader
+            .get(self.as_fastfield().unwrap())
+            .and_then(|v| v.as_u64())
+            .unwrap();
+        Some(val as f64 / FLOAT_SCALING as f64)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct IsHttps;
+impl Signal for IsHttps {
+    fn default_coefficient(&self) -> f64 {
+        0.01
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::IsHttps.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some((webpage.html.url().scheme() == "https").into())
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+        let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+        let val = fastfield_reader
+            .get(self.as_fastfield().unwrap())
+            .and_then(|v| v.as_u64())
+            .unwrap();
+        Some(val as f64)
+    }
+}
+
+fn outlink_flags(
+    field: schema::FastFieldEnum,
+    doc: DocId,
+    signal_computer: &SignalComputer,
+) -> OutlinkFlags {
+    let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+    let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+    fastfield_reader
+        .get(field)
+        .and_then(|v| v.as_u64())
+        .map(OutlinkFlags::from)
+        .unwrap_or_default()
+}
+
+/// Hosts without the flag score 1 and flagged hosts score 0.
+fn score_outlink_flag(flags: OutlinkFlags, flag: OutlinkFlags) -> f64 {
+    if flags.contains(flag) {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+/// The outgoing links of the host are dominated by affiliate links.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct HostAffiliateLinks;
+impl Signal for HostAffiliateLinks {
+    fn default_coefficient(&self) -> f64 {
+        0.05
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::HostOutlinkFlags.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(score_outlink_flag(
+            webpage.host_outlink_flags,
+            OutlinkFlags::AFFILIATE,
+        ))
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let flags = outlink_flags(self.as_fastfield().unwrap(), doc, signal_computer);
+        Some(score_outlink_flag(flags, OutlinkFlags::AFFILIATE))
+    }
+}
+
+/// The host sends its outgoing links through redirect scripts or url shorteners.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct HostLinkRotator;
+impl Signal for HostLinkRotator {
+    fn default_coefficient(&self) -> f64 {
+        0.05
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::HostOutlinkFlags.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(score_outlink_flag(
+            webpage.host_outlink_flags,
+            OutlinkFlags::LINK_ROTATOR,
+        ))
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let flags = outlink_flags(self.as_fastfield().unwrap(), doc, signal_computer);
+        Some(score_outlink_flag(flags, OutlinkFlags::LINK_ROTATOR))
+    }
+}
+
+/// The outgoing links of the host have the footprint of a private blog network.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct HostPbn;
+impl Signal for HostPbn {
+    fn default_coefficient(&self) -> f64 {
+        0.1
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::HostOutlinkFlags.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(score_outlink_flag(
+            webpage.host_outlink_flags,
+            OutlinkFlags::PBN,
+        ))
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let flags = outlink_flags(self.as_fastfield().unwrap(), doc, signal_computer);
+        Some(score_outlink_flag(flags, OutlinkFlags::PBN))
+    }
+}
+
+/// Pages tagged with the region selected by the user score 1 for queries with local
+/// intent. All other pages and queries score 0.
+fn score_local_region(tags: RegionTags, computer: &SignalComputer) -> f64 {
+    let Some(query) = computer.query_data().filter(|q| q.local_intent()) else {
+        return 0.0;
+    };
+
+    match query.selected_region() {
+        Some(region) if tags.contains(region) => 1.0,
+        _ => 0.0,
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct LocalRegion;
+impl Signal for LocalRegion {
+    fn default_coefficient(&self) -> f64 {
+        2.0
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::RegionTags.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, signal_computer: &SignalComputer) -> Option<f64> {
+        Some(score_local_region(
+            webpage.html.region_tags(),
+            signal_computer,
+        ))
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+        let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+        let tags = fastfield_reader
+            .get(self.as_fastfield().unwrap())
+            .and_then(|v| v.as_u64())
+            .map(RegionTags::from)
+            .unwrap_or_default();
+
+        Some(score_local_region(tags, signal_computer))
+    }
+}
+
+/// Parked domains and domain sale pages score 0 and all other pages score 1. Parked pages
+/// are excluded from the results unless the query includes them, and the high coefficient
+/// then ranks them below all regular pages that match the query.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct ParkedDomain;
+impl Signal for ParkedDomain {
+    fn default_coefficient(&self) -> f64 {
+        10.0
+    }
+
+    fn as_field(&self) -> Option<Field> {
+        Some(Field::Fast(schema::fast_field::IsParked.into()))
+    }
+
+    fn precompute(self, webpage: &Webpage, _: &SignalComputer) -> Option<f64> {
+        Some(if webpage.html.is_parked() { 0.0 } else { 1.0 })
+    }
+
+    fn compute(&self, doc: DocId, signal_computer: &SignalComputer) -> Option<f64> {
+        let seg_reader = signal_computer.segment_reader().unwrap().borrow_mut();
+        let fastfield_reader = seg_reader.fastfield_reader().get_field_reader(doc);
+
+        let val = fastfield_reader
+            .get(self.as_fastfield().unwrap())
+            .and_then(|v| v.as_u64())
+            .unwrap();
+        Some(if val == 0 { 1.0 } else { 0.0 })
+    }
+}
@@ -116,6 +116,16 @@ pub enum SignalEnum {
     UrlDigits,
     UrlSlashes,
     LinkDensity,
+    HostBrokenOutlinkRatio,
+    HostCloaking,
+    HostAbout,
+    ContentQuality,
+    IsHttps,
+    HostAffiliateLinks,
+    HostLinkRotator,
+    HostPbn,
+    LocalRegion,
+    ParkedDomain,
     TitleEmbeddingSimilarity,
     KeywordEmbeddingSimilarity,
 }
@@ -160,6 +170,16 @@ enum_dispatch_from_discriminant!(SignalEnumDiscriminants => SignalEnum,
     UrlDigits,
     UrlSlashes,
     LinkDensity,
+    HostBrokenOutlinkRatio,
+    HostCloaking,
+    HostAbout,
+    ContentQuality,
+    IsHttps,
+    HostAffiliateLinks,
+    HostLinkRotator,
+    HostPbn,
+    LocalRegion,
+    ParkedDomain,
     TitleEmbeddingSimilarity,
     KeywordEmbeddingSimilarity,
 ]);
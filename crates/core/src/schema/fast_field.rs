@@ -127,6 +127,15 @@ pub enum FastFieldEnum {
     LikelyHasAds,
     LikelyHasPaywall,
     LinkDensity,
+    HostBrokenOutlinkRatio,
+    HostIsCloaking,
+    HostAboutCompleteness,
+    ContentQuality,
+    HostClusterId,
+    IsHttps,
+    HostOutlinkFlags,
+    RegionTags,
+    IsParked,
     TitleEmbeddings,
     KeywordEmbeddings,
 }
@@ -170,6 +179,15 @@ enum_dispatch_from_discriminant!(FastFieldEnumDiscriminants => FastFieldEnum,
     LikelyHasAds,
     LikelyHasPaywall,
     LinkDensity,
+    HostBrokenOutlinkRatio,
+    HostIsCloaking,
+    HostAboutCompleteness,
+    ContentQuality,
+    HostClusterId,
+    IsHttps,
+    HostOutlinkFlags,
+    RegionTags,
+    IsParked,
     TitleEmbeddings,
     KeywordEmbeddings,
 ]);
@@ -225,6 +243,69 @@ impl FastField for IsHomepage {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IsHttps;
+impl FastField for IsHttps {
+    fn name(&self) -> &str {
+        "is_https"
+    }
+
+    fn add_html_tantivy(
+        &self,
+        html: &Html,
+        _cache: &mut FnCache,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(
+            self.tantivy_field(schema),
+            (html.url().scheme() == "https").into(),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RegionTags;
+impl FastField for RegionTags {
+    fn name(&self) -> &str {
+        "region_tags"
+    }
+
+    fn add_html_tantivy(
+        &self,
+        html: &Html,
+        _cache: &mut FnCache,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(self.tantivy_field(schema), html.region_tags().as_u64());
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IsParked;
+impl FastField for IsParked {
+    fn name(&self) -> &str {
+        "is_parked"
+    }
+
+    fn add_html_tantivy(
+        &self,
+        html: &Html,
+        _cache: &mut FnCache,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(self.tantivy_field(schema), html.is_parked().into());
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct HostCentrality;
 impl FastField for HostCentrality {
@@ -1145,6 +1226,198 @@ impl FastField for LinkDensity {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HostBrokenOutlinkRatio;
+impl FastField for HostBrokenOutlinkRatio {
+    fn name(&self) -> &str {
+        "host_broken_outlink_ratio"
+    }
+
+    fn add_html_tantivy(
+        &self,
+        _html: &Html,
+        _cache: &mut FnCache,
+        _doc: &mut TantivyDocument,
+        _schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_webpage_tantivy(
+        &self,
+        webpage: &Webpage,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(
+            self.tantivy_field(schema),
+            (webpage.host_broken_outlink_ratio * FLOAT_SCALING as f64) as u64,
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HostIsCloaking;
+impl FastField for HostIsCloaking {
+    fn name(&self) -> &str {
+        "host_is_cloaking"
+    }
+
+    fn add_html_tantivy(
+        &self,
+        _html: &Html,
+        _cache: &mut FnCache,
+        _doc: &mut TantivyDocument,
+        _schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_webpage_tantivy(
+        &self,
+        webpage: &Webpage,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(self.tantivy_field(schema), webpage.host_is_cloaking as u64);
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HostOutlinkFlags;
+impl FastField for HostOutlinkFlags {
+    fn name(&self) -> &str {
+        "host_outlink_flags"
+    }
+
+    fn add_html_tantivy(
+        &self,
+        _html: &Html,
+        _cache: &mut FnCache,
+        _doc: &mut TantivyDocument,
+        _schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_webpage_tantivy(
+        &self,
+        webpage: &Webpage,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(
+            self.tantivy_field(schema),
+            webpage.host_outlink_flags.as_u64(),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HostAboutCompleteness;
+impl FastField for HostAboutCompleteness {
+    fn name(&self) -> &str {
+        "host_about_completeness"
+    }
+
+    fn add_html_tantivy(
+        &self,
+        _html: &Html,
+        _cache: &mut FnCache,
+        _doc: &mut TantivyDocument,
+        _schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_webpage_tantivy(
+        &self,
+        webpage: &Webpage,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(
+            self.tantivy_field(schema),
+            (webpage.host_about_completeness * FLOAT_SCALING as f64) as u64,
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentQuality;
+impl FastField for ContentQuality {
+    fn name(&self) -> &str {
+        "content_quality"
+    }
+
+    fn is_stored(&self) -> bool {
+        true
+    }
+
+    fn add_html_tantivy(
+        &self,
+        html: &Html,
+        _cache: &mut FnCache,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_u64(
+            self.tantivy_field(schema),
+            (html.content_quality() * FLOAT_SCALING as f64) as u64,
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HostClusterId;
+impl FastField for HostClusterId {
+    fn name(&self) -> &str {
+        "host_cluster_id"
+    }
+
+    fn is_stored(&self) -> bool {
+        true
+    }
+
+    fn add_html_tantivy(
+        &self,
+        _html: &Html,
+        _cache: &mut FnCache,
+        _doc: &mut TantivyDocument,
+        _schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn add_webpage_tantivy(
+        &self,
+        webpage: &Webpage,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        match &webpage.host_cluster {
+            Some(cluster) => {
+                doc.add_u64(self.tantivy_field(schema), cluster.as_u64());
+            }
+            None => {
+                doc.add_u64(self.tantivy_field(schema), u64::MAX);
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TitleEmbeddings;
 impl FastField for TitleEmbeddings {
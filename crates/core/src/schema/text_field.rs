@@ -39,6 +39,9 @@ use crate::webpage::html::FnCache;
 
 use super::IndexingOption;
 
+/// Token stored in [`ParkedDomain`] for parked pages.
+pub const PARKED_TOKEN: &str = "parked";
+
 #[enum_dispatch]
 pub trait TextField:
     Clone + Copy + std::fmt::Debug + PartialEq + Eq + std::hash::Hash + Into<TextFieldEnum>
@@ -181,9 +184,13 @@ pub enum TextFieldEnum {
     MicroformatTags,
     /// can either be NSFW or SFW (see safety classifier)
     SafetyClassification,
+    ParkedDomain,
     InsertionTimestamp,
     RecipeFirstIngredientTagId,
     Keywords,
+    Breadcrumb,
+    /// ISO 639-3 code of the language detected in the text of the page
+    Language,
 }
 
 enum_dispatch_from_discriminant!(TextFieldEnumDiscriminants => TextFieldEnum,
@@ -217,9 +224,12 @@ enum_dispatch_from_discriminant!(TextFieldEnumDiscriminants => TextFieldEnum,
     TitleTrigrams,
     MicroformatTags,
     SafetyClassification,
+    ParkedDomain,
     InsertionTimestamp,
     RecipeFirstIngredientTagId,
     Keywords,
+    Breadcrumb,
+    Language,
 ]);
 
 impl TextFieldEnum {
@@ -1433,6 +1443,41 @@ impl TextField for SafetyClassification {
     }
 }
 
+/// Contains the token `parked` if the page is a parked domain or a domain sale page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParkedDomain;
+impl TextField for ParkedDomain {
+    fn name(&self) -> &str {
+        "parked_domain"
+    }
+
+    fn tokenizer(&self, _: Option<&whatlang::Lang>) -> Tokenizer {
+        Tokenizer::Identity(Identity {})
+    }
+
+    fn add_html_tantivy(
+        &self,
+        html: &Html,
+        _cache: &mut FnCache,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        let parked = if html.is_parked() {
+            PARKED_TOKEN.to_string()
+        } else {
+            String::new()
+        };
+
+        doc.add_text(
+            self.tantivy_field(schema)
+                .unwrap_or_else(|| panic!("could not find field '{}' in index", self.name())),
+            parked,
+        );
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct InsertionTimestamp;
 impl TextField for InsertionTimestamp {
@@ -1544,3 +1589,63 @@ impl TextField for Keywords {
         Ok(())
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Breadcrumb;
+impl TextField for Breadcrumb {
+    fn name(&self) -> &str {
+        "breadcrumb"
+    }
+
+    fn is_stored(&self) -> bool {
+        true
+    }
+
+    fn add_html_tantivy(
+        &self,
+        _html: &Html,
+        cache: &mut FnCache,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_text(
+            self.tantivy_field(schema)
+                .unwrap_or_else(|| panic!("could not find field '{}' in index", self.name())),
+            cache.breadcrumb().join("\n"),
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Language;
+impl TextField for Language {
+    fn name(&self) -> &str {
+        "language"
+    }
+
+    fn tokenizer(&self, _: Option<&whatlang::Lang>) -> Tokenizer {
+        Tokenizer::Identity(Identity {})
+    }
+
+    fn is_stored(&self) -> bool {
+        true
+    }
+
+    fn add_html_tantivy(
+        &self,
+        html: &Html,
+        _cache: &mut FnCache,
+        doc: &mut TantivyDocument,
+        schema: &tantivy::schema::Schema,
+    ) -> Result<()> {
+        doc.add_text(
+            self.tantivy_field(schema)
+                .unwrap_or_else(|| panic!("could not find field '{}' in index", self.name())),
+            html.lang().map(|lang| lang.code()).unwrap_or_default(),
+        );
+
+        Ok(())
+    }
+}
@@ -15,7 +15,10 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod entity;
+mod product;
+mod recipe;
 mod schema_org;
+mod scholarly;
 mod stack_overflow;
 
 use std::collections::HashMap;
@@ -31,16 +34,28 @@ use crate::api::search::ReturnBody;
 use crate::{
     highlighted::HighlightedFragment,
     inverted_index::RetrievedWebpage,
+    localization::Language,
     ranking::{SignalEnumDiscriminants, SignalScore},
     searcher::SearchQuery,
     snippet::TextSnippet,
+    threat_list::Threat,
     web_spell::{self, CorrectionTerm},
-    webpage::url_ext::UrlExt,
+    webpage::{
+        homograph::{self, Homograph},
+        url_cleaner::url_cleaner,
+        url_ext::UrlExt,
+    },
 };
 
 pub use self::stack_overflow::{create_stackoverflow_sidebar, CodeOrText};
 pub use entity::DisplayedEntity;
+pub use product::{
+    Availability, DisplayedProduct, ProductFilter, ProductPage, MAX_PRODUCT_FILTER_RESULTS,
+    PRODUCT_FILTER_OVERFETCH,
+};
+pub use recipe::{recipe_group, DisplayedRecipe, GroupedRecipe};
 pub use schema_org::{OneOrManyProperty, OneOrManyString, Property, StructuredData};
+pub use scholarly::PaperMetadata;
 
 pub use self::stack_overflow::{stackoverflow_snippet, StackOverflowAnswer, StackOverflowQuestion};
 
@@ -62,6 +77,15 @@ pub enum RichSnippet {
         question: StackOverflowQuestion,
         answers: Vec<StackOverflowAnswer>,
     },
+    Product {
+        product: DisplayedProduct,
+    },
+    Recipe {
+        recipe: DisplayedRecipe,
+    },
+    Paper {
+        paper: PaperMetadata,
+    },
 }
 
 #[derive(
@@ -130,33 +154,43 @@ fn prettify_url(url: &Url) -> String {
     pretty_url
 }
 
-fn prettify_date(date: NaiveDateTime) -> String {
+/// Use the breadcrumb of the page if we have one, as it is usually more readable than the raw url.
+fn prettify_breadcrumb(url: &Url, breadcrumb: &[String]) -> String {
+    if breadcrumb.is_empty() {
+        return prettify_url(url);
+    }
+
+    let mut pretty_url = url.scheme().to_string() + "://" + url.host_str().unwrap_or_default();
+
+    for crumb in breadcrumb {
+        pretty_url.push_str(" › ");
+        pretty_url.push_str(crumb);
+    }
+
+    pretty_url
+}
+
+fn prettify_date(date: NaiveDateTime, language: Language) -> String {
     let current_time = Utc::now().naive_utc();
     let diff = current_time.signed_duration_since(date);
 
     let num_hours = diff.num_hours() + 1;
     if num_hours < 24 {
-        if num_hours <= 1 {
-            return "1 hour ago".to_string();
-        }
-
-        return format!("{num_hours} hours ago");
+        return language.hours_ago(num_hours);
     }
 
     let num_days = diff.num_days();
     if num_days < 30 {
-        if num_days <= 1 {
-            return "1 day ago".to_string();
-        }
-
-        return format!("{num_days} days ago");
+        return language.days_ago(num_days);
     }
 
-    format!("{}", date.format("%d. %b. %Y"))
+    language.format_date(date)
 }
 
-fn generate_snippet(webpage: &RetrievedWebpage) -> Snippet {
-    let last_updated = webpage.updated_time.map(prettify_date);
+fn generate_snippet(webpage: &RetrievedWebpage, language: Language) -> Snippet {
+    let last_updated = webpage
+        .updated_time
+        .map(|date| prettify_date(date, language));
 
     Snippet {
         date: last_updated,
@@ -178,6 +212,18 @@ fn generate_rich_snippet(webpage: &RetrievedWebpage) -> Option<RichSnippet> {
         }
     }
 
+    if let Some(product) = product::product(&webpage.schema_org) {
+        return Some(RichSnippet::Product { product });
+    }
+
+    if let Some(recipe) = recipe::recipe(&webpage.schema_org) {
+        return Some(RichSnippet::Recipe { recipe });
+    }
+
+    if let Some(paper) = scholarly::paper_metadata(&webpage.schema_org) {
+        return Some(RichSnippet::Paper { paper });
+    }
+
     None
 }
 
@@ -188,9 +234,13 @@ fn generate_rich_snippet(webpage: &RetrievedWebpage) -> Option<RichSnippet> {
 pub struct DisplayedWebpage {
     pub title: String,
     pub url: String,
+    /// The url as it is stored in the index, before it was cleaned for display.
+    #[serde(skip)]
+    pub indexed_url: String,
     pub site: String,
     pub domain: String,
     pub pretty_url: String,
+    pub breadcrumb: Vec<String>,
     pub snippet: Snippet,
     #[cfg(feature = "return_body")]
     pub body: Option<String>,
@@ -200,6 +250,10 @@ pub struct DisplayedWebpage {
     pub score: Option<f64>,
     pub likely_has_ads: bool,
     pub likely_has_paywall: bool,
+    /// Set if the domain uses characters that imitate another domain.
+    pub homograph_warning: Option<Homograph>,
+    /// Set if the page is on a malware or phishing list.
+    pub security_threat: Option<Threat>,
 }
 
 #[derive(
@@ -216,12 +270,16 @@ pub struct DisplayedAnswer {
 
 impl DisplayedWebpage {
     pub fn new(webpage: RetrievedWebpage, query: &SearchQuery) -> Self {
-        let snippet = generate_snippet(&webpage);
+        let snippet = generate_snippet(&webpage, query.language);
         let rich_snippet = generate_rich_snippet(&webpage);
 
-        let url = Url::parse(&webpage.url).unwrap();
+        let mut url = Url::parse(&webpage.url).unwrap();
+        // pages indexed before a rule was added can still have tracking parameters
+        url_cleaner().clean(&mut url);
+
         let domain = url.root_domain().unwrap_or_default().to_string();
-        let pretty_url = prettify_url(&url);
+        let pretty_url = prettify_breadcrumb(&url, &webpage.breadcrumb);
+        let homograph_warning = url.host_str().and_then(homograph::detect);
 
         let structured_data = if query.return_structured_data {
             Some(
@@ -244,8 +302,10 @@ impl DisplayedWebpage {
         Self {
             title: webpage.title,
             site: url.normalized_host().unwrap_or_default().to_string(),
-            url: webpage.url,
+            url: url.to_string(),
+            indexed_url: webpage.url,
             pretty_url,
+            breadcrumb: webpage.breadcrumb,
             domain,
             snippet,
             #[cfg(feature = "return_body")]
@@ -254,6 +314,8 @@ impl DisplayedWebpage {
             score: None,
             likely_has_ads: webpage.likely_has_ads,
             likely_has_paywall: webpage.likely_has_paywall,
+            homograph_warning,
+            security_threat: None,
             rich_snippet,
             structured_data,
         }
@@ -280,19 +342,31 @@ mod tests {
     #[test]
     fn prettify_date_in_hours() {
         let date = Utc::now().naive_utc();
-        assert_eq!(prettify_date(date), "1 hour ago".to_string());
+        assert_eq!(
+            prettify_date(date, Language::English),
+            "1 hour ago".to_string()
+        );
 
         let date = (Utc::now() - chrono::Duration::seconds(4000)).naive_utc();
-        assert_eq!(prettify_date(date), "2 hours ago".to_string());
+        assert_eq!(
+            prettify_date(date, Language::English),
+            "2 hours ago".to_string()
+        );
     }
 
     #[test]
     fn prettify_date_days() {
         let date = (Utc::now() - chrono::Duration::days(1)).naive_utc();
-        assert_eq!(prettify_date(date), "1 day ago".to_string());
+        assert_eq!(
+            prettify_date(date, Language::English),
+            "1 day ago".to_string()
+        );
 
         let date = (Utc::now() - chrono::Duration::days(2)).naive_utc();
-        assert_eq!(prettify_date(date), "2 days ago".to_string());
+        assert_eq!(
+            prettify_date(date, Language::English),
+            "2 days ago".to_string()
+        );
     }
 
     #[test]
@@ -300,6 +374,27 @@ mod tests {
         let date = DateTime::parse_from_rfc3339("1996-12-19T16:39:57-08:00")
             .unwrap()
             .naive_local();
-        assert_eq!(prettify_date(date), "19. Dec. 1996".to_string());
+        assert_eq!(
+            prettify_date(date, Language::English),
+            "19. Dec. 1996".to_string()
+        );
+        assert_eq!(
+            prettify_date(date, Language::German),
+            "19.12.1996".to_string()
+        );
+    }
+
+    #[test]
+    fn prettify_breadcrumb_falls_back_to_url() {
+        let url = Url::parse("https://www.example.com/books/scifi?id=1").unwrap();
+
+        assert_eq!(
+            prettify_breadcrumb(&url, &[]),
+            "https://www.example.com › books › scifi".to_string()
+        );
+        assert_eq!(
+            prettify_breadcrumb(&url, &["Books".to_string(), "Science Fiction".to_string()]),
+            "https://www.example.com › Books › Science Fiction".to_string()
+        );
     }
 }
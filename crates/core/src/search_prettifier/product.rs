@@ -0,0 +1,518 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use utoipa::ToSchema;
+
+use crate::{collector::approx_count::Count, webpage::schema_org::Item};
+
+use super::{DisplayedWebpage, RichSnippet};
+
+const PRODUCT_TERMS: [&str; 12] = [
+    "buy", "cheap", "cheapest", "deal", "deals", "discount", "order", "price", "prices", "sale",
+    "shop", "store",
+];
+
+/// The product filter removes results after they have been retrieved, so this many
+/// times the requested results are fetched to fill the page after filtering.
+pub const PRODUCT_FILTER_OVERFETCH: usize = 3;
+
+/// Maximum number of results fetched for a query with a product filter.
+pub const MAX_PRODUCT_FILTER_RESULTS: usize = 500;
+
+/// The part of the product-filtered results that makes up a page. The filter only
+/// ever sees the first [`MAX_PRODUCT_FILTER_RESULTS`] results, so pages are only
+/// served within that window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProductPage {
+    offset: usize,
+    limit: usize,
+}
+
+impl ProductPage {
+    /// Returns `None` if the page starts beyond the results the filter can see.
+    pub fn new(page: usize, num_results: usize) -> Option<Self> {
+        let offset = page * num_results;
+
+        if offset >= MAX_PRODUCT_FILTER_RESULTS {
+            return None;
+        }
+
+        Some(Self {
+            offset,
+            limit: num_results,
+        })
+    }
+
+    /// Number of unfiltered results to fetch, starting from the first page.
+    pub fn num_fetched(&self) -> usize {
+        ((self.offset + self.limit) * PRODUCT_FILTER_OVERFETCH).min(MAX_PRODUCT_FILTER_RESULTS)
+    }
+
+    /// Whether the next page can return any results. Pages past the cap are never
+    /// served, so this is false once the cap is reached even if the index has more hits.
+    pub fn has_next(&self, num_filtered: usize, has_more_unfiltered: bool) -> bool {
+        let end = self.offset + self.limit;
+        end < MAX_PRODUCT_FILTER_RESULTS && (has_more_unfiltered || num_filtered > end)
+    }
+
+    /// Select the page from the filtered results.
+    pub fn select<T>(&self, results: Vec<T>) -> Vec<T> {
+        results
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit)
+            .collect()
+    }
+}
+
+/// Simple keyword based classifier that decides whether the user is shopping for a product.
+pub fn is_product_query(query: &str) -> bool {
+    query
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|term| PRODUCT_TERMS.contains(&term))
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Availability {
+    InStock,
+    OutOfStock,
+    PreOrder,
+    Discontinued,
+}
+
+impl Availability {
+    /// Parse the availability from an `ItemAvailability` value.
+    /// These are usually urls like `https://schema.org/InStock`, but some sites
+    /// only use the last segment.
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().trim_end_matches('/');
+        let name = s.rsplit('/').next().unwrap_or(s);
+
+        match name.to_ascii_lowercase().as_str() {
+            "instock" | "instoreonly" | "onlineonly" | "limitedavailability" => {
+                Some(Availability::InStock)
+            }
+            "outofstock" | "soldout" => Some(Availability::OutOfStock),
+            "preorder" | "presale" | "backorder" => Some(Availability::PreOrder),
+            "discontinued" => Some(Availability::Discontinued),
+            _ => None,
+        }
+    }
+}
+
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Clone, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayedProduct {
+    pub name: Option<String>,
+    pub price: Option<f64>,
+    /// Only set if the product has multiple offers with different prices.
+    pub high_price: Option<f64>,
+    pub currency: Option<String>,
+    pub availability: Option<Availability>,
+    pub rating: Option<f64>,
+    pub best_rating: Option<f64>,
+    pub num_reviews: Option<u64>,
+}
+
+/// Filter the product results of a search. Results without any product data are
+/// never removed by the filter.
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ProductFilter {
+    #[serde(default)]
+    pub in_stock: bool,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+}
+
+impl ProductFilter {
+    /// Whether the filter does not remove any results.
+    pub fn is_empty(&self) -> bool {
+        !self.in_stock && self.min_price.is_none() && self.max_price.is_none()
+    }
+
+    /// The filter for the query, if it restricts the results and the query has product
+    /// intent. Other queries are not filtered, as most of their results are not products.
+    pub fn for_query(filter: Option<&Self>, query: &str) -> Option<Self> {
+        filter
+            .filter(|filter| !filter.is_empty() && is_product_query(query))
+            .cloned()
+    }
+
+    pub fn matches(&self, product: &DisplayedProduct) -> bool {
+        if self.in_stock && product.availability != Some(Availability::InStock) {
+            return false;
+        }
+
+        if self.min_price.is_some() || self.max_price.is_some() {
+            let Some(low) = product.price else {
+                return false;
+            };
+            let high = product.high_price.unwrap_or(low);
+
+            if let Some(min_price) = self.min_price {
+                if high < min_price {
+                    return false;
+                }
+            }
+
+            if let Some(max_price) = self.max_price {
+                if low > max_price {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Remove the products that do not match the filter, and return the number of hits
+    /// corrected for the removed results. If all the hits were retrieved, the count is
+    /// exact. Otherwise it is estimated from the share of the retrieved results that
+    /// were kept.
+    pub fn retain(
+        &self,
+        webpages: &mut Vec<DisplayedWebpage>,
+        num_hits: Count,
+        all_retrieved: bool,
+    ) -> Count {
+        let retrieved = webpages.len();
+
+        webpages.retain(|webpage| match &webpage.rich_snippet {
+            Some(RichSnippet::Product { product }) => self.matches(product),
+            _ => true,
+        });
+
+        if all_retrieved {
+            return Count::Exact(webpages.len() as u64);
+        }
+
+        if retrieved == 0 {
+            return num_hits;
+        }
+
+        Count::Approximate(
+            (num_hits.as_u64() as f64 * webpages.len() as f64 / retrieved as f64).round() as u64,
+        )
+    }
+}
+
+fn string_of(item: &Item, key: &str) -> Option<String> {
+    item.properties
+        .get(key)
+        .and_then(|p| p.clone().one())
+        .and_then(|p| p.try_into_string())
+}
+
+fn item_of(item: &Item, key: &str) -> Option<Item> {
+    item.properties
+        .get(key)
+        .and_then(|p| p.clone().one())
+        .and_then(|p| p.try_into_item())
+}
+
+/// Parse prices like "19.99", "$1,299.00" or "12,50 €".
+fn parse_price(s: &str) -> Option<f64> {
+    let s: String = s
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .collect();
+
+    let s = if s.contains('.') {
+        s.replace(',', "")
+    } else {
+        s.replace(',', ".")
+    };
+
+    s.parse().ok().filter(|price: &f64| price.is_finite())
+}
+
+fn number_of(item: &Item, key: &str) -> Option<f64> {
+    string_of(item, key).and_then(|s| parse_price(&s))
+}
+
+pub fn product(schemas: &[Item]) -> Option<DisplayedProduct> {
+    let item = schemas.iter().find(|item| item.types_contains("Product"))?;
+
+    let mut res = DisplayedProduct {
+        name: string_of(item, "name"),
+        price: None,
+        high_price: None,
+        currency: None,
+        availability: None,
+        rating: None,
+        best_rating: None,
+        num_reviews: None,
+    };
+
+    let offers = item
+        .properties
+        .get("offers")
+        .cloned()
+        .map(|offers| offers.many())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|offer| offer.try_into_item());
+
+    for offer in offers {
+        res.currency = res.currency.or_else(|| string_of(&offer, "priceCurrency"));
+
+        if res.availability != Some(Availability::InStock) {
+            if let Some(availability) =
+                string_of(&offer, "availability").and_then(|s| Availability::parse(&s))
+            {
+                res.availability = Some(availability);
+            }
+        }
+
+        let (low, high) = if offer.types_contains("AggregateOffer") {
+            (
+                number_of(&offer, "lowPrice").or_else(|| number_of(&offer, "price")),
+                number_of(&offer, "highPrice"),
+            )
+        } else {
+            (number_of(&offer, "price"), None)
+        };
+
+        if let Some(low) = low {
+            let high = high.unwrap_or(low).max(low);
+
+            res.price = Some(res.price.map_or(low, |price| price.min(low)));
+            res.high_price = Some(res.high_price.map_or(high, |price| price.max(high)));
+        }
+    }
+
+    if res.high_price == res.price {
+        res.high_price = None;
+    }
+
+    if let Some(rating) = item_of(item, "aggregateRating") {
+        res.rating = number_of(&rating, "ratingValue");
+        res.best_rating = number_of(&rating, "bestRating");
+        res.num_reviews = number_of(&rating, "reviewCount")
+            .or_else(|| number_of(&rating, "ratingCount"))
+            .map(|n| n as u64);
+    }
+
+    if res.price.is_none() && res.availability.is_none() && res.rating.is_none() {
+        return None;
+    }
+
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::webpage::Html;
+
+    use super::*;
+
+    fn product_from_html(html: &str) -> Option<DisplayedProduct> {
+        let html = Html::parse(html, "https://www.example.com/").unwrap();
+        product(&html.schema_org())
+    }
+
+    #[test]
+    fn offer() {
+        let product = product_from_html(
+            r#"
+            <html>
+                <head>
+                    <script type="application/ld+json">
+                    {
+                        "@context": "https://schema.org/",
+                        "@type": "Product",
+                        "name": "Executive Anvil",
+                        "offers": {
+                            "@type": "Offer",
+                            "price": 119.99,
+                            "priceCurrency": "USD",
+                            "availability": "https://schema.org/InStock"
+                        },
+                        "aggregateRating": {
+                            "@type": "AggregateRating",
+                            "ratingValue": "4.4",
+                            "reviewCount": 89
+                        }
+                    }
+                    </script>
+                </head>
+                <body></body>
+            </html>
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(product.name, Some("Executive Anvil".to_string()));
+        assert_eq!(product.price, Some(119.99));
+        assert_eq!(product.high_price, None);
+        assert_eq!(product.currency, Some("USD".to_string()));
+        assert_eq!(product.availability, Some(Availability::InStock));
+        assert_eq!(product.rating, Some(4.4));
+        assert_eq!(product.num_reviews, Some(89));
+    }
+
+    #[test]
+    fn aggregate_offer() {
+        let product = product_from_html(
+            r#"
+            <html>
+                <head>
+                    <script type="application/ld+json">
+                    {
+                        "@context": "https://schema.org/",
+                        "@type": "Product",
+                        "name": "Executive Anvil",
+                        "offers": {
+                            "@type": "AggregateOffer",
+                            "lowPrice": "1,119.99",
+                            "highPrice": "1,299.00",
+                            "priceCurrency": "USD",
+                            "availability": "OutOfStock"
+                        }
+                    }
+                    </script>
+                </head>
+                <body></body>
+            </html>
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(product.price, Some(1119.99));
+        assert_eq!(product.high_price, Some(1299.0));
+        assert_eq!(product.availability, Some(Availability::OutOfStock));
+        assert_eq!(product.rating, None);
+    }
+
+    #[test]
+    fn no_product() {
+        assert!(product_from_html(
+            r#"
+            <html>
+                <head><title>Test</title></head>
+                <body>test</body>
+            </html>
+            "#,
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn filter() {
+        let product = DisplayedProduct {
+            name: None,
+            price: Some(10.0),
+            high_price: Some(20.0),
+            currency: None,
+            availability: Some(Availability::OutOfStock),
+            rating: None,
+            best_rating: None,
+            num_reviews: None,
+        };
+
+        assert!(ProductFilter::default().matches(&product));
+        assert!(ProductFilter::default().is_empty());
+        assert!(!ProductFilter {
+            in_stock: true,
+            ..Default::default()
+        }
+        .matches(&product));
+        assert!(ProductFilter {
+            min_price: Some(15.0),
+            max_price: Some(30.0),
+            ..Default::default()
+        }
+        .matches(&product));
+        assert!(!ProductFilter {
+            min_price: Some(25.0),
+            ..Default::default()
+        }
+        .matches(&product));
+        assert!(!ProductFilter {
+            max_price: Some(5.0),
+            ..Default::default()
+        }
+        .matches(&product));
+    }
+
+    #[test]
+    fn price() {
+        assert_eq!(parse_price("19.99"), Some(19.99));
+        assert_eq!(parse_price("$1,299.00"), Some(1299.0));
+        assert_eq!(parse_price("12,50 €"), Some(12.5));
+        assert_eq!(parse_price("free"), None);
+    }
+
+    #[test]
+    fn product_intent() {
+        assert!(is_product_query("buy running shoes"));
+        assert!(is_product_query("Cheapest 4K TV"));
+        assert!(is_product_query("laptop prices"));
+        assert!(!is_product_query("running shoes history"));
+        assert!(!is_product_query("shopify api"));
+
+        let filter = ProductFilter {
+            in_stock: true,
+            ..Default::default()
+        };
+        assert!(ProductFilter::for_query(Some(&filter), "buy running shoes").is_some());
+        assert!(ProductFilter::for_query(Some(&filter), "running shoes history").is_none());
+        assert!(ProductFilter::for_query(Some(&ProductFilter::default()), "buy shoes").is_none());
+        assert!(ProductFilter::for_query(None, "buy running shoes").is_none());
+    }
+
+    #[test]
+    fn page_within_cap() {
+        let page = ProductPage::new(1, 10).unwrap();
+        assert_eq!(page.num_fetched(), 60);
+        assert_eq!(page.select((0..30).collect()), (10..20).collect::<Vec<_>>());
+        assert!(page.has_next(21, false));
+        assert!(!page.has_next(20, false));
+        assert!(page.has_next(20, true));
+
+        let last = ProductPage::new(49, 10).unwrap();
+        assert_eq!(last.num_fetched(), MAX_PRODUCT_FILTER_RESULTS);
+        assert!(!last.has_next(MAX_PRODUCT_FILTER_RESULTS, true));
+
+        assert!(ProductPage::new(50, 10).is_none());
+    }
+}
@@ -0,0 +1,314 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use utoipa::ToSchema;
+
+use crate::webpage::schema_org::{Item, Property};
+
+use super::{DisplayedWebpage, RichSnippet};
+
+/// Only group recipes if at least this many results have a thumbnail.
+const MIN_GROUP_SIZE: usize = 3;
+const MAX_GROUP_SIZE: usize = 8;
+
+const RECIPE_TERMS: [&str; 4] = ["recipe", "recipes", "recept", "rezept"];
+const COOKING_VERBS: [&str; 6] = ["make", "cook", "bake", "prepare", "grill", "roast"];
+
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Clone, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayedRecipe {
+    pub name: Option<String>,
+    pub thumbnail: Option<String>,
+    pub total_time_minutes: Option<u32>,
+    pub total_time: Option<String>,
+    pub rating: Option<f64>,
+    pub num_reviews: Option<u64>,
+    pub num_ingredients: usize,
+}
+
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Clone, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupedRecipe {
+    pub title: String,
+    pub url: String,
+    pub site: String,
+    pub recipe: DisplayedRecipe,
+}
+
+/// Parse an ISO 8601 duration (e.g. `PT1H30M`) into minutes.
+fn parse_duration(s: &str) -> Option<u32> {
+    let s = s.trim().to_ascii_uppercase();
+    let s = s.strip_prefix('P')?;
+
+    let mut seconds: u32 = 0;
+    let mut num = String::new();
+    let mut in_time = false;
+    let mut found_any = false;
+
+    for c in s.chars() {
+        match c {
+            'T' => in_time = true,
+            '0'..='9' | '.' => num.push(c),
+            unit => {
+                let n: f64 = num.parse().ok()?;
+                num.clear();
+
+                let unit_seconds = match (unit, in_time) {
+                    ('W', false) => 7 * 24 * 60 * 60,
+                    ('D', false) => 24 * 60 * 60,
+                    ('H', true) => 60 * 60,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+
+                seconds = seconds.saturating_add((n * unit_seconds as f64) as u32);
+                found_any = true;
+            }
+        }
+    }
+
+    if !found_any || !num.is_empty() {
+        return None;
+    }
+
+    Some(seconds.div_ceil(60))
+}
+
+fn prettify_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{m} min"),
+        (h, 0) => format!("{h} h"),
+        (h, m) => format!("{h} h {m} min"),
+    }
+}
+
+fn string_of(item: &Item, key: &str) -> Option<String> {
+    item.properties
+        .get(key)
+        .and_then(|p| p.clone().one())
+        .and_then(|p| p.try_into_string())
+}
+
+fn thumbnail(item: &Item) -> Option<String> {
+    let image = item
+        .properties
+        .get("image")?
+        .clone()
+        .many()
+        .into_iter()
+        .next()?;
+
+    let url = match image {
+        Property::String(url) => url,
+        Property::Item(image) => {
+            string_of(&image, "thumbnailUrl").or_else(|| string_of(&image, "url"))?
+        }
+    };
+
+    if url.starts_with("https://") || url.starts_with("http://") {
+        Some(url)
+    } else {
+        None
+    }
+}
+
+pub fn recipe(schemas: &[Item]) -> Option<DisplayedRecipe> {
+    let item = schemas.iter().find(|item| item.types_contains("Recipe"))?;
+
+    let total_time_minutes = string_of(item, "totalTime")
+        .and_then(|s| parse_duration(&s))
+        .or_else(|| {
+            let prep = string_of(item, "prepTime").and_then(|s| parse_duration(&s));
+            let cook = string_of(item, "cookTime").and_then(|s| parse_duration(&s));
+
+            match (prep, cook) {
+                (None, None) => None,
+                (prep, cook) => Some(prep.unwrap_or(0) + cook.unwrap_or(0)),
+            }
+        })
+        .filter(|minutes| *minutes > 0);
+
+    let num_ingredients = item
+        .properties
+        .get("recipeIngredient")
+        .or_else(|| item.properties.get("ingredients"))
+        .cloned()
+        .map(|ingredients| ingredients.many().len())
+        .unwrap_or_default();
+
+    let (rating, num_reviews) = item
+        .properties
+        .get("aggregateRating")
+        .and_then(|p| p.clone().one())
+        .and_then(|p| p.try_into_item())
+        .map(|rating| {
+            (
+                string_of(&rating, "ratingValue").and_then(|s| s.trim().parse().ok()),
+                string_of(&rating, "reviewCount")
+                    .or_else(|| string_of(&rating, "ratingCount"))
+                    .and_then(|s| s.trim().parse().ok()),
+            )
+        })
+        .unwrap_or_default();
+
+    Some(DisplayedRecipe {
+        name: string_of(item, "name"),
+        thumbnail: thumbnail(item),
+        total_time: total_time_minutes.map(prettify_minutes),
+        total_time_minutes,
+        rating,
+        num_reviews,
+        num_ingredients,
+    })
+}
+
+/// Simple keyword based classifier that decides whether the user is looking for a recipe.
+pub fn is_recipe_query(query: &str) -> bool {
+    let query = query.to_lowercase();
+    let terms: Vec<_> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .collect();
+
+    if terms.iter().any(|term| RECIPE_TERMS.contains(term)) {
+        return true;
+    }
+
+    terms
+        .windows(3)
+        .any(|w| w[0] == "how" && w[1] == "to" && COOKING_VERBS.contains(&w[2]))
+}
+
+/// Group the recipe results for recipe queries so they can be shown
+/// together with their thumbnails.
+pub fn recipe_group(query: &str, webpages: &[DisplayedWebpage]) -> Option<Vec<GroupedRecipe>> {
+    if !is_recipe_query(query) {
+        return None;
+    }
+
+    let group: Vec<_> = webpages
+        .iter()
+        .filter_map(|webpage| match &webpage.rich_snippet {
+            Some(RichSnippet::Recipe { recipe }) if recipe.thumbnail.is_some() => {
+                Some(GroupedRecipe {
+                    title: webpage.title.clone(),
+                    url: webpage.url.clone(),
+                    site: webpage.site.clone(),
+                    recipe: recipe.clone(),
+                })
+            }
+            _ => None,
+        })
+        .take(MAX_GROUP_SIZE)
+        .collect();
+
+    if group.len() < MIN_GROUP_SIZE {
+        return None;
+    }
+
+    Some(group)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::webpage::Html;
+
+    use super::*;
+
+    #[test]
+    fn duration() {
+        assert_eq!(parse_duration("PT1H30M"), Some(90));
+        assert_eq!(parse_duration("PT20M"), Some(20));
+        assert_eq!(parse_duration("P0DT0H45M"), Some(45));
+        assert_eq!(parse_duration("PT90S"), Some(2));
+        assert_eq!(parse_duration("P1D"), Some(24 * 60));
+        assert_eq!(parse_duration("20 minutes"), None);
+        assert_eq!(parse_duration("PT20"), None);
+    }
+
+    #[test]
+    fn pretty_time() {
+        assert_eq!(prettify_minutes(20), "20 min");
+        assert_eq!(prettify_minutes(60), "1 h");
+        assert_eq!(prettify_minutes(75), "1 h 15 min");
+    }
+
+    #[test]
+    fn recipe_schema() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head>
+                    <script type="application/ld+json">
+                    {
+                        "@context": "https://schema.org",
+                        "@type": "Recipe",
+                        "name": "Mom's World Famous Banana Bread",
+                        "image": ["https://www.example.com/banana-bread.jpg"],
+                        "prepTime": "PT15M",
+                        "cookTime": "PT1H",
+                        "recipeIngredient": [
+                            "3 or 4 ripe bananas, smashed",
+                            "1 egg",
+                            "3/4 cup of sugar"
+                        ],
+                        "aggregateRating": {
+                            "@type": "AggregateRating",
+                            "ratingValue": 4.7,
+                            "ratingCount": 1203
+                        }
+                    }
+                    </script>
+                </head>
+                <body></body>
+            </html>
+            "#,
+            "https://www.example.com/banana-bread",
+        )
+        .unwrap();
+
+        let recipe = recipe(&html.schema_org()).unwrap();
+
+        assert_eq!(
+            recipe.name,
+            Some("Mom's World Famous Banana Bread".to_string())
+        );
+        assert_eq!(
+            recipe.thumbnail,
+            Some("https://www.example.com/banana-bread.jpg".to_string())
+        );
+        assert_eq!(recipe.total_time_minutes, Some(75));
+        assert_eq!(recipe.total_time, Some("1 h 15 min".to_string()));
+        assert_eq!(recipe.num_ingredients, 3);
+        assert_eq!(recipe.rating, Some(4.7));
+        assert_eq!(recipe.num_reviews, Some(1203));
+    }
+
+    #[test]
+    fn recipe_intent() {
+        assert!(is_recipe_query("banana bread recipe"));
+        assert!(is_recipe_query("Recipes with chicken"));
+        assert!(is_recipe_query("how to bake banana bread"));
+        assert!(!is_recipe_query("banana bread"));
+        assert!(!is_recipe_query("how to fix a flat tire"));
+        assert!(!is_recipe_query("recipeasy"));
+    }
+}
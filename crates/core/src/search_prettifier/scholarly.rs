@@ -0,0 +1,95 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use utoipa::ToSchema;
+
+use crate::webpage::schema_org::{Item, Property};
+
+/// Don't show more than this number of authors in the snippet.
+const MAX_AUTHORS: usize = 5;
+
+#[derive(
+    Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Clone, ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct PaperMetadata {
+    pub authors: Vec<String>,
+    pub num_authors: usize,
+    pub year: Option<String>,
+    pub venue: Option<String>,
+    pub doi: Option<String>,
+}
+
+fn name_of(property: Property) -> Option<String> {
+    match property {
+        Property::String(s) => Some(s),
+        Property::Item(item) => item
+            .properties
+            .get("name")
+            .and_then(|name| name.clone().one())
+            .and_then(|name| name.try_into_string()),
+    }
+}
+
+fn string_of(item: &Item, key: &str) -> Option<String> {
+    item.properties
+        .get(key)
+        .and_then(|p| p.clone().one())
+        .and_then(|p| p.try_into_string())
+}
+
+pub fn paper_metadata(schemas: &[Item]) -> Option<PaperMetadata> {
+    let item = schemas
+        .iter()
+        .find(|item| item.types_contains("ScholarlyArticle"))?;
+
+    let authors: Vec<_> = item
+        .properties
+        .get("author")
+        .cloned()
+        .map(|authors| authors.many())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(name_of)
+        .collect();
+
+    let year = string_of(item, "datePublished").map(|date| date.chars().take(4).collect());
+
+    let venue = item
+        .properties
+        .get("isPartOf")
+        .and_then(|p| p.clone().one())
+        .and_then(name_of);
+
+    let doi = string_of(item, "identifier")
+        .filter(|id| id.starts_with("10."))
+        .or_else(|| {
+            string_of(item, "sameAs")
+                .and_then(|url| url.strip_prefix("https://doi.org/").map(String::from))
+        });
+
+    if authors.is_empty() && year.is_none() && venue.is_none() && doi.is_none() {
+        return None;
+    }
+
+    Some(PaperMetadata {
+        num_authors: authors.len(),
+        authors: authors.into_iter().take(MAX_AUTHORS).collect(),
+        year,
+        venue,
+        doi,
+    })
+}
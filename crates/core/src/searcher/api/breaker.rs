@@ -0,0 +1,214 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Deadlines and circuit breakers for the optional stages of a search.
+//! A stage that fails or misses its deadline is skipped and the search continues
+//! without it. After `failure_threshold` consecutive failures the breaker opens and
+//! the stage is skipped without being called until the cooldown has passed. Calls
+//! after the cooldown are let through again: a success closes the breaker and
+//! a failure opens it for another cooldown.
+
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::config::{StageLimitConfig, StageLimitsConfig};
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: usize,
+    open_until: Option<Instant>,
+}
+
+pub struct CircuitBreaker {
+    stage: &'static str,
+    timeout: Duration,
+    failure_threshold: usize,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(stage: &'static str, config: &StageLimitConfig) -> Self {
+        Self {
+            stage,
+            timeout: Duration::from_millis(config.timeout_ms),
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: Duration::from_millis(config.cooldown_ms),
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.open_until.is_some() {
+            tracing::info!("circuit breaker for {} closed", self.stage);
+        }
+
+        *state = BreakerState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.failure_threshold {
+            if state.open_until.is_none() {
+                tracing::warn!(
+                    "circuit breaker for {} opened after {} consecutive failures",
+                    self.stage,
+                    state.consecutive_failures
+                );
+            }
+
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// Run the stage within its deadline. Returns `None` if the breaker is open
+    /// or the stage did not finish in time.
+    pub async fn call<T, F>(&self, fut: F) -> Option<T>
+    where
+        F: Future<Output = T>,
+    {
+        self.try_call(async { Ok::<_, Infallible>(fut.await) })
+            .await
+    }
+
+    /// Like [`CircuitBreaker::call`], but an error from the stage also counts as a failure.
+    pub async fn try_call<T, E, F>(&self, fut: F) -> Option<T>
+    where
+        E: Display,
+        F: Future<Output = std::result::Result<T, E>>,
+    {
+        if self.is_open() {
+            return None;
+        }
+
+        match tokio::time::timeout(self.timeout, fut).await {
+            Ok(Ok(res)) => {
+                self.record_success();
+                Some(res)
+            }
+            Ok(Err(err)) => {
+                tracing::warn!("{} failed: {}", self.stage, err);
+                self.record_failure();
+                None
+            }
+            Err(_) => {
+                tracing::warn!("{} timed out after {:?}", self.stage, self.timeout);
+                self.record_failure();
+                None
+            }
+        }
+    }
+}
+
+/// Breakers for the stages that have a limit in the config.
+/// Stages without a limit are always waited for.
+#[derive(Default)]
+pub struct StageBreakers {
+    pub widgets: Option<CircuitBreaker>,
+    pub reranker: Option<CircuitBreaker>,
+    pub webgraph: Option<CircuitBreaker>,
+}
+
+impl From<&StageLimitsConfig> for StageBreakers {
+    fn from(config: &StageLimitsConfig) -> Self {
+        Self {
+            widgets: config
+                .widgets
+                .as_ref()
+                .map(|c| CircuitBreaker::new("widgets", c)),
+            reranker: config
+                .reranker
+                .as_ref()
+                .map(|c| CircuitBreaker::new("reranker", c)),
+            webgraph: config
+                .webgraph
+                .as_ref()
+                .map(|c| CircuitBreaker::new("webgraph", c)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(
+            "test",
+            &StageLimitConfig {
+                timeout_ms: 10,
+                failure_threshold: 2,
+                cooldown_ms: 50,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn opens_after_consecutive_timeouts() {
+        let breaker = breaker();
+
+        assert_eq!(breaker.call(async { 1 }).await, Some(1));
+
+        for _ in 0..2 {
+            let res = breaker
+                .call(tokio::time::sleep(Duration::from_millis(100)))
+                .await;
+            assert!(res.is_none());
+        }
+
+        assert!(breaker.is_open());
+        assert_eq!(breaker.call(async { 1 }).await, None);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.call(async { 1 }).await, Some(1));
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn errors_are_failures() {
+        let breaker = breaker();
+
+        assert_eq!(breaker.try_call(async { Err::<(), _>("down") }).await, None);
+        assert!(!breaker.is_open());
+
+        // a success resets the count
+        assert_eq!(breaker.try_call(async { Ok::<_, &str>(1) }).await, Some(1));
+        assert_eq!(breaker.try_call(async { Err::<(), _>("down") }).await, None);
+        assert!(!breaker.is_open());
+
+        assert_eq!(breaker.try_call(async { Err::<(), _>("down") }).await, None);
+        assert!(breaker.is_open());
+    }
+}
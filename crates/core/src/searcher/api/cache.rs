@@ -0,0 +1,197 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Cache of search results. The query is normalized before it is used as a key,
+//! so queries that only differ in case, whitespace or stopwords share an entry.
+
+use std::{sync::Mutex, time::Duration};
+
+use bloom::fast_stable_hash_128;
+use itertools::Itertools;
+use whatlang::Lang;
+
+use crate::{
+    config::ResultCacheConfig,
+    metrics::Counter,
+    query::parser::{self, SimpleOrPhrase, Term},
+    searcher::{SearchQuery, SearchResult},
+    stopwords,
+    ttl_cache::TTLCache,
+};
+
+fn is_stopword(term: &Term, lang: &Lang) -> bool {
+    match term {
+        Term::SimpleOrPhrase(SimpleOrPhrase::Simple(simple)) => {
+            stopwords::get(lang).is_some_and(|stopwords| stopwords.contains(simple.as_str()))
+        }
+        _ => false,
+    }
+}
+
+/// Lowercase the query, collapse whitespace and remove the stopwords
+/// outside of phrases. A query that only consists of stopwords keeps them.
+pub fn normalize_query(query: &str) -> String {
+    let query = query.to_lowercase();
+
+    let terms = match parser::parse(&query) {
+        Ok(terms) => terms,
+        Err(_) => return query.split_whitespace().join(" "),
+    };
+
+    // the language of short queries can rarely be detected reliably
+    let lang = whatlang::detect(&query)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang())
+        .unwrap_or(Lang::Eng);
+
+    if terms.iter().all(|term| is_stopword(term, &lang)) {
+        return terms.iter().join(" ");
+    }
+
+    terms
+        .iter()
+        .filter(|term| !is_stopword(term, &lang))
+        .join(" ")
+}
+
+pub struct ResultCache {
+    cache: Mutex<TTLCache<u128, Vec<u8>>>,
+    hits: Counter,
+    misses: Counter,
+}
+
+impl ResultCache {
+    pub fn new(config: &ResultCacheConfig) -> Self {
+        Self {
+            cache: Mutex::new(TTLCache::with_ttl_and_max_size(
+                Duration::from_secs(config.ttl_secs),
+                Some(config.max_entries.max(1)),
+            )),
+            hits: Counter::default(),
+            misses: Counter::default(),
+        }
+    }
+
+    pub fn with_counters(mut self, hits: Counter, misses: Counter) -> Self {
+        self.hits = hits;
+        self.misses = misses;
+        self
+    }
+
+    fn key(query: &SearchQuery) -> u128 {
+        let query = SearchQuery {
+            query: normalize_query(&query.query),
+            ..query.clone()
+        };
+
+        let bytes = bincode::encode_to_vec(&query, bincode::config::standard()).unwrap();
+
+        fast_stable_hash_128(&bytes)
+    }
+
+    pub fn get(&self, query: &SearchQuery) -> Option<SearchResult> {
+        let key = Self::key(query);
+
+        let res = self.cache.lock().unwrap().get(&key).and_then(|bytes| {
+            bincode::decode_from_slice(bytes, bincode::config::standard())
+                .ok()
+                .map(|(res, _)| res)
+        });
+
+        match res {
+            Some(_) => self.hits.inc(),
+            None => self.misses.inc(),
+        }
+
+        res
+    }
+
+    pub fn insert(&self, query: &SearchQuery, result: &SearchResult) {
+        let key = Self::key(query);
+
+        match bincode::encode_to_vec(result, bincode::config::standard()) {
+            Ok(bytes) => self.cache.lock().unwrap().insert(key, bytes),
+            Err(err) => tracing::warn!("failed to cache search result: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{collector::approx_count::Count, searcher::WebsitesResult};
+
+    #[test]
+    fn normalization() {
+        assert_eq!(
+            normalize_query("The  Rust   Programming Language"),
+            "rust programming language"
+        );
+        assert_eq!(
+            normalize_query("rust programming language"),
+            "rust programming language"
+        );
+        assert_eq!(normalize_query("the who"), "the who");
+        assert_eq!(
+            normalize_query("\"The Rust Programming Language\" site:Rust-Lang.org"),
+            "\"the rust programming language\" site:rust-lang.org"
+        );
+    }
+
+    #[test]
+    fn similar_queries_share_entries() {
+        let cache = ResultCache::new(&ResultCacheConfig {
+            ttl_secs: 60,
+            max_entries: 10,
+        });
+
+        let query = SearchQuery {
+            query: "The Rust Programming Language".to_string(),
+            ..Default::default()
+        };
+
+        assert!(cache.get(&query).is_none());
+
+        cache.insert(
+            &query,
+            &SearchResult::Websites(WebsitesResult {
+                webpages: vec![],
+                recipes: None,
+                num_hits: Count::Exact(42),
+                search_duration_ms: 1,
+                has_more_results: false,
+                rewrites: vec![],
+                result_set: None,
+            }),
+        );
+
+        let similar = SearchQuery {
+            query: "rust  programming language".to_string(),
+            ..Default::default()
+        };
+
+        match cache.get(&similar) {
+            Some(SearchResult::Websites(res)) => assert_eq!(res.num_hits, Count::Exact(42)),
+            _ => panic!("expected cached result"),
+        }
+
+        let next_page = SearchQuery { page: 1, ..similar };
+        assert!(cache.get(&next_page).is_none());
+
+        assert_eq!(cache.hits.get(), 1);
+        assert_eq!(cache.misses.get(), 2);
+    }
+}
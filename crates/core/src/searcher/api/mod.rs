@@ -14,12 +14,15 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod breaker;
+mod cache;
+mod result_set;
 mod sidebar;
 mod widget;
 
 use std::future::Future;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use itertools::{intersperse, Itertools};
 use url::Url;
@@ -28,20 +31,32 @@ use ahash::AHashMap as HashMap;
 
 use crate::bangs::{Bang, BangHit};
 use crate::collector::{self, approx_count, Doc};
-use crate::config::{ApiConfig, ApiSpellCheck, ApiThresholds, CollectorConfig, WidgetsConfig};
+use crate::config::{
+    ApiConfig, ApiSpellCheck, ApiThreatList, ApiThresholds, CollectorConfig, ResultCacheConfig,
+    ResultSetConfig, SlowQueryLogConfig, StageLimitsConfig, ThreatPolicy, WidgetsConfig,
+};
 use crate::enum_map::EnumMap;
 use crate::image_store::Image;
-use crate::inverted_index::RetrievedWebpage;
+use crate::inverted_index::{RetrievedWebpage, StoredSignals};
+use crate::localization::Language;
+use crate::metrics::Counter;
 use crate::models::dual_encoder::DualEncoder;
+use crate::models::registry::ModelHandle;
 use crate::ranking::models::cross_encoder::CrossEncoderModel;
 use crate::ranking::pipeline::{PrecisionRankingWebpage, RankableWebpage, RecallRankingWebpage};
 use crate::ranking::{
-    bitvec_similarity, inbound_similarity, SignalCoefficient, SignalEnum, SignalScore,
+    bitvec_similarity, inbound_similarity, SignalCoefficient, SignalEnum, SignalEnumDiscriminants,
+    SignalScore,
+};
+use crate::search_prettifier::{
+    recipe_group, DisplayedSidebar, DisplayedWebpage, HighlightedSpellCorrection, ProductFilter,
+    ProductPage,
 };
-use crate::search_prettifier::{DisplayedSidebar, DisplayedWebpage, HighlightedSpellCorrection};
+use crate::threat_list::ThreatList;
 use crate::web_spell::SpellChecker;
 use crate::webgraph::remote::RemoteWebgraph;
 use crate::webgraph::EdgeLimit;
+use crate::webpage::safety_classifier;
 use crate::widgets::{Widget, Widgets};
 use crate::{
     bangs::Bangs,
@@ -50,9 +65,14 @@ use crate::{
 };
 use crate::{query, webgraph, Result};
 
+use self::breaker::StageBreakers;
+use self::cache::ResultCache;
+use self::result_set::ResultSets;
 use self::sidebar::SidebarManager;
 use self::widget::WidgetManager;
 
+use super::cost::{CpuTimer, ExplainedResult, QueryCost, SlowQuery, SlowQueryLog};
+use super::rewrite::{self, QueryRewrite, QueryRewriteKind};
 use super::{distributed, live, SearchQuery, SearchResult, WebsitesResult};
 
 #[derive(Clone)]
@@ -92,6 +112,10 @@ impl RankableWebpage for ScoredWebpagePointer {
             ScoredWebpagePointer::Live(p) => p.website.signals(),
         }
     }
+
+    fn host_cluster(&self) -> Option<webgraph::NodeID> {
+        self.as_ranking().host_cluster()
+    }
 }
 
 impl collector::Doc for ScoredWebpagePointer {
@@ -104,45 +128,97 @@ impl collector::Doc for ScoredWebpagePointer {
     }
 }
 
+fn ranking_signals(
+    pointer: &ScoredWebpagePointer,
+    coeffs: &SignalCoefficient,
+) -> std::collections::HashMap<SignalEnumDiscriminants, SignalScore> {
+    let mut signals = std::collections::HashMap::new();
+
+    for signal in SignalEnum::all() {
+        if let Some(signal_value) = pointer.as_ranking().signals().get(signal) {
+            signals.insert(
+                signal.into(),
+                SignalScore {
+                    value: *signal_value,
+                    coefficient: coeffs.get(&signal),
+                },
+            );
+        }
+    }
+
+    signals
+}
+
 pub fn add_ranking_signals(
     websites: &mut [DisplayedWebpage],
     pointers: &[ScoredWebpagePointer],
     coeffs: &SignalCoefficient,
 ) {
     for (website, pointer) in websites.iter_mut().zip(pointers.iter()) {
-        let mut signals = std::collections::HashMap::new();
-
-        for signal in SignalEnum::all() {
-            if let Some(signal_value) = pointer.as_ranking().signals().get(signal) {
-                signals.insert(
-                    signal.into(),
-                    SignalScore {
-                        value: *signal_value,
-                        coefficient: coeffs.get(&signal),
-                    },
-                );
-            }
-        }
-
-        website.ranking_signals = Some(signals);
+        website.ranking_signals = Some(ranking_signals(pointer, coeffs));
     }
 }
 
+/// Rerank the results and measure the CPU time spent, which might be on another
+/// thread than the one handling the search.
+fn rerank(
+    pipeline: RankingPipeline<PrecisionRankingWebpage>,
+    webpages: Vec<PrecisionRankingWebpage>,
+) -> (Vec<PrecisionRankingWebpage>, Duration) {
+    let timer = CpuTimer::start();
+    let webpages = pipeline.apply(webpages);
+
+    (webpages, timer.elapsed())
+}
+
+/// The ranking signals of each result for the slow query log.
+fn explain(
+    websites: &[DisplayedWebpage],
+    pointers: &[ScoredWebpagePointer],
+    coeffs: &SignalCoefficient,
+) -> Vec<ExplainedResult> {
+    websites
+        .iter()
+        .zip(pointers.iter())
+        .map(|(website, pointer)| ExplainedResult {
+            url: website.url.clone(),
+            score: pointer.score(),
+            signals: ranking_signals(pointer, coeffs),
+        })
+        .collect()
+}
+
 #[derive(Default)]
 pub struct Config {
     pub thresholds: ApiThresholds,
     pub widgets: WidgetsConfig,
     pub collector: CollectorConfig,
     pub spell_check: Option<ApiSpellCheck>,
+    pub threat_list: Option<ApiThreatList>,
+    pub slow_query_log: Option<SlowQueryLogConfig>,
+    pub stage_limits: StageLimitsConfig,
+    pub result_cache: Option<ResultCacheConfig>,
+    pub result_sets: Option<ResultSetConfig>,
 }
 
 impl From<ApiConfig> for Config {
     fn from(conf: ApiConfig) -> Self {
+        let mut widgets = conf.widgets;
+
+        if conf.disable_outbound_requests {
+            widgets.calculator_fetch_currencies_exchange = false;
+        }
+
         Self {
             thresholds: conf.thresholds,
-            widgets: conf.widgets,
+            widgets,
             collector: conf.collector,
             spell_check: conf.spell_check,
+            threat_list: conf.threat_list,
+            slow_query_log: conf.slow_query_log,
+            stage_limits: conf.stage_limits,
+            result_cache: conf.result_cache,
+            result_sets: conf.result_sets,
         }
     }
 }
@@ -206,17 +282,42 @@ where
     }
 }
 
+/// A website search that is ready to be sent to the searchers.
+struct PreparedSearch {
+    start: Instant,
+    /// The query after the rewrites.
+    query: SearchQuery,
+    /// The query that is sent to the searchers, which fetches the number of
+    /// results needed by the recall stage.
+    search_query: SearchQuery,
+    rewrites: Vec<QueryRewrite>,
+    requested_page: usize,
+    product_filter: Option<ProductFilter>,
+    product_page: Option<ProductPage>,
+    recall_pipeline: RankingPipeline<ScoredWebpagePointer>,
+}
+
 pub struct ApiSearcher<S, L, G> {
     distributed_searcher: Arc<S>,
     sidebar_manager: SidebarManager<S>,
     live_searcher: Option<L>,
-    cross_encoder: Option<Arc<CrossEncoderModel>>,
-    lambda_model: Option<Arc<LambdaMART>>,
-    dual_encoder: Option<Arc<DualEncoder>>,
+    cross_encoder: ModelHandle<CrossEncoderModel>,
+    lambda_model: ModelHandle<LambdaMART>,
+    dual_encoder: ModelHandle<DualEncoder>,
+    /// Drops nsfw results with safe search, including results from indices
+    /// that were built without a safety classifier.
+    safety_classifier: ModelHandle<safety_classifier::Model>,
     bangs: Bangs,
     collector_config: CollectorConfig,
-    widget_manager: WidgetManager,
+    widget_manager: Arc<WidgetManager>,
     spell_checker: Option<SpellChecker>,
+    /// Search for the spelling correction of the query instead of the query.
+    auto_correct: bool,
+    threat_list: Option<(ThreatList, ThreatPolicy)>,
+    slow_query_log: Option<SlowQueryLog>,
+    breakers: StageBreakers,
+    result_cache: Option<ResultCache>,
+    result_sets: Option<ResultSets>,
     webgraph: Option<G>,
 }
 
@@ -235,21 +336,32 @@ where
         let sidebar_manager =
             SidebarManager::new(Arc::clone(&dist_searcher), config.thresholds.clone());
 
-        let widget_manager = WidgetManager::new(Widgets::new(config.widgets).unwrap());
+        let widget_manager = Arc::new(WidgetManager::new(Widgets::new(config.widgets).unwrap()));
 
         Self {
             distributed_searcher: dist_searcher,
             sidebar_manager,
             live_searcher: None,
-            cross_encoder: None,
-            lambda_model: None,
-            dual_encoder: None,
+            cross_encoder: ModelHandle::default(),
+            lambda_model: ModelHandle::default(),
+            dual_encoder: ModelHandle::default(),
+            safety_classifier: ModelHandle::default(),
             bangs,
             collector_config: config.collector,
             widget_manager,
+            auto_correct: config.spell_check.as_ref().is_some_and(|c| c.auto_correct),
             spell_checker: config
                 .spell_check
                 .map(|c| SpellChecker::open(c.path, c.correction_config).unwrap()),
+            threat_list: config
+                .threat_list
+                .map(|c| (ThreatList::open(c.path).unwrap(), c.policy)),
+            slow_query_log: config
+                .slow_query_log
+                .map(|c| SlowQueryLog::open(&c).unwrap()),
+            breakers: StageBreakers::from(&config.stage_limits),
+            result_cache: config.result_cache.as_ref().map(ResultCache::new),
+            result_sets: config.result_sets.as_ref().map(ResultSets::new),
             webgraph: None,
         }
     }
@@ -259,18 +371,34 @@ where
         self
     }
 
-    pub fn with_cross_encoder(mut self, cross_encoder: CrossEncoderModel) -> Self {
-        self.cross_encoder = Some(Arc::new(cross_encoder));
+    pub fn with_cross_encoder(mut self, cross_encoder: ModelHandle<CrossEncoderModel>) -> Self {
+        self.cross_encoder = cross_encoder;
+        self
+    }
+
+    pub fn with_dual_encoder(mut self, dual_encoder: ModelHandle<DualEncoder>) -> Self {
+        self.dual_encoder = dual_encoder;
         self
     }
 
-    pub fn with_dual_encoder(mut self, dual_encoder: DualEncoder) -> Self {
-        self.dual_encoder = Some(Arc::new(dual_encoder));
+    pub fn with_safety_classifier(
+        mut self,
+        safety_classifier: ModelHandle<safety_classifier::Model>,
+    ) -> Self {
+        self.safety_classifier = safety_classifier;
         self
     }
 
-    pub fn with_lambda_model(mut self, lambda_model: LambdaMART) -> Self {
-        self.lambda_model = Some(Arc::new(lambda_model));
+    pub fn with_lambda_model(mut self, lambda_model: ModelHandle<LambdaMART>) -> Self {
+        self.lambda_model = lambda_model;
+        self
+    }
+
+    /// Count the hits and misses of the result cache in the given counters.
+    pub fn with_result_cache_counters(mut self, hits: Counter, misses: Counter) -> Self {
+        self.result_cache = self
+            .result_cache
+            .map(|cache| cache.with_counters(hits, misses));
         self
     }
 
@@ -317,8 +445,21 @@ where
         Ok(self.bangs.get(&parsed_terms))
     }
 
-    pub async fn widget(&self, query: &str) -> Option<Widget> {
-        self.widget_manager.widget(query).await
+    pub async fn widget(&self, query: &str, language: Language) -> Option<Widget> {
+        match &self.breakers.widgets {
+            Some(breaker) => {
+                let widget_manager = Arc::clone(&self.widget_manager);
+                let query = query.to_string();
+
+                breaker
+                    .try_call(tokio::task::spawn_blocking(move || {
+                        widget_manager.widget(&query, language)
+                    }))
+                    .await
+                    .flatten()
+            }
+            None => self.widget_manager.widget(query, language),
+        }
     }
 
     pub async fn sidebar(&self, query: &str) -> Option<DisplayedSidebar> {
@@ -326,6 +467,11 @@ where
     }
 
     pub fn spell_check(&self, query: &str) -> Option<HighlightedSpellCorrection> {
+        self.spell_correction(query)
+            .map(HighlightedSpellCorrection::from)
+    }
+
+    fn spell_correction(&self, query: &str) -> Option<crate::web_spell::Correction> {
         let query = query.to_lowercase();
 
         let terms = query::parser::parse(&query).ok()?;
@@ -381,7 +527,46 @@ where
             }
         }
 
-        Some(HighlightedSpellCorrection::from(correction))
+        Some(correction)
+    }
+
+    /// The query that is searched and the rewrites that were applied to get it.
+    fn rewrite_query(&self, query: &SearchQuery) -> (SearchQuery, Vec<QueryRewrite>) {
+        let mut query = query.clone();
+        let mut rewrites = Vec::new();
+
+        if query.disable_rewrites {
+            return (query, rewrites);
+        }
+
+        let normalized = rewrite::normalize_operators(&query.query);
+        if let Some(rewrite) = QueryRewrite::new(
+            QueryRewriteKind::OperatorNormalization,
+            &query.query,
+            normalized,
+        ) {
+            query.query = rewrite.to.clone();
+            rewrites.push(rewrite);
+        }
+
+        if self.auto_correct {
+            let correction = self
+                .spell_correction(&query.query)
+                .filter(|correction| !correction.is_all_orig());
+
+            if let Some(rewrite) = correction.and_then(|correction| {
+                QueryRewrite::new(
+                    QueryRewriteKind::SpellCorrection,
+                    &query.query,
+                    String::from(correction),
+                )
+            }) {
+                query.query = rewrite.to.clone();
+                rewrites.push(rewrite);
+            }
+        }
+
+        (query, rewrites)
     }
 
     async fn retrieve_webpages(
@@ -432,6 +617,11 @@ where
         &self,
         query: &SearchQuery,
     ) -> Option<Vec<live::InitialSearchResultSplit>> {
+        // the live index only has recent pages, which are not part of a snapshot
+        if query.snapshot.is_some() {
+            return None;
+        }
+
         match &self.live_searcher {
             Some(searcher) => Some(searcher.search_initial(query).await),
             None => None,
@@ -450,10 +640,21 @@ where
     }
 
     async fn inbound_vecs(&self, ids: &[webgraph::NodeID]) -> Vec<bitvec_similarity::BitVec> {
-        match self.webgraph.as_ref() {
-            Some(webgraph) => bitvec_similarity::BitVec::batch_new_for(ids, webgraph).await,
-            None => vec![bitvec_similarity::BitVec::default(); ids.len()],
-        }
+        let lookup = async {
+            match self.webgraph.as_ref() {
+                Some(webgraph) => {
+                    Some(bitvec_similarity::BitVec::batch_new_for(ids, webgraph).await)
+                }
+                None => None,
+            }
+        };
+
+        let vecs = match &self.breakers.webgraph {
+            Some(breaker) => breaker.call(lookup).await.flatten(),
+            None => lookup.await,
+        };
+
+        vecs.unwrap_or_else(|| vec![bitvec_similarity::BitVec::default(); ids.len()])
     }
 
     async fn combine_results(
@@ -462,7 +663,7 @@ where
         initial_results: Vec<distributed::InitialSearchResultShard>,
         live_results: Vec<live::InitialSearchResultSplit>,
         pipeline: RankingPipeline<ScoredWebpagePointer>,
-    ) -> (Vec<ScoredWebpagePointer>, bool) {
+    ) -> (Vec<ScoredWebpagePointer>, bool, Duration) {
         let mut collector = BucketCollector::new(pipeline.collector_top_n(), collector_config);
 
         let initial_host_nodes = initial_results
@@ -491,6 +692,7 @@ where
             .map(|(v, n)| (n, v))
             .collect::<HashMap<_, _>>();
 
+        let timer = CpuTimer::start();
         let mut has_more = false;
         for result in initial_results {
             if result.local_result.has_more {
@@ -505,6 +707,7 @@ where
                 let pointer = distributed::ScoredWebpagePointer {
                     website: RecallRankingWebpage::new(website, inbound),
                     shard: result.shard,
+                    snapshot: result.snapshot,
                 };
 
                 let pointer = ScoredWebpagePointer::Normal(pointer);
@@ -542,10 +745,25 @@ where
 
         let res = pipeline.apply(top_websites);
 
-        (res, has_more)
+        (res, has_more, timer.elapsed())
     }
 
     async fn inbound_scorer(&self, query: &SearchQuery) -> inbound_similarity::Scorer {
+        let scorer = self.inbound_scorer_from_webgraph(query);
+
+        match &self.breakers.webgraph {
+            Some(breaker) => breaker
+                .call(scorer)
+                .await
+                .unwrap_or_else(inbound_similarity::Scorer::empty),
+            None => scorer.await,
+        }
+    }
+
+    async fn inbound_scorer_from_webgraph(
+        &self,
+        query: &SearchQuery,
+    ) -> inbound_similarity::Scorer {
         match self.webgraph.as_ref() {
             Some(webgraph) => {
                 let host_rankings = query.host_rankings();
@@ -567,13 +785,37 @@ where
         }
     }
 
-    async fn search_websites(&self, query: &SearchQuery) -> Result<WebsitesResult> {
+    /// Rewrites the query and sets up the recall stage, so the search is ready to be
+    /// sent to the searchers.
+    async fn prepare_search(&self, query: &SearchQuery) -> Result<PreparedSearch> {
         let start = Instant::now();
 
         if query.is_empty() {
             return Err(distributed::Error::EmptyQuery.into());
         }
 
+        let (query, rewrites) = self.rewrite_query(query);
+        let requested_page = query.page;
+
+        // the product filter is applied after the results are retrieved, so the results
+        // from the first page and on are over-fetched to fill the requested page
+        let product_filter = ProductFilter::for_query(query.product_filter.as_ref(), &query.query);
+        let product_page = match &product_filter {
+            Some(_) => Some(
+                ProductPage::new(query.page, query.num_results)
+                    .ok_or(distributed::Error::PageOutOfRange)?,
+            ),
+            None => None,
+        };
+        let query = match product_page {
+            Some(page) => SearchQuery {
+                page: 0,
+                num_results: page.num_fetched(),
+                ..query
+            },
+            None => query,
+        };
+
         let mut search_query = query.clone();
         let inbound_scorer = self.inbound_scorer(&search_query).await;
 
@@ -585,27 +827,133 @@ where
             RankingPipeline::<ScoredWebpagePointer>::recall_stage(
                 &mut search_query,
                 inbound_scorer,
-                self.lambda_model.clone(),
-                self.dual_encoder.clone(),
+                self.lambda_model.get(),
+                self.dual_encoder.get(),
                 self.collector_config.clone(),
                 top_n,
             );
 
+        Ok(PreparedSearch {
+            start,
+            query,
+            search_query,
+            rewrites,
+            requested_page,
+            product_filter,
+            product_page,
+            recall_pipeline,
+        })
+    }
+
+    async fn search_websites(&self, query: &SearchQuery) -> Result<WebsitesResult> {
+        let search = self.prepare_search(query).await?;
+
         let (initial_results, live_results) = tokio::join!(
-            self.distributed_searcher.search_initial(&search_query),
-            self.search_initial_from_live(&search_query),
+            self.distributed_searcher
+                .search_initial(&search.search_query),
+            self.search_initial_from_live(&search.search_query),
         );
 
+        self.finish_search(search, initial_results, live_results.unwrap_or_default())
+            .await
+    }
+
+    /// Searches the queries with a single request to each of the searchers. The
+    /// results are in the order of the queries.
+    async fn search_websites_batch(&self, queries: &[&SearchQuery]) -> Vec<Result<WebsitesResult>> {
+        let prepared =
+            futures::future::join_all(queries.iter().map(|query| self.prepare_search(query))).await;
+
+        let mut results = Vec::with_capacity(prepared.len());
+        let mut searches = Vec::new();
+
+        for search in prepared {
+            match search {
+                Ok(search) => {
+                    searches.push(search);
+                    results.push(None);
+                }
+                Err(err) => results.push(Some(Err(err))),
+            }
+        }
+
+        let search_queries: Vec<_> = searches
+            .iter()
+            .map(|search| search.search_query.clone())
+            .collect();
+
+        let (initial_results, live_results) = tokio::join!(
+            self.distributed_searcher
+                .search_initial_batch(&search_queries),
+            futures::future::join_all(
+                search_queries
+                    .iter()
+                    .map(|query| self.search_initial_from_live(query))
+            ),
+        );
+
+        let mut finished = futures::future::join_all(
+            searches
+                .into_iter()
+                .zip_eq(initial_results)
+                .zip_eq(live_results)
+                .map(|((search, initial_results), live_results)| {
+                    self.finish_search(search, initial_results, live_results.unwrap_or_default())
+                }),
+        )
+        .await
+        .into_iter();
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| finished.next().unwrap()))
+            .collect()
+    }
+
+    /// Ranks and retrieves the results of a prepared search from the initial results
+    /// of the searchers.
+    async fn finish_search(
+        &self,
+        search: PreparedSearch,
+        initial_results: Vec<distributed::InitialSearchResultShard>,
+        live_results: Vec<live::InitialSearchResultSplit>,
+    ) -> Result<WebsitesResult> {
+        let PreparedSearch {
+            start,
+            query,
+            search_query,
+            rewrites,
+            requested_page,
+            product_filter,
+            product_page,
+            recall_pipeline,
+        } = search;
+        let query = &query;
+
         let num_docs = initial_results
             .iter()
             .map(|result| result.local_result.num_websites)
             .fold(approx_count::Count::Exact(0), |acc, count| acc + count);
 
-        let (top_websites, has_more_results) = self
+        let mut cost = QueryCost {
+            shards: initial_results.len() + live_results.len(),
+            ..Default::default()
+        };
+
+        for local_result in initial_results
+            .iter()
+            .map(|result| &result.local_result)
+            .chain(live_results.iter().map(|result| &result.local_result))
+        {
+            cost.docs_scored += local_result.num_websites.as_u64();
+            cost.cpu_ms += local_result.cpu_ms;
+        }
+
+        let (top_websites, mut has_more_results, combine_cpu) = self
             .combine_results(
                 self.collector_config.clone(),
                 initial_results,
-                live_results.unwrap_or_default(),
+                live_results,
                 recall_pipeline,
             )
             .await;
@@ -619,27 +967,60 @@ where
             ..query.clone()
         };
 
+        let cross_encoder = self.cross_encoder.get();
+
+        // the cross encoder is run on both snippets and titles
+        cost.rerank_batches = cross_encoder
+            .as_ref()
+            .map(|model| 2 * model.num_batches(retrieved_webpages.len()))
+            .unwrap_or_default();
+
         let reranking_pipeline: RankingPipeline<PrecisionRankingWebpage> =
             RankingPipeline::<PrecisionRankingWebpage>::reranker(
                 &mut search_query,
-                self.cross_encoder.clone(),
-                self.lambda_model.clone(),
+                cross_encoder,
+                self.lambda_model.get(),
                 self.collector_config.clone(),
                 query.num_results,
             )?;
 
-        let retrieved_webpages = reranking_pipeline.apply(retrieved_webpages);
+        let (retrieved_webpages, rerank_cpu) = match &self.breakers.reranker {
+            Some(breaker) => {
+                // the results keep the order from the recall stage if the reranker is skipped
+                let recall_order = retrieved_webpages.clone();
+
+                breaker
+                    .try_call(tokio::task::spawn_blocking(move || {
+                        rerank(reranking_pipeline, retrieved_webpages)
+                    }))
+                    .await
+                    .unwrap_or((recall_order, Duration::ZERO))
+            }
+            None => rerank(reranking_pipeline, retrieved_webpages),
+        };
 
+        let timer = CpuTimer::start();
         let mut retrieved_webpages: Vec<_> = retrieved_webpages
             .into_iter()
             .map(|webpage| webpage.into_retrieved_webpage())
             .map(|webpage| DisplayedWebpage::new(webpage, &search_query))
             .collect();
+        cost.cpu_ms += (combine_cpu + rerank_cpu + timer.elapsed()).as_millis() as u64;
 
         if retrieved_webpages.len() != top_websites.len() {
             return Err(distributed::Error::SearchFailed.into());
         }
 
+        // the explain payload is only needed if the query turns out to be slow,
+        // but it must be created before the results are filtered
+        let explained = self.slow_query_log.as_ref().map(|_| {
+            explain(
+                &retrieved_webpages,
+                &top_websites,
+                &search_query.signal_coefficients(),
+            )
+        });
+
         if query.return_ranking_signals {
             add_ranking_signals(
                 &mut retrieved_webpages,
@@ -652,28 +1033,171 @@ where
             website.score = Some(pointer.score());
         }
 
+        if query.safe_search {
+            if let Some(classifier) = self.safety_classifier.get() {
+                retrieved_webpages.retain(|webpage| {
+                    let text =
+                        webpage.title.clone() + " " + &webpage.snippet.text.unhighlighted_string();
+
+                    classifier.predict_text(&text).label != safety_classifier::Label::NSFW
+                });
+            }
+        }
+
+        let mut num_hits = num_docs;
+
+        if let (Some(filter), Some(page)) = (&product_filter, product_page) {
+            num_hits = filter.retain(&mut retrieved_webpages, num_docs, !has_more_results);
+            has_more_results = page.has_next(retrieved_webpages.len(), has_more_results);
+            retrieved_webpages = page.select(retrieved_webpages);
+        }
+
+        if let Some((threat_list, policy)) = &self.threat_list {
+            threat_list.apply(&mut retrieved_webpages, *policy);
+        }
+
+        let recipes = recipe_group(&query.query, &retrieved_webpages);
+
         let search_duration_ms = start.elapsed().as_millis();
 
+        cost.wall_ms = search_duration_ms as u64;
+        tracing::debug!(?cost, "query cost");
+
+        if let Some(log) = self.slow_query_log.as_ref() {
+            if log.is_slow(&cost) {
+                let slow = SlowQuery::new(
+                    query.query.clone(),
+                    requested_page,
+                    cost,
+                    explained.unwrap_or_default(),
+                );
+
+                if let Err(err) = log.log(&slow) {
+                    tracing::warn!("failed to write slow query log: {}", err);
+                }
+            }
+        }
+
         Ok(WebsitesResult {
-            num_hits: num_docs,
+            num_hits,
             webpages: retrieved_webpages,
+            recipes,
             search_duration_ms,
             has_more_results,
+            rewrites,
+            result_set: None,
         })
     }
 
+    /// The result of the query if it can be answered without searching the index,
+    /// i.e. from the result cache or with a bang.
+    async fn search_shortcut(&self, query: &SearchQuery) -> Result<Option<SearchResult>> {
+        let start = Instant::now();
+
+        if let Some(date) = query.snapshot {
+            if !self.distributed_searcher.has_snapshot(date).await {
+                return Err(distributed::Error::UnknownSnapshot.into());
+            }
+        }
+
+        if let Some(mut cached) = self
+            .result_cache
+            .as_ref()
+            .and_then(|cache| cache.get(query))
+        {
+            // the cached duration is from the request that searched the index
+            if let SearchResult::Websites(websites) = &mut cached {
+                websites.search_duration_ms = start.elapsed().as_millis();
+            }
+
+            return Ok(Some(cached));
+        }
+
+        Ok(self
+            .check_bangs(query)
+            .await?
+            .map(|bang| SearchResult::Bang(Box::new(bang))))
+    }
+
+    fn cache_result(&self, query: &SearchQuery, websites: WebsitesResult) -> SearchResult {
+        let result = SearchResult::Websites(websites);
+
+        // bangs are not cached, as their redirect depends on the exact query
+        if let Some(cache) = &self.result_cache {
+            cache.insert(query, &result);
+        }
+
+        result
+    }
+
     pub async fn search(&self, query: &SearchQuery) -> Result<SearchResult> {
-        if let Some(bang) = self.check_bangs(query).await? {
-            return Ok(SearchResult::Bang(Box::new(bang)));
+        let result = match self.search_shortcut(query).await? {
+            Some(result) => result,
+            None => self.cache_result(query, self.search_websites(query).await?),
+        };
+
+        Ok(self.with_result_set(result))
+    }
+
+    /// Searches the queries of a batch. The queries that are not answered from the
+    /// result cache or with a bang are sent to each searcher in a single request.
+    pub async fn search_batch(&self, queries: &[SearchQuery]) -> Vec<Result<SearchResult>> {
+        let mut results: Vec<_> =
+            futures::future::join_all(queries.iter().map(|query| self.search_shortcut(query)))
+                .await
+                .into_iter()
+                .map(|result| result.transpose())
+                .collect();
+
+        let misses: Vec<_> = results
+            .iter()
+            .positions(|result| result.is_none())
+            .collect();
+
+        let searched = self
+            .search_websites_batch(&misses.iter().map(|i| &queries[*i]).collect::<Vec<_>>())
+            .await;
+
+        for (i, websites) in misses.into_iter().zip_eq(searched) {
+            results[i] = Some(websites.map(|websites| self.cache_result(&queries[i], websites)));
         }
 
-        Ok(SearchResult::Websites(self.search_websites(query).await?))
+        results
+            .into_iter()
+            .map(|result| result.unwrap().map(|result| self.with_result_set(result)))
+            .collect()
+    }
+
+    fn with_result_set(&self, mut result: SearchResult) -> SearchResult {
+        // every response gets its own token, so a cached response does not share the
+        // expiry of the set it was first returned with. The urls are matched against
+        // the index, so the set has the urls as they are indexed.
+        if let (Some(sets), SearchResult::Websites(websites)) = (&self.result_sets, &mut result) {
+            let urls = websites
+                .webpages
+                .iter()
+                .map(|webpage| webpage.indexed_url.clone())
+                .collect();
+            websites.result_set = Some(sets.insert(urls));
+        }
+
+        result
+    }
+
+    /// The urls of a result set that was returned with an earlier response, or `None`
+    /// if the token is unknown or has expired.
+    pub fn result_set(&self, token: &str) -> Option<Arc<Vec<String>>> {
+        self.result_sets.as_ref()?.get(token)
     }
 
     pub async fn get_webpage(&self, url: &str) -> Result<Option<RetrievedWebpage>> {
         self.distributed_searcher.get_webpage(url).await
     }
 
+    pub async fn get_stored_signals(&self, url: &str) -> Result<Option<StoredSignals>> {
+        self.distributed_searcher.get_stored_signals(url).await
+    }
+
     pub async fn get_entity_image(
         &self,
         image_id: &str,
@@ -0,0 +1,84 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The urls of recent responses, so a follow-up query can be restricted to the
+//! results of an earlier query. Each set is identified by a random token that is
+//! returned with the response, and is forgotten when its time to live has passed.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{config::ResultSetConfig, ttl_cache::TTLCache};
+
+pub struct ResultSets {
+    sets: Mutex<TTLCache<String, Arc<Vec<String>>>>,
+}
+
+impl ResultSets {
+    pub fn new(config: &ResultSetConfig) -> Self {
+        Self {
+            sets: Mutex::new(TTLCache::with_ttl_and_max_size(
+                Duration::from_secs(config.ttl_secs),
+                Some(config.max_entries.max(1)),
+            )),
+        }
+    }
+
+    /// Store the urls and return the token of the set.
+    pub fn insert(&self, urls: Vec<String>) -> String {
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        self.sets
+            .lock()
+            .unwrap()
+            .insert(token.clone(), Arc::new(urls));
+
+        token
+    }
+
+    /// The urls of the set, or `None` if the token is unknown or has expired.
+    pub fn get(&self, token: &str) -> Option<Arc<Vec<String>>> {
+        self.sets.lock().unwrap().get(&token.to_string()).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let sets = ResultSets::new(&ResultSetConfig {
+            ttl_secs: 60,
+            max_entries: 2,
+        });
+
+        let a = sets.insert(vec!["https://a.com/".to_string()]);
+        let b = sets.insert(vec!["https://b.com/".to_string()]);
+
+        assert_ne!(a, b);
+        assert_eq!(
+            sets.get(&a).unwrap().as_slice(),
+            ["https://a.com/".to_string()]
+        );
+        assert!(sets.get("unknown").is_none());
+
+        sets.insert(vec![]);
+        assert!(sets.get(&a).is_none());
+        assert!(sets.get(&b).is_some());
+    }
+}
@@ -72,8 +72,14 @@ where
             tracing::debug!(?score, ?self.thresholds.stackoverflow, "stackoverflow score");
             if website.score() > self.thresholds.stackoverflow {
                 let website = RecallRankingWebpage::new(website, Default::default());
-                let scored_websites =
-                    vec![(0, distributed::ScoredWebpagePointer { website, shard })];
+                let scored_websites = vec![(
+                    0,
+                    distributed::ScoredWebpagePointer {
+                        website,
+                        shard,
+                        snapshot: None,
+                    },
+                )];
                 let mut retrieved = self
                     .distributed_searcher
                     .retrieve_webpages(&scored_websites, &query.query)
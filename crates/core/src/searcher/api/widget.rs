@@ -16,6 +16,7 @@
 
 use itertools::Itertools;
 
+use crate::localization::Language;
 use crate::query;
 use crate::widgets::{Widget, Widgets};
 
@@ -28,7 +29,7 @@ impl WidgetManager {
         Self { widgets }
     }
 
-    pub async fn widget(&self, query: &str) -> Option<Widget> {
+    pub fn widget(&self, query: &str, language: Language) -> Option<Widget> {
         let parsed_terms = query::parser::parse(query).ok()?;
 
         self.widgets.widget(
@@ -46,6 +47,7 @@ impl WidgetManager {
                 })
                 .join(" ")
                 .as_str(),
+            language,
         )
     }
 }
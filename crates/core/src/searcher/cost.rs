@@ -0,0 +1,222 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Resources used by each search and a log of the slowest queries.
+//! CPU time is measured per thread around the synchronous parts of the search,
+//! both on the search servers and in the api, so time spent waiting on the
+//! network is not included.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    config::SlowQueryLogConfig,
+    ranking::{SignalEnumDiscriminants, SignalScore},
+    Result,
+};
+
+/// CPU time used by the current thread.
+fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+
+    // SAFETY: `ts` is a valid timespec that outlives the call.
+    let res = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+
+    if res != 0 {
+        return Duration::ZERO;
+    }
+
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Measures the CPU time of the current thread. The timer must be stopped
+/// on the same thread it was started on, so it should not be held across an `.await`.
+pub struct CpuTimer {
+    start: Duration,
+}
+
+impl CpuTimer {
+    pub fn start() -> Self {
+        Self {
+            start: thread_cpu_time(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        thread_cpu_time().saturating_sub(self.start)
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryCost {
+    /// Number of shards and live index splits that were searched.
+    pub shards: usize,
+    /// Number of documents that matched the query and were scored in the shards.
+    pub docs_scored: u64,
+    /// Number of batches sent to the cross encoder during reranking.
+    pub rerank_batches: usize,
+    /// CPU time across the shards and the api.
+    pub cpu_ms: u64,
+    pub wall_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainedResult {
+    pub url: String,
+    pub score: f64,
+    pub signals: HashMap<SignalEnumDiscriminants, SignalScore>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowQuery {
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+    pub query: String,
+    pub page: usize,
+    pub cost: QueryCost,
+    /// The ranking signals of the results.
+    pub explain: Vec<ExplainedResult>,
+}
+
+impl SlowQuery {
+    pub fn new(query: String, page: usize, cost: QueryCost, explain: Vec<ExplainedResult>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            timestamp,
+            query,
+            page,
+            cost,
+            explain,
+        }
+    }
+}
+
+/// Appends the queries that took longer than the threshold to a file
+/// with one json object per line.
+pub struct SlowQueryLog {
+    threshold: Duration,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl SlowQueryLog {
+    pub fn open(config: &SlowQueryLogConfig) -> Result<Self> {
+        if let Some(parent) = std::path::Path::new(&config.path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+
+        Ok(Self {
+            threshold: Duration::from_millis(config.threshold_ms),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub fn is_slow(&self, cost: &QueryCost) -> bool {
+        Duration::from_millis(cost.wall_ms) >= self.threshold
+    }
+
+    pub fn log(&self, query: &SlowQuery) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+
+        serde_json::to_writer(&mut *writer, query)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use super::*;
+
+    #[test]
+    fn cpu_timer() {
+        let timer = CpuTimer::start();
+
+        let mut x = 0_u64;
+        for i in 0..1_000_000 {
+            x = x.wrapping_add(std::hint::black_box(i));
+        }
+        std::hint::black_box(x);
+
+        assert!(timer.elapsed() > Duration::ZERO);
+    }
+
+    #[test]
+    fn slow_queries_are_logged() {
+        let path = crate::gen_temp_path().join("slow.jsonl");
+        let log = SlowQueryLog::open(&SlowQueryLogConfig {
+            path: path.to_str().unwrap().to_string(),
+            threshold_ms: 100,
+        })
+        .unwrap();
+
+        let fast = QueryCost {
+            wall_ms: 99,
+            ..Default::default()
+        };
+        let slow = QueryCost {
+            wall_ms: 100,
+            ..Default::default()
+        };
+
+        assert!(!log.is_slow(&fast));
+        assert!(log.is_slow(&slow));
+
+        let query = SlowQuery::new("test".to_string(), 0, slow, vec![]);
+        log.log(&query).unwrap();
+        log.log(&query).unwrap();
+
+        let lines: Vec<SlowQuery> = BufReader::new(File::open(&path).unwrap())
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(lines, vec![query.clone(), query]);
+    }
+}
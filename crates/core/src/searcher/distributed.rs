@@ -17,11 +17,11 @@
 use crate::{
     distributed::{
         cluster::Cluster,
-        member::{Service, ShardId},
+        member::{Service, ShardId, SnapshotDate},
         sonic::replication::{
             AllShardsSelector, RandomReplicaSelector, RemoteClient, ReplicatedClient,
-            ReusableClientManager, ReusableShardedClient, Shard, ShardIdentifier, ShardedClient,
-            SpecificShardSelector,
+            ReusableClientManager, ReusableShardedClient, Shard, ShardIdentifier, ShardSelector,
+            ShardedClient, SpecificShardSelector,
         },
     },
     entity_index::EntityMatch,
@@ -31,7 +31,7 @@ use crate::{
     },
     image_store::Image,
     index::Index,
-    inverted_index::{RetrievedWebpage, WebpagePointer},
+    inverted_index::{RetrievedWebpage, StoredSignals, WebpagePointer},
     ranking::pipeline::{PrecisionRankingWebpage, RecallRankingWebpage},
     Result,
 };
@@ -46,7 +46,10 @@ use thiserror::Error;
 use tokio::sync::Mutex;
 use url::Url;
 
-use super::{InitialWebsiteResult, LocalSearcher, SearchQuery};
+use super::{
+    host_routing::{HostRouter, RoutedShardSelector},
+    InitialWebsiteResult, LocalSearcher, SearchQuery,
+};
 
 const CLIENT_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 
@@ -60,14 +63,29 @@ pub enum Error {
 
     #[error("Webpage not found")]
     WebpageNotFound,
+
+    #[error("Index snapshot not found")]
+    UnknownSnapshot,
+
+    #[error("Page is beyond the results the product filter can see")]
+    PageOutOfRange,
 }
 
 pub trait SearchClient {
+    /// Whether there are searchers for the index snapshot from `date`.
+    fn has_snapshot(&self, date: SnapshotDate) -> impl Future<Output = bool> + Send;
+
     fn search_initial(
         &self,
         query: &SearchQuery,
     ) -> impl Future<Output = Vec<InitialSearchResultShard>> + Send;
 
+    /// The initial results of each of the queries, in the order of the queries.
+    fn search_initial_batch(
+        &self,
+        queries: &[SearchQuery],
+    ) -> impl Future<Output = Vec<Vec<InitialSearchResultShard>>> + Send;
+
     fn retrieve_webpages(
         &self,
         top_websites: &[(usize, ScoredWebpagePointer)],
@@ -81,6 +99,11 @@ pub trait SearchClient {
         url: &str,
     ) -> impl Future<Output = Result<Option<RetrievedWebpage>>> + Send;
 
+    fn get_stored_signals(
+        &self,
+        url: &str,
+    ) -> impl Future<Output = Result<Option<StoredSignals>>> + Send;
+
     fn get_homepage_descriptions(
         &self,
         urls: &[Url],
@@ -98,6 +121,8 @@ pub trait SearchClient {
 pub struct ScoredWebpagePointer {
     pub website: RecallRankingWebpage,
     pub shard: ShardId,
+    /// The index snapshot the shard is from, or `None` for the current index.
+    pub snapshot: Option<SnapshotDate>,
 }
 
 impl ShardIdentifier for ShardId {}
@@ -106,9 +131,14 @@ impl ShardIdentifier for ShardId {}
 pub struct InitialSearchResultShard {
     pub local_result: InitialWebsiteResult,
     pub shard: ShardId,
+    pub snapshot: Option<SnapshotDate>,
 }
 
-struct SearchClientManager;
+/// Connects to the searchers of the current index, or to the searchers of the
+/// index snapshot from `snapshot`.
+struct SearchClientManager {
+    snapshot: Option<SnapshotDate>,
+}
 
 impl ReusableClientManager for SearchClientManager {
     const CLIENT_REFRESH_INTERVAL: std::time::Duration = CLIENT_REFRESH_INTERVAL;
@@ -120,7 +150,17 @@ impl ReusableClientManager for SearchClientManager {
     async fn new_client(&self, cluster: &Cluster) -> ShardedClient<Self::Service, Self::ShardId> {
         let mut shards = HashMap::new();
         for member in cluster.members().await {
-            if let Service::Searcher { host, shard } = member.service {
+            let replica = match (member.service, self.snapshot) {
+                (Service::Searcher { host, shard }, None) => Some((shard, host)),
+                (Service::SnapshotSearcher { host, shard, date }, Some(snapshot))
+                    if date == snapshot =>
+                {
+                    Some((shard, host))
+                }
+                _ => None,
+            };
+
+            if let Some((shard, host)) = replica {
                 shards.entry(shard).or_insert_with(Vec::new).push(host);
             }
         }
@@ -164,20 +204,32 @@ impl ReusableClientManager for EntitySearchClientManager {
     }
 }
 
+type SnapshotClient = Arc<Mutex<ReusableShardedClient<SearchClientManager>>>;
+
 pub struct DistributedSearcher {
+    cluster: Arc<Cluster>,
     client: Mutex<ReusableShardedClient<SearchClientManager>>,
+    /// Clients for the index snapshots that have been searched. Only snapshots with
+    /// searchers in the cluster get a client, and the clients of snapshots that are
+    /// no longer served are removed when a new client is added.
+    snapshot_clients: std::sync::Mutex<HashMap<SnapshotDate, SnapshotClient>>,
     entiy_client: Mutex<ReusableShardedClient<EntitySearchClientManager>>,
+    router: Arc<HostRouter>,
 }
 
 impl DistributedSearcher {
     pub async fn new(cluster: Arc<Cluster>) -> Self {
         Self {
             client: Mutex::new(
-                ReusableShardedClient::new(cluster.clone(), SearchClientManager).await,
+                ReusableShardedClient::new(cluster.clone(), SearchClientManager { snapshot: None })
+                    .await,
             ),
+            snapshot_clients: std::sync::Mutex::new(HashMap::new()),
             entiy_client: Mutex::new(
-                ReusableShardedClient::new(cluster, EntitySearchClientManager).await,
+                ReusableShardedClient::new(cluster.clone(), EntitySearchClientManager).await,
             ),
+            router: Arc::new(HostRouter::new()),
+            cluster,
         }
     }
 
@@ -185,6 +237,146 @@ impl DistributedSearcher {
         self.client.lock().await.conn().await
     }
 
+    /// The dates of the index snapshots that have searchers in the cluster.
+    async fn snapshot_dates(&self) -> Vec<SnapshotDate> {
+        self.cluster
+            .members()
+            .await
+            .into_iter()
+            .filter_map(|member| match member.service {
+                Service::SnapshotSearcher { date, .. } => Some(date),
+                _ => None,
+            })
+            .unique()
+            .collect()
+    }
+
+    /// The client for the searchers of the snapshot, or of the current index if `None`.
+    /// Returns `None` if no searchers serve the snapshot.
+    async fn snapshot_conn(
+        &self,
+        snapshot: Option<SnapshotDate>,
+    ) -> Option<Arc<ShardedClient<SearchService, ShardId>>> {
+        let Some(date) = snapshot else {
+            return Some(self.conn().await);
+        };
+
+        let client = self.snapshot_clients.lock().unwrap().get(&date).cloned();
+
+        let client = match client {
+            Some(client) => client,
+            None => {
+                let dates = self.snapshot_dates().await;
+
+                if !dates.contains(&date) {
+                    return None;
+                }
+
+                let client = Arc::new(Mutex::new(
+                    ReusableShardedClient::new(
+                        Arc::clone(&self.cluster),
+                        SearchClientManager {
+                            snapshot: Some(date),
+                        },
+                    )
+                    .await,
+                ));
+
+                let mut clients = self.snapshot_clients.lock().unwrap();
+                clients.retain(|served, _| dates.contains(served));
+                Arc::clone(clients.entry(date).or_insert(client))
+            }
+        };
+
+        let conn = client.lock().await.conn().await;
+        Some(conn)
+    }
+
+    async fn search_shards<Sel>(
+        client: &ShardedClient<SearchService, ShardId>,
+        query: &SearchQuery,
+        selector: &Sel,
+    ) -> Vec<InitialSearchResultShard>
+    where
+        Sel: ShardSelector<SearchService, ShardId>,
+    {
+        let mut results = Vec::new();
+
+        if let Ok(res) = client
+            .send(
+                search_server::Search {
+                    query: query.clone(),
+                },
+                selector,
+                &RandomReplicaSelector,
+            )
+            .await
+        {
+            for (shard_id, mut res) in res {
+                if let Some((_, Some(res))) = res.pop() {
+                    results.push(InitialSearchResultShard {
+                        local_result: res,
+                        shard: shard_id,
+                        snapshot: query.snapshot,
+                    });
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Sends the queries to the shards with a single request to each shard. A shard only
+    /// gets the queries in `routed` that are routed to it.
+    async fn search_shards_batch(
+        client: &ShardedClient<SearchService, ShardId>,
+        queries: &[SearchQuery],
+        routed: Vec<(usize, Vec<ShardId>)>,
+        results: &mut [Vec<InitialSearchResultShard>],
+    ) {
+        let mut batches: HashMap<ShardId, Vec<usize>> = HashMap::new();
+
+        for (i, shards) in routed {
+            for shard in shards {
+                batches.entry(shard).or_default().push(i);
+            }
+        }
+
+        let futures = batches.into_iter().map(|(shard, idxs)| async move {
+            let res = client
+                .send(
+                    search_server::SearchBatch {
+                        queries: idxs.iter().map(|i| queries[*i].clone()).collect(),
+                    },
+                    &SpecificShardSelector(shard.clone()),
+                    &RandomReplicaSelector,
+                )
+                .await;
+
+            (shard, idxs, res)
+        });
+
+        for (shard, idxs, res) in join_all(futures).await {
+            let Ok(res) = res else {
+                continue;
+            };
+
+            for (_, mut res) in res {
+                if let Some((_, batch)) = res.pop() {
+                    for (i, res) in idxs.iter().zip(batch) {
+                        if let Some(res) = res {
+                            results[*i].push(InitialSearchResultShard {
+                                local_result: res,
+                                shard: shard.clone(),
+                                snapshot: queries[*i].snapshot,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     async fn entity_conn(&self) -> Arc<ShardedClient<entity_search_server::SearchService, ()>> {
         self.entiy_client.lock().await.conn().await
     }
@@ -223,28 +415,63 @@ impl DistributedSearcher {
 }
 
 impl SearchClient for DistributedSearcher {
+    async fn has_snapshot(&self, date: SnapshotDate) -> bool {
+        self.snapshot_dates().await.contains(&date)
+    }
+
     async fn search_initial(&self, query: &SearchQuery) -> Vec<InitialSearchResultShard> {
+        if let Some(date) = query.snapshot {
+            let Some(client) = self.snapshot_conn(Some(date)).await else {
+                tracing::warn!("no searchers for the index snapshot from {date}");
+                return Vec::new();
+            };
+
+            // the routing table only knows the domains of the current shards
+            return Self::search_shards(&client, query, &AllShardsSelector).await;
+        }
+
         let client = self.conn().await;
-        let mut results = Vec::new();
+        let routing = self.router.table(&client);
 
-        if let Ok(res) = client
-            .send(
-                search_server::Search {
-                    query: query.clone(),
-                },
-                &AllShardsSelector,
-                &RandomReplicaSelector,
-            )
-            .await
-        {
-            for (shard_id, mut res) in res {
-                if let Some((_, Some(res))) = res.pop() {
-                    results.push(InitialSearchResultShard {
-                        local_result: res,
-                        shard: shard_id,
-                    });
+        // queries restricted to a few hosts are only sent to the shards with those hosts
+        Self::search_shards(&client, query, &RoutedShardSelector::new(&routing, query)).await
+    }
+
+    async fn search_initial_batch(
+        &self,
+        queries: &[SearchQuery],
+    ) -> Vec<Vec<InitialSearchResultShard>> {
+        let mut results: Vec<_> = queries.iter().map(|_| Vec::new()).collect();
+        let mut snapshots: HashMap<_, Vec<_>> = HashMap::new();
+
+        for (i, query) in queries.iter().enumerate() {
+            snapshots.entry(query.snapshot).or_default().push(i);
+        }
+
+        for (snapshot, idxs) in snapshots {
+            let Some(client) = self.snapshot_conn(snapshot).await else {
+                tracing::warn!(?snapshot, "no searchers for the index snapshot");
+                continue;
+            };
+
+            let routed = match snapshot {
+                // the routing table only knows the domains of the current shards
+                Some(_) => {
+                    let shards = client.selected_shards(&AllShardsSelector);
+                    idxs.into_iter().map(|i| (i, shards.clone())).collect()
                 }
-            }
+                None => {
+                    let routing = self.router.table(&client);
+                    idxs.into_iter()
+                        .map(|i| {
+                            let selector = RoutedShardSelector::new(&routing, &queries[i]);
+                            (i, client.selected_shards(&selector))
+                        })
+                        .collect()
+                }
+            };
+
+            Self::search_shards_batch(&client, queries, routed, &mut results).await;
         }
 
         results
@@ -260,17 +487,29 @@ impl SearchClient for DistributedSearcher {
 
         for (i, pointer) in top_websites {
             pointers
-                .entry(pointer.shard)
+                .entry((pointer.snapshot, pointer.shard))
                 .or_default()
                 .push((*i, pointer.website.pointer().clone()));
 
             rankings.insert(*i, pointer.website.clone());
         }
 
-        let client = self.conn().await;
+        let mut clients = HashMap::new();
+        for (snapshot, _) in pointers.keys() {
+            if !clients.contains_key(snapshot) {
+                if let Some(client) = self.snapshot_conn(*snapshot).await {
+                    clients.insert(*snapshot, client);
+                }
+            }
+        }
+
         let mut futures = Vec::new();
-        for (shard, pointers) in pointers {
-            futures.push(self.retrieve_webpages_from_shard(shard, &client, query, pointers));
+        for ((snapshot, shard), pointers) in pointers {
+            let Some(client) = clients.get(&snapshot) else {
+                continue;
+            };
+
+            futures.push(self.retrieve_webpages_from_shard(shard, client, query, pointers));
         }
 
         let mut retrieved_webpages = Vec::new();
@@ -314,6 +553,27 @@ impl SearchClient for DistributedSearcher {
         }
     }
 
+    async fn get_stored_signals(&self, url: &str) -> Result<Option<StoredSignals>> {
+        let client = self.conn().await;
+
+        let res = client
+            .send(
+                search_server::GetStoredSignals {
+                    url: url.to_string(),
+                },
+                &AllShardsSelector,
+                &RandomReplicaSelector,
+            )
+            .await
+            .map_err(|_| Error::SearchFailed)?;
+
+        Ok(res
+            .into_iter()
+            .flat_map(|(_, v)| v.into_iter().map(|(_, v)| v))
+            .flatten()
+            .next())
+    }
+
     async fn get_homepage_descriptions(&self, urls: &[Url]) -> HashMap<Url, String> {
         let client = self.conn().await;
 
@@ -393,15 +653,33 @@ impl From<LocalSearcher<Index>> for LocalSearchClient {
 }
 
 impl SearchClient for LocalSearchClient {
+    async fn has_snapshot(&self, _: SnapshotDate) -> bool {
+        false
+    }
+
     async fn search_initial(&self, query: &SearchQuery) -> Vec<InitialSearchResultShard> {
         let res = self.0.search_initial(query, true).unwrap();
 
         vec![InitialSearchResultShard {
             local_result: res,
             shard: ShardId::new(0),
+            snapshot: None,
         }]
     }
 
+    async fn search_initial_batch(
+        &self,
+        queries: &[SearchQuery],
+    ) -> Vec<Vec<InitialSearchResultShard>> {
+        let mut results = Vec::with_capacity(queries.len());
+
+        for query in queries {
+            results.push(self.search_initial(query).await);
+        }
+
+        results
+    }
+
     async fn retrieve_webpages(
         &self,
         top_websites: &[(usize, ScoredWebpagePointer)],
@@ -428,6 +706,10 @@ impl SearchClient for LocalSearchClient {
         Ok(self.0.get_webpage(url))
     }
 
+    async fn get_stored_signals(&self, url: &str) -> Result<Option<StoredSignals>> {
+        self.0.stored_signals(url)
+    }
+
     async fn get_homepage_descriptions(
         &self,
         urls: &[url::Url],
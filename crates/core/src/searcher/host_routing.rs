@@ -0,0 +1,420 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Routing of queries that are restricted to a few hosts, like `site:` queries or optics
+//! that only match a fixed set of sites, to the shards that have pages from those hosts.
+//!
+//! Every search server reports the root domains in its index, and the distributed searcher
+//! keeps these in a routing table. A shard only receives a restricted query if it has at
+//! least one page that can match the restriction. Shards that are not in the routing
+//! table receive every query, so pruning never removes results.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    distributed::{
+        member::ShardId,
+        sonic::replication::{
+            AllReplicaSelector, AllShardsSelector, Shard, ShardSelector, ShardedClient,
+        },
+    },
+    entrypoint::search_server::{GetShardDomains, SearchService},
+    inverted_index::InvertedIndex,
+    query::parser::{self, Term},
+    schema::{text_field, Field, TextFieldEnum},
+    webpage::url_ext::UrlExt,
+    Result,
+};
+
+use super::SearchQuery;
+
+/// The routing table is fetched from the search servers at most this often. The domains
+/// of a shard only change when its search servers are restarted with a new index.
+pub const ROUTING_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+fn domain_hash(domain: &[u8]) -> u32 {
+    (bloom::fast_stable_hash_64(domain) >> 32) as u32
+}
+
+/// The root domain that all pages matched by the site has, if any.
+fn root_domain(site: &str) -> Option<String> {
+    // same parsing as the tokenizer of the site operator
+    let url = url::Url::parse(site)
+        .or_else(|_| url::Url::parse(&format!("http://{}", site)))
+        .ok()?;
+
+    // the tokenizer only leaves out the scheme for web pages
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    url.root_domain()
+        .filter(|domain| !domain.is_empty())
+        .map(|domain| domain.to_string())
+}
+
+/// Hashes of the root domains of all pages in a shard. Two domains can have the same
+/// hash, so a shard might be asked for domains it doesn't have, but never the other
+/// way around.
+#[derive(Debug, Clone, Default, bincode::Encode, bincode::Decode)]
+pub struct ShardDomains {
+    hashes: Vec<u32>,
+}
+
+impl ShardDomains {
+    pub fn from_index(index: &InvertedIndex) -> Result<Self> {
+        let tv_searcher = index.tv_searcher();
+        let field = tv_searcher
+            .schema()
+            .get_field(Field::Text(TextFieldEnum::from(text_field::DomainNoTokenizer)).name())?;
+
+        let mut hashes = Vec::new();
+
+        for segment_reader in tv_searcher.segment_readers() {
+            let inverted_index = segment_reader.inverted_index(field)?;
+            let mut stream = inverted_index.terms().stream()?;
+
+            while stream.advance() {
+                if !stream.key().is_empty() {
+                    hashes.push(domain_hash(stream.key()));
+                }
+            }
+        }
+
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        Ok(Self { hashes })
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    pub fn contains(&self, domain: &str) -> bool {
+        self.hashes
+            .binary_search(&domain_hash(domain.as_bytes()))
+            .is_ok()
+    }
+}
+
+impl<'a> FromIterator<&'a str> for ShardDomains {
+    fn from_iter<T: IntoIterator<Item = &'a str>>(iter: T) -> Self {
+        let mut hashes: Vec<_> = iter
+            .into_iter()
+            .map(|domain| domain_hash(domain.as_bytes()))
+            .collect();
+
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        Self { hashes }
+    }
+}
+
+/// The root domains that every result of a query must be on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiredDomains {
+    /// All `site:` terms must match, so a shard needs pages from every domain.
+    all_of: Vec<String>,
+    /// The optic only matches pages on one of these domains.
+    any_of: Option<Vec<String>>,
+}
+
+impl RequiredDomains {
+    /// The domains that the query is restricted to, or `None` if the query can match pages on
+    /// any domain.
+    pub fn from_query(query: &SearchQuery) -> Option<Self> {
+        let mut all_of = Vec::new();
+
+        // the terms are truncated the same way as when the search servers parse the query
+        if let Ok(terms) = parser::parse(&query.query) {
+            for term in parser::truncate(terms) {
+                if let Term::Site(site) = term {
+                    if let Some(domain) = root_domain(&site) {
+                        all_of.push(domain);
+                    }
+                }
+            }
+        }
+
+        // a single site that doesn't have a root domain can match pages on any domain
+        let any_of = query
+            .optic
+            .as_ref()
+            .and_then(|optic| optic.required_sites())
+            .and_then(|sites| {
+                sites
+                    .iter()
+                    .map(|site| root_domain(site))
+                    .collect::<Option<Vec<_>>>()
+            });
+
+        if all_of.is_empty() && any_of.is_none() {
+            return None;
+        }
+
+        all_of.sort();
+        all_of.dedup();
+
+        Some(Self { all_of, any_of })
+    }
+
+    /// Whether a shard with the domains can have results for the query.
+    pub fn may_match(&self, domains: &ShardDomains) -> bool {
+        self.all_of.iter().all(|domain| domains.contains(domain))
+            && self.any_of.as_ref().map_or(true, |any_of| {
+                any_of.iter().any(|domain| domains.contains(domain))
+            })
+    }
+}
+
+/// The domains of each shard. A shard can have several entries if its replicas have
+/// different versions of the index, e.g. while a new index is rolled out.
+#[derive(Debug, Default)]
+pub struct HostRoutingTable {
+    shards: HashMap<ShardId, Vec<ShardDomains>>,
+}
+
+impl HostRoutingTable {
+    pub fn insert(&mut self, shard: ShardId, domains: ShardDomains) {
+        self.shards.entry(shard).or_default().push(domains);
+    }
+
+    /// Remove the shard from the table, so it receives every query.
+    pub fn remove(&mut self, shard: &ShardId) {
+        self.shards.remove(shard);
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    /// Whether the shard can have results for a query with the required domains.
+    pub fn may_match(&self, shard: &ShardId, required: &RequiredDomains) -> bool {
+        match self.shards.get(shard) {
+            Some(replicas) => replicas.iter().any(|domains| required.may_match(domains)),
+            None => true,
+        }
+    }
+
+    async fn fetch(client: &ShardedClient<SearchService, ShardId>) -> Result<Self> {
+        let mut table = Self::default();
+
+        for (shard, replicas) in client
+            .send(GetShardDomains, &AllShardsSelector, &AllReplicaSelector)
+            .await?
+        {
+            for (_, domains) in replicas {
+                match domains {
+                    Some(domains) => table.insert(shard, domains),
+                    None => {
+                        // the replica didn't report its domains, so we can't know
+                        // which hosts the shard has
+                        table.remove(&shard);
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(table)
+    }
+}
+
+/// Selects the shards that can have results for a query. All shards are selected if the
+/// query is not restricted to any domains.
+pub struct RoutedShardSelector<'a> {
+    table: &'a HostRoutingTable,
+    required: Option<RequiredDomains>,
+}
+
+impl<'a> RoutedShardSelector<'a> {
+    pub fn new(table: &'a HostRoutingTable, query: &SearchQuery) -> Self {
+        Self {
+            table,
+            required: RequiredDomains::from_query(query),
+        }
+    }
+}
+
+impl ShardSelector<SearchService, ShardId> for RoutedShardSelector<'_> {
+    fn select<'b>(
+        &self,
+        shards: &'b [Shard<SearchService, ShardId>],
+    ) -> Vec<&'b Shard<SearchService, ShardId>> {
+        match &self.required {
+            Some(required) => shards
+                .iter()
+                .filter(|shard| self.table.may_match(shard.id(), required))
+                .collect(),
+            None => shards.iter().collect(),
+        }
+    }
+}
+
+/// Keeps the routing table up to date. The table is refreshed in the background, so
+/// queries never wait for the search servers to report their domains.
+pub struct HostRouter {
+    table: RwLock<Arc<HostRoutingTable>>,
+    last_refresh: Mutex<Option<Instant>>,
+}
+
+impl Default for HostRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostRouter {
+    pub fn new() -> Self {
+        Self {
+            table: RwLock::new(Arc::new(HostRoutingTable::default())),
+            last_refresh: Mutex::new(None),
+        }
+    }
+
+    /// The current routing table. Starts a refresh of the table if it is older than
+    /// [`ROUTING_REFRESH_INTERVAL`].
+    pub fn table(
+        self: &Arc<Self>,
+        client: &Arc<ShardedClient<SearchService, ShardId>>,
+    ) -> Arc<HostRoutingTable> {
+        let should_refresh = {
+            let mut last_refresh = self.last_refresh.lock().unwrap();
+
+            match *last_refresh {
+                Some(refreshed) if refreshed.elapsed() < ROUTING_REFRESH_INTERVAL => false,
+                _ => {
+                    *last_refresh = Some(Instant::now());
+                    true
+                }
+            }
+        };
+
+        if should_refresh {
+            let router = Arc::clone(self);
+            let client = Arc::clone(client);
+
+            tokio::spawn(async move {
+                match HostRoutingTable::fetch(&client).await {
+                    Ok(table) => {
+                        tracing::debug!("host routing table covers {} shards", table.len());
+                        *router.table.write().unwrap() = Arc::new(table);
+                    }
+                    Err(err) => {
+                        tracing::warn!("failed to refresh host routing table: {}", err);
+                        *router.last_refresh.lock().unwrap() = None;
+                    }
+                }
+            });
+        }
+
+        Arc::clone(&self.table.read().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use optics::Optic;
+
+    use super::*;
+
+    fn query(q: &str) -> SearchQuery {
+        SearchQuery {
+            query: q.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn required_domains_from_query() {
+        assert_eq!(RequiredDomains::from_query(&query("test")), None);
+        assert_eq!(
+            RequiredDomains::from_query(&query("test -site:a.com")),
+            None
+        );
+
+        // sites without a root domain can match pages on many domains
+        assert_eq!(RequiredDomains::from_query(&query("test site:com")), None);
+
+        let required = RequiredDomains::from_query(&query("test site:www.a.com")).unwrap();
+        assert_eq!(required.all_of, vec!["a.com".to_string()]);
+
+        let required = RequiredDomains::from_query(&SearchQuery {
+            optic: Some(
+                Optic::parse(
+                    r#"
+                    DiscardNonMatching;
+                    Rule {
+                        Matches {
+                            Site("|blog.b.com|")
+                        }
+                    };
+                    Rule {
+                        Matches {
+                            Domain("|c.co.uk|")
+                        }
+                    };
+                    "#,
+                )
+                .unwrap(),
+            ),
+            ..query("test site:https://a.com/path")
+        })
+        .unwrap();
+
+        assert_eq!(required.all_of, vec!["a.com".to_string()]);
+        assert_eq!(
+            required.any_of,
+            Some(vec!["b.com".to_string(), "c.co.uk".to_string()])
+        );
+    }
+
+    #[test]
+    fn route_to_shards_with_domains() {
+        let mut table = HostRoutingTable::default();
+        table.insert(ShardId::new(0), ["a.com", "b.com"].into_iter().collect());
+        table.insert(ShardId::new(1), ["b.com", "c.com"].into_iter().collect());
+
+        let required = RequiredDomains::from_query(&query("site:a.com test")).unwrap();
+        assert!(table.may_match(&ShardId::new(0), &required));
+        assert!(!table.may_match(&ShardId::new(1), &required));
+
+        // shards that are not in the table might have the domain
+        assert!(table.may_match(&ShardId::new(2), &required));
+
+        let required = RequiredDomains::from_query(&query("site:a.com site:c.com")).unwrap();
+        assert!(!table.may_match(&ShardId::new(0), &required));
+        assert!(!table.may_match(&ShardId::new(1), &required));
+
+        // a replica with another version of the index
+        table.insert(ShardId::new(1), ["a.com", "c.com"].into_iter().collect());
+        assert!(table.may_match(&ShardId::new(1), &required));
+    }
+}
@@ -0,0 +1,182 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Lenses are optics that ship with Stract and can be selected by name
+//! instead of sending the full optic with each request. They are applied on
+//! top of any optic the user might also have selected.
+
+use optics::Optic;
+use utoipa::ToSchema;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Lens {
+    /// Boost scholarly articles and papers.
+    Academic,
+}
+
+impl Lens {
+    pub fn optic(&self) -> Optic {
+        let raw = match self {
+            Lens::Academic => include_str!("lenses/academic.optic"),
+        };
+
+        Optic::parse(raw).expect("bundled lenses should always be valid optics")
+    }
+
+    /// Combine the lens with an optional user provided optic.
+    pub fn apply(&self, optic: Option<Optic>) -> Optic {
+        let lens = self.optic();
+
+        match optic {
+            Some(mut optic) => {
+                optic.rules.extend(lens.rules);
+                optic.host_rankings.merge_into(lens.host_rankings);
+                optic.discard_non_matching |= lens.discard_non_matching;
+
+                optic
+            }
+            None => lens,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        index::Index,
+        searcher::{LocalSearcher, SearchQuery},
+        webpage::{Html, Webpage},
+    };
+
+    use super::*;
+
+    const CONTENT: &str = "this is the best example website ever this is the best example website ever this is the best example website ever this is the best example website ever this is the best example website ever this is the best example website ever";
+
+    #[test]
+    fn lenses_are_valid() {
+        for lens in [Lens::Academic] {
+            assert!(!lens.optic().rules.is_empty());
+        }
+    }
+
+    #[test]
+    fn apply_keeps_user_rules() {
+        let user = Optic::parse(
+            r#"
+            Rule {
+                Matches {
+                    Domain("a.com")
+                },
+                Action(Discard)
+            }
+        "#,
+        )
+        .unwrap();
+
+        let combined = Lens::Academic.apply(Some(user.clone()));
+
+        assert_eq!(
+            combined.rules.len(),
+            user.rules.len() + Lens::Academic.optic().rules.len()
+        );
+        assert_eq!(combined.rules[0], user.rules[0]);
+    }
+
+    #[test]
+    fn academic_lens_boosts_papers() {
+        let mut index = Index::temporary().expect("Unable to open index");
+
+        index
+            .insert(&Webpage {
+                html: Html::parse(
+                    &format!(
+                        r#"
+                    <html>
+                        <head>
+                            <title>Website A</title>
+                        </head>
+                        <body>
+                            {CONTENT} {}
+                            example example example
+                        </body>
+                    </html>
+                "#,
+                        crate::rand_words(100)
+                    ),
+                    "https://www.a.com",
+                )
+                .unwrap(),
+                host_centrality: 0.01,
+                fetch_time_ms: 500,
+                ..Default::default()
+            })
+            .expect("failed to insert webpage");
+        index
+            .insert(&Webpage {
+                html: Html::parse(
+                    &format!(
+                        r#"
+                    <html>
+                        <head>
+                            <title>Website B</title>
+                            <meta name="citation_title" content="Website B">
+                            <meta name="citation_author" content="Doe, Jane">
+                            <meta name="citation_doi" content="10.1145/3397271.3401075">
+                        </head>
+                        <body>
+                            {CONTENT} {}
+                        </body>
+                    </html>
+                "#,
+                        crate::rand_words(100)
+                    ),
+                    "https://www.b.com",
+                )
+                .unwrap(),
+                fetch_time_ms: 500,
+                ..Default::default()
+            })
+            .expect("failed to insert webpage");
+
+        index.commit().expect("failed to commit index");
+        let searcher = LocalSearcher::from(index);
+
+        let res = searcher
+            .search(&SearchQuery {
+                query: "website".to_string(),
+                optic: Some(Lens::Academic.apply(None)),
+                ..Default::default()
+            })
+            .unwrap()
+            .webpages;
+
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].url, "https://www.b.com/");
+        assert_eq!(res[1].url, "https://www.a.com/");
+    }
+}
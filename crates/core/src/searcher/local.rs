@@ -23,7 +23,7 @@ use url::Url;
 use crate::collector::approx_count;
 use crate::config::{CollectorConfig, SnippetConfig};
 use crate::index::Index;
-use crate::inverted_index::{InvertedIndex, RetrievedWebpage};
+use crate::inverted_index::{InvertedIndex, RetrievedWebpage, StoredSignals};
 use crate::models::dual_encoder::DualEncoder;
 use crate::query::Query;
 use crate::ranking::models::lambdamart::LambdaMART;
@@ -33,9 +33,12 @@ use crate::ranking::pipeline::{
 };
 use crate::ranking::{Ranker, SignalComputer, SignalEnum, SignalScore};
 use crate::search_ctx::Ctx;
-use crate::search_prettifier::DisplayedWebpage;
+use crate::search_prettifier::{recipe_group, DisplayedWebpage, ProductFilter, ProductPage};
 use crate::{inverted_index, live_index, Result};
 
+use super::cost::CpuTimer;
+use super::distributed;
+use super::host_routing::ShardDomains;
 use super::WebsitesResult;
 use super::{InitialWebsiteResult, SearchQuery};
 
@@ -258,6 +261,7 @@ where
         query: &SearchQuery,
         de_rank_similar: bool,
     ) -> Result<InitialWebsiteResult> {
+        let timer = CpuTimer::start();
         let guard = self.index.guard();
         let ctx = guard.inverted_index().local_search_ctx();
         let inverted_index_result =
@@ -267,6 +271,7 @@ where
             websites: inverted_index_result.webpages,
             num_websites: inverted_index_result.num_hits,
             has_more: inverted_index_result.has_more,
+            cpu_ms: timer.elapsed().as_millis() as u64,
         })
     }
 
@@ -291,6 +296,25 @@ where
         use std::time::Instant;
 
         let start = Instant::now();
+
+        // over-fetch from the first page when filtering products, like the api searcher
+        let product_filter = ProductFilter::for_query(query.product_filter.as_ref(), &query.query);
+        let product_page = match &product_filter {
+            Some(_) => Some(
+                ProductPage::new(query.page, query.num_results)
+                    .ok_or(distributed::Error::PageOutOfRange)?,
+            ),
+            None => None,
+        };
+        let query = &match product_page {
+            Some(page) => SearchQuery {
+                page: 0,
+                num_results: page.num_fetched(),
+                ..query.clone()
+            },
+            None => query.clone(),
+        };
+
         let mut search_query = query.clone();
 
         let pipeline = {
@@ -335,7 +359,7 @@ where
 
         let top_websites = pipeline.apply(websites);
 
-        let has_more_results = search_len != top_websites.len();
+        let mut has_more_results = search_len != top_websites.len();
 
         let pointers: Vec<_> = top_websites
             .iter()
@@ -369,11 +393,24 @@ where
             webpage.ranking_signals = Some(ranking_signals);
         }
 
+        let mut num_hits = search_result.num_websites;
+
+        if let (Some(filter), Some(page)) = (&product_filter, product_page) {
+            num_hits = filter.retain(&mut webpages, num_hits, !has_more_results);
+            has_more_results = page.has_next(webpages.len(), has_more_results);
+            webpages = page.select(webpages);
+        }
+
+        let recipes = recipe_group(&query.query, &webpages);
+
         Ok(WebsitesResult {
-            num_hits: search_result.num_websites,
+            num_hits,
             webpages,
+            recipes,
             search_duration_ms: start.elapsed().as_millis(),
             has_more_results,
+            rewrites: Vec::new(),
+            result_set: None,
         })
     }
 
@@ -384,6 +421,15 @@ where
     pub fn get_homepage(&self, url: &Url) -> Option<RetrievedWebpage> {
         self.index.guard().inverted_index().get_homepage(url)
     }
+
+    pub fn stored_signals(&self, url: &str) -> Result<Option<StoredSignals>> {
+        self.index.guard().inverted_index().stored_signals(url)
+    }
+
+    /// The root domains of the pages in the index, used to route queries to the shard.
+    pub fn shard_domains(&self) -> Result<ShardDomains> {
+        ShardDomains::from_index(self.index.guard().inverted_index())
+    }
 }
 
 #[cfg(test)]
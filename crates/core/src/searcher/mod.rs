@@ -15,13 +15,18 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 pub mod api;
+pub mod cost;
 pub mod distributed;
+pub mod host_routing;
+pub mod lens;
 pub mod live;
 pub mod local;
+pub mod rewrite;
 
 pub use distributed::*;
 pub use local::*;
 use optics::{HostRankings, Optic};
+use rewrite::QueryRewrite;
 
 use utoipa::ToSchema;
 
@@ -31,8 +36,10 @@ use crate::{
     bangs::BangHit,
     collector::approx_count::Count,
     config::defaults,
+    distributed::member::SnapshotDate,
+    localization::Language,
     ranking::{pipeline::LocalRecallRankingWebpage, SignalCoefficient},
-    search_prettifier::DisplayedWebpage,
+    search_prettifier::{DisplayedWebpage, GroupedRecipe, ProductFilter},
     webpage::region::Region,
 };
 
@@ -61,9 +68,15 @@ impl SearchResult {
 #[serde(rename_all = "camelCase")]
 pub struct WebsitesResult {
     pub webpages: Vec<DisplayedWebpage>,
+    pub recipes: Option<Vec<GroupedRecipe>>,
     pub num_hits: Count,
     pub search_duration_ms: u128,
     pub has_more_results: bool,
+    /// Rewrites that were applied to the query before it was searched.
+    pub rewrites: Vec<QueryRewrite>,
+    /// Token of the urls of the results, which can be passed as `withinResults` to search
+    /// within them. Only set if the api keeps result sets.
+    pub result_set: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Clone)]
@@ -76,9 +89,26 @@ pub struct SearchQuery {
     pub host_rankings: Option<HostRankings>,
     pub return_ranking_signals: bool,
     pub safe_search: bool,
+    /// Include parked domains and domain sale pages in the results.
+    pub include_parked: bool,
+    /// Limit the number of results from each cluster of related hosts for ambiguous queries.
+    pub diversify: bool,
+    /// Hosts (and their subdomains) to remove from the results. They are filtered out in the index,
+    /// so they don't take up any of the requested results.
+    pub exclude_hosts: Vec<String>,
     pub count_results_exact: bool,
     pub return_body: Option<ReturnBody>,
     pub return_structured_data: bool,
+    pub product_filter: Option<ProductFilter>,
+    /// Language of the strings in the response, like the dates in snippets.
+    pub language: Language,
+    /// Search for the query exactly as it was typed, without spell corrections
+    /// or other rewrites.
+    pub disable_rewrites: bool,
+    /// Only return the pages with these urls, to search within the results of an earlier query.
+    pub within_urls: Option<Vec<String>>,
+    /// Search the frozen index snapshot of this date instead of the current index.
+    pub snapshot: Option<SnapshotDate>,
 
     pub signal_coefficients: SignalCoefficient,
 }
@@ -88,6 +118,8 @@ pub struct InitialWebsiteResult {
     pub num_websites: Count,
     pub websites: Vec<LocalRecallRankingWebpage>,
     pub has_more: bool,
+    /// CPU time used by the search in the index.
+    pub cpu_ms: u64,
 }
 
 impl Default for SearchQuery {
@@ -104,9 +136,17 @@ impl Default for SearchQuery {
             host_rankings: Default::default(),
             return_ranking_signals: defaults::SearchQuery::return_ranking_signals(),
             safe_search: defaults::SearchQuery::safe_search(),
+            include_parked: defaults::SearchQuery::include_parked(),
+            diversify: defaults::SearchQuery::diversify(),
+            exclude_hosts: Default::default(),
             count_results_exact: defaults::SearchQuery::count_results_exact(),
             return_body: None,
             return_structured_data: defaults::SearchQuery::return_structured_data(),
+            product_filter: Default::default(),
+            language: Default::default(),
+            disable_rewrites: defaults::SearchQuery::disable_rewrites(),
+            within_urls: Default::default(),
+            snapshot: None,
             signal_coefficients: Default::default(),
         }
     }
@@ -0,0 +1,144 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Rewrites of the query that are applied before it is searched. Every rewrite is
+//! returned with the results, so the user can see why the results do not match
+//! what they typed and search for the original query instead.
+
+use itertools::Itertools;
+use utoipa::ToSchema;
+
+const OPERATORS: [&str; 4] = ["site:", "intitle:", "inbody:", "inurl:"];
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum QueryRewriteKind {
+    /// Operators are written in the form the query parser understands.
+    OperatorNormalization,
+    /// Misspelled terms are replaced by their correction.
+    SpellCorrection,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRewrite {
+    pub kind: QueryRewriteKind,
+    pub from: String,
+    pub to: String,
+}
+
+impl QueryRewrite {
+    /// `None` if the rewrite did not change more than the whitespace of the query.
+    pub fn new(kind: QueryRewriteKind, from: &str, to: String) -> Option<Self> {
+        if from.split_whitespace().eq(to.split_whitespace()) {
+            return None;
+        }
+
+        Some(Self {
+            kind,
+            from: from.to_string(),
+            to,
+        })
+    }
+}
+
+/// Lowercase the operators (`Site:` becomes `site:`), remove the space between an
+/// operator and its value and replace typographic double quotes with plain quotes,
+/// so the operators and phrases are recognized by the query parser.
+pub fn normalize_operators(query: &str) -> String {
+    let query = query.replace(['“', '”', '„'], "\"");
+    let mut terms = Vec::new();
+    let mut words = query.split_whitespace();
+
+    while let Some(word) = words.next() {
+        let (negation, rest) = match word.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", word),
+        };
+
+        let operator = OPERATORS.iter().find(|operator| {
+            rest.get(..operator.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(operator))
+        });
+
+        match operator {
+            Some(operator) => {
+                let mut value = rest[operator.len()..].to_string();
+
+                if value.is_empty() {
+                    if let Some(next) = words.next() {
+                        value = next.to_string();
+                    }
+                }
+
+                terms.push(format!("{negation}{operator}{value}"));
+            }
+            None => terms.push(word.to_string()),
+        }
+    }
+
+    terms.into_iter().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            normalize_operators("rust Site:rust-lang.org"),
+            "rust site:rust-lang.org"
+        );
+        assert_eq!(
+            normalize_operators("INTITLE: “the book”  -INURL:blog"),
+            "intitle:\"the book\" -inurl:blog"
+        );
+        assert_eq!(normalize_operators("site of rust"), "site of rust");
+        assert_eq!(normalize_operators("rust site:"), "rust site:");
+    }
+
+    #[test]
+    fn unchanged_queries_are_not_rewrites() {
+        assert!(QueryRewrite::new(
+            QueryRewriteKind::OperatorNormalization,
+            "rust  lang",
+            "rust lang".to_string()
+        )
+        .is_none());
+    }
+}
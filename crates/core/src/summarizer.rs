@@ -22,11 +22,166 @@ use std::{
     path::Path,
 };
 
-use crate::{models::dual_encoder::DualEncoder, Result};
+use crate::{
+    config::{CitationVerificationConfig, UnsupportedClaimPolicy},
+    models::{accelerator::Accelerator, dual_encoder::DualEncoder},
+    ranking::models::cross_encoder::CrossEncoderModel,
+    web_spell::sentence_ranges,
+    Result,
+};
 use itertools::{intersperse, Itertools};
+use utoipa::ToSchema;
 
 use crate::ceil_char_boundary;
 
+/// Maximum number of passages of a page that are embedded when the page is summarized.
+/// The rest of the page is not part of the summary.
+const MAX_PASSAGES: usize = 256;
+
+/// A piece of a summary that is streamed to the client. Each segment of the
+/// summary is announced with the sources it was extracted from, followed by
+/// the tokens of the segment.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SummaryChunk {
+    Segment {
+        index: usize,
+        sources: Vec<String>,
+    },
+    Token {
+        segment: usize,
+        text: String,
+    },
+    /// A sentence of the segment that is not supported by the sources of the segment.
+    Unsupported {
+        segment: usize,
+        claim: String,
+    },
+    Done,
+}
+
+/// Split the text into tokens that each keep their trailing whitespace,
+/// so the tokens can be concatenated to get the original text.
+pub fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let word_end = rest
+            .char_indices()
+            .skip_while(|(_, c)| c.is_whitespace())
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(rest.len(), |(i, _)| i);
+
+        let end = rest[word_end..]
+            .char_indices()
+            .find(|(_, c)| !c.is_whitespace())
+            .map_or(rest.len(), |(i, _)| word_end + i);
+
+        let (token, tail) = rest.split_at(end);
+        rest = tail;
+
+        Some(token)
+    })
+}
+
+/// The chunks of a segment with index `index` of a summary.
+pub fn segment_chunks(
+    index: usize,
+    sources: Vec<String>,
+    text: &str,
+) -> impl Iterator<Item = SummaryChunk> + '_ {
+    std::iter::once(SummaryChunk::Segment { index, sources }).chain(tokens(text).map(
+        move |token| SummaryChunk::Token {
+            segment: index,
+            text: token.to_string(),
+        },
+    ))
+}
+
+/// A sentence of an answer and how well it is supported by the source it cites.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedSentence<'a> {
+    pub text: &'a str,
+    /// Score between 0 and 1 from the verifier model for the sentence and the
+    /// passages it was extracted from.
+    pub support: f32,
+}
+
+/// An extractive summary and the passages of the source it cites.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary<'a> {
+    pub text: String,
+    /// The range in `text` of each cited passage.
+    citations: Vec<(Range<usize>, &'a str)>,
+}
+
+impl<'a> Summary<'a> {
+    fn new(passages: &[CandidatePassage<'a>]) -> Self {
+        let mut text = String::new();
+        let mut citations = Vec::with_capacity(passages.len());
+
+        for (i, passage) in passages.iter().enumerate() {
+            if i > 0 && passage.index != passages[i - 1].index + 1 {
+                text.push_str(". \n");
+            }
+
+            let start = text.len();
+            text.push_str(passage.passage);
+            citations.push((start..text.len(), passage.passage));
+        }
+
+        Self { text, citations }
+    }
+
+    /// The passages that the part of the summary in `range` was extracted from.
+    fn cited(&self, range: &Range<usize>) -> String {
+        self.citations
+            .iter()
+            .filter(|(cited, _)| cited.start < range.end && range.start < cited.end)
+            .map(|(_, passage)| *passage)
+            .join(" ")
+    }
+}
+
+/// An answer where the policy for unsupported sentences has been applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedAnswer {
+    pub text: String,
+    /// Unsupported sentences that are kept in the text.
+    pub flagged: Vec<String>,
+}
+
+impl CheckedAnswer {
+    pub fn new(sentences: &[VerifiedSentence<'_>], config: &CitationVerificationConfig) -> Self {
+        let mut text = Vec::new();
+        let mut flagged = Vec::new();
+
+        for sentence in sentences {
+            if sentence.support >= config.min_support {
+                text.push(sentence.text);
+                continue;
+            }
+
+            match config.policy {
+                UnsupportedClaimPolicy::Drop => {}
+                UnsupportedClaimPolicy::Flag => {
+                    text.push(sentence.text);
+                    flagged.push(sentence.text.to_string());
+                }
+            }
+        }
+
+        Self {
+            text: text.join(" "),
+            flagged,
+        }
+    }
+}
+
 #[derive(Clone)]
 struct CandidatePassage<'a> {
     passage: &'a str,
@@ -155,6 +310,8 @@ trait PassageScorer {
 
 pub struct ExtractiveSummarizer {
     passage_scorer: DualEncoder,
+    /// Scores whether a sentence of a summary is supported by the passages it cites.
+    verifier: Option<CrossEncoderModel>,
     top_n_passages: usize,
     window_size: usize,
     overlap: usize,
@@ -162,19 +319,42 @@ pub struct ExtractiveSummarizer {
 
 impl ExtractiveSummarizer {
     pub fn open<P: AsRef<Path>>(path: P, top_n_passages: usize) -> Result<Self> {
+        Self::open_with_accelerator(path, top_n_passages, &Accelerator::default())
+    }
+
+    pub fn open_with_accelerator<P: AsRef<Path>>(
+        path: P,
+        top_n_passages: usize,
+        accelerator: &Accelerator,
+    ) -> Result<Self> {
         Ok(Self {
-            passage_scorer: DualEncoder::open(path)?,
+            passage_scorer: DualEncoder::open_with_accelerator(path, accelerator)?,
+            verifier: None,
             top_n_passages,
             window_size: 64,
             overlap: 0,
         })
     }
 
+    /// Verify the citations of summaries with a cross encoder.
+    pub fn with_verifier(mut self, verifier: CrossEncoderModel) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
     pub fn set_window_size(&mut self, window_size: usize) {
         self.window_size = window_size;
     }
 
-    fn query_specific(&self, query: &str, text: &str) -> Option<String> {
+    /// The passages of the text that are most relevant to the query, or `None`
+    /// if no passage could be scored.
+    pub fn query_specific(&self, query: &str, text: &str) -> Option<String> {
+        self.cited_summary(query, text).map(|summary| summary.text)
+    }
+
+    /// Like [`ExtractiveSummarizer::query_specific`], but the summary keeps track
+    /// of the passages of `text` it was extracted from.
+    pub fn cited_summary<'a>(&self, query: &str, text: &'a str) -> Option<Summary<'a>> {
         let query_vectors = self.passage_scorer.embed_query(query)?;
 
         let mut best_passages: BinaryHeap<Reverse<CandidatePassage<'_>>> =
@@ -182,7 +362,7 @@ impl ExtractiveSummarizer {
 
         let overlap_sents = OverlappingSents::new(text, self.window_size, self.overlap);
 
-        for (index, (passage, range)) in overlap_sents.enumerate() {
+        for (index, (passage, range)) in overlap_sents.take(MAX_PASSAGES).enumerate() {
             if let Some(passage_vec) = self.passage_scorer.embed_passage(passage) {
                 let score = self.passage_scorer.score(&query_vectors, &passage_vec);
 
@@ -225,20 +405,35 @@ impl ExtractiveSummarizer {
             new_best_passages.push(b);
         }
 
-        let mut res = String::new();
-
-        res.push_str(new_best_passages[0].passage);
-
-        for (a, b) in new_best_passages.into_iter().tuple_windows() {
-            if b.index == a.index + 1 {
-                res.push_str(b.passage);
-            } else {
-                res.push_str(". \n");
-                res.push_str(b.passage);
-            }
-        }
+        Some(Summary::new(&new_best_passages))
+    }
 
-        Some(res)
+    /// Check how well each sentence of the summary is supported by the passages it
+    /// was extracted from. A sentence that was cut from its context, or stitched
+    /// together from two passages, can say something the source does not.
+    pub fn verify<'s>(&self, summary: &'s Summary<'_>) -> Result<Vec<VerifiedSentence<'s>>> {
+        let verifier = self
+            .verifier
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("the summarizer has no verifier model"))?;
+
+        Ok(sentence_ranges(&summary.text)
+            .into_iter()
+            .filter(|range| !summary.text[range.clone()].trim().is_empty())
+            .map(|range| {
+                let text = &summary.text[range.clone()];
+                let support = verifier
+                    .scores(text, &[summary.cited(&range)])
+                    .first()
+                    .copied()
+                    .unwrap_or_default();
+
+                VerifiedSentence {
+                    text,
+                    support: support as f32,
+                }
+            })
+            .collect())
     }
 
     pub fn summarize(&self, query: &str, text: &str) -> String {
@@ -279,6 +474,41 @@ impl PassageScorer for DualEncoder {
 mod tests {
     use super::*;
 
+    #[test]
+    fn streamed_tokens() {
+        let text = "  the answer is\n42. ";
+        assert_eq!(
+            tokens(text).collect::<Vec<_>>(),
+            vec!["  the ", "answer ", "is\n", "42. "]
+        );
+        assert_eq!(tokens(text).collect::<String>(), text);
+        assert_eq!(tokens("").next(), None);
+
+        let chunks: Vec<_> =
+            segment_chunks(1, vec!["https://example.com/".to_string()], "a b").collect();
+        assert_eq!(
+            chunks,
+            vec![
+                SummaryChunk::Segment {
+                    index: 1,
+                    sources: vec!["https://example.com/".to_string()]
+                },
+                SummaryChunk::Token {
+                    segment: 1,
+                    text: "a ".to_string()
+                },
+                SummaryChunk::Token {
+                    segment: 1,
+                    text: "b".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&chunks[1]).unwrap(),
+            r#"{"type":"token","segment":1,"text":"a "}"#
+        );
+    }
+
     #[test]
     fn overlapping_sentences() {
         let mut it = OverlappingSents::new("this is a test sentence", 3, 1).map(|(p, _)| p);
@@ -320,6 +550,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn unsupported_claims() {
+        let sentences = [
+            VerifiedSentence {
+                text: "Paris is the capital of France.",
+                support: 0.9,
+            },
+            VerifiedSentence {
+                text: "It has ten million bakeries.",
+                support: 0.2,
+            },
+        ];
+
+        let mut config = CitationVerificationConfig {
+            min_support: 0.5,
+            policy: UnsupportedClaimPolicy::Drop,
+        };
+
+        assert_eq!(
+            CheckedAnswer::new(&sentences, &config),
+            CheckedAnswer {
+                text: "Paris is the capital of France.".to_string(),
+                flagged: Vec::new(),
+            }
+        );
+
+        config.policy = UnsupportedClaimPolicy::Flag;
+
+        assert_eq!(
+            CheckedAnswer::new(&sentences, &config),
+            CheckedAnswer {
+                text: "Paris is the capital of France. It has ten million bakeries.".to_string(),
+                flagged: vec!["It has ten million bakeries.".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn cited_passages() {
+        let source = "first passage. second passage. third passage.";
+        let passages = [
+            CandidatePassage {
+                passage: &source[..15],
+                range: 0..15,
+                index: 0,
+                score: 1.0,
+            },
+            CandidatePassage {
+                passage: &source[31..],
+                range: 31..source.len(),
+                index: 2,
+                score: 1.0,
+            },
+        ];
+
+        let summary = Summary::new(&passages);
+        assert_eq!(summary.text, "first passage. . \nthird passage.");
+        assert_eq!(summary.cited(&(0..5)), "first passage. ");
+        assert_eq!(
+            summary.cited(&(0..summary.text.len())),
+            "first passage.  third passage."
+        );
+    }
+
+    #[test]
+    fn verify_citations() {
+        let dual_encoder_path = Path::new("../../data/summarizer/dual_encoder");
+        let cross_encoder_path = Path::new("../../data/cross_encoder");
+        if !dual_encoder_path.exists() || !cross_encoder_path.exists() {
+            // Skip the test if the test data is not available
+            return;
+        }
+        let summarizer = ExtractiveSummarizer::open(dual_encoder_path, 1)
+            .unwrap()
+            .with_verifier(CrossEncoderModel::open(cross_encoder_path).unwrap());
+
+        let source = "Paris is the capital and most populous city of France.";
+        let passages = [CandidatePassage {
+            passage: source,
+            range: 0..source.len(),
+            index: 0,
+            score: 1.0,
+        }];
+        let mut summary = Summary::new(&passages);
+        summary.text.push_str(" The moon is made of cheese.");
+
+        let verified = summarizer.verify(&summary).unwrap();
+
+        assert_eq!(verified.len(), 2);
+        assert!(verified[0].support > verified[1].support);
+    }
+
     #[test]
     fn test_dual_encoder() {
         let data_path = Path::new("../../data/summarizer/dual_encoder");
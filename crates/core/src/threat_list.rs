@@ -0,0 +1,279 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Open lists of malware and phishing urls (e.g. URLhaus and OpenPhish).
+//! The lists are ingested into a key-value store that is checked when results
+//! are served, so flagged results can be annotated or removed.
+//!
+//! Two formats are supported, both with `#` comments:
+//! - One url per line, as used by OpenPhish and the URLhaus text exports.
+//! - The URLhaus csv export, where the url is the third quoted column.
+//!
+//! An url without a path flags every page on the host.
+//!
+//! Every build ingests the lists into a new folder inside the output folder and
+//! then atomically replaces the `CURRENT` file that names the folder in use, so
+//! urls that were removed from the lists are no longer flagged.
+
+use std::{
+    fs,
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+
+use url::Url;
+
+use crate::{
+    config::{ThreatListSource, ThreatListsConfig, ThreatPolicy},
+    search_prettifier::DisplayedWebpage,
+    webgraph::{Node, NodeID},
+    Result,
+};
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Threat {
+    Malware,
+    Phishing,
+}
+
+fn parse_line(line: &str) -> Option<Url> {
+    let line = line.trim();
+
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let url = if line.starts_with('"') {
+        // "id","dateadded","url","url_status",...
+        line.split("\",\"").nth(2)?.trim_matches('"')
+    } else {
+        line
+    };
+
+    Url::parse(url).ok()
+}
+
+fn read_source(source: &ThreatListSource) -> Result<Box<dyn BufRead>> {
+    if source.path.starts_with("http://") || source.path.starts_with("https://") {
+        let body = reqwest::blocking::get(&source.path)?
+            .error_for_status()?
+            .text()?;
+
+        Ok(Box::new(std::io::Cursor::new(body)))
+    } else {
+        let file = std::fs::File::open(&source.path)?;
+
+        Ok(Box::new(std::io::BufReader::new(file)))
+    }
+}
+
+/// File in the output folder with the name of the folder that holds the current list.
+const CURRENT_FILE: &str = "CURRENT";
+
+/// The folder of the list at `path`. Folders that were not created by
+/// [`ThreatList::build`] hold the list directly.
+fn current_path(path: &Path) -> Result<PathBuf> {
+    let current = path.join(CURRENT_FILE);
+
+    if current.exists() {
+        Ok(path.join(fs::read_to_string(current)?.trim()))
+    } else {
+        Ok(path.to_path_buf())
+    }
+}
+
+pub struct ThreatList {
+    inner: speedy_kv::Db<NodeID, Threat>,
+}
+
+impl ThreatList {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(current_path(path.as_ref())?)?,
+        })
+    }
+
+    pub fn build(config: &ThreatListsConfig) -> Result<Self> {
+        let output = Path::new(&config.output_path);
+        fs::create_dir_all(output)?;
+
+        let previous = output
+            .join(CURRENT_FILE)
+            .exists()
+            .then(|| current_path(output))
+            .transpose()?;
+
+        let name = uuid::Uuid::new_v4().to_string();
+        let mut list = Self::open(output.join(&name))?;
+
+        for source in &config.lists {
+            let mut num_urls = 0;
+
+            for line in read_source(source)?.lines() {
+                if let Some(url) = parse_line(&line?) {
+                    list.insert(&url, source.threat)?;
+                    num_urls += 1;
+                }
+            }
+
+            tracing::info!("ingested {} urls from {}", num_urls, source.path);
+        }
+
+        list.inner.commit()?;
+        list.inner.merge_all_segments()?;
+
+        let tmp = output.join(format!("{CURRENT_FILE}.tmp"));
+        fs::write(&tmp, &name)?;
+        fs::rename(tmp, output.join(CURRENT_FILE))?;
+
+        // searchers that already opened the previous list keep their open files
+        if let Some(previous) = previous {
+            fs::remove_dir_all(previous)?;
+        }
+
+        Ok(list)
+    }
+
+    pub fn insert(&mut self, url: &Url, threat: Threat) -> Result<()> {
+        self.inner.insert(Node::from(url).id(), threat)
+    }
+
+    pub fn commit(&mut self) -> Result<()> {
+        self.inner.commit()
+    }
+
+    /// Check both the page and its host, since the lists can flag entire hosts.
+    pub fn get(&self, url: &Url) -> Result<Option<Threat>> {
+        let node = Node::from(url);
+
+        if let Some(threat) = self.inner.get(&node.id())? {
+            return Ok(Some(threat));
+        }
+
+        self.inner.get(&node.into_host().id())
+    }
+
+    /// Annotate the flagged webpages, and remove them if the policy says so.
+    pub fn apply(&self, webpages: &mut Vec<DisplayedWebpage>, policy: ThreatPolicy) {
+        for webpage in webpages.iter_mut() {
+            webpage.security_threat = Url::parse(&webpage.url)
+                .ok()
+                .and_then(|url| self.get(&url).ok().flatten());
+        }
+
+        if policy == ThreatPolicy::Filter {
+            webpages.retain(|webpage| webpage.security_threat.is_none());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_formats() {
+        assert_eq!(parse_line("# comment"), None);
+        assert_eq!(parse_line(""), None);
+        assert_eq!(
+            parse_line("https://evil.example.com/login.php\n"),
+            Some(Url::parse("https://evil.example.com/login.php").unwrap())
+        );
+        assert_eq!(
+            parse_line(
+                r#""2912345","2024-03-01 10:00:00","http://1.2.3.4/bins/x.sh","online","2024-03-01 10:00:00","malware_download","elf,mirai","https://urlhaus.abuse.ch/url/2912345/","anonymous""#
+            ),
+            Some(Url::parse("http://1.2.3.4/bins/x.sh").unwrap())
+        );
+    }
+
+    #[test]
+    fn lookup() {
+        let mut list = ThreatList::open(crate::gen_temp_path()).unwrap();
+
+        list.insert(
+            &Url::parse("https://phish.example.com/login").unwrap(),
+            Threat::Phishing,
+        )
+        .unwrap();
+        list.insert(
+            &Url::parse("http://malware.example.org/").unwrap(),
+            Threat::Malware,
+        )
+        .unwrap();
+        list.commit().unwrap();
+
+        let get = |url: &str| list.get(&Url::parse(url).unwrap()).unwrap();
+
+        assert_eq!(
+            get("http://phish.example.com/login"),
+            Some(Threat::Phishing)
+        );
+        assert_eq!(get("https://phish.example.com/"), None);
+        assert_eq!(
+            get("https://malware.example.org/any/page"),
+            Some(Threat::Malware)
+        );
+        assert_eq!(get("https://example.org/"), None);
+    }
+
+    #[test]
+    fn rebuild_drops_removed_urls() {
+        let output = crate::gen_temp_path();
+        let source = crate::gen_temp_path().join("list.txt");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+
+        let config = ThreatListsConfig {
+            lists: vec![ThreatListSource {
+                path: source.to_str().unwrap().to_string(),
+                threat: Threat::Phishing,
+            }],
+            output_path: output.to_str().unwrap().to_string(),
+        };
+
+        let phish = Url::parse("https://phish.example.com/login").unwrap();
+        let other = Url::parse("https://other.example.com/login").unwrap();
+
+        fs::write(&source, "https://phish.example.com/login\n").unwrap();
+        ThreatList::build(&config).unwrap();
+        assert_eq!(
+            ThreatList::open(&output).unwrap().get(&phish).unwrap(),
+            Some(Threat::Phishing)
+        );
+
+        fs::write(&source, "https://other.example.com/login\n").unwrap();
+        ThreatList::build(&config).unwrap();
+
+        let list = ThreatList::open(&output).unwrap();
+        assert_eq!(list.get(&phish).unwrap(), None);
+        assert_eq!(list.get(&other).unwrap(), Some(Threat::Phishing));
+
+        // only the current list and the pointer to it are left
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,246 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BlockKey {
+    object: u64,
+    block: u64,
+}
+
+impl BlockKey {
+    fn file_name(&self) -> String {
+        format!("{:016x}_{}", self.object, self.block)
+    }
+
+    fn from_file_name(name: &str) -> Option<Self> {
+        let (object, block) = name.split_once('_')?;
+
+        Some(Self {
+            object: u64::from_str_radix(object, 16).ok()?,
+            block: block.parse().ok()?,
+        })
+    }
+}
+
+struct CachedBlock {
+    len: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct State {
+    blocks: HashMap<BlockKey, CachedBlock>,
+    lru: BTreeMap<u64, BlockKey>,
+    clock: u64,
+    size: u64,
+}
+
+impl State {
+    fn touch(&mut self, key: BlockKey) {
+        self.clock += 1;
+
+        if let Some(block) = self.blocks.get_mut(&key) {
+            self.lru.remove(&block.last_used);
+            block.last_used = self.clock;
+            self.lru.insert(self.clock, key);
+        }
+    }
+
+    fn insert(&mut self, key: BlockKey, len: u64) {
+        self.clock += 1;
+
+        if let Some(old) = self.blocks.insert(
+            key,
+            CachedBlock {
+                len,
+                last_used: self.clock,
+            },
+        ) {
+            self.lru.remove(&old.last_used);
+            self.size -= old.len;
+        }
+
+        self.lru.insert(self.clock, key);
+        self.size += len;
+    }
+
+    fn pop_lru(&mut self) -> Option<BlockKey> {
+        let (_, key) = self.lru.pop_first()?;
+
+        if let Some(block) = self.blocks.remove(&key) {
+            self.size -= block.len;
+        }
+
+        Some(key)
+    }
+}
+
+/// Fixed size blocks of remote objects stored in a local folder. The least recently
+/// used blocks are evicted when the blocks take up more than the capacity. Blocks that
+/// are already in the folder when the cache is opened are kept.
+pub struct BlockCache {
+    path: PathBuf,
+    block_size: u64,
+    capacity_bytes: u64,
+    state: Mutex<State>,
+}
+
+impl BlockCache {
+    pub fn open<P: AsRef<Path>>(path: P, block_size: u64, capacity_bytes: u64) -> Result<Self> {
+        assert!(block_size > 0);
+        std::fs::create_dir_all(path.as_ref())?;
+
+        let mut existing = Vec::new();
+
+        for entry in std::fs::read_dir(path.as_ref())? {
+            let entry = entry?;
+            let name = entry.file_name();
+
+            match name.to_str().and_then(BlockKey::from_file_name) {
+                Some(key) => {
+                    let metadata = entry.metadata()?;
+                    existing.push((metadata.modified()?, key, metadata.len()));
+                }
+                // leftovers from interrupted writes
+                None => std::fs::remove_file(entry.path())?,
+            }
+        }
+
+        existing.sort_by_key(|(modified, _, _)| *modified);
+
+        let mut state = State::default();
+        for (_, key, len) in existing {
+            state.insert(key, len);
+        }
+
+        let cache = Self {
+            path: path.as_ref().to_path_buf(),
+            block_size,
+            capacity_bytes,
+            state: Mutex::new(state),
+        };
+
+        cache.evict()?;
+
+        Ok(cache)
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// Total size of the cached blocks.
+    pub fn size(&self) -> u64 {
+        self.state.lock().unwrap().size
+    }
+
+    /// The block of the object, where `object` identifies the remote object.
+    /// The block is read with `fetch` and stored if it is not in the cache.
+    pub fn get_or_fetch<F>(&self, object: u64, block: u64, fetch: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce() -> Result<Vec<u8>>,
+    {
+        let key = BlockKey { object, block };
+        let path = self.path.join(key.file_name());
+
+        let cached = self.state.lock().unwrap().blocks.contains_key(&key);
+
+        if cached {
+            if let Ok(data) = std::fs::read(&path) {
+                self.state.lock().unwrap().touch(key);
+                return Ok(data);
+            }
+        }
+
+        let data = fetch()?;
+
+        // blocks are written under a temporary name, so a block that is being
+        // written is never read by another thread
+        let tmp_path = self
+            .path
+            .join(format!("{}.{}.tmp", key.file_name(), uuid::Uuid::new_v4()));
+        std::fs::write(&tmp_path, &data)?;
+        std::fs::rename(&tmp_path, &path)?;
+
+        self.state.lock().unwrap().insert(key, data.len() as u64);
+        self.evict()?;
+
+        Ok(data)
+    }
+
+    fn evict(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        while state.size > self.capacity_bytes {
+            let Some(key) = state.pop_lru() else {
+                break;
+            };
+
+            std::fs::remove_file(self.path.join(key.file_name())).ok();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let path = crate::gen_temp_path();
+        let cache = BlockCache::open(&path, 4, 8).unwrap();
+
+        let block = |b: u8| move || Ok(vec![b; 4]);
+
+        assert_eq!(cache.get_or_fetch(1, 0, block(0)).unwrap(), vec![0; 4]);
+        assert_eq!(cache.get_or_fetch(1, 1, block(1)).unwrap(), vec![1; 4]);
+
+        // cached blocks are not fetched again
+        assert_eq!(
+            cache
+                .get_or_fetch(1, 0, || panic!("block should be cached"))
+                .unwrap(),
+            vec![0; 4]
+        );
+
+        // block 1 is the least recently used
+        cache.get_or_fetch(2, 0, block(2)).unwrap();
+        assert_eq!(cache.size(), 8);
+        assert_eq!(cache.get_or_fetch(1, 1, block(3)).unwrap(), vec![3; 4]);
+
+        drop(cache);
+
+        // the blocks survive a restart
+        let cache = BlockCache::open(&path, 4, 8).unwrap();
+        assert_eq!(cache.size(), 8);
+        assert_eq!(
+            cache
+                .get_or_fetch(1, 1, || panic!("block should be cached"))
+                .unwrap(),
+            vec![3; 4]
+        );
+    }
+}
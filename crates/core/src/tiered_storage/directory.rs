@@ -0,0 +1,351 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use tantivy::{
+    directory::{
+        error::{DeleteError, LockError, OpenReadError, OpenWriteError},
+        DirectoryLock, FileHandle, Lock, MmapDirectory, OwnedBytes, WatchCallback, WatchHandle,
+        WritePtr,
+    },
+    Directory, HasLen,
+};
+
+use super::{BlockCache, ObjectStore};
+use crate::Result;
+
+const COLD_FILES: &str = "cold_files.json";
+
+/// Offloaded files are uploaded in parts, so no single upload has to hold a
+/// large segment file in memory. Must be a multiple of the block size.
+const BLOCKS_PER_PART: u64 = 256;
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+struct ColdFile {
+    len: u64,
+    part_size: u64,
+}
+
+/// Files that have been moved to the object store, keyed by their path in the directory.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct ColdFiles {
+    files: BTreeMap<PathBuf, ColdFile>,
+}
+
+impl ColdFiles {
+    fn open(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+}
+
+/// A tantivy directory where the files can be moved to an object store. Files that
+/// are still on local disk are memory mapped as usual, while reads from the files in
+/// the object store go through the block cache. New files are always written locally.
+#[derive(Clone)]
+pub struct TieredDirectory {
+    root: PathBuf,
+    local: MmapDirectory,
+    store: Arc<dyn ObjectStore>,
+    cache: Arc<BlockCache>,
+    cold: Arc<RwLock<ColdFiles>>,
+}
+
+impl fmt::Debug for TieredDirectory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TieredDirectory")
+            .field("root", &self.root)
+            .finish()
+    }
+}
+
+fn object_key(path: &Path, part: u64) -> String {
+    format!("{}.{}", path.to_string_lossy(), part)
+}
+
+impl TieredDirectory {
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        store: Arc<dyn ObjectStore>,
+        cache: Arc<BlockCache>,
+    ) -> Result<Self> {
+        let root = path.as_ref().to_path_buf();
+        std::fs::create_dir_all(&root)?;
+
+        let cold = ColdFiles::open(&root.join(COLD_FILES))?;
+
+        Ok(Self {
+            local: MmapDirectory::open(&root)?,
+            root,
+            store,
+            cache,
+            cold: Arc::new(RwLock::new(cold)),
+        })
+    }
+
+    /// Whether the file has been moved to the object store.
+    pub fn is_cold(&self, path: &Path) -> bool {
+        self.cold.read().unwrap().files.contains_key(path)
+    }
+
+    /// Upload the local file to the object store and remove it from local disk.
+    /// The object keys are relative to the directory, so directories that share an
+    /// object store must use different folders in it.
+    pub fn offload(&self, path: &Path) -> Result<()> {
+        let local_path = self.root.join(path);
+        let len = std::fs::metadata(&local_path)?.len();
+        let part_size = self.cache.block_size() * BLOCKS_PER_PART;
+
+        let mut file = std::fs::File::open(&local_path)?;
+
+        for part in 0..len.div_ceil(part_size).max(1) {
+            let start = part * part_size;
+            let part_len = part_size.min(len - start);
+
+            let mut buf = vec![0; part_len as usize];
+            file.read_exact(&mut buf)?;
+
+            self.store.put(&object_key(path, part), &buf)?;
+        }
+
+        {
+            let mut cold = self.cold.write().unwrap();
+            cold.files
+                .insert(path.to_path_buf(), ColdFile { len, part_size });
+            cold.save(&self.root.join(COLD_FILES))?;
+        }
+
+        std::fs::remove_file(local_path)?;
+
+        Ok(())
+    }
+}
+
+impl Directory for TieredDirectory {
+    fn get_file_handle(&self, path: &Path) -> Result<Arc<dyn FileHandle>, OpenReadError> {
+        if let Some(file) = self.cold.read().unwrap().files.get(path) {
+            let block_size = self.cache.block_size();
+
+            if file.part_size % block_size != 0 {
+                return Err(OpenReadError::IoError {
+                    io_error: Arc::new(io::Error::other(format!(
+                        "{path:?} was offloaded with parts that are not a multiple of the \
+                         block size {block_size}"
+                    ))),
+                    filepath: path.to_path_buf(),
+                });
+            }
+
+            // blocks of different sizes must not be mixed up in the cache
+            let object = xxhash_rust::xxh3::xxh3_64(
+                format!("{}:{}", path.to_string_lossy(), block_size).as_bytes(),
+            );
+
+            return Ok(Arc::new(ColdFileHandle {
+                path: path.to_path_buf(),
+                object,
+                file: *file,
+                store: Arc::clone(&self.store),
+                cache: Arc::clone(&self.cache),
+            }));
+        }
+
+        self.local.get_file_handle(path)
+    }
+
+    fn delete(&self, path: &Path) -> Result<(), DeleteError> {
+        let mut cold = self.cold.write().unwrap();
+
+        // the objects are left in the store, as a replica of the directory might still
+        // reference them
+        if cold.files.remove(path).is_some() {
+            return cold
+                .save(&self.root.join(COLD_FILES))
+                .map_err(|err| DeleteError::IoError {
+                    io_error: Arc::new(io::Error::other(err.to_string())),
+                    filepath: path.to_path_buf(),
+                });
+        }
+
+        self.local.delete(path)
+    }
+
+    fn exists(&self, path: &Path) -> Result<bool, OpenReadError> {
+        if self.is_cold(path) {
+            return Ok(true);
+        }
+
+        self.local.exists(path)
+    }
+
+    fn open_write(&self, path: &Path) -> Result<WritePtr, OpenWriteError> {
+        self.local.open_write(path)
+    }
+
+    fn atomic_read(&self, path: &Path) -> Result<Vec<u8>, OpenReadError> {
+        self.local.atomic_read(path)
+    }
+
+    fn atomic_write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        self.local.atomic_write(path, data)
+    }
+
+    fn sync_directory(&self) -> io::Result<()> {
+        self.local.sync_directory()
+    }
+
+    fn acquire_lock(&self, lock: &Lock) -> Result<DirectoryLock, LockError> {
+        self.local.acquire_lock(lock)
+    }
+
+    fn watch(&self, watch_callback: WatchCallback) -> tantivy::Result<WatchHandle> {
+        self.local.watch(watch_callback)
+    }
+}
+
+struct ColdFileHandle {
+    path: PathBuf,
+    object: u64,
+    file: ColdFile,
+    store: Arc<dyn ObjectStore>,
+    cache: Arc<BlockCache>,
+}
+
+impl fmt::Debug for ColdFileHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ColdFileHandle")
+            .field("path", &self.path)
+            .field("len", &self.file.len)
+            .finish()
+    }
+}
+
+impl ColdFileHandle {
+    fn block(&self, block: u64) -> Result<Vec<u8>> {
+        self.cache.get_or_fetch(self.object, block, || {
+            let block_size = self.cache.block_size();
+            let start = block * block_size;
+            let end = (start + block_size).min(self.file.len);
+
+            let part = start / self.file.part_size;
+            let offset = part * self.file.part_size;
+
+            self.store.read_range(
+                &object_key(&self.path, part),
+                (start - offset)..(end - offset),
+            )
+        })
+    }
+}
+
+impl HasLen for ColdFileHandle {
+    fn len(&self) -> usize {
+        self.file.len as usize
+    }
+}
+
+impl FileHandle for ColdFileHandle {
+    fn read_bytes(&self, range: Range<usize>) -> io::Result<OwnedBytes> {
+        if range.end > self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{:?} is out of bounds for {:?}", range, self.path),
+            ));
+        }
+
+        if range.is_empty() {
+            return Ok(OwnedBytes::empty());
+        }
+
+        let block_size = self.cache.block_size() as usize;
+        let mut buf = Vec::with_capacity(range.len());
+
+        for block in (range.start / block_size)..=((range.end - 1) / block_size) {
+            let data = self
+                .block(block as u64)
+                .map_err(|err| io::Error::other(err.to_string()))?;
+
+            let block_start = block * block_size;
+            let from = range.start.max(block_start) - block_start;
+            let to = range.end.min(block_start + data.len()) - block_start;
+
+            buf.extend_from_slice(&data[from..to]);
+        }
+
+        Ok(OwnedBytes::new(buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiered_storage::LocalObjectStore;
+
+    #[test]
+    fn read_offloaded_file() {
+        let store = Arc::new(LocalObjectStore::new(crate::gen_temp_path()));
+        let cache = Arc::new(BlockCache::open(crate::gen_temp_path(), 16, 1024).unwrap());
+        let dir = TieredDirectory::open(crate::gen_temp_path(), store.clone(), cache).unwrap();
+
+        let path = Path::new("segment.idx");
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        dir.atomic_write(path, &data).unwrap();
+
+        dir.offload(path).unwrap();
+        assert!(dir.is_cold(path));
+        assert!(dir.exists(path).unwrap());
+        assert!(!dir.root.join(path).exists());
+
+        let handle = dir.get_file_handle(path).unwrap();
+        assert_eq!(handle.len(), data.len());
+        assert_eq!(handle.read_bytes(0..10_000).unwrap().as_slice(), &data[..]);
+
+        // ranges across blocks and parts
+        for range in [3..7, 10..40, 4090..4100, 9_990..10_000] {
+            assert_eq!(
+                handle.read_bytes(range.clone()).unwrap().as_slice(),
+                &data[range]
+            );
+        }
+
+        // the list of cold files survives a restart
+        let cache = Arc::new(BlockCache::open(crate::gen_temp_path(), 16, 1024).unwrap());
+        let reopened = TieredDirectory::open(&dir.root, store, cache).unwrap();
+        assert!(reopened.is_cold(path));
+
+        reopened.delete(path).unwrap();
+        assert!(!reopened.exists(path).unwrap());
+    }
+}
@@ -0,0 +1,50 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Tiered storage for the search index.
+//!
+//! The segment files of an index can be offloaded to an S3-compatible object store,
+//! after which the searchers read them through a block cache on local disk. Only the
+//! blocks that are actually hit by queries take up space on the searcher, so the full
+//! index does not need to fit on its local disk. The webgraph segments are read
+//! through memory maps all the way down and are not covered.
+
+mod block_cache;
+mod directory;
+mod object_store;
+
+use std::{path::Path, sync::Arc};
+
+pub use block_cache::BlockCache;
+pub use directory::TieredDirectory;
+pub use object_store::{LocalObjectStore, ObjectStore, S3ObjectStore};
+
+use crate::{config::TieredStorageConfig, Result};
+
+/// Open the directory at `path` with the object store and block cache from the config.
+pub fn open_directory<P: AsRef<Path>>(
+    path: P,
+    config: &TieredStorageConfig,
+) -> Result<TieredDirectory> {
+    let store = Arc::new(S3ObjectStore::new(&config.s3)?);
+    let cache = Arc::new(BlockCache::open(
+        &config.cache_path,
+        config.block_size_bytes,
+        config.cache_size_bytes,
+    )?);
+
+    TieredDirectory::open(path, store, cache)
+}
@@ -0,0 +1,144 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    ops::Range,
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::bail;
+
+use crate::{config::S3Config, Result};
+
+pub trait ObjectStore: Send + Sync + 'static {
+    /// Read the bytes in `range` of the object.
+    fn read_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>>;
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+}
+
+/// Objects in a folder of an S3-compatible bucket.
+pub struct S3ObjectStore {
+    bucket: s3::Bucket,
+    folder: String,
+}
+
+impl S3ObjectStore {
+    pub fn new(config: &S3Config) -> Result<Self> {
+        let bucket = s3::Bucket::new(
+            &config.bucket,
+            s3::Region::Custom {
+                region: "".to_string(),
+                endpoint: config.endpoint.clone(),
+            },
+            s3::creds::Credentials {
+                access_key: Some(config.access_key.clone()),
+                secret_key: Some(config.secret_key.clone()),
+                security_token: None,
+                session_token: None,
+                expiration: None,
+            },
+        )?
+        .with_path_style()
+        .with_request_timeout(Duration::from_secs(5 * 60));
+
+        Ok(Self {
+            bucket,
+            folder: config.folder.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn path(&self, key: &str) -> String {
+        format!("{}/{}", self.folder, key)
+    }
+}
+
+impl ObjectStore for S3ObjectStore {
+    fn read_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>> {
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // the end of the range is inclusive in the request
+        let res = self.bucket.get_object_range_blocking(
+            self.path(key),
+            range.start,
+            Some(range.end - 1),
+        )?;
+
+        if !matches!(res.status_code(), 200 | 206) {
+            bail!(
+                "failed to read {} from bucket: status {}",
+                key,
+                res.status_code()
+            );
+        }
+
+        Ok(res.bytes().to_vec())
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let res = self.bucket.put_object_blocking(self.path(key), data)?;
+
+        if res.status_code() != 200 {
+            bail!(
+                "failed to upload {} to bucket: status {}",
+                key,
+                res.status_code()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Objects stored as files in a local folder, e.g. on a network mount.
+pub struct LocalObjectStore {
+    root: PathBuf,
+}
+
+impl LocalObjectStore {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl ObjectStore for LocalObjectStore {
+    fn read_range(&self, key: &str, range: Range<u64>) -> Result<Vec<u8>> {
+        let mut file = File::open(self.root.join(key))?;
+        file.seek(SeekFrom::Start(range.start))?;
+
+        let mut buf = vec![0; (range.end - range.start) as usize];
+        file.read_exact(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let path = self.root.join(key);
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, data)?;
+
+        Ok(())
+    }
+}
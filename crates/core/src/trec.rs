@@ -0,0 +1,293 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Reading and writing the file formats used by TREC-style relevance evaluations,
+//! and the metrics we compute from them. Documents are identified by their url.
+//!
+//! - Topics: `<qid>\t<query>` per line.
+//! - Qrels: `<qid> <iteration> <url> <relevance>` per line.
+//! - Runs: `<qid> Q0 <url> <rank> <score> <run name>` per line.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, Write},
+};
+
+use anyhow::anyhow;
+
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topic {
+    pub qid: String,
+    pub query: String,
+}
+
+pub fn read_topics<R: BufRead>(reader: R) -> Result<Vec<Topic>> {
+    let mut topics = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (qid, query) = line
+            .split_once('\t')
+            .or_else(|| line.split_once(' '))
+            .ok_or_else(|| anyhow!("invalid topic: {line}"))?;
+
+        topics.push(Topic {
+            qid: qid.trim().to_string(),
+            query: query.trim().to_string(),
+        });
+    }
+
+    Ok(topics)
+}
+
+/// Relevance judgements for each query.
+#[derive(Debug, Default)]
+pub struct Qrels {
+    judgements: HashMap<String, HashMap<String, u32>>,
+}
+
+impl Qrels {
+    pub fn read<R: BufRead>(reader: R) -> Result<Self> {
+        let mut qrels = Self::default();
+
+        for line in reader.lines() {
+            let line = line?;
+            let parts: Vec<_> = line.split_whitespace().collect();
+
+            if parts.is_empty() {
+                continue;
+            }
+
+            let [qid, _, url, relevance] = parts[..] else {
+                return Err(anyhow!("invalid qrel: {line}"));
+            };
+
+            // negative judgements (e.g. spam) count as not relevant
+            let relevance = relevance.parse::<i64>()?.max(0) as u32;
+
+            qrels
+                .judgements
+                .entry(qid.to_string())
+                .or_default()
+                .insert(url.to_string(), relevance);
+        }
+
+        Ok(qrels)
+    }
+
+    fn relevance(&self, qid: &str, url: &str) -> u32 {
+        self.judgements
+            .get(qid)
+            .and_then(|judgements| judgements.get(url))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Normalized discounted cumulative gain of the top `k` urls, with the relevance as gain.
+    pub fn ndcg(&self, qid: &str, urls: &[String], k: usize) -> f64 {
+        let dcg = |relevances: &mut dyn Iterator<Item = u32>| -> f64 {
+            relevances
+                .take(k)
+                .enumerate()
+                .map(|(rank, rel)| rel as f64 / (rank as f64 + 2.0).log2())
+                .sum()
+        };
+
+        let mut ideal: Vec<u32> = self
+            .judgements
+            .get(qid)
+            .map(|judgements| judgements.values().copied().collect())
+            .unwrap_or_default();
+        ideal.sort_unstable_by(|a, b| b.cmp(a));
+
+        let ideal_dcg = dcg(&mut ideal.into_iter());
+
+        if ideal_dcg == 0.0 {
+            return 0.0;
+        }
+
+        dcg(&mut urls.iter().map(|url| self.relevance(qid, url))) / ideal_dcg
+    }
+
+    /// Reciprocal rank of the first relevant url among the top `k`.
+    pub fn reciprocal_rank(&self, qid: &str, urls: &[String], k: usize) -> f64 {
+        urls.iter()
+            .take(k)
+            .position(|url| self.relevance(qid, url) > 0)
+            .map(|rank| 1.0 / (rank as f64 + 1.0))
+            .unwrap_or_default()
+    }
+}
+
+/// The ranked urls for each query.
+#[derive(Debug, Default)]
+pub struct Run {
+    pub name: String,
+    pub results: BTreeMap<String, Vec<(String, f64)>>,
+}
+
+impl Run {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            results: BTreeMap::new(),
+        }
+    }
+
+    pub fn insert(&mut self, qid: String, results: Vec<(String, f64)>) {
+        self.results.insert(qid, results);
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<()> {
+        for (qid, results) in &self.results {
+            for (rank, (url, score)) in results.iter().enumerate() {
+                writeln!(writer, "{qid} Q0 {url} {} {score} {}", rank + 1, self.name)?;
+            }
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn urls(&self, qid: &str) -> Vec<String> {
+        self.results
+            .get(qid)
+            .map(|results| results.iter().map(|(url, _)| url.clone()).collect())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub num_queries: usize,
+    pub ndcg: f64,
+    pub mrr: f64,
+}
+
+/// Mean metrics over the judged queries. Queries without results count as 0.
+pub fn evaluate(run: &Run, qrels: &Qrels, k: usize) -> Metrics {
+    let qids: Vec<_> = qrels.judgements.keys().collect();
+
+    if qids.is_empty() {
+        return Metrics {
+            num_queries: 0,
+            ndcg: 0.0,
+            mrr: 0.0,
+        };
+    }
+
+    let mut ndcg = 0.0;
+    let mut mrr = 0.0;
+
+    for qid in &qids {
+        let urls = run.urls(qid);
+
+        ndcg += qrels.ndcg(qid, &urls, k);
+        mrr += qrels.reciprocal_rank(qid, &urls, k);
+    }
+
+    Metrics {
+        num_queries: qids.len(),
+        ndcg: ndcg / qids.len() as f64,
+        mrr: mrr / qids.len() as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_topics() {
+        let topics = read_topics("1\thello world\n\n2 rust book\n".as_bytes()).unwrap();
+
+        assert_eq!(
+            topics,
+            vec![
+                Topic {
+                    qid: "1".to_string(),
+                    query: "hello world".to_string()
+                },
+                Topic {
+                    qid: "2".to_string(),
+                    query: "rust book".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn metrics() {
+        let qrels = Qrels::read(
+            "1 0 https://a.com/ 2\n1 0 https://b.com/ 1\n1 0 https://c.com/ 0\n2 0 https://d.com/ -1\n"
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let perfect = urls(&["https://a.com/", "https://b.com/"]);
+        assert!((qrels.ndcg("1", &perfect, 10) - 1.0).abs() < 1e-9);
+        assert_eq!(qrels.reciprocal_rank("1", &perfect, 10), 1.0);
+
+        let swapped = urls(&["https://c.com/", "https://b.com/", "https://a.com/"]);
+        let expected = (1.0 / 3f64.log2() + 2.0 / 4f64.log2()) / (2.0 + 1.0 / 3f64.log2());
+        assert!((qrels.ndcg("1", &swapped, 10) - expected).abs() < 1e-9);
+        assert_eq!(qrels.reciprocal_rank("1", &swapped, 10), 0.5);
+        assert_eq!(qrels.reciprocal_rank("1", &swapped, 1), 0.0);
+
+        // no relevant documents
+        assert_eq!(qrels.ndcg("2", &urls(&["https://d.com/"]), 10), 0.0);
+
+        let mut run = Run::new("test".to_string());
+        run.insert("1".to_string(), vec![("https://a.com/".to_string(), 1.0)]);
+
+        let metrics = evaluate(&run, &qrels, 10);
+        assert_eq!(metrics.num_queries, 2);
+        assert_eq!(metrics.mrr, 0.5);
+    }
+
+    #[test]
+    fn write_run() {
+        let mut run = Run::new("stract".to_string());
+        run.insert(
+            "1".to_string(),
+            vec![
+                ("https://a.com/".to_string(), 2.5),
+                ("https://b.com/".to_string(), 1.0),
+            ],
+        );
+
+        let mut out = Vec::new();
+        run.write(&mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "1 Q0 https://a.com/ 1 2.5 stract\n1 Q0 https://b.com/ 2 1 stract\n"
+        );
+    }
+}
@@ -22,6 +22,7 @@ use std::fs::File;
 use std::io::{BufRead, BufReader, Cursor, Read, Seek, Write};
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Mutex;
 use std::thread::sleep;
 use std::time::Duration;
 
@@ -34,8 +35,17 @@ use proptest_derive::Arbitrary;
 
 use tracing::{debug, trace};
 
+/// The name of the single warc "file" when reading from [`WarcSource::Stdin`].
+pub const STDIN_PATH: &str = "stdin";
+
+enum WarcData {
+    Bytes(Vec<u8>),
+    /// The records can only be read once from a stream.
+    Stream(Mutex<Option<Box<dyn Read + Send>>>),
+}
+
 pub struct WarcFile {
-    bytes: Vec<u8>,
+    data: WarcData,
 }
 
 fn rtrim(s: &mut String) {
@@ -64,7 +74,17 @@ fn decode_string(raw: &[u8]) -> String {
 
 impl WarcFile {
     pub fn new(bytes: Vec<u8>) -> Self {
-        Self { bytes }
+        Self {
+            data: WarcData::Bytes(bytes),
+        }
+    }
+
+    /// A warc file that is read as the records are iterated.
+    /// The records can only be iterated once.
+    pub fn stream<R: Read + Send + 'static>(reader: R) -> Self {
+        Self {
+            data: WarcData::Stream(Mutex::new(Some(Box::new(reader)))),
+        }
     }
 
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -77,14 +97,30 @@ impl WarcFile {
         Ok(Self::new(bytes))
     }
 
-    pub fn records(&self) -> RecordIterator<&[u8]> {
+    pub fn records(&self) -> RecordIterator<Box<dyn Read + Send + '_>> {
+        let reader: Box<dyn Read + Send + '_> = match &self.data {
+            WarcData::Bytes(bytes) => Box::new(&bytes[..]),
+            WarcData::Stream(stream) => match stream.lock().unwrap().take() {
+                Some(reader) => reader,
+                None => {
+                    debug!("warc stream has already been read");
+                    Box::new(std::io::empty())
+                }
+            },
+        };
+
         RecordIterator {
-            reader: BufReader::new(MultiGzDecoder::new(&self.bytes[..])),
+            reader: BufReader::new(MultiGzDecoder::new(reader)),
             num_reads: 0,
         }
     }
 
     pub(crate) fn download(source: &WarcSource, warc_path: &str) -> Result<Self> {
+        // the stream can't be retried, so it is never buffered
+        if let WarcSource::Stdin = source {
+            return Ok(Self::stream(std::io::stdin()));
+        }
+
         let mut cursor = Cursor::new(Vec::new());
         Self::download_into_buf(source, warc_path, &mut cursor)?;
         cursor.rewind()?;
@@ -112,6 +148,11 @@ impl WarcFile {
                     WarcFile::load_from_folder(warc_path, &config.folder, buf)
                 }
                 WarcSource::S3(config) => WarcFile::download_from_s3(warc_path, &config, buf),
+                WarcSource::Stdin => {
+                    buf.rewind()?;
+                    std::io::copy(&mut std::io::stdin(), buf)?;
+                    return Ok(());
+                }
             };
 
             if res.is_ok() {
@@ -302,12 +343,21 @@ impl Response {
 pub struct Metadata {
     // fetchTimeMs
     pub fetch_time_ms: u64,
+    // redirect, one for each url that redirected to the url of the record, in order
+    #[cfg_attr(
+        test,
+        proptest(strategy = "proptest::collection::vec(\"https://[a-z]{1,10}/\", 0..3)")
+    )]
+    pub redirects: Vec<String>,
 }
 
 impl Metadata {
     fn from_raw(record: RawWarcRecord) -> Result<Self> {
         let r = BufReader::new(&record.content[..]);
 
+        let mut fetch_time_ms = None;
+        let mut redirects = Vec::new();
+
         for line in r.lines() {
             let mut line = line?;
             if let Some(semi) = line.find(':') {
@@ -315,13 +365,20 @@ impl Metadata {
                 line.pop(); // remove colon
                 let key = line;
                 if key == "fetchTimeMs" {
-                    let fetch_time_ms = value.parse::<u64>()?;
-                    return Ok(Self { fetch_time_ms });
+                    fetch_time_ms = Some(value.parse::<u64>()?);
+                } else if key == "redirect" {
+                    redirects.push(value);
                 }
             }
         }
 
-        Err(Error::WarcParse("Failed to parse metadata".to_string()).into())
+        match fetch_time_ms {
+            Some(fetch_time_ms) => Ok(Self {
+                fetch_time_ms,
+                redirects,
+            }),
+            None => Err(Error::WarcParse("Failed to parse metadata".to_string()).into()),
+        }
     }
 }
 
@@ -631,7 +688,10 @@ impl WarcWriter {
         self.writer
             .write_all("WARC-Type: metadata\r\n".as_bytes())?;
 
-        let body = format!("fetchTimeMs: {}", record.metadata.fetch_time_ms);
+        let mut body = format!("fetchTimeMs: {}", record.metadata.fetch_time_ms);
+        for redirect in &record.metadata.redirects {
+            body.push_str(&format!("\r\nredirect: {redirect}"));
+        }
         let content_len = body.len();
 
         self.writer
@@ -751,6 +811,7 @@ mod tests {
             },
             metadata: Metadata {
                 fetch_time_ms: 1337,
+                redirects: Vec::new(),
             },
         };
         writer.write(&record1).unwrap();
@@ -765,6 +826,7 @@ mod tests {
             },
             metadata: Metadata {
                 fetch_time_ms: 4242,
+                redirects: vec!["http://b.com".to_string(), "https://www.b.com".to_string()],
             },
         };
         writer.write(&record2).unwrap();
@@ -786,6 +848,38 @@ mod tests {
         assert_eq!(records[1].metadata.fetch_time_ms, 4242);
     }
 
+    #[test]
+    fn concatenated_stream() {
+        let mut bytes = Vec::new();
+
+        for url in ["https://a.com", "https://b.com"] {
+            let mut writer = WarcWriter::new();
+            writer
+                .write(&WarcRecord {
+                    request: Request {
+                        url: url.to_string(),
+                    },
+                    response: Response {
+                        body: "body".to_string(),
+                        payload_type: Some(PayloadType::Html),
+                    },
+                    metadata: Metadata {
+                        fetch_time_ms: 0,
+                        redirects: Vec::new(),
+                    },
+                })
+                .unwrap();
+
+            bytes.extend(writer.finish().unwrap());
+        }
+
+        let file = WarcFile::stream(Cursor::new(bytes));
+        let urls: Vec<_> = file.records().map(|res| res.unwrap().request.url).collect();
+
+        assert_eq!(urls, vec!["https://a.com", "https://b.com"]);
+        assert_eq!(file.records().count(), 0);
+    }
+
     #[test]
     fn writer_utf8() {
         let utf8 = "🦀";
@@ -799,7 +893,10 @@ mod tests {
                 body: utf8.to_string(),
                 payload_type: Some(PayloadType::Html),
             },
-            metadata: Metadata { fetch_time_ms: 0 },
+            metadata: Metadata {
+                fetch_time_ms: 0,
+                redirects: Vec::new(),
+            },
         };
         writer.write(&record).unwrap();
 
@@ -831,7 +928,10 @@ mod tests {
                 body: body.to_string(),
                 payload_type: Some(PayloadType::Html),
             },
-            metadata: Metadata { fetch_time_ms: 0 },
+            metadata: Metadata {
+                fetch_time_ms: 0,
+                redirects: Vec::new(),
+            },
         };
         writer.write(&record).unwrap();
 
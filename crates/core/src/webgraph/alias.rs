@@ -0,0 +1,242 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Nodes that are known to be the same page under a different url, e.g. because
+//! the page redirects or has a canonical link to another url.
+//!
+//! The edges are stored under the node they were found for. Edge queries for any
+//! node in a group of aliases return the edges of the whole group, with the aliases
+//! replaced by their canonical node.
+
+use std::path::{Path, PathBuf};
+
+use super::NodeID;
+
+const ALIASES_FOLDER: &str = "aliases";
+
+/// The aliases are stored on disk, as a graph can have an alias for every page.
+/// Inserted aliases are visible to lookups before they are committed.
+pub struct NodeAliases {
+    /// The canonical node of each alias.
+    canonical: speedy_kv::Db<NodeID, NodeID>,
+    /// The aliases of each canonical node.
+    aliases: speedy_kv::Db<NodeID, Vec<NodeID>>,
+}
+
+fn get<V: bincode::Decode>(db: &speedy_kv::Db<NodeID, V>, key: &NodeID) -> Option<V> {
+    let key = bincode::encode_to_vec(key, bincode::config::standard()).unwrap();
+    let value = db.get_raw_with_live(key.as_slice())?;
+
+    bincode::decode_from_slice(value.as_bytes(), bincode::config::standard())
+        .ok()
+        .map(|(value, _)| value)
+}
+
+impl NodeAliases {
+    pub fn path<P: AsRef<Path>>(graph_path: P) -> PathBuf {
+        graph_path.as_ref().join(ALIASES_FOLDER)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            canonical: speedy_kv::Db::open_or_create(path.as_ref().join("canonical")).unwrap(),
+            aliases: speedy_kv::Db::open_or_create(path.as_ref().join("aliases")).unwrap(),
+        }
+    }
+
+    /// Write the inserted aliases to a new segment. Committed segments are never
+    /// changed, so snapshots that hard-link them are not affected.
+    pub fn commit(&mut self) {
+        self.canonical.commit().unwrap();
+        self.aliases.commit().unwrap();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.canonical.is_empty() && self.canonical.uncommitted_inserts() == 0
+    }
+
+    /// Record that `alias` is the same page as `canonical`. Aliases of aliases are
+    /// resolved, so every alias points directly to its canonical node. An alias keeps
+    /// the first canonical node it was inserted with.
+    pub fn insert(&mut self, alias: NodeID, canonical: NodeID) {
+        let canonical = self.resolve(&canonical);
+
+        if alias == canonical || get(&self.canonical, &alias).is_some() {
+            return;
+        }
+
+        let mut moved = get(&self.aliases, &alias).unwrap_or_default();
+        if !moved.is_empty() {
+            // the newest value of a key is the one that is read
+            self.aliases.insert(alias, Vec::new()).unwrap();
+        }
+        moved.push(alias);
+
+        for node in &moved {
+            self.canonical.insert(*node, canonical).unwrap();
+        }
+
+        let mut group = get(&self.aliases, &canonical).unwrap_or_default();
+        group.extend(moved);
+        self.aliases.insert(canonical, group).unwrap();
+    }
+
+    /// The canonical node of `node`, or the node itself if it is not an alias.
+    pub fn resolve(&self, node: &NodeID) -> NodeID {
+        get(&self.canonical, node).unwrap_or(*node)
+    }
+
+    /// All nodes that are the same page as `node`, starting with the canonical node.
+    pub fn group(&self, node: &NodeID) -> Vec<NodeID> {
+        let canonical = self.resolve(node);
+
+        std::iter::once(canonical)
+            .chain(get(&self.aliases, &canonical).unwrap_or_default())
+            .collect()
+    }
+
+    /// Insert the aliases of `other` and commit them.
+    pub fn merge(&mut self, other: NodeAliases) {
+        for (alias, canonical) in other.canonical.sorted_iter() {
+            self.insert(alias, canonical);
+        }
+
+        self.commit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{Compression, EdgeLimit, Node, Webgraph, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    fn writer() -> WebgraphWriter {
+        WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        )
+    }
+
+    #[test]
+    fn aliases_point_to_canonical() {
+        let (a, b, c, d) = (
+            NodeID::from(1_u64),
+            NodeID::from(2_u64),
+            NodeID::from(3_u64),
+            NodeID::from(4_u64),
+        );
+
+        let path = crate::gen_temp_path();
+        let mut aliases = NodeAliases::open(&path);
+        assert!(aliases.is_empty());
+
+        aliases.insert(b, a);
+        aliases.insert(c, b);
+        aliases.insert(a, a);
+
+        assert_eq!(aliases.resolve(&c), a);
+        assert_eq!(aliases.group(&c), vec![a, b, c]);
+        aliases.commit();
+
+        // `a` becomes an alias of `d`, and so do its aliases
+        aliases.insert(a, d);
+        assert_eq!(aliases.resolve(&b), d);
+        assert_eq!(aliases.group(&a).len(), 4);
+        assert_eq!(aliases.group(&a)[0], d);
+
+        // no cycles
+        aliases.insert(d, c);
+        assert_eq!(aliases.resolve(&d), d);
+
+        aliases.commit();
+        drop(aliases);
+
+        let aliases = NodeAliases::open(&path);
+        assert_eq!(aliases.resolve(&b), d);
+        assert_eq!(aliases.group(&a)[0], d);
+        assert_eq!(aliases.group(&d).len(), 4);
+        assert!(NodeAliases::open(crate::gen_temp_path()).is_empty());
+    }
+
+    #[test]
+    fn queries_include_edges_of_aliases() {
+        let page = Node::from("https://example.com/page");
+        let alias = Node::from("https://example.com/page?ref=1");
+
+        let mut a = writer();
+        a.insert(
+            Node::from("A"),
+            alias.clone(),
+            String::new(),
+            RelFlags::default(),
+        );
+        a.insert(
+            alias.clone(),
+            page.clone(),
+            String::new(),
+            RelFlags::default(),
+        );
+        a.insert_alias(alias.clone(), page.clone());
+        let mut graph = a.finalize();
+
+        let mut b = writer();
+        b.insert(
+            Node::from("B"),
+            page.clone(),
+            String::new(),
+            RelFlags::default(),
+        );
+        b.insert(
+            Node::from("A"),
+            page.clone(),
+            String::new(),
+            RelFlags::default(),
+        );
+        graph.merge(b.finalize()).unwrap();
+
+        let expected = vec![Node::from("A").id(), Node::from("B").id()];
+
+        for node in [&page, &alias] {
+            let mut from: Vec<_> = graph
+                .raw_ingoing_edges(&node.id(), EdgeLimit::Unlimited)
+                .into_iter()
+                .map(|edge| {
+                    assert_eq!(edge.to, page.id());
+                    edge.from
+                })
+                .collect();
+            from.sort();
+
+            assert_eq!(from, expected);
+        }
+
+        let outgoing = graph.raw_outgoing_edges(&Node::from("A").id(), EdgeLimit::Unlimited);
+        assert_eq!(outgoing.len(), 1);
+        assert_eq!(outgoing[0].to, page.id());
+        assert_eq!(graph.canonical_node(&alias.id()), page.id());
+
+        let path = graph.path.clone();
+        drop(graph);
+        let graph = Webgraph::builder(&path).single_threaded().open();
+        assert_eq!(graph.canonical_node(&alias.id()), page.id());
+    }
+}
@@ -0,0 +1,198 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Export of the edges of a graph as Arrow record batches, which can be
+//! loaded directly into Polars or pandas.
+
+use std::{fs::File, path::Path, sync::Arc};
+
+use arrow::{
+    array::{ArrayRef, Float64Builder, StringBuilder, UInt32Builder},
+    datatypes::{DataType, Field, Schema, SchemaRef},
+    record_batch::RecordBatch,
+};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+use super::{export::EdgeRow, Edge, Webgraph};
+use crate::Result;
+
+fn edge_batch(rows: &[EdgeRow]) -> Result<RecordBatch> {
+    let mut from = StringBuilder::new();
+    let mut to = StringBuilder::new();
+    let mut label = StringBuilder::new();
+    let mut rel = UInt32Builder::new();
+    let mut weight = Float64Builder::new();
+
+    for row in rows {
+        from.append_value(row.from.as_str());
+        to.append_value(row.to.as_str());
+        label.append_value(&row.label);
+        rel.append_value(row.rel);
+        weight.append_value(row.weight);
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(from.finish()),
+        Arc::new(to.finish()),
+        Arc::new(label.finish()),
+        Arc::new(rel.finish()),
+        Arc::new(weight.finish()),
+    ];
+
+    Ok(RecordBatch::try_new(Webgraph::arrow_schema(), columns)?)
+}
+
+impl Webgraph {
+    /// The schema of the batches from [`Webgraph::to_arrow_batches`]. `rel` holds the
+    /// bits of the [`RelFlags`](crate::webpage::html::links::RelFlags) of the edge and
+    /// `weight` the number of links the edge represents.
+    pub fn arrow_schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("from", DataType::Utf8, false),
+            Field::new("to", DataType::Utf8, false),
+            Field::new("label", DataType::Utf8, false),
+            Field::new("rel", DataType::UInt32, false),
+            Field::new("weight", DataType::Float64, false),
+        ]))
+    }
+
+    /// All edges of the graph as record batches of at most `chunk_size` edges.
+    /// The batches are built lazily, so only one batch is in memory at a time.
+    pub fn to_arrow_batches(
+        &self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Result<RecordBatch>> + '_ {
+        let chunk_size = chunk_size.max(1);
+        let tombstones = &self.meta.tombstones;
+
+        let mut rows = self
+            .segments
+            .iter()
+            .flat_map(|segment| segment.edges_with_label())
+            .map(Edge::from)
+            .filter(move |edge| !tombstones.is_edge_deleted(&edge.from, &edge.to))
+            .filter_map(|edge| EdgeRow::new(self, edge));
+
+        std::iter::from_fn(move || {
+            let chunk: Vec<_> = rows.by_ref().take(chunk_size).collect();
+
+            if chunk.is_empty() {
+                None
+            } else {
+                Some(edge_batch(&chunk))
+            }
+        })
+    }
+
+    /// Write all edges of the graph to a parquet file with the schema from [`Webgraph::arrow_schema`].
+    /// The edges are read in parallel and written in batches of `chunk_size` edges.
+    pub fn export_parquet<P: AsRef<Path>>(&self, path: P, chunk_size: usize) -> Result<()> {
+        let chunk_size = chunk_size.max(1);
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer =
+            ArrowWriter::try_new(File::create(path)?, Self::arrow_schema(), Some(props))?;
+
+        let mut chunk = Vec::with_capacity(chunk_size);
+        self.for_each_edge_row(|row| {
+            chunk.push(row);
+
+            if chunk.len() >= chunk_size {
+                writer.write(&edge_batch(&chunk)?)?;
+                chunk.clear();
+            }
+
+            Ok(())
+        })?;
+
+        if !chunk.is_empty() {
+            writer.write(&edge_batch(&chunk)?)?;
+        }
+
+        writer.close()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+    use crate::webgraph::tests::{test_edges, test_graph};
+
+    #[test]
+    fn edges_as_batches() {
+        let graph = test_graph();
+        let batches: Vec<_> = graph.to_arrow_batches(2).collect::<Result<_>>().unwrap();
+
+        assert_eq!(batches.len(), 3);
+        assert!(batches.iter().all(|batch| batch.num_rows() <= 2));
+        assert_eq!(
+            batches.iter().map(|batch| batch.num_rows()).sum::<usize>(),
+            test_edges().len()
+        );
+
+        let mut edges: Vec<_> = batches
+            .iter()
+            .flat_map(|batch| {
+                let from = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap();
+                let to = batch
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap();
+
+                (0..batch.num_rows())
+                    .map(|i| (from.value(i).to_string(), to.value(i).to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        edges.sort();
+
+        let mut expected: Vec<_> = test_edges()
+            .into_iter()
+            .map(|(from, to, _)| (from.as_str().to_string(), to.as_str().to_string()))
+            .collect();
+        expected.sort();
+
+        assert_eq!(edges, expected);
+    }
+
+    #[test]
+    fn parquet() {
+        let graph = test_graph();
+        let path = crate::gen_temp_path().join("edges.parquet");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        graph.export_parquet(&path, 1024).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let num_rows: usize = reader.map(|batch| batch.unwrap().num_rows()).sum();
+
+        assert_eq!(num_rows, test_edges().len());
+    }
+}
@@ -0,0 +1,300 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A cache of decoded edge and label blocks that is shared by the segments of one or
+//! more graphs.
+//!
+//! The page cache only keeps the compressed blocks, so every query still decompresses
+//! the blocks it reads. The cache keeps the decoded blocks of the most recently read
+//! nodes within a memory budget, and evicts the least recently used blocks when the
+//! budget is exceeded.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use super::StoredEdge;
+use crate::metrics::{Counter, Label, PrometheusRegistry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BlockKind {
+    Edges,
+    Labels,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BlockKey {
+    store: u64,
+    kind: BlockKind,
+    offset: u64,
+}
+
+#[derive(Clone)]
+enum Block {
+    Edges(Arc<[StoredEdge]>),
+    Labels(Arc<[String]>),
+}
+
+impl Block {
+    /// Approximate number of bytes used by the decoded block.
+    fn size(&self) -> u64 {
+        let bytes = match self {
+            Block::Edges(edges) => std::mem::size_of_val(edges.as_ref()),
+            Block::Labels(labels) => labels
+                .iter()
+                .map(|label| std::mem::size_of::<String>() + label.len())
+                .sum(),
+        };
+
+        bytes as u64
+    }
+}
+
+struct CachedBlock {
+    block: Block,
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct State {
+    blocks: HashMap<BlockKey, CachedBlock>,
+    lru: BTreeMap<u64, BlockKey>,
+    clock: u64,
+    size: u64,
+}
+
+impl State {
+    fn get(&mut self, key: &BlockKey) -> Option<Block> {
+        self.clock += 1;
+
+        let block = self.blocks.get_mut(key)?;
+        self.lru.remove(&block.last_used);
+        block.last_used = self.clock;
+        self.lru.insert(self.clock, *key);
+
+        Some(block.block.clone())
+    }
+
+    fn insert(&mut self, key: BlockKey, block: Block) {
+        self.clock += 1;
+        let size = block.size();
+
+        if let Some(old) = self.blocks.insert(
+            key,
+            CachedBlock {
+                block,
+                size,
+                last_used: self.clock,
+            },
+        ) {
+            self.lru.remove(&old.last_used);
+            self.size -= old.size;
+        }
+
+        self.lru.insert(self.clock, key);
+        self.size += size;
+    }
+
+    fn evict(&mut self, capacity_bytes: u64) {
+        while self.size > capacity_bytes {
+            let Some((_, key)) = self.lru.pop_first() else {
+                break;
+            };
+
+            if let Some(block) = self.blocks.remove(&key) {
+                self.size -= block.size;
+            }
+        }
+    }
+}
+
+/// Decoded blocks of the edge stores, bounded by `capacity_bytes`.
+pub struct BlockCache {
+    capacity_bytes: u64,
+    state: Mutex<State>,
+    hits: Counter,
+    misses: Counter,
+}
+
+impl BlockCache {
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            capacity_bytes,
+            state: Mutex::new(State::default()),
+            hits: Counter::default(),
+            misses: Counter::default(),
+        }
+    }
+
+    /// Approximate size of the cached blocks.
+    pub fn size(&self) -> u64 {
+        self.state.lock().unwrap().size
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.get()
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.get()
+    }
+
+    /// Fraction of the block reads that were served from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        let hits = self.hits() as f64;
+        let total = hits + self.misses() as f64;
+
+        if total == 0.0 {
+            0.0
+        } else {
+            hits / total
+        }
+    }
+
+    /// Register the hits and misses in a group named `{prefix}_block_cache_lookups`.
+    pub fn register_metrics(
+        &self,
+        registry: &mut PrometheusRegistry,
+        prefix: &str,
+    ) -> Result<(), crate::metrics::Error> {
+        let group = registry.new_group(
+            format!("{prefix}_block_cache_lookups"),
+            Some("Total number of lookups in the webgraph block cache.".to_string()),
+        )?;
+
+        group.register(
+            self.hits.clone(),
+            vec![Label {
+                key: "result".to_string(),
+                val: "hit".to_string(),
+            }],
+        );
+        group.register(
+            self.misses.clone(),
+            vec![Label {
+                key: "result".to_string(),
+                val: "miss".to_string(),
+            }],
+        );
+
+        Ok(())
+    }
+
+    /// The cached block, or the block from `load` which is then cached. The lock is not
+    /// held while the block is loaded, so two threads can load the same block at once.
+    fn get_or_load(&self, key: BlockKey, load: impl FnOnce() -> Block) -> Block {
+        if let Some(block) = self.state.lock().unwrap().get(&key) {
+            self.hits.inc();
+            return block;
+        }
+
+        self.misses.inc();
+        let block = load();
+
+        let mut state = self.state.lock().unwrap();
+        state.insert(key, block.clone());
+        state.evict(self.capacity_bytes);
+
+        block
+    }
+}
+
+/// The part of a [`BlockCache`] that holds the blocks of one edge store.
+#[derive(Clone)]
+pub(super) struct StoreBlockCache {
+    cache: Arc<BlockCache>,
+    store: u64,
+}
+
+impl StoreBlockCache {
+    /// Segment ids are never reused, so blocks of segments that have been merged
+    /// away are never read again and are evicted over time.
+    pub fn new(cache: Arc<BlockCache>, segment_id: &str, reversed: bool) -> Self {
+        let store = xxhash_rust::xxh3::xxh3_64(format!("{segment_id}/{reversed}").as_bytes());
+
+        Self { cache, store }
+    }
+
+    fn key(&self, kind: BlockKind, offset: usize) -> BlockKey {
+        BlockKey {
+            store: self.store,
+            kind,
+            offset: offset as u64,
+        }
+    }
+
+    /// The edges of the block at byte `offset` in the edge file of the store.
+    pub fn edges(
+        &self,
+        offset: usize,
+        load: impl FnOnce() -> Vec<StoredEdge>,
+    ) -> Arc<[StoredEdge]> {
+        let key = self.key(BlockKind::Edges, offset);
+
+        match self.cache.get_or_load(key, || Block::Edges(load().into())) {
+            Block::Edges(edges) => edges,
+            Block::Labels(_) => unreachable!("edge blocks are cached with their own key"),
+        }
+    }
+
+    /// The labels of the block at byte `offset` in the label file of the store.
+    pub fn labels(&self, offset: usize, load: impl FnOnce() -> Vec<String>) -> Arc<[String]> {
+        let key = self.key(BlockKind::Labels, offset);
+
+        match self.cache.get_or_load(key, || Block::Labels(load().into())) {
+            Block::Labels(labels) => labels,
+            Block::Edges(_) => unreachable!("label blocks are cached with their own key"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let labels = |label: &str| vec![label.to_string()];
+        let block_size = Block::Labels(labels("a").into()).size();
+
+        let cache = Arc::new(BlockCache::new(2 * block_size));
+        let store = StoreBlockCache::new(Arc::clone(&cache), "segment", false);
+        let reversed = StoreBlockCache::new(Arc::clone(&cache), "segment", true);
+
+        assert_eq!(store.labels(0, || labels("a")).as_ref(), labels("a"));
+        assert_eq!(reversed.labels(0, || labels("b")).as_ref(), labels("b"));
+        assert_eq!(cache.misses(), 2);
+
+        assert_eq!(
+            store
+                .labels(0, || panic!("block should be cached"))
+                .as_ref(),
+            labels("a")
+        );
+        assert_eq!(cache.hits(), 1);
+
+        // the block of the reversed store is the least recently used
+        store.labels(8, || labels("c"));
+        assert_eq!(cache.size(), 2 * block_size);
+        assert_eq!(reversed.labels(0, || labels("d")).as_ref(), labels("d"));
+
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(cache.misses(), 4);
+        assert_eq!(cache.hit_rate(), 0.2);
+    }
+}
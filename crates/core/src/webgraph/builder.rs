@@ -14,15 +14,22 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
-use crate::executor::Executor;
+use crate::{executor::Executor, Result};
 
-use super::Webgraph;
+use super::{
+    snapshot, AdjacencyCodec, BlockCache, Codec, Compression, DefaultNormalization,
+    NormalizationPolicy, Webgraph,
+};
 
 pub struct WebgraphBuilder {
     path: Box<Path>,
     executor: Executor,
+    compression: Compression,
+    block_cache: Option<Arc<BlockCache>>,
+    node_cache: Option<usize>,
+    normalization: Arc<dyn NormalizationPolicy>,
 }
 
 impl WebgraphBuilder {
@@ -30,6 +37,10 @@ impl WebgraphBuilder {
         Self {
             path: path.as_ref().into(),
             executor: Executor::multi_thread("webgraph").unwrap(),
+            compression: Compression::default(),
+            block_cache: None,
+            node_cache: None,
+            normalization: Arc::new(DefaultNormalization),
         }
     }
 
@@ -38,7 +49,63 @@ impl WebgraphBuilder {
         self
     }
 
+    /// The compression of the segments that are written when segments of the graph are merged.
+    /// Existing segments are read with the compression they were written with.
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn adjacency_compression(mut self, codec: AdjacencyCodec) -> Self {
+        self.compression.adjacency = codec;
+        self
+    }
+
+    pub fn label_compression(mut self, codec: Codec) -> Self {
+        self.compression.labels = codec;
+        self
+    }
+
+    /// Cache the decoded blocks that are read from the segments. The cache can be
+    /// shared between graphs, so they are all kept within the same memory budget.
+    pub fn block_cache(mut self, cache: Arc<BlockCache>) -> Self {
+        self.block_cache = Some(cache);
+        self
+    }
+
+    /// Keep the `capacity` most recently looked up nodes of the id2node database in memory.
+    pub fn node_cache(mut self, capacity: usize) -> Self {
+        self.node_cache = Some(capacity);
+        self
+    }
+
+    /// How [`Webgraph::node`] turns urls into nodes. It must be the normalization the
+    /// graph was built with, or the nodes of the urls will not be found.
+    pub fn normalization(mut self, policy: Arc<dyn NormalizationPolicy>) -> Self {
+        self.normalization = policy;
+        self
+    }
+
     pub fn open(self) -> Webgraph {
-        Webgraph::open(self.path, self.executor)
+        let mut graph = Webgraph::open(
+            self.path,
+            self.executor,
+            self.compression,
+            self.block_cache,
+            self.normalization,
+        );
+
+        if let Some(capacity) = self.node_cache {
+            graph.id2node.enable_cache(capacity);
+        }
+
+        graph
+    }
+
+    /// Open the snapshot at the path of the builder, which must have been created
+    /// with [`Webgraph::snapshot`].
+    pub fn open_snapshot(self) -> Result<Webgraph> {
+        snapshot::ensure_complete(&self.path)?;
+        Ok(self.open())
     }
 }
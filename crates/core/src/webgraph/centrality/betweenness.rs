@@ -22,10 +22,80 @@ use std::collections::{HashMap, VecDeque};
 use indicatif::{ProgressBar, ProgressStyle};
 
 use crate::{
+    executor::Priority,
     intmap::IntMap,
     webgraph::{EdgeLimit, Node, NodeID, Webgraph},
 };
 
+/// Accumulated dependencies of the source `s` on every other node reachable from it,
+/// together with the largest distance from the source.
+fn dependencies(graph: &Webgraph, s: NodeID) -> (Vec<(NodeID, f64)>, i32) {
+    let mut stack = Vec::new();
+    let mut predecessors: IntMap<NodeID, Vec<NodeID>> = IntMap::new();
+
+    let mut sigma = IntMap::new();
+
+    sigma.insert(s, 1);
+
+    let mut distances = IntMap::new();
+    distances.insert(s, 0);
+
+    let mut q = VecDeque::new();
+    q.push_back(s);
+
+    while let Some(v) = q.pop_front() {
+        stack.push(v);
+        for edge in graph.raw_outgoing_edges(&v, EdgeLimit::Unlimited) {
+            let w = edge.to;
+
+            if !distances.contains_key(&w) {
+                let dist_v = distances.get(&v).unwrap();
+                q.push_back(w);
+                distances.insert(w, dist_v + 1);
+            }
+
+            if *distances.get(&w).unwrap() == distances.get(&v).unwrap() + 1 {
+                let sigma_v = *sigma.get(&v).unwrap_or(&0);
+
+                if !sigma.contains_key(&w) {
+                    sigma.insert(w, 0);
+                }
+                *sigma.get_mut(&w).unwrap() += sigma_v;
+
+                if !predecessors.contains_key(&w) {
+                    predecessors.insert(w, Vec::new());
+                }
+
+                predecessors.get_mut(&w).unwrap().push(v);
+            }
+        }
+    }
+
+    let max_dist = *distances.iter().map(|(_, dist)| dist).max().unwrap_or(&0);
+
+    let mut res = Vec::new();
+    let mut delta = IntMap::new();
+    while let Some(w) = stack.pop() {
+        if let Some(pred) = predecessors.get(&w) {
+            for v in pred {
+                let dv = delta.get(v).copied().unwrap_or(0.0);
+
+                delta.insert(
+                    *v,
+                    dv + (*sigma.get(v).unwrap() as f64 / *sigma.get(&w).unwrap() as f64)
+                        * (1.0 + delta.get(&w).unwrap_or(&0.0)),
+                );
+            }
+        }
+
+        if w != s {
+            res.push((w, *delta.get(&w).unwrap_or(&0.0)));
+        }
+    }
+
+    (res, max_dist)
+}
+
 fn calculate(graph: &Webgraph, with_progress: bool) -> (HashMap<Node, f64>, i32) {
     let mut centrality: HashMap<NodeID, f64> = HashMap::new();
     let mut n = 0;
@@ -55,66 +125,11 @@ fn calculate(graph: &Webgraph, with_progress: bool) -> (HashMap<Node, f64>, i32)
         n += 1;
         centrality.entry(s).or_default();
 
-        let mut stack = Vec::new();
-        let mut predecessors: IntMap<NodeID, Vec<NodeID>> = IntMap::new();
-
-        let mut sigma = IntMap::new();
-
-        sigma.insert(s, 1);
-
-        let mut distances = IntMap::new();
-        distances.insert(s, 0);
-
-        let mut q = VecDeque::new();
-        q.push_back(s);
-
-        while let Some(v) = q.pop_front() {
-            stack.push(v);
-            for edge in graph.raw_outgoing_edges(&v, EdgeLimit::Unlimited) {
-                let w = edge.to;
-
-                if !distances.contains_key(&w) {
-                    let dist_v = distances.get(&v).unwrap();
-                    q.push_back(w);
-                    distances.insert(w, dist_v + 1);
-                }
-
-                if *distances.get(&w).unwrap() == distances.get(&v).unwrap() + 1 {
-                    let sigma_v = *sigma.get(&v).unwrap_or(&0);
-
-                    if !sigma.contains_key(&w) {
-                        sigma.insert(w, 0);
-                    }
-                    *sigma.get_mut(&w).unwrap() += sigma_v;
-
-                    if !predecessors.contains_key(&w) {
-                        predecessors.insert(w, Vec::new());
-                    }
+        let (dependencies, dist) = dependencies(graph, s);
+        max_dist = max_dist.max(dist);
 
-                    predecessors.get_mut(&w).unwrap().push(v);
-                }
-            }
-        }
-
-        max_dist = max_dist.max(*distances.iter().map(|(_, dist)| dist).max().unwrap_or(&0));
-
-        let mut delta = IntMap::new();
-        while let Some(w) = stack.pop() {
-            if let Some(pred) = predecessors.get(&w) {
-                for v in pred {
-                    let dv = delta.get(v).copied().unwrap_or(0.0);
-
-                    delta.insert(
-                        *v,
-                        dv + (*sigma.get(v).unwrap() as f64 / *sigma.get(&w).unwrap() as f64)
-                            * (1.0 + delta.get(&w).unwrap_or(&0.0)),
-                    );
-                }
-            }
-
-            if w != s {
-                *centrality.entry(w).or_insert(0.0) += *delta.get(&w).unwrap_or(&0.0);
-            }
+        for (w, delta) in dependencies {
+            *centrality.entry(w).or_insert(0.0) += delta;
         }
     }
 
@@ -161,6 +176,95 @@ impl Betweenness {
     }
 }
 
+/// Number of pivots whose dependencies are computed in parallel before they are
+/// added to the centralities. Bounds the memory used for the dependencies.
+const PIVOT_BATCH_SIZE: usize = 1_024;
+
+/// Betweenness estimated from the shortest paths of a random sample of pivots
+/// instead of every node in the graph, as described in "Centrality Estimation in
+/// Large Networks". The dependencies of the pivots are extrapolated to all nodes with
+/// outgoing edges, so the estimate is normalized like [`Betweenness`]. The number of
+/// nodes is estimated, so only the relative scores are exact when all nodes are pivots.
+#[derive(Debug, Default)]
+pub struct ApproxBetweenness {
+    centrality: HashMap<NodeID, f64>,
+    pub num_pivots: usize,
+    pub max_dist: usize,
+}
+
+impl ApproxBetweenness {
+    pub fn calculate(graph: &Webgraph, num_pivots: usize) -> Self {
+        let pivots = graph.random_nodes_with_outgoing(num_pivots);
+        let num_nodes = graph.estimate_num_nodes().max(pivots.len());
+
+        tracing::info!(
+            "estimating betweenness from {} pivots in a graph of approximately {} nodes",
+            pivots.len(),
+            num_nodes
+        );
+
+        let mut centrality: HashMap<NodeID, f64> = HashMap::new();
+        let mut max_dist = 0;
+
+        for batch in pivots.chunks(PIVOT_BATCH_SIZE) {
+            let results = graph
+                .executor
+                .map_with_priority(
+                    Priority::Batch,
+                    |s| dependencies(graph, s),
+                    batch.iter().copied(),
+                )
+                .unwrap();
+
+            for (dependencies, dist) in results {
+                max_dist = max_dist.max(dist);
+
+                for (w, delta) in dependencies {
+                    *centrality.entry(w).or_insert(0.0) += delta;
+                }
+            }
+        }
+
+        // if fewer pivots than requested were found, every node with outgoing edges is a pivot
+        let num_sources = if pivots.len() < num_pivots {
+            pivots.len()
+        } else {
+            num_nodes
+        };
+
+        let n = num_nodes as f64;
+        let norm = (pivots.len() as f64 / num_sources.max(1) as f64) * n * (n - 1.0);
+
+        if norm > 0.0 {
+            for score in centrality.values_mut() {
+                *score /= norm;
+            }
+        }
+
+        Self {
+            centrality,
+            num_pivots: pivots.len(),
+            max_dist: max_dist.max(0) as usize,
+        }
+    }
+
+    pub fn get(&self, node: &NodeID) -> Option<f64> {
+        self.centrality.get(node).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeID, f64)> {
+        self.centrality.iter().map(|(node, score)| (node, *score))
+    }
+
+    pub fn len(&self) -> usize {
+        self.centrality.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use maplit::hashmap;
@@ -205,4 +309,22 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn approximate_with_all_pivots() {
+        let p = create_path_graph(5);
+        let centrality = ApproxBetweenness::calculate(&p, 10);
+
+        assert_eq!(centrality.num_pivots, 4);
+        assert_eq!(centrality.max_dist, 4);
+
+        let get = |n: &str| centrality.get(&Node::from(n.to_string()).id());
+
+        assert_eq!(get("0"), None);
+        assert_eq!(get("4"), Some(0.0));
+
+        let (c1, c2, c3) = (get("1").unwrap(), get("2").unwrap(), get("3").unwrap());
+        assert!((c1 - c3).abs() < 1e-9);
+        assert!((c2 / c1 - 0.2 / 0.15).abs() < 1e-9);
+    }
 }
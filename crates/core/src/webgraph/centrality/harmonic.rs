@@ -27,11 +27,11 @@ use tracing::info;
 use crate::{
     hyperloglog::HyperLogLog,
     kahan_sum::KahanSum,
-    webgraph::{EdgeLimit, NodeID, Webgraph},
+    webgraph::{EdgeFilter, EdgeLimit, NodeID, Webgraph},
     webpage::html::links::RelFlags,
 };
 
-const HYPERLOGLOG_COUNTERS: usize = 64;
+pub(super) const HYPERLOGLOG_COUNTERS: usize = 64;
 
 pub static SKIPPED_REL: once_cell::sync::Lazy<RelFlags> = once_cell::sync::Lazy::new(|| {
     RelFlags::TAG
@@ -43,6 +43,7 @@ pub static SKIPPED_REL: once_cell::sync::Lazy<RelFlags> = once_cell::sync::Lazy:
         | RelFlags::SEARCH
         | RelFlags::LINK_TAG
         | RelFlags::SCRIPT_TAG
+        | RelFlags::PARKED
 });
 
 type Counter = BTreeMap<NodeID, HyperLogLog<HYPERLOGLOG_COUNTERS>>;
@@ -71,6 +72,7 @@ fn initialize(
 
 fn update_changed_counters(
     graph: &Webgraph,
+    seen_since: Option<u32>,
     exact_changed_nodes: &mut BTreeSet<NodeID>,
     counters: &mut Counters,
     new_changed_nodes: &mut U64BloomFilter,
@@ -79,8 +81,13 @@ fn update_changed_counters(
     let has_changes = AtomicBool::new(false);
 
     exact_changed_nodes.iter().for_each(|changed_node| {
+        let filter = EdgeFilter {
+            seen_since,
+            ..EdgeFilter::all()
+        };
+
         for edge in graph
-            .raw_outgoing_edges(changed_node, EdgeLimit::Unlimited)
+            .raw_outgoing_edges_with_filter(changed_node, EdgeLimit::Unlimited, filter)
             .into_iter()
             .filter(|e| !e.rel_flags().intersects(*SKIPPED_REL))
         {
@@ -111,6 +118,7 @@ fn update_changed_counters(
 
 fn update_all_counters(
     graph: &Webgraph,
+    seen_since: Option<u32>,
     mut exact_changed_nodes: Option<&mut BTreeSet<NodeID>>,
     counters: &mut Counters,
     changed_nodes: &U64BloomFilter,
@@ -125,6 +133,7 @@ fn update_all_counters(
     graph
         .edges()
         .filter(|e| !e.rel_flags().intersects(*SKIPPED_REL))
+        .filter(|e| seen_since.map_or(true, |timestamp| e.timestamps.seen_since(timestamp)))
         .for_each(|edge| {
             if changed_nodes.contains(edge.from.as_u64()) {
                 if let (Some(counter_to), Some(counter_from)) =
@@ -208,7 +217,7 @@ impl Counters {
     }
 }
 
-fn calculate_centrality(graph: &Webgraph) -> BTreeMap<NodeID, f64> {
+fn calculate_centrality(graph: &Webgraph, seen_since: Option<u32>) -> BTreeMap<NodeID, f64> {
     let mut counters = Counters::new();
     let mut centralities: BTreeMap<NodeID, KahanSum> = BTreeMap::new();
 
@@ -242,6 +251,7 @@ fn calculate_centrality(graph: &Webgraph) -> BTreeMap<NodeID, f64> {
         {
             has_changes = update_changed_counters(
                 graph,
+                seen_since,
                 &mut exact_changed_nodes,
                 &mut counters,
                 &mut new_changed_nodes,
@@ -249,6 +259,7 @@ fn calculate_centrality(graph: &Webgraph) -> BTreeMap<NodeID, f64> {
         } else if exact_counting {
             has_changes = update_all_counters(
                 graph,
+                seen_since,
                 Some(&mut exact_changed_nodes),
                 &mut counters,
                 &changed_nodes,
@@ -257,6 +268,7 @@ fn calculate_centrality(graph: &Webgraph) -> BTreeMap<NodeID, f64> {
         } else {
             has_changes = update_all_counters(
                 graph,
+                seen_since,
                 None,
                 &mut counters,
                 &changed_nodes,
@@ -286,7 +298,13 @@ pub struct HarmonicCentrality(BTreeMap<NodeID, f64>);
 
 impl HarmonicCentrality {
     pub fn calculate(graph: &Webgraph) -> Self {
-        Self(calculate_centrality(graph))
+        Self(calculate_centrality(graph, None))
+    }
+
+    /// Only count the links that have been seen in a crawl since the unix timestamp,
+    /// so links that have disappeared from the web stop contributing to the centrality.
+    pub fn calculate_seen_since(graph: &Webgraph, timestamp: u32) -> Self {
+        Self(calculate_centrality(graph, Some(timestamp)))
     }
 
     pub fn get(&self, node: &NodeID) -> Option<f64> {
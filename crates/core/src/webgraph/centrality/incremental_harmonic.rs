@@ -0,0 +1,384 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Incremental update of the harmonic centrality after new batches have been merged
+//! into the graph.
+//!
+//! The harmonic centrality of a node only depends on the shortest paths that end in
+//! the node, so an edge that is added or removed can only change the centrality of
+//! the nodes that are reachable from it. The nodes that are at most `max_hops` hops
+//! from a dirty node (see [`DirtyNodes`]) are recomputed, and the centrality of all
+//! other nodes is kept and rescaled to the new number of nodes.
+//!
+//! The recomputed nodes use the same HyperLogLog counters as [`HarmonicCentrality`],
+//! so their centrality is comparable with the centrality of the nodes that are kept.
+//! The counters are only propagated `max_hops` steps through the part of the graph that
+//! can reach the recomputed nodes in that many hops, so paths longer than `max_hops`
+//! are not counted for the recomputed nodes. The centrality should therefore still be
+//! rebuilt from scratch from time to time.
+//!
+//! [`HarmonicCentrality`]: super::harmonic::HarmonicCentrality
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use super::harmonic::{HYPERLOGLOG_COUNTERS, SKIPPED_REL};
+use crate::{
+    hyperloglog::HyperLogLog,
+    kahan_sum::KahanSum,
+    webgraph::{DirtyNodes, EdgeFilter, EdgeLimit, NodeID, Webgraph},
+    Result,
+};
+
+const STATE_FILE: &str = "incremental_harmonic.json";
+
+/// Bookkeeping for the centralities in an output folder, so the next update knows
+/// how to rescale the stored centralities and when to rebuild them from scratch.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct IncrementalState {
+    /// Number of nodes in the graph when the centralities were last updated.
+    pub num_nodes: u64,
+    /// Number of incremental updates since the last full rebuild.
+    pub incremental_updates: usize,
+}
+
+impl IncrementalState {
+    pub fn path<P: AsRef<Path>>(output: P) -> PathBuf {
+        output.as_ref().join(STATE_FILE)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+}
+
+/// The dirty nodes and the nodes that can be reached from them in at most `max_hops` hops.
+/// Returns `None` if there are more than `max_nodes` such nodes.
+fn affected_nodes(
+    graph: &Webgraph,
+    dirty: &DirtyNodes,
+    max_hops: u8,
+    max_nodes: usize,
+) -> Option<HashSet<NodeID>> {
+    let mut affected: HashSet<NodeID> = dirty.iter().copied().collect();
+    let mut frontier: Vec<NodeID> = affected.iter().copied().collect();
+
+    for _ in 0..max_hops {
+        if frontier.is_empty() {
+            break;
+        }
+
+        let mut next = Vec::new();
+
+        for node in frontier {
+            for edge in graph.raw_outgoing_edges_with_filter(
+                &node,
+                EdgeLimit::Unlimited,
+                EdgeFilter::exclude(*SKIPPED_REL),
+            ) {
+                if affected.insert(edge.to) {
+                    next.push(edge.to);
+                }
+            }
+
+            if affected.len() > max_nodes {
+                return None;
+            }
+        }
+
+        frontier = next;
+    }
+
+    if affected.len() > max_nodes {
+        return None;
+    }
+
+    Some(affected)
+}
+
+/// The part of the graph the counters of `targets` depend on for `max_hops` steps:
+/// the nodes that can reach one of the targets in at most `max_hops` hops and the
+/// edges between them. Returns `None` if there are more than `max_nodes` such nodes.
+fn ingoing_ball(
+    graph: &Webgraph,
+    targets: &HashSet<NodeID>,
+    max_hops: u8,
+    max_nodes: usize,
+) -> Option<(HashSet<NodeID>, Vec<(NodeID, NodeID)>)> {
+    let mut nodes = targets.clone();
+    let mut edges = Vec::new();
+    let mut frontier: Vec<NodeID> = targets.iter().copied().collect();
+
+    // nodes at distance `max_hops` only need their initial counter, so their
+    // ingoing edges are never read.
+    for _ in 0..max_hops {
+        if frontier.is_empty() {
+            break;
+        }
+
+        let mut next = Vec::new();
+
+        for node in frontier {
+            for edge in graph
+                .raw_ingoing_edges(&node, EdgeLimit::Unlimited)
+                .into_iter()
+                .filter(|edge| !edge.rel_flags().intersects(*SKIPPED_REL))
+            {
+                edges.push((edge.from, edge.to));
+
+                if nodes.insert(edge.from) {
+                    next.push(edge.from);
+                }
+            }
+
+            if nodes.len() > max_nodes {
+                return None;
+            }
+        }
+
+        frontier = next;
+    }
+
+    Some((nodes, edges))
+}
+
+/// Propagate the HyperLogLog counters like [`super::harmonic::HarmonicCentrality`] for
+/// `steps` steps and return the (unnormalized) centrality of the targets.
+fn propagate(
+    nodes: &HashSet<NodeID>,
+    edges: &[(NodeID, NodeID)],
+    targets: &HashSet<NodeID>,
+    steps: u8,
+) -> HashMap<NodeID, f64> {
+    let mut old: HashMap<NodeID, HyperLogLog<HYPERLOGLOG_COUNTERS>> = nodes
+        .iter()
+        .map(|node| {
+            let mut counter = HyperLogLog::default();
+            counter.add(node.as_u64());
+            (*node, counter)
+        })
+        .collect();
+    let mut new = old.clone();
+
+    let mut sums: HashMap<NodeID, KahanSum> = targets
+        .iter()
+        .map(|node| (*node, KahanSum::default()))
+        .collect();
+
+    for t in 0..steps as u64 {
+        let mut has_changes = false;
+
+        for (from, to) in edges {
+            if let (Some(counter_to), Some(counter_from)) = (new.get_mut(to), old.get(from)) {
+                if counter_to
+                    .registers()
+                    .iter()
+                    .zip(counter_from.registers().iter())
+                    .any(|(to, from)| *from > *to)
+                {
+                    counter_to.merge(counter_from);
+                    has_changes = true;
+                }
+            }
+        }
+
+        for (node, sum) in sums.iter_mut() {
+            *sum += new[node].size().saturating_sub(old[node].size()) as f64 / (t + 1) as f64;
+        }
+
+        if !has_changes {
+            break;
+        }
+
+        old.clone_from(&new);
+    }
+
+    sums.into_iter()
+        .map(|(node, sum)| (node, f64::from(sum)))
+        .collect()
+}
+
+pub struct IncrementalHarmonic {
+    updated: BTreeMap<NodeID, f64>,
+    num_nodes: u64,
+}
+
+impl IncrementalHarmonic {
+    /// Recompute the centrality of the nodes that are affected by the dirty nodes.
+    /// Returns `None` if the update would have to read more than `max_nodes` nodes,
+    /// in which case the centrality should be rebuilt from scratch instead.
+    pub fn calculate(
+        graph: &Webgraph,
+        dirty: &DirtyNodes,
+        max_hops: u8,
+        max_nodes: usize,
+    ) -> Option<Self> {
+        let affected = affected_nodes(graph, dirty, max_hops, max_nodes)?;
+        let (nodes, edges) = ingoing_ball(graph, &affected, max_hops, max_nodes)?;
+        tracing::info!(
+            "recomputing harmonic centrality for {} nodes affected by {} dirty nodes ({} nodes read)",
+            affected.len(),
+            dirty.len(),
+            nodes.len()
+        );
+
+        let num_nodes = graph.nodes().count() as u64;
+        let norm_factor = num_nodes.saturating_sub(1) as f64;
+
+        let updated = propagate(&nodes, &edges, &affected, max_hops)
+            .into_iter()
+            .map(|(node, sum)| {
+                let centrality = sum / norm_factor;
+                (
+                    node,
+                    if centrality.is_finite() {
+                        centrality
+                    } else {
+                        0.0
+                    },
+                )
+            })
+            .collect();
+
+        Some(Self { updated, num_nodes })
+    }
+
+    /// Number of nodes in the graph the centralities were computed for.
+    pub fn num_nodes(&self) -> u64 {
+        self.num_nodes
+    }
+
+    /// Number of nodes whose centrality was recomputed.
+    pub fn len(&self) -> usize {
+        self.updated.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Apply the update to the `previous` centralities that were computed when the
+    /// graph had `previous_num_nodes` nodes. Like the full computation, nodes
+    /// without any centrality are left out.
+    pub fn apply<'a, I>(
+        &'a self,
+        previous: I,
+        previous_num_nodes: u64,
+    ) -> impl Iterator<Item = (NodeID, f64)> + 'a
+    where
+        I: Iterator<Item = (NodeID, f64)> + 'a,
+    {
+        let scale = previous_num_nodes.saturating_sub(1) as f64
+            / self.num_nodes.saturating_sub(1).max(1) as f64;
+
+        previous
+            .filter(move |(node, _)| !self.updated.contains_key(node))
+            .map(move |(node, centrality)| (node, centrality * scale))
+            .chain(self.updated.iter().map(|(node, c)| (*node, *c)))
+            .filter(|(_, centrality)| *centrality > 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{
+            centrality::harmonic::HarmonicCentrality, tests::test_graph, Compression, Node,
+            WebgraphWriter,
+        },
+        webpage::html::links::RelFlags,
+    };
+
+    fn full(graph: &Webgraph) -> BTreeMap<NodeID, f64> {
+        HarmonicCentrality::calculate(graph)
+            .iter()
+            .map(|(node, centrality)| (*node, centrality))
+            .collect()
+    }
+
+    #[test]
+    fn update_matches_full_computation() {
+        // A -> B, B -> C, A -> C, C -> A, D -> C
+        let mut graph = test_graph();
+        let previous = full(&graph);
+        let previous_num_nodes = graph.nodes().count() as u64;
+
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+        writer.insert(
+            Node::from("E"),
+            Node::from("F"),
+            String::new(),
+            RelFlags::default(),
+        );
+        writer.insert(
+            Node::from("F"),
+            Node::from("A"),
+            String::new(),
+            RelFlags::default(),
+        );
+        graph.merge(writer.finalize()).unwrap();
+
+        let dirty = graph.dirty_nodes().unwrap();
+
+        // D is the only node that cannot be reached from the new edges
+        let update = IncrementalHarmonic::calculate(&graph, &dirty, 8, usize::MAX).unwrap();
+        assert_eq!(update.len(), 5);
+
+        let updated: BTreeMap<_, _> = update
+            .apply(previous.into_iter(), previous_num_nodes)
+            .collect();
+        let expected = full(&graph);
+
+        assert_eq!(
+            updated.keys().collect::<Vec<_>>(),
+            expected.keys().collect::<Vec<_>>()
+        );
+
+        for (node, centrality) in expected {
+            assert!((updated[&node] - centrality).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn too_many_nodes() {
+        let graph = test_graph();
+
+        let mut dirty = DirtyNodes::default();
+        dirty.insert(Node::from("D").id());
+
+        // D -> C -> A -> B, and all nodes can reach them
+        assert!(IncrementalHarmonic::calculate(&graph, &dirty, 1, 4).is_some());
+        assert!(IncrementalHarmonic::calculate(&graph, &dirty, 3, 3).is_none());
+    }
+}
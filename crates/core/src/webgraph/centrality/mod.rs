@@ -16,7 +16,7 @@
 
 use std::{cmp::Reverse, fs::File, path::Path};
 
-use crate::{external_sort::ExternalSorter, SortableFloat};
+use crate::{external_sort::ExternalSorter, Result, SortableFloat};
 
 use super::{Node, NodeID};
 
@@ -24,6 +24,12 @@ pub mod approx_harmonic;
 pub mod betweenness;
 pub mod derived_harmonic;
 pub mod harmonic;
+pub mod incremental_harmonic;
+pub mod neighborhood;
+mod pagerank;
+pub mod trustrank;
+
+pub use pagerank::personalized_pagerank;
 
 #[derive(Debug, Clone, Copy)]
 pub enum TopNodes {
@@ -52,6 +58,17 @@ pub fn top_nodes(
     .collect()
 }
 
+/// Read the hosts in a seed file with one host per line.
+/// Empty lines and lines starting with `#` are skipped.
+pub fn read_seeds<P: AsRef<Path>>(path: P) -> Result<Vec<NodeID>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|host| Node::from(host).into_host().id())
+        .collect())
+}
+
 pub fn store_csv<P: AsRef<Path>>(data: Vec<(Node, f64)>, output: P) {
     let csv_file = File::options()
         .write(true)
@@ -0,0 +1,258 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Neighborhood function of each node estimated with HyperBall
+//! ("In-Core Computation of Geometric Centralities with HyperBall").
+//!
+//! Every node has a HyperLogLog counter of the nodes in its ball of radius `t`.
+//! The ball of radius `t + 1` is the union of the node's own ball and the balls of
+//! its neighbours, so each hop only needs a single pass over the edges. These are the
+//! same counters that are used for the harmonic centrality, which is the sum over
+//! `t` of the number of nodes at distance exactly `t`, divided by `t`.
+
+use std::{collections::BTreeMap, fs::File, path::Path};
+
+use super::harmonic::SKIPPED_REL;
+use crate::{
+    hyperloglog::HyperLogLog,
+    webgraph::{DegreeDirection, NodeID, Webgraph},
+    Result,
+};
+
+const HYPERLOGLOG_COUNTERS: usize = 64;
+
+type Counters = BTreeMap<NodeID, HyperLogLog<HYPERLOGLOG_COUNTERS>>;
+
+pub struct NeighborhoodFunction {
+    /// Estimated size of the ball of radius `t` for `t` in `0..=max_hops`.
+    balls: BTreeMap<NodeID, Vec<f64>>,
+    max_hops: u8,
+    direction: DegreeDirection,
+}
+
+impl NeighborhoodFunction {
+    /// Estimate the neighborhood function of all nodes up to `max_hops` hops.
+    /// With [`DegreeDirection::Out`] the ball of a node contains the nodes it can reach,
+    /// and with [`DegreeDirection::In`] the nodes that can reach it.
+    pub fn calculate(graph: &Webgraph, max_hops: u8, direction: DegreeDirection) -> Self {
+        let mut counters: Counters = graph
+            .nodes()
+            .map(|node| {
+                let mut counter = HyperLogLog::default();
+                counter.add(node.as_u64());
+                (node, counter)
+            })
+            .collect();
+
+        let mut balls: BTreeMap<NodeID, Vec<f64>> = counters
+            .keys()
+            .map(|node| {
+                let mut ball = Vec::with_capacity(max_hops as usize + 1);
+                ball.push(1.0);
+                (*node, ball)
+            })
+            .collect();
+
+        tracing::info!("estimating neighborhood function for {} nodes", balls.len());
+
+        for hop in 1..=max_hops {
+            let mut next = counters.clone();
+            let mut changed = false;
+
+            for edge in graph
+                .edges()
+                .filter(|e| !e.rel_flags().intersects(*SKIPPED_REL))
+            {
+                let (node, neighbour) = match direction {
+                    DegreeDirection::Out => (edge.from, edge.to),
+                    DegreeDirection::In => (edge.to, edge.from),
+                };
+
+                if let (Some(counter), Some(neighbour_counter)) =
+                    (next.get_mut(&node), counters.get(&neighbour))
+                {
+                    if counter
+                        .registers()
+                        .iter()
+                        .zip(neighbour_counter.registers().iter())
+                        .any(|(c, n)| *n > *c)
+                    {
+                        counter.merge(neighbour_counter);
+                        changed = true;
+                    }
+                }
+            }
+
+            counters = next;
+
+            for (node, ball) in balls.iter_mut() {
+                let size = counters[node].size() as f64;
+                // the estimates are not guaranteed to be monotone, but the balls are
+                ball.push(size.max(*ball.last().unwrap()));
+            }
+
+            tracing::debug!("neighborhood function hop {} done", hop);
+
+            if !changed {
+                for ball in balls.values_mut() {
+                    let last = *ball.last().unwrap();
+                    ball.resize(max_hops as usize + 1, last);
+                }
+
+                break;
+            }
+        }
+
+        Self {
+            balls,
+            max_hops,
+            direction,
+        }
+    }
+
+    pub fn max_hops(&self) -> u8 {
+        self.max_hops
+    }
+
+    pub fn direction(&self) -> DegreeDirection {
+        self.direction
+    }
+
+    /// The estimated ball sizes of the node for `t` in `0..=max_hops`.
+    /// The ball of radius `t` includes the node itself.
+    pub fn get(&self, node: &NodeID) -> Option<&[f64]> {
+        self.balls.get(node).map(|ball| ball.as_slice())
+    }
+
+    /// Estimated number of other nodes that are at most `hops` hops from the node.
+    /// Hops above `max_hops` are capped at `max_hops`.
+    pub fn within(&self, node: &NodeID, hops: u8) -> Option<f64> {
+        let ball = self.balls.get(node)?;
+        let hops = hops.min(self.max_hops) as usize;
+
+        Some(ball[hops] - 1.0)
+    }
+
+    /// The neighborhood function of the graph, i.e. the estimated number of pairs of
+    /// nodes with a distance of at most `t` for `t` in `0..=max_hops`.
+    pub fn global(&self) -> Vec<f64> {
+        let mut res = vec![0.0; self.max_hops as usize + 1];
+
+        for ball in self.balls.values() {
+            for (total, size) in res.iter_mut().zip(ball) {
+                *total += size;
+            }
+        }
+
+        res
+    }
+
+    /// Harmonic centrality of the node from the distances up to `max_hops`, without
+    /// the normalization by the number of nodes.
+    pub fn harmonic(&self, node: &NodeID) -> Option<f64> {
+        let ball = self.balls.get(node)?;
+
+        Some(
+            ball.windows(2)
+                .enumerate()
+                .map(|(t, w)| (w[1] - w[0]) / (t + 1) as f64)
+                .sum(),
+        )
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeID, &[f64])> {
+        self.balls
+            .iter()
+            .map(|(node, ball)| (node, ball.as_slice()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.balls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Write the neighborhood function of every node as csv with a column per hop.
+    pub fn export_csv<P: AsRef<Path>>(&self, graph: &Webgraph, output: P) -> Result<()> {
+        let mut wtr = csv::Writer::from_writer(File::create(output)?);
+
+        let mut header = vec!["node".to_string()];
+        header.extend((0..=self.max_hops).map(|t| format!("hops_{t}")));
+        wtr.write_record(&header)?;
+
+        for (node, ball) in self.iter() {
+            let Some(node) = graph.id2node(node) else {
+                continue;
+            };
+
+            let mut record = vec![node.as_str().to_string()];
+            record.extend(ball.iter().map(|size| size.round().to_string()));
+            wtr.write_record(&record)?;
+        }
+
+        wtr.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::{tests::test_graph, Node};
+
+    fn assert_close(estimate: Option<f64>, expected: f64) {
+        let estimate = estimate.unwrap();
+        assert!(
+            (estimate - expected).abs() <= 1.0,
+            "estimated {estimate}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn balls_grow_with_hops() {
+        // A -> B, B -> C, A -> C, C -> A, D -> C
+        let graph = test_graph();
+
+        let a = Node::from("A").id();
+        let b = Node::from("B").id();
+        let d = Node::from("D").id();
+
+        let out = NeighborhoodFunction::calculate(&graph, 4, DegreeDirection::Out);
+
+        assert_eq!(out.within(&a, 0), Some(0.0));
+        assert_close(out.within(&a, 1), 2.0);
+        assert_close(out.within(&b, 1), 1.0);
+        assert_close(out.within(&b, 2), 2.0);
+        assert_close(out.within(&d, 10), 3.0);
+        assert!(out.within(&d, 2).unwrap() >= out.within(&d, 1).unwrap());
+        assert_eq!(out.get(&d).unwrap().len(), 5);
+
+        let ingoing = NeighborhoodFunction::calculate(&graph, 4, DegreeDirection::In);
+
+        // nothing links to D
+        assert_eq!(ingoing.within(&d, 4), Some(0.0));
+        assert_close(ingoing.within(&a, 2), 3.0);
+
+        // C -> A, B -> C -> A and D -> C -> A
+        assert_close(ingoing.harmonic(&a), 2.0);
+
+        assert_eq!(out.global()[0], 4.0);
+        assert!(out.global()[4] >= out.global()[1]);
+    }
+}
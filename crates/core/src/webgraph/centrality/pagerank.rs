@@ -0,0 +1,185 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Personalized PageRank where the random surfer restarts from a set of seed
+//! nodes instead of any node in the graph. This gives a topic-specific authority
+//! when the seeds are e.g. known good sites within a topic.
+//!
+//! Only nodes that are reachable from the seeds get a score, so the scores are kept
+//! in a sparse map and the outgoing edges of a node are read from the graph on disk
+//! when its score is propagated. Scores below [`MIN_SCORE`] are dropped after each
+//! iteration. The scores sum to at most 1, so at most `1 / MIN_SCORE` nodes are
+//! kept between iterations.
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use super::harmonic::SKIPPED_REL;
+use crate::webgraph::{EdgeFilter, EdgeLimit, NodeID, Webgraph};
+
+const MIN_SCORE: f64 = 1e-7;
+
+/// Propagate the scores one step and return the new scores together with the score
+/// of the nodes without any outgoing links.
+fn step(
+    graph: &Webgraph,
+    scores: &HashMap<NodeID, f64>,
+    alpha: f64,
+) -> (HashMap<NodeID, f64>, f64) {
+    scores
+        .par_iter()
+        .fold(
+            || (HashMap::new(), 0.0),
+            |(mut next, mut dangling): (HashMap<NodeID, f64>, f64), (node, score)| {
+                let outgoing: Vec<_> = graph
+                    .raw_outgoing_edges_with_filter(
+                        node,
+                        EdgeLimit::Unlimited,
+                        EdgeFilter::exclude(*SKIPPED_REL),
+                    )
+                    .into_iter()
+                    .map(|edge| edge.to)
+                    .filter(|to| to != node)
+                    .collect();
+
+                if outgoing.is_empty() {
+                    dangling += score;
+                } else {
+                    let contribution = (1.0 - alpha) * score / outgoing.len() as f64;
+
+                    for to in outgoing {
+                        *next.entry(to).or_default() += contribution;
+                    }
+                }
+
+                (next, dangling)
+            },
+        )
+        .reduce(
+            || (HashMap::new(), 0.0),
+            |(a, a_dangling), (b, b_dangling)| {
+                if a.len() < b.len() {
+                    return merge(b, a, a_dangling + b_dangling);
+                }
+
+                merge(a, b, a_dangling + b_dangling)
+            },
+        )
+}
+
+fn merge(
+    mut into: HashMap<NodeID, f64>,
+    other: HashMap<NodeID, f64>,
+    dangling: f64,
+) -> (HashMap<NodeID, f64>, f64) {
+    for (node, score) in other {
+        *into.entry(node).or_default() += score;
+    }
+
+    (into, dangling)
+}
+
+/// Run `iterations` iterations of personalized PageRank. In each step the surfer
+/// restarts from a uniformly chosen seed with probability `alpha` and otherwise follows
+/// a random outgoing link. Nodes without outgoing links always restart from the seeds.
+///
+/// The scores sum to (almost) 1. Nodes that are not reachable from the seeds,
+/// or whose score is below [`MIN_SCORE`], are not part of the result.
+pub fn personalized_pagerank(
+    graph: &Webgraph,
+    seeds: &[NodeID],
+    alpha: f64,
+    iterations: usize,
+) -> HashMap<NodeID, f64> {
+    let mut seeds = seeds.to_vec();
+    seeds.sort();
+    seeds.dedup();
+
+    if seeds.is_empty() {
+        return HashMap::new();
+    }
+
+    let alpha = alpha.clamp(0.0, 1.0);
+    let seed_score = 1.0 / seeds.len() as f64;
+
+    let mut scores: HashMap<NodeID, f64> = seeds.iter().map(|seed| (*seed, seed_score)).collect();
+
+    for iteration in 0..iterations {
+        let total: f64 = scores.values().sum();
+        let (mut next, dangling) = step(graph, &scores, alpha);
+
+        let restart = alpha * total + (1.0 - alpha) * dangling;
+        for seed in &seeds {
+            *next.entry(*seed).or_default() += restart * seed_score;
+        }
+
+        next.retain(|_, score| *score >= MIN_SCORE);
+
+        let delta: f64 = next
+            .iter()
+            .map(|(node, score)| (score - scores.get(node).copied().unwrap_or_default()).abs())
+            .sum();
+
+        tracing::debug!(
+            "personalized pagerank iteration {}: {} nodes, delta {}",
+            iteration,
+            next.len(),
+            delta
+        );
+
+        scores = next;
+    }
+
+    scores
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::{tests::test_graph, Node};
+
+    #[test]
+    fn scores_follow_links_from_seeds() {
+        // A -> B, B -> C, A -> C, C -> A, D -> C
+        let graph = test_graph();
+
+        let a = Node::from("A").id();
+        let b = Node::from("B").id();
+        let c = Node::from("C").id();
+        let d = Node::from("D").id();
+
+        let scores = personalized_pagerank(&graph, &[d], 0.15, 50);
+
+        let total: f64 = scores.values().sum();
+        assert!((total - 1.0).abs() < 1e-3);
+
+        // nothing links to D, so it only gets the restarts
+        assert!((scores[&d] - 0.15).abs() < 1e-6);
+        assert!(scores[&c] > scores[&a]);
+        assert!(scores[&a] > scores[&b]);
+
+        let scores = personalized_pagerank(&graph, &[a], 0.15, 50);
+        assert!(!scores.contains_key(&d));
+        assert!(scores[&a] > scores[&b]);
+    }
+
+    #[test]
+    fn no_seeds() {
+        let graph = test_graph();
+        assert!(personalized_pagerank(&graph, &[], 0.15, 10).is_empty());
+    }
+}
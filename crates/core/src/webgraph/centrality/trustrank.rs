@@ -0,0 +1,127 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! TrustRank as described in "Combating Web Spam with TrustRank".
+//! Trust starts at a whitelist of hosts that are known not to be spam and is
+//! propagated along the outgoing edges. The trust a node passes on is split
+//! between its outgoing links and decays by `decay` for every hop, so spam
+//! sites that are only linked from other spam sites end up with little trust.
+
+use std::{collections::HashMap, path::Path};
+
+use super::{pagerank::personalized_pagerank, read_seeds};
+use crate::{
+    webgraph::{NodeID, Webgraph},
+    Result,
+};
+
+pub struct TrustRank(HashMap<NodeID, f64>);
+
+impl TrustRank {
+    /// Propagate trust from the `seeds` for `iterations` iterations.
+    /// This is personalized PageRank where the surfer keeps following links with
+    /// probability `decay` and otherwise restarts from a trusted seed.
+    pub fn calculate(graph: &Webgraph, seeds: &[NodeID], decay: f64, iterations: usize) -> Self {
+        Self(personalized_pagerank(graph, seeds, 1.0 - decay, iterations))
+    }
+
+    /// Like [`TrustRank::calculate`] with the seeds read from a whitelist file
+    /// with one host per line.
+    pub fn calculate_from_file<P: AsRef<Path>>(
+        graph: &Webgraph,
+        whitelist: P,
+        decay: f64,
+        iterations: usize,
+    ) -> Result<Self> {
+        let seeds = read_seeds(whitelist)?;
+        tracing::info!("propagating trust from {} seeds", seeds.len());
+
+        Ok(Self::calculate(graph, &seeds, decay, iterations))
+    }
+
+    pub fn get(&self, node: &NodeID) -> Option<f64> {
+        self.0.get(node).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&NodeID, f64)> {
+        self.0.iter().map(|(node, trust)| (node, *trust))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        webgraph::{Node, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    #[test]
+    fn spam_gets_less_trust() {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            crate::executor::Executor::single_thread(),
+            crate::webgraph::Compression::default(),
+            None,
+        );
+
+        let edges = [
+            ("trusted.com", "good.com"),
+            ("good.com", "trusted.com"),
+            ("good.com", "other.com"),
+            ("spam1.com", "spam2.com"),
+            ("spam2.com", "spam1.com"),
+            ("spam1.com", "other.com"),
+        ];
+
+        for (from, to) in edges {
+            writer.insert(
+                Node::from(from).into_host(),
+                Node::from(to).into_host(),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        let graph = writer.finalize();
+
+        let whitelist = crate::gen_temp_path().join("whitelist.txt");
+        std::fs::create_dir_all(whitelist.parent().unwrap()).unwrap();
+        std::fs::write(&whitelist, "# trusted hosts\ntrusted.com\n\n").unwrap();
+
+        let trust = TrustRank::calculate_from_file(&graph, &whitelist, 0.85, 20).unwrap();
+        let id = |host: &str| Node::from(host).into_host().id();
+
+        let trusted = trust.get(&id("trusted.com")).unwrap();
+        let good = trust.get(&id("good.com")).unwrap();
+        let other = trust.get(&id("other.com")).unwrap();
+
+        assert!(trusted > good);
+        assert!(good > other);
+        assert!(other > 0.0);
+
+        assert_eq!(trust.get(&id("spam1.com")), None);
+        assert_eq!(trust.get(&id("spam2.com")), None);
+    }
+}
@@ -0,0 +1,410 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Community detection with the Louvain method. The graph is treated as undirected,
+//! where each distinct directed edge adds a weight of 1 between its two nodes.
+//!
+//! Nodes are moved between communities in parallel sweeps: the best move for each
+//! node is found from the communities at the start of the sweep, and all the moves
+//! are applied at the end of the sweep. To prevent pairs of singleton communities
+//! from swapping nodes forever, a singleton only moves into another singleton
+//! with a smaller id. The communities are then collapsed into single nodes and
+//! the process is repeated until the modularity stops improving.
+//!
+//! Each community is identified by the smallest node id among its members.
+
+use std::{collections::HashMap, path::Path};
+
+use rayon::prelude::*;
+
+use super::{NodeID, Webgraph};
+use crate::Result;
+
+/// Maximum number of times the communities are collapsed into a new graph.
+const MAX_LEVELS: usize = 16;
+
+/// Maximum number of parallel sweeps on each level.
+const MAX_SWEEPS: usize = 32;
+
+/// A level is only kept if it improves the modularity by at least this much.
+const MIN_MODULARITY_GAIN: f64 = 1e-6;
+
+/// A node only moves if the modularity gain is larger than this.
+const MIN_MOVE_GAIN: f64 = 1e-12;
+
+/// Undirected weighted graph where the nodes are numbered `0..n`.
+struct WeightedGraph {
+    /// Neighbours of each node and the total weight of the edges to them.
+    /// Edges within a node (after collapsing) are not included.
+    adjacency: Vec<Vec<(u32, f64)>>,
+    /// Weighted degree of each node, including the edges within the node.
+    degrees: Vec<f64>,
+    /// Weight of the edges within each node, counted once from each endpoint.
+    internal: Vec<f64>,
+    /// Sum of all degrees (twice the total edge weight).
+    total_weight: f64,
+}
+
+fn merge_neighbours(mut neighbours: Vec<(u32, f64)>) -> Vec<(u32, f64)> {
+    neighbours.sort_unstable_by_key(|(n, _)| *n);
+
+    let mut merged: Vec<(u32, f64)> = Vec::with_capacity(neighbours.len());
+    for (n, w) in neighbours {
+        match merged.last_mut() {
+            Some((last, weight)) if *last == n => *weight += w,
+            _ => merged.push((n, w)),
+        }
+    }
+
+    merged
+}
+
+impl WeightedGraph {
+    /// Builds the graph from the directed edges of the webgraph. Returns the graph and
+    /// the original id of each node.
+    fn from_edges(mut edges: Vec<(NodeID, NodeID)>) -> (Self, Vec<NodeID>) {
+        edges.retain(|(from, to)| from != to);
+        edges.par_sort_unstable();
+        edges.dedup();
+
+        let mut nodes: Vec<NodeID> = edges.par_iter().flat_map(|(a, b)| [*a, *b]).collect();
+        nodes.par_sort_unstable();
+        nodes.dedup();
+
+        let index = |id: &NodeID| nodes.binary_search(id).unwrap() as u32;
+
+        let mut adjacency = vec![Vec::new(); nodes.len()];
+        for (from, to) in &edges {
+            let (from, to) = (index(from), index(to));
+            adjacency[from as usize].push((to, 1.0));
+            adjacency[to as usize].push((from, 1.0));
+        }
+
+        let adjacency: Vec<_> = adjacency.into_par_iter().map(merge_neighbours).collect();
+
+        (Self::new(adjacency, vec![0.0; nodes.len()]), nodes)
+    }
+
+    fn new(adjacency: Vec<Vec<(u32, f64)>>, internal: Vec<f64>) -> Self {
+        let degrees: Vec<f64> = adjacency
+            .par_iter()
+            .zip(internal.par_iter())
+            .map(|(neighbours, internal)| neighbours.iter().map(|(_, w)| w).sum::<f64>() + internal)
+            .collect();
+        let total_weight = degrees.iter().sum();
+
+        Self {
+            adjacency,
+            degrees,
+            internal,
+            total_weight,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    fn modularity(&self, community: &[u32], num_communities: usize) -> f64 {
+        if self.total_weight == 0.0 {
+            return 0.0;
+        }
+
+        let mut inside = vec![0.0; num_communities];
+        let mut totals = vec![0.0; num_communities];
+
+        for (node, neighbours) in self.adjacency.iter().enumerate() {
+            let c = community[node] as usize;
+            totals[c] += self.degrees[node];
+            inside[c] += self.internal[node];
+
+            for (neighbour, w) in neighbours {
+                if community[*neighbour as usize] as usize == c {
+                    inside[c] += w;
+                }
+            }
+        }
+
+        inside
+            .iter()
+            .zip(totals.iter())
+            .map(|(inside, total)| inside / self.total_weight - (total / self.total_weight).powi(2))
+            .sum()
+    }
+
+    /// The community a node should move to, if any, given the current communities.
+    fn best_move(
+        &self,
+        node: usize,
+        community: &[u32],
+        totals: &[f64],
+        sizes: &[u32],
+    ) -> Option<u32> {
+        let own = community[node];
+        let degree = self.degrees[node];
+
+        let mut weights: HashMap<u32, f64> = HashMap::new();
+        for (neighbour, w) in &self.adjacency[node] {
+            *weights.entry(community[*neighbour as usize]).or_default() += w;
+        }
+
+        let gain = |c: u32, w: f64| {
+            let total = if c == own {
+                totals[c as usize] - degree
+            } else {
+                totals[c as usize]
+            };
+
+            w - degree * total / self.total_weight
+        };
+
+        let stay = gain(own, weights.get(&own).copied().unwrap_or_default());
+
+        let (best, best_gain) = weights
+            .iter()
+            .filter(|(c, _)| **c != own)
+            .map(|(c, w)| (*c, gain(*c, *w)))
+            .max_by(|(a, a_gain), (b, b_gain)| a_gain.total_cmp(b_gain).then_with(|| b.cmp(a)))?;
+
+        let swaps_singletons = sizes[own as usize] == 1 && sizes[best as usize] == 1 && best > own;
+
+        (best_gain > stay + MIN_MOVE_GAIN && !swaps_singletons).then_some(best)
+    }
+
+    /// Moves nodes between communities until no node wants to move. Returns the
+    /// community of each node, numbered `0..num_communities`, and the number of communities.
+    fn local_moving(&self) -> (Vec<u32>, usize) {
+        let mut community: Vec<u32> = (0..self.len() as u32).collect();
+        let mut totals = self.degrees.clone();
+        let mut sizes = vec![1_u32; self.len()];
+
+        for _ in 0..MAX_SWEEPS {
+            let moves: Vec<(usize, u32)> = (0..self.len())
+                .into_par_iter()
+                .filter_map(|node| {
+                    self.best_move(node, &community, &totals, &sizes)
+                        .map(|c| (node, c))
+                })
+                .collect();
+
+            if moves.is_empty() {
+                break;
+            }
+
+            for (node, c) in moves {
+                let own = community[node] as usize;
+                totals[own] -= self.degrees[node];
+                sizes[own] -= 1;
+                totals[c as usize] += self.degrees[node];
+                sizes[c as usize] += 1;
+                community[node] = c;
+            }
+        }
+
+        let mut renumbered: HashMap<u32, u32> = HashMap::new();
+        for c in community.iter_mut() {
+            let next = renumbered.len() as u32;
+            *c = *renumbered.entry(*c).or_insert(next);
+        }
+
+        (community, renumbered.len())
+    }
+
+    /// Collapse each community into a single node.
+    fn aggregate(&self, community: &[u32], num_communities: usize) -> Self {
+        let mut adjacency = vec![Vec::new(); num_communities];
+        let mut internal = vec![0.0; num_communities];
+
+        for (node, neighbours) in self.adjacency.iter().enumerate() {
+            let c = community[node] as usize;
+            internal[c] += self.internal[node];
+
+            for (neighbour, w) in neighbours {
+                let other = community[*neighbour as usize];
+
+                if other as usize == c {
+                    internal[c] += w;
+                } else {
+                    adjacency[c].push((other, *w));
+                }
+            }
+        }
+
+        let adjacency = adjacency.into_par_iter().map(merge_neighbours).collect();
+
+        Self::new(adjacency, internal)
+    }
+}
+
+/// The community of each node. Returns the communities and the modularity of the partition.
+fn louvain(edges: Vec<(NodeID, NodeID)>) -> (HashMap<NodeID, NodeID>, f64) {
+    let (mut graph, nodes) = WeightedGraph::from_edges(edges);
+
+    // the node of each original node in the current (collapsed) graph
+    let mut assignment: Vec<u32> = (0..nodes.len() as u32).collect();
+    let mut modularity = graph.modularity(&assignment, nodes.len());
+
+    for level in 0..MAX_LEVELS {
+        let (community, num_communities) = graph.local_moving();
+
+        if num_communities == graph.len() {
+            break;
+        }
+
+        let new_modularity = graph.modularity(&community, num_communities);
+        if new_modularity - modularity < MIN_MODULARITY_GAIN {
+            break;
+        }
+
+        tracing::debug!(
+            "louvain level {}: {} communities with modularity {}",
+            level,
+            num_communities,
+            new_modularity
+        );
+
+        modularity = new_modularity;
+        assignment
+            .par_iter_mut()
+            .for_each(|a| *a = community[*a as usize]);
+        graph = graph.aggregate(&community, num_communities);
+    }
+
+    // `nodes` is sorted, so the first member seen is the smallest id in the community
+    let mut representative: HashMap<u32, NodeID> = HashMap::new();
+    let communities = nodes
+        .iter()
+        .zip(assignment.iter())
+        .map(|(node, c)| (*node, *representative.entry(*c).or_insert(*node)))
+        .collect();
+
+    (communities, modularity)
+}
+
+/// The community of each node in the graph. Stored in the `communities` folder of the graph.
+pub struct Communities {
+    inner: speedy_kv::Db<NodeID, NodeID>,
+}
+
+impl Communities {
+    pub fn path<P: AsRef<Path>>(graph_path: P) -> std::path::PathBuf {
+        graph_path.as_ref().join("communities")
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            inner: speedy_kv::Db::open_or_create(path)?,
+        })
+    }
+
+    pub(super) fn build(graph: &Webgraph) -> Result<Self> {
+        let path = Self::path(&graph.path);
+        if path.exists() {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        let (communities, modularity) =
+            louvain(graph.par_edges().map(|e| (e.from, e.to)).collect());
+
+        tracing::info!(
+            "found communities for {} nodes with modularity {}",
+            communities.len(),
+            modularity
+        );
+
+        let mut store = Self::open(path)?;
+
+        for (node, community) in communities {
+            store.inner.insert(node, community)?;
+        }
+
+        store.inner.commit()?;
+        store.inner.merge_all_segments()?;
+
+        Ok(store)
+    }
+
+    /// The community of the node, identified by the smallest node id among its members.
+    pub fn get(&self, node: &NodeID) -> Result<Option<NodeID>> {
+        self.inner.get(node)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(i: u64) -> NodeID {
+        NodeID::from(i)
+    }
+
+    /// Two cliques of four nodes connected by a single edge.
+    fn two_cliques() -> Vec<(NodeID, NodeID)> {
+        let mut edges = Vec::new();
+
+        for offset in [0, 4] {
+            for a in 0..4 {
+                for b in 0..4 {
+                    if a != b {
+                        edges.push((id(offset + a), id(offset + b)));
+                    }
+                }
+            }
+        }
+
+        edges.push((id(3), id(4)));
+        edges
+    }
+
+    #[test]
+    fn cliques_are_communities() {
+        let (communities, modularity) = louvain(two_cliques());
+
+        assert_eq!(communities.len(), 8);
+
+        for i in 0..4 {
+            assert_eq!(communities[&id(i)], id(0));
+            assert_eq!(communities[&id(i + 4)], id(4));
+        }
+
+        assert!(modularity > 0.4);
+    }
+
+    #[test]
+    fn duplicate_edges_and_self_loops_are_ignored() {
+        let mut edges = two_cliques();
+        edges.extend(two_cliques());
+        edges.push((id(0), id(0)));
+
+        assert_eq!(louvain(edges).0, louvain(two_cliques()).0);
+    }
+
+    #[test]
+    fn modularity_of_single_community() {
+        let (graph, nodes) = WeightedGraph::from_edges(two_cliques());
+
+        assert!(graph.modularity(&vec![0; nodes.len()], 1).abs() < 1e-9);
+        assert_eq!(graph.total_weight, 2.0 * 25.0);
+    }
+}
@@ -0,0 +1,474 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Incremental compaction of the segments of a graph.
+//!
+//! Unlike [`Webgraph::merge_all_segments`], a compaction only rewrites a few
+//! segments of similar size at a time, so it needs little extra disk space and
+//! can run in the background while the graph is used. The merged segments are
+//! read from their own copy of the files, so the graph only has to be locked
+//! while the merged segment replaces them.
+//!
+//! Tombstones are applied to the merged segment but kept in the metadata, as
+//! they may still refer to edges in the segments that were not compacted.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{RecvTimeoutError, Sender, TryRecvError};
+use uuid::Uuid;
+
+use super::{
+    manifest::{self, SegmentRef},
+    segment::Segment,
+    Compression, SegmentID, Tombstones, Webgraph,
+};
+use crate::{config::CompactionConfig, executor::Executor, Result};
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+fn tier(size: u64, config: &CompactionConfig) -> u32 {
+    if size <= config.min_segment_size_bytes {
+        return 0;
+    }
+
+    let ratio = size as f64 / config.min_segment_size_bytes.max(1) as f64;
+    ratio.log(config.size_ratio.max(1.1)).floor() as u32 + 1
+}
+
+/// The segments to merge next: the smallest segments of the lowest tier
+/// that has at least `segments_per_merge` segments.
+fn plan(sizes: &[(SegmentID, u64)], config: &CompactionConfig) -> Option<Vec<SegmentID>> {
+    let segments_per_merge = config.segments_per_merge.max(2);
+    let mut tiers: BTreeMap<u32, Vec<(&SegmentID, u64)>> = BTreeMap::new();
+
+    for (id, size) in sizes {
+        if config.max_segment_size_bytes.is_some_and(|max| *size > max) {
+            continue;
+        }
+
+        tiers
+            .entry(tier(*size, config))
+            .or_default()
+            .push((id, *size));
+    }
+
+    tiers
+        .into_values()
+        .find(|segments| segments.len() >= segments_per_merge)
+        .map(|mut segments| {
+            segments.sort_by_key(|(_, size)| *size);
+            segments
+                .into_iter()
+                .take(segments_per_merge)
+                .map(|(id, _)| id.clone())
+                .collect()
+        })
+}
+
+/// A merged segment that has not been committed to the graph yet. Its folder is
+/// registered as open, so merges of the graph in the meantime do not remove it as
+/// an unreferenced segment.
+struct MergedSegment {
+    id: SegmentID,
+    _open: SegmentRef,
+}
+
+/// Merge the segments into a new segment in the graph at `graph_path`.
+/// The segments are opened from disk, so the graph itself is left untouched.
+fn merge_segments(
+    graph_path: &str,
+    ids: &[SegmentID],
+    tombstones: &Tombstones,
+    compression: Compression,
+    executor: &Executor,
+) -> Result<MergedSegment> {
+    let segments_path = Path::new(graph_path).join("segments");
+    let segments = ids
+        .iter()
+        .map(|id| Segment::open(&segments_path, id.clone()))
+        .collect();
+
+    let id = Uuid::new_v4().to_string();
+
+    // the folder must exist before it is registered, as open folders are
+    // identified by their canonical path
+    let path = segments_path.join(&id);
+    fs::create_dir_all(&path)?;
+    let merged = MergedSegment {
+        id,
+        _open: SegmentRef::acquire(&path),
+    };
+
+    Segment::merge(
+        segments,
+        compression,
+        &segments_path,
+        merged.id.clone(),
+        tombstones,
+        executor,
+    )?;
+
+    Ok(merged)
+}
+
+impl Webgraph {
+    fn compaction_plan(&self, config: &CompactionConfig) -> Option<Vec<SegmentID>> {
+        let sizes: Vec<_> = self
+            .segments
+            .iter()
+            .map(|segment| (segment.id(), dir_size(&segment.path())))
+            .collect();
+
+        plan(&sizes, config)
+    }
+
+    /// Replace the `merged` segments with the segment `output`. If some of them are no
+    /// longer part of the graph, e.g. because all segments were merged in the meantime,
+    /// the new segment is removed instead. Returns whether the segments were replaced.
+    ///
+    /// Folders that are no longer referenced are removed through
+    /// [`manifest::remove_unreferenced_segments`], so segments that are still open
+    /// elsewhere, e.g. by another compaction, are kept until they are closed.
+    fn commit_compaction(&mut self, merged: &[SegmentID], output: MergedSegment) -> Result<bool> {
+        let segments_path = Path::new(&self.path).join("segments");
+
+        if !merged
+            .iter()
+            .all(|merged| self.meta.comitted_segments.contains(merged))
+        {
+            drop(output);
+            manifest::remove_unreferenced_segments(&self.path, &self.meta)?;
+            return Ok(false);
+        }
+
+        let id = output.id.clone();
+
+        let pos = self
+            .segments
+            .iter()
+            .position(|segment| merged.contains(&segment.id()))
+            .unwrap_or(self.segments.len());
+
+        self.segments
+            .retain(|segment| !merged.contains(&segment.id()));
+        self.meta
+            .comitted_segments
+            .retain(|segment| !merged.contains(segment));
+
+        let pos = pos.min(self.segments.len());
+        let segment = self.open_segment(&segments_path, id.clone());
+        self.segments.insert(pos, segment);
+        self.meta.comitted_segments.insert(pos, id);
+
+        self.save_metadata();
+
+        // the new segment is opened by the graph now
+        drop(output);
+        manifest::remove_unreferenced_segments(&self.path, &self.meta)?;
+
+        Ok(true)
+    }
+
+    /// Merge the next set of segments chosen by the compaction policy.
+    /// Returns false if there was nothing to compact.
+    pub fn compact_once(&mut self, config: &CompactionConfig) -> Result<bool> {
+        let Some(ids) = self.compaction_plan(config) else {
+            return Ok(false);
+        };
+
+        let start = Instant::now();
+        let merged = merge_segments(
+            &self.path,
+            &ids,
+            &self.meta.tombstones,
+            self.compression,
+            &self.executor,
+        )?;
+        self.metrics.observe_merge(start.elapsed());
+
+        self.commit_compaction(&ids, merged)
+    }
+
+    /// Compact the segments until the policy finds nothing more to merge.
+    pub fn compact(&mut self, config: &CompactionConfig) -> Result<()> {
+        while self.compact_once(config)? {}
+
+        Ok(())
+    }
+}
+
+/// Merge one set of segments without holding the lock on the graph during the merge.
+fn compact_in_background(graph: &RwLock<Webgraph>, config: &CompactionConfig) -> Result<bool> {
+    let (path, ids, tombstones, compression, executor, metrics) = {
+        let graph = graph.read().unwrap();
+
+        match graph.compaction_plan(config) {
+            Some(ids) => (
+                graph.path.clone(),
+                ids,
+                graph.meta.tombstones.clone(),
+                graph.compression,
+                Arc::clone(&graph.executor),
+                graph.metrics.clone(),
+            ),
+            None => return Ok(false),
+        }
+    };
+
+    let start = Instant::now();
+    let merged = merge_segments(&path, &ids, &tombstones, compression, &executor)?;
+    metrics.observe_merge(start.elapsed());
+
+    graph.write().unwrap().commit_compaction(&ids, merged)
+}
+
+/// Compacts the segments of a graph in a background thread until it is stopped or dropped.
+pub struct CompactionThread {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CompactionThread {
+    pub fn spawn(graph: Arc<RwLock<Webgraph>>, config: CompactionConfig) -> Self {
+        let (stop, stopped) = crossbeam_channel::bounded::<()>(1);
+        let interval = Duration::from_secs(config.interval_secs);
+
+        let handle = thread::spawn(move || loop {
+            match compact_in_background(&graph, &config) {
+                Ok(true) => match stopped.try_recv() {
+                    Err(TryRecvError::Empty) => continue,
+                    _ => break,
+                },
+                Ok(false) => {}
+                Err(err) => tracing::error!("webgraph compaction failed: {:?}", err),
+            }
+
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => break,
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+
+    /// Stop the compaction once the current merge is done and wait for the thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        // dropping the sender wakes up the thread if it is waiting
+        self.stop.take();
+
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                tracing::error!("webgraph compaction thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for CompactionThread {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{EdgeLimit, Node, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    fn graph_with_segments(num_segments: usize) -> Webgraph {
+        let mut graph: Option<Webgraph> = None;
+
+        for i in 0..num_segments {
+            let mut writer = WebgraphWriter::new(
+                crate::gen_temp_path(),
+                Executor::single_thread(),
+                Compression::default(),
+                None,
+            );
+            writer.insert(
+                Node::from(format!("A{i}")),
+                Node::from("B"),
+                String::new(),
+                RelFlags::default(),
+            );
+            let other = writer.finalize();
+
+            match graph.as_mut() {
+                Some(graph) => graph.merge(other).unwrap(),
+                None => graph = Some(other),
+            }
+        }
+
+        graph.unwrap()
+    }
+
+    fn config(segments_per_merge: usize) -> CompactionConfig {
+        CompactionConfig {
+            segments_per_merge,
+            interval_secs: 0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn plan_merges_smallest_segments_in_lowest_full_tier() {
+        let mb = 1024 * 1024;
+        let config = CompactionConfig {
+            segments_per_merge: 2,
+            size_ratio: 4.0,
+            min_segment_size_bytes: mb,
+            max_segment_size_bytes: Some(1000 * mb),
+            interval_secs: 0,
+        };
+
+        let sizes = vec![
+            ("a".to_string(), 2000 * mb),
+            ("b".to_string(), 3000 * mb),
+            ("c".to_string(), 2 * mb),
+            ("d".to_string(), mb / 2),
+            ("e".to_string(), 3 * mb),
+        ];
+
+        assert_eq!(tier(mb / 2, &config), 0);
+        assert_eq!(tier(2 * mb, &config), 1);
+        assert_eq!(
+            plan(&sizes, &config),
+            Some(vec!["c".to_string(), "e".to_string()])
+        );
+
+        // the large segments are above the maximum size
+        assert_eq!(plan(&sizes[..2], &config), None);
+    }
+
+    #[test]
+    fn compact() {
+        let mut graph = graph_with_segments(5);
+        let b = Node::from("B").id();
+
+        assert_eq!(graph.segments.len(), 5);
+        assert_eq!(graph.raw_ingoing_edges(&b, EdgeLimit::Unlimited).len(), 5);
+
+        graph.compact(&config(2)).unwrap();
+
+        assert_eq!(graph.segments.len(), 1);
+        assert_eq!(graph.meta.comitted_segments.len(), 1);
+        assert_eq!(graph.raw_ingoing_edges(&b, EdgeLimit::Unlimited).len(), 5);
+
+        let segments = fs::read_dir(Path::new(&graph.path).join("segments"))
+            .unwrap()
+            .count();
+        assert_eq!(segments, 1);
+
+        let path = graph.path.clone();
+        drop(graph);
+        let graph = Webgraph::builder(&path).single_threaded().open();
+        assert_eq!(graph.raw_ingoing_edges(&b, EdgeLimit::Unlimited).len(), 5);
+    }
+
+    #[test]
+    fn merge_during_compaction_keeps_output() {
+        let mut graph = graph_with_segments(3);
+        let b = Node::from("B").id();
+
+        let ids = graph.compaction_plan(&config(2)).unwrap();
+        let merged = merge_segments(
+            &graph.path,
+            &ids,
+            &graph.meta.tombstones,
+            graph.compression,
+            &graph.executor,
+        )
+        .unwrap();
+
+        // a merge removes the unreferenced segment folders of the graph
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+        writer.insert(
+            Node::from("C"),
+            Node::from("B"),
+            String::new(),
+            RelFlags::default(),
+        );
+        graph.merge(writer.finalize()).unwrap();
+
+        assert!(graph.commit_compaction(&ids, merged).unwrap());
+        assert_eq!(graph.segments.len(), 3);
+        assert_eq!(graph.raw_ingoing_edges(&b, EdgeLimit::Unlimited).len(), 4);
+
+        let segments = fs::read_dir(Path::new(&graph.path).join("segments"))
+            .unwrap()
+            .count();
+        assert_eq!(segments, 3);
+    }
+
+    #[test]
+    fn background_compaction() {
+        let graph = Arc::new(RwLock::new(graph_with_segments(4)));
+        let b = Node::from("B").id();
+
+        let compaction = CompactionThread::spawn(Arc::clone(&graph), config(4));
+
+        for _ in 0..100 {
+            if graph.read().unwrap().segments.len() == 1 {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        compaction.stop();
+
+        let graph = graph.read().unwrap();
+        assert_eq!(graph.segments.len(), 1);
+        assert_eq!(graph.raw_ingoing_edges(&b, EdgeLimit::Unlimited).len(), 4);
+    }
+}
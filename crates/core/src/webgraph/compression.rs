@@ -14,25 +14,280 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
-#[derive(Default, Debug, Clone, Copy, bincode::Encode, bincode::Decode)]
-pub enum Compression {
+//! Compression of the edge stores of a segment.
+//!
+//! The adjacency store and the label store can be compressed with different codecs,
+//! as the adjacency store holds small records of integers while the labels are text.
+
+use anyhow::bail;
+use file_store::ConstSerializable;
+
+use super::{merge::NodeDatum, EdgeMetadata, EdgeTimestamps, NodeID, StoredEdge};
+use crate::Result;
+
+/// A general purpose codec for a block of bytes.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Codec {
     None,
     #[default]
     Lz4,
+    /// Levels go from 1 to 22. Higher levels compress better but are slower to compress.
+    /// Decompression is equally fast for all levels.
+    Zstd {
+        level: i32,
+    },
 }
 
-impl Compression {
+impl Codec {
     pub fn compress(&self, bytes: &[u8]) -> Vec<u8> {
         match self {
-            Compression::None => bytes.to_vec(),
-            Compression::Lz4 => lz4_flex::compress_prepend_size(bytes),
+            Codec::None => bytes.to_vec(),
+            Codec::Lz4 => lz4_flex::compress_prepend_size(bytes),
+            Codec::Zstd { level } => zstd::encode_all(bytes, *level).unwrap(),
         }
     }
 
     pub fn decompress(&self, bytes: &[u8]) -> Vec<u8> {
+        self.try_decompress(bytes).unwrap()
+    }
+
+    pub fn try_decompress(&self, bytes: &[u8]) -> Result<Vec<u8>> {
         match self {
-            Compression::None => bytes.to_vec(),
-            Compression::Lz4 => lz4_flex::decompress_size_prepended(bytes).unwrap(),
+            Codec::None => Ok(bytes.to_vec()),
+            Codec::Lz4 => Ok(lz4_flex::decompress_size_prepended(bytes)?),
+            Codec::Zstd { .. } => Ok(zstd::decode_all(bytes)?),
+        }
+    }
+}
+
+/// How the edges are stored in the adjacency store.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdjacencyCodec {
+    /// Uncompressed fixed-width edges. Queries read the edges directly from the memory
+    /// mapped file and binary search them for cursors, so this is the fastest to query.
+    #[default]
+    Fixed,
+    /// Blocks of edges where the sort key and node id are stored as the difference to
+    /// the previous edge, and all integers are written as varints.
+    DeltaVarint,
+    /// Blocks of fixed-width edges compressed with the codec.
+    Compressed { codec: Codec },
+}
+
+/// An edge in a delta-varint encoded block. The edges of a node are sorted by their
+/// sort key and then by node id, so the differences are small.
+#[derive(bincode::Encode, bincode::Decode)]
+struct DeltaEdge {
+    sort_key: u64,
+    /// The difference to the previous node id if the sort keys are equal, otherwise the node id.
+    node: u64,
+    rel: u32,
+    first_seen: u32,
+    /// Seconds from when the edge was first seen until it was last seen.
+    seen_for: u32,
+    metadata: EdgeMetadata,
+}
+
+impl AdjacencyCodec {
+    /// Whether the edges are stored in blocks that must be decoded rather than as fixed-width records.
+    pub fn is_blocked(&self) -> bool {
+        !matches!(self, AdjacencyCodec::Fixed)
+    }
+
+    pub fn encode(&self, edges: &[StoredEdge]) -> Vec<u8> {
+        match self {
+            AdjacencyCodec::Fixed => encode_fixed(edges),
+            AdjacencyCodec::Compressed { codec } => codec.compress(&encode_fixed(edges)),
+            AdjacencyCodec::DeltaVarint => {
+                let mut prev = NodeDatum::new(NodeID::from(0_u64), 0);
+                let delta_edges: Vec<_> = edges
+                    .iter()
+                    .map(|edge| {
+                        let sort_key = edge.other.sort_key().wrapping_sub(prev.sort_key());
+                        let node = if sort_key == 0 {
+                            edge.other
+                                .node()
+                                .as_u64()
+                                .wrapping_sub(prev.node().as_u64())
+                        } else {
+                            edge.other.node().as_u64()
+                        };
+                        prev = edge.other.clone();
+
+                        DeltaEdge {
+                            sort_key,
+                            node,
+                            rel: edge.rel.as_u32(),
+                            first_seen: edge.timestamps.first_seen,
+                            seen_for: edge
+                                .timestamps
+                                .last_seen
+                                .wrapping_sub(edge.timestamps.first_seen),
+                            metadata: edge.metadata,
+                        }
+                    })
+                    .collect();
+
+                bincode::encode_to_vec(delta_edges, bincode::config::standard()).unwrap()
+            }
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<Vec<StoredEdge>> {
+        match self {
+            AdjacencyCodec::Fixed => decode_fixed(bytes),
+            AdjacencyCodec::Compressed { codec } => decode_fixed(&codec.try_decompress(bytes)?),
+            AdjacencyCodec::DeltaVarint => {
+                let (delta_edges, _): (Vec<DeltaEdge>, _) =
+                    bincode::decode_from_slice(bytes, bincode::config::standard())?;
+
+                let mut prev = NodeDatum::new(NodeID::from(0_u64), 0);
+                Ok(delta_edges
+                    .into_iter()
+                    .map(|edge| {
+                        let sort_key = prev.sort_key().wrapping_add(edge.sort_key);
+                        let node = if edge.sort_key == 0 {
+                            prev.node().as_u64().wrapping_add(edge.node)
+                        } else {
+                            edge.node
+                        };
+                        prev = NodeDatum::new(NodeID::from(node), sort_key);
+
+                        let timestamps = EdgeTimestamps {
+                            first_seen: edge.first_seen,
+                            last_seen: edge.first_seen.wrapping_add(edge.seen_for),
+                        };
+
+                        StoredEdge::new(prev.clone(), edge.rel.into(), timestamps)
+                            .with_metadata(edge.metadata)
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+fn encode_fixed(edges: &[StoredEdge]) -> Vec<u8> {
+    let mut bytes = vec![0; edges.len() * StoredEdge::BYTES];
+
+    for (edge, buf) in edges.iter().zip(bytes.chunks_exact_mut(StoredEdge::BYTES)) {
+        edge.serialize(buf);
+    }
+
+    bytes
+}
+
+fn decode_fixed(bytes: &[u8]) -> Result<Vec<StoredEdge>> {
+    if bytes.len() % StoredEdge::BYTES != 0 {
+        bail!(
+            "block of {} bytes is not a whole number of edges",
+            bytes.len()
+        );
+    }
+
+    Ok(bytes
+        .chunks_exact(StoredEdge::BYTES)
+        .map(StoredEdge::deserialize)
+        .collect())
+}
+
+/// The codecs of the stores in a segment.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Compression {
+    #[serde(default)]
+    pub adjacency: AdjacencyCodec,
+    #[serde(default)]
+    pub labels: Codec,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webpage::html::links::RelFlags;
+
+    fn edges() -> Vec<StoredEdge> {
+        (0..300_u64)
+            .map(|i| {
+                let datum = NodeDatum::new(NodeID::from(i * 7919 + (i % 3)), i / 10);
+                let timestamps = EdgeTimestamps {
+                    first_seen: 1_700_000_000 + i as u32,
+                    last_seen: 1_700_100_000,
+                };
+
+                StoredEdge::new(datum, RelFlags::from(i as u32 % 4), timestamps).with_metadata(
+                    EdgeMetadata {
+                        position: i as u16,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adjacency_codecs_roundtrip() {
+        let edges = edges();
+
+        for codec in [
+            AdjacencyCodec::Fixed,
+            AdjacencyCodec::DeltaVarint,
+            AdjacencyCodec::Compressed { codec: Codec::Lz4 },
+            AdjacencyCodec::Compressed {
+                codec: Codec::Zstd { level: 3 },
+            },
+        ] {
+            let bytes = codec.encode(&edges);
+            let decoded = codec.decode(&bytes).unwrap();
+
+            assert_eq!(decoded.len(), edges.len());
+            for (a, b) in decoded.iter().zip(&edges) {
+                assert_eq!(a.other, b.other);
+                assert_eq!(a.rel, b.rel);
+                assert_eq!(a.timestamps, b.timestamps);
+                assert_eq!(a.metadata, b.metadata);
+            }
+        }
+
+        assert!(
+            AdjacencyCodec::DeltaVarint.encode(&edges).len()
+                < AdjacencyCodec::Fixed.encode(&edges).len()
+        );
+    }
+
+    #[test]
+    fn zstd_levels() {
+        let bytes = "anchor text ".repeat(100).into_bytes();
+
+        for level in [1, 3, 19] {
+            let codec = Codec::Zstd { level };
+            let compressed = codec.compress(&bytes);
+
+            assert!(compressed.len() < bytes.len());
+            assert_eq!(codec.decompress(&compressed), bytes);
         }
     }
 }
@@ -0,0 +1,278 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A writer that can be shared between threads.
+//!
+//! The edges are buffered in a number of shards, each with its own [`WebgraphWriter`]
+//! behind a lock, so threads only wait for each other when they insert into the same
+//! shard. Edges are assigned to a shard by the host of their source node, which keeps
+//! the outlink caps and the collapsed boilerplate links of a host within one shard.
+//!
+//! Each shard is written to its own segment when the writer is finalized, and the
+//! segments are merged into the graph of the first shard.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::{
+    Compression, EdgeMetadata, Node, NodeID, NormalizationPolicy, Webgraph, WebgraphWriter,
+};
+use crate::{
+    config::WebgraphInsertPolicy, executor::Executor, webpage::html::links::RelFlags, Result,
+};
+
+const SHARDS_FOLDER: &str = "shards";
+
+pub struct ConcurrentWebgraphWriter {
+    path: PathBuf,
+    shards: Vec<Mutex<WebgraphWriter>>,
+}
+
+impl ConcurrentWebgraphWriter {
+    /// The first shard is written to the graph folder and the others to folders
+    /// inside it, which are removed when the shards are merged.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        num_shards: usize,
+        executor: Executor,
+        compression: Compression,
+        host_centrality_rank_store: Option<Arc<speedy_kv::Db<NodeID, u64>>>,
+    ) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let mut executor = Some(executor);
+
+        let shards = (0..num_shards.max(1))
+            .map(|i| {
+                let (shard_path, executor) = match i {
+                    0 => (path.clone(), executor.take().unwrap()),
+                    _ => (
+                        path.join(SHARDS_FOLDER).join(i.to_string()),
+                        Executor::single_thread(),
+                    ),
+                };
+
+                Mutex::new(WebgraphWriter::new(
+                    shard_path,
+                    executor,
+                    compression,
+                    host_centrality_rank_store.clone(),
+                ))
+            })
+            .collect();
+
+        Self { path, shards }
+    }
+
+    fn map_shards(self, f: impl Fn(WebgraphWriter) -> WebgraphWriter) -> Self {
+        let shards = self
+            .shards
+            .into_iter()
+            .map(|shard| Mutex::new(f(shard.into_inner().unwrap())))
+            .collect();
+
+        Self {
+            path: self.path,
+            shards,
+        }
+    }
+
+    /// See [`WebgraphWriter::with_insert_policy`].
+    pub fn with_insert_policy(self, policy: WebgraphInsertPolicy) -> Self {
+        self.map_shards(|shard| shard.with_insert_policy(policy.clone()))
+    }
+
+    /// See [`WebgraphWriter::with_normalization`].
+    pub fn with_normalization(self, policy: Arc<dyn NormalizationPolicy>) -> Self {
+        self.map_shards(|shard| shard.with_normalization(Arc::clone(&policy)))
+    }
+
+    /// The node of `url` with the normalization of the shards.
+    pub fn node(&self, url: &url::Url) -> Node {
+        self.shards[0].lock().unwrap().node(url)
+    }
+
+    /// See [`WebgraphWriter::with_wal`]. Each shard has its own log.
+    pub fn with_wal(self) -> Self {
+        self.map_shards(|shard| shard.with_wal())
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn set_timestamp(&self, timestamp: u32) {
+        for shard in &self.shards {
+            shard.lock().unwrap().set_timestamp(timestamp);
+        }
+    }
+
+    fn shard(&self, node: &Node) -> MutexGuard<'_, WebgraphWriter> {
+        let host = node.clone().into_host().id();
+        let shard = host.as_u64() as usize % self.shards.len();
+
+        self.shards[shard].lock().unwrap()
+    }
+
+    pub fn insert(&self, from: Node, to: Node, label: String, rel: RelFlags) {
+        self.insert_with_metadata(from, to, label, rel, EdgeMetadata::default());
+    }
+
+    /// See [`WebgraphWriter::insert_with_metadata`].
+    pub fn insert_with_metadata(
+        &self,
+        from: Node,
+        to: Node,
+        label: String,
+        rel: RelFlags,
+        metadata: EdgeMetadata,
+    ) {
+        self.shard(&from)
+            .insert_with_metadata(from, to, label, rel, metadata);
+    }
+
+    /// See [`WebgraphWriter::insert_alias`].
+    pub fn insert_alias(&self, alias: Node, canonical: Node) {
+        self.shard(&alias).insert_alias(alias, canonical);
+    }
+
+    /// See [`WebgraphWriter::delete_node`].
+    pub fn delete_node(&self, node: &Node) {
+        self.shard(node).delete_node(node);
+    }
+
+    /// See [`WebgraphWriter::delete_edge`].
+    pub fn delete_edge(&self, from: &Node, to: &Node) {
+        self.shard(from).delete_edge(from, to);
+    }
+
+    pub fn commit(&self) {
+        for shard in &self.shards {
+            shard.lock().unwrap().commit();
+        }
+    }
+
+    /// See [`WebgraphWriter::checkpoint`]. The checkpoint is recorded in every shard.
+    pub fn checkpoint(&self, name: String) {
+        for shard in &self.shards {
+            shard.lock().unwrap().checkpoint(name.clone());
+        }
+    }
+
+    pub fn has_checkpoint(&self, name: &str) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.lock().unwrap().has_checkpoint(name))
+    }
+
+    /// Write a segment for each shard in parallel and merge them into a single graph.
+    pub fn finalize(self) -> Result<Webgraph> {
+        let mut graphs: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .shards
+                .into_iter()
+                .map(|shard| scope.spawn(move || shard.into_inner().unwrap().finalize()))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut graph = graphs.remove(0);
+        for other in graphs {
+            graph.merge(other)?;
+        }
+
+        let shards_path = self.path.join(SHARDS_FOLDER);
+        if shards_path.exists() {
+            fs::remove_dir_all(shards_path)?;
+        }
+
+        Ok(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::EdgeLimit;
+
+    #[test]
+    fn concurrent_inserts() {
+        let path = crate::gen_temp_path();
+        let writer = ConcurrentWebgraphWriter::new(
+            &path,
+            4,
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        let num_threads = 8;
+        let num_edges = 100;
+
+        std::thread::scope(|scope| {
+            for t in 0..num_threads {
+                let writer = &writer;
+
+                scope.spawn(move || {
+                    for i in 0..num_edges {
+                        writer.insert(
+                            Node::from(format!("https://host{t}.com/{i}")),
+                            Node::from(format!("https://target.com/{}", i % 10)),
+                            format!("{t}-{i}"),
+                            RelFlags::default(),
+                        );
+                    }
+                });
+            }
+        });
+
+        writer.delete_node(&Node::from("https://host0.com/0"));
+
+        let graph = writer.finalize().unwrap();
+
+        assert_eq!(graph.segments.len(), 4);
+        assert!(!path.join(SHARDS_FOLDER).exists());
+
+        let ingoing: usize = (0..10)
+            .map(|i| {
+                graph
+                    .raw_ingoing_edges(
+                        &Node::from(format!("https://target.com/{i}")).id(),
+                        EdgeLimit::Unlimited,
+                    )
+                    .len()
+            })
+            .sum();
+        assert_eq!(ingoing, num_threads * num_edges - 1);
+
+        let source = Node::from("https://host3.com/42");
+        assert_eq!(graph.id2node(&source.id()), Some(source.clone()));
+        assert_eq!(
+            graph
+                .raw_outgoing_edges_with_labels(&source.id(), EdgeLimit::Unlimited)
+                .into_iter()
+                .map(|edge| edge.label)
+                .collect::<Vec<_>>(),
+            vec!["3-42".to_string()]
+        );
+    }
+}
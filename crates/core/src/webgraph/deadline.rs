@@ -0,0 +1,173 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Deadlines for edge queries. Reading all the edges of a hub node can take
+//! seconds, so a query with a deadline stops reading the segments when the
+//! deadline has passed and returns the edges it has read so far.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{EdgeCursor, FullEdge};
+
+/// The time an edge query may run until. Clones share whether the deadline was
+/// exceeded, so it can be checked by each segment that is read in parallel.
+#[derive(Debug, Clone, Default)]
+pub struct Deadline {
+    at: Option<Instant>,
+    exceeded: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    /// A deadline that is never exceeded.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn at(instant: Instant) -> Self {
+        Self {
+            at: Some(instant),
+            exceeded: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn after(timeout: Duration) -> Self {
+        Self::at(Instant::now() + timeout)
+    }
+
+    /// Whether the query may continue. Once the deadline has passed, this is
+    /// recorded so [`Deadline::exceeded`] can tell that the result is incomplete.
+    pub fn has_time(&self) -> bool {
+        match self.at {
+            Some(at) if Instant::now() >= at => {
+                self.exceeded.store(true, Ordering::Relaxed);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether a read was stopped because the deadline had passed.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+
+    /// Stop the iterator when the deadline has passed.
+    pub fn apply<'a, T>(
+        &self,
+        it: impl Iterator<Item = T> + 'a,
+    ) -> Box<dyn Iterator<Item = T> + 'a> {
+        if self.at.is_none() {
+            return Box::new(it);
+        }
+
+        let deadline = self.clone();
+        Box::new(it.take_while(move |_| deadline.has_time()))
+    }
+}
+
+/// The edges of a query with a deadline.
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub struct EdgeQueryResult {
+    pub edges: Vec<FullEdge>,
+    /// The deadline passed before all the edges were read. The edges are then the
+    /// first edges of the full result up to where every segment had been read, see
+    /// [`CursorBound`], and may be fewer than the limit.
+    pub truncated: bool,
+}
+
+/// Where the merged edges of several sources (segments or shards) must be cut so
+/// no edges are skipped when paging with the cursor of the last edge. Each source
+/// returns its edges ordered by cursor, so a source that stopped early may be missing
+/// any edge after the last one it returned. The bound is the smallest of those cursors.
+#[derive(Debug, Default)]
+pub struct CursorBound {
+    /// `Some(None)` when a source stopped before it returned any edge.
+    bound: Mutex<Option<Option<EdgeCursor>>>,
+}
+
+impl CursorBound {
+    /// Record a source that stopped early after returning edges up to `last`.
+    pub fn add(&self, last: Option<EdgeCursor>) {
+        let mut bound = self.bound.lock().unwrap();
+
+        *bound = Some(match *bound {
+            Some(current) => current.min(last),
+            None => last,
+        });
+    }
+
+    /// Remove the edges after the bound.
+    pub fn apply<T>(self, edges: &mut Vec<T>, cursor: impl Fn(&T) -> EdgeCursor) {
+        match self.bound.into_inner().unwrap() {
+            None => {}
+            Some(None) => edges.clear(),
+            Some(Some(bound)) => edges.retain(|edge| cursor(edge) <= bound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::webgraph::NodeID;
+
+    use super::*;
+
+    #[test]
+    fn deadlines() {
+        let deadline = Deadline::none();
+        assert_eq!(deadline.apply(0..10).count(), 10);
+        assert!(!deadline.exceeded());
+
+        let deadline = Deadline::after(Duration::from_secs(60));
+        assert_eq!(deadline.apply(0..10).count(), 10);
+        assert!(!deadline.exceeded());
+
+        let deadline = Deadline::at(Instant::now());
+        assert_eq!(deadline.clone().apply(0..10).count(), 0);
+        assert!(deadline.exceeded());
+    }
+
+    #[test]
+    fn cursor_bound() {
+        let cursor = |sort_key: u64| EdgeCursor::new(sort_key, NodeID::from(0_u64));
+        let edges = || (0..10).map(cursor).collect::<Vec<_>>();
+
+        let bound = CursorBound::default();
+        let mut res = edges();
+        bound.apply(&mut res, |c| *c);
+        assert_eq!(res.len(), 10);
+
+        let bound = CursorBound::default();
+        bound.add(Some(cursor(7)));
+        bound.add(Some(cursor(3)));
+        let mut res = edges();
+        bound.apply(&mut res, |c| *c);
+        assert_eq!(res, (0..=3).map(cursor).collect::<Vec<_>>());
+
+        let bound = CursorBound::default();
+        bound.add(Some(cursor(3)));
+        bound.add(None);
+        let mut res = edges();
+        bound.apply(&mut res, |c| *c);
+        assert!(res.is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The nodes with the highest in- or out-degree. The degrees are read from the
+//! edge ranges of the segments, so no edges are loaded. The result is cached on
+//! disk until the segments of the graph change or something is deleted.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use super::{segment::Segment, NodeDegree, NodeID, SegmentID, Tombstones};
+use crate::Result;
+
+const CACHE_FOLDER: &str = "cache";
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DegreeDirection {
+    In,
+    Out,
+}
+
+impl DegreeDirection {
+    fn cache_file(&self) -> &'static str {
+        match self {
+            DegreeDirection::In => "top_in_degree.bin",
+            DegreeDirection::Out => "top_out_degree.bin",
+        }
+    }
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+pub(super) struct TopDegreeCache {
+    segments: Vec<SegmentID>,
+    /// Number of tombstones when the cache was written. Tombstones are only
+    /// added until the segments are merged, so this changes on every deletion.
+    num_tombstones: usize,
+    k: usize,
+    nodes: Vec<NodeDegree>,
+}
+
+impl TopDegreeCache {
+    pub fn path<P: AsRef<Path>>(graph_path: P, direction: DegreeDirection) -> PathBuf {
+        graph_path
+            .as_ref()
+            .join(CACHE_FOLDER)
+            .join(direction.cache_file())
+    }
+
+    pub fn new(
+        segments: Vec<SegmentID>,
+        num_tombstones: usize,
+        k: usize,
+        nodes: Vec<NodeDegree>,
+    ) -> Self {
+        Self {
+            segments,
+            num_tombstones,
+            k,
+            nodes,
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        let (cache, _) = bincode::decode_from_slice(&bytes, bincode::config::standard()).ok()?;
+
+        Some(cache)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let bytes = bincode::encode_to_vec(self, bincode::config::standard())?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+
+    /// The top `k` nodes if the cache is still valid for the graph.
+    pub fn get(
+        &self,
+        segments: &[SegmentID],
+        num_tombstones: usize,
+        k: usize,
+    ) -> Option<Vec<NodeDegree>> {
+        // a cache with fewer nodes than it was computed for contains every node
+        let complete = k <= self.k || self.nodes.len() < self.k;
+
+        if self.segments == segments && self.num_tombstones == num_tombstones && complete {
+            Some(self.nodes.iter().take(k).cloned().collect())
+        } else {
+            None
+        }
+    }
+}
+
+/// The degree of every node with at least one edge in the direction, summed over all segments.
+pub(super) fn degrees(segments: &[Segment], direction: DegreeDirection) -> HashMap<NodeID, usize> {
+    let mut degrees: HashMap<NodeID, usize> = HashMap::new();
+
+    for segment in segments {
+        for (node, degree) in segment.degrees(direction) {
+            *degrees.entry(node).or_default() += degree;
+        }
+    }
+
+    degrees
+}
+
+/// The `k` nodes with the highest degree summed over all segments, sorted by
+/// degree. Deleted nodes are skipped, but like [`super::Webgraph::in_degree`]
+/// deleted edges are counted until the segments are merged.
+pub(super) fn top_nodes(
+    segments: &[Segment],
+    direction: DegreeDirection,
+    tombstones: &Tombstones,
+    k: usize,
+) -> Vec<NodeDegree> {
+    let mut nodes: Vec<_> = degrees(segments, direction)
+        .into_iter()
+        .filter(|(node, _)| !tombstones.is_node_deleted(node))
+        .map(|(node, degree)| NodeDegree { node, degree })
+        .collect();
+
+    let order =
+        |a: &NodeDegree, b: &NodeDegree| b.degree.cmp(&a.degree).then_with(|| a.node.cmp(&b.node));
+
+    if nodes.len() > k {
+        nodes.select_nth_unstable_by(k, order);
+        nodes.truncate(k);
+    }
+
+    nodes.sort_by(order);
+
+    nodes
+}
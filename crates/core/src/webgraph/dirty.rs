@@ -0,0 +1,88 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Nodes that have gained or lost edges since the centralities were last calculated.
+//! They are recorded in [`super::Webgraph::merge`] and kept in a file in the graph
+//! folder until they are cleared, so the centralities can be updated incrementally
+//! after one or more batches have been merged into the graph.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use super::NodeID;
+use crate::Result;
+
+const DIRTY_NODES_FILE: &str = "dirty_nodes.bin";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct DirtyNodes {
+    nodes: HashSet<NodeID>,
+}
+
+impl DirtyNodes {
+    pub fn path<P: AsRef<Path>>(graph_path: P) -> PathBuf {
+        graph_path.as_ref().join(DIRTY_NODES_FILE)
+    }
+
+    /// Open the dirty nodes in the file. A missing file has no dirty nodes.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Self::default());
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        Ok(bincode::decode_from_std_read(
+            &mut reader,
+            bincode::config::standard(),
+        )?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard())?;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, node: NodeID) {
+        self.nodes.insert(node);
+    }
+
+    pub fn contains(&self, node: &NodeID) -> bool {
+        self.nodes.contains(node)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &NodeID> {
+        self.nodes.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl Extend<NodeID> for DirtyNodes {
+    fn extend<T: IntoIterator<Item = NodeID>>(&mut self, iter: T) {
+        self.nodes.extend(iter);
+    }
+}
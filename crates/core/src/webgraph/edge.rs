@@ -50,6 +50,193 @@ impl EdgeLabel for () {
     }
 }
 
+/// Seconds since the unix epoch.
+pub fn unix_timestamp() -> u32 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+/// When an edge was first and last seen in a crawl, in seconds since the unix epoch.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct EdgeTimestamps {
+    pub first_seen: u32,
+    pub last_seen: u32,
+}
+
+impl EdgeTimestamps {
+    pub fn at(timestamp: u32) -> Self {
+        Self {
+            first_seen: timestamp,
+            last_seen: timestamp,
+        }
+    }
+
+    /// Timestamps of an edge that has been seen at the times of both `self` and `other`.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            first_seen: self.first_seen.min(other.first_seen),
+            last_seen: self.last_seen.max(other.last_seen),
+        }
+    }
+
+    pub fn seen_since(&self, timestamp: u32) -> bool {
+        self.last_seen >= timestamp
+    }
+}
+
+/// The closest block element around a link, e.g. to tell links in the running
+/// text of a paragraph apart from links in lists.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+#[repr(u8)]
+pub enum LinkBlock {
+    #[default]
+    Unknown = 0,
+    Paragraph = 1,
+    ListItem = 2,
+    TableCell = 3,
+    Heading = 4,
+    Quote = 5,
+}
+
+impl LinkBlock {
+    pub fn from_element(name: &str) -> Option<Self> {
+        match name {
+            "p" => Some(Self::Paragraph),
+            "li" | "dt" | "dd" => Some(Self::ListItem),
+            "td" | "th" => Some(Self::TableCell),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Some(Self::Heading),
+            "blockquote" => Some(Self::Quote),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl From<u8> for LinkBlock {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Paragraph,
+            2 => Self::ListItem,
+            3 => Self::TableCell,
+            4 => Self::Heading,
+            5 => Self::Quote,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Where on the source page a link was found. Edges that were inserted without
+/// the position of the link, e.g. links that are not anchors, have the default
+/// metadata where all fields are 0.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct EdgeMetadata {
+    /// Number of elements from the root of the document to the link, including the link itself.
+    pub dom_depth: u16,
+    /// Approximate vertical position of the link, from 0 at the top of the page to
+    /// `u16::MAX` at the bottom. Estimated from the amount of text before the link.
+    pub position: u16,
+    /// Level of the closest heading before the link, from 1 for `h1` to 6 for `h6`,
+    /// or 0 if no heading comes before the link.
+    pub heading_level: u8,
+    /// The block element the link is inside of.
+    pub block: LinkBlock,
+    /// Length of the text that comes before the link in the document.
+    pub text_offset: u32,
+    /// Number of links from other pages on the same host that were collapsed into this edge.
+    pub num_collapsed: u16,
+}
+
+impl EdgeMetadata {
+    /// Whether the position of the link was recorded.
+    pub fn is_known(&self) -> bool {
+        self.dom_depth > 0
+    }
+
+    /// Position of the link between 0.0 at the top and 1.0 at the bottom of the page.
+    pub fn relative_position(&self) -> f64 {
+        self.position as f64 / u16::MAX as f64
+    }
+
+    /// How prominent the link is on the page, between 0.0 and 1.0. Links close to the
+    /// top of the page, shallow in the document and under a high level heading are the
+    /// most prominent. `None` if the position of the link was not recorded.
+    pub fn prominence(&self) -> Option<f64> {
+        if !self.is_known() {
+            return None;
+        }
+
+        let position = 1.0 - self.relative_position();
+        let depth = 1.0 / (1.0 + (self.dom_depth.saturating_sub(1) as f64 / 8.0));
+        let heading = match self.heading_level {
+            0 => 0.5,
+            level => 1.0 - (level.min(6) - 1) as f64 / 6.0,
+        };
+
+        Some((position + depth + heading) / 3.0)
+    }
+
+    /// Weight of the edge, i.e. the number of links it represents.
+    pub fn weight(&self) -> f64 {
+        1.0 + self.num_collapsed as f64
+    }
+
+    /// Metadata of an edge that has been seen with both `self` and `other`.
+    /// The most prominent position of the link is kept.
+    pub fn merge(self, other: Self) -> Self {
+        let num_collapsed = self.num_collapsed.saturating_add(other.num_collapsed);
+
+        let mut kept = match (self.prominence(), other.prominence()) {
+            (Some(a), Some(b)) if b > a => other,
+            (None, Some(_)) => other,
+            _ => self,
+        };
+        kept.num_collapsed = num_collapsed;
+
+        kept
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, bincode::Encode, bincode::Decode)]
 pub struct Edge<L>
 where
@@ -59,6 +246,8 @@ where
     pub to: NodeID,
     pub rel: RelFlags,
     pub label: L,
+    pub timestamps: EdgeTimestamps,
+    pub metadata: EdgeMetadata,
 }
 
 impl<L> Edge<L>
@@ -79,6 +268,8 @@ where
     pub to: FullNodeID,
     pub rel: RelFlags,
     pub label: L,
+    pub timestamps: EdgeTimestamps,
+    pub metadata: EdgeMetadata,
 }
 
 #[cfg(test)]
@@ -92,6 +283,8 @@ where
             to: edge.to.id,
             rel: edge.rel,
             label: edge.label,
+            timestamps: edge.timestamps,
+            metadata: edge.metadata,
         }
     }
 }
@@ -113,6 +306,9 @@ pub struct FullEdge {
     pub from: Node,
     pub to: Node,
     pub label: String,
+    /// Use with [`super::EdgeLimit::After`] to get the edges after this edge.
+    #[schema(value_type = String)]
+    pub cursor: super::EdgeCursor,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -124,6 +320,8 @@ where
     pub to: NodeDatum,
     pub rel: RelFlags,
     pub label: L,
+    pub timestamps: EdgeTimestamps,
+    pub metadata: EdgeMetadata,
 }
 
 impl<L> From<SegmentEdge<L>> for Edge<L>
@@ -136,6 +334,8 @@ where
             to: edge.to.node(),
             rel: edge.rel,
             label: edge.label,
+            timestamps: edge.timestamps,
+            metadata: edge.metadata,
         }
     }
 }
@@ -151,6 +351,8 @@ where
             to: NodeDatum::new(edge.to, 0),
             rel: edge.rel,
             label: edge.label,
+            timestamps: edge.timestamps,
+            metadata: edge.metadata,
         }
     }
 }
@@ -166,24 +368,35 @@ where
             to: NodeDatum::new(edge.to.id, 0),
             rel: edge.rel,
             label: edge.label,
+            timestamps: edge.timestamps,
+            metadata: edge.metadata,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct StoredEdge<L = ()> {
     pub other: NodeDatum,
     pub rel: RelFlags,
+    pub timestamps: EdgeTimestamps,
+    pub metadata: EdgeMetadata,
     pub label: L,
 }
 
 impl StoredEdge<()> {
-    pub fn new(other: NodeDatum, rel: RelFlags) -> Self {
+    pub fn new(other: NodeDatum, rel: RelFlags, timestamps: EdgeTimestamps) -> Self {
         StoredEdge {
             other,
             rel,
+            timestamps,
+            metadata: EdgeMetadata::default(),
             label: (),
         }
     }
+
+    pub fn with_metadata(self, metadata: EdgeMetadata) -> Self {
+        StoredEdge { metadata, ..self }
+    }
 }
 
 impl<L> StoredEdge<L> {
@@ -191,6 +404,8 @@ impl<L> StoredEdge<L> {
         StoredEdge {
             other: self.other,
             rel: self.rel,
+            timestamps: self.timestamps,
+            metadata: self.metadata,
             label,
         }
     }
@@ -0,0 +1,315 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Export of the graph to formats that other graph tools can read, so the graph
+//! can be analysed in e.g. NetworkX or Spark without a reader for the segments.
+//!
+//! The edges are read from the segments in parallel and written by a single
+//! writer, so the order of the edges in the output is not deterministic.
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use rayon::prelude::*;
+
+use super::{Edge, Node, Webgraph};
+use crate::Result;
+
+/// Number of edges that can be waiting for the writer before the readers are blocked.
+const EXPORT_BUFFER_SIZE: usize = 16_384;
+
+/// Number of edges in each row group of the parquet export.
+const PARQUET_CHUNK_SIZE: usize = 65_536;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    GraphMl,
+    Tsv,
+    Parquet,
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ExportFormat::GraphMl => "graphml",
+            ExportFormat::Tsv => "tsv",
+            ExportFormat::Parquet => "parquet",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "graphml" => Ok(ExportFormat::GraphMl),
+            "tsv" => Ok(ExportFormat::Tsv),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(crate::Error::UnknownCLIOption),
+        }
+    }
+}
+
+/// An edge with the names of its nodes.
+pub(super) struct EdgeRow {
+    pub from: Node,
+    pub to: Node,
+    pub label: String,
+    pub rel: u32,
+    pub weight: f64,
+}
+
+impl EdgeRow {
+    /// `None` if one of the nodes is not in the graph.
+    pub fn new(graph: &Webgraph, edge: Edge<String>) -> Option<Self> {
+        Some(EdgeRow {
+            from: graph.id2node(&edge.from)?,
+            to: graph.id2node(&edge.to)?,
+            rel: edge.rel.as_u32(),
+            weight: edge.metadata.weight(),
+            label: edge.label,
+        })
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if c.is_control() => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Tabs and line breaks would split the field or the row.
+fn escape_tsv(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+impl Webgraph {
+    /// Call `f` with every edge of the graph. The edges are read and their nodes looked up
+    /// in parallel while `f` is called from the current thread. Stops at the first error.
+    pub(super) fn for_each_edge_row(&self, mut f: impl FnMut(EdgeRow) -> Result<()>) -> Result<()> {
+        let (tx, rx) = crossbeam_channel::bounded(EXPORT_BUFFER_SIZE);
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                self.par_edges_with_labels()
+                    .filter_map(|edge| EdgeRow::new(self, edge))
+                    // the receiver is only dropped if the export failed
+                    .try_for_each_with(tx, |tx, row| tx.send(row))
+                    .ok();
+            });
+
+            for row in rx {
+                f(row)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Write the graph to `path` in `format`.
+    pub fn export<P: AsRef<Path>>(&self, format: ExportFormat, path: P) -> Result<()> {
+        match format {
+            ExportFormat::GraphMl => self.export_graphml(BufWriter::new(File::create(path)?)),
+            ExportFormat::Tsv => self.export_tsv(BufWriter::new(File::create(path)?)),
+            ExportFormat::Parquet => self.export_parquet(path, PARQUET_CHUNK_SIZE),
+        }
+    }
+
+    /// Write the edges as tab separated values with the columns `from`, `to`, `label`,
+    /// `rel` and `weight`. See [`Webgraph::arrow_schema`] for the meaning of the columns.
+    pub fn export_tsv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "from\tto\tlabel\trel\tweight")?;
+
+        self.for_each_edge_row(|row| {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                escape_tsv(row.from.as_str()),
+                escape_tsv(row.to.as_str()),
+                escape_tsv(&row.label),
+                row.rel,
+                row.weight
+            )?;
+
+            Ok(())
+        })?;
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Write the graph as a directed GraphML graph. The nodes are identified by their id
+    /// and have their url in the `name` attribute. The edges have the attributes `label`,
+    /// `rel` and `weight`.
+    pub fn export_graphml<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            writer,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="label" for="edge" attr.name="label" attr.type="string"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="rel" for="edge" attr.name="rel" attr.type="long"/>"#
+        )?;
+        writeln!(
+            writer,
+            r#"  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>"#
+        )?;
+        writeln!(writer, r#"  <graph id="webgraph" edgedefault="directed">"#)?;
+
+        for (id, node) in self.id2node.iter() {
+            if self.is_deleted(&id) {
+                continue;
+            }
+
+            writeln!(
+                writer,
+                r#"    <node id="n{}"><data key="name">{}</data></node>"#,
+                id.as_u64(),
+                escape_xml(node.as_str())
+            )?;
+        }
+
+        self.for_each_edge_row(|row| {
+            write!(
+                writer,
+                r#"    <edge source="n{}" target="n{}">"#,
+                row.from.id().as_u64(),
+                row.to.id().as_u64()
+            )?;
+            write!(
+                writer,
+                r#"<data key="label">{}</data>"#,
+                escape_xml(&row.label)
+            )?;
+            writeln!(
+                writer,
+                r#"<data key="rel">{}</data><data key="weight">{}</data></edge>"#,
+                row.rel, row.weight
+            )?;
+
+            Ok(())
+        })?;
+
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::tests::{test_edges, test_graph};
+
+    #[test]
+    fn tsv() {
+        let graph = test_graph();
+        let mut out = Vec::new();
+        graph.export_tsv(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+
+        assert_eq!(lines.next(), Some("from\tto\tlabel\trel\tweight"));
+
+        let mut edges: Vec<_> = lines
+            .map(|line| {
+                let columns: Vec<_> = line.split('\t').collect();
+                assert_eq!(columns.len(), 5);
+                (columns[0].to_string(), columns[1].to_string())
+            })
+            .collect();
+        edges.sort();
+
+        let mut expected: Vec<_> = test_edges()
+            .into_iter()
+            .map(|(from, to, _)| (from.as_str().to_string(), to.as_str().to_string()))
+            .collect();
+        expected.sort();
+
+        assert_eq!(edges, expected);
+    }
+
+    #[test]
+    fn graphml() {
+        let graph = test_graph();
+        let mut out = Vec::new();
+        graph.export_graphml(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("<?xml"));
+        assert!(out.trim_end().ends_with("</graphml>"));
+        assert_eq!(out.matches("<edge ").count(), test_edges().len());
+
+        let a = Node::from("A");
+        assert!(out.contains(&format!(
+            r#"<node id="n{}"><data key="name">{}</data></node>"#,
+            a.id().as_u64(),
+            a.as_str()
+        )));
+    }
+
+    #[test]
+    fn formats() {
+        for format in [
+            ExportFormat::GraphMl,
+            ExportFormat::Tsv,
+            ExportFormat::Parquet,
+        ] {
+            assert_eq!(format.to_string().parse::<ExportFormat>().unwrap(), format);
+        }
+
+        assert!("csv".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape_xml(r#"a<b & "c""#), "a&lt;b &amp; &quot;c&quot;");
+        assert_eq!(escape_tsv("a\tb\nc"), "a b c");
+    }
+}
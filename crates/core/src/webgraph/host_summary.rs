@@ -0,0 +1,353 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Aggregated summaries of the edges of a node: the number of edges in each
+//! direction, the most common anchor texts and the hosts that link the most to it.
+//!
+//! Summaries of nodes with many edges are cached in a file in the graph folder and
+//! recomputed for the nodes that are touched by a merge. Other nodes are summarized
+//! when they are requested, as they only have few edges to aggregate.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{DegreeDirection, EdgeLimit, NodeDegree, NodeID, Webgraph};
+use crate::Result;
+
+const HOST_SUMMARIES_FILE: &str = "host_summaries.bin";
+
+/// Nodes with at least this many edges in either direction have their summary cached.
+const MIN_CACHED_EDGES: usize = 1_000;
+
+/// Number of anchor texts and linking hosts kept in a summary.
+const SUMMARY_TOP_K: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct AnchorCount {
+    pub text: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct HostEdgeSummary {
+    pub num_ingoing: usize,
+    pub num_outgoing: usize,
+    /// The most common anchor texts of the ingoing edges, trimmed and lowercased.
+    pub top_anchors: Vec<AnchorCount>,
+    /// The hosts with the most edges to the node.
+    pub top_linking_hosts: Vec<NodeDegree>,
+}
+
+impl HostEdgeSummary {
+    /// Combine the summaries of the same node from different shards. The top lists are
+    /// truncated in each summary, so an entry just outside the top of every shard can be missing.
+    pub fn merge(&mut self, other: HostEdgeSummary) {
+        self.num_ingoing += other.num_ingoing;
+        self.num_outgoing += other.num_outgoing;
+
+        let mut anchors: HashMap<String, usize> = HashMap::new();
+        for anchor in self.top_anchors.drain(..).chain(other.top_anchors) {
+            *anchors.entry(anchor.text).or_default() += anchor.count;
+        }
+        self.top_anchors = top_k_anchors(anchors, SUMMARY_TOP_K);
+
+        let mut hosts: HashMap<NodeID, usize> = HashMap::new();
+        for host in self
+            .top_linking_hosts
+            .drain(..)
+            .chain(other.top_linking_hosts)
+        {
+            *hosts.entry(host.node).or_default() += host.degree;
+        }
+        self.top_linking_hosts = top_k_hosts(hosts, SUMMARY_TOP_K);
+    }
+}
+
+fn top_k_anchors(counts: HashMap<String, usize>, k: usize) -> Vec<AnchorCount> {
+    let mut anchors: Vec<_> = counts
+        .into_iter()
+        .map(|(text, count)| AnchorCount { text, count })
+        .collect();
+
+    anchors.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.text.cmp(&b.text)));
+    anchors.truncate(k);
+
+    anchors
+}
+
+fn top_k_hosts(counts: HashMap<NodeID, usize>, k: usize) -> Vec<NodeDegree> {
+    let mut hosts: Vec<_> = counts
+        .into_iter()
+        .map(|(node, degree)| NodeDegree { node, degree })
+        .collect();
+
+    hosts.sort_by(|a, b| b.degree.cmp(&a.degree).then_with(|| a.node.cmp(&b.node)));
+    hosts.truncate(k);
+
+    hosts
+}
+
+/// The `k` most common anchor texts. The texts are trimmed and lowercased and empty texts are skipped.
+pub fn top_anchor_texts(labels: impl Iterator<Item = String>, k: usize) -> Vec<AnchorCount> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+
+    for label in labels {
+        let label = label.trim().to_lowercase();
+
+        if !label.is_empty() {
+            *counts.entry(label).or_default() += 1;
+        }
+    }
+
+    top_k_anchors(counts, k)
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub(super) struct HostSummaries {
+    summaries: HashMap<NodeID, HostEdgeSummary>,
+}
+
+impl HostSummaries {
+    pub fn path<P: AsRef<Path>>(graph_path: P) -> PathBuf {
+        graph_path.as_ref().join(HOST_SUMMARIES_FILE)
+    }
+
+    /// Open the summaries in the file. A missing file has no summaries.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Self::default());
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        Ok(bincode::decode_from_std_read(
+            &mut reader,
+            bincode::config::standard(),
+        )?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("bin.tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        bincode::encode_into_std_write(self, &mut writer, bincode::config::standard())?;
+        writer.flush()?;
+
+        fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, node: &NodeID) -> Option<&HostEdgeSummary> {
+        self.summaries.get(node)
+    }
+
+    pub fn len(&self) -> usize {
+        self.summaries.len()
+    }
+
+    /// Remove the summaries of the nodes. Returns whether any summary was removed.
+    pub fn invalidate(&mut self, nodes: &HashSet<NodeID>) -> bool {
+        let len = self.summaries.len();
+        self.summaries.retain(|node, _| !nodes.contains(node));
+
+        self.summaries.len() != len
+    }
+}
+
+impl Webgraph {
+    /// The summary of the edges of `node` and its aliases.
+    pub fn host_summary(&self, node: &NodeID) -> HostEdgeSummary {
+        let node = self.canonical_node(node);
+
+        match self.host_summaries.get(&node) {
+            Some(summary) if !self.is_deleted(&node) => summary.clone(),
+            _ => self.summarize(&node),
+        }
+    }
+
+    fn summarize(&self, node: &NodeID) -> HostEdgeSummary {
+        let ingoing = self.raw_ingoing_edges_with_labels(node, EdgeLimit::Unlimited);
+        let num_outgoing = self.raw_outgoing_edges(node, EdgeLimit::Unlimited).len();
+
+        let mut hosts: HashMap<NodeID, usize> = HashMap::new();
+        for edge in &ingoing {
+            if let Some(from) = self.id2node(&edge.from) {
+                *hosts.entry(from.into_host().id()).or_default() += 1;
+            }
+        }
+
+        HostEdgeSummary {
+            num_ingoing: ingoing.len(),
+            num_outgoing,
+            top_linking_hosts: top_k_hosts(hosts, SUMMARY_TOP_K),
+            top_anchors: top_anchor_texts(
+                ingoing.into_iter().map(|edge| edge.label),
+                SUMMARY_TOP_K,
+            ),
+        }
+    }
+
+    fn degree(&self, node: &NodeID, direction: DegreeDirection) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| segment.degree(node, direction))
+            .sum()
+    }
+
+    /// Remove the cached summaries of the nodes before they are changed by a merge,
+    /// so a merge that is interrupted does not leave stale summaries behind.
+    pub(super) fn invalidate_host_summaries(&mut self, nodes: &HashSet<NodeID>) {
+        if self.host_summaries.invalidate(nodes) {
+            self.save_host_summaries();
+        }
+    }
+
+    /// Recompute the cached summaries of the nodes. Only nodes with enough edges are cached.
+    pub(super) fn update_host_summaries(&mut self, nodes: HashSet<NodeID>) {
+        let nodes: HashSet<_> = nodes
+            .into_iter()
+            .map(|node| self.canonical_node(&node))
+            .collect();
+        self.host_summaries.invalidate(&nodes);
+
+        for node in nodes {
+            if self.is_deleted(&node) {
+                continue;
+            }
+
+            let num_edges = self
+                .degree(&node, DegreeDirection::In)
+                .max(self.degree(&node, DegreeDirection::Out));
+
+            if num_edges >= MIN_CACHED_EDGES {
+                let summary = self.summarize(&node);
+                self.host_summaries.summaries.insert(node, summary);
+            }
+        }
+
+        self.save_host_summaries();
+    }
+
+    fn save_host_summaries(&self) {
+        if let Err(err) = self.host_summaries.save(HostSummaries::path(&self.path)) {
+            tracing::warn!("failed to save host summaries: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{Compression, Node, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    fn writer() -> WebgraphWriter {
+        WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        )
+    }
+
+    #[test]
+    fn summaries_are_cached_at_merge() {
+        let popular = Node::from("popular.com");
+        let small = Node::from("small.com");
+
+        let mut a = writer();
+        a.insert(
+            Node::from("a.com"),
+            small.clone(),
+            "Small".to_string(),
+            RelFlags::default(),
+        );
+        let mut graph = a.finalize();
+
+        let mut b = writer();
+        for i in 0..MIN_CACHED_EDGES {
+            let label = if i % 3 == 0 {
+                "Popular site"
+            } else {
+                "popular"
+            };
+
+            b.insert(
+                Node::from(format!("site{i}.com")),
+                popular.clone(),
+                label.to_string(),
+                RelFlags::default(),
+            );
+        }
+        b.insert(
+            popular.clone(),
+            small.clone(),
+            "small site".to_string(),
+            RelFlags::default(),
+        );
+        graph.merge(b.finalize()).unwrap();
+
+        assert_eq!(graph.host_summaries.len(), 1);
+        let summary = graph.host_summaries.get(&popular.id()).unwrap().clone();
+        assert_eq!(graph.host_summary(&popular.id()), summary);
+
+        assert_eq!(summary.num_ingoing, MIN_CACHED_EDGES);
+        assert_eq!(summary.num_outgoing, 1);
+        assert_eq!(
+            summary.top_anchors,
+            vec![
+                AnchorCount {
+                    text: "popular".to_string(),
+                    count: 666
+                },
+                AnchorCount {
+                    text: "popular site".to_string(),
+                    count: 334
+                },
+            ]
+        );
+        assert_eq!(summary.top_linking_hosts.len(), SUMMARY_TOP_K);
+        assert!(summary
+            .top_linking_hosts
+            .iter()
+            .all(|host| host.degree == 1));
+
+        // nodes with few edges are summarized when requested
+        let summary = graph.host_summary(&small.id());
+        assert_eq!(summary.num_ingoing, 2);
+        assert_eq!(summary.num_outgoing, 0);
+        assert_eq!(summary.top_anchors.len(), 2);
+        assert_eq!(summary.top_linking_hosts.len(), 2);
+
+        let mut merged = summary.clone();
+        merged.merge(summary);
+        assert_eq!(merged.num_ingoing, 4);
+        assert_eq!(merged.top_anchors[0].count, 2);
+        assert_eq!(merged.top_linking_hosts[0].degree, 2);
+
+        let path = graph.path.clone();
+        drop(graph);
+        let graph = Webgraph::builder(&path).single_threaded().open();
+        assert_eq!(graph.host_summaries.len(), 1);
+    }
+}
@@ -14,27 +14,200 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/license
 
-use std::path::Path;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    sync::Mutex,
+};
 
 use super::{Node, NodeID};
 
+struct CachedNode {
+    node: Node,
+    last_used: u64,
+}
+
+/// The most recently looked up nodes. A node id is the hash of its node,
+/// so a cached node never has to be invalidated.
+struct NodeCache {
+    capacity: usize,
+    nodes: HashMap<NodeID, CachedNode>,
+    lru: BTreeMap<u64, NodeID>,
+    clock: u64,
+}
+
+impl NodeCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            nodes: HashMap::new(),
+            lru: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, id: &NodeID) -> Option<Node> {
+        self.clock += 1;
+
+        let cached = self.nodes.get_mut(id)?;
+        self.lru.remove(&cached.last_used);
+        cached.last_used = self.clock;
+        self.lru.insert(self.clock, *id);
+
+        Some(cached.node.clone())
+    }
+
+    fn insert(&mut self, id: NodeID, node: Node) {
+        self.clock += 1;
+
+        if let Some(old) = self.nodes.insert(
+            id,
+            CachedNode {
+                node,
+                last_used: self.clock,
+            },
+        ) {
+            self.lru.remove(&old.last_used);
+        }
+        self.lru.insert(self.clock, id);
+
+        while self.nodes.len() > self.capacity {
+            let Some((_, id)) = self.lru.pop_first() else {
+                break;
+            };
+
+            self.nodes.remove(&id);
+        }
+    }
+}
+
+/// The id of each node keyed by the bytes of the node, so the nodes that start
+/// with a prefix are next to each other in the keys of a segment.
+struct NameIndex {
+    db: speedy_kv::Db<Vec<u8>, Vec<u8>>,
+}
+
+impl NameIndex {
+    fn open<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            db: speedy_kv::Db::open_or_create(path).unwrap(),
+        }
+    }
+
+    fn insert(&mut self, id: &NodeID, node: &Node) {
+        self.db.insert_raw(
+            node.as_str().as_bytes().to_vec(),
+            id.as_u64().to_le_bytes().to_vec(),
+        );
+    }
+
+    /// A node that was inserted in more than one segment is only returned once.
+    fn search<'a>(&'a self, prefix: &'a [u8]) -> impl Iterator<Item = NodeID> + 'a {
+        let query = speedy_kv::automaton::ExactMatch(prefix).starts_with();
+        let mut seen = HashSet::new();
+
+        self.db
+            .search_raw(query)
+            .map(|(_, id)| NodeID::from(u64::from_le_bytes(id.as_bytes().try_into().unwrap())))
+            .filter(move |id| seen.insert(*id))
+    }
+}
+
 pub struct Id2NodeDb {
     db: speedy_kv::Db<NodeID, Node>,
+    names: NameIndex,
+    cache: Option<Mutex<NodeCache>>,
 }
 
 impl Id2NodeDb {
     pub fn open<P: AsRef<Path>>(path: P) -> Self {
-        Self {
+        let names_path = path.as_ref().join("names");
+        let build_names = !names_path.exists();
+
+        let mut db = Self {
             db: speedy_kv::Db::open_or_create(path).unwrap(),
+            names: NameIndex::open(names_path),
+            cache: None,
+        };
+
+        // databases that were written before the name index existed
+        if build_names && !db.db.is_empty() {
+            for (id, node) in db.db.iter() {
+                db.names.insert(&id, &node);
+            }
+            db.names.db.commit().unwrap();
         }
+
+        db
+    }
+
+    /// Keep the `capacity` most recently looked up nodes in memory.
+    pub fn enable_cache(&mut self, capacity: usize) {
+        self.cache = Some(Mutex::new(NodeCache::new(capacity)));
     }
 
     pub fn put(&mut self, id: &NodeID, node: &Node) {
         self.db.insert(*id, node.clone()).unwrap();
+        self.names.insert(id, node);
     }
 
     pub fn get(&self, id: &NodeID) -> Option<Node> {
-        self.db.get(id).unwrap()
+        if let Some(cache) = &self.cache {
+            if let Some(node) = cache.lock().unwrap().get(id) {
+                return Some(node);
+            }
+        }
+
+        let node = self.db.get(id).unwrap();
+
+        if let (Some(cache), Some(node)) = (&self.cache, &node) {
+            cache.lock().unwrap().insert(*id, node.clone());
+        }
+
+        node
+    }
+
+    /// The nodes of the ids, in the order of the ids. The ids that are not cached
+    /// are looked up in a single sorted pass over the segments of the database.
+    pub fn get_many(&self, ids: &[NodeID]) -> Vec<Option<Node>> {
+        let mut res = vec![None; ids.len()];
+
+        if let Some(cache) = &self.cache {
+            let mut cache = cache.lock().unwrap();
+            for (id, node) in ids.iter().zip(res.iter_mut()) {
+                *node = cache.get(id);
+            }
+        }
+
+        let missing: Vec<usize> = (0..ids.len()).filter(|&i| res[i].is_none()).collect();
+        if missing.is_empty() {
+            return res;
+        }
+
+        let missing_ids: Vec<NodeID> = missing.iter().map(|&i| ids[i]).collect();
+        let nodes = self.db.get_many(&missing_ids).unwrap();
+
+        let mut cache = self.cache.as_ref().map(|cache| cache.lock().unwrap());
+        for (i, node) in missing.into_iter().zip(nodes) {
+            if let (Some(cache), Some(node)) = (&mut cache, &node) {
+                cache.insert(ids[i], node.clone());
+            }
+
+            res[i] = node;
+        }
+
+        res
+    }
+
+    /// The nodes that start with `prefix`, e.g. `"https://example.com/"` for
+    /// the pages of a host. The nodes are only sorted within each segment.
+    pub fn search_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (Node, NodeID)> + 'a {
+        self.names
+            .search(prefix.as_bytes())
+            .filter_map(|id| self.get(&id).map(|node| (node, id)))
     }
 
     pub fn keys(&self) -> impl Iterator<Item = NodeID> + '_ {
@@ -51,14 +224,17 @@ impl Id2NodeDb {
 
     pub fn merge(&mut self, other: Self) {
         self.db.merge(other.db).unwrap();
+        self.names.db.merge(other.names.db).unwrap();
     }
 
     pub fn flush(&mut self) {
         self.db.commit().unwrap();
+        self.names.db.commit().unwrap();
     }
 
     pub fn optimize_read(&mut self) {
         self.db.merge_all_segments().unwrap();
+        self.names.db.merge_all_segments().unwrap();
     }
 }
 
@@ -90,4 +266,80 @@ mod tests {
         assert_eq!(db.get(&b_id), Some(b_node));
         assert_eq!(db.get(&a_id), Some(a_node));
     }
+
+    #[test]
+    fn test_search_prefix() {
+        let mut db = Id2NodeDb::open(gen_temp_path());
+
+        let nodes = [
+            Node::from("https://a.com/"),
+            Node::from("https://a.com/page"),
+            Node::from("https://ab.com/"),
+            Node::from("https://b.com/"),
+        ];
+
+        db.put(&nodes[0].id(), &nodes[0]);
+        db.put(&nodes[3].id(), &nodes[3]);
+        db.flush();
+
+        db.put(&nodes[1].id(), &nodes[1]);
+        db.put(&nodes[2].id(), &nodes[2]);
+        db.put(&nodes[0].id(), &nodes[0]);
+        db.flush();
+
+        let search = |db: &Id2NodeDb, prefix: &str| {
+            let mut res: Vec<_> = db.search_prefix(prefix).map(|(node, _)| node).collect();
+            res.sort();
+            res
+        };
+
+        assert_eq!(
+            search(&db, "https://a.com/"),
+            vec![nodes[0].clone(), nodes[1].clone()]
+        );
+        assert_eq!(search(&db, "https://a"), nodes[..3].to_vec());
+        assert_eq!(search(&db, "https://c"), vec![]);
+
+        db.optimize_read();
+        assert_eq!(search(&db, "https://"), nodes.to_vec());
+    }
+
+    #[test]
+    fn test_get_many() {
+        let mut db = Id2NodeDb::open(gen_temp_path());
+        db.enable_cache(2);
+
+        let nodes: Vec<_> = (0..4_u64)
+            .map(|i| (NodeID::from(i), Node::from(format!("{i}"))))
+            .collect();
+
+        for (id, node) in &nodes[..2] {
+            db.put(id, node);
+        }
+        db.flush();
+
+        for (id, node) in &nodes[2..] {
+            db.put(id, node);
+        }
+        db.flush();
+
+        assert_eq!(db.get(&nodes[3].0), Some(nodes[3].1.clone()));
+
+        let missing = NodeID::from(42_u64);
+        assert_eq!(
+            db.get_many(&[nodes[2].0, missing, nodes[0].0, nodes[3].0, nodes[1].0]),
+            vec![
+                Some(nodes[2].1.clone()),
+                None,
+                Some(nodes[0].1.clone()),
+                Some(nodes[3].1.clone()),
+                Some(nodes[1].1.clone()),
+            ]
+        );
+
+        let cache = db.cache.as_ref().unwrap().lock().unwrap();
+        assert_eq!(cache.nodes.len(), 2);
+        assert!(cache.nodes.contains_key(&nodes[0].0));
+        assert!(cache.nodes.contains_key(&nodes[1].0));
+    }
 }
@@ -0,0 +1,364 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Import of graphs that were built elsewhere, like the host graphs published by
+//! Common Crawl, so centrality can be computed before our own crawl covers enough
+//! of the web.
+//!
+//! The files are read in parallel and inserted into a [`ConcurrentWebgraphWriter`],
+//! so the import is only parallel when the edges are split over several files.
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context};
+use arrow::array::{Array, StringArray, UInt32Array};
+use flate2::read::MultiGzDecoder;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use rayon::prelude::*;
+
+use super::{Compression, ConcurrentWebgraphWriter, Node, Webgraph};
+use crate::{executor::Executor, webpage::html::links::RelFlags, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Tab separated `from`, `to` and optionally `label` and `rel` columns, like the
+    /// files written by [`Webgraph::export_tsv`].
+    Tsv,
+    /// Parquet files with the schema from [`Webgraph::arrow_schema`]. Only the
+    /// `from` and `to` columns are required.
+    Parquet,
+    /// The host graphs of Common Crawl, where a vertex file maps ids to hosts in
+    /// reverse domain notation (`com.example.www`) and the edge files have the
+    /// tab separated ids of the source and destination.
+    CommonCrawl,
+}
+
+impl Display for ImportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ImportFormat::Tsv => "tsv",
+            ImportFormat::Parquet => "parquet",
+            ImportFormat::CommonCrawl => "common-crawl",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tsv" => Ok(ImportFormat::Tsv),
+            "parquet" => Ok(ImportFormat::Parquet),
+            "common-crawl" => Ok(ImportFormat::CommonCrawl),
+            _ => Err(crate::Error::UnknownCLIOption),
+        }
+    }
+}
+
+/// Lines of a text file, which is decompressed if its name ends with `.gz`.
+fn lines(path: &Path) -> Result<impl Iterator<Item = std::io::Result<String>>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(MultiGzDecoder::new(BufReader::new(file)))
+    } else {
+        Box::new(file)
+    };
+
+    Ok(BufReader::new(reader).lines())
+}
+
+/// `com.example.www` becomes `www.example.com`.
+fn reverse_host(reversed: &str) -> String {
+    reversed.rsplit('.').collect::<Vec<_>>().join(".")
+}
+
+pub struct WebgraphImporter {
+    writer: ConcurrentWebgraphWriter,
+    /// Nodes of the ids in the Common Crawl edge files.
+    vertices: HashMap<u64, Node>,
+}
+
+impl WebgraphImporter {
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        num_shards: usize,
+        compression: Compression,
+    ) -> Result<Self> {
+        Ok(Self {
+            writer: ConcurrentWebgraphWriter::new(
+                path,
+                num_shards,
+                Executor::multi_thread("webgraph-import")?,
+                compression,
+                None,
+            ),
+            vertices: HashMap::new(),
+        })
+    }
+
+    /// Load the Common Crawl vertex files that map the ids of the edge files to hosts.
+    /// All vertices are kept in memory until the importer is finalized.
+    pub fn load_vertices(&mut self, files: &[PathBuf]) -> Result<()> {
+        let vertices: Vec<Vec<(u64, Node)>> = files
+            .par_iter()
+            .map(|path| {
+                let mut vertices = Vec::new();
+
+                for line in lines(path)? {
+                    let line = line?;
+                    let mut columns = line.split('\t');
+
+                    let (Some(id), Some(host)) = (columns.next(), columns.next()) else {
+                        continue;
+                    };
+
+                    let id = id
+                        .parse()
+                        .with_context(|| format!("invalid vertex id {id:?}"))?;
+                    vertices.push((id, Node::from(reverse_host(host))));
+                }
+
+                Ok(vertices)
+            })
+            .collect::<Result<_>>()?;
+
+        self.vertices.extend(vertices.into_iter().flatten());
+        tracing::info!("loaded {} vertices", self.vertices.len());
+
+        Ok(())
+    }
+
+    /// Insert the edges of the files in parallel. Returns the number of inserted edges.
+    pub fn import(&self, format: ImportFormat, files: &[PathBuf]) -> Result<u64> {
+        if format == ImportFormat::CommonCrawl && self.vertices.is_empty() {
+            return Err(anyhow!("the vertices must be loaded before the edges"));
+        }
+
+        files
+            .par_iter()
+            .map(|path| {
+                let num_edges = match format {
+                    ImportFormat::Tsv => self.import_tsv(path),
+                    ImportFormat::Parquet => self.import_parquet(path),
+                    ImportFormat::CommonCrawl => self.import_common_crawl(path),
+                }?;
+
+                self.writer.commit();
+                tracing::info!("imported {num_edges} edges from {}", path.display());
+
+                Ok(num_edges)
+            })
+            .try_reduce(|| 0, |a, b| Ok(a + b))
+    }
+
+    fn import_tsv(&self, path: &Path) -> Result<u64> {
+        let mut num_edges = 0;
+
+        for line in lines(path)? {
+            let line = line?;
+            let columns: Vec<_> = line.split('\t').collect();
+
+            if columns.len() < 2 || columns[..2] == ["from", "to"] {
+                continue;
+            }
+
+            let label = columns.get(2).copied().unwrap_or_default().to_string();
+            let rel = columns
+                .get(3)
+                .and_then(|rel| rel.parse::<u32>().ok())
+                .map(RelFlags::from)
+                .unwrap_or_default();
+
+            self.writer
+                .insert(Node::from(columns[0]), Node::from(columns[1]), label, rel);
+            num_edges += 1;
+        }
+
+        Ok(num_edges)
+    }
+
+    fn import_parquet(&self, path: &Path) -> Result<u64> {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+        let mut num_edges = 0;
+
+        for batch in reader {
+            let batch = batch?;
+
+            let strings = |name: &str| {
+                batch
+                    .column_by_name(name)
+                    .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+            };
+
+            let (Some(from), Some(to)) = (strings("from"), strings("to")) else {
+                return Err(anyhow!(
+                    "{} must have string columns `from` and `to`",
+                    path.display()
+                ));
+            };
+            let label = strings("label");
+            let rel = batch
+                .column_by_name("rel")
+                .and_then(|column| column.as_any().downcast_ref::<UInt32Array>());
+
+            for i in 0..batch.num_rows() {
+                if from.is_null(i) || to.is_null(i) {
+                    continue;
+                }
+
+                let label = label
+                    .filter(|label| !label.is_null(i))
+                    .map(|label| label.value(i).to_string())
+                    .unwrap_or_default();
+                let rel = rel
+                    .filter(|rel| !rel.is_null(i))
+                    .map(|rel| RelFlags::from(rel.value(i)))
+                    .unwrap_or_default();
+
+                self.writer.insert(
+                    Node::from(from.value(i)),
+                    Node::from(to.value(i)),
+                    label,
+                    rel,
+                );
+                num_edges += 1;
+            }
+        }
+
+        Ok(num_edges)
+    }
+
+    fn import_common_crawl(&self, path: &Path) -> Result<u64> {
+        let mut num_edges = 0;
+
+        for line in lines(path)? {
+            let line = line?;
+            let mut columns = line.split('\t').map(|id| id.parse::<u64>().ok());
+
+            let (Some(Some(from)), Some(Some(to))) = (columns.next(), columns.next()) else {
+                continue;
+            };
+
+            let (Some(from), Some(to)) = (self.vertices.get(&from), self.vertices.get(&to)) else {
+                continue;
+            };
+
+            self.writer
+                .insert(from.clone(), to.clone(), String::new(), RelFlags::default());
+            num_edges += 1;
+        }
+
+        Ok(num_edges)
+    }
+
+    /// Write the imported edges to the graph.
+    pub fn finalize(self) -> Result<Webgraph> {
+        self.writer.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression as GzCompression};
+
+    use super::*;
+    use crate::webgraph::{tests::test_graph, EdgeLimit};
+
+    fn write_gz(path: &Path, content: &str) {
+        let mut encoder = GzEncoder::new(File::create(path).unwrap(), GzCompression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    fn num_ingoing(graph: &Webgraph, node: &str) -> usize {
+        graph
+            .raw_ingoing_edges(&Node::from(node).id(), EdgeLimit::Unlimited)
+            .len()
+    }
+
+    #[test]
+    fn tsv_roundtrip() {
+        let dir = crate::gen_temp_path();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let graph = test_graph();
+        let exported = dir.join("edges.tsv");
+        graph.export_tsv(File::create(&exported).unwrap()).unwrap();
+
+        let importer = WebgraphImporter::new(dir.join("graph"), 2, Compression::default()).unwrap();
+        let num_edges = importer.import(ImportFormat::Tsv, &[exported]).unwrap();
+        let imported = importer.finalize().unwrap();
+
+        assert_eq!(num_edges as usize, graph.edges().count());
+        for node in ["A", "B", "C", "D"] {
+            assert_eq!(num_ingoing(&imported, node), num_ingoing(&graph, node));
+        }
+    }
+
+    #[test]
+    fn common_crawl() {
+        let dir = crate::gen_temp_path();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let vertices = dir.join("vertices.txt.gz");
+        write_gz(
+            &vertices,
+            "0\tcom.example\n1\torg.rust-lang.www\n2\tcom.other\n",
+        );
+
+        let edges = [dir.join("edges-0.txt.gz"), dir.join("edges-1.txt")];
+        write_gz(&edges[0], "0\t1\n2\t1\n");
+        std::fs::write(&edges[1], "1\t0\n1\t42\n").unwrap();
+
+        let mut importer =
+            WebgraphImporter::new(dir.join("graph"), 2, Compression::default()).unwrap();
+        assert!(importer.import(ImportFormat::CommonCrawl, &edges).is_err());
+
+        importer.load_vertices(&[vertices]).unwrap();
+        let num_edges = importer.import(ImportFormat::CommonCrawl, &edges).unwrap();
+        let graph = importer.finalize().unwrap();
+
+        // the edge to the unknown vertex 42 is skipped
+        assert_eq!(num_edges, 3);
+        assert_eq!(num_ingoing(&graph, "https://www.rust-lang.org"), 2);
+        assert_eq!(num_ingoing(&graph, "example.com"), 1);
+    }
+
+    #[test]
+    fn formats() {
+        for format in [
+            ImportFormat::Tsv,
+            ImportFormat::Parquet,
+            ImportFormat::CommonCrawl,
+        ] {
+            assert_eq!(format.to_string().parse::<ImportFormat>().unwrap(), format);
+        }
+
+        assert_eq!(reverse_host("com.example.www"), "www.example.com");
+    }
+}
@@ -0,0 +1,194 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Set operations over the backlinks of multiple nodes.
+//! This allows queries like "who links to A but not to B".
+
+use std::collections::{HashMap, HashSet};
+
+use utoipa::ToSchema;
+
+use super::NodeID;
+
+/// Maximum number of backlinks used for each target. Popular hosts have millions of
+/// backlinks, so the sets of those hosts only contain a sample of their backlinks and
+/// the result is marked as [truncated](LinkSet::truncated). One more backlink than this
+/// must be read for each target, so it is known whether the set was capped.
+pub const MAX_BACKLINKS_PER_TARGET: usize = 100_000;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkSetOperation {
+    /// Nodes that link to at least one of the targets.
+    Union,
+    /// Nodes that link to all of the targets.
+    Intersection,
+    /// Nodes that link to the first target but none of the others.
+    Difference,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct LinkSetEntry {
+    pub node: NodeID,
+    /// Number of targets the node links to.
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct LinkSet {
+    pub entries: Vec<LinkSetEntry>,
+    /// Whether some of the targets have more than `max_per_set` backlinks. The entries
+    /// are then computed from a sample of their backlinks, so nodes may be missing from
+    /// a union or intersection and wrongly included in a difference.
+    pub truncated: bool,
+}
+
+/// Combine the backlink sets of each target. `sets` must be in the same order
+/// as the targets of the query, as the first set is special for [`LinkSetOperation::Difference`].
+/// Sets with more than `max_per_set` distinct nodes are reduced to the `max_per_set`
+/// nodes with the lowest ids, so the result is the same no matter how the backlinks
+/// were read.
+///
+/// The entries are sorted by the number of targets each node links to.
+pub fn combine<I, S>(sets: I, op: LinkSetOperation, max_per_set: usize) -> LinkSet
+where
+    I: IntoIterator<Item = S>,
+    S: IntoIterator<Item = NodeID>,
+{
+    let mut counts: HashMap<NodeID, usize> = HashMap::new();
+    let mut first: HashSet<NodeID> = HashSet::new();
+    let mut num_sets = 0;
+    let mut truncated = false;
+
+    for (i, set) in sets.into_iter().enumerate() {
+        num_sets += 1;
+
+        // the same node can appear multiple times in a set if
+        // the edges are spread across several segments or shards.
+        let mut set: Vec<NodeID> = set
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        if set.len() > max_per_set {
+            truncated = true;
+            set.sort();
+            set.truncate(max_per_set);
+        }
+
+        for node in set {
+            if i == 0 {
+                first.insert(node);
+            }
+
+            *counts.entry(node).or_default() += 1;
+        }
+    }
+
+    let mut res: Vec<_> = counts
+        .into_iter()
+        .filter(|(node, count)| match op {
+            LinkSetOperation::Union => true,
+            LinkSetOperation::Intersection => *count == num_sets,
+            LinkSetOperation::Difference => *count == 1 && first.contains(node),
+        })
+        .map(|(node, count)| LinkSetEntry { node, count })
+        .collect();
+
+    res.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.node.cmp(&b.node)));
+
+    LinkSet {
+        entries: res,
+        truncated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[u64]) -> Vec<NodeID> {
+        ids.iter().copied().map(NodeID::from).collect()
+    }
+
+    fn nodes(entries: &[LinkSetEntry]) -> Vec<NodeID> {
+        entries.iter().map(|e| e.node).collect()
+    }
+
+    #[test]
+    fn operations() {
+        let sets = vec![ids(&[1, 2, 3]), ids(&[2, 3, 4]), ids(&[3, 5])];
+
+        let union = combine(sets.clone(), LinkSetOperation::Union, 10).entries;
+        assert_eq!(nodes(&union), ids(&[3, 2, 1, 4, 5]));
+        assert_eq!(union[0].count, 3);
+        assert_eq!(union[1].count, 2);
+
+        let intersection = combine(sets.clone(), LinkSetOperation::Intersection, 10).entries;
+        assert_eq!(nodes(&intersection), ids(&[3]));
+
+        let difference = combine(sets, LinkSetOperation::Difference, 10).entries;
+        assert_eq!(nodes(&difference), ids(&[1]));
+    }
+
+    #[test]
+    fn duplicates_in_set() {
+        let sets = vec![ids(&[1, 1, 2]), ids(&[1])];
+
+        let intersection = combine(sets, LinkSetOperation::Intersection, 10).entries;
+        assert_eq!(
+            intersection,
+            vec![LinkSetEntry {
+                node: NodeID::from(1u64),
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let sets: Vec<Vec<NodeID>> = vec![];
+        let res = combine(sets, LinkSetOperation::Union, 10);
+        assert!(res.entries.is_empty());
+        assert!(!res.truncated);
+    }
+
+    #[test]
+    fn truncated() {
+        let sets = vec![ids(&[4, 1, 3, 2]), ids(&[3, 4])];
+
+        let res = combine(sets.clone(), LinkSetOperation::Difference, 4);
+        assert!(!res.truncated);
+        assert_eq!(nodes(&res.entries), ids(&[1, 2]));
+
+        // 3 and 4 are not in the capped first set
+        let res = combine(sets, LinkSetOperation::Intersection, 2);
+        assert!(res.truncated);
+        assert!(res.entries.is_empty());
+    }
+}
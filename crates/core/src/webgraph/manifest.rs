@@ -0,0 +1,286 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Bookkeeping that makes changes to the segments of a graph all-or-nothing.
+//!
+//! The metadata of the graph is the commit point: a segment is part of the graph
+//! once the metadata refers to it and not before. Before a merge moves any files,
+//! it writes a manifest of the segments it is going to move. If the process
+//! crashes halfway, the merge is completed from the manifest the next time the
+//! graph is opened. Segments that the metadata no longer refers to, e.g. the
+//! inputs of a merge of all segments, are removed after a merge, unless a graph
+//! that is open in this process still reads from them.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::{Meta, SegmentID, Tombstones, Webgraph};
+
+const PENDING_MERGE_FILE: &str = "pending_merge.json";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PendingMerge {
+    /// Path of the graph that is merged into this graph.
+    pub source: String,
+    pub segments: Vec<SegmentID>,
+    pub tombstones: Tombstones,
+}
+
+impl PendingMerge {
+    pub fn new(other: &Webgraph) -> Self {
+        Self {
+            source: other.path.clone(),
+            segments: other.meta.comitted_segments.clone(),
+            tombstones: other.meta.tombstones.clone(),
+        }
+    }
+
+    fn path<P: AsRef<Path>>(graph_path: P) -> PathBuf {
+        graph_path.as_ref().join(PENDING_MERGE_FILE)
+    }
+
+    /// The merge that was in progress in the graph at `graph_path`, if any.
+    pub fn open<P: AsRef<Path>>(graph_path: P) -> io::Result<Option<Self>> {
+        let path = Self::path(graph_path);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json = fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, graph_path: P) -> io::Result<()> {
+        let path = Self::path(graph_path);
+        let tmp_path = path.with_extension("json.tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.into_inner()?.sync_all()?;
+
+        fs::rename(tmp_path, path)
+    }
+
+    pub fn remove<P: AsRef<Path>>(graph_path: P) -> io::Result<()> {
+        let path = Self::path(graph_path);
+
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of open segments for each segment folder in this process.
+static OPEN_SEGMENTS: once_cell::sync::Lazy<Mutex<HashMap<PathBuf, usize>>> =
+    once_cell::sync::Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn segment_key(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Keeps the folder of an open segment from being removed until it is dropped.
+pub struct SegmentRef {
+    path: PathBuf,
+}
+
+impl SegmentRef {
+    pub fn acquire(path: &Path) -> Self {
+        let path = segment_key(path);
+        *OPEN_SEGMENTS
+            .lock()
+            .unwrap()
+            .entry(path.clone())
+            .or_default() += 1;
+
+        Self { path }
+    }
+}
+
+impl Drop for SegmentRef {
+    fn drop(&mut self) {
+        let mut open = OPEN_SEGMENTS.lock().unwrap();
+
+        if let Some(count) = open.get_mut(&self.path) {
+            *count -= 1;
+
+            if *count == 0 {
+                open.remove(&self.path);
+            }
+        }
+    }
+}
+
+/// Whether a graph in this process has the segment in `path` open.
+pub fn is_segment_open(path: &Path) -> bool {
+    OPEN_SEGMENTS
+        .lock()
+        .unwrap()
+        .contains_key(&segment_key(path))
+}
+
+/// Remove the segment folders of the graph at `graph_path` that `meta` doesn't refer to
+/// and that are not open. Open segments are removed by a later merge.
+pub fn remove_unreferenced_segments<P: AsRef<Path>>(graph_path: P, meta: &Meta) -> io::Result<()> {
+    let segments_path = graph_path.as_ref().join("segments");
+
+    if !segments_path.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(segments_path)? {
+        let entry = entry?;
+
+        let is_referenced = entry
+            .file_name()
+            .to_str()
+            .map(|name| meta.comitted_segments.iter().any(|id| id == name))
+            .unwrap_or(false);
+
+        if !is_referenced && entry.file_type()?.is_dir() {
+            if is_segment_open(&entry.path()) {
+                tracing::debug!(
+                    "keeping unreferenced segment {:?} until it is closed",
+                    entry.path()
+                );
+                continue;
+            }
+
+            tracing::info!("removing unreferenced segment {:?}", entry.path());
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{tests::test_graph, EdgeLimit, Node, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    #[test]
+    fn interrupted_merge_is_completed() {
+        let graph = test_graph();
+        let path = graph.path.clone();
+
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Default::default(),
+            None,
+        );
+        writer.insert(
+            Node::from("E"),
+            Node::from("A"),
+            String::new(),
+            RelFlags::default(),
+        );
+        let other = writer.finalize();
+        let other_path = other.path.clone();
+
+        // crash right after the manifest has been written
+        PendingMerge::new(&other).save(&path).unwrap();
+        drop(other);
+        drop(graph);
+
+        let graph = Webgraph::builder(&path).single_threaded().open();
+
+        let a = Node::from("A").id();
+        assert_eq!(graph.raw_ingoing_edges(&a, EdgeLimit::Unlimited).len(), 2);
+        assert_eq!(graph.id2node(&Node::from("E").id()), Some(Node::from("E")));
+        assert!(PendingMerge::open(&path).unwrap().is_none());
+        assert!(!Path::new(&other_path).exists());
+    }
+
+    fn other_graph(from: &str, to: &str) -> Webgraph {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Default::default(),
+            None,
+        );
+        writer.insert(
+            Node::from(from),
+            Node::from(to),
+            String::new(),
+            RelFlags::default(),
+        );
+        writer.finalize()
+    }
+
+    #[test]
+    fn unreferenced_segments_are_removed() {
+        let graph = test_graph();
+        let path = graph.path.clone();
+        drop(graph);
+
+        let orphan = Path::new(&path).join("segments").join("orphan");
+        fs::create_dir_all(&orphan).unwrap();
+
+        // opening a graph never removes segments
+        let mut graph = Webgraph::builder(&path).single_threaded().open();
+        assert!(orphan.exists());
+        assert_eq!(graph.nodes().count(), 4);
+
+        graph.merge(other_graph("E", "A")).unwrap();
+        assert!(!orphan.exists());
+    }
+
+    #[test]
+    fn open_segments_are_kept() {
+        let graph = test_graph();
+        let path = graph.path.clone();
+        drop(graph);
+
+        let reader = Webgraph::builder(&path).single_threaded().open();
+        let old_segments: Vec<_> = reader.segments.iter().map(|s| s.path()).collect();
+
+        let a = Node::from("A").id();
+        let before = reader.raw_ingoing_edges(&a, EdgeLimit::Unlimited).len();
+
+        let mut writer = Webgraph::builder(&path).single_threaded().open();
+        writer.merge(other_graph("E", "A")).unwrap();
+        writer.merge_all_segments().unwrap();
+
+        // the reader still reads from the segments that were merged
+        assert!(old_segments.iter().all(|path| path.exists()));
+        assert_eq!(
+            reader.raw_ingoing_edges(&a, EdgeLimit::Unlimited).len(),
+            before
+        );
+        assert_eq!(
+            writer.raw_ingoing_edges(&a, EdgeLimit::Unlimited).len(),
+            before + 1
+        );
+
+        drop(reader);
+        writer.merge(other_graph("F", "A")).unwrap();
+        assert!(old_segments.iter().all(|path| !path.exists()));
+    }
+}
@@ -65,9 +65,12 @@ impl<O> MergeNode<O> {
 
 impl<O> Ord for MergeNode<O> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // nodes in the fst are sorted by their id,
+        // nodes in the fst are sorted by the bytes of their serialized id,
         // not by their sort key
-        self.node.cmp(&other.node)
+        self.node
+            .as_u64()
+            .to_le_bytes()
+            .cmp(&other.node.as_u64().to_le_bytes())
     }
 }
 impl<O> PartialOrd for MergeNode<O> {
@@ -151,7 +154,8 @@ impl<'a> MergeIter<'a> {
     }
 }
 
-/// Merge multiple iterators of NodeDatum into a single iterator based on the sort key
+/// Merge multiple iterators of NodeDatum into a single iterator based on the sort key.
+/// Edges to the same node are only returned once with their timestamps and metadata merged.
 pub struct EdgeMerger<'a, L = String> {
     iters: MinHeap<file_store::Peekable<Box<dyn Iterator<Item = StoredEdge<L>> + 'a>>>,
 }
@@ -174,12 +178,14 @@ impl<'a, L> Iterator for EdgeMerger<'a, L> {
     type Item = StoredEdge<L>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let res = self.iters.peek_mut().and_then(|mut item| item.0.next());
+        let mut res = self.iters.peek_mut().and_then(|mut item| item.0.next());
 
-        if let Some(edge) = &res {
+        if let Some(edge) = &mut res {
             while let Some(mut peek) = self.iters.peek_mut() {
-                if peek.0.peek().map(|x| x.other.sort_key) == Some(edge.other.sort_key) {
-                    peek.0.next().unwrap();
+                if peek.0.peek().map(|x| &x.other) == Some(&edge.other) {
+                    let duplicate = peek.0.next().unwrap();
+                    edge.timestamps = edge.timestamps.merge(duplicate.timestamps);
+                    edge.metadata = edge.metadata.merge(duplicate.metadata);
                 } else {
                     break;
                 }
@@ -258,22 +264,22 @@ impl<L> Eq for StoredEdge<L> {}
 
 #[cfg(test)]
 mod tests {
-    use crate::webpage::html::links::RelFlags;
+    use crate::{webgraph::EdgeTimestamps, webpage::html::links::RelFlags};
 
     use super::*;
 
     #[test]
     fn test_merge_nodes() {
         let a = vec![
-            MergeNode::new(1u64.into(), EdgeRange::new(0..10, 1), 0..10),
-            MergeNode::new(4u64.into(), EdgeRange::new(0..10, 2), 0..10),
-            MergeNode::new(5u64.into(), EdgeRange::new(0..10, 3), 0..10),
+            MergeNode::new(1u64.into(), EdgeRange::new(0..10, 1, 1), 0..10),
+            MergeNode::new(4u64.into(), EdgeRange::new(0..10, 1, 2), 0..10),
+            MergeNode::new(5u64.into(), EdgeRange::new(0..10, 1, 3), 0..10),
         ];
 
         let b = vec![
-            MergeNode::new(2u64.into(), EdgeRange::new(0..10, 4), 0..10),
-            MergeNode::new(3u64.into(), EdgeRange::new(0..10, 5), 0..10),
-            MergeNode::new(5u64.into(), EdgeRange::new(0..10, 3), 0..10),
+            MergeNode::new(2u64.into(), EdgeRange::new(0..10, 1, 4), 0..10),
+            MergeNode::new(3u64.into(), EdgeRange::new(0..10, 1, 5), 0..10),
+            MergeNode::new(5u64.into(), EdgeRange::new(0..10, 1, 3), 0..10),
         ];
 
         let mut merger = MergeIter::new(vec![a.into_iter(), b.into_iter()]);
@@ -303,18 +309,73 @@ mod tests {
         assert!(!merger.advance(&mut buf));
     }
 
+    #[test]
+    fn test_merge_nodes_key_order() {
+        // the ids are compared by their serialized bytes, so 256 comes before 1
+        let a = vec![
+            MergeNode::new(256u64.into(), EdgeRange::new(0..10, 1, 1), 0..10),
+            MergeNode::new(2u64.into(), EdgeRange::new(0..10, 1, 2), 0..10),
+        ];
+
+        let b = vec![
+            MergeNode::new(256u64.into(), EdgeRange::new(0..10, 1, 1), 0..10),
+            MergeNode::new(1u64.into(), EdgeRange::new(0..10, 1, 3), 0..10),
+        ];
+
+        let mut merger = MergeIter::new(vec![a.into_iter(), b.into_iter()]);
+        let mut buf = Vec::new();
+
+        assert!(merger.advance(&mut buf));
+        assert_eq!(buf.len(), 2);
+        assert_eq!(buf[0].id(), 256u64.into());
+
+        assert!(merger.advance(&mut buf));
+        assert_eq!(buf.len(), 1);
+        assert_eq!(buf[0].id(), 1u64.into());
+
+        assert!(merger.advance(&mut buf));
+        assert_eq!(buf.len(), 1);
+        assert_eq!(buf[0].id(), 2u64.into());
+
+        assert!(!merger.advance(&mut buf));
+    }
+
     #[test]
     fn test_datum_merge() {
         let a = vec![
-            StoredEdge::new(NodeDatum::new(1u64.into(), 1), RelFlags::default()),
-            StoredEdge::new(NodeDatum::new(2u64.into(), 4), RelFlags::default()),
-            StoredEdge::new(NodeDatum::new(3u64.into(), 5), RelFlags::default()),
+            StoredEdge::new(
+                NodeDatum::new(1u64.into(), 1),
+                RelFlags::default(),
+                EdgeTimestamps::default(),
+            ),
+            StoredEdge::new(
+                NodeDatum::new(2u64.into(), 4),
+                RelFlags::default(),
+                EdgeTimestamps::default(),
+            ),
+            StoredEdge::new(
+                NodeDatum::new(3u64.into(), 5),
+                RelFlags::default(),
+                EdgeTimestamps::default(),
+            ),
         ];
 
         let b = vec![
-            StoredEdge::new(NodeDatum::new(4u64.into(), 2), RelFlags::default()),
-            StoredEdge::new(NodeDatum::new(5u64.into(), 3), RelFlags::default()),
-            StoredEdge::new(NodeDatum::new(3u64.into(), 5), RelFlags::default()),
+            StoredEdge::new(
+                NodeDatum::new(4u64.into(), 2),
+                RelFlags::default(),
+                EdgeTimestamps::default(),
+            ),
+            StoredEdge::new(
+                NodeDatum::new(5u64.into(), 3),
+                RelFlags::default(),
+                EdgeTimestamps::default(),
+            ),
+            StoredEdge::new(
+                NodeDatum::new(3u64.into(), 5),
+                RelFlags::default(),
+                EdgeTimestamps::default(),
+            ),
         ];
 
         let mut merger = EdgeMerger::new(vec![a.into_iter(), b.into_iter()]);
@@ -326,4 +387,44 @@ mod tests {
         assert_eq!(merger.next().unwrap().other.sort_key(), 5);
         assert!(merger.next().is_none());
     }
+
+    #[test]
+    fn test_merge_timestamps() {
+        let a = vec![StoredEdge::new(
+            NodeDatum::new(1u64.into(), 0),
+            RelFlags::default(),
+            EdgeTimestamps::at(10),
+        )];
+
+        let b = vec![
+            StoredEdge::new(
+                NodeDatum::new(1u64.into(), 0),
+                RelFlags::default(),
+                EdgeTimestamps::at(20),
+            ),
+            StoredEdge::new(
+                NodeDatum::new(2u64.into(), 0),
+                RelFlags::default(),
+                EdgeTimestamps::at(30),
+            ),
+        ];
+
+        let mut merger = EdgeMerger::new(vec![a.into_iter(), b.into_iter()]);
+
+        let edge = merger.next().unwrap();
+        assert_eq!(edge.other.node(), 1u64.into());
+        assert_eq!(
+            edge.timestamps,
+            EdgeTimestamps {
+                first_seen: 10,
+                last_seen: 20
+            }
+        );
+
+        let edge = merger.next().unwrap();
+        assert_eq!(edge.other.node(), 2u64.into());
+        assert_eq!(edge.timestamps, EdgeTimestamps::at(30));
+
+        assert!(merger.next().is_none());
+    }
 }
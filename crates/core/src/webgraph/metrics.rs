@@ -0,0 +1,130 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Metrics of the read path of a [`Webgraph`](super::Webgraph), so operators can
+//! alert when edge queries get slow, e.g. because the block cache is too small
+//! or because too many segments have piled up between merges.
+
+use std::time::Duration;
+
+use super::DegreeDirection;
+use crate::metrics::{Counter, Histogram, Label, PrometheusRegistry};
+
+#[derive(Clone)]
+pub struct WebgraphMetrics {
+    edges_scanned: Counter,
+    segments_touched: Counter,
+    ingoing_latency: Histogram,
+    outgoing_latency: Histogram,
+    merge_duration: Histogram,
+}
+
+impl Default for WebgraphMetrics {
+    fn default() -> Self {
+        Self {
+            edges_scanned: Counter::default(),
+            segments_touched: Counter::default(),
+            ingoing_latency: Histogram::seconds(),
+            outgoing_latency: Histogram::seconds(),
+            merge_duration: Histogram::new(vec![
+                1.0, 10.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0,
+            ]),
+        }
+    }
+}
+
+impl WebgraphMetrics {
+    /// Record that `num_edges` edges were read from a segment for a query.
+    pub(super) fn record_segment_read(&self, num_edges: usize) {
+        self.segments_touched.inc();
+        self.edges_scanned.add(num_edges as u64);
+    }
+
+    pub(super) fn observe_query(&self, direction: DegreeDirection, elapsed: Duration) {
+        self.latency(direction).observe(elapsed.as_secs_f64());
+    }
+
+    pub(super) fn observe_merge(&self, elapsed: Duration) {
+        self.merge_duration.observe(elapsed.as_secs_f64());
+    }
+
+    fn latency(&self, direction: DegreeDirection) -> &Histogram {
+        match direction {
+            DegreeDirection::In => &self.ingoing_latency,
+            DegreeDirection::Out => &self.outgoing_latency,
+        }
+    }
+
+    pub fn edges_scanned(&self) -> u64 {
+        self.edges_scanned.get()
+    }
+
+    pub fn segments_touched(&self) -> u64 {
+        self.segments_touched.get()
+    }
+
+    pub fn num_queries(&self, direction: DegreeDirection) -> u64 {
+        self.latency(direction).count()
+    }
+
+    pub fn num_merges(&self) -> u64 {
+        self.merge_duration.count()
+    }
+
+    /// Register the metrics in groups prefixed with `{prefix}_`.
+    pub fn register(
+        &self,
+        registry: &mut PrometheusRegistry,
+        prefix: &str,
+    ) -> Result<(), crate::metrics::Error> {
+        registry
+            .new_group(
+                format!("{prefix}_edges_scanned"),
+                Some("Total number of edges read from the segments by queries.".to_string()),
+            )?
+            .register(self.edges_scanned.clone(), vec![]);
+
+        registry
+            .new_group(
+                format!("{prefix}_segments_touched"),
+                Some("Total number of segment reads by queries.".to_string()),
+            )?
+            .register(self.segments_touched.clone(), vec![]);
+
+        let group = registry.new_group(
+            format!("{prefix}_edge_query_seconds"),
+            Some("Latency of the edge queries in seconds.".to_string()),
+        )?;
+        for (direction, val) in [(DegreeDirection::In, "in"), (DegreeDirection::Out, "out")] {
+            group.register(
+                self.latency(direction).clone(),
+                vec![Label {
+                    key: "direction".to_string(),
+                    val: val.to_string(),
+                }],
+            );
+        }
+
+        registry
+            .new_group(
+                format!("{prefix}_merge_seconds"),
+                Some("Duration of the segment merges in seconds.".to_string()),
+            )?
+            .register(self.merge_duration.clone(), vec![]);
+
+        Ok(())
+    }
+}
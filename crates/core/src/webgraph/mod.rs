@@ -13,10 +13,12 @@
 //
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 use std::{fs, io};
 
 use itertools::Itertools;
@@ -24,37 +26,100 @@ use rand::seq::{IteratorRandom, SliceRandom};
 use rayon::prelude::*;
 use uuid::Uuid;
 
+use self::degree::TopDegreeCache;
+use self::host_summary::HostSummaries;
 use self::id_node_db::Id2NodeDb;
+use self::manifest::PendingMerge;
 use self::segment::Segment;
-use crate::executor::Executor;
+use self::stats::GraphStats;
+use self::store::EdgeStore;
+use crate::executor::{Executor, ExecutorMetrics, Priority};
+use crate::metrics::PrometheusRegistry;
+use crate::webpage::html::links::RelFlags;
 
 use crate::Result;
+pub use alias::NodeAliases;
+pub use block_cache::BlockCache;
 pub use builder::WebgraphBuilder;
-pub use compression::Compression;
+pub use community::Communities;
+pub use compaction::CompactionThread;
+pub use compression::{AdjacencyCodec, Codec, Compression};
+pub use concurrent_writer::ConcurrentWebgraphWriter;
+pub use deadline::{CursorBound, Deadline, EdgeQueryResult};
+pub use degree::DegreeDirection;
+pub use dirty::DirtyNodes;
 pub use edge::*;
+pub use export::ExportFormat;
+pub use host_summary::{top_anchor_texts, AnchorCount, HostEdgeSummary};
+pub use import::{ImportFormat, WebgraphImporter};
+pub use link_set::{LinkSet, LinkSetEntry, LinkSetOperation, MAX_BACKLINKS_PER_TARGET};
+pub use metrics::WebgraphMetrics;
 pub use node::*;
+pub use normalization::{DefaultNormalization, NormalizationPolicy};
 pub use shortest_path::ShortestPaths;
+pub use similarity::{
+    cocitation_score, top_candidates, top_similar, CocitationCandidates, SimilarNode,
+    SimilarityConfig,
+};
+pub use subgraph::{Subgraph, SubgraphNode, DEFAULT_MAX_NEIGHBORHOOD_NODES};
+pub use tombstone::Tombstones;
+pub use verify::{verify, VerifyReport};
 pub use writer::WebgraphWriter;
 
+mod alias;
+mod arrow_export;
+mod block_cache;
 mod builder;
 pub mod centrality;
+mod community;
+mod compaction;
 mod compression;
+mod concurrent_writer;
+mod deadline;
+mod degree;
+mod dirty;
 mod edge;
+mod export;
+mod host_summary;
 mod id_node_db;
+mod import;
+mod link_set;
+mod manifest;
 mod merge;
+mod metrics;
 mod node;
+mod normalization;
+mod query_async;
+mod random_walk;
 pub mod remote;
 mod segment;
 mod shortest_path;
+mod similarity;
+mod snapshot;
+pub mod stats;
 mod store;
 mod store_writer;
+mod subgraph;
+mod tombstone;
+mod verify;
+mod wal;
 mod writer;
 
 type SegmentID = String;
 
+/// The pages of a host are first ranked by their raw in-degree, and only the best
+/// `k * TOP_PAGE_CANDIDATES_PER_RESULT` of them get their linking hosts counted.
+const TOP_PAGE_CANDIDATES_PER_RESULT: usize = 4;
+
+/// Maximum number of backlinks read for each candidate when the linking hosts of
+/// the top pages of a host are counted.
+const MAX_TOP_PAGE_BACKLINKS: usize = 10_000;
+
 #[derive(serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Default)]
 struct Meta {
     comitted_segments: Vec<SegmentID>,
+    #[serde(default)]
+    tombstones: Tombstones,
 }
 
 impl Meta {
@@ -73,19 +138,105 @@ impl Meta {
         serde_json::from_str(&buf).unwrap_or_default()
     }
 
+    /// The metadata is written to a temporary file that replaces the old metadata,
+    /// so a crash while saving never leaves a partially written file behind.
     fn save<P: AsRef<Path>>(&self, path: P) {
-        let mut writer = BufWriter::new(
-            File::options()
-                .create(true)
-                .write(true)
-                .read(true)
-                .truncate(true)
-                .open(path)
-                .unwrap(),
-        );
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("json.tmp");
+
+        let file = File::options()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&tmp_path)
+            .unwrap();
+        let mut writer = BufWriter::new(file);
 
         let json = serde_json::to_string_pretty(&self).unwrap();
         writer.write_all(json.as_bytes()).unwrap();
+        writer.into_inner().unwrap().sync_all().unwrap();
+
+        fs::rename(tmp_path, path).unwrap();
+    }
+}
+
+/// Position of an edge in the edges of a node. The edges of a node are ordered by the
+/// sort key and id of the other node, so the cursor of the last edge in a page is
+/// enough to find the start of the next page.
+///
+/// The cursor is serialized as an opaque string.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+#[serde(into = "String", try_from = "String")]
+pub struct EdgeCursor {
+    sort_key: u64,
+    node: NodeID,
+}
+
+impl EdgeCursor {
+    pub fn new(sort_key: u64, node: NodeID) -> Self {
+        Self { sort_key, node }
+    }
+
+    /// Whether the other node of an edge comes after the cursor.
+    fn is_before(&self, other: &merge::NodeDatum) -> bool {
+        (self.sort_key, self.node) < (other.sort_key(), other.node())
+    }
+}
+
+impl From<&merge::NodeDatum> for EdgeCursor {
+    fn from(datum: &merge::NodeDatum) -> Self {
+        Self {
+            sort_key: datum.sort_key(),
+            node: datum.node(),
+        }
+    }
+}
+
+impl std::fmt::Display for EdgeCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}{:016x}", self.sort_key, self.node.as_u64())
+    }
+}
+
+impl std::str::FromStr for EdgeCursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.len() != 32 || !s.is_ascii() {
+            anyhow::bail!("invalid edge cursor");
+        }
+
+        Ok(Self {
+            sort_key: u64::from_str_radix(&s[..16], 16)?,
+            node: NodeID::from(u64::from_str_radix(&s[16..], 16)?),
+        })
+    }
+}
+
+impl From<EdgeCursor> for String {
+    fn from(cursor: EdgeCursor) -> Self {
+        cursor.to_string()
+    }
+}
+
+impl TryFrom<String> for EdgeCursor {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
     }
 }
 
@@ -95,6 +246,16 @@ impl Meta {
 pub enum EdgeLimit {
     Unlimited,
     Limit(usize),
+    /// Skip the first `offset` edges and return at most `limit` of the following edges.
+    Window {
+        offset: usize,
+        limit: usize,
+    },
+    /// At most `limit` edges after the edge with the cursor.
+    After {
+        cursor: EdgeCursor,
+        limit: usize,
+    },
 }
 
 impl EdgeLimit {
@@ -105,8 +266,148 @@ impl EdgeLimit {
         match self {
             EdgeLimit::Unlimited => Box::new(it),
             EdgeLimit::Limit(limit) => Box::new(it.take(*limit)),
+            EdgeLimit::Window { offset, limit } => Box::new(it.skip(*offset).take(*limit)),
+            // the edges before the cursor are skipped when they are read from the segments
+            EdgeLimit::After { limit, .. } => Box::new(it.take(*limit)),
+        }
+    }
+
+    pub fn cursor(&self) -> Option<EdgeCursor> {
+        match self {
+            EdgeLimit::After { cursor, .. } => Some(*cursor),
+            _ => None,
+        }
+    }
+
+    /// The limit for each segment. The window can only be applied
+    /// after the edges from all segments have been merged.
+    fn segment_limit(&self) -> EdgeLimit {
+        match self {
+            EdgeLimit::Window { offset, limit } => EdgeLimit::Limit(offset + limit),
+            limit => *limit,
+        }
+    }
+
+    /// Allow `extra` more edges than the limit.
+    fn extend(self, extra: usize) -> EdgeLimit {
+        match self {
+            EdgeLimit::Unlimited => EdgeLimit::Unlimited,
+            EdgeLimit::Limit(limit) => EdgeLimit::Limit(limit + extra),
+            EdgeLimit::Window { offset, limit } => EdgeLimit::Window {
+                offset,
+                limit: limit + extra,
+            },
+            EdgeLimit::After { cursor, limit } => EdgeLimit::After {
+                cursor,
+                limit: limit + extra,
+            },
+        }
+    }
+}
+
+/// Select edges based on their [`RelFlags`] and when they were last seen. The filter is applied when the
+/// edges are read from the segments, so the labels of excluded edges are not
+/// decompressed and excluded edges do not count towards the [`EdgeLimit`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct EdgeFilter {
+    /// Edges must have all of these flags.
+    pub required: RelFlags,
+    /// Edges must have none of these flags.
+    pub excluded: RelFlags,
+    /// Edges must have been seen in a crawl at or after this unix timestamp.
+    pub seen_since: Option<u32>,
+}
+
+impl EdgeFilter {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    pub fn require(flags: RelFlags) -> Self {
+        Self {
+            required: flags,
+            ..Self::default()
+        }
+    }
+
+    pub fn exclude(flags: RelFlags) -> Self {
+        Self {
+            excluded: flags,
+            ..Self::default()
+        }
+    }
+
+    /// Edges that are not marked as nofollow, sponsored or user generated content.
+    pub fn followed() -> Self {
+        Self::exclude(RelFlags::NOFOLLOW | RelFlags::SPONSORED | RelFlags::UGC)
+    }
+
+    /// Edges from anchors in the main content of the page.
+    pub fn content_area() -> Self {
+        Self::exclude(
+            RelFlags::IS_IN_FOOTER
+                | RelFlags::IS_IN_NAVIGATION
+                | RelFlags::LINK_TAG
+                | RelFlags::SCRIPT_TAG
+                | RelFlags::META_TAG,
+        )
+    }
+
+    /// Edges that have been seen in a crawl at or after the unix timestamp.
+    pub fn seen_since(timestamp: u32) -> Self {
+        Self {
+            seen_since: Some(timestamp),
+            ..Self::default()
+        }
+    }
+
+    pub fn and(self, other: Self) -> Self {
+        Self {
+            required: self.required | other.required,
+            excluded: self.excluded | other.excluded,
+            seen_since: self.seen_since.max(other.seen_since),
         }
     }
+
+    #[inline]
+    pub fn matches(&self, rel: RelFlags) -> bool {
+        rel.contains(self.required) && !rel.intersects(self.excluded)
+    }
+
+    /// An edge that is seen in multiple segments is included if it has
+    /// been seen since the timestamp in any of them.
+    #[inline]
+    fn includes(&self, edge: &StoredEdge) -> bool {
+        self.matches(edge.rel)
+            && self
+                .seen_since
+                .map_or(true, |timestamp| edge.timestamps.seen_since(timestamp))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, bincode::Encode, bincode::Decode)]
+pub struct NodeDegree {
+    pub node: NodeID,
+    pub degree: usize,
+}
+
+/// Remove edges that were loaded from multiple segments. The timestamps and
+/// metadata of the removed edges are merged into the edge that is kept.
+fn dedup_edges<L, F>(edges: &mut Vec<SegmentEdge<L>>, other: F)
+where
+    L: EdgeLabel,
+    F: Fn(&SegmentEdge<L>) -> NodeID,
+{
+    edges.sort_by_key(&other);
+    edges.dedup_by(|removed, kept| {
+        if other(removed) == other(kept) {
+            kept.timestamps = kept.timestamps.merge(removed.timestamps);
+            kept.metadata = kept.metadata.merge(removed.metadata);
+            true
+        } else {
+            false
+        }
+    });
 }
 
 pub struct Webgraph {
@@ -114,7 +415,14 @@ pub struct Webgraph {
     segments: Vec<Segment>,
     executor: Arc<Executor>,
     id2node: Id2NodeDb,
+    aliases: NodeAliases,
+    host_summaries: HostSummaries,
     meta: Meta,
+    /// Used for the segments that are written when segments are merged.
+    compression: Compression,
+    block_cache: Option<Arc<BlockCache>>,
+    normalization: Arc<dyn NormalizationPolicy>,
+    metrics: WebgraphMetrics,
 }
 
 impl Webgraph {
@@ -132,96 +440,325 @@ impl Webgraph {
         self.meta.save(path);
     }
 
-    fn open<P: AsRef<Path>>(path: P, executor: Executor) -> Self {
+    fn open<P: AsRef<Path>>(
+        path: P,
+        executor: Executor,
+        compression: Compression,
+        block_cache: Option<Arc<BlockCache>>,
+        normalization: Arc<dyn NormalizationPolicy>,
+    ) -> Self {
         fs::create_dir_all(&path).unwrap();
         let meta = Self::meta(&path);
 
         fs::create_dir_all(path.as_ref().join("segments")).unwrap();
 
+        let pending = PendingMerge::open(&path).unwrap();
+
         let mut segments = Vec::new();
         for segment in &meta.comitted_segments {
-            segments.push(Segment::open(
-                path.as_ref().join("segments"),
-                segment.clone(),
-            ));
+            segments.push(
+                Segment::open(path.as_ref().join("segments"), segment.clone())
+                    .with_block_cache(block_cache.clone()),
+            );
         }
 
-        Self {
+        let mut graph = Self {
             path: path.as_ref().as_os_str().to_str().unwrap().to_string(),
             segments,
             executor: Arc::new(executor),
             id2node: Id2NodeDb::open(path.as_ref().join("id2node")),
+            aliases: NodeAliases::open(NodeAliases::path(&path)),
+            host_summaries: HostSummaries::open(HostSummaries::path(&path)).unwrap(),
             meta,
+            compression,
+            block_cache,
+            normalization,
+            metrics: WebgraphMetrics::default(),
+        };
+
+        if let Some(pending) = pending {
+            tracing::info!("completing interrupted merge from {}", pending.source);
+            graph.complete_merge(pending).unwrap();
         }
+
+        graph
+    }
+
+    /// The node of `url` with the normalization the graph was opened with.
+    pub fn node(&self, url: &url::Url) -> Node {
+        Node::from_url_with(url, self.normalization.as_ref())
+    }
+
+    fn open_segment<P: AsRef<Path>>(&self, folder_path: P, id: String) -> Segment {
+        Segment::open(folder_path, id).with_block_cache(self.block_cache.clone())
     }
 
     pub fn merge(&mut self, other: Webgraph) -> io::Result<()> {
-        let other_folder = other.path.clone();
-        self.id2node.merge(other.id2node);
-        self.id2node.flush();
+        let start = Instant::now();
+        let touched = self.touched_nodes(&other);
+        self.mark_dirty(&touched);
+        self.invalidate_host_summaries(&touched);
+
+        let pending = PendingMerge::new(&other);
+        pending.save(&self.path)?;
+        drop(other);
+
+        self.complete_merge(pending)?;
+        self.update_host_summaries(touched);
+
+        self.metrics.observe_merge(start.elapsed());
+
+        Ok(())
+    }
 
-        for segment in other.segments {
-            let id = segment.id();
-            let new_path = Path::new(&self.path).join("segments");
-            std::fs::rename(segment.path(), &new_path.join(segment.id())).unwrap();
+    /// Move the nodes and segments of the merged graph into this graph. Each step can be
+    /// repeated, so a merge that was interrupted is completed from its manifest.
+    fn complete_merge(&mut self, pending: PendingMerge) -> io::Result<()> {
+        let source = Path::new(&pending.source);
 
-            self.meta.comitted_segments.push(segment.id());
-            drop(segment);
-            self.segments.push(Segment::open(new_path, id));
+        let id2node_path = source.join("id2node");
+        if id2node_path.exists() {
+            self.id2node.merge(Id2NodeDb::open(id2node_path));
+            self.id2node.flush();
         }
 
-        fs::remove_dir_all(other_folder)?;
+        let aliases_path = NodeAliases::path(source);
+        if aliases_path.exists() {
+            self.aliases.merge(NodeAliases::open(aliases_path));
+        }
+
+        let segments_path = Path::new(&self.path).join("segments");
+        for id in pending.segments {
+            let old_path = source.join("segments").join(&id);
+            if old_path.exists() {
+                fs::rename(old_path, segments_path.join(&id))?;
+            }
+
+            if !self.meta.comitted_segments.contains(&id) {
+                self.meta.comitted_segments.push(id.clone());
+                let segment = self.open_segment(&segments_path, id);
+                self.segments.push(segment);
+            }
+        }
 
+        self.meta.tombstones.merge(pending.tombstones);
         self.save_metadata();
+        PendingMerge::remove(&self.path)?;
+
+        if source.exists() {
+            fs::remove_dir_all(source)?;
+        }
+
+        manifest::remove_unreferenced_segments(&self.path, &self.meta)?;
 
         Ok(())
     }
 
-    pub fn merge_all_segments(&mut self, compression: Compression) -> Result<()> {
+    /// The nodes that gain or lose edges when `other` is merged into the graph.
+    /// The outgoing edges of deleted nodes are hidden after the merge, so their
+    /// neighbours are looked up before the tombstones are merged.
+    fn touched_nodes(&self, other: &Webgraph) -> HashSet<NodeID> {
+        let mut touched = HashSet::new();
+
+        for edge in other.edges() {
+            touched.insert(edge.from);
+            touched.insert(edge.to);
+        }
+
+        for node in other.meta.tombstones.deleted_nodes() {
+            touched.insert(*node);
+            touched.extend(
+                self.raw_outgoing_edges(node, EdgeLimit::Unlimited)
+                    .into_iter()
+                    .map(|edge| edge.to),
+            );
+        }
+
+        for (from, to) in other.meta.tombstones.deleted_edges() {
+            touched.insert(*from);
+            touched.insert(*to);
+        }
+
+        touched
+    }
+
+    /// Record the nodes that are touched by a merge as dirty.
+    ///
+    /// If the existing dirty nodes cannot be read, the file is left as is so the
+    /// next incremental update falls back to a full recomputation.
+    fn mark_dirty(&self, touched: &HashSet<NodeID>) {
+        let mut dirty = match self.dirty_nodes() {
+            Ok(dirty) => dirty,
+            Err(err) => {
+                tracing::warn!("failed to open dirty nodes: {}", err);
+                return;
+            }
+        };
+
+        dirty.extend(touched.iter().copied());
+
+        if let Err(err) = dirty.save(DirtyNodes::path(&self.path)) {
+            tracing::warn!("failed to save dirty nodes: {}", err);
+        }
+    }
+
+    /// Nodes that have gained or lost edges in a merge since the dirty nodes were last cleared.
+    pub fn dirty_nodes(&self) -> Result<DirtyNodes> {
+        DirtyNodes::open(DirtyNodes::path(&self.path))
+    }
+
+    pub fn clear_dirty_nodes(&self) -> Result<()> {
+        let path = DirtyNodes::path(&self.path);
+
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Merge all segments into a single segment. Deleted nodes and edges
+    /// are dropped from the merged segment and their tombstones are removed.
+    ///
+    /// The old segments are only removed once the metadata points to the merged
+    /// segment, so a crash during the merge leaves the graph as it was before.
+    pub fn merge_all_segments(&mut self) -> Result<()> {
+        if self.segments.is_empty() || (self.segments.len() == 1 && self.meta.tombstones.is_empty())
+        {
+            return Ok(());
+        }
+
+        let start = Instant::now();
         let segments = std::mem::take(&mut self.segments);
 
         let id = Uuid::new_v4().to_string();
         let path = Path::new(&self.path).join("segments");
 
-        Segment::merge(segments, compression, &path, id.clone())?;
-        let new_segment = Segment::open(path, id.clone());
+        Segment::merge(
+            segments,
+            self.compression,
+            &path,
+            id.clone(),
+            &self.meta.tombstones,
+            &self.executor,
+        )?;
+        let new_segment = self.open_segment(path, id.clone());
 
         self.segments.push(new_segment);
         self.meta.comitted_segments = vec![id];
+        self.meta.tombstones = Tombstones::default();
 
         self.save_metadata();
 
+        // the old segments are kept while other graphs in the process read from them
+        manifest::remove_unreferenced_segments(&self.path, &self.meta)?;
+
+        self.metrics.observe_merge(start.elapsed());
+
+        Ok(())
+    }
+
+    /// The statistics from the last time [`Webgraph::update_stats`] was called.
+    pub fn stats(&self) -> Option<GraphStats> {
+        GraphStats::open(GraphStats::path(&self.path)).ok()
+    }
+
+    /// Calculate the statistics of the graph and save them in the folder of the graph.
+    /// This reads the degrees of all nodes, so it is only done when asked for and not
+    /// as part of merges.
+    pub fn update_stats(&self) -> Result<GraphStats> {
+        let stats = GraphStats::calculate(self);
+        stats.save(GraphStats::path(&self.path))?;
+
+        Ok(stats)
+    }
+
+    /// Utilization of the executor that runs the queries and merges of the graph.
+    pub fn executor_metrics(&self) -> &ExecutorMetrics {
+        self.executor.metrics()
+    }
+
+    /// Edges scanned, segments touched, query latencies and merge durations of the graph.
+    pub fn metrics(&self) -> &WebgraphMetrics {
+        &self.metrics
+    }
+
+    /// Register the metrics of the graph, its executor and its block cache, if it has one,
+    /// in groups prefixed with `{prefix}_`.
+    pub fn register_metrics(
+        &self,
+        registry: &mut PrometheusRegistry,
+        prefix: &str,
+    ) -> std::result::Result<(), crate::metrics::Error> {
+        self.metrics.register(registry, prefix)?;
+        self.executor_metrics().register(registry, prefix)?;
+
+        if let Some(cache) = &self.block_cache {
+            cache.register_metrics(registry, prefix)?;
+        }
+
         Ok(())
     }
 
     pub fn optimize_read(&mut self) {
         self.executor
-            .map(|s| s.optimize_read(), self.segments.iter_mut())
+            .map_with_priority(
+                Priority::Batch,
+                |s| s.optimize_read(),
+                self.segments.iter_mut(),
+            )
             .unwrap();
 
         self.id2node.optimize_read();
     }
 
-    pub fn ingoing_edges(&self, node: Node, limit: EdgeLimit) -> Vec<FullEdge> {
+    pub fn ingoing_edges(&self, node: Node, limit: EdgeLimit, filter: EdgeFilter) -> Vec<FullEdge> {
+        self.ingoing_edges_until(node, limit, filter, &Deadline::none())
+            .edges
+    }
+
+    /// Like [`Webgraph::ingoing_edges`], but the segments are only read until the deadline
+    /// has passed. The edges that were read by then are returned as a truncated result,
+    /// cut at the last edge read from the slowest segment so paging with the cursor of
+    /// the last edge does not skip any edges.
+    pub fn ingoing_edges_until(
+        &self,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+        deadline: &Deadline,
+    ) -> EdgeQueryResult {
+        let segment_limit = self.segment_limit(&limit);
         let dedup = |edges: &mut Vec<SegmentEdge<String>>| {
-            edges.sort_by_key(|e| e.from.node());
-            edges.dedup_by_key(|e| e.from.node());
+            dedup_edges(edges, |e| e.from.node());
         };
 
+        let bound = CursorBound::default();
+
         let mut edges = self.inner_edges(
-            |segment| segment.ingoing_edges_with_label(&node.id(), &limit),
+            &node.id(),
+            DegreeDirection::In,
+            |segment, node| {
+                let edges =
+                    segment.ingoing_edges_with_label(node, &segment_limit, &filter, deadline);
+
+                if deadline.exceeded() {
+                    bound.add(edges.iter().map(|e| EdgeCursor::from(&e.from)).max());
+                }
+
+                edges
+            },
             dedup,
         );
         edges.sort_by(|a, b| a.from.sort_key().cmp(&b.from.sort_key()));
+        bound.apply(&mut edges, |e| EdgeCursor::from(&e.from));
 
-        limit
-            .apply(edges.into_iter())
-            .map(|e| FullEdge {
-                from: self.id2node(&e.from.node()).unwrap(),
-                to: self.id2node(&e.to.node()).unwrap(),
-                label: e.label,
-            })
-            .collect()
+        let edges: Vec<_> = limit.apply(edges.into_iter()).collect();
+
+        EdgeQueryResult {
+            edges: self.full_edges(edges, |e| EdgeCursor::from(&e.from)),
+            truncated: deadline.exceeded(),
+        }
     }
 
     pub fn pages_by_host(&self, host_node: &NodeID) -> Vec<NodeID> {
@@ -234,6 +771,7 @@ impl Webgraph {
             .unwrap()
             .into_iter()
             .flatten()
+            .filter(|page| !self.is_deleted(page))
             .collect();
 
         pages.sort();
@@ -242,13 +780,148 @@ impl Webgraph {
         pages
     }
 
+    /// The pages with outgoing edges grouped by their host, one host at a time. The hosts
+    /// are ordered by their serialized id, so the hosts of all segments can be merged
+    /// without holding the pages of more than one host in memory.
+    pub fn pages_with_outlinks_by_host(&self) -> impl Iterator<Item = (NodeID, Vec<NodeID>)> + '_ {
+        let key =
+            |host: &NodeID| bincode::encode_to_vec(host, bincode::config::standard()).unwrap();
+
+        self.segments
+            .iter()
+            .map(|segment| segment.pages_with_outlinks_by_host())
+            .kmerge_by(move |(a, _), (b, _)| key(a) < key(b))
+            .coalesce(|(a, mut a_pages), (b, b_pages)| {
+                if a == b {
+                    a_pages.extend(b_pages);
+                    Ok((a, a_pages))
+                } else {
+                    Err(((a, a_pages), (b, b_pages)))
+                }
+            })
+            .map(|(host, mut pages)| {
+                pages.sort();
+                pages.dedup();
+                pages.retain(|page| !self.is_deleted(page));
+
+                (host, pages)
+            })
+    }
+
+    /// Number of ingoing edges for the node summed over all segments.
+    /// Edges that are present in multiple segments are counted multiple times,
+    /// and deleted edges are counted until the segments are merged.
+    pub fn in_degree(&self, node: &NodeID) -> usize {
+        if self.is_deleted(node) {
+            return 0;
+        }
+
+        self.segments
+            .iter()
+            .map(|segment| segment.in_degree(node))
+            .sum()
+    }
+
+    /// The `k` pages from the host that are linked from the most other hosts. The degree
+    /// of a page is the number of distinct hosts linking to it, so links from the host
+    /// itself are not counted and a host linking to the page many times counts once.
+    pub fn top_pages_for_host(&self, host: &NodeID, k: usize) -> Vec<NodeDegree> {
+        let mut candidates: Vec<_> = self
+            .pages_by_host(host)
+            .into_iter()
+            .map(|node| NodeDegree {
+                node,
+                degree: self.in_degree(&node),
+            })
+            .collect();
+
+        candidates.sort_by(|a, b| b.degree.cmp(&a.degree).then_with(|| a.node.cmp(&b.node)));
+        candidates.truncate(k.saturating_mul(TOP_PAGE_CANDIDATES_PER_RESULT));
+
+        let mut pages: Vec<_> = candidates
+            .into_iter()
+            .map(|candidate| NodeDegree {
+                node: candidate.node,
+                degree: self.num_external_linking_hosts(&candidate.node, host),
+            })
+            .filter(|page| page.degree > 0)
+            .collect();
+
+        pages.sort_by(|a, b| b.degree.cmp(&a.degree).then_with(|| a.node.cmp(&b.node)));
+        pages.truncate(k);
+
+        pages
+    }
+
+    /// Number of distinct hosts, other than `host`, with a link to the node.
+    fn num_external_linking_hosts(&self, node: &NodeID, host: &NodeID) -> usize {
+        let sources: Vec<_> = self
+            .raw_ingoing_edges(node, EdgeLimit::Limit(MAX_TOP_PAGE_BACKLINKS))
+            .into_iter()
+            .map(|edge| edge.from)
+            .collect();
+
+        self.id2node_many(&sources)
+            .into_iter()
+            .flatten()
+            .map(|source| source.into_host().id())
+            .filter(|source_host| source_host != host)
+            .unique()
+            .count()
+    }
+
+    /// The `k` nodes with the highest in- or out-degree, sorted by degree. The result is
+    /// cached on disk, so repeated calls are cheap until the graph changes.
+    pub fn top_nodes_by_degree(&self, direction: DegreeDirection, k: usize) -> Vec<NodeDegree> {
+        let cache_path = TopDegreeCache::path(&self.path, direction);
+        let segments = &self.meta.comitted_segments;
+        let num_tombstones = self.meta.tombstones.len();
+
+        if let Some(nodes) = TopDegreeCache::open(&cache_path)
+            .and_then(|cache| cache.get(segments, num_tombstones, k))
+        {
+            return nodes;
+        }
+
+        let nodes = degree::top_nodes(&self.segments, direction, &self.meta.tombstones, k);
+
+        let cache = TopDegreeCache::new(segments.clone(), num_tombstones, k, nodes.clone());
+        if let Err(err) = cache.save(&cache_path) {
+            tracing::warn!("failed to cache top nodes by degree: {}", err);
+        }
+
+        nodes
+    }
+
+    /// Detect communities in the graph with the Louvain method and store them
+    /// in the graph folder. Replaces any previously detected communities.
+    pub fn detect_communities(&self) -> Result<Communities> {
+        Communities::build(self)
+    }
+
+    /// The communities from the last call to [`Webgraph::detect_communities`].
+    pub fn communities(&self) -> Option<Communities> {
+        let path = Communities::path(&self.path);
+
+        if !path.exists() {
+            return None;
+        }
+
+        Communities::open(path).ok()
+    }
+
     pub fn raw_ingoing_edges(&self, node: &NodeID, limit: EdgeLimit) -> Vec<Edge<()>> {
+        let segment_limit = self.segment_limit(&limit);
         let dedup = |edges: &mut Vec<SegmentEdge<()>>| {
-            edges.sort_by_key(|e| e.from.node());
-            edges.dedup_by_key(|e| e.from.node());
+            dedup_edges(edges, |e| e.from.node());
         };
 
-        let mut edges = self.inner_edges(|segment| segment.ingoing_edges(node, &limit), dedup);
+        let mut edges = self.inner_edges(
+            node,
+            DegreeDirection::In,
+            |segment, node| segment.ingoing_edges(node, &segment_limit, &EdgeFilter::all()),
+            dedup,
+        );
         edges.sort_by(|a, b| a.from.sort_key().cmp(&b.from.sort_key()));
 
         limit
@@ -258,6 +931,8 @@ impl Webgraph {
                 to: e.to.node(),
                 label: e.label,
                 rel: e.rel,
+                timestamps: e.timestamps,
+                metadata: e.metadata,
             })
             .collect()
     }
@@ -267,13 +942,22 @@ impl Webgraph {
         node: &NodeID,
         limit: EdgeLimit,
     ) -> Vec<Edge<String>> {
+        let segment_limit = self.segment_limit(&limit);
         let dedup = |edges: &mut Vec<SegmentEdge<String>>| {
-            edges.sort_by_key(|e| e.from.node());
-            edges.dedup_by_key(|e| e.from.node());
+            dedup_edges(edges, |e| e.from.node());
         };
 
         let mut edges = self.inner_edges(
-            |segment| segment.ingoing_edges_with_label(node, &limit),
+            node,
+            DegreeDirection::In,
+            |segment, node| {
+                segment.ingoing_edges_with_label(
+                    node,
+                    &segment_limit,
+                    &EdgeFilter::all(),
+                    &Deadline::none(),
+                )
+            },
             dedup,
         );
         edges.sort_by(|a, b| a.from.sort_key().cmp(&b.from.sort_key()));
@@ -285,6 +969,8 @@ impl Webgraph {
                 to: e.to.node(),
                 label: e.label,
                 rel: e.rel,
+                timestamps: e.timestamps,
+                metadata: e.metadata,
             })
             .collect()
     }
@@ -294,13 +980,22 @@ impl Webgraph {
         node: &NodeID,
         limit: EdgeLimit,
     ) -> Vec<Edge<String>> {
+        let segment_limit = self.segment_limit(&limit);
         let dedup = |edges: &mut Vec<SegmentEdge<String>>| {
-            edges.sort_by_key(|e| e.to.node());
-            edges.dedup_by_key(|e| e.to.node());
+            dedup_edges(edges, |e| e.to.node());
         };
 
         let mut edges = self.inner_edges(
-            |segment| segment.outgoing_edges_with_label(node, &limit),
+            node,
+            DegreeDirection::Out,
+            |segment, node| {
+                segment.outgoing_edges_with_label(
+                    node,
+                    &segment_limit,
+                    &EdgeFilter::all(),
+                    &Deadline::none(),
+                )
+            },
             dedup,
         );
 
@@ -313,39 +1008,89 @@ impl Webgraph {
                 to: e.to.node(),
                 label: e.label,
                 rel: e.rel,
+                timestamps: e.timestamps,
+                metadata: e.metadata,
             })
             .collect()
     }
 
-    pub fn outgoing_edges(&self, node: Node, limit: EdgeLimit) -> Vec<FullEdge> {
+    pub fn outgoing_edges(
+        &self,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+    ) -> Vec<FullEdge> {
+        self.outgoing_edges_until(node, limit, filter, &Deadline::none())
+            .edges
+    }
+
+    /// Like [`Webgraph::outgoing_edges`], but the segments are only read until the deadline
+    /// has passed. The edges that were read by then are returned as a truncated result,
+    /// cut at the last edge read from the slowest segment so paging with the cursor of
+    /// the last edge does not skip any edges.
+    pub fn outgoing_edges_until(
+        &self,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+        deadline: &Deadline,
+    ) -> EdgeQueryResult {
+        let segment_limit = self.segment_limit(&limit);
         let dedup = |edges: &mut Vec<SegmentEdge<String>>| {
-            edges.sort_by_key(|e| e.to.node());
-            edges.dedup_by_key(|e| e.to.node());
+            dedup_edges(edges, |e| e.to.node());
         };
 
+        let bound = CursorBound::default();
+
         let mut edges = self.inner_edges(
-            |segment| segment.outgoing_edges_with_label(&node.id(), &limit),
+            &node.id(),
+            DegreeDirection::Out,
+            |segment, node| {
+                let edges =
+                    segment.outgoing_edges_with_label(node, &segment_limit, &filter, deadline);
+
+                if deadline.exceeded() {
+                    bound.add(edges.iter().map(|e| EdgeCursor::from(&e.to)).max());
+                }
+
+                edges
+            },
             dedup,
         );
         edges.sort_by(|a, b| a.to.sort_key().cmp(&b.to.sort_key()));
+        bound.apply(&mut edges, |e| EdgeCursor::from(&e.to));
 
-        limit
-            .apply(edges.into_iter())
-            .map(|e| FullEdge {
-                from: self.id2node(&e.from.node()).unwrap(),
-                to: self.id2node(&e.to.node()).unwrap(),
-                label: e.label,
-            })
-            .collect()
+        let edges: Vec<_> = limit.apply(edges.into_iter()).collect();
+
+        EdgeQueryResult {
+            edges: self.full_edges(edges, |e| EdgeCursor::from(&e.to)),
+            truncated: deadline.exceeded(),
+        }
     }
 
     pub fn raw_outgoing_edges(&self, node: &NodeID, limit: EdgeLimit) -> Vec<Edge<()>> {
+        self.raw_outgoing_edges_with_filter(node, limit, EdgeFilter::all())
+    }
+
+    /// Outgoing edges that match the filter, e.g. [`EdgeFilter::seen_since`]
+    /// to only get the links that were seen in recent crawls.
+    pub fn raw_outgoing_edges_with_filter(
+        &self,
+        node: &NodeID,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+    ) -> Vec<Edge<()>> {
+        let segment_limit = self.segment_limit(&limit);
         let dedup = |edges: &mut Vec<SegmentEdge<()>>| {
-            edges.sort_by_key(|e| e.to.node());
-            edges.dedup_by_key(|e| e.to.node());
+            dedup_edges(edges, |e| e.to.node());
         };
 
-        let mut edges = self.inner_edges(|segment| segment.outgoing_edges(node, &limit), dedup);
+        let mut edges = self.inner_edges(
+            node,
+            DegreeDirection::Out,
+            |segment, node| segment.outgoing_edges(node, &segment_limit, &filter),
+            dedup,
+        );
         edges.sort_by(|a, b| a.to.sort_key().cmp(&b.to.sort_key()));
 
         limit
@@ -355,35 +1100,194 @@ impl Webgraph {
                 to: e.to.node(),
                 label: e.label,
                 rel: e.rel,
+                timestamps: e.timestamps,
+                metadata: e.metadata,
             })
             .collect()
     }
 
-    fn inner_edges<F1, F2, L>(&self, loader: F1, dedup: F2) -> Vec<SegmentEdge<L>>
+    /// Compute a set operation over the backlinks of `nodes`.
+    pub fn backlink_set(&self, nodes: &[NodeID], op: LinkSetOperation) -> LinkSet {
+        link_set::combine(
+            nodes.iter().map(|node| {
+                self.raw_ingoing_edges(node, EdgeLimit::Limit(MAX_BACKLINKS_PER_TARGET + 1))
+                    .into_iter()
+                    .map(|edge| edge.from)
+            }),
+            op,
+            MAX_BACKLINKS_PER_TARGET,
+        )
+    }
+
+    /// Deleted edges are only filtered out after they have been read from the
+    /// segments, so each segment must return enough edges to fill the limit
+    /// after the deleted edges are removed. A node has at most one edge to
+    /// each other node in a segment.
+    fn segment_limit(&self, limit: &EdgeLimit) -> EdgeLimit {
+        limit.segment_limit().extend(self.meta.tombstones.len())
+    }
+
+    fn is_deleted(&self, node: &NodeID) -> bool {
+        self.meta.tombstones.is_node_deleted(node)
+    }
+
+    /// Edges of `node` in the direction, with the edges of all its aliases. The aliases
+    /// are replaced by their canonical node on both ends of the edges. Only the segments
+    /// whose filters may contain one of the nodes are read.
+    fn inner_edges<F1, F2, L>(
+        &self,
+        node: &NodeID,
+        direction: DegreeDirection,
+        loader: F1,
+        dedup: F2,
+    ) -> Vec<SegmentEdge<L>>
     where
         L: EdgeLabel,
-        F1: Sized + Sync + Fn(&Segment) -> Vec<SegmentEdge<L>>,
+        F1: Sized + Sync + Fn(&Segment, &NodeID) -> Vec<SegmentEdge<L>>,
         F2: Fn(&mut Vec<SegmentEdge<L>>),
     {
-        let mut edges: Vec<_> = self
-            .executor
-            .map(loader, self.segments.iter())
-            .unwrap()
-            .into_iter()
-            .flatten()
+        let group = if self.aliases.is_empty() {
+            vec![*node]
+        } else {
+            self.aliases.group(node)
+        };
+
+        let start = Instant::now();
+
+        let load = |segment: &Segment| -> Vec<SegmentEdge<L>> {
+            let edges: Vec<_> = group
+                .iter()
+                .filter(|node| segment.may_contain(node, direction))
+                .flat_map(|node| loader(segment, node))
+                .collect();
+
+            self.metrics.record_segment_read(edges.len());
+
+            edges
+        };
+
+        let segments: Vec<_> = self
+            .segments
+            .iter()
+            .filter(|segment| {
+                group
+                    .iter()
+                    .any(|node| segment.may_contain(node, direction))
+            })
             .collect();
 
+        let mut edges: Vec<_> = match segments.as_slice() {
+            [] => {
+                self.metrics.observe_query(direction, start.elapsed());
+                return Vec::new();
+            }
+            [segment] => load(segment),
+            _ => self
+                .executor
+                .map(&load, segments.into_iter())
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .collect(),
+        };
+
+        if !self.meta.tombstones.is_empty() {
+            edges.retain(|e| {
+                !self
+                    .meta
+                    .tombstones
+                    .is_edge_deleted(&e.from.node(), &e.to.node())
+            });
+        }
+
+        if !self.aliases.is_empty() {
+            edges = edges
+                .into_iter()
+                .filter_map(|e| self.resolve_aliases(e))
+                .collect();
+        }
+
         dedup(&mut edges);
 
+        self.metrics.observe_query(direction, start.elapsed());
+
         edges
     }
 
+    /// Replace aliases by their canonical node on both ends of the edge. Links between
+    /// aliases of the same page are dropped, as they become links from the page to itself.
+    fn resolve_aliases<L: EdgeLabel>(&self, mut edge: SegmentEdge<L>) -> Option<SegmentEdge<L>> {
+        let from = self.aliases.resolve(&edge.from.node());
+        let to = self.aliases.resolve(&edge.to.node());
+
+        if from == to && edge.from.node() != edge.to.node() {
+            return None;
+        }
+
+        edge.from = merge::NodeDatum::new(from, edge.from.sort_key());
+        edge.to = merge::NodeDatum::new(to, edge.to.sort_key());
+
+        Some(edge)
+    }
+
+    /// The canonical node of `node` if it is an alias of another node.
+    pub fn canonical_node(&self, node: &NodeID) -> NodeID {
+        self.aliases.resolve(node)
+    }
+
     pub fn id2node(&self, id: &NodeID) -> Option<Node> {
+        if self.is_deleted(id) {
+            return None;
+        }
+
         self.id2node.get(id)
     }
 
+    /// Like [`Webgraph::id2node`] for many ids, which are looked up together.
+    pub fn id2node_many(&self, ids: &[NodeID]) -> Vec<Option<Node>> {
+        let mut nodes = self.id2node.get_many(ids);
+
+        for (id, node) in ids.iter().zip(nodes.iter_mut()) {
+            if self.is_deleted(id) {
+                *node = None;
+            }
+        }
+
+        nodes
+    }
+
+    /// Resolve the nodes of the edges with a single batched lookup.
+    fn full_edges(
+        &self,
+        edges: Vec<SegmentEdge<String>>,
+        cursor: impl Fn(&SegmentEdge<String>) -> EdgeCursor,
+    ) -> Vec<FullEdge> {
+        let ids: Vec<_> = edges
+            .iter()
+            .flat_map(|e| [e.from.node(), e.to.node()])
+            .collect();
+        let mut nodes = self.id2node_many(&ids).into_iter();
+
+        edges
+            .into_iter()
+            .map(|e| {
+                let cursor = cursor(&e);
+
+                FullEdge {
+                    from: nodes.next().flatten().unwrap(),
+                    to: nodes.next().flatten().unwrap(),
+                    label: e.label,
+                    cursor,
+                }
+            })
+            .collect()
+    }
+
     pub fn nodes(&self) -> impl Iterator<Item = NodeID> + '_ {
-        self.id2node.keys()
+        let tombstones = &self.meta.tombstones;
+        self.id2node
+            .keys()
+            .filter(move |id| !tombstones.is_node_deleted(id))
     }
 
     pub fn random_nodes_with_outgoing(&self, num: usize) -> Vec<NodeID> {
@@ -398,11 +1302,25 @@ impl Webgraph {
     }
 
     pub fn par_nodes(&self) -> impl ParallelIterator<Item = NodeID> + '_ {
-        self.id2node.keys().par_bridge()
+        self.nodes().par_bridge()
     }
 
     pub fn node_ids(&self) -> impl Iterator<Item = (Node, NodeID)> + '_ {
-        self.id2node.iter().map(|(id, node)| (node, id))
+        self.id2node
+            .iter()
+            .filter(|(id, _)| !self.is_deleted(id))
+            .map(|(id, node)| (node, id))
+    }
+
+    /// The nodes whose url starts with `prefix`, e.g. `"https://example.com/"`.
+    /// The matches are found lazily, so the caller can stop after the first few.
+    pub fn nodes_by_prefix<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (Node, NodeID)> + 'a {
+        self.id2node
+            .search_prefix(prefix)
+            .filter(|(_, id)| !self.is_deleted(id))
     }
 
     pub fn estimate_num_nodes(&self) -> usize {
@@ -413,20 +1331,54 @@ impl Webgraph {
     /// Some edges may be returned multiple times.
     /// This happens if they are present in more than one segment.
     pub fn edges(&self) -> impl Iterator<Item = Edge<()>> + '_ {
+        let tombstones = &self.meta.tombstones;
         self.segments
             .iter()
             .flat_map(|segment| segment.edges().map(|e| e.into()))
+            .filter(move |e: &Edge<()>| !tombstones.is_edge_deleted(&e.from, &e.to))
     }
 
     pub fn par_edges(&self) -> impl ParallelIterator<Item = Edge<()>> + '_ {
+        let tombstones = &self.meta.tombstones;
         self.segments
             .par_iter()
             .flat_map(|segment| segment.edges().par_bridge().map(|e| e.into()))
+            .filter(move |e: &Edge<()>| !tombstones.is_edge_deleted(&e.from, &e.to))
+    }
+
+    /// Every edge of the graph once, ordered by the id of the node it is from (in the byte
+    /// order of the serialized ids, like the segments store them). Edges that are in several
+    /// segments are merged like when the segments are merged, but without writing a new
+    /// segment, so exporters and algorithms that need all the edges of a node together
+    /// can stream the graph.
+    pub fn edges_dedup_sorted(&self) -> impl Iterator<Item = Edge<String>> + '_ {
+        let tombstones = &self.meta.tombstones;
+        let stores = self
+            .segments
+            .iter()
+            .map(|segment| segment.adjacency())
+            .collect();
+
+        EdgeStore::sorted_edges(stores)
+            .map(Edge::from)
+            .filter(move |e| !tombstones.is_edge_deleted(&e.from, &e.to))
+    }
+
+    /// Like [`Webgraph::par_edges`] with the labels of the edges. The segments are read
+    /// in parallel, but the edges of each segment are read by a single thread.
+    pub fn par_edges_with_labels(&self) -> impl ParallelIterator<Item = Edge<String>> + '_ {
+        let tombstones = &self.meta.tombstones;
+        self.segments
+            .par_iter()
+            .flat_map_iter(|segment| segment.edges_with_label().map(Edge::from))
+            .filter(move |e| !tombstones.is_edge_deleted(&e.from, &e.to))
     }
 }
 
 #[cfg(test)]
 pub mod tests {
+    use std::collections::HashMap;
+
     use crate::webpage::html::links::RelFlags;
 
     use super::*;
@@ -480,6 +1432,57 @@ pub mod tests {
         assert_eq!(distances.get(&Node::from("B")), Some(&3));
     }
 
+    #[test]
+    fn shortest_paths() {
+        let graph = test_graph();
+
+        assert_eq!(
+            graph.paths(Node::from("D"), Node::from("B"), 3),
+            vec![vec![
+                Node::from("D"),
+                Node::from("C"),
+                Node::from("A"),
+                Node::from("B")
+            ]]
+        );
+        assert!(graph.paths(Node::from("D"), Node::from("B"), 2).is_empty());
+        assert!(graph.paths(Node::from("A"), Node::from("D"), 10).is_empty());
+        assert_eq!(
+            graph.paths(Node::from("A"), Node::from("A"), 0),
+            vec![vec![Node::from("A")]]
+        );
+
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        for (from, to) in [("A", "B"), ("A", "C"), ("B", "D"), ("C", "D"), ("A", "E")] {
+            writer.insert(
+                Node::from(from),
+                Node::from(to),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        writer.commit();
+        let graph = writer.finalize();
+
+        let mut paths = graph.paths(Node::from("A"), Node::from("D"), 2);
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                vec![Node::from("A"), Node::from("B"), Node::from("D")],
+                vec![Node::from("A"), Node::from("C"), Node::from("D")],
+            ]
+        );
+    }
+
     #[test]
     fn nonexisting_node() {
         let graph = test_graph();
@@ -504,6 +1507,117 @@ pub mod tests {
         assert_eq!(distances.get(&Node::from("B")), Some(&2));
     }
 
+    #[test]
+    fn top_pages_for_host() {
+        let mut graph = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        let a = Node::from("https://a.com/");
+        let popular = Node::from("https://a.com/popular");
+        let other = Node::from("https://a.com/other");
+
+        for from in ["https://b.com/", "https://c.com/", "https://d.com/"] {
+            graph.insert(
+                Node::from(from),
+                popular.clone(),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        for from in ["https://b.com/", "https://c.com/"] {
+            graph.insert(
+                Node::from(from),
+                other.clone(),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        // links from the host itself and repeated links from the same host
+        // do not make a page more popular
+        for from in [
+            "https://a.com/",
+            "https://a.com/1",
+            "https://a.com/2",
+            "https://b.com/1",
+            "https://b.com/2",
+        ] {
+            graph.insert(
+                Node::from(from),
+                other.clone(),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        graph.insert(
+            popular.clone(),
+            a.clone(),
+            String::new(),
+            RelFlags::default(),
+        );
+
+        graph.commit();
+        let graph = graph.finalize();
+
+        let host = a.clone().into_host().id();
+        let top = graph.top_pages_for_host(&host, 2);
+
+        assert_eq!(
+            top,
+            vec![
+                NodeDegree {
+                    node: popular.id(),
+                    degree: 3
+                },
+                NodeDegree {
+                    node: other.id(),
+                    degree: 2
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn backlink_set() {
+        let graph = test_graph();
+        let c = Node::from("C").id();
+        let b = Node::from("B").id();
+
+        let intersection: Vec<_> = graph
+            .backlink_set(&[c, b], LinkSetOperation::Intersection)
+            .entries
+            .into_iter()
+            .map(|e| e.node)
+            .collect();
+        assert_eq!(intersection, vec![Node::from("A").id()]);
+
+        let mut difference: Vec<_> = graph
+            .backlink_set(&[c, b], LinkSetOperation::Difference)
+            .entries
+            .into_iter()
+            .map(|e| e.node)
+            .collect();
+        difference.sort();
+
+        let mut expected = vec![Node::from("B").id(), Node::from("D").id()];
+        expected.sort();
+        assert_eq!(difference, expected);
+
+        let union = graph.backlink_set(&[c, b], LinkSetOperation::Union);
+        assert!(!union.truncated);
+
+        let union = union.entries;
+        assert_eq!(union.len(), 3);
+        assert_eq!(union[0].node, Node::from("A").id());
+        assert_eq!(union[0].count, 2);
+    }
+
     #[test]
     fn merge() {
         let mut graphs = Vec::new();
@@ -540,6 +1654,49 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn edges_dedup_sorted() {
+        let mut graphs = Vec::new();
+        for edges in [
+            vec![("A", "B"), ("B", "C")],
+            vec![("A", "B"), ("C", "A")],
+            vec![("A", "C"), ("B", "C")],
+        ] {
+            let mut wrt = WebgraphWriter::new(
+                crate::gen_temp_path(),
+                Executor::single_thread(),
+                Compression::default(),
+                None,
+            );
+
+            for (from, to) in edges {
+                wrt.insert(
+                    Node::from(from),
+                    Node::from(to),
+                    String::new(),
+                    RelFlags::default(),
+                );
+            }
+
+            graphs.push(wrt.finalize());
+        }
+
+        let mut graph = graphs.pop().unwrap();
+        for other in graphs {
+            graph.merge(other).unwrap();
+        }
+
+        assert_eq!(graph.edges().count(), 6);
+
+        let edges: Vec<_> = graph.edges_dedup_sorted().map(|e| (e.from, e.to)).collect();
+        assert_eq!(edges.len(), 4);
+        assert_eq!(edges.iter().unique().count(), 4);
+
+        // all the edges of a node are next to each other
+        let froms: Vec<_> = edges.iter().map(|(from, _)| *from).dedup().collect();
+        assert_eq!(froms.len(), froms.iter().unique().count());
+    }
+
     #[test]
     fn merge_cycle() {
         let mut graphs = Vec::new();
@@ -570,7 +1727,7 @@ pub mod tests {
             Some(&2)
         );
 
-        graph.merge_all_segments(Compression::default()).unwrap();
+        graph.merge_all_segments().unwrap();
 
         assert_eq!(
             graph.distances(Node::from("A")).get(&Node::from("C")),
@@ -590,6 +1747,22 @@ pub mod tests {
         assert_eq!(n.as_str(), "example.com");
     }
 
+    #[test]
+    fn domain_node() {
+        let domain = |url: &str| Node::from(url).into_domain().as_str().to_string();
+
+        assert_eq!(domain("https://blog.example.com/post"), "example.com");
+        assert_eq!(domain("https://www.example.co.uk"), "example.co.uk");
+        assert_eq!(
+            domain("https://www.example.blogspot.com"),
+            "example.blogspot.com"
+        );
+        assert_eq!(
+            Node::from("https://a.example.com").into_domain().id(),
+            Node::from("https://b.example.com").into_domain().id()
+        );
+    }
+
     #[test]
     fn remove_protocol() {
         let n = Node::from("https://www.example.com/?test");
@@ -617,7 +1790,7 @@ pub mod tests {
 
         assert_eq!(graph.segments.len(), 1);
         assert_eq!(
-            graph.outgoing_edges(Node::from("A"), EdgeLimit::Unlimited)[0].label,
+            graph.outgoing_edges(Node::from("A"), EdgeLimit::Unlimited, EdgeFilter::all())[0].label,
             "a".repeat(MAX_LABEL_LENGTH)
         );
     }
@@ -628,14 +1801,14 @@ pub mod tests {
 
         assert_eq!(
             graph
-                .outgoing_edges(Node::from("A"), EdgeLimit::Unlimited)
+                .outgoing_edges(Node::from("A"), EdgeLimit::Unlimited, EdgeFilter::all())
                 .len(),
             2
         );
 
         assert_eq!(
             graph
-                .outgoing_edges(Node::from("A"), EdgeLimit::Limit(1))
+                .outgoing_edges(Node::from("A"), EdgeLimit::Limit(1), EdgeFilter::all())
                 .len(),
             1
         );
@@ -663,14 +1836,14 @@ pub mod tests {
 
         assert_eq!(
             graph
-                .outgoing_edges(Node::from("A"), EdgeLimit::Unlimited)
+                .outgoing_edges(Node::from("A"), EdgeLimit::Unlimited, EdgeFilter::all())
                 .len(),
             2
         );
 
         assert_eq!(
             graph
-                .outgoing_edges(Node::from("A"), EdgeLimit::Limit(1))
+                .outgoing_edges(Node::from("A"), EdgeLimit::Limit(1), EdgeFilter::all())
                 .len(),
             1
         );
@@ -679,19 +1852,260 @@ pub mod tests {
 
         assert_eq!(
             graph
-                .outgoing_edges(Node::from("A"), EdgeLimit::Unlimited)
+                .outgoing_edges(Node::from("A"), EdgeLimit::Unlimited, EdgeFilter::all())
                 .len(),
             2
         );
 
         assert_eq!(
             graph
-                .outgoing_edges(Node::from("A"), EdgeLimit::Limit(1))
+                .outgoing_edges(Node::from("A"), EdgeLimit::Limit(1), EdgeFilter::all())
                 .len(),
             1
         );
     }
 
+    #[test]
+    fn edge_pagination() {
+        let graph = test_graph();
+        let node = Node::from("C");
+
+        let all = graph.ingoing_edges(node.clone(), EdgeLimit::Unlimited, EdgeFilter::all());
+        assert_eq!(all.len(), 3);
+
+        let window = graph.ingoing_edges(
+            node.clone(),
+            EdgeLimit::Window {
+                offset: 1,
+                limit: 1,
+            },
+            EdgeFilter::all(),
+        );
+        assert_eq!(window, all[1..2]);
+
+        let after = graph.ingoing_edges(
+            node,
+            EdgeLimit::After {
+                cursor: all[0].cursor,
+                limit: 10,
+            },
+            EdgeFilter::all(),
+        );
+        assert_eq!(after, all[1..]);
+
+        let cursor: EdgeCursor = all[0].cursor.to_string().parse().unwrap();
+        assert_eq!(cursor, all[0].cursor);
+    }
+
+    #[test]
+    fn tombstones() {
+        let mut graph = test_graph();
+
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+        writer.delete_node(&Node::from("B"));
+        writer.delete_edge(&Node::from("A"), &Node::from("C"));
+        graph.merge(writer.finalize()).unwrap();
+
+        let check = |graph: &Webgraph| {
+            let a = Node::from("A");
+            let b = Node::from("B");
+            let c = Node::from("C");
+
+            assert!(graph
+                .outgoing_edges(a.clone(), EdgeLimit::Unlimited, EdgeFilter::all())
+                .is_empty());
+            assert!(graph
+                .raw_outgoing_edges(&b.id(), EdgeLimit::Unlimited)
+                .is_empty());
+
+            let ingoing = graph.ingoing_edges(c.clone(), EdgeLimit::Limit(1), EdgeFilter::all());
+            assert_eq!(ingoing.len(), 1);
+            assert_eq!(ingoing[0].from, Node::from("D"));
+
+            assert_eq!(graph.id2node(&b.id()), None);
+            assert_eq!(graph.in_degree(&b.id()), 0);
+            assert!(!graph.nodes().contains(&b.id()));
+            assert!(graph
+                .nodes_by_prefix(b.as_str())
+                .all(|(_, id)| id != b.id()));
+            assert_eq!(graph.edges().count(), 2);
+        };
+
+        check(&graph);
+
+        graph.merge_all_segments().unwrap();
+        assert!(graph.meta.tombstones.is_empty());
+
+        check(&graph);
+    }
+
+    #[test]
+    fn dirty_nodes() {
+        let mut graph = test_graph();
+        assert!(graph.dirty_nodes().unwrap().is_empty());
+
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+        writer.insert(
+            Node::from("D"),
+            Node::from("E"),
+            String::new(),
+            RelFlags::default(),
+        );
+        writer.delete_node(&Node::from("B"));
+        graph.merge(writer.finalize()).unwrap();
+
+        // B -> C is removed together with B
+        let dirty = graph.dirty_nodes().unwrap();
+        let mut expected = vec![
+            Node::from("B").id(),
+            Node::from("C").id(),
+            Node::from("D").id(),
+            Node::from("E").id(),
+        ];
+        expected.sort();
+        assert_eq!(dirty.iter().copied().sorted().collect::<Vec<_>>(), expected);
+
+        graph.clear_dirty_nodes().unwrap();
+        assert!(graph.dirty_nodes().unwrap().is_empty());
+    }
+
+    #[test]
+    fn edge_timestamps() {
+        let writer_at = |timestamp: u32, edges: &[(&str, &str)]| {
+            let mut writer = WebgraphWriter::new(
+                crate::gen_temp_path(),
+                Executor::single_thread(),
+                Compression::default(),
+                None,
+            );
+            writer.set_timestamp(timestamp);
+
+            for (from, to) in edges {
+                writer.insert(
+                    Node::from(*from),
+                    Node::from(*to),
+                    String::new(),
+                    RelFlags::default(),
+                );
+            }
+
+            writer.finalize()
+        };
+
+        let mut graph = writer_at(100, &[("A", "B"), ("A", "C")]);
+        graph.merge(writer_at(200, &[("A", "B")])).unwrap();
+
+        let check = |graph: &Webgraph| {
+            let a = Node::from("A").id();
+
+            let edges: HashMap<_, _> = graph
+                .raw_outgoing_edges(&a, EdgeLimit::Unlimited)
+                .into_iter()
+                .map(|e| (e.to, e.timestamps))
+                .collect();
+
+            assert_eq!(edges.len(), 2);
+            assert_eq!(
+                edges[&Node::from("B").id()],
+                EdgeTimestamps {
+                    first_seen: 100,
+                    last_seen: 200
+                }
+            );
+            assert_eq!(edges[&Node::from("C").id()], EdgeTimestamps::at(100));
+
+            let recent = graph.raw_outgoing_edges_with_filter(
+                &a,
+                EdgeLimit::Unlimited,
+                EdgeFilter::seen_since(150),
+            );
+            assert_eq!(recent.len(), 1);
+            assert_eq!(recent[0].to, Node::from("B").id());
+        };
+
+        check(&graph);
+
+        graph.merge_all_segments().unwrap();
+
+        check(&graph);
+    }
+
+    #[test]
+    fn edge_metadata() {
+        let top = EdgeMetadata {
+            dom_depth: 3,
+            position: 0,
+            heading_level: 1,
+            block: LinkBlock::Heading,
+            text_offset: 0,
+            num_collapsed: 0,
+        };
+        let bottom = EdgeMetadata {
+            dom_depth: 12,
+            position: u16::MAX,
+            heading_level: 0,
+            block: LinkBlock::ListItem,
+            text_offset: 123_456,
+            num_collapsed: 0,
+        };
+        assert!(top.prominence() > bottom.prominence());
+        assert_eq!(EdgeMetadata::default().prominence(), None);
+
+        let writer_with = |edges: &[(&str, &str, EdgeMetadata)]| {
+            let mut writer = WebgraphWriter::new(
+                crate::gen_temp_path(),
+                Executor::single_thread(),
+                Compression::default(),
+                None,
+            );
+
+            for (from, to, metadata) in edges {
+                writer.insert_with_metadata(
+                    Node::from(*from),
+                    Node::from(*to),
+                    String::new(),
+                    RelFlags::default(),
+                    *metadata,
+                );
+            }
+
+            writer.finalize()
+        };
+
+        let mut graph = writer_with(&[("A", "B", bottom), ("A", "C", EdgeMetadata::default())]);
+        graph
+            .merge(writer_with(&[("A", "B", top), ("A", "C", bottom)]))
+            .unwrap();
+
+        let check = |graph: &Webgraph| {
+            let edges: HashMap<_, _> = graph
+                .raw_outgoing_edges(&Node::from("A").id(), EdgeLimit::Unlimited)
+                .into_iter()
+                .map(|e| (e.to, e.metadata))
+                .collect();
+
+            assert_eq!(edges.len(), 2);
+            assert_eq!(edges[&Node::from("B").id()], top);
+            assert_eq!(edges[&Node::from("C").id()], bottom);
+        };
+
+        check(&graph);
+
+        graph.merge_all_segments().unwrap();
+
+        check(&graph);
+    }
+
     #[test]
     fn test_node_normalized() {
         let n = Node::from("http://www.example.com/abc");
@@ -701,6 +2115,129 @@ pub mod tests {
         assert_eq!(n.as_str(), "example.com/abc");
     }
 
+    #[test]
+    fn top_nodes_by_degree() {
+        let mut graph = test_graph();
+        let c = Node::from("C").id();
+
+        let top = graph.top_nodes_by_degree(DegreeDirection::In, 1);
+        assert_eq!(top, vec![NodeDegree { node: c, degree: 3 }]);
+        assert!(TopDegreeCache::path(&graph.path, DegreeDirection::In).exists());
+
+        let top = graph.top_nodes_by_degree(DegreeDirection::In, 10);
+        assert_eq!(
+            top.iter().map(|n| n.degree).collect::<Vec<_>>(),
+            vec![3, 1, 1]
+        );
+
+        // served from the cache
+        assert_eq!(graph.top_nodes_by_degree(DegreeDirection::In, 2), top[..2]);
+
+        let top = graph.top_nodes_by_degree(DegreeDirection::Out, 1);
+        assert_eq!(
+            top,
+            vec![NodeDegree {
+                node: Node::from("A").id(),
+                degree: 2
+            }]
+        );
+
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+        writer.delete_node(&Node::from("C"));
+        graph.merge(writer.finalize()).unwrap();
+
+        let top = graph.top_nodes_by_degree(DegreeDirection::In, 1);
+        assert_eq!(top.len(), 1);
+        assert_ne!(top[0].node, c);
+        assert_eq!(top[0].degree, 1);
+    }
+
+    #[test]
+    fn communities_are_stored() {
+        let graph = test_graph();
+        assert!(graph.communities().is_none());
+
+        let detected = graph.detect_communities().unwrap();
+        assert_eq!(detected.len(), 4);
+
+        let stored = graph.communities().unwrap();
+        for node in graph.nodes() {
+            let community = stored.get(&node).unwrap();
+            assert!(community.is_some());
+            assert_eq!(community, detected.get(&node).unwrap());
+        }
+    }
+
+    #[test]
+    fn graph_stats() {
+        let graph = test_graph();
+        assert!(graph.stats().is_none());
+
+        graph.update_stats().unwrap();
+        let stats = graph.stats().unwrap();
+
+        assert_eq!(stats.num_nodes, 4);
+        assert_eq!(stats.num_edges, 5);
+        assert_eq!(stats.in_degrees.buckets, vec![1, 2, 1]);
+        assert_eq!(stats.out_degrees.buckets, vec![0, 3, 1]);
+        assert_eq!(stats.reciprocity, Some(2.0 / 5.0));
+        assert_eq!(stats.avg_path_length, Some(14.0 / 9.0));
+    }
+
+    #[test]
+    fn edge_query_deadline() {
+        let graph = test_graph();
+        let node = Node::from("C");
+
+        let res = graph.ingoing_edges_until(
+            node.clone(),
+            EdgeLimit::Unlimited,
+            EdgeFilter::all(),
+            &Deadline::after(std::time::Duration::from_secs(60)),
+        );
+        assert!(!res.truncated);
+        assert_eq!(
+            res.edges.len(),
+            graph
+                .ingoing_edges(node.clone(), EdgeLimit::Unlimited, EdgeFilter::all())
+                .len()
+        );
+
+        let res = graph.ingoing_edges_until(
+            node,
+            EdgeLimit::Unlimited,
+            EdgeFilter::all(),
+            &Deadline::at(std::time::Instant::now()),
+        );
+        assert!(res.truncated);
+        assert!(res.edges.is_empty());
+    }
+
+    #[test]
+    fn query_metrics() {
+        let graph = test_graph();
+        assert_eq!(graph.metrics().segments_touched(), 0);
+
+        let edges = graph.ingoing_edges(Node::from("C"), EdgeLimit::Unlimited, EdgeFilter::all());
+        assert!(!edges.is_empty());
+
+        let metrics = graph.metrics();
+        assert!(metrics.segments_touched() > 0);
+        assert!(metrics.edges_scanned() >= edges.len() as u64);
+        assert_eq!(metrics.num_queries(DegreeDirection::In), 1);
+        assert_eq!(metrics.num_queries(DegreeDirection::Out), 0);
+
+        let mut registry = PrometheusRegistry::default();
+        graph.register_metrics(&mut registry, "webgraph").unwrap();
+        let output = registry.to_string();
+        assert!(output.contains("webgraph_edge_query_seconds_count{direction=\"in\"} 1"));
+    }
+
     #[test]
     fn test_rel_flags() {
         let mut writer = WebgraphWriter::new(
@@ -724,4 +2261,55 @@ pub mod tests {
             RelFlags::IS_IN_FOOTER | RelFlags::TAG,
         );
     }
+
+    #[test]
+    fn insert_policy() {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        )
+        .with_insert_policy(crate::config::WebgraphInsertPolicy {
+            max_outlinks_per_node: Some(2),
+            blocked_tlds: vec![".zip".to_string()],
+            collapse_boilerplate: true,
+        });
+
+        for to in ["a.com/1", "spam.zip/", "a.com/2", "a.com/3"] {
+            writer.insert(
+                Node::from("a.com/"),
+                Node::from(to),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        for from in ["a.com/1", "a.com/2", "a.com/3"] {
+            writer.insert(
+                Node::from(from),
+                Node::from("b.com/about"),
+                String::new(),
+                RelFlags::IS_IN_FOOTER,
+            );
+        }
+
+        let graph = writer.finalize();
+
+        let outgoing: Vec<_> = graph
+            .raw_outgoing_edges(&Node::from("a.com/").id(), EdgeLimit::Unlimited)
+            .into_iter()
+            .map(|edge| edge.to)
+            .collect();
+        assert_eq!(outgoing.len(), 2);
+        assert!(!outgoing.contains(&Node::from("spam.zip/").id()));
+        assert!(!outgoing.contains(&Node::from("a.com/3").id()));
+        assert!(graph.id2node(&Node::from("spam.zip/").id()).is_none());
+
+        let ingoing =
+            graph.raw_ingoing_edges(&Node::from("b.com/about").id(), EdgeLimit::Unlimited);
+        assert_eq!(ingoing.len(), 1);
+        assert_eq!(ingoing[0].metadata.num_collapsed, 2);
+        assert_eq!(ingoing[0].metadata.weight(), 3.0);
+    }
 }
@@ -18,6 +18,7 @@ use bloom::fast_stable_hash_128;
 use url::Url;
 use utoipa::ToSchema;
 
+use super::NormalizationPolicy;
 use crate::{intmap, webpage::url_ext::UrlExt};
 
 #[derive(
@@ -129,6 +130,31 @@ impl Node {
         }
     }
 
+    /// The registrable domain of the node according to the public suffix list,
+    /// e.g. `example.com` for `blog.example.com` and `example.blogspot.com`
+    /// for `www.example.blogspot.com`.
+    pub fn into_domain(self) -> Node {
+        let url = if self.name.contains("://") {
+            Url::parse(&self.name)
+        } else {
+            Url::parse(&("http://".to_string() + self.name.as_str()))
+        };
+
+        let name = url
+            .ok()
+            .and_then(|url| url.root_domain().map(|domain| domain.to_string()))
+            .unwrap_or_default();
+
+        Node { name }
+    }
+
+    /// The node of the url as normalized by `policy`.
+    pub fn from_url_with(url: &Url, policy: &dyn NormalizationPolicy) -> Node {
+        Node {
+            name: policy.normalize(url),
+        }
+    }
+
     pub fn as_str(&self) -> &str {
         self.name.as_str()
     }
@@ -173,6 +199,11 @@ pub fn normalize_url(url: &Url) -> String {
     let mut url = url.clone();
     url.normalize();
 
+    strip_scheme(&url)
+}
+
+/// The url without its scheme, `www.` prefix and trailing slash.
+pub(super) fn strip_scheme(url: &Url) -> String {
     let scheme = url.scheme();
     let mut normalized = url
         .as_str()
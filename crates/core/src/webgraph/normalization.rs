@@ -0,0 +1,133 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! How urls are turned into the names of nodes.
+//!
+//! Two urls that normalize to the same name are the same node in the graph, so a
+//! graph must be queried with the normalization it was built with.
+
+use url::Url;
+
+use super::node::{normalize_url, strip_scheme};
+use crate::{
+    config::{QueryNormalization, WebgraphNormalizationConfig},
+    webpage::url_ext::UrlExt,
+};
+
+pub trait NormalizationPolicy: Send + Sync {
+    /// The name of the node of `url`.
+    fn normalize(&self, url: &Url) -> String;
+}
+
+/// The normalization of `Node::from`. The scheme, `www.` prefix, fragment, trailing
+/// slash and tracking parameters are removed.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultNormalization;
+
+impl NormalizationPolicy for DefaultNormalization {
+    fn normalize(&self, url: &Url) -> String {
+        normalize_url(url)
+    }
+}
+
+impl NormalizationPolicy for WebgraphNormalizationConfig {
+    fn normalize(&self, url: &Url) -> String {
+        let mut url = url.clone();
+
+        match self.query {
+            QueryNormalization::Clean => url.normalize(),
+            QueryNormalization::Keep => url.set_fragment(None),
+            QueryNormalization::Strip => {
+                url.set_fragment(None);
+                url.set_query(None);
+            }
+        }
+
+        let alias = url.host_str().and_then(|host| {
+            self.host_aliases
+                .get(host)
+                .or_else(|| self.host_aliases.get(host.strip_prefix("www.")?))
+        });
+
+        if let Some(alias) = alias.cloned() {
+            if url.set_host(Some(&alias)).is_err() {
+                tracing::warn!("invalid host alias {alias}");
+            }
+        }
+
+        if self.lowercase_path {
+            let path = url.path().to_lowercase();
+            url.set_path(&path);
+        }
+
+        strip_scheme(&url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::Node;
+
+    fn node(url: &str, policy: &dyn NormalizationPolicy) -> String {
+        Node::from_url_with(&Url::parse(url).unwrap(), policy)
+            .as_str()
+            .to_string()
+    }
+
+    #[test]
+    fn default_matches_node_from() {
+        let config = WebgraphNormalizationConfig::default();
+
+        for url in [
+            "https://www.example.com/",
+            "https://example.com/Path?utm_source=a&q=1#top",
+            "http://sub.example.com/a/b/",
+        ] {
+            let expected = Node::from(url).as_str().to_string();
+
+            assert_eq!(node(url, &DefaultNormalization), expected);
+            assert_eq!(node(url, &config), expected);
+        }
+    }
+
+    #[test]
+    fn custom_normalization() {
+        let config = WebgraphNormalizationConfig {
+            query: QueryNormalization::Keep,
+            lowercase_path: true,
+            host_aliases: [("m.example.com".to_string(), "example.com".to_string())]
+                .into_iter()
+                .collect(),
+        };
+
+        assert_eq!(
+            node("https://m.example.com/Some/Page?utm_source=a#top", &config),
+            "example.com/some/page?utm_source=a"
+        );
+        assert_eq!(node("https://www.m.example.com/", &config), "example.com");
+
+        let config = WebgraphNormalizationConfig {
+            query: QueryNormalization::Strip,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            node("https://example.com/Page?q=1", &config),
+            "example.com/Page"
+        );
+    }
+}
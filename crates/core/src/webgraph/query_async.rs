@@ -0,0 +1,182 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Async variants of the queries on a [`Webgraph`].
+//!
+//! The queries read from memory mapped files and can block on disk IO for a
+//! long time, so they run on tokio's blocking pool instead of a worker thread.
+
+use std::{panic, sync::Arc};
+
+use super::{
+    CocitationCandidates, Deadline, Edge, EdgeFilter, EdgeLimit, EdgeQueryResult, FullEdge,
+    HostEdgeSummary, Node, NodeDegree, NodeID, SimilarityConfig, Webgraph,
+};
+
+impl Webgraph {
+    async fn run_blocking<F, T>(self: &Arc<Self>, f: F) -> T
+    where
+        F: FnOnce(&Webgraph) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let graph = Arc::clone(self);
+
+        tokio::task::spawn_blocking(move || f(&graph))
+            .await
+            .unwrap_or_else(|err| panic::resume_unwind(err.into_panic()))
+    }
+
+    pub async fn id2node_async(self: &Arc<Self>, id: NodeID) -> Option<Node> {
+        self.run_blocking(move |graph| graph.id2node(&id)).await
+    }
+
+    pub async fn ingoing_edges_async(
+        self: &Arc<Self>,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+    ) -> Vec<FullEdge> {
+        self.run_blocking(move |graph| graph.ingoing_edges(node, limit, filter))
+            .await
+    }
+
+    pub async fn outgoing_edges_async(
+        self: &Arc<Self>,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+    ) -> Vec<FullEdge> {
+        self.run_blocking(move |graph| graph.outgoing_edges(node, limit, filter))
+            .await
+    }
+
+    pub async fn ingoing_edges_until_async(
+        self: &Arc<Self>,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+        deadline: Deadline,
+    ) -> EdgeQueryResult {
+        self.run_blocking(move |graph| graph.ingoing_edges_until(node, limit, filter, &deadline))
+            .await
+    }
+
+    pub async fn outgoing_edges_until_async(
+        self: &Arc<Self>,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+        deadline: Deadline,
+    ) -> EdgeQueryResult {
+        self.run_blocking(move |graph| graph.outgoing_edges_until(node, limit, filter, &deadline))
+            .await
+    }
+
+    pub async fn raw_ingoing_edges_async(
+        self: &Arc<Self>,
+        node: NodeID,
+        limit: EdgeLimit,
+    ) -> Vec<Edge<()>> {
+        self.run_blocking(move |graph| graph.raw_ingoing_edges(&node, limit))
+            .await
+    }
+
+    pub async fn raw_outgoing_edges_async(
+        self: &Arc<Self>,
+        node: NodeID,
+        limit: EdgeLimit,
+    ) -> Vec<Edge<()>> {
+        self.run_blocking(move |graph| graph.raw_outgoing_edges(&node, limit))
+            .await
+    }
+
+    pub async fn raw_ingoing_edges_with_labels_async(
+        self: &Arc<Self>,
+        node: NodeID,
+        limit: EdgeLimit,
+    ) -> Vec<Edge<String>> {
+        self.run_blocking(move |graph| graph.raw_ingoing_edges_with_labels(&node, limit))
+            .await
+    }
+
+    pub async fn raw_outgoing_edges_with_labels_async(
+        self: &Arc<Self>,
+        node: NodeID,
+        limit: EdgeLimit,
+    ) -> Vec<Edge<String>> {
+        self.run_blocking(move |graph| graph.raw_outgoing_edges_with_labels(&node, limit))
+            .await
+    }
+
+    pub async fn pages_by_hosts_async(self: &Arc<Self>, hosts: Vec<NodeID>) -> Vec<Vec<NodeID>> {
+        self.run_blocking(move |graph| hosts.iter().map(|host| graph.pages_by_host(host)).collect())
+            .await
+    }
+
+    pub async fn top_pages_for_host_async(
+        self: &Arc<Self>,
+        host: NodeID,
+        k: usize,
+    ) -> Vec<NodeDegree> {
+        self.run_blocking(move |graph| graph.top_pages_for_host(&host, k))
+            .await
+    }
+
+    pub async fn cocitation_candidates_async(
+        self: &Arc<Self>,
+        node: NodeID,
+        config: SimilarityConfig,
+    ) -> CocitationCandidates {
+        self.run_blocking(move |graph| graph.cocitation_candidates(&node, &config))
+            .await
+    }
+
+    pub async fn in_degrees_async(self: &Arc<Self>, nodes: Vec<NodeID>) -> Vec<usize> {
+        self.run_blocking(move |graph| nodes.iter().map(|node| graph.in_degree(node)).collect())
+            .await
+    }
+
+    pub async fn host_summary_async(self: &Arc<Self>, node: NodeID) -> HostEdgeSummary {
+        self.run_blocking(move |graph| graph.host_summary(&node))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::tests::test_graph;
+
+    #[tokio::test]
+    async fn async_queries_match_blocking_queries() {
+        let graph = Arc::new(test_graph());
+        let a = Node::from("A");
+
+        assert_eq!(
+            graph
+                .raw_ingoing_edges_async(a.id(), EdgeLimit::Unlimited)
+                .await,
+            graph.raw_ingoing_edges(&a.id(), EdgeLimit::Unlimited)
+        );
+        assert_eq!(
+            graph
+                .outgoing_edges_async(a.clone(), EdgeLimit::Unlimited, EdgeFilter::all())
+                .await,
+            graph.outgoing_edges(a.clone(), EdgeLimit::Unlimited, EdgeFilter::all())
+        );
+        assert_eq!(graph.id2node_async(a.id()).await, Some(a));
+    }
+}
@@ -0,0 +1,188 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Random walks with restart along the outgoing edges of the graph, e.g. to sample
+//! training data for node embeddings or to estimate pagerank with Monte-Carlo.
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::{EdgeFilter, EdgeLimit, NodeID, Webgraph};
+
+impl Webgraph {
+    /// Walk `steps` steps from `start` along the outgoing edges that match the filter.
+    /// In each step the walk jumps back to `start` with probability `restart_prob` and
+    /// otherwise follows a random outgoing edge. A walk that reaches a node without any
+    /// outgoing edges also jumps back to `start`.
+    ///
+    /// The returned nodes start with `start` and contain the node after each step, so
+    /// there are `steps + 1` nodes unless `start` itself has no outgoing edges.
+    pub fn random_walk(
+        &self,
+        start: NodeID,
+        steps: usize,
+        restart_prob: f64,
+        filter: EdgeFilter,
+    ) -> Vec<NodeID> {
+        self.random_walk_with_rng(start, steps, restart_prob, filter, &mut rand::thread_rng())
+    }
+
+    /// Same as [`Webgraph::random_walk`] but with the given source of randomness, so
+    /// walks can be reproduced.
+    pub fn random_walk_with_rng<R: Rng>(
+        &self,
+        start: NodeID,
+        steps: usize,
+        restart_prob: f64,
+        filter: EdgeFilter,
+        rng: &mut R,
+    ) -> Vec<NodeID> {
+        let mut walk = Vec::with_capacity(steps + 1);
+        walk.push(start);
+
+        let mut current = start;
+
+        for _ in 0..steps {
+            if current != start && rng.gen_bool(restart_prob.clamp(0.0, 1.0)) {
+                current = start;
+                walk.push(current);
+                continue;
+            }
+
+            let edges = self.raw_outgoing_edges_with_filter(&current, EdgeLimit::Unlimited, filter);
+
+            current = match edges.choose(rng) {
+                Some(edge) => edge.to,
+                None if current == start => break,
+                None => start,
+            };
+
+            walk.push(current);
+        }
+
+        walk
+    }
+
+    /// A random walk from each of the start nodes. The walks are run in parallel on the
+    /// executor of the graph and the walk from `starts[i]` is seeded with `seed + i`.
+    pub fn random_walks(
+        &self,
+        starts: &[NodeID],
+        steps: usize,
+        restart_prob: f64,
+        filter: EdgeFilter,
+        seed: u64,
+    ) -> Vec<Vec<NodeID>> {
+        self.executor
+            .map(
+                |(i, start)| {
+                    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(i as u64));
+                    self.random_walk_with_rng(*start, steps, restart_prob, filter, &mut rng)
+                },
+                starts.iter().enumerate(),
+            )
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{tests::test_graph, Compression, Node, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    #[test]
+    fn walks_follow_edges() {
+        // A -> B, B -> C, A -> C, C -> A, D -> C
+        let graph = test_graph();
+        let d = Node::from("D").id();
+
+        let walk = graph.random_walk(d, 20, 0.0, EdgeFilter::all());
+        assert_eq!(walk.len(), 21);
+        assert_eq!(walk[0], d);
+
+        // nothing links to D, so it can never be visited again without restarts
+        assert!(walk[1..].iter().all(|node| *node != d));
+
+        for pair in walk.windows(2) {
+            let edges = graph.raw_outgoing_edges(&pair[0], EdgeLimit::Unlimited);
+            assert!(edges.iter().any(|edge| edge.to == pair[1]));
+        }
+
+        // every other step is a restart
+        let walk = graph.random_walk(d, 20, 1.0, EdgeFilter::all());
+        assert!(walk.iter().step_by(2).all(|node| *node == d));
+    }
+
+    #[test]
+    fn walks_respect_filter() {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        writer.insert(
+            Node::from("A"),
+            Node::from("B"),
+            String::new(),
+            RelFlags::NOFOLLOW,
+        );
+        writer.insert(
+            Node::from("B"),
+            Node::from("C"),
+            String::new(),
+            RelFlags::default(),
+        );
+
+        let graph = writer.finalize();
+        let a = Node::from("A").id();
+        let b = Node::from("B").id();
+
+        assert_eq!(
+            graph.random_walk(a, 10, 0.0, EdgeFilter::followed()),
+            vec![a]
+        );
+
+        // C has no outgoing edges, so the walk jumps back to B
+        let walk = graph.random_walk(b, 4, 0.0, EdgeFilter::followed());
+        assert_eq!(walk.len(), 5);
+        assert_eq!(walk[2], b);
+    }
+
+    #[test]
+    fn seeded_walks_are_reproducible() {
+        let graph = test_graph();
+        let starts: Vec<_> = ["A", "B", "C", "D"]
+            .into_iter()
+            .map(|node| Node::from(node).id())
+            .collect();
+
+        let walks = graph.random_walks(&starts, 10, 0.15, EdgeFilter::all(), 42);
+        assert_eq!(walks.len(), starts.len());
+        assert_eq!(
+            walks,
+            graph.random_walks(&starts, 10, 0.15, EdgeFilter::all(), 42)
+        );
+
+        for (walk, start) in walks.iter().zip(&starts) {
+            assert_eq!(walk[0], *start);
+        }
+    }
+}
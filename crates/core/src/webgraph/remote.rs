@@ -14,7 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use itertools::Itertools;
 use tokio::sync::Mutex;
@@ -33,13 +33,18 @@ use crate::{
         },
     },
     entrypoint::webgraph_server::{
-        GetNode, IngoingEdges, OutgoingEdges, PagesByHosts, RawIngoingEdges,
-        RawIngoingEdgesWithLabels, RawOutgoingEdges, RawOutgoingEdgesWithLabels, WebGraphService,
+        GetNode, HostSummary, InDegrees, IngoingEdges, NumNodes, OutgoingEdges, PagesByHosts,
+        RawIngoingEdges, RawIngoingEdgesWithLabels, RawOutgoingEdges, RawOutgoingEdgesWithLabels,
+        SimilarNodeCandidates, TopPagesForHost, WebGraphService,
     },
     Result,
 };
 
-use super::{Edge, EdgeLimit, FullEdge, Node, NodeID};
+use super::{
+    link_set::{self, MAX_BACKLINKS_PER_TARGET},
+    CursorBound, Edge, EdgeFilter, EdgeLimit, EdgeQueryResult, HostEdgeSummary, LinkSet,
+    LinkSetOperation, Node, NodeDegree, NodeID, SimilarNode, SimilarityConfig,
+};
 
 struct WebgraphClientManager {
     granularity: WebgraphGranularity,
@@ -89,6 +94,43 @@ impl sonic::replication::ReusableClientManager for WebgraphClientManager {
     }
 }
 
+/// Merge the edges from the shards in cursor order. A shard that was truncated or
+/// returned as many edges as the limit may have more edges after its last edge, so the
+/// merged edges are cut at the first such edge to not skip edges when paging.
+fn merge_edge_results(
+    results: impl Iterator<Item = EdgeQueryResult>,
+    limit: &EdgeLimit,
+) -> EdgeQueryResult {
+    let max_edges = match limit {
+        EdgeLimit::Limit(limit) | EdgeLimit::After { limit, .. } => Some(*limit),
+        EdgeLimit::Unlimited | EdgeLimit::Window { .. } => None,
+    };
+
+    let mut merged = EdgeQueryResult {
+        edges: Vec::new(),
+        truncated: false,
+    };
+    let bound = CursorBound::default();
+
+    for result in results {
+        if result.truncated || max_edges.is_some_and(|max| result.edges.len() >= max) {
+            bound.add(result.edges.iter().map(|edge| edge.cursor).max());
+        }
+
+        merged.edges.extend(result.edges);
+        merged.truncated |= result.truncated;
+    }
+
+    merged.edges.sort_by_key(|edge| edge.cursor);
+    bound.apply(&mut merged.edges, |edge| edge.cursor);
+
+    if let Some(max) = max_edges {
+        merged.edges.truncate(max);
+    }
+
+    merged
+}
+
 #[derive(Clone)]
 pub struct RemoteWebgraph {
     client: Arc<Mutex<sonic::replication::ReusableShardedClient<WebgraphClientManager>>>,
@@ -174,24 +216,36 @@ impl RemoteWebgraph {
         Ok(nodes)
     }
 
-    pub async fn ingoing_edges(&self, node: Node, limit: EdgeLimit) -> Result<Vec<FullEdge>> {
+    /// The edges are truncated if any shard did not read all its edges within the timeout.
+    pub async fn ingoing_edges(
+        &self,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+        timeout: Option<Duration>,
+    ) -> Result<EdgeQueryResult> {
         let res = self
             .conn()
             .await
             .send(
-                IngoingEdges { node, limit },
+                IngoingEdges {
+                    node,
+                    limit,
+                    filter,
+                    timeout,
+                },
                 &AllShardsSelector,
                 &RandomReplicaSelector,
             )
             .await?;
 
-        Ok(res
-            .into_iter()
-            .flat_map(|(_, reps)| {
+        Ok(merge_edge_results(
+            res.into_iter().flat_map(|(_, reps)| {
                 debug_assert!(reps.len() <= 1);
-                reps.into_iter().flat_map(|(_, rep)| rep)
-            })
-            .collect())
+                reps.into_iter().map(|(_, rep)| rep)
+            }),
+            &limit,
+        ))
     }
 
     pub async fn raw_ingoing_edges(&self, id: NodeID, limit: EdgeLimit) -> Result<Vec<Edge<()>>> {
@@ -300,24 +354,36 @@ impl RemoteWebgraph {
         Ok(edges)
     }
 
-    pub async fn outgoing_edges(&self, node: Node, limit: EdgeLimit) -> Result<Vec<FullEdge>> {
+    /// The edges are truncated if any shard did not read all its edges within the timeout.
+    pub async fn outgoing_edges(
+        &self,
+        node: Node,
+        limit: EdgeLimit,
+        filter: EdgeFilter,
+        timeout: Option<Duration>,
+    ) -> Result<EdgeQueryResult> {
         let res = self
             .conn()
             .await
             .send(
-                OutgoingEdges { node, limit },
+                OutgoingEdges {
+                    node,
+                    limit,
+                    filter,
+                    timeout,
+                },
                 &AllShardsSelector,
                 &RandomReplicaSelector,
             )
             .await?;
 
-        Ok(res
-            .into_iter()
-            .flat_map(|(_, reps)| {
+        Ok(merge_edge_results(
+            res.into_iter().flat_map(|(_, reps)| {
                 debug_assert!(reps.len() <= 1);
-                reps.into_iter().flat_map(|(_, rep)| rep)
-            })
-            .collect())
+                reps.into_iter().map(|(_, rep)| rep)
+            }),
+            &limit,
+        ))
     }
 
     pub async fn raw_outgoing_edges(&self, id: NodeID, limit: EdgeLimit) -> Result<Vec<Edge<()>>> {
@@ -395,6 +461,23 @@ impl RemoteWebgraph {
         Ok(edges)
     }
 
+    /// Compute a set operation over the backlinks of `nodes`.
+    /// The edges can be spread across all shards, so the sets are combined here
+    /// instead of on the individual webgraph servers.
+    pub async fn backlink_set(&self, nodes: &[NodeID], op: LinkSetOperation) -> Result<LinkSet> {
+        let edges = self
+            .batch_raw_ingoing_edges(nodes, EdgeLimit::Limit(MAX_BACKLINKS_PER_TARGET + 1))
+            .await?;
+
+        Ok(link_set::combine(
+            edges
+                .into_iter()
+                .map(|edges| edges.into_iter().map(|edge| edge.from)),
+            op,
+            MAX_BACKLINKS_PER_TARGET,
+        ))
+    }
+
     pub async fn pages_by_hosts(&self, hosts: &[NodeID]) -> Result<Vec<NodeID>> {
         let res = self
             .conn()
@@ -417,4 +500,164 @@ impl RemoteWebgraph {
             .unique()
             .collect())
     }
+
+    /// The `k` pages from the host with the most ingoing links.
+    /// The degrees of each page are summed over the shards, so a page
+    /// that is just outside the top `k` on every shard might be missing.
+    pub async fn top_pages_for_host(&self, host: NodeID, k: usize) -> Result<Vec<NodeDegree>> {
+        let res = self
+            .conn()
+            .await
+            .send(
+                TopPagesForHost { host, k },
+                &AllShardsSelector,
+                &RandomReplicaSelector,
+            )
+            .await?;
+
+        let mut degrees: std::collections::HashMap<NodeID, usize> =
+            std::collections::HashMap::new();
+
+        for (_, reps) in res {
+            debug_assert!(reps.len() <= 1);
+
+            for (_, rep) in reps {
+                for page in rep {
+                    *degrees.entry(page.node).or_default() += page.degree;
+                }
+            }
+        }
+
+        let mut pages: Vec<_> = degrees
+            .into_iter()
+            .map(|(node, degree)| NodeDegree { node, degree })
+            .collect();
+
+        pages.sort_by(|a, b| b.degree.cmp(&a.degree).then_with(|| a.node.cmp(&b.node)));
+        pages.truncate(k);
+
+        Ok(pages)
+    }
+
+    /// The summary of the edges of the node, combined from all shards.
+    pub async fn host_summary(&self, node: NodeID) -> Result<HostEdgeSummary> {
+        let res = self
+            .conn()
+            .await
+            .send(
+                HostSummary { node },
+                &AllShardsSelector,
+                &RandomReplicaSelector,
+            )
+            .await?;
+
+        let mut summary = HostEdgeSummary::default();
+
+        for (_, reps) in res {
+            debug_assert!(reps.len() <= 1);
+
+            for (_, rep) in reps {
+                summary.merge(rep);
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// The number of ingoing edges of each of the nodes, summed over all shards.
+    pub async fn in_degrees(&self, nodes: Vec<NodeID>) -> Result<Vec<usize>> {
+        let num_nodes = nodes.len();
+        let res = self
+            .conn()
+            .await
+            .send(
+                InDegrees { nodes },
+                &AllShardsSelector,
+                &RandomReplicaSelector,
+            )
+            .await?;
+
+        let mut degrees = vec![0; num_nodes];
+
+        for (_, reps) in res {
+            debug_assert!(reps.len() <= 1);
+
+            for (_, rep) in reps {
+                for (degree, shard_degree) in degrees.iter_mut().zip(rep) {
+                    *degree += shard_degree;
+                }
+            }
+        }
+
+        Ok(degrees)
+    }
+
+    /// The number of nodes in the graph, summed over all shards. Nodes that have edges in
+    /// several shards are counted once per shard, so this is an upper bound. Returns `None`
+    /// if no shard replied.
+    pub async fn num_nodes(&self) -> Result<Option<u64>> {
+        let res = self
+            .conn()
+            .await
+            .send(NumNodes, &AllShardsSelector, &RandomReplicaSelector)
+            .await?;
+
+        Ok(res
+            .into_iter()
+            .flat_map(|(_, reps)| reps.into_iter().filter_map(|(_, rep)| rep))
+            .reduce(|a, b| a + b))
+    }
+
+    /// The `top_k` nodes that are most similar to the node by co-citation. The shared
+    /// backlinks and the degrees are summed over all shards before the candidates are
+    /// scored, as the backlinks of a node are spread over the shards.
+    pub async fn similar_nodes(&self, node: NodeID, top_k: usize) -> Result<Vec<SimilarNode>> {
+        let res = self
+            .conn()
+            .await
+            .send(
+                SimilarNodeCandidates { node },
+                &AllShardsSelector,
+                &RandomReplicaSelector,
+            )
+            .await?;
+
+        let mut node_backlinks = 0;
+        let mut shared: std::collections::HashMap<NodeID, usize> = std::collections::HashMap::new();
+
+        for (_, reps) in res {
+            debug_assert!(reps.len() <= 1);
+
+            for (_, rep) in reps {
+                node_backlinks += rep.node_backlinks;
+
+                for (candidate, count) in rep.shared {
+                    *shared.entry(candidate).or_default() += count;
+                }
+            }
+        }
+
+        let candidates = super::top_candidates(shared, SimilarityConfig::default().max_candidates);
+        let nodes: Vec<_> = candidates.iter().map(|(candidate, _)| *candidate).collect();
+
+        let degrees = self.in_degrees(nodes).await?;
+
+        let similar = candidates
+            .into_iter()
+            .zip(degrees)
+            .map(|((candidate, shared), degree)| {
+                let candidate_backlinks = degree.max(shared);
+
+                SimilarNode {
+                    node: candidate,
+                    score: super::cocitation_score(shared, node_backlinks, candidate_backlinks),
+                    shared_backlinks: shared,
+                    node_backlinks,
+                    candidate_backlinks,
+                }
+            })
+            .collect();
+
+        Ok(super::top_similar(similar, top_k))
+    }
 }
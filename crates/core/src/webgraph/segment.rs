@@ -20,14 +20,138 @@ use std::{
     thread,
 };
 
+use bloom::U64BloomFilter;
+
 use super::{
-    store::EdgeStore, store_writer::EdgeStoreWriter, Compression, EdgeLimit, InsertableEdge,
-    NodeID, SegmentEdge,
+    block_cache::{BlockCache, StoreBlockCache},
+    deadline::Deadline,
+    manifest::SegmentRef,
+    store::EdgeStore,
+    store_writer::EdgeStoreWriter,
+    tombstone::Tombstones,
+    Compression, DegreeDirection, EdgeFilter, EdgeLimit, InsertableEdge, NodeID, SegmentEdge,
 };
-use crate::Result;
+use crate::{executor::Executor, Result};
 
 const ADJACENCY_STORE: &str = "adjacency";
 const REVERSED_ADJACENCY_STORE: &str = "reversed_adjacency";
+const VERSION_FILE: &str = "version";
+const SUMMARY_FILE: &str = "summary.bin";
+
+/// [`U64BloomFilter`] sets a single bit per item, so the filter is sized for this many
+/// times more items than there are nodes to get a false positive rate of around 10%.
+const FILTER_OVERSIZE: u64 = 8;
+
+/// Segments with more nodes than this only store the range of their node ids,
+/// as the filter would take up too much memory.
+const MAX_FILTER_NODES: u64 = 50_000_000;
+
+/// Version of the on-disk format of a segment. Segments written with another
+/// version cannot be opened and the graph must be rebuilt.
+///
+/// Version 2 added first-seen and last-seen timestamps to the stored edges.
+/// Version 3 added the position of the link on the page to the stored edges.
+/// Version 4 added the enclosing block element and text offset of the link.
+/// Version 5 added the number of boilerplate links collapsed into the edge.
+/// Version 6 added the number of edges to the edge ranges, as the edges can be stored in blocks.
+const FORMAT_VERSION: u32 = 6;
+
+fn write_version(segment_path: &Path) -> Result<()> {
+    std::fs::write(segment_path.join(VERSION_FILE), FORMAT_VERSION.to_string())?;
+    Ok(())
+}
+
+/// Segments without a version file were written before the format was versioned.
+fn read_version(segment_path: &Path) -> u32 {
+    std::fs::read_to_string(segment_path.join(VERSION_FILE))
+        .ok()
+        .and_then(|version| version.trim().parse().ok())
+        .unwrap_or(1)
+}
+
+/// Whether the segment at `segment_path` can be opened by this version of the code.
+pub fn has_current_version(segment_path: &Path) -> bool {
+    read_version(segment_path) == FORMAT_VERSION
+}
+
+/// The nodes that have edges in one direction of a segment.
+#[derive(bincode::Encode, bincode::Decode)]
+struct NodeSummary {
+    /// Smallest and largest node id, or `None` if there are no nodes.
+    range: Option<(u64, u64)>,
+    filter: Option<U64BloomFilter>,
+}
+
+impl NodeSummary {
+    fn build(store: &EdgeStore) -> Self {
+        let num_nodes = store.num_nodes() as u64;
+
+        let mut range: Option<(u64, u64)> = None;
+        let mut filter = (num_nodes <= MAX_FILTER_NODES)
+            .then(|| U64BloomFilter::new(num_nodes.max(1) * FILTER_OVERSIZE, 0.01));
+
+        for (node, _) in store.degrees() {
+            let id = node.as_u64();
+
+            range = Some(match range {
+                Some((min, max)) => (min.min(id), max.max(id)),
+                None => (id, id),
+            });
+
+            if let Some(filter) = filter.as_mut() {
+                filter.insert(id);
+            }
+        }
+
+        Self { range, filter }
+    }
+
+    fn may_contain(&self, node: &NodeID) -> bool {
+        let id = node.as_u64();
+
+        match self.range {
+            Some((min, max)) if (min..=max).contains(&id) => self
+                .filter
+                .as_ref()
+                .map_or(true, |filter| filter.contains(id)),
+            _ => false,
+        }
+    }
+}
+
+/// Lets queries skip the segments that cannot have edges for a node.
+#[derive(bincode::Encode, bincode::Decode)]
+struct SegmentSummary {
+    outgoing: NodeSummary,
+    ingoing: NodeSummary,
+}
+
+impl SegmentSummary {
+    fn build(adjacency: &EdgeStore, reversed_adjacency: &EdgeStore) -> Self {
+        Self {
+            outgoing: NodeSummary::build(adjacency),
+            ingoing: NodeSummary::build(reversed_adjacency),
+        }
+    }
+
+    fn open(segment_path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(segment_path.join(SUMMARY_FILE)).ok()?;
+
+        match bincode::decode_from_slice(&bytes, bincode::config::standard()) {
+            Ok((summary, _)) => Some(summary),
+            Err(err) => {
+                tracing::warn!("failed to read segment summary: {}", err);
+                None
+            }
+        }
+    }
+
+    fn save(&self, segment_path: &Path) -> Result<()> {
+        let bytes = bincode::encode_to_vec(self, bincode::config::standard())?;
+        std::fs::write(segment_path.join(SUMMARY_FILE), bytes)?;
+        Ok(())
+    }
+}
 
 pub struct SegmentWriter {
     adjacency: EdgeStoreWriter,
@@ -69,10 +193,26 @@ impl SegmentWriter {
         }
     }
 
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
     pub fn finalize(self) -> Segment {
+        let path = Path::new(&self.folder_path).join(&self.id);
+
+        let adjacency = self.adjacency.finalize();
+        let reversed_adjacency = self.reversed_adjacency.finalize();
+
+        let summary = SegmentSummary::build(&adjacency, &reversed_adjacency);
+        summary.save(&path).unwrap();
+
+        write_version(&path).unwrap();
+
         Segment {
-            adjacency: self.adjacency.finalize(),
-            reversed_adjacency: self.reversed_adjacency.finalize(),
+            adjacency,
+            reversed_adjacency,
+            summary: Some(summary),
+            _open: SegmentRef::acquire(&path),
             folder_path: self.folder_path,
             id: self.id,
         }
@@ -87,12 +227,21 @@ impl SegmentWriter {
 pub struct Segment {
     adjacency: EdgeStore,
     reversed_adjacency: EdgeStore,
+    /// `None` for segments written before summaries were stored.
+    summary: Option<SegmentSummary>,
     id: String,
     folder_path: String,
+    _open: SegmentRef,
 }
 
 impl Segment {
     pub fn open<P: AsRef<Path>>(folder_path: P, id: String) -> Self {
+        let version = read_version(&folder_path.as_ref().join(&id));
+        assert_eq!(
+            version, FORMAT_VERSION,
+            "segment {id} has format version {version}, but version {FORMAT_VERSION} is required. The webgraph must be rebuilt."
+        );
+
         Segment {
             adjacency: EdgeStore::open(folder_path.as_ref().join(&id).join(ADJACENCY_STORE), false),
             reversed_adjacency: EdgeStore::open(
@@ -102,6 +251,8 @@ impl Segment {
                     .join(REVERSED_ADJACENCY_STORE),
                 true,
             ),
+            summary: SegmentSummary::open(&folder_path.as_ref().join(&id)),
+            _open: SegmentRef::acquire(&folder_path.as_ref().join(&id)),
             folder_path: folder_path
                 .as_ref()
                 .as_os_str()
@@ -112,50 +263,81 @@ impl Segment {
         }
     }
 
+    /// Read the decoded blocks of the segment through the cache.
+    pub fn with_block_cache(mut self, cache: Option<Arc<BlockCache>>) -> Self {
+        if let Some(cache) = cache {
+            self.adjacency.set_block_cache(StoreBlockCache::new(
+                Arc::clone(&cache),
+                &self.id,
+                false,
+            ));
+            self.reversed_adjacency
+                .set_block_cache(StoreBlockCache::new(cache, &self.id, true));
+        }
+
+        self
+    }
+
+    /// Whether the segment may have edges for the node in the direction. False
+    /// positives are possible, but the segment never has edges if this is false.
+    pub fn may_contain(&self, node: &NodeID, direction: DegreeDirection) -> bool {
+        match (&self.summary, direction) {
+            (None, _) => true,
+            (Some(summary), DegreeDirection::Out) => summary.outgoing.may_contain(node),
+            (Some(summary), DegreeDirection::In) => summary.ingoing.may_contain(node),
+        }
+    }
+
+    /// Merge the segments into a new segment. Nodes and edges with a tombstone are dropped.
+    /// The merged segments are left on disk, so the caller can remove them once the new
+    /// segment has been committed.
     pub fn merge<P: AsRef<Path>>(
         segments: Vec<Self>,
-        label_compression: Compression,
+        compression: Compression,
         folder: P,
         id: String,
+        tombstones: &Tombstones,
+        executor: &Executor,
     ) -> Result<()> {
         if segments.is_empty() {
             return Ok(());
         }
 
-        if segments.len() == 1 {
-            let segment = &segments[0];
-            std::fs::rename(segment.path(), folder.as_ref().join(&id))?;
-            return Ok(());
-        }
-
-        let old_paths = segments.iter().map(|s| s.path()).collect::<Vec<_>>();
-
         let (adjacency, reversed_adjacency) = segments
             .into_iter()
             .map(|s| (s.adjacency, s.reversed_adjacency))
             .unzip();
 
         let adjacency_path = folder.as_ref().join(&id).join(ADJACENCY_STORE);
-        let adjacency =
-            thread::spawn(move || EdgeStore::merge(adjacency, label_compression, adjacency_path));
-
         let reversed_adjacency_path = folder.as_ref().join(&id).join(REVERSED_ADJACENCY_STORE);
-        let reversed_adjacency = thread::spawn(move || {
-            EdgeStore::merge(
-                reversed_adjacency,
-                label_compression,
-                reversed_adjacency_path,
-            )
-        });
-
-        adjacency.join().unwrap()?;
-        reversed_adjacency.join().unwrap()?;
 
-        for path in old_paths {
-            if Path::new(&path).exists() {
-                std::fs::remove_dir_all(path)?;
-            }
-        }
+        thread::scope(|scope| {
+            let adjacency = scope.spawn(|| {
+                EdgeStore::merge(adjacency, compression, adjacency_path, tombstones, executor)
+            });
+
+            let reversed_adjacency = scope.spawn(|| {
+                EdgeStore::merge(
+                    reversed_adjacency,
+                    compression,
+                    reversed_adjacency_path,
+                    tombstones,
+                    executor,
+                )
+            });
+
+            adjacency.join().unwrap()?;
+            reversed_adjacency.join().unwrap()
+        })?;
+
+        let path = folder.as_ref().join(&id);
+        SegmentSummary::build(
+            &EdgeStore::open(path.join(ADJACENCY_STORE), false),
+            &EdgeStore::open(path.join(REVERSED_ADJACENCY_STORE), true),
+        )
+        .save(&path)?;
+
+        write_version(&path)?;
 
         Ok(())
     }
@@ -164,30 +346,79 @@ impl Segment {
         &self,
         node: &NodeID,
         limit: &EdgeLimit,
+        filter: &EdgeFilter,
+        deadline: &Deadline,
     ) -> Vec<SegmentEdge<String>> {
-        self.adjacency.get_with_label(node, limit)
+        self.adjacency
+            .get_with_label_until(node, limit, filter, deadline)
     }
 
-    pub fn outgoing_edges(&self, node: &NodeID, limit: &EdgeLimit) -> Vec<SegmentEdge<()>> {
-        self.adjacency.get_without_label(node, limit)
+    pub fn outgoing_edges(
+        &self,
+        node: &NodeID,
+        limit: &EdgeLimit,
+        filter: &EdgeFilter,
+    ) -> Vec<SegmentEdge<()>> {
+        self.adjacency.get_without_label(node, limit, filter)
     }
 
     pub fn ingoing_edges_with_label(
         &self,
         node: &NodeID,
         limit: &EdgeLimit,
+        filter: &EdgeFilter,
+        deadline: &Deadline,
     ) -> Vec<SegmentEdge<String>> {
-        self.reversed_adjacency.get_with_label(node, limit)
+        self.reversed_adjacency
+            .get_with_label_until(node, limit, filter, deadline)
+    }
+
+    pub fn ingoing_edges(
+        &self,
+        node: &NodeID,
+        limit: &EdgeLimit,
+        filter: &EdgeFilter,
+    ) -> Vec<SegmentEdge<()>> {
+        self.reversed_adjacency
+            .get_without_label(node, limit, filter)
     }
 
-    pub fn ingoing_edges(&self, node: &NodeID, limit: &EdgeLimit) -> Vec<SegmentEdge<()>> {
-        self.reversed_adjacency.get_without_label(node, limit)
+    pub fn in_degree(&self, node: &NodeID) -> usize {
+        self.degree(node, DegreeDirection::In)
+    }
+
+    pub fn degree(&self, node: &NodeID, direction: DegreeDirection) -> usize {
+        if !self.may_contain(node, direction) {
+            return 0;
+        }
+
+        match direction {
+            DegreeDirection::In => self.reversed_adjacency.degree(node),
+            DegreeDirection::Out => self.adjacency.degree(node),
+        }
+    }
+
+    pub fn degrees(
+        &self,
+        direction: DegreeDirection,
+    ) -> impl Iterator<Item = (NodeID, usize)> + '_ {
+        let store = match direction {
+            DegreeDirection::In => &self.reversed_adjacency,
+            DegreeDirection::Out => &self.adjacency,
+        };
+
+        store.degrees()
     }
 
     pub fn pages_by_host(&self, host_node: &NodeID) -> Vec<NodeID> {
         self.reversed_adjacency.nodes_by_host(host_node)
     }
 
+    /// The pages with outgoing edges in the segment, grouped by host.
+    pub fn pages_with_outlinks_by_host(&self) -> impl Iterator<Item = (NodeID, Vec<NodeID>)> + '_ {
+        self.adjacency.hosts()
+    }
+
     pub fn id(&self) -> String {
         self.id.clone()
     }
@@ -200,16 +431,29 @@ impl Segment {
         self.adjacency.iter_without_label()
     }
 
+    pub fn edges_with_label(&self) -> impl Iterator<Item = SegmentEdge<String>> + '_ {
+        self.adjacency.iter_with_label()
+    }
+
+    pub(super) fn adjacency(&self) -> &EdgeStore {
+        &self.adjacency
+    }
+
     pub fn optimize_read(&mut self) {
         self.adjacency.optimize_read();
         self.reversed_adjacency.optimize_read();
     }
+
+    /// Check the label blocks of both directions. Returns the number of blocks that were checked.
+    pub fn verify_labels(&self) -> Result<u64> {
+        Ok(self.adjacency.verify_labels()? + self.reversed_adjacency.verify_labels()?)
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
-        webgraph::{Edge, FullNodeID},
+        webgraph::{Edge, EdgeMetadata, EdgeTimestamps, FullNodeID},
         webpage::html::links::RelFlags,
     };
 
@@ -252,24 +496,32 @@ mod test {
             to: b.clone(),
             label: String::new(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         });
         edges.push(InsertableEdge {
             from: b.clone(),
             to: c.clone(),
             label: String::new(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         });
         edges.push(InsertableEdge {
             from: c.clone(),
             to: a.clone(),
             label: String::new(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         });
         edges.push(InsertableEdge {
             from: a.clone(),
             to: c.clone(),
             label: String::new(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         });
 
         for edge in &edges {
@@ -277,7 +529,8 @@ mod test {
         }
         let segment = writer.finalize();
 
-        let mut out: Vec<_> = segment.outgoing_edges(&a.id, &EdgeLimit::Unlimited);
+        let mut out: Vec<_> =
+            segment.outgoing_edges(&a.id, &EdgeLimit::Unlimited, &EdgeFilter::all());
 
         out.sort_by(|a, b| a.to.node().cmp(&b.to.node()));
 
@@ -289,6 +542,8 @@ mod test {
                     to: b.id,
                     label: (),
                     rel: RelFlags::default(),
+                    timestamps: EdgeTimestamps::default(),
+                    metadata: EdgeMetadata::default(),
                 }
                 .into(),
                 Edge {
@@ -296,12 +551,15 @@ mod test {
                     to: c.id,
                     label: (),
                     rel: RelFlags::default(),
+                    timestamps: EdgeTimestamps::default(),
+                    metadata: EdgeMetadata::default(),
                 }
                 .into(),
             ]
         );
 
-        let mut out: Vec<_> = segment.outgoing_edges(&b.id, &EdgeLimit::Unlimited);
+        let mut out: Vec<_> =
+            segment.outgoing_edges(&b.id, &EdgeLimit::Unlimited, &EdgeFilter::all());
         out.sort_by(|a, b| a.to.node().cmp(&b.to.node()));
         assert_eq!(
             out,
@@ -310,11 +568,14 @@ mod test {
                 to: c.id,
                 label: (),
                 rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
             }
             .into(),]
         );
 
-        let mut out: Vec<_> = segment.outgoing_edges(&c.id, &EdgeLimit::Unlimited);
+        let mut out: Vec<_> =
+            segment.outgoing_edges(&c.id, &EdgeLimit::Unlimited, &EdgeFilter::all());
         out.sort_by(|a, b| a.to.node().cmp(&b.to.node()));
         assert_eq!(
             out,
@@ -323,11 +584,13 @@ mod test {
                 to: a.id,
                 label: (),
                 rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
             }
             .into(),]
         );
 
-        let out: Vec<_> = segment.ingoing_edges(&a.id, &EdgeLimit::Unlimited);
+        let out: Vec<_> = segment.ingoing_edges(&a.id, &EdgeLimit::Unlimited, &EdgeFilter::all());
         assert_eq!(
             out,
             vec![Edge {
@@ -335,11 +598,13 @@ mod test {
                 to: a.id,
                 label: (),
                 rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
             }
             .into(),]
         );
 
-        let out: Vec<_> = segment.ingoing_edges(&b.id, &EdgeLimit::Unlimited);
+        let out: Vec<_> = segment.ingoing_edges(&b.id, &EdgeLimit::Unlimited, &EdgeFilter::all());
         assert_eq!(
             out,
             vec![Edge {
@@ -347,11 +612,14 @@ mod test {
                 to: b.id,
                 label: (),
                 rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
             }
             .into(),]
         );
 
-        let mut out: Vec<_> = segment.ingoing_edges(&c.id, &EdgeLimit::Unlimited);
+        let mut out: Vec<_> =
+            segment.ingoing_edges(&c.id, &EdgeLimit::Unlimited, &EdgeFilter::all());
         out.sort_by(|a, b| a.from.node().cmp(&b.from.node()));
         assert_eq!(
             out,
@@ -361,6 +629,8 @@ mod test {
                     to: c.id,
                     label: (),
                     rel: RelFlags::default(),
+                    timestamps: EdgeTimestamps::default(),
+                    metadata: EdgeMetadata::default(),
                 }
                 .into(),
                 Edge {
@@ -368,9 +638,56 @@ mod test {
                     to: c.id,
                     label: (),
                     rel: RelFlags::default(),
+                    timestamps: EdgeTimestamps::default(),
+                    metadata: EdgeMetadata::default(),
                 }
                 .into(),
             ]
         );
     }
+
+    #[test]
+    fn summary_prunes_absent_nodes() {
+        let path = crate::gen_temp_path();
+        let mut writer =
+            SegmentWriter::open(&path, "test".to_string(), Compression::default(), None);
+
+        let node = |id: u64| FullNodeID {
+            id: NodeID::from(id),
+            host: NodeID::from(id),
+        };
+
+        for (from, to) in [(10, 20), (20, 30)] {
+            writer.insert(InsertableEdge {
+                from: node(from),
+                to: node(to),
+                label: String::new(),
+                rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
+            });
+        }
+        let segment = writer.finalize();
+
+        let id = NodeID::from;
+
+        assert!(segment.may_contain(&id(10_u64), DegreeDirection::Out));
+        assert!(segment.may_contain(&id(20_u64), DegreeDirection::Out));
+        assert!(segment.may_contain(&id(30_u64), DegreeDirection::In));
+        assert!(!segment.may_contain(&id(5_u64), DegreeDirection::Out));
+        assert!(!segment.may_contain(&id(30_u64), DegreeDirection::Out));
+        assert!(!segment.may_contain(&id(10_u64), DegreeDirection::In));
+
+        assert_eq!(segment.degree(&id(10_u64), DegreeDirection::Out), 1);
+        assert_eq!(segment.degree(&id(30_u64), DegreeDirection::In), 1);
+        assert_eq!(segment.in_degree(&id(10_u64)), 0);
+
+        drop(segment);
+        let segment = Segment::open(&path, "test".to_string());
+        assert!(!segment.may_contain(&id(5_u64), DegreeDirection::Out));
+
+        std::fs::remove_file(path.join("test").join(SUMMARY_FILE)).unwrap();
+        let segment = Segment::open(&path, "test".to_string());
+        assert!(segment.may_contain(&id(5_u64), DegreeDirection::Out));
+    }
 }
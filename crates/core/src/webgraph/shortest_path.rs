@@ -21,12 +21,94 @@ use std::{
 
 use super::{Edge, EdgeLabel, Node, NodeID, Webgraph};
 
+/// The number of shortest paths between two nodes can grow exponentially
+/// with the path length, so we never return more than this.
+pub const MAX_SHORTEST_PATHS: usize = 100;
+
 pub trait ShortestPaths {
     fn distances(&self, source: Node) -> BTreeMap<Node, u8>;
     fn raw_distances(&self, source: NodeID) -> BTreeMap<NodeID, u8>;
     fn raw_distances_with_max(&self, source: NodeID, max_dist: u8) -> BTreeMap<NodeID, u8>;
     fn raw_reversed_distances(&self, source: NodeID) -> BTreeMap<NodeID, u8>;
     fn reversed_distances(&self, source: Node) -> BTreeMap<Node, u8>;
+    fn raw_paths(&self, from: NodeID, to: NodeID, max_hops: u8) -> Vec<Vec<NodeID>>;
+    /// The node sequences of the shortest paths from `from` to `to` with at most `max_hops` edges.
+    /// Both `from` and `to` are included in each path.
+    fn paths(&self, from: Node, to: Node, max_hops: u8) -> Vec<Vec<Node>>;
+}
+
+fn shortest_paths<F>(from: NodeID, to: NodeID, max_hops: u8, neighbours: F) -> Vec<Vec<NodeID>>
+where
+    F: Fn(NodeID) -> Vec<NodeID>,
+{
+    if from == to {
+        return vec![vec![from]];
+    }
+
+    let mut distances: BTreeMap<NodeID, u8> = BTreeMap::default();
+    let mut predecessors: BTreeMap<NodeID, Vec<NodeID>> = BTreeMap::default();
+
+    distances.insert(from, 0);
+    let mut frontier = vec![from];
+    let mut dist = 0;
+
+    // breadth first search that stops after the level where `to` is found,
+    // so all the predecessors of `to` on a shortest path are known.
+    while !frontier.is_empty() && dist < max_hops && !distances.contains_key(&to) {
+        dist += 1;
+        let mut next = Vec::new();
+
+        for node in frontier {
+            for neighbour in neighbours(node) {
+                match distances.get(&neighbour) {
+                    None => {
+                        distances.insert(neighbour, dist);
+                        predecessors.insert(neighbour, vec![node]);
+                        next.push(neighbour);
+                    }
+                    Some(d) if *d == dist => {
+                        let preds = predecessors.entry(neighbour).or_default();
+
+                        if !preds.contains(&node) {
+                            preds.push(node);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        frontier = next;
+    }
+
+    if !distances.contains_key(&to) {
+        return Vec::new();
+    }
+
+    let mut paths = Vec::new();
+    let mut stack = vec![vec![to]];
+
+    while let Some(mut path) = stack.pop() {
+        if paths.len() >= MAX_SHORTEST_PATHS {
+            break;
+        }
+
+        let last = *path.last().unwrap();
+
+        if last == from {
+            path.reverse();
+            paths.push(path);
+            continue;
+        }
+
+        for pred in predecessors.get(&last).into_iter().flatten() {
+            let mut path = path.clone();
+            path.push(*pred);
+            stack.push(path);
+        }
+    }
+
+    paths
 }
 
 fn dijkstra_multi<F1, F2, L>(
@@ -119,4 +201,24 @@ impl ShortestPaths for Webgraph {
             .filter_map(|(id, dist)| self.id2node(&id).map(|node| (node, dist)))
             .collect()
     }
+
+    fn raw_paths(&self, from: NodeID, to: NodeID, max_hops: u8) -> Vec<Vec<NodeID>> {
+        shortest_paths(from, to, max_hops, |node| {
+            self.raw_outgoing_edges(&node, super::EdgeLimit::Unlimited)
+                .into_iter()
+                .map(|edge| edge.to)
+                .collect()
+        })
+    }
+
+    fn paths(&self, from: Node, to: Node, max_hops: u8) -> Vec<Vec<Node>> {
+        self.raw_paths(from.id(), to.id(), max_hops)
+            .into_iter()
+            .filter_map(|path| {
+                path.into_iter()
+                    .map(|id| self.id2node(&id))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .collect()
+    }
 }
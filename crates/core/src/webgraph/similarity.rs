@@ -0,0 +1,375 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Similar nodes based on co-citation, i.e. how many backlinks two nodes share.
+//!
+//! The candidates are the nodes that are linked to from the backlinks of the node.
+//! They are scored by the cosine similarity of their backlinks, and can optionally
+//! be re-scored with a few iterations of SimRank ("SimRank: A Measure of
+//! Structural-Context Similarity"), where two nodes are similar if they are linked
+//! to from similar nodes. The first iteration of SimRank is co-citation, and each
+//! additional iteration follows the backlinks one hop further.
+
+use std::collections::HashMap;
+
+use super::{EdgeLimit, NodeID, Webgraph};
+
+#[derive(Debug, Clone, Copy)]
+pub struct SimilarityConfig {
+    /// Number of backlinks of the node that are used to find candidates.
+    pub max_backlinks: usize,
+    /// Number of outgoing edges from each backlink that become candidates.
+    pub max_outlinks_per_backlink: usize,
+    /// Number of candidates with the most shared backlinks that are scored.
+    pub max_candidates: usize,
+    /// Number of SimRank iterations. With 0 the candidates are scored by co-citation.
+    pub simrank_iterations: u8,
+    /// Decay factor of SimRank.
+    pub simrank_decay: f64,
+    /// Number of backlinks of each node that are followed by SimRank. The cost grows
+    /// with this to the power of twice the number of iterations.
+    pub simrank_backlinks: usize,
+}
+
+impl Default for SimilarityConfig {
+    fn default() -> Self {
+        Self {
+            max_backlinks: 128,
+            max_outlinks_per_backlink: 1024,
+            max_candidates: 512,
+            simrank_iterations: 0,
+            simrank_decay: 0.8,
+            simrank_backlinks: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct SimilarNode {
+    pub node: NodeID,
+    pub score: f64,
+    /// Number of the sampled backlinks of the queried node that also link to this node.
+    pub shared_backlinks: usize,
+    /// Number of backlinks of the queried node.
+    pub node_backlinks: usize,
+    /// Number of backlinks of this node.
+    pub candidate_backlinks: usize,
+}
+
+/// The candidates of a node before they are scored. A sharded graph sums the counts
+/// from all shards before the candidates are scored with [`cocitation_score`].
+#[derive(Debug, Clone, Default, PartialEq, bincode::Encode, bincode::Decode)]
+pub struct CocitationCandidates {
+    /// Number of backlinks of the queried node.
+    pub node_backlinks: usize,
+    /// The candidates and the number of sampled backlinks they share with the node.
+    pub shared: Vec<(NodeID, usize)>,
+}
+
+/// Cosine similarity of the backlinks of two nodes.
+pub fn cocitation_score(shared: usize, node_backlinks: usize, candidate_backlinks: usize) -> f64 {
+    if node_backlinks == 0 || candidate_backlinks == 0 {
+        return 0.0;
+    }
+
+    shared as f64 / ((node_backlinks * candidate_backlinks) as f64).sqrt()
+}
+
+/// Sort the nodes by their score and keep the `top_k` best.
+pub fn top_similar(mut similar: Vec<SimilarNode>, top_k: usize) -> Vec<SimilarNode> {
+    similar.retain(|similar| similar.score > 0.0);
+    similar.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.node.cmp(&b.node))
+    });
+    similar.truncate(top_k);
+
+    similar
+}
+
+/// The `max_candidates` candidates with the most shared backlinks.
+pub fn top_candidates(
+    shared: impl IntoIterator<Item = (NodeID, usize)>,
+    max_candidates: usize,
+) -> Vec<(NodeID, usize)> {
+    let mut candidates: Vec<_> = shared.into_iter().collect();
+    candidates
+        .sort_by(|(a, a_shared), (b, b_shared)| b_shared.cmp(a_shared).then_with(|| a.cmp(b)));
+    candidates.truncate(max_candidates);
+
+    candidates
+}
+
+/// SimRank between pairs of nodes, where the backlinks of each node are loaded
+/// from the graph the first time they are needed.
+struct SimRank<'a> {
+    graph: &'a Webgraph,
+    decay: f64,
+    backlink_limit: usize,
+    backlinks: HashMap<NodeID, Vec<NodeID>>,
+    memo: HashMap<(NodeID, NodeID, u8), f64>,
+}
+
+impl<'a> SimRank<'a> {
+    fn new(graph: &'a Webgraph, config: &SimilarityConfig) -> Self {
+        Self {
+            graph,
+            decay: config.simrank_decay,
+            backlink_limit: config.simrank_backlinks,
+            backlinks: HashMap::new(),
+            memo: HashMap::new(),
+        }
+    }
+
+    fn backlinks(&mut self, node: NodeID) -> Vec<NodeID> {
+        self.backlinks
+            .entry(node)
+            .or_insert_with(|| {
+                self.graph
+                    .raw_ingoing_edges(&node, EdgeLimit::Limit(self.backlink_limit))
+                    .into_iter()
+                    .map(|edge| edge.from)
+                    .collect()
+            })
+            .clone()
+    }
+
+    /// SimRank of the two nodes after `iterations` iterations.
+    fn score(&mut self, a: NodeID, b: NodeID, iterations: u8) -> f64 {
+        if a == b {
+            return 1.0;
+        }
+
+        if iterations == 0 {
+            return 0.0;
+        }
+
+        let key = if a < b {
+            (a, b, iterations)
+        } else {
+            (b, a, iterations)
+        };
+
+        if let Some(score) = self.memo.get(&key) {
+            return *score;
+        }
+
+        let a_backlinks = self.backlinks(a);
+        let b_backlinks = self.backlinks(b);
+
+        let score = if a_backlinks.is_empty() || b_backlinks.is_empty() {
+            0.0
+        } else {
+            let mut sum = 0.0;
+
+            for i in &a_backlinks {
+                for j in &b_backlinks {
+                    sum += self.score(*i, *j, iterations - 1);
+                }
+            }
+
+            self.decay * sum / (a_backlinks.len() * b_backlinks.len()) as f64
+        };
+
+        self.memo.insert(key, score);
+
+        score
+    }
+}
+
+impl Webgraph {
+    /// The `top_k` nodes that are most similar to `node` by co-citation.
+    pub fn similar_nodes(&self, node: &NodeID, top_k: usize) -> Vec<SimilarNode> {
+        self.similar_nodes_with_config(node, top_k, &SimilarityConfig::default())
+    }
+
+    /// The candidates of `node` that share the most sampled backlinks with it.
+    pub fn cocitation_candidates(
+        &self,
+        node: &NodeID,
+        config: &SimilarityConfig,
+    ) -> CocitationCandidates {
+        let backlinks: Vec<_> = self
+            .raw_ingoing_edges(node, EdgeLimit::Limit(config.max_backlinks))
+            .into_iter()
+            .map(|edge| edge.from)
+            .collect();
+
+        let mut shared: HashMap<NodeID, usize> = HashMap::new();
+
+        for backlink in &backlinks {
+            for edge in self
+                .raw_outgoing_edges(backlink, EdgeLimit::Limit(config.max_outlinks_per_backlink))
+            {
+                if edge.to != *node {
+                    *shared.entry(edge.to).or_default() += 1;
+                }
+            }
+        }
+
+        CocitationCandidates {
+            node_backlinks: self.in_degree(node).max(backlinks.len()),
+            shared: top_candidates(shared, config.max_candidates),
+        }
+    }
+
+    pub fn similar_nodes_with_config(
+        &self,
+        node: &NodeID,
+        top_k: usize,
+        config: &SimilarityConfig,
+    ) -> Vec<SimilarNode> {
+        let CocitationCandidates {
+            node_backlinks,
+            shared: candidates,
+        } = self.cocitation_candidates(node, config);
+
+        let mut simrank = SimRank::new(self, config);
+
+        let res: Vec<_> = candidates
+            .into_iter()
+            .map(|(candidate, shared)| {
+                let candidate_backlinks = self.in_degree(&candidate).max(shared);
+
+                let score = if config.simrank_iterations == 0 {
+                    cocitation_score(shared, node_backlinks, candidate_backlinks)
+                } else {
+                    simrank.score(*node, candidate, config.simrank_iterations)
+                };
+
+                SimilarNode {
+                    node: candidate,
+                    score,
+                    shared_backlinks: shared,
+                    node_backlinks,
+                    candidate_backlinks,
+                }
+            })
+            .collect();
+
+        top_similar(res, top_k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{Compression, Node, WebgraphWriter},
+        webpage::html::links::RelFlags,
+    };
+
+    fn graph() -> Webgraph {
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        for (from, to) in [
+            ("X", "A"),
+            ("X", "B"),
+            ("Y", "A"),
+            ("Y", "B"),
+            ("Z", "A"),
+            ("Z", "C"),
+            ("W", "C"),
+            ("V", "Y"),
+            ("V", "Z"),
+        ] {
+            writer.insert(
+                Node::from(from),
+                Node::from(to),
+                String::new(),
+                RelFlags::default(),
+            );
+        }
+
+        writer.finalize()
+    }
+
+    #[test]
+    fn cocitation() {
+        let graph = graph();
+        let a = Node::from("A").id();
+        let b = Node::from("B").id();
+        let c = Node::from("C").id();
+
+        let similar = graph.similar_nodes(&a, 10);
+
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0].node, b);
+        assert_eq!(similar[1].node, c);
+
+        // B shares 2 of A's 3 backlinks and has no others
+        assert!((similar[0].score - 2.0 / 6.0_f64.sqrt()).abs() < 1e-9);
+        assert_eq!(similar[0].shared_backlinks, 2);
+        assert_eq!(similar[0].node_backlinks, 3);
+        assert_eq!(similar[0].candidate_backlinks, 2);
+
+        // nodes are only similar to nodes they share backlinks with
+        let y = Node::from("Y").id();
+        assert_eq!(graph.similar_nodes(&y, 10)[0].node, Node::from("Z").id());
+        assert_eq!(graph.similar_nodes(&y, 10).len(), 1);
+
+        assert_eq!(graph.similar_nodes(&a, 1).len(), 1);
+    }
+
+    #[test]
+    fn simrank() {
+        let graph = graph();
+        let a = Node::from("A").id();
+        let b = Node::from("B").id();
+        let c = Node::from("C").id();
+
+        let config = SimilarityConfig {
+            simrank_iterations: 1,
+            simrank_decay: 1.0,
+            ..Default::default()
+        };
+
+        // a single iteration only counts the shared backlinks
+        let similar = graph.similar_nodes_with_config(&a, 10, &config);
+        assert_eq!(similar[0].node, b);
+        assert!((similar[0].score - 2.0 / 6.0).abs() < 1e-9);
+
+        let one = graph.similar_nodes_with_config(
+            &a,
+            10,
+            &SimilarityConfig {
+                simrank_iterations: 1,
+                ..Default::default()
+            },
+        );
+        let three = graph.similar_nodes_with_config(
+            &a,
+            10,
+            &SimilarityConfig {
+                simrank_iterations: 3,
+                ..Default::default()
+            },
+        );
+
+        // Y and Z are similar as both are linked to from V, so the backlinks
+        // of C are more similar to the backlinks of A than co-citation shows
+        let score = |res: &[SimilarNode], node| res.iter().find(|s| s.node == node).unwrap().score;
+        assert!(score(&three, c) > score(&one, c));
+        assert_eq!(three[0].node, b);
+    }
+}
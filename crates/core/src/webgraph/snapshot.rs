@@ -0,0 +1,175 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Point-in-time snapshots of a webgraph.
+//!
+//! Segment files are never modified after they have been written, so a snapshot
+//! hard-links them instead of copying. The metadata files are rewritten when the
+//! graph changes and are therefore copied. Merges in the live graph only remove
+//! its own links to the old segment files, so the snapshot stays readable.
+
+use std::{fs, path::Path};
+
+use super::{alias::NodeAliases, host_summary::HostSummaries, Meta, Webgraph};
+use crate::Result;
+
+const METADATA_FILE: &str = "metadata.json";
+
+/// Hard-link all files in `from` into `to`. Json files hold mutable metadata
+/// and are copied instead.
+fn link_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            link_dir(&entry.path(), &target)?;
+        } else if entry.path().extension().is_some_and(|ext| ext == "json") {
+            fs::copy(entry.path(), target)?;
+        } else {
+            fs::hard_link(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fail unless `path` holds a snapshot that was completely written.
+pub(super) fn ensure_complete(path: &Path) -> Result<()> {
+    if !path.join(METADATA_FILE).exists() {
+        anyhow::bail!("{} is not a complete snapshot", path.display());
+    }
+
+    Ok(())
+}
+
+/// Link the committed segments and the id to node mapping of the graph at `from`
+/// into a new graph at `to`, with `meta` as its metadata.
+fn link_graph(from: &Path, meta: &Meta, to: &Path) -> Result<()> {
+    if to.exists() {
+        anyhow::bail!("{} already exists", to.display());
+    }
+
+    for segment in &meta.comitted_segments {
+        link_dir(
+            &from.join("segments").join(segment),
+            &to.join("segments").join(segment),
+        )?;
+    }
+    fs::create_dir_all(to.join("segments"))?;
+
+    link_dir(&from.join("id2node"), &to.join("id2node"))?;
+
+    let aliases = NodeAliases::path(from);
+    if aliases.exists() {
+        link_dir(&aliases, &NodeAliases::path(to))?;
+    }
+
+    let host_summaries = HostSummaries::path(from);
+    if host_summaries.exists() {
+        fs::copy(host_summaries, HostSummaries::path(to))?;
+    }
+
+    // the metadata is written last, so a graph is only complete once it exists
+    meta.save(to.join(METADATA_FILE));
+
+    Ok(())
+}
+
+impl Webgraph {
+    /// Create a snapshot of the graph as it is right now at `path`, which must not exist.
+    /// The snapshot is unaffected by later merges into the graph.
+    pub fn snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        link_graph(Path::new(&self.path), &self.meta, path.as_ref())
+    }
+
+    /// Open a snapshot created with [`Webgraph::snapshot`] with the default settings of
+    /// [`Webgraph::builder`]. Use [`WebgraphBuilder::open_snapshot`] to open a snapshot of
+    /// a graph that was built with other settings, e.g. another normalization.
+    ///
+    /// [`WebgraphBuilder::open_snapshot`]: super::WebgraphBuilder::open_snapshot
+    pub fn open_snapshot<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::builder(path).open_snapshot()
+    }
+
+    /// Restore the snapshot at `snapshot` into a new graph at `path`, which must not exist.
+    /// The snapshot itself is left untouched, so it can be restored again.
+    pub fn restore_snapshot<P: AsRef<Path>, Q: AsRef<Path>>(snapshot: P, path: Q) -> Result<()> {
+        let snapshot = snapshot.as_ref();
+        ensure_complete(snapshot)?;
+
+        link_graph(
+            snapshot,
+            &Meta::open(snapshot.join(METADATA_FILE)),
+            path.as_ref(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::Executor;
+    use crate::webgraph::{tests::test_graph, EdgeLimit, Node, WebgraphWriter};
+    use crate::webpage::html::links::RelFlags;
+
+    #[test]
+    fn snapshot_survives_merge() {
+        let mut graph = test_graph();
+        let snapshot_path = crate::gen_temp_path();
+
+        graph.snapshot(&snapshot_path).unwrap();
+        assert!(graph.snapshot(&snapshot_path).is_err());
+
+        let mut writer = WebgraphWriter::new(
+            crate::gen_temp_path(),
+            Executor::single_thread(),
+            Default::default(),
+            None,
+        );
+        writer.insert(
+            Node::from("E"),
+            Node::from("A"),
+            String::new(),
+            RelFlags::default(),
+        );
+        graph.merge(writer.finalize()).unwrap();
+        graph.merge_all_segments().unwrap();
+
+        let a = Node::from("A").id();
+        assert_eq!(graph.raw_ingoing_edges(&a, EdgeLimit::Unlimited).len(), 2);
+
+        let snapshot = Webgraph::open_snapshot(&snapshot_path).unwrap();
+        assert_eq!(
+            snapshot.raw_ingoing_edges(&a, EdgeLimit::Unlimited).len(),
+            1
+        );
+        assert!(snapshot.id2node(&Node::from("E").id()).is_none());
+        assert_eq!(snapshot.nodes().count(), 4);
+
+        let restored_path = crate::gen_temp_path();
+        Webgraph::restore_snapshot(&snapshot_path, &restored_path).unwrap();
+        let restored = Webgraph::builder(&restored_path).open();
+        assert_eq!(
+            restored.raw_ingoing_edges(&a, EdgeLimit::Unlimited).len(),
+            1
+        );
+
+        assert!(Webgraph::open_snapshot(crate::gen_temp_path()).is_err());
+    }
+}
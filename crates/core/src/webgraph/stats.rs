@@ -0,0 +1,208 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Statistics about the shape of the graph that are useful to monitor the graph
+//! after each crawl batch. Calculating them reads the degrees of every node, so they
+//! are only calculated with `webgraph stats` and written to a file in the graph folder.
+//!
+//! Degrees and edge counts are read from the edge ranges of the segments, so
+//! they include edges that are present in multiple segments and deleted edges
+//! until the segments are merged. Reciprocity and path lengths are estimated
+//! from a sample of the nodes.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use rand::seq::IteratorRandom;
+
+use super::{degree, shortest_path::ShortestPaths, DegreeDirection, EdgeLimit, Webgraph};
+use crate::Result;
+
+const STATS_FILE: &str = "stats.json";
+
+/// Number of nodes that reciprocity is estimated from.
+const RECIPROCITY_SAMPLE: usize = 1_000;
+
+/// Number of source nodes that path lengths are estimated from.
+const PATH_SAMPLE: usize = 16;
+
+/// Paths longer than this are not included in the average path length.
+const MAX_PATH_LENGTH: u8 = 8;
+
+/// Number of nodes per degree. Bucket 0 counts the nodes with degree 0 and
+/// bucket `i > 0` counts the nodes with a degree in `[2^(i-1), 2^i)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DegreeHistogram {
+    pub buckets: Vec<u64>,
+}
+
+impl DegreeHistogram {
+    fn bucket(degree: usize) -> usize {
+        (usize::BITS - degree.leading_zeros()) as usize
+    }
+
+    pub fn add(&mut self, degree: usize) {
+        self.add_many(degree, 1);
+    }
+
+    fn add_many(&mut self, degree: usize, count: u64) {
+        let bucket = Self::bucket(degree);
+
+        if self.buckets.len() <= bucket {
+            self.buckets.resize(bucket + 1, 0);
+        }
+
+        self.buckets[bucket] += count;
+    }
+
+    /// Smallest degree that falls into the bucket.
+    pub fn bucket_start(bucket: usize) -> usize {
+        match bucket {
+            0 => 0,
+            b => 1 << (b - 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GraphStats {
+    pub num_nodes: u64,
+    pub num_edges: u64,
+    pub in_degrees: DegreeHistogram,
+    pub out_degrees: DegreeHistogram,
+    /// Fraction of the sampled edges where the edge in the opposite direction also exists.
+    pub reciprocity: Option<f64>,
+    /// Average length of the shortest paths from the sampled nodes to the nodes they can reach.
+    pub avg_path_length: Option<f64>,
+}
+
+impl GraphStats {
+    pub fn path<P: AsRef<Path>>(graph_path: P) -> PathBuf {
+        graph_path.as_ref().join(STATS_FILE)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn calculate(graph: &Webgraph) -> Self {
+        let tombstones = &graph.meta.tombstones;
+        let num_nodes = graph.nodes().count() as u64;
+
+        let histogram = |direction| {
+            let degrees = degree::degrees(&graph.segments, direction);
+            let mut histogram = DegreeHistogram::default();
+            let mut num_with_edges = 0;
+            let mut num_edges = 0;
+
+            for (node, degree) in &degrees {
+                num_edges += *degree as u64;
+
+                if !tombstones.is_node_deleted(node) {
+                    histogram.add(*degree);
+                    num_with_edges += 1;
+                }
+            }
+
+            histogram.add_many(0, num_nodes.saturating_sub(num_with_edges));
+
+            (histogram, num_edges, degrees)
+        };
+
+        let (in_degrees, _, _) = histogram(DegreeDirection::In);
+        let (out_degrees, num_edges, out) = histogram(DegreeDirection::Out);
+
+        let mut rng = rand::thread_rng();
+        let sources = out
+            .into_keys()
+            .filter(|node| !tombstones.is_node_deleted(node))
+            .choose_multiple(&mut rng, RECIPROCITY_SAMPLE);
+
+        let mut num_sampled_edges = 0;
+        let mut num_reciprocal = 0;
+
+        for node in &sources {
+            let ingoing: HashSet<_> = graph
+                .raw_ingoing_edges(node, EdgeLimit::Unlimited)
+                .into_iter()
+                .map(|edge| edge.from)
+                .collect();
+
+            for edge in graph.raw_outgoing_edges(node, EdgeLimit::Unlimited) {
+                num_sampled_edges += 1;
+
+                if ingoing.contains(&edge.to) {
+                    num_reciprocal += 1;
+                }
+            }
+        }
+
+        let reciprocity =
+            (num_sampled_edges > 0).then(|| num_reciprocal as f64 / num_sampled_edges as f64);
+
+        let mut num_paths = 0;
+        let mut sum_path_lengths = 0;
+
+        for node in sources.iter().take(PATH_SAMPLE) {
+            for (_, dist) in graph.raw_distances_with_max(*node, MAX_PATH_LENGTH) {
+                if dist > 0 {
+                    num_paths += 1;
+                    sum_path_lengths += dist as u64;
+                }
+            }
+        }
+
+        let avg_path_length = (num_paths > 0).then(|| sum_path_lengths as f64 / num_paths as f64);
+
+        Self {
+            num_nodes,
+            num_edges,
+            in_degrees,
+            out_degrees,
+            reciprocity,
+            avg_path_length,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets() {
+        let mut histogram = DegreeHistogram::default();
+
+        for degree in [0, 1, 2, 3, 4, 7, 8] {
+            histogram.add(degree);
+        }
+
+        assert_eq!(histogram.buckets, vec![1, 1, 2, 2, 1]);
+        assert_eq!(DegreeHistogram::bucket_start(3), 4);
+        assert_eq!(DegreeHistogram::bucket(7), 3);
+    }
+}
@@ -14,9 +14,17 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{fs::File, ops::Range, path::Path};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io,
+    ops::{Bound, Range},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use crate::{
+    executor::{Executor, Priority},
     webgraph::merge::{EdgeMerger, MergeIter},
     webpage::html::links::RelFlags,
     Result,
@@ -27,14 +35,17 @@ use file_store::{
         ConstIterableStoreReader, ConstIterableStoreWriter, IterableStoreReader,
         IterableStoreWriter,
     },
-    ConstSerializable,
+    Advice, ConstSerializable,
 };
-use fst::Automaton;
 use itertools::Itertools;
 
 use super::{
+    block_cache::StoreBlockCache,
+    deadline::Deadline,
     merge::{MergeNode, MergeSegmentOrd, NodeDatum},
-    Compression, EdgeLimit, FullNodeID, NodeID, SegmentEdge, StoredEdge,
+    tombstone::Tombstones,
+    AdjacencyCodec, Codec, Compression, EdgeCursor, EdgeFilter, EdgeLabel, EdgeLimit, EdgeMetadata,
+    EdgeTimestamps, FullNodeID, LinkBlock, NodeID, SegmentEdge, StoredEdge,
 };
 
 #[derive(
@@ -54,65 +65,227 @@ struct SerializedEdge {
     label: Vec<u8>,
 }
 
+const HOST_POSTINGS_FOLDER: &str = "postings";
+
+fn host_key(host: &NodeID) -> [u8; 8] {
+    host.as_u64().to_le_bytes()
+}
+
+fn parse_host_key(key: &[u8]) -> (NodeID, NodeID) {
+    let host = u64::from_le_bytes(key[..u64::BITS as usize / 8].try_into().unwrap());
+    let page = u64::from_le_bytes(key[u64::BITS as usize / 8..].try_into().unwrap());
+
+    (NodeID::from(host), NodeID::from(page))
+}
+
+/// The sorted union of two sorted posting lists.
+fn union_postings(a: Vec<NodeID>, b: Vec<NodeID>) -> Vec<NodeID> {
+    if a.is_empty() {
+        return b;
+    }
+
+    if b.is_empty() {
+        return a;
+    }
+
+    a.into_iter().merge(b).dedup().collect()
+}
+
+/// The pages of each host. The pages are inserted with their host as the prefix
+/// of the key, and are added to a sorted posting list for each host when the db
+/// is flushed, so the pages of a host are found with a single read.
 pub struct HostDb {
     db: speedy_kv::Db<Vec<u8>, ()>,
+    /// `None` for stores written before the postings were materialized. The pages
+    /// of a host are then found with a prefix search in `db`.
+    postings: Option<speedy_kv::Db<NodeID, Vec<NodeID>>>,
+    /// The pages inserted since the last flush.
+    pending: HashMap<NodeID, Vec<NodeID>>,
 }
 
 impl HostDb {
     pub fn open<P: AsRef<Path>>(path: P) -> Self {
+        let postings_path = path.as_ref().join(HOST_POSTINGS_FOLDER);
+        let has_postings = postings_path.exists() || !path.as_ref().exists();
+
         let db = speedy_kv::Db::open_or_create(path).unwrap();
+        let postings = has_postings.then(|| speedy_kv::Db::open_or_create(postings_path).unwrap());
 
-        Self { db }
+        Self {
+            db,
+            postings,
+            pending: HashMap::new(),
+        }
     }
 
     fn optimize_read(&mut self) {
         self.db.merge_all_segments().unwrap();
+
+        if let Some(postings) = &mut self.postings {
+            postings.merge_all_segments().unwrap();
+        }
     }
 
     pub fn insert(&mut self, node: &FullNodeID) {
-        let key = [
-            node.host.as_u64().to_le_bytes(),
-            node.id.as_u64().to_le_bytes(),
-        ]
-        .concat();
+        let key = [host_key(&node.host), node.id.as_u64().to_le_bytes()].concat();
 
         self.db.insert_raw(key, vec![]);
+        self.pending.entry(node.host).or_default().push(node.id);
     }
 
+    /// The pages of the host, sorted by their id.
     fn get(&self, host: &NodeID) -> Vec<NodeID> {
-        let host = host.as_u64().to_le_bytes().to_vec();
+        match &self.postings {
+            Some(postings) => postings.get(host).unwrap().unwrap_or_default(),
+            None => {
+                let key = host_key(host);
+                let query = speedy_kv::automaton::ExactMatch(&key).starts_with();
+
+                let mut pages: Vec<_> = self
+                    .db
+                    .search_raw(query)
+                    .map(|(key, _)| parse_host_key(key.as_bytes()).1)
+                    .collect();
+                pages.sort();
+                pages.dedup();
+
+                pages
+            }
+        }
+    }
 
-        let query = speedy_kv::automaton::ExactMatch(&host).starts_with();
+    /// The posting list of every host, ordered by the serialized host id.
+    fn sorted_iter(&self) -> Box<dyn Iterator<Item = (NodeID, Vec<NodeID>)> + '_> {
+        match &self.postings {
+            Some(postings) => Box::new(postings.sorted_iter()),
+            None => Box::new(self.sorted_iter_pages()),
+        }
+    }
 
+    /// The posting lists built from the pages in `db`. The keys start with the host,
+    /// so the pages of each host are next to each other.
+    fn sorted_iter_pages(&self) -> impl Iterator<Item = (NodeID, Vec<NodeID>)> + '_ {
         self.db
-            .search_raw(query)
+            .sorted_iter_raw()
             .map(|(key, _)| {
-                let id = u64::from_le_bytes(
-                    key.as_bytes()[u64::BITS as usize / 8..].try_into().unwrap(),
-                );
-                NodeID::from(id)
+                let (host, page) = parse_host_key(key.as_bytes());
+                (host, vec![page])
             })
-            .collect()
+            .coalesce(|(a, mut a_pages), (b, b_pages)| {
+                if a == b {
+                    a_pages.extend(b_pages);
+                    Ok((a, a_pages))
+                } else {
+                    Err(((a, a_pages), (b, b_pages)))
+                }
+            })
+            .map(|(host, mut pages)| {
+                pages.sort();
+                pages.dedup();
+
+                (host, pages)
+            })
+    }
+
+    /// Add the pages to the posting lists of their hosts. Only the posting lists of
+    /// the hosts with new pages are written, in a new segment of the postings.
+    fn add_postings(&mut self, pages: impl Iterator<Item = (NodeID, Vec<NodeID>)>) {
+        if self.postings.is_none() {
+            self.materialize_postings();
+        }
+
+        for (host, mut new_pages) in pages {
+            new_pages.sort();
+            new_pages.dedup();
+
+            let pages = union_postings(self.get(&host), new_pages);
+            self.postings.as_mut().unwrap().insert(host, pages).unwrap();
+        }
+
+        self.postings.as_mut().unwrap().commit().unwrap();
+    }
+
+    /// Write the posting lists of a store from before the postings were materialized.
+    /// This only happens the first time pages are added to such a store.
+    fn materialize_postings(&mut self) {
+        let mut postings =
+            speedy_kv::Db::open_or_create(self.db.folder().join(HOST_POSTINGS_FOLDER)).unwrap();
+
+        for (host, pages) in self.sorted_iter_pages() {
+            postings.insert(host, pages).unwrap();
+        }
+
+        postings.commit().unwrap();
+        self.postings = Some(postings);
     }
 
     pub fn flush(&mut self) {
         self.db.commit().unwrap();
+
+        let pending = std::mem::take(&mut self.pending);
+        self.add_postings(pending.into_iter());
     }
 
-    fn merge(&mut self, other: HostDb) {
-        self.db.merge(other.db).unwrap();
+    /// Merge the pages of `other` into the db. The posting lists of `other` are added
+    /// to the existing posting lists, so only the hosts in `other` are written.
+    fn merge(&mut self, other: HostDb, tombstones: &Tombstones) {
+        self.add_postings(
+            other
+                .sorted_iter()
+                .map(|(host, mut pages)| {
+                    pages.retain(|page| !tombstones.is_node_deleted(page));
+                    (host, pages)
+                })
+                .filter(|(_, pages)| !pages.is_empty()),
+        );
+
+        if tombstones.is_empty() {
+            self.db.merge(other.db).unwrap();
+            return;
+        }
+
+        for (key, _) in other.db.iter_raw() {
+            let key = key.as_bytes();
+            let (_, id) = parse_host_key(key);
+
+            if !tombstones.is_node_deleted(&id) {
+                self.db.insert_raw(key.to_vec(), vec![]);
+            }
+        }
+
+        self.db.commit().unwrap();
+    }
+}
+
+/// The fixed-width edges of a store.
+const EDGES_FILE: &str = "edges";
+/// The edges of a store in blocks encoded with an [`AdjacencyCodec`].
+const EDGE_BLOCKS_FILE: &str = "edge_blocks";
+
+fn adjacency_file(codec: &AdjacencyCodec) -> &'static str {
+    if codec.is_blocked() {
+        EDGE_BLOCKS_FILE
+    } else {
+        EDGES_FILE
     }
 }
 
+/// The byte range of the edges of a node in the adjacency store. The number of edges
+/// is stored as well, as it cannot be derived from the size of encoded blocks.
 #[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
 pub struct EdgeRange {
     range: std::ops::Range<u64>,
+    num_edges: u64,
     sort_key: u64,
 }
 
 impl EdgeRange {
-    pub fn new(range: std::ops::Range<u64>, sort_key: u64) -> Self {
-        Self { range, sort_key }
+    pub fn new(range: std::ops::Range<u64>, num_edges: u64, sort_key: u64) -> Self {
+        Self {
+            range,
+            num_edges,
+            sort_key,
+        }
     }
 }
 
@@ -139,6 +312,18 @@ impl RangesDb {
         self.labels.merge_all_segments().unwrap();
     }
 
+    /// Every edge query starts with a lookup in the ranges, so they are kept in memory if
+    /// possible. If the pages cannot be locked, e.g. because of `RLIMIT_MEMLOCK`, they are
+    /// only read ahead.
+    fn lock(&self) {
+        if let Err(err) = self.edges.lock().and_then(|_| self.labels.lock()) {
+            tracing::warn!("failed to lock edge ranges in memory: {}", err);
+
+            let _ = self.edges.advise(Advice::WillNeed);
+            let _ = self.labels.advise(Advice::WillNeed);
+        }
+    }
+
     fn uncommitted_node_inserts(&self) -> usize {
         self.edges.uncommitted_inserts()
     }
@@ -170,9 +355,18 @@ impl RangesDb {
         self.labels.insert_raw(node, range);
     }
 
+    /// The nodes in the order of their keys, which is the order [`MergeIter`] expects.
     fn merge_nodes(&self) -> impl Iterator<Item = MergeNode> + '_ {
-        self.edges.iter_raw().zip_eq(self.labels.iter_raw()).map(
-            move |((key_node, val), (key_label, labels))| {
+        self.merge_nodes_in(KeyRange::default())
+    }
+
+    /// The nodes with keys in the range in the order of their keys. The segments are
+    /// only read from the start of the range.
+    fn merge_nodes_in<'a>(&'a self, range: KeyRange<'a>) -> impl Iterator<Item = MergeNode> + 'a {
+        self.edges
+            .sorted_range_raw(range.bounds())
+            .zip_eq(self.labels.sorted_range_raw(range.bounds()))
+            .map(move |((key_node, val), (key_label, labels))| {
                 debug_assert_eq!(key_node, key_label);
 
                 let node = NodeID::deserialize(key_node.as_bytes());
@@ -184,8 +378,7 @@ impl RangesDb {
                 };
 
                 MergeNode::new(node, range, labels)
-            },
-        )
+            })
     }
 }
 
@@ -235,16 +428,70 @@ impl ConstSerializable for EdgeRange {
 
     fn serialize(&self, buf: &mut [u8]) {
         const RANGE_BYTES: usize = std::mem::size_of::<u64>() * 2;
+        const NUM_EDGES_END: usize = RANGE_BYTES + u64::BYTES;
         self.range.serialize(&mut buf[..RANGE_BYTES]);
-        self.sort_key.serialize(&mut buf[RANGE_BYTES..]);
+        self.num_edges
+            .serialize(&mut buf[RANGE_BYTES..NUM_EDGES_END]);
+        self.sort_key.serialize(&mut buf[NUM_EDGES_END..]);
     }
 
     fn deserialize(buf: &[u8]) -> Self {
         const RANGE_BYTES: usize = std::mem::size_of::<u64>() * 2;
+        const NUM_EDGES_END: usize = RANGE_BYTES + u64::BYTES;
         let range: Range<u64> = Range::deserialize(&buf[..RANGE_BYTES]);
-        let sort_key = u64::deserialize(&buf[RANGE_BYTES..]);
+        let num_edges = u64::deserialize(&buf[RANGE_BYTES..NUM_EDGES_END]);
+        let sort_key = u64::deserialize(&buf[NUM_EDGES_END..]);
 
-        Self { range, sort_key }
+        Self {
+            range,
+            num_edges,
+            sort_key,
+        }
+    }
+}
+
+impl ConstSerializable for EdgeTimestamps {
+    const BYTES: usize = std::mem::size_of::<EdgeTimestamps>();
+
+    fn serialize(&self, buf: &mut [u8]) {
+        const FIRST_SEEN_BYTES: usize = std::mem::size_of::<u32>();
+        self.first_seen.serialize(&mut buf[..FIRST_SEEN_BYTES]);
+        self.last_seen.serialize(&mut buf[FIRST_SEEN_BYTES..]);
+    }
+
+    fn deserialize(buf: &[u8]) -> Self {
+        const FIRST_SEEN_BYTES: usize = std::mem::size_of::<u32>();
+        let first_seen = u32::deserialize(&buf[..FIRST_SEEN_BYTES]);
+        let last_seen = u32::deserialize(&buf[FIRST_SEEN_BYTES..]);
+
+        Self {
+            first_seen,
+            last_seen,
+        }
+    }
+}
+
+impl ConstSerializable for EdgeMetadata {
+    const BYTES: usize = std::mem::size_of::<EdgeMetadata>();
+
+    fn serialize(&self, buf: &mut [u8]) {
+        self.dom_depth.serialize(&mut buf[..2]);
+        self.position.serialize(&mut buf[2..4]);
+        self.heading_level.serialize(&mut buf[4..5]);
+        self.block.as_u8().serialize(&mut buf[5..6]);
+        self.text_offset.serialize(&mut buf[6..10]);
+        self.num_collapsed.serialize(&mut buf[10..12]);
+    }
+
+    fn deserialize(buf: &[u8]) -> Self {
+        Self {
+            dom_depth: u16::deserialize(&buf[..2]),
+            position: u16::deserialize(&buf[2..4]),
+            heading_level: u8::deserialize(&buf[4..5]),
+            block: LinkBlock::from(u8::deserialize(&buf[5..6])),
+            text_offset: u32::deserialize(&buf[6..10]),
+            num_collapsed: u16::deserialize(&buf[10..12]),
+        }
     }
 }
 
@@ -252,15 +499,25 @@ impl ConstSerializable for StoredEdge {
     const BYTES: usize = std::mem::size_of::<StoredEdge>();
 
     fn serialize(&self, buf: &mut [u8]) {
+        const REL_END: usize = NodeDatum::BYTES + RelFlags::BYTES;
+        const TIMESTAMPS_END: usize = REL_END + EdgeTimestamps::BYTES;
         self.other.serialize(&mut buf[..NodeDatum::BYTES]);
-        self.rel.serialize(&mut buf[NodeDatum::BYTES..]);
+        self.rel.serialize(&mut buf[NodeDatum::BYTES..REL_END]);
+        self.timestamps.serialize(&mut buf[REL_END..TIMESTAMPS_END]);
+        self.metadata
+            .serialize(&mut buf[TIMESTAMPS_END..TIMESTAMPS_END + EdgeMetadata::BYTES]);
     }
 
     fn deserialize(buf: &[u8]) -> Self {
+        const REL_END: usize = NodeDatum::BYTES + RelFlags::BYTES;
+        const TIMESTAMPS_END: usize = REL_END + EdgeTimestamps::BYTES;
         let other = NodeDatum::deserialize(&buf[..NodeDatum::BYTES]);
-        let rel = RelFlags::deserialize(&buf[NodeDatum::BYTES..]);
+        let rel = RelFlags::deserialize(&buf[NodeDatum::BYTES..REL_END]);
+        let timestamps = EdgeTimestamps::deserialize(&buf[REL_END..TIMESTAMPS_END]);
+        let metadata =
+            EdgeMetadata::deserialize(&buf[TIMESTAMPS_END..TIMESTAMPS_END + EdgeMetadata::BYTES]);
 
-        Self::new(other, rel)
+        Self::new(other, rel, timestamps).with_metadata(metadata)
     }
 }
 
@@ -276,12 +533,12 @@ impl LabelBlock {
         Self { labels }
     }
 
-    pub fn compress(&self, compression: Compression) -> CompressedLabelBlock {
+    pub fn compress(&self, codec: Codec) -> CompressedLabelBlock {
         let bytes = bincode::encode_to_vec(self, bincode::config::standard()).unwrap();
-        let compressed = compression.compress(&bytes);
+        let compressed = codec.compress(&bytes);
 
         CompressedLabelBlock {
-            compressions: compression,
+            compressions: codec,
             data: compressed,
         }
     }
@@ -289,25 +546,289 @@ impl LabelBlock {
 
 #[derive(bincode::Encode, bincode::Decode)]
 pub struct CompressedLabelBlock {
-    compressions: Compression,
+    compressions: Codec,
     data: Vec<u8>,
 }
 
 impl CompressedLabelBlock {
     pub fn decompress(&self) -> LabelBlock {
-        let bytes = self.compressions.decompress(&self.data);
-        let (res, _) = bincode::decode_from_slice(&bytes, bincode::config::standard()).unwrap();
-        res
+        self.try_decompress().unwrap()
+    }
+
+    pub fn try_decompress(&self) -> Result<LabelBlock> {
+        let bytes = self.compressions.try_decompress(&self.data)?;
+        let (res, _) = bincode::decode_from_slice(&bytes, bincode::config::standard())?;
+        Ok(res)
+    }
+}
+
+/// Up to `NUM_LABELS_PER_BLOCK` edges of a node, so the edge blocks line up with the label blocks.
+#[derive(bincode::Encode, bincode::Decode)]
+pub struct CompressedEdgeBlock {
+    codec: AdjacencyCodec,
+    data: Vec<u8>,
+}
+
+impl CompressedEdgeBlock {
+    fn new(edges: &[StoredEdge], codec: AdjacencyCodec) -> Self {
+        Self {
+            codec,
+            data: codec.encode(edges),
+        }
+    }
+
+    pub fn decode(&self) -> Vec<StoredEdge> {
+        self.try_decode().unwrap()
+    }
+
+    pub fn try_decode(&self) -> Result<Vec<StoredEdge>> {
+        self.codec.decode(&self.data)
     }
 }
 
+/// The edges of the nodes in a store. Whether they are stored as fixed-width edges
+/// or in blocks is given by which of the files exists.
+enum AdjacencyReader {
+    Fixed(ConstIterableStoreReader<StoredEdge>),
+    Blocks(IterableStoreReader<CompressedEdgeBlock>),
+}
+
+impl AdjacencyReader {
+    fn open<P: AsRef<Path>>(path: P) -> Self {
+        let blocks_path = path.as_ref().join(EDGE_BLOCKS_FILE);
+
+        if blocks_path.exists() {
+            Self::Blocks(IterableStoreReader::open(blocks_path).unwrap())
+        } else {
+            Self::Fixed(ConstIterableStoreReader::open(path.as_ref().join(EDGES_FILE)).unwrap())
+        }
+    }
+
+    /// The edges in `range`, starting from the first edge of block `first_block`.
+    /// Decoded blocks are read from and added to the cache if there is one.
+    fn edges(
+        &self,
+        range: &EdgeRange,
+        first_block: usize,
+        cache: Option<&StoreBlockCache>,
+    ) -> Box<dyn Iterator<Item = StoredEdge>> {
+        let bytes = usize_range(range.range.clone());
+
+        match (self, cache) {
+            (Self::Fixed(edges), _) => {
+                let start = bytes.start + first_block * NUM_LABELS_PER_BLOCK * StoredEdge::BYTES;
+                Box::new(edges.slice(start..bytes.end))
+            }
+            (Self::Blocks(blocks), None) => Box::new(
+                blocks
+                    .slice(bytes)
+                    .skip(first_block)
+                    .flat_map(|block| block.decode()),
+            ),
+            (Self::Blocks(blocks), Some(cache)) => Box::new(
+                Self::cached_blocks(blocks, bytes, first_block, cache.clone())
+                    .flat_map(|edges| edges.to_vec()),
+            ),
+        }
+    }
+
+    /// The decoded blocks in `bytes` from block `first_block`, through the cache.
+    fn cached_blocks(
+        blocks: &IterableStoreReader<CompressedEdgeBlock>,
+        bytes: Range<usize>,
+        first_block: usize,
+        cache: StoreBlockCache,
+    ) -> impl Iterator<Item = Arc<[StoredEdge]>> {
+        let blocks = blocks.slice(bytes.clone());
+        let ranges: Vec<_> = blocks.item_ranges().skip(first_block).collect();
+
+        ranges.into_iter().map(move |range| {
+            cache.edges(bytes.start + range.start, || {
+                blocks.slice(range).next().unwrap().decode()
+            })
+        })
+    }
+
+    /// Index of the first edge in `range` that comes after the cursor.
+    fn first_edge_after(
+        &self,
+        range: &EdgeRange,
+        cursor: &EdgeCursor,
+        cache: Option<&StoreBlockCache>,
+    ) -> usize {
+        let bytes = usize_range(range.range.clone());
+
+        match self {
+            // the edges of a node are sorted, so this is a binary search.
+            Self::Fixed(edges) => {
+                let edge_at = |i: usize| {
+                    let start = bytes.start + i * StoredEdge::BYTES;
+                    edges
+                        .slice(start..start + StoredEdge::BYTES)
+                        .next()
+                        .unwrap()
+                };
+
+                let mut low = 0;
+                let mut high = range.num_edges as usize;
+
+                while low < high {
+                    let mid = low + (high - low) / 2;
+
+                    if cursor.is_before(&edge_at(mid).other) {
+                        high = mid;
+                    } else {
+                        low = mid + 1;
+                    }
+                }
+
+                low
+            }
+            Self::Blocks(blocks) => {
+                let decoded: Box<dyn Iterator<Item = Arc<[StoredEdge]>>> = match cache {
+                    Some(cache) => Box::new(Self::cached_blocks(blocks, bytes, 0, cache.clone())),
+                    None => Box::new(blocks.slice(bytes).map(|block| block.decode().into())),
+                };
+
+                let mut num_skipped = 0;
+
+                for edges in decoded {
+                    match edges.iter().position(|edge| cursor.is_before(&edge.other)) {
+                        Some(pos) => return num_skipped + pos,
+                        None => num_skipped += edges.len(),
+                    }
+                }
+
+                num_skipped
+            }
+        }
+    }
+
+    fn advise(&self, advice: Advice) -> std::io::Result<()> {
+        match self {
+            Self::Fixed(edges) => edges.advise(advice),
+            Self::Blocks(blocks) => blocks.advise(advice),
+        }
+    }
+
+    /// Decode every block in `range` and return the number of edges.
+    fn verify(&self, range: &EdgeRange) -> Result<u64> {
+        match self {
+            Self::Fixed(_) => Ok(range.num_edges),
+            Self::Blocks(blocks) => {
+                let mut num_edges = 0;
+
+                for block in blocks.slice(usize_range(range.range.clone())) {
+                    num_edges += block.try_decode()?.len() as u64;
+                }
+
+                Ok(num_edges)
+            }
+        }
+    }
+}
+
+pub enum AdjacencyWriter {
+    Fixed(ConstIterableStoreWriter<StoredEdge, File>),
+    Blocks(
+        IterableStoreWriter<CompressedEdgeBlock, File>,
+        AdjacencyCodec,
+    ),
+}
+
+impl AdjacencyWriter {
+    pub fn open<P: AsRef<Path>>(folder: P, codec: AdjacencyCodec) -> Self {
+        let file = File::options()
+            .read(true)
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(folder.as_ref().join(adjacency_file(&codec)))
+            .unwrap();
+
+        if codec.is_blocked() {
+            Self::Blocks(IterableStoreWriter::new(file), codec)
+        } else {
+            Self::Fixed(ConstIterableStoreWriter::new(file))
+        }
+    }
+
+    /// Write a block of edges of a node and return the bytes they were written to.
+    /// The edges of a node must be written in consecutive calls.
+    pub fn write_block(&mut self, edges: &[StoredEdge]) -> Result<Range<u64>> {
+        match self {
+            Self::Fixed(writer) => {
+                let mut range: Option<Range<u64>> = None;
+
+                for edge in edges {
+                    let offset = writer.write(edge)?;
+                    range = Some(match range {
+                        Some(range) => range.start..offset.range().end,
+                        None => offset.range(),
+                    });
+                }
+
+                Ok(range.unwrap_or_default())
+            }
+            Self::Blocks(writer, codec) => {
+                let offset = writer.write(&CompressedEdgeBlock::new(edges, *codec))?;
+                Ok(offset.range())
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Fixed(writer) => writer.flush(),
+            Self::Blocks(writer, _) => writer.flush(),
+        }
+    }
+}
+
+/// A range of keys in the ranges of a store. An unset bound is unbounded.
+#[derive(Clone, Copy, Default)]
+struct KeyRange<'a> {
+    start: Option<&'a [u8]>,
+    end: Option<&'a [u8]>,
+}
+
+impl<'a> KeyRange<'a> {
+    fn bounds<T>(
+        &self,
+    ) -> (
+        Bound<speedy_kv::SerializedRef<'a, T>>,
+        Bound<speedy_kv::SerializedRef<'a, T>>,
+    ) {
+        (
+            self.start
+                .map_or(Bound::Unbounded, |start| Bound::Included(start.into())),
+            self.end
+                .map_or(Bound::Unbounded, |end| Bound::Excluded(end.into())),
+        )
+    }
+}
+
+/// The first key of each partition when the keys are split into `num_partitions` ranges.
+/// The keys are the little-endian bytes of the node ids, so the keys are split on their
+/// first two bytes. Node ids are hashes, so the partitions hold roughly the same number
+/// of nodes.
+fn partition_starts(num_partitions: usize) -> Vec<[u8; 2]> {
+    (0..num_partitions)
+        .map(|i| {
+            let prefix = (i << 16).div_ceil(num_partitions).min(u16::MAX as usize);
+            (prefix as u16).to_be_bytes()
+        })
+        .collect()
+}
+
 pub struct EdgeStore {
     reversed: bool,
     ranges: RangesDb,
     hosts: HostDb,
 
     edge_labels: IterableStoreReader<CompressedLabelBlock>,
-    edges: ConstIterableStoreReader<StoredEdge>,
+    edges: AdjacencyReader,
+    block_cache: Option<StoreBlockCache>,
 }
 
 impl EdgeStore {
@@ -316,31 +837,61 @@ impl EdgeStore {
 
         let edge_labels = IterableStoreReader::open(path.as_ref().join("labels")).unwrap();
 
-        let edges = ConstIterableStoreReader::open(path.as_ref().join("edges")).unwrap();
+        let edges = AdjacencyReader::open(&path);
 
-        Self {
+        let store = Self {
             ranges,
             hosts: HostDb::open(path.as_ref().join("hosts")),
             edge_labels,
             edges,
             reversed,
+            block_cache: None,
+        };
+
+        // queries read a few edges of a node at a time, so reading ahead only
+        // wastes the page cache.
+        store.advise(Advice::Random);
+
+        store
+    }
+
+    /// Hint the kernel about how the edges and labels will be read.
+    fn advise(&self, advice: Advice) {
+        if let Err(err) = self
+            .edges
+            .advise(advice)
+            .and_then(|_| self.edge_labels.advise(advice))
+        {
+            tracing::warn!("failed to advise edge store: {}", err);
         }
     }
 
-    pub fn optimize_read(&mut self) {
+    /// Read the decoded edge and label blocks through the cache.
+    pub fn set_block_cache(&mut self, cache: StoreBlockCache) {
+        self.block_cache = Some(cache);
+    }
+
+    pub fn merge_index_segments(&mut self) {
         self.ranges.optimize_read();
         self.hosts.optimize_read();
     }
 
+    /// Merge the segments of the ranges and hosts, and lock the ranges in memory
+    /// for as long as the store is open.
+    pub fn optimize_read(&mut self) {
+        self.merge_index_segments();
+        self.ranges.lock();
+    }
+
     fn merge_postings_for_node<'a>(
         buf: &[MergeNode<MergeSegmentOrd>],
-        stores: &'a [EdgeStore],
+        stores: &[&'a EdgeStore],
     ) -> EdgeMerger<'a> {
         let mut edges = Vec::new();
 
         for node in buf {
-            let store = &stores[node.ord().as_usize()];
-            let edge_nodes = store.edges.slice(usize_range(node.range().range.clone()));
+            let store = stores[node.ord().as_usize()];
+            let edge_nodes = store.edges.edges(node.range(), 0, None);
             let edge_labels = store
                 .edge_labels
                 .slice(usize_range(node.labels()))
@@ -357,11 +908,15 @@ impl EdgeStore {
         EdgeMerger::new(edges)
     }
 
+    /// Merge the postings of the nodes with keys in `partition` into the ranges,
+    /// labels and edges files in `folder`.
     fn merge_postings<P: AsRef<Path>>(
         stores: &[EdgeStore],
-        label_compression: Compression,
+        compression: Compression,
         folder: P,
-    ) -> Result<Self> {
+        tombstones: &Tombstones,
+        partition: KeyRange<'_>,
+    ) -> Result<()> {
         let reversed = stores[0].reversed;
         let mut ranges = RangesDb::open(folder.as_ref().join("ranges"));
 
@@ -374,23 +929,16 @@ impl EdgeStore {
             .unwrap();
         let mut labels_store = IterableStoreWriter::new(labels_file);
 
-        let edges_file = File::options()
-            .read(true)
-            .create(true)
-            .truncate(false)
-            .write(true)
-            .open(folder.as_ref().join("edges"))
-            .unwrap();
-        let mut edges_store: ConstIterableStoreWriter<StoredEdge, File> =
-            ConstIterableStoreWriter::new(edges_file);
+        let mut edges_store = AdjacencyWriter::open(&folder, compression.adjacency);
 
         let mut merge_iter = MergeIter::new(
             stores
                 .iter()
-                .map(|store| store.ranges.merge_nodes())
+                .map(|store| store.ranges.merge_nodes_in(partition))
                 .collect(),
         );
 
+        let stores: Vec<_> = stores.iter().collect();
         let mut buf = Vec::new();
 
         while merge_iter.advance(&mut buf) {
@@ -398,22 +946,35 @@ impl EdgeStore {
                 continue;
             }
 
-            let edges = Self::merge_postings_for_node(&buf, stores);
+            let node_id = buf[0].id();
+
+            if tombstones.is_node_deleted(&node_id) {
+                continue;
+            }
+
+            let edges = Self::merge_postings_for_node(&buf, &stores).filter(|edge| {
+                let (from, to) = if reversed {
+                    (edge.other(), node_id)
+                } else {
+                    (node_id, edge.other())
+                };
+
+                !tombstones.is_edge_deleted(&from, &to)
+            });
 
             // write postings
             let node_sort_key = buf[0].range().sort_key;
-            let node_id = buf[0].id();
             let mut first_label_offset = None;
             let mut last_label_offset = None;
-            let mut first_node_offset = None;
-            let mut last_node_offset = None;
+            let mut node_bytes_range: Option<Range<u64>> = None;
+            let mut num_edges = 0;
 
             for chunk in edges.chunks(NUM_LABELS_PER_BLOCK).into_iter() {
                 let (labels, edges): (Vec<_>, Vec<_>) = chunk
                     .map(|edge| (edge.label().clone(), edge.with_label(())))
                     .unzip();
 
-                let label_block = LabelBlock::new(labels).compress(label_compression);
+                let label_block = LabelBlock::new(labels).compress(compression.labels);
 
                 let label_offset = labels_store.write(&label_block).unwrap();
 
@@ -423,29 +984,26 @@ impl EdgeStore {
 
                 last_label_offset = Some(label_offset);
 
-                for edge in edges {
-                    let offset = edges_store.write(&edge).unwrap();
-
-                    if first_node_offset.is_none() {
-                        first_node_offset = Some(offset);
-                    }
+                let block_range = edges_store.write_block(&edges).unwrap();
+                num_edges += edges.len() as u64;
 
-                    last_node_offset = Some(offset);
-                }
+                node_bytes_range = Some(match node_bytes_range {
+                    Some(range) => range.start..block_range.end,
+                    None => block_range,
+                });
             }
 
+            // all edges of the node were deleted
+            let Some(node_bytes_range) = node_bytes_range else {
+                continue;
+            };
+
             let label_range = Range {
                 start: first_label_offset.unwrap().start,
                 end: last_label_offset.unwrap().start + last_label_offset.unwrap().num_bytes,
             };
 
-            let node_range: EdgeRange = EdgeRange::new(
-                Range {
-                    start: first_node_offset.unwrap().start,
-                    end: last_node_offset.unwrap().start + last_node_offset.unwrap().num_bytes,
-                },
-                node_sort_key,
-            );
+            let node_range: EdgeRange = EdgeRange::new(node_bytes_range, num_edges, node_sort_key);
             let node_range_bytes = node_range.serialize_to_vec();
 
             let node_bytes = node_id.serialize_to_vec();
@@ -464,17 +1022,83 @@ impl EdgeStore {
 
         if ranges.uncommitted_node_inserts() > 0 {
             ranges.commit();
-            edges_store.flush().unwrap();
-            labels_store.flush().unwrap();
         }
 
-        Ok(Self::open(folder, reversed))
+        edges_store.flush()?;
+        labels_store.flush()?;
+
+        Ok(())
+    }
+
+    /// Append the merged partitions in `partitions` to the store at `folder`. The edges
+    /// and labels files are concatenated, so the ranges of each partition are shifted
+    /// by the size of the files of the partitions before it.
+    fn concat_partitions<P: AsRef<Path>>(
+        partitions: &[PathBuf],
+        folder: P,
+        codec: &AdjacencyCodec,
+    ) -> Result<()> {
+        let edges_file_name = adjacency_file(codec);
+        let mut ranges = RangesDb::open(folder.as_ref().join("ranges"));
+        let mut edges_file = File::create(folder.as_ref().join(edges_file_name))?;
+        let mut labels_file = File::create(folder.as_ref().join("labels"))?;
+
+        let mut edges_offset = 0;
+        let mut labels_offset = 0;
+
+        for partition in partitions {
+            let partition_ranges = RangesDb::open(partition.join("ranges"));
+
+            for ((node, edge_range), (_, label_range)) in partition_ranges
+                .edges
+                .iter_raw()
+                .zip_eq(partition_ranges.labels.iter_raw())
+            {
+                let mut edge_range = EdgeRange::deserialize(edge_range.as_bytes());
+                edge_range.range =
+                    edge_range.range.start + edges_offset..edge_range.range.end + edges_offset;
+
+                let label_range: Range<u64> = Range::deserialize(label_range.as_bytes());
+                let label_range =
+                    label_range.start + labels_offset..label_range.end + labels_offset;
+
+                let node = node.as_bytes().to_vec();
+                ranges.insert_raw_node(node.clone(), edge_range.serialize_to_vec());
+                ranges.insert_raw_label(node, label_range.serialize_to_vec());
+
+                if ranges.uncommitted_node_inserts() > 100_000_000 {
+                    ranges.commit();
+                }
+            }
+
+            edges_offset += io::copy(
+                &mut File::open(partition.join(edges_file_name))?,
+                &mut edges_file,
+            )?;
+            labels_offset +=
+                io::copy(&mut File::open(partition.join("labels"))?, &mut labels_file)?;
+
+            drop(partition_ranges);
+            std::fs::remove_dir_all(partition)?;
+        }
+
+        ranges.commit();
+        edges_file.sync_all()?;
+        labels_file.sync_all()?;
+
+        Ok(())
     }
 
+    /// Merge the stores into a new store at `path`. Nodes and edges with a tombstone are dropped.
+    ///
+    /// The nodes are split into a range of keys per executor thread, and the ranges are
+    /// merged in parallel. Each range only reads the nodes in it from the stores.
     pub fn merge<P: AsRef<Path>>(
         stores: Vec<EdgeStore>,
-        label_compression: Compression,
+        compression: Compression,
         path: P,
+        tombstones: &Tombstones,
+        executor: &Executor,
     ) -> Result<()> {
         if stores.is_empty() {
             return Ok(());
@@ -489,18 +1113,118 @@ impl EdgeStore {
             bail!("Cannot merge stores with different reversed flags");
         }
 
-        let mut res = Self::merge_postings(&stores, label_compression, path)?;
+        // the stores are read from start to end
+        for store in &stores {
+            store.advise(Advice::Sequential);
+        }
+
+        let num_partitions = executor.num_threads();
+
+        if num_partitions <= 1 {
+            Self::merge_postings(&stores, compression, &path, tombstones, KeyRange::default())?;
+        } else {
+            let partitions: Vec<_> = (0..num_partitions)
+                .map(|i| path.as_ref().join(format!("partition_{i}")))
+                .collect();
+            let starts = partition_starts(num_partitions);
+
+            executor
+                .map_with_priority(
+                    Priority::Batch,
+                    |(i, partition)| -> Result<()> {
+                        std::fs::create_dir_all(partition)?;
+
+                        let range = KeyRange {
+                            start: (i > 0).then(|| starts[i].as_slice()),
+                            end: starts.get(i + 1).map(|end| end.as_slice()),
+                        };
+
+                        Self::merge_postings(&stores, compression, partition, tombstones, range)
+                    },
+                    partitions.iter().enumerate(),
+                )?
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?;
+
+            Self::concat_partitions(&partitions, &path, &compression.adjacency)?;
+        }
+
+        let mut res = Self::open(path, reversed);
 
         for store in stores {
-            res.hosts.merge(store.hosts);
+            res.hosts.merge(store.hosts, tombstones);
         }
+        res.hosts.flush();
 
-        res.optimize_read();
+        res.merge_index_segments();
 
         Ok(())
     }
 
-    pub fn get_with_label(&self, node: &NodeID, limit: &EdgeLimit) -> Vec<SegmentEdge<String>> {
+    /// The edges of all the stores, see [`SortedEdges`]. The stores must have the
+    /// same direction.
+    pub fn sorted_edges<'a>(stores: Vec<&'a EdgeStore>) -> SortedEdges<'a> {
+        debug_assert!(stores.windows(2).all(|w| w[0].reversed == w[1].reversed));
+
+        SortedEdges {
+            nodes: MergeIter::new(
+                stores
+                    .iter()
+                    .map(|store| store.ranges.merge_nodes())
+                    .collect(),
+            ),
+            stores,
+            buf: Vec::new(),
+            current: None,
+        }
+    }
+
+    fn segment_edge<L: EdgeLabel>(
+        &self,
+        node: &NodeID,
+        sort_key: u64,
+        edge: StoredEdge,
+        label: L,
+    ) -> SegmentEdge<L> {
+        if self.reversed {
+            SegmentEdge {
+                from: edge.other,
+                to: NodeDatum::new(*node, sort_key),
+                rel: edge.rel,
+                label,
+                timestamps: edge.timestamps,
+                metadata: edge.metadata,
+            }
+        } else {
+            SegmentEdge {
+                from: NodeDatum::new(*node, sort_key),
+                to: edge.other,
+                rel: edge.rel,
+                label,
+                timestamps: edge.timestamps,
+                metadata: edge.metadata,
+            }
+        }
+    }
+
+    pub fn get_with_label(
+        &self,
+        node: &NodeID,
+        limit: &EdgeLimit,
+        filter: &EdgeFilter,
+    ) -> Vec<SegmentEdge<String>> {
+        self.get_with_label_until(node, limit, filter, &Deadline::none())
+    }
+
+    /// Like [`EdgeStore::get_with_label`], but stops reading the edges and labels
+    /// of the node when the deadline has passed.
+    pub fn get_with_label_until(
+        &self,
+        node: &NodeID,
+        limit: &EdgeLimit,
+        filter: &EdgeFilter,
+        deadline: &Deadline,
+    ) -> Vec<SegmentEdge<String>> {
         let node_bytes = node.as_u64().to_le_bytes();
 
         match (
@@ -511,82 +1235,194 @@ impl EdgeStore {
                 let node_range = EdgeRange::deserialize(node_range_bytes.as_bytes());
                 let edge_range: Range<u64> = Range::deserialize(edge_range_bytes.as_bytes());
 
-                let labels = self
-                    .edge_labels
-                    .slice(usize_range(edge_range))
-                    .map(|r| r.decompress())
-                    .flat_map(|block| block.labels.into_iter());
-
-                let labels = limit.apply(labels);
-
-                let edges = self.edges.slice(usize_range(node_range.range));
-                let edges = limit.apply(edges);
-
-                labels
-                    .zip_eq(edges)
-                    .map(|(label, edge)| {
-                        if self.reversed {
-                            SegmentEdge {
-                                from: edge.other,
-                                to: NodeDatum::new(*node, node_range.sort_key),
-                                rel: edge.rel,
-                                label,
-                            }
-                        } else {
-                            SegmentEdge {
-                                from: NodeDatum::new(*node, node_range.sort_key),
-                                to: edge.other,
-                                rel: edge.rel,
-                                label,
-                            }
-                        }
-                    })
+                let filter = *filter;
+                let cursor = limit.cursor();
+
+                let cache = self.block_cache.as_ref();
+
+                // start reading from the label block that contains the first edge after the cursor
+                let first_block = cursor
+                    .map(|cursor| self.edges.first_edge_after(&node_range, &cursor, cache))
+                    .unwrap_or(0)
+                    / NUM_LABELS_PER_BLOCK;
+                let mut edges = self.edges.edges(&node_range, first_block, cache);
+
+                let include = move |edge: &StoredEdge| {
+                    filter.includes(edge)
+                        && cursor.map_or(true, |cursor| cursor.is_before(&edge.other))
+                };
+
+                // each label block holds the labels of the next `NUM_LABELS_PER_BLOCK` edges
+                // of the node, so a block is only decompressed if some of its edges are included.
+                let label_bytes = usize_range(edge_range);
+                let label_blocks = self.edge_labels.slice(label_bytes.clone());
+                let block_ranges: Vec<_> = label_blocks.item_ranges().skip(first_block).collect();
+
+                let edges = block_ranges.into_iter().flat_map(move |range| {
+                    let block_edges: Vec<_> = edges.by_ref().take(NUM_LABELS_PER_BLOCK).collect();
+
+                    if !block_edges.iter().any(include) {
+                        return Vec::new();
+                    }
+
+                    let decompress = || {
+                        label_blocks
+                            .slice(range.clone())
+                            .next()
+                            .unwrap()
+                            .decompress()
+                            .labels
+                    };
+
+                    let labels = match cache {
+                        Some(cache) => cache
+                            .labels(label_bytes.start + range.start, decompress)
+                            .to_vec(),
+                        None => decompress(),
+                    };
+
+                    labels
+                        .into_iter()
+                        .zip_eq(block_edges)
+                        .filter(|(_, edge)| include(edge))
+                        .collect()
+                });
+
+                deadline
+                    .apply(limit.apply(edges))
+                    .map(|(label, edge)| self.segment_edge(node, node_range.sort_key, edge, label))
                     .collect()
             }
             _ => Vec::new(),
         }
     }
 
-    pub fn get_without_label(&self, node: &NodeID, limit: &EdgeLimit) -> Vec<SegmentEdge<()>> {
+    pub fn get_without_label(
+        &self,
+        node: &NodeID,
+        limit: &EdgeLimit,
+        filter: &EdgeFilter,
+    ) -> Vec<SegmentEdge<()>> {
         let node_bytes = node.as_u64().to_le_bytes();
 
         match self.ranges.edges.get_raw(&node_bytes) {
             Some(node_range_bytes) => {
                 let edge_range = EdgeRange::deserialize(node_range_bytes.as_bytes());
 
-                let edges = self.edges.slice(usize_range(edge_range.range));
-
-                let edges = limit.apply(edges);
-
-                edges
-                    .into_iter()
-                    .map(|edge| {
-                        if self.reversed {
-                            SegmentEdge {
-                                from: edge.other,
-                                to: NodeDatum::new(*node, edge_range.sort_key),
-                                rel: edge.rel,
-                                label: (),
-                            }
-                        } else {
-                            SegmentEdge {
-                                from: NodeDatum::new(*node, edge_range.sort_key),
-                                to: edge.other,
-                                rel: edge.rel,
-                                label: (),
-                            }
-                        }
-                    })
+                let cache = self.block_cache.as_ref();
+                let cursor = limit.cursor();
+                let first_block = cursor
+                    .map(|cursor| self.edges.first_edge_after(&edge_range, &cursor, cache))
+                    .unwrap_or(0)
+                    / NUM_LABELS_PER_BLOCK;
+
+                let edges = self
+                    .edges
+                    .edges(&edge_range, first_block, cache)
+                    .filter(|edge| {
+                        filter.includes(edge)
+                            && cursor.map_or(true, |cursor| cursor.is_before(&edge.other))
+                    });
+
+                limit
+                    .apply(edges)
+                    .map(|edge| self.segment_edge(node, edge_range.sort_key, edge, ()))
                     .collect()
             }
             _ => Vec::new(),
         }
     }
 
+    /// Number of edges stored for the node. This only looks up the range
+    /// of the edges, so it is much cheaper than loading them.
+    pub fn degree(&self, node: &NodeID) -> usize {
+        let node_bytes = node.as_u64().to_le_bytes();
+
+        match self.ranges.edges.get_raw(&node_bytes) {
+            Some(node_range_bytes) => {
+                EdgeRange::deserialize(node_range_bytes.as_bytes()).num_edges as usize
+            }
+            None => 0,
+        }
+    }
+
+    /// Decompress every label block in the store and check that each node has a label
+    /// for every edge. The blocks don't store a checksum, so a corrupt block is detected
+    /// by failing to decompress or decode. Edges stored in blocks are decoded as well.
+    /// Returns the number of label blocks that were checked.
+    pub fn verify_labels(&self) -> Result<u64> {
+        let mut num_blocks = 0;
+
+        for (key, val) in self.ranges.labels.iter_raw() {
+            let node = NodeID::deserialize(key.as_bytes());
+            let label_range: Range<u64> = Range::deserialize(val.as_bytes());
+
+            let mut num_labels = 0;
+            for block in self.edge_labels.slice(usize_range(label_range)) {
+                num_labels += block.try_decompress()?.labels.len();
+                num_blocks += 1;
+            }
+
+            let degree = self.degree(&node);
+
+            if let Some(edge_range) = self.ranges.edges.get_raw(key.as_bytes()) {
+                let edge_range = EdgeRange::deserialize(edge_range.as_bytes());
+                let num_edges = self.edges.verify(&edge_range)?;
+
+                if num_edges != edge_range.num_edges {
+                    bail!(
+                        "node {} has {} edges but {} were decoded",
+                        node.as_u64(),
+                        edge_range.num_edges,
+                        num_edges
+                    );
+                }
+            }
+
+            if num_labels != degree {
+                bail!(
+                    "node {} has {} edges but {} labels",
+                    node.as_u64(),
+                    degree,
+                    num_labels
+                );
+            }
+        }
+
+        Ok(num_blocks)
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.ranges.edges.len()
+    }
+
+    /// The degree of every node in the store. Like [`EdgeStore::degree`], this
+    /// only reads the edge ranges.
+    pub fn degrees(&self) -> impl Iterator<Item = (NodeID, usize)> + '_ {
+        self.ranges.edges.iter_raw().map(|(key, val)| {
+            let node = u64::from_le_bytes((key.as_bytes()).try_into().unwrap());
+            let edge_range = EdgeRange::deserialize(val.as_bytes());
+
+            (NodeID::from(node), edge_range.num_edges as usize)
+        })
+    }
+
     pub fn nodes_by_host(&self, host: &NodeID) -> Vec<NodeID> {
         self.hosts.get(host)
     }
 
+    /// The nodes of every host, see [`HostDb::sorted_iter`].
+    pub fn hosts(&self) -> impl Iterator<Item = (NodeID, Vec<NodeID>)> + '_ {
+        self.hosts.sorted_iter()
+    }
+
+    pub fn iter_with_label(&self) -> impl Iterator<Item = SegmentEdge<String>> + '_ {
+        self.ranges.edges.iter_raw().flat_map(move |(key, _)| {
+            let node = NodeID::deserialize(key.as_bytes());
+            self.get_with_label(&node, &EdgeLimit::Unlimited, &EdgeFilter::all())
+        })
+    }
+
     pub fn iter_without_label(&self) -> impl Iterator<Item = SegmentEdge<()>> + '_ + Send + Sync {
         self.ranges.edges.iter_raw().flat_map(move |(key, val)| {
             let node = u64::from_le_bytes((key.as_bytes()).try_into().unwrap());
@@ -594,10 +1430,8 @@ impl EdgeStore {
 
             let edge_range = EdgeRange::deserialize(val.as_bytes());
 
-            let edges = self
-                .edges
-                .slice(usize_range(edge_range.range))
-                .collect::<Vec<_>>();
+            // a full scan would only evict the blocks of the queries from the cache
+            let edges = self.edges.edges(&edge_range, 0, None).collect::<Vec<_>>();
 
             edges.into_iter().map(move |edge| {
                 if self.reversed {
@@ -606,6 +1440,8 @@ impl EdgeStore {
                         to: NodeDatum::new(node, edge_range.sort_key),
                         rel: edge.rel,
                         label: (),
+                        timestamps: edge.timestamps,
+                        metadata: edge.metadata,
                     }
                 } else {
                     SegmentEdge {
@@ -613,6 +1449,8 @@ impl EdgeStore {
                         to: edge.other,
                         rel: edge.rel,
                         label: (),
+                        timestamps: edge.timestamps,
+                        metadata: edge.metadata,
                     }
                 }
             })
@@ -620,11 +1458,56 @@ impl EdgeStore {
     }
 }
 
+/// The edges of several stores ordered by the key of their node, so all the edges of a
+/// node are next to each other and in the order of their sort key. An edge that is in
+/// more than one store is only returned once with its timestamps and metadata merged.
+///
+/// The stores are read one node at a time, so only the edges of the current node are
+/// held in memory.
+pub struct SortedEdges<'a> {
+    stores: Vec<&'a EdgeStore>,
+    nodes: MergeIter<'a>,
+    buf: Vec<MergeNode<MergeSegmentOrd>>,
+    current: Option<(NodeDatum, EdgeMerger<'a>)>,
+}
+
+impl<'a> Iterator for SortedEdges<'a> {
+    type Item = SegmentEdge<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((node, edges)) = &mut self.current {
+                if let Some(mut edge) = edges.next() {
+                    let label = std::mem::take(&mut edge.label);
+                    return Some(self.stores[0].segment_edge(
+                        &node.node(),
+                        node.sort_key(),
+                        edge.with_label(()),
+                        label,
+                    ));
+                }
+            }
+
+            if !self.nodes.advance(&mut self.buf) {
+                return None;
+            }
+
+            if self.buf.is_empty() {
+                continue;
+            }
+
+            let node = NodeDatum::new(self.buf[0].id(), self.buf[0].range().sort_key);
+            let edges = EdgeStore::merge_postings_for_node(&self.buf, &self.stores);
+            self.current = Some((node, edges));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
-    use crate::webgraph::{store_writer::EdgeStoreWriter, Edge, InsertableEdge};
+    use crate::webgraph::{store_writer::EdgeStoreWriter, BlockCache, Edge, InsertableEdge};
 
     use super::*;
 
@@ -648,24 +1531,379 @@ mod tests {
             },
             label: "test".to_string(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         };
 
         kv.put(e.clone());
 
         let store = kv.finalize();
 
-        let edges: Vec<_> = store.get_with_label(&NodeID::from(0_u64), &EdgeLimit::Unlimited);
+        let edges: Vec<_> = store.get_with_label(
+            &NodeID::from(0_u64),
+            &EdgeLimit::Unlimited,
+            &EdgeFilter::all(),
+        );
 
         assert_eq!(edges.len(), 1);
         assert_eq!(&edges[0], &SegmentEdge::from(e.clone()));
 
-        let edges: Vec<_> = store.get_with_label(&NodeID::from(1_u64), &EdgeLimit::Unlimited);
+        let edges: Vec<_> = store.get_with_label(
+            &NodeID::from(1_u64),
+            &EdgeLimit::Unlimited,
+            &EdgeFilter::all(),
+        );
 
         assert_eq!(edges.len(), 0);
 
         let edges = store.iter_without_label().collect::<Vec<_>>();
 
         assert_eq!(edges.len(), 1);
+
+        assert_eq!(store.degree(&NodeID::from(0_u64)), 1);
+        assert_eq!(store.degree(&NodeID::from(1_u64)), 0);
+    }
+
+    #[test]
+    fn parallel_merge() {
+        let node = |i: u64| FullNodeID {
+            id: NodeID::from(i.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+            host: NodeID::from(0_u64),
+        };
+
+        let stores = || {
+            (0..2)
+                .map(|store| {
+                    let mut writer: EdgeStoreWriter = EdgeStoreWriter::new(
+                        crate::gen_temp_path().join("test-segment"),
+                        Compression::default(),
+                        false,
+                        None,
+                    );
+
+                    // node 0 has more edges than fit in a single label block
+                    for (from, to) in (1..300)
+                        .map(|to| (0, to))
+                        .chain((1..100).map(|from| (from, from + 1)))
+                    {
+                        writer.put(InsertableEdge {
+                            from: node(from),
+                            to: node(to + store * 1000),
+                            label: format!("{from}-{to}-{store}"),
+                            rel: RelFlags::default(),
+                            timestamps: EdgeTimestamps::default(),
+                            metadata: EdgeMetadata::default(),
+                        });
+                    }
+
+                    writer.finalize()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let single_path = crate::gen_temp_path();
+        EdgeStore::merge(
+            stores(),
+            Compression::default(),
+            &single_path,
+            &Tombstones::default(),
+            &Executor::single_thread(),
+        )
+        .unwrap();
+
+        let parallel_path = crate::gen_temp_path();
+        EdgeStore::merge(
+            stores(),
+            Compression::default(),
+            &parallel_path,
+            &Tombstones::default(),
+            &Executor::with_threads(4, "test-merge").unwrap(),
+        )
+        .unwrap();
+
+        let single = EdgeStore::open(&single_path, false);
+        let parallel = EdgeStore::open(&parallel_path, false);
+
+        assert_eq!(parallel.num_nodes(), 100);
+        assert_eq!(
+            parallel.verify_labels().unwrap(),
+            single.verify_labels().unwrap()
+        );
+
+        for i in 0..100 {
+            let edges = |store: &EdgeStore| {
+                store.get_with_label(&node(i).id, &EdgeLimit::Unlimited, &EdgeFilter::all())
+            };
+
+            assert_eq!(edges(&parallel), edges(&single));
+        }
+
+        assert_eq!(
+            parallel
+                .get_with_label(&node(0).id, &EdgeLimit::Unlimited, &EdgeFilter::all())
+                .len(),
+            2 * 299
+        );
+    }
+
+    #[test]
+    fn partitions_split_key_bytes() {
+        assert_eq!(partition_starts(1), vec![[0, 0]]);
+        assert_eq!(
+            partition_starts(4),
+            vec![[0x00, 0], [0x40, 0], [0x80, 0], [0xC0, 0]]
+        );
+
+        let starts = partition_starts(3);
+        assert!(starts.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn host_pages() {
+        let page = |host: u64, i: u64| FullNodeID {
+            id: NodeID::from(host * 100 + i),
+            host: NodeID::from(host),
+        };
+
+        let stores: Vec<_> = [(0..3), (2..5)]
+            .into_iter()
+            .map(|pages| {
+                let mut writer: EdgeStoreWriter = EdgeStoreWriter::new(
+                    crate::gen_temp_path().join("test-segment"),
+                    Compression::default(),
+                    false,
+                    None,
+                );
+
+                for i in pages {
+                    for host in [1, 2] {
+                        writer.put(InsertableEdge {
+                            from: page(host, i),
+                            to: page(3, 0),
+                            label: String::new(),
+                            rel: RelFlags::default(),
+                            timestamps: EdgeTimestamps::default(),
+                            metadata: EdgeMetadata::default(),
+                        });
+                    }
+                }
+
+                writer.finalize()
+            })
+            .collect();
+
+        assert_eq!(
+            stores[1].nodes_by_host(&NodeID::from(1_u64)),
+            (2..5).map(|i| page(1, i).id).collect::<Vec<_>>()
+        );
+
+        let mut tombstones = Tombstones::default();
+        tombstones.delete_node(page(2, 3).id);
+
+        let path = crate::gen_temp_path();
+        EdgeStore::merge(
+            stores,
+            Compression::default(),
+            &path,
+            &tombstones,
+            &Executor::single_thread(),
+        )
+        .unwrap();
+
+        let store = EdgeStore::open(&path, false);
+        assert_eq!(
+            store.nodes_by_host(&NodeID::from(1_u64)),
+            (0..5).map(|i| page(1, i).id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            store.nodes_by_host(&NodeID::from(2_u64)),
+            [0, 1, 2, 4].map(|i| page(2, i).id).to_vec()
+        );
+        assert!(store.nodes_by_host(&NodeID::from(3_u64)).is_empty());
+    }
+
+    #[test]
+    fn host_db_flushes() {
+        let page = |i: u64| FullNodeID {
+            id: NodeID::from(100 + i),
+            host: NodeID::from(1_u64),
+        };
+        let host = NodeID::from(1_u64);
+        let path = crate::gen_temp_path().join("hosts");
+
+        let mut db = HostDb::open(&path);
+        for i in [3, 1] {
+            db.insert(&page(i));
+        }
+        db.flush();
+        assert_eq!(db.get(&host), vec![page(1).id, page(3).id]);
+
+        // only the new pages are added to the posting list
+        for i in [2, 1] {
+            db.insert(&page(i));
+        }
+        db.flush();
+        assert_eq!(
+            db.get(&host),
+            (1..=3).map(|i| page(i).id).collect::<Vec<_>>()
+        );
+        drop(db);
+
+        // stores from before the postings were materialized are read without writing
+        std::fs::remove_dir_all(path.join(HOST_POSTINGS_FOLDER)).unwrap();
+        let db = HostDb::open(&path);
+        assert!(!path.join(HOST_POSTINGS_FOLDER).exists());
+        assert_eq!(
+            db.get(&host),
+            (1..=3).map(|i| page(i).id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            db.sorted_iter().collect::<Vec<_>>(),
+            vec![(host, (1..=3).map(|i| page(i).id).collect())]
+        );
+    }
+
+    #[test]
+    fn blocked_adjacency() {
+        let node = |i: u64| FullNodeID {
+            id: NodeID::from(i.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+            host: NodeID::from(0_u64),
+        };
+
+        let store = |compression: Compression| {
+            let mut writer: EdgeStoreWriter = EdgeStoreWriter::new(
+                crate::gen_temp_path().join("test-segment"),
+                compression,
+                false,
+                None,
+            );
+
+            for to in 1..300 {
+                writer.put(InsertableEdge {
+                    from: node(0),
+                    to: node(to),
+                    label: format!("label {to}"),
+                    rel: RelFlags::default(),
+                    timestamps: EdgeTimestamps::at(to as u32),
+                    metadata: EdgeMetadata::default(),
+                });
+            }
+
+            writer.finalize()
+        };
+
+        let blocked_compression = Compression {
+            adjacency: AdjacencyCodec::DeltaVarint,
+            labels: Codec::Zstd { level: 3 },
+        };
+
+        let fixed = store(Compression::default());
+        let blocked = store(blocked_compression);
+
+        let edges = |store: &EdgeStore, limit: &EdgeLimit| {
+            (
+                store.get_with_label(&node(0).id, limit, &EdgeFilter::all()),
+                store.get_without_label(&node(0).id, limit, &EdgeFilter::all()),
+            )
+        };
+
+        let (all_edges, _) = edges(&fixed, &EdgeLimit::Unlimited);
+        assert_eq!(all_edges.len(), 299);
+        assert_eq!(blocked.degree(&node(0).id), 299);
+        assert_eq!(blocked.verify_labels().unwrap(), 3);
+
+        let after = EdgeLimit::After {
+            cursor: EdgeCursor::from(&all_edges[199].to),
+            limit: 10,
+        };
+
+        for limit in [EdgeLimit::Unlimited, after] {
+            assert_eq!(edges(&blocked, &limit), edges(&fixed, &limit));
+        }
+
+        let cache = Arc::new(BlockCache::new(1 << 20));
+        let mut cached = store(blocked_compression);
+        cached.set_block_cache(StoreBlockCache::new(Arc::clone(&cache), "test", false));
+
+        for _ in 0..2 {
+            for limit in [EdgeLimit::Unlimited, after] {
+                assert_eq!(edges(&cached, &limit), edges(&fixed, &limit));
+            }
+        }
+        assert!(cache.hits() > 0);
+        assert!(cache.size() > 0);
+
+        let merged_path = crate::gen_temp_path();
+        EdgeStore::merge(
+            vec![store(blocked_compression), store(Compression::default())],
+            Compression {
+                adjacency: AdjacencyCodec::Compressed { codec: Codec::Lz4 },
+                labels: Codec::None,
+            },
+            &merged_path,
+            &Tombstones::default(),
+            &Executor::with_threads(4, "test-merge").unwrap(),
+        )
+        .unwrap();
+
+        let merged = EdgeStore::open(&merged_path, false);
+        assert!(merged_path.join(EDGE_BLOCKS_FILE).exists());
+        assert_eq!(
+            edges(&merged, &EdgeLimit::Unlimited),
+            edges(&fixed, &EdgeLimit::Unlimited)
+        );
+    }
+
+    #[test]
+    fn test_duplicate_timestamps() {
+        let mut kv: EdgeStoreWriter = EdgeStoreWriter::new(
+            crate::gen_temp_path().join("test-segment"),
+            Compression::default(),
+            false,
+            None,
+        );
+
+        for timestamp in [5, 1, 3] {
+            kv.put(InsertableEdge {
+                from: FullNodeID {
+                    id: NodeID::from(0_u64),
+                    host: NodeID::from(0_u64),
+                },
+                to: FullNodeID {
+                    id: NodeID::from(1_u64),
+                    host: NodeID::from(0_u64),
+                },
+                label: "test".to_string(),
+                rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::at(timestamp),
+                metadata: EdgeMetadata::default(),
+            });
+        }
+
+        let store = kv.finalize();
+
+        let edges = store.get_without_label(
+            &NodeID::from(0_u64),
+            &EdgeLimit::Unlimited,
+            &EdgeFilter::all(),
+        );
+
+        assert_eq!(edges.len(), 1);
+        assert_eq!(
+            edges[0].timestamps,
+            EdgeTimestamps {
+                first_seen: 1,
+                last_seen: 5
+            }
+        );
+
+        assert!(store
+            .get_without_label(
+                &NodeID::from(0_u64),
+                &EdgeLimit::Unlimited,
+                &EdgeFilter::seen_since(6),
+            )
+            .is_empty());
     }
 
     #[test]
@@ -688,16 +1926,26 @@ mod tests {
             },
             label: "test".to_string(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         };
 
         kv.put(e.clone());
 
         let store = kv.finalize();
 
-        let edges: Vec<_> = store.get_with_label(&NodeID::from(0_u64), &EdgeLimit::Unlimited);
+        let edges: Vec<_> = store.get_with_label(
+            &NodeID::from(0_u64),
+            &EdgeLimit::Unlimited,
+            &EdgeFilter::all(),
+        );
         assert_eq!(edges.len(), 0);
 
-        let edges: Vec<_> = store.get_with_label(&NodeID::from(1_u64), &EdgeLimit::Unlimited);
+        let edges: Vec<_> = store.get_with_label(
+            &NodeID::from(1_u64),
+            &EdgeLimit::Unlimited,
+            &EdgeFilter::all(),
+        );
         assert_eq!(edges.len(), 1);
         assert_eq!(&edges[0], &SegmentEdge::from(e.clone()));
     }
@@ -723,6 +1971,8 @@ mod tests {
                 },
                 label: "test".to_string(),
                 rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
             };
 
             kv.put(e.clone());
@@ -730,10 +1980,131 @@ mod tests {
 
         let store = kv.finalize();
 
-        let edges: Vec<_> = store.get_with_label(&NodeID::from(1_u64), &EdgeLimit::Limit(5));
+        let edges: Vec<_> = store.get_with_label(
+            &NodeID::from(1_u64),
+            &EdgeLimit::Limit(5),
+            &EdgeFilter::all(),
+        );
         assert_eq!(edges.len(), 5);
     }
 
+    #[test]
+    fn test_filter() {
+        let mut kv: EdgeStoreWriter = EdgeStoreWriter::new(
+            crate::gen_temp_path().join("test-segment"),
+            Compression::default(),
+            true,
+            None,
+        );
+
+        // more than one label block is excluded
+        for i in 0..300 {
+            let rel = if i < 200 {
+                RelFlags::NOFOLLOW
+            } else {
+                RelFlags::default()
+            };
+
+            kv.put(InsertableEdge {
+                from: FullNodeID {
+                    id: NodeID::from(i as u64),
+                    host: NodeID::from(0_u64),
+                },
+                to: FullNodeID {
+                    id: NodeID::from(1000_u64),
+                    host: NodeID::from(0_u64),
+                },
+                label: i.to_string(),
+                rel,
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
+            });
+        }
+
+        let store = kv.finalize();
+        let node = NodeID::from(1000_u64);
+
+        let edges = store.get_with_label(&node, &EdgeLimit::Limit(5), &EdgeFilter::followed());
+        assert_eq!(edges.len(), 5);
+        for edge in edges {
+            assert_eq!(edge.rel, RelFlags::default());
+            assert_eq!(edge.label, edge.from.node().as_u64().to_string());
+        }
+
+        let edges = store.get_without_label(&node, &EdgeLimit::Unlimited, &EdgeFilter::followed());
+        assert_eq!(edges.len(), 100);
+
+        let edges = store.get_with_label(
+            &node,
+            &EdgeLimit::Unlimited,
+            &EdgeFilter::require(RelFlags::NOFOLLOW),
+        );
+        assert_eq!(edges.len(), 200);
+        assert!(edges.iter().all(|edge| edge.rel == RelFlags::NOFOLLOW));
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut kv: EdgeStoreWriter = EdgeStoreWriter::new(
+            crate::gen_temp_path().join("test-segment"),
+            Compression::default(),
+            true,
+            None,
+        );
+
+        for i in 0..300 {
+            kv.put(InsertableEdge {
+                from: FullNodeID {
+                    id: NodeID::from(i as u64),
+                    host: NodeID::from(0_u64),
+                },
+                to: FullNodeID {
+                    id: NodeID::from(1000_u64),
+                    host: NodeID::from(0_u64),
+                },
+                label: i.to_string(),
+                rel: RelFlags::default(),
+                timestamps: EdgeTimestamps::default(),
+                metadata: EdgeMetadata::default(),
+            });
+        }
+
+        let store = kv.finalize();
+        let node = NodeID::from(1000_u64);
+
+        let mut labels = Vec::new();
+        let mut limit = EdgeLimit::Limit(70);
+
+        loop {
+            let edges = store.get_with_label(&node, &limit, &EdgeFilter::all());
+
+            let Some(last) = edges.last() else {
+                break;
+            };
+
+            // the edges without labels are paged the same way
+            assert_eq!(
+                store
+                    .get_without_label(&node, &limit, &EdgeFilter::all())
+                    .iter()
+                    .map(|edge| edge.from.node())
+                    .collect::<Vec<_>>(),
+                edges
+                    .iter()
+                    .map(|edge| edge.from.node())
+                    .collect::<Vec<_>>()
+            );
+
+            limit = EdgeLimit::After {
+                cursor: EdgeCursor::from(&last.from),
+                limit: 70,
+            };
+            labels.extend(edges.into_iter().map(|edge| edge.label));
+        }
+
+        assert_eq!(labels, (0..300).map(|i| i.to_string()).collect::<Vec<_>>());
+    }
+
     #[test]
     fn test_edge_ordering() {
         let mut rank_store =
@@ -763,6 +2134,8 @@ mod tests {
             },
             label: "1".to_string(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         };
 
         let e2 = InsertableEdge {
@@ -776,6 +2149,8 @@ mod tests {
             },
             label: "2".to_string(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         };
 
         let e3 = InsertableEdge {
@@ -789,6 +2164,8 @@ mod tests {
             },
             label: "3".to_string(),
             rel: RelFlags::default(),
+            timestamps: EdgeTimestamps::default(),
+            metadata: EdgeMetadata::default(),
         };
 
         kv.put(e1.clone());
@@ -797,7 +2174,11 @@ mod tests {
 
         let store = kv.finalize();
 
-        let edges: Vec<_> = store.get_with_label(&NodeID::from(0_u64), &EdgeLimit::Unlimited);
+        let edges: Vec<_> = store.get_with_label(
+            &NodeID::from(0_u64),
+            &EdgeLimit::Unlimited,
+            &EdgeFilter::all(),
+        );
 
         assert_eq!(edges.len(), 3);
 
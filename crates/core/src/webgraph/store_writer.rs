@@ -31,16 +31,13 @@ use crate::{
     Result,
 };
 use file_store::{
-    iterable::{
-        ConstIterableStoreWriter, IterableStoreReader, IterableStoreWriter,
-        SortedIterableStoreReader,
-    },
+    iterable::{IterableStoreReader, IterableStoreWriter, SortedIterableStoreReader},
     ConstSerializable,
 };
 
 use super::{
     merge::NodeDatum,
-    store::{CompressedLabelBlock, EdgeStore, HostDb, LabelBlock, RangesDb},
+    store::{AdjacencyWriter, CompressedLabelBlock, EdgeStore, HostDb, LabelBlock, RangesDb},
     Compression, EdgeLabel, InsertableEdge, NodeID, StoredEdge,
 };
 
@@ -166,11 +163,18 @@ impl EdgeStoreWriter {
             (edge.from.id, edge.to.id)
         };
 
-        self.edges.insert(SortableEdge {
+        let mut edge = SortableEdge {
             sort_node,
             secondary_node,
             edge,
-        });
+        };
+
+        if let Some(existing) = self.edges.take(&edge) {
+            edge.edge.timestamps = edge.edge.timestamps.merge(existing.edge.timestamps);
+            edge.edge.metadata = edge.edge.metadata.merge(existing.edge.metadata);
+        }
+
+        self.edges.insert(edge);
 
         if self.edges.len() >= MAX_BATCH_SIZE {
             self.flush_to_file().unwrap();
@@ -203,8 +207,18 @@ impl EdgeStoreWriter {
             &self.path,
         );
 
-        let mut store = final_writer.build_store(self.sorted_edges().dedup().map(|e| e.edge));
-        store.optimize_read();
+        let edges = self.sorted_edges().coalesce(|mut a, b| {
+            if a == b {
+                a.edge.timestamps = a.edge.timestamps.merge(b.edge.timestamps);
+                a.edge.metadata = a.edge.metadata.merge(b.edge.metadata);
+                Ok(a)
+            } else {
+                Err((a, b))
+            }
+        });
+
+        let mut store = final_writer.build_store(edges.map(|e| e.edge));
+        store.merge_index_segments();
 
         store
     }
@@ -218,12 +232,27 @@ impl Drop for EdgeStoreWriter {
     }
 }
 
+/// Remove duplicate edges from a sorted batch and merge their timestamps and metadata.
+fn dedup_batch(batch: &mut Vec<InsertableEdge<String>>, reversed: bool) {
+    let other = |e: &InsertableEdge<String>| if reversed { e.from.id } else { e.to.id };
+
+    batch.dedup_by(|removed, kept| {
+        if other(removed) == other(kept) {
+            kept.timestamps = kept.timestamps.merge(removed.timestamps);
+            kept.metadata = kept.metadata.merge(removed.metadata);
+            true
+        } else {
+            false
+        }
+    });
+}
+
 struct FinalEdgeStoreWriter {
     ranges: RangesDb,
     hosts: HostDb,
 
     edge_labels: IterableStoreWriter<CompressedLabelBlock, File>,
-    edge_nodes: ConstIterableStoreWriter<StoredEdge, File>,
+    edge_nodes: AdjacencyWriter,
 
     host_centrality_rank_store: Option<Arc<speedy_kv::Db<NodeID, u64>>>,
 
@@ -251,14 +280,7 @@ impl FinalEdgeStoreWriter {
             .unwrap();
         let edge_labels = IterableStoreWriter::new(edge_labels_file);
 
-        let edge_nodes_file = File::options()
-            .read(true)
-            .create(true)
-            .truncate(false)
-            .write(true)
-            .open(path.as_ref().join("edges"))
-            .unwrap();
-        let edge_nodes = ConstIterableStoreWriter::new(edge_nodes_file);
+        let edge_nodes = AdjacencyWriter::open(&path, compression.adjacency);
 
         Self {
             ranges,
@@ -323,20 +345,20 @@ impl FinalEdgeStoreWriter {
 
             let datum = NodeDatum::new(node, sort_key);
             let rel = edge.rel;
-            edge_nodes.push(StoredEdge::new(datum, rel));
+            edge_nodes
+                .push(StoredEdge::new(datum, rel, edge.timestamps).with_metadata(edge.metadata));
         }
 
         let edge_labels: Vec<_> = edge_labels
             .into_iter()
             .chunks(NUM_LABELS_PER_BLOCK)
             .into_iter()
-            .map(|chunk| LabelBlock::new(chunk.collect()).compress(self.compression))
+            .map(|chunk| LabelBlock::new(chunk.collect()).compress(self.compression.labels))
             .collect();
 
         let mut first_label_offset = None;
         let mut last_label_offset = None;
-        let mut first_node_offset = None;
-        let mut last_node_offset = None;
+        let mut node_range: Option<Range<u64>> = None;
 
         for block in &edge_labels {
             let offset = self.edge_labels.write(block).unwrap();
@@ -348,14 +370,13 @@ impl FinalEdgeStoreWriter {
             last_label_offset = Some(offset);
         }
 
-        for node in &edge_nodes {
-            let offset = self.edge_nodes.write(node).unwrap();
-
-            if first_node_offset.is_none() {
-                first_node_offset = Some(offset);
-            }
+        for block in edge_nodes.chunks(NUM_LABELS_PER_BLOCK) {
+            let block_range = self.edge_nodes.write_block(block).unwrap();
 
-            last_node_offset = Some(offset);
+            node_range = Some(match node_range {
+                Some(range) => range.start..block_range.end,
+                None => block_range,
+            });
         }
 
         let label_range = Range {
@@ -369,13 +390,8 @@ impl FinalEdgeStoreWriter {
             .map(|store| store.get(&node.host).unwrap().unwrap_or(u64::MAX))
             .unwrap_or(0);
 
-        let node_range: EdgeRange = EdgeRange::new(
-            Range {
-                start: first_node_offset.unwrap().start,
-                end: last_node_offset.unwrap().start + last_node_offset.unwrap().num_bytes,
-            },
-            sort_key,
-        );
+        let node_range: EdgeRange =
+            EdgeRange::new(node_range.unwrap(), edge_nodes.len() as u64, sort_key);
         let node_range_bytes = node_range.serialize_to_vec();
 
         self.ranges
@@ -407,7 +423,7 @@ impl FinalEdgeStoreWriter {
                     batch.sort_unstable_by_key(
                         |e: &InsertableEdge<_>| if self.reversed { e.from.id } else { e.to.id },
                     );
-                    batch.dedup_by_key(|e| if self.reversed { e.from.id } else { e.to.id });
+                    dedup_batch(&mut batch, self.reversed);
                     let batch_len = batch.len();
                     self.put_store(&mut batch);
                     batch.clear();
@@ -432,7 +448,7 @@ impl FinalEdgeStoreWriter {
             batch.sort_unstable_by_key(
                 |e: &InsertableEdge<_>| if self.reversed { e.from.id } else { e.to.id },
             );
-            batch.dedup_by_key(|e| if self.reversed { e.from.id } else { e.to.id });
+            dedup_batch(&mut batch, self.reversed);
             self.put_store(&mut batch);
         }
 
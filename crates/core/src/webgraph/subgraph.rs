@@ -0,0 +1,306 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The k-hop neighborhood of a node as a small in-memory graph, e.g. to look at the
+//! ego network of a host when investigating link spam.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fmt::Write as _,
+    fs::File,
+    path::Path,
+};
+
+use super::{
+    Compression, DegreeDirection, Edge, EdgeLimit, Node, NodeID, Webgraph, WebgraphWriter,
+};
+use crate::{executor::Executor, Result};
+
+/// The neighborhood stops growing when it has this many nodes.
+pub const DEFAULT_MAX_NEIGHBORHOOD_NODES: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct SubgraphNode {
+    pub node: Node,
+    /// Number of hops from the center of the subgraph.
+    pub distance: u8,
+}
+
+/// The subgraph induced by a set of nodes, i.e. the nodes and all edges between them.
+#[derive(Debug, Clone)]
+pub struct Subgraph {
+    center: NodeID,
+    nodes: BTreeMap<NodeID, SubgraphNode>,
+    edges: Vec<Edge<String>>,
+}
+
+impl Subgraph {
+    pub fn center(&self) -> NodeID {
+        self.center
+    }
+
+    pub fn get(&self, id: &NodeID) -> Option<&SubgraphNode> {
+        self.nodes.get(id)
+    }
+
+    pub fn contains(&self, id: &NodeID) -> bool {
+        self.nodes.contains_key(id)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = (&NodeID, &SubgraphNode)> {
+        self.nodes.iter()
+    }
+
+    pub fn edges(&self) -> &[Edge<String>] {
+        &self.edges
+    }
+
+    /// Outgoing edges of the node within the subgraph.
+    pub fn outgoing(&self, id: &NodeID) -> impl Iterator<Item = &Edge<String>> {
+        let id = *id;
+        self.edges.iter().filter(move |edge| edge.from == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Write the subgraph as a new webgraph at `path`.
+    pub fn into_webgraph<P: AsRef<Path>>(self, path: P) -> Webgraph {
+        let mut writer = WebgraphWriter::new(
+            path,
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        );
+
+        for edge in self.edges {
+            let (Some(from), Some(to)) = (self.nodes.get(&edge.from), self.nodes.get(&edge.to))
+            else {
+                continue;
+            };
+
+            writer.insert_with_metadata(
+                from.node.clone(),
+                to.node.clone(),
+                edge.label,
+                edge.rel,
+                edge.metadata,
+            );
+        }
+
+        writer.finalize()
+    }
+
+    /// The subgraph in the DOT language of graphviz. The center is drawn as a box.
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph neighborhood {\n");
+
+        for (id, node) in &self.nodes {
+            let shape = if *id == self.center { "box" } else { "ellipse" };
+            writeln!(
+                dot,
+                "  \"{}\" [shape={shape}, distance={}];",
+                escape(node.node.as_str()),
+                node.distance
+            )
+            .unwrap();
+        }
+
+        for edge in &self.edges {
+            let (Some(from), Some(to)) = (self.nodes.get(&edge.from), self.nodes.get(&edge.to))
+            else {
+                continue;
+            };
+
+            writeln!(
+                dot,
+                "  \"{}\" -> \"{}\" [label=\"{}\"];",
+                escape(from.node.as_str()),
+                escape(to.node.as_str()),
+                escape(&edge.label)
+            )
+            .unwrap();
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Write the edges of the subgraph as csv with the columns from, to and label.
+    pub fn export_csv<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut wtr = csv::Writer::from_writer(File::create(path)?);
+        wtr.write_record(["from", "to", "label"])?;
+
+        for edge in &self.edges {
+            let (Some(from), Some(to)) = (self.nodes.get(&edge.from), self.nodes.get(&edge.to))
+            else {
+                continue;
+            };
+
+            wtr.write_record([from.node.as_str(), to.node.as_str(), edge.label.as_str()])?;
+        }
+
+        wtr.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Webgraph {
+    /// The subgraph induced by the nodes that are at most `hops` hops away from `node`,
+    /// following the edges in `direction`. At most [`DEFAULT_MAX_NEIGHBORHOOD_NODES`]
+    /// nodes are included.
+    pub fn neighborhood(&self, node: &Node, hops: u8, direction: DegreeDirection) -> Subgraph {
+        self.neighborhood_with_limit(node, hops, direction, DEFAULT_MAX_NEIGHBORHOOD_NODES)
+    }
+
+    /// Same as [`Webgraph::neighborhood`], but with at most `max_nodes` nodes. The nodes
+    /// closest to the center are included first.
+    pub fn neighborhood_with_limit(
+        &self,
+        node: &Node,
+        hops: u8,
+        direction: DegreeDirection,
+        max_nodes: usize,
+    ) -> Subgraph {
+        let center = node.id();
+        let mut distances: BTreeMap<NodeID, u8> = BTreeMap::new();
+        let mut frontier = Vec::new();
+
+        if self.id2node(&center).is_some() && max_nodes > 0 {
+            distances.insert(center, 0);
+            frontier.push(center);
+        }
+
+        'bfs: for distance in 1..=hops {
+            let mut next = Vec::new();
+
+            for current in frontier {
+                let neighbours: Vec<_> = match direction {
+                    DegreeDirection::Out => self
+                        .raw_outgoing_edges(&current, EdgeLimit::Unlimited)
+                        .into_iter()
+                        .map(|edge| edge.to)
+                        .collect(),
+                    DegreeDirection::In => self
+                        .raw_ingoing_edges(&current, EdgeLimit::Unlimited)
+                        .into_iter()
+                        .map(|edge| edge.from)
+                        .collect(),
+                };
+
+                for neighbour in neighbours {
+                    if distances.len() >= max_nodes {
+                        break 'bfs;
+                    }
+
+                    if let Entry::Vacant(entry) = distances.entry(neighbour) {
+                        entry.insert(distance);
+                        next.push(neighbour);
+                    }
+                }
+            }
+
+            frontier = next;
+        }
+
+        let mut nodes = BTreeMap::new();
+        for (id, distance) in distances {
+            if let Some(node) = self.id2node(&id) {
+                nodes.insert(id, SubgraphNode { node, distance });
+            }
+        }
+
+        let edges = nodes
+            .keys()
+            .flat_map(|id| self.raw_outgoing_edges_with_labels(id, EdgeLimit::Unlimited))
+            .filter(|edge| nodes.contains_key(&edge.to))
+            .collect();
+
+        Subgraph {
+            center,
+            nodes,
+            edges,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::tests::test_graph;
+
+    #[test]
+    fn induced_subgraph() {
+        // A -> B, B -> C, A -> C, C -> A, D -> C
+        let graph = test_graph();
+        let d = Node::from("D");
+
+        let subgraph = graph.neighborhood(&d, 1, DegreeDirection::Out);
+        assert_eq!(subgraph.len(), 2);
+        assert_eq!(subgraph.edges().len(), 1);
+
+        // A is 2 hops from D, and the edges between A and C are included
+        let subgraph = graph.neighborhood(&d, 2, DegreeDirection::Out);
+        assert_eq!(subgraph.len(), 3);
+        assert_eq!(subgraph.get(&Node::from("A").id()).unwrap().distance, 2);
+        assert_eq!(subgraph.edges().len(), 3);
+        assert!(!subgraph.contains(&Node::from("B").id()));
+
+        // nothing links to D
+        let subgraph = graph.neighborhood(&d, 2, DegreeDirection::In);
+        assert_eq!(subgraph.len(), 1);
+        assert!(subgraph.edges().is_empty());
+
+        let subgraph = graph.neighborhood(&Node::from("C"), 1, DegreeDirection::In);
+        assert_eq!(subgraph.len(), 4);
+        assert_eq!(subgraph.edges().len(), 5);
+
+        let limited = graph.neighborhood_with_limit(&Node::from("C"), 1, DegreeDirection::In, 2);
+        assert_eq!(limited.len(), 2);
+        assert!(limited.contains(&Node::from("C").id()));
+
+        assert!(graph
+            .neighborhood(&Node::from("unknown"), 2, DegreeDirection::Out)
+            .is_empty());
+    }
+
+    #[test]
+    fn export() {
+        let graph = test_graph();
+        let subgraph = graph.neighborhood(&Node::from("D"), 2, DegreeDirection::Out);
+
+        let dot = subgraph.to_dot();
+        assert!(dot.starts_with("digraph"));
+        assert!(dot.contains("\"D\" [shape=box, distance=0];"));
+        assert_eq!(dot.matches("->").count(), 3);
+
+        let exported = subgraph.into_webgraph(crate::gen_temp_path());
+        assert_eq!(exported.nodes().count(), 3);
+        assert_eq!(
+            exported
+                .raw_outgoing_edges(&Node::from("C").id(), EdgeLimit::Unlimited)
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,114 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Segments are immutable, so deleted nodes and edges are recorded as tombstones
+//! in the metadata of the graph. The tombstones apply to all segments of the graph
+//! and are removed once the deleted nodes and edges have been dropped from the
+//! segments in [`super::Webgraph::merge_all_segments`].
+
+use std::collections::HashSet;
+
+use super::NodeID;
+
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct Tombstones {
+    /// Deleted nodes. All edges to and from the nodes are deleted as well.
+    nodes: HashSet<NodeID>,
+    /// Deleted edges as `(from, to)`.
+    edges: HashSet<(NodeID, NodeID)>,
+}
+
+impl Tombstones {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.edges.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len() + self.edges.len()
+    }
+
+    pub fn delete_node(&mut self, node: NodeID) {
+        self.nodes.insert(node);
+    }
+
+    pub fn delete_edge(&mut self, from: NodeID, to: NodeID) {
+        self.edges.insert((from, to));
+    }
+
+    pub fn is_node_deleted(&self, node: &NodeID) -> bool {
+        self.nodes.contains(node)
+    }
+
+    pub fn is_edge_deleted(&self, from: &NodeID, to: &NodeID) -> bool {
+        self.nodes.contains(from) || self.nodes.contains(to) || self.edges.contains(&(*from, *to))
+    }
+
+    pub fn deleted_nodes(&self) -> impl Iterator<Item = &NodeID> {
+        self.nodes.iter()
+    }
+
+    pub fn deleted_edges(&self) -> impl Iterator<Item = &(NodeID, NodeID)> {
+        self.edges.iter()
+    }
+
+    pub fn merge(&mut self, other: Tombstones) {
+        self.nodes.extend(other.nodes);
+        self.edges.extend(other.edges);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deleted_edges() {
+        let (a, b, c) = (
+            NodeID::from(1_u64),
+            NodeID::from(2_u64),
+            NodeID::from(3_u64),
+        );
+
+        let mut tombstones = Tombstones::default();
+        assert!(tombstones.is_empty());
+
+        tombstones.delete_node(a);
+        tombstones.delete_edge(b, c);
+
+        assert_eq!(tombstones.len(), 2);
+        assert!(tombstones.is_edge_deleted(&a, &b));
+        assert!(tombstones.is_edge_deleted(&c, &a));
+        assert!(tombstones.is_edge_deleted(&b, &c));
+        assert!(!tombstones.is_edge_deleted(&c, &b));
+        assert!(!tombstones.is_node_deleted(&b));
+
+        let json = serde_json::to_string(&tombstones).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Tombstones>(&json).unwrap(),
+            tombstones
+        );
+    }
+}
@@ -0,0 +1,246 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Integrity checks for a webgraph on disk.
+//!
+//! Opening a [`Webgraph`](super::Webgraph) completes pending merges and removes
+//! unreferenced segments, so the checks work on the files directly and never
+//! modify the graph.
+
+use std::{
+    fmt, fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use super::{
+    id_node_db::Id2NodeDb,
+    manifest::PendingMerge,
+    segment::{self, Segment},
+    DegreeDirection, Meta, NodeID, SegmentID,
+};
+use crate::Result;
+
+/// At most this many unresolved nodes are listed in the report. All of them are counted.
+const MAX_LISTED_UNRESOLVED: usize = 100;
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub num_segments: usize,
+    pub num_nodes_checked: u64,
+    pub num_label_blocks: u64,
+    /// Nodes with edges that have no entry in the id to node mapping.
+    pub unresolved_nodes: Vec<NodeID>,
+    pub num_unresolved_nodes: u64,
+    /// Segments in the metadata without a folder.
+    pub missing_segments: Vec<SegmentID>,
+    /// Segments that could not be read, with the reason.
+    pub corrupt_segments: Vec<(SegmentID, String)>,
+    /// Segment folders that the metadata doesn't refer to.
+    pub orphaned_segments: Vec<String>,
+    /// Whether a merge was interrupted. It is completed when the graph is opened.
+    pub pending_merge: bool,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.num_unresolved_nodes == 0
+            && self.missing_segments.is_empty()
+            && self.corrupt_segments.is_empty()
+            && self.orphaned_segments.is_empty()
+            && !self.pending_merge
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "checked {} segments, {} nodes and {} label blocks",
+            self.num_segments, self.num_nodes_checked, self.num_label_blocks
+        )?;
+
+        if self.pending_merge {
+            writeln!(f, "a merge was interrupted")?;
+        }
+
+        for id in &self.missing_segments {
+            writeln!(f, "segment {id} is in the metadata but has no folder")?;
+        }
+
+        for (id, reason) in &self.corrupt_segments {
+            writeln!(f, "segment {id} is corrupt: {reason}")?;
+        }
+
+        for name in &self.orphaned_segments {
+            writeln!(f, "segment folder {name} is not in the metadata")?;
+        }
+
+        if self.num_unresolved_nodes > 0 {
+            writeln!(
+                f,
+                "{} nodes have edges but are not in id2node, e.g. {:?}",
+                self.num_unresolved_nodes,
+                self.unresolved_nodes
+                    .iter()
+                    .map(|id| id.as_u64())
+                    .collect::<Vec<_>>()
+            )?;
+        }
+
+        if self.is_ok() {
+            writeln!(f, "no problems found")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check the graph at `path` without modifying it.
+pub fn verify<P: AsRef<Path>>(path: P) -> Result<VerifyReport> {
+    let path = path.as_ref();
+    let meta_path = path.join("metadata.json");
+
+    if !meta_path.exists() || !path.join("id2node").exists() {
+        anyhow::bail!("{} is not a webgraph", path.display());
+    }
+
+    let meta = Meta::open(meta_path);
+    let id2node = Id2NodeDb::open(path.join("id2node"));
+    let segments_path = path.join("segments");
+
+    let mut report = VerifyReport {
+        pending_merge: PendingMerge::open(path)?.is_some(),
+        ..Default::default()
+    };
+
+    if segments_path.exists() {
+        for entry in fs::read_dir(&segments_path)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+
+            if !meta.comitted_segments.contains(&name) {
+                report.orphaned_segments.push(name);
+            }
+        }
+    }
+
+    for id in &meta.comitted_segments {
+        let segment_path = segments_path.join(id);
+
+        if !segment_path.exists() {
+            report.missing_segments.push(id.clone());
+            continue;
+        }
+
+        if !segment::has_current_version(&segment_path) {
+            report
+                .corrupt_segments
+                .push((id.clone(), "unsupported format version".to_string()));
+            continue;
+        }
+
+        // a corrupt store can make the readers panic
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            verify_segment(&segments_path, id, &id2node, &mut report)
+        }));
+
+        match res {
+            Ok(Ok(())) => {}
+            Ok(Err(err)) => report.corrupt_segments.push((id.clone(), err.to_string())),
+            Err(_) => report
+                .corrupt_segments
+                .push((id.clone(), "failed to read segment".to_string())),
+        }
+
+        report.num_segments += 1;
+    }
+
+    Ok(report)
+}
+
+fn verify_segment(
+    segments_path: &Path,
+    id: &SegmentID,
+    id2node: &Id2NodeDb,
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let segment = Segment::open(segments_path, id.clone());
+
+    report.num_label_blocks += segment.verify_labels()?;
+
+    // every edge has its source in the outgoing and its destination in the ingoing
+    // direction, so checking the nodes of both covers all nodes referenced by edges.
+    for direction in [DegreeDirection::Out, DegreeDirection::In] {
+        for (node, _) in segment.degrees(direction) {
+            report.num_nodes_checked += 1;
+
+            if id2node.get(&node).is_none() {
+                report.num_unresolved_nodes += 1;
+
+                if report.unresolved_nodes.len() < MAX_LISTED_UNRESOLVED {
+                    report.unresolved_nodes.push(node);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webgraph::tests::test_graph;
+
+    #[test]
+    fn healthy_graph() {
+        let graph = test_graph();
+        let report = verify(&graph.path).unwrap();
+
+        assert!(report.is_ok(), "{report}");
+        assert_eq!(report.num_segments, 1);
+        assert!(report.num_nodes_checked > 0);
+        assert!(report.num_label_blocks > 0);
+    }
+
+    #[test]
+    fn problems_are_reported() {
+        let graph = test_graph();
+        let path = Path::new(&graph.path).to_path_buf();
+        let segment = graph.meta.comitted_segments[0].clone();
+        drop(graph);
+
+        fs::create_dir_all(path.join("segments").join("orphan")).unwrap();
+        fs::write(
+            path.join("segments")
+                .join(&segment)
+                .join("adjacency")
+                .join("labels"),
+            b"",
+        )
+        .unwrap();
+
+        let report = verify(&path).unwrap();
+
+        assert!(!report.is_ok());
+        assert_eq!(report.orphaned_segments, vec!["orphan".to_string()]);
+        assert_eq!(report.corrupt_segments.len(), 1);
+        assert_eq!(report.corrupt_segments[0].0, segment);
+        assert!(path.join("segments").join("orphan").exists());
+
+        assert!(verify(crate::gen_temp_path()).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Write-ahead log of a [`super::WebgraphWriter`].
+//!
+//! The edges of a writer are only stored in the graph when it is finalized. With a
+//! log, every insert and deletion is also appended to a file in the graph folder,
+//! which is replayed when a writer is opened on the folder again after a crash.
+//!
+//! The records are buffered and written to the file in larger chunks. The buffer is
+//! flushed and the file synced to disk on commit, so the records logged since the last
+//! commit can be lost in a crash. Every record has a length and a checksum, and a record
+//! that was cut short by a crash ends the log.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{EdgeMetadata, Node, NodeID};
+use crate::{webpage::html::links::RelFlags, Result};
+
+const WAL_FILE: &str = "wal.log";
+
+/// Length of the payload and its checksum.
+const HEADER_BYTES: usize = 4 + 8;
+
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
+pub(super) enum WalEntry {
+    Edge {
+        from: Node,
+        to: Node,
+        label: String,
+        rel: RelFlags,
+        metadata: EdgeMetadata,
+        timestamp: u32,
+    },
+    Alias {
+        alias: Node,
+        canonical: Node,
+    },
+    DeleteNode(NodeID),
+    DeleteEdge(NodeID, NodeID),
+    Checkpoint(String),
+}
+
+pub(super) struct WriteAheadLog {
+    file: BufWriter<File>,
+    buf: Vec<u8>,
+}
+
+impl WriteAheadLog {
+    pub fn path<P: AsRef<Path>>(graph_path: P) -> PathBuf {
+        graph_path.as_ref().join(WAL_FILE)
+    }
+
+    /// Open the log and replay the entries that are already in it with `apply`. The
+    /// entries are read one at a time, so the log is never loaded into memory. A
+    /// partially written record at the end of the file is removed.
+    pub fn open<P: AsRef<Path>>(path: P, mut apply: impl FnMut(WalEntry)) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let file_len = file.metadata()?.len() as usize;
+        let mut reader = BufReader::new(&file);
+        let mut buf = Vec::new();
+        let mut offset = 0;
+
+        while let Some((entry, len)) = Self::read_record(&mut reader, file_len - offset, &mut buf) {
+            apply(entry);
+            offset += len;
+        }
+
+        if offset < file_len {
+            tracing::warn!(
+                "discarding {} bytes of partially written records in write-ahead log",
+                file_len - offset
+            );
+            file.set_len(offset as u64)?;
+        }
+
+        file.seek(SeekFrom::Start(offset as u64))?;
+
+        Ok(Self {
+            file: BufWriter::new(file),
+            buf,
+        })
+    }
+
+    /// Read the next record and return it with its length. Returns `None` at the end
+    /// of the log or if the record is incomplete or corrupt.
+    fn read_record(
+        reader: &mut impl Read,
+        remaining: usize,
+        buf: &mut Vec<u8>,
+    ) -> Option<(WalEntry, usize)> {
+        let mut header = [0; HEADER_BYTES];
+        reader.read_exact(&mut header).ok()?;
+
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let checksum = u64::from_le_bytes(header[4..].try_into().unwrap());
+
+        // a corrupt length must not allocate more than is left in the file
+        if HEADER_BYTES + len > remaining {
+            return None;
+        }
+
+        buf.resize(len, 0);
+        reader.read_exact(buf).ok()?;
+
+        if xxhash_rust::xxh3::xxh3_64(buf) != checksum {
+            return None;
+        }
+
+        let (entry, _) = bincode::decode_from_slice(buf, bincode::config::standard()).ok()?;
+
+        Some((entry, HEADER_BYTES + len))
+    }
+
+    pub fn append(&mut self, entry: &WalEntry) -> Result<()> {
+        let payload = bincode::encode_to_vec(entry, bincode::config::standard())?;
+
+        self.buf.clear();
+        self.buf
+            .extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        self.buf
+            .extend_from_slice(&xxhash_rust::xxh3::xxh3_64(&payload).to_le_bytes());
+        self.buf.extend_from_slice(&payload);
+
+        self.file.write_all(&self.buf)?;
+
+        Ok(())
+    }
+
+    /// Write the buffered records to the file and sync it to disk.
+    pub fn commit(&mut self) -> Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_data()?;
+        Ok(())
+    }
+
+    pub fn remove<P: AsRef<Path>>(path: P) -> Result<()> {
+        if path.as_ref().exists() {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        executor::Executor,
+        webgraph::{Compression, EdgeLimit, Webgraph, WebgraphWriter},
+    };
+
+    fn writer<P: AsRef<Path>>(path: P) -> WebgraphWriter {
+        WebgraphWriter::new(
+            path,
+            Executor::single_thread(),
+            Compression::default(),
+            None,
+        )
+        .with_wal()
+    }
+
+    #[test]
+    fn inserts_are_replayed_after_crash() {
+        let path = crate::gen_temp_path();
+        let (a, b, c) = (Node::from("A"), Node::from("B"), Node::from("C"));
+
+        let mut crashed = writer(&path);
+        crashed.insert(a.clone(), b.clone(), "ab".to_string(), RelFlags::default());
+        crashed.checkpoint("first".to_string());
+        crashed.insert(b.clone(), c.clone(), "bc".to_string(), RelFlags::default());
+        crashed.insert(a.clone(), c.clone(), "ac".to_string(), RelFlags::default());
+        crashed.delete_edge(&a, &c);
+        drop(crashed);
+
+        // a record that was cut short by the crash
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(WriteAheadLog::path(&path))
+            .unwrap();
+        file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let writer = writer(&path);
+        assert!(writer.has_checkpoint("first"));
+        assert!(!writer.has_checkpoint("second"));
+
+        let graph = writer.finalize();
+        assert!(!WriteAheadLog::path(&path).exists());
+
+        let outgoing = |graph: &Webgraph, node: &Node| {
+            graph
+                .raw_outgoing_edges_with_labels(&node.id(), EdgeLimit::Unlimited)
+                .into_iter()
+                .map(|edge| edge.label)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(outgoing(&graph, &a), vec!["ab".to_string()]);
+        assert_eq!(outgoing(&graph, &b), vec!["bc".to_string()]);
+
+        // only the segment of the replayed writer is left
+        let segments = fs::read_dir(Path::new(&graph.path).join("segments"))
+            .unwrap()
+            .count();
+        assert_eq!(segments, 1);
+    }
+}
@@ -14,21 +14,43 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses
 
-use std::{fs, path::Path, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Arc,
+};
 
-use crate::{executor::Executor, webpage::html::links::RelFlags};
+use crate::{config::WebgraphInsertPolicy, executor::Executor, webpage::html::links::RelFlags};
 
 use super::{
-    id_node_db::Id2NodeDb, segment::SegmentWriter, Compression, FullNodeID, InsertableEdge, Meta,
-    Node, NodeID, Webgraph, MAX_LABEL_LENGTH,
+    alias::NodeAliases,
+    id_node_db::Id2NodeDb,
+    manifest,
+    segment::SegmentWriter,
+    unix_timestamp,
+    wal::{WalEntry, WriteAheadLog},
+    Compression, DefaultNormalization, EdgeMetadata, EdgeTimestamps, FullNodeID, InsertableEdge,
+    Meta, Node, NodeID, NormalizationPolicy, Tombstones, Webgraph, MAX_LABEL_LENGTH,
 };
 
 pub struct WebgraphWriter {
     pub path: String,
     segment: SegmentWriter,
     id2node: Id2NodeDb,
+    aliases: NodeAliases,
     executor: Executor,
     meta: Meta,
+    compression: Compression,
+    timestamp: u32,
+    policy: WebgraphInsertPolicy,
+    normalization: Arc<dyn NormalizationPolicy>,
+    num_outlinks: HashMap<NodeID, usize>,
+    /// Boilerplate edges keyed by the host of the source node and the destination.
+    /// They are inserted when the writer is finalized.
+    collapsed: HashMap<(NodeID, NodeID), InsertableEdge<String>>,
+    wal: Option<WriteAheadLog>,
+    checkpoints: HashSet<String>,
 }
 
 impl WebgraphWriter {
@@ -51,26 +73,136 @@ impl WebgraphWriter {
         fs::create_dir_all(&path).unwrap();
         let mut meta = Self::meta(&path);
         meta.comitted_segments.clear();
+        meta.tombstones = Tombstones::default();
 
         fs::create_dir_all(path.as_ref().join("segments")).unwrap();
 
-        let id = uuid::Uuid::new_v4().to_string();
         let segment = SegmentWriter::open(
             path.as_ref().join("segments"),
-            id.clone(),
+            uuid::Uuid::new_v4().to_string(),
             compression,
             host_centrality_rank_store,
         );
 
-        meta.comitted_segments.push(id);
-
         Self {
             path: path.as_ref().as_os_str().to_str().unwrap().to_string(),
             segment,
             id2node: Id2NodeDb::open(path.as_ref().join("id2node")),
+            aliases: NodeAliases::open(NodeAliases::path(&path)),
             executor,
             meta,
+            compression,
+            timestamp: unix_timestamp(),
+            policy: WebgraphInsertPolicy::default(),
+            normalization: Arc::new(DefaultNormalization),
+            num_outlinks: HashMap::new(),
+            collapsed: HashMap::new(),
+            wal: None,
+            checkpoints: HashSet::new(),
+        }
+    }
+
+    /// Log every change to a write-ahead log in the graph folder until the writer is
+    /// finalized. If a log is left from a writer that crashed before it was finalized,
+    /// its changes are replayed first and the segment of the crashed writer is removed.
+    ///
+    /// The log must be enabled before anything is inserted, and with the same insert
+    /// policy as the writer that crashed. Edges that are inserted again after the
+    /// replay are merged with the replayed edges when the segment is written.
+    pub fn with_wal(mut self) -> Self {
+        let path = WriteAheadLog::path(&self.path);
+        let mut num_entries = 0;
+
+        let wal = WriteAheadLog::open(path, |entry| {
+            if num_entries == 0 {
+                self.remove_uncommitted_segments()
+                    .expect("failed to remove segment of crashed writer");
+            }
+
+            num_entries += 1;
+            self.apply(entry);
+        })
+        .expect("failed to open write-ahead log");
+
+        if num_entries > 0 {
+            tracing::info!("replayed {} entries from write-ahead log", num_entries);
+        }
+
+        self.wal = Some(wal);
+        self
+    }
+
+    fn apply(&mut self, entry: WalEntry) {
+        match entry {
+            WalEntry::Edge {
+                from,
+                to,
+                label,
+                rel,
+                metadata,
+                timestamp,
+            } => {
+                let current = std::mem::replace(&mut self.timestamp, timestamp);
+                self.insert_with_metadata(from, to, label, rel, metadata);
+                self.timestamp = current;
+            }
+            WalEntry::Alias { alias, canonical } => self.insert_alias(alias, canonical),
+            WalEntry::DeleteNode(node) => self.meta.tombstones.delete_node(node),
+            WalEntry::DeleteEdge(from, to) => self.meta.tombstones.delete_edge(from, to),
+            WalEntry::Checkpoint(name) => {
+                self.checkpoints.insert(name);
+            }
+        }
+    }
+
+    fn log(&mut self, entry: impl FnOnce() -> WalEntry) {
+        if let Some(wal) = self.wal.as_mut() {
+            wal.append(&entry())
+                .expect("failed to write to write-ahead log");
+        }
+    }
+
+    /// Remove the segments in the graph folder that are neither committed nor written by this writer.
+    fn remove_uncommitted_segments(&self) -> crate::Result<()> {
+        let id = self.segment.id();
+
+        for entry in fs::read_dir(Path::new(&self.path).join("segments"))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+
+            if name != id
+                && !self.meta.comitted_segments.iter().any(|s| *s == name)
+                && !manifest::is_segment_open(&entry.path())
+            {
+                fs::remove_dir_all(entry.path())?;
+            }
         }
+
+        Ok(())
+    }
+
+    /// Only keep the links allowed by `policy`.
+    pub fn with_insert_policy(mut self, policy: WebgraphInsertPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Turn urls into nodes with `policy` in [`WebgraphWriter::node`]. The finalized
+    /// graph uses the same normalization.
+    pub fn with_normalization(mut self, policy: Arc<dyn NormalizationPolicy>) -> Self {
+        self.normalization = policy;
+        self
+    }
+
+    /// The node of `url` with the normalization of the writer.
+    pub fn node(&self, url: &url::Url) -> Node {
+        Node::from_url_with(url, self.normalization.as_ref())
+    }
+
+    /// Set when the inserted edges were seen. Defaults to the time the writer was created.
+    pub fn set_timestamp(&mut self, timestamp: u32) {
+        self.timestamp = timestamp;
     }
 
     pub fn id2node(&self, id: &NodeID) -> Option<Node> {
@@ -85,11 +217,74 @@ impl WebgraphWriter {
         id
     }
 
+    /// Record that `alias` is the same page as `canonical`, e.g. because it redirects
+    /// to it or has a canonical link to it. Edge queries for either node return the
+    /// edges of both.
+    pub fn insert_alias(&mut self, alias: Node, canonical: Node) {
+        if alias == canonical {
+            return;
+        }
+
+        self.log(|| WalEntry::Alias {
+            alias: alias.clone(),
+            canonical: canonical.clone(),
+        });
+
+        let alias = self.id_or_assign(alias);
+        let canonical = self.id_or_assign(canonical);
+
+        self.aliases.insert(alias.id, canonical.id);
+    }
+
     pub fn insert(&mut self, from: Node, to: Node, label: String, rel: RelFlags) {
+        self.insert_with_metadata(from, to, label, rel, EdgeMetadata::default());
+    }
+
+    /// Insert an edge together with the position of the link on the source page.
+    pub fn insert_with_metadata(
+        &mut self,
+        from: Node,
+        to: Node,
+        label: String,
+        rel: RelFlags,
+        metadata: EdgeMetadata,
+    ) {
         if from == to {
             return;
         }
 
+        let timestamp = self.timestamp;
+        self.log(|| WalEntry::Edge {
+            from: from.clone(),
+            to: to.clone(),
+            label: label.clone(),
+            rel,
+            metadata,
+            timestamp,
+        });
+
+        if !self.policy.blocked_tlds.is_empty()
+            && self.policy.is_blocked_host(to.clone().into_host().as_str())
+        {
+            return;
+        }
+
+        let is_boilerplate = self.policy.collapse_boilerplate
+            && rel.intersects(RelFlags::IS_IN_FOOTER | RelFlags::IS_IN_NAVIGATION);
+
+        // boilerplate links are collapsed instead, so they don't count towards the cap
+        if !is_boilerplate {
+            if let Some(max_outlinks) = self.policy.max_outlinks_per_node {
+                let num_outlinks = self.num_outlinks.entry(from.id()).or_default();
+
+                if *num_outlinks >= max_outlinks {
+                    return;
+                }
+
+                *num_outlinks += 1;
+            }
+        }
+
         let (from_id, to_id) = (
             self.id_or_assign(from.clone()),
             self.id_or_assign(to.clone()),
@@ -100,25 +295,96 @@ impl WebgraphWriter {
             to: to_id,
             label: label.chars().take(MAX_LABEL_LENGTH).collect(),
             rel,
+            timestamps: EdgeTimestamps::at(self.timestamp),
+            metadata,
         };
 
-        self.segment.insert(edge);
+        if !is_boilerplate {
+            self.segment.insert(edge);
+            return;
+        }
+
+        match self.collapsed.entry((edge.from.host, edge.to.id)) {
+            Entry::Occupied(mut collapsed) => {
+                let metadata = &mut collapsed.get_mut().metadata;
+                metadata.num_collapsed = metadata.num_collapsed.saturating_add(1);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(edge);
+            }
+        }
+    }
+
+    /// Delete the node and all its edges. The deletion applies to every segment of the
+    /// graph the writer is merged into, including edges inserted by this writer.
+    pub fn delete_node(&mut self, node: &Node) {
+        self.log(|| WalEntry::DeleteNode(node.id()));
+        self.meta.tombstones.delete_node(node.id());
+    }
+
+    /// Delete the edge between the nodes. See [`WebgraphWriter::delete_node`].
+    pub fn delete_edge(&mut self, from: &Node, to: &Node) {
+        self.log(|| WalEntry::DeleteEdge(from.id(), to.id()));
+        self.meta.tombstones.delete_edge(from.id(), to.id());
     }
 
+    /// Commit and record that everything up to now belongs to `name`, e.g. a processed
+    /// batch of WARC files. With a write-ahead log, the checkpoints of a writer that
+    /// crashed are known after the log is replayed.
+    pub fn checkpoint(&mut self, name: String) {
+        self.log(|| WalEntry::Checkpoint(name.clone()));
+        self.checkpoints.insert(name);
+        self.commit();
+    }
+
+    pub fn has_checkpoint(&self, name: &str) -> bool {
+        self.checkpoints.contains(name)
+    }
+
+    /// The nodes are flushed before the metadata is saved, so the metadata never
+    /// refers to edges of nodes that are not stored. The write-ahead log is flushed
+    /// and synced to disk first.
     pub fn commit(&mut self) {
-        self.save_metadata();
+        if let Some(wal) = self.wal.as_mut() {
+            wal.commit().expect("failed to commit write-ahead log");
+        }
+
         self.id2node.flush();
+
+        self.aliases.commit();
+
+        self.save_metadata();
     }
 
+    /// Write the segment and commit it to the metadata. Until then the segment is not
+    /// part of the graph, so a crash while writing never leaves a partial segment behind.
     pub fn finalize(mut self) -> Webgraph {
+        for (_, edge) in self.collapsed.drain() {
+            self.segment.insert(edge);
+        }
+
+        let segment = self.segment.finalize();
+        self.meta.comitted_segments.push(segment.id());
         self.commit();
 
+        // the changes in the log are now part of the committed segment
+        if self.wal.take().is_some() {
+            WriteAheadLog::remove(WriteAheadLog::path(&self.path))
+                .expect("failed to remove write-ahead log");
+        }
+
         Webgraph {
             path: self.path,
-            segments: vec![self.segment.finalize()],
+            segments: vec![segment],
             executor: self.executor.into(),
             id2node: self.id2node,
+            aliases: self.aliases,
+            host_summaries: Default::default(),
             meta: self.meta,
+            compression: self.compression,
+            block_cache: None,
+            normalization: self.normalization,
+            metrics: Default::default(),
         }
     }
 }
@@ -0,0 +1,271 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Detection of IDN homograph domains. These are internationalized domain names
+//! that use characters from other scripts which look like latin letters,
+//! e.g. `аррӏе.com` written with cyrillic characters, to imitate another domain.
+//!
+//! A label is flagged if it mixes scripts in a way that is not used by any
+//! language, or if all its non-ascii characters are confusable with ascii letters.
+//! This is a simplified version of the restriction levels in Unicode TS #39.
+
+use std::collections::BTreeSet;
+
+const ACE_PREFIX: &str = "xn--";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Han,
+    Hiragana,
+    Katakana,
+    Hangul,
+    Bopomofo,
+    Other,
+}
+
+impl Script {
+    /// `None` for characters that are used by all scripts, like digits and hyphens.
+    fn of(c: char) -> Option<Self> {
+        let script = match c as u32 {
+            0x30..=0x39 | 0x2D | 0x5F => return None,
+            0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F | 0x250..=0x2AF | 0x1E00..=0x1EFF => {
+                Script::Latin
+            }
+            0x2160..=0x217F => Script::Latin, // roman numerals
+            0x370..=0x3FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x400..=0x52F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Script::Cyrillic,
+            0x530..=0x58F => Script::Armenian,
+            0x590..=0x5FF => Script::Hebrew,
+            0x600..=0x6FF | 0x750..=0x77F => Script::Arabic,
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => Script::Han,
+            0x3040..=0x309F => Script::Hiragana,
+            0x30A0..=0x30FF => Script::Katakana,
+            0xAC00..=0xD7AF | 0x1100..=0x11FF | 0x3130..=0x318F => Script::Hangul,
+            0x3100..=0x312F => Script::Bopomofo,
+            _ => Script::Other,
+        };
+
+        Some(script)
+    }
+}
+
+/// Script combinations that are used by real languages.
+const ALLOWED_MIXES: [&[Script]; 3] = [
+    &[
+        Script::Latin,
+        Script::Han,
+        Script::Hiragana,
+        Script::Katakana,
+    ],
+    &[Script::Latin, Script::Han, Script::Hangul],
+    &[Script::Latin, Script::Han, Script::Bopomofo],
+];
+
+/// Characters that are visually confusable with an ascii letter.
+fn confusable(c: char) -> Option<char> {
+    let ascii = match c {
+        // cyrillic
+        'а' => 'a',
+        'в' => 'b',
+        'с' => 'c',
+        'ԁ' => 'd',
+        'е' => 'e',
+        'һ' => 'h',
+        'і' => 'i',
+        'ј' => 'j',
+        'к' => 'k',
+        'ӏ' => 'l',
+        'о' => 'o',
+        'р' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'у' => 'y',
+        'ԝ' => 'w',
+        'х' => 'x',
+        // greek
+        'α' => 'a',
+        'ι' => 'i',
+        'κ' => 'k',
+        'ν' => 'v',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'υ' => 'u',
+        'χ' => 'x',
+        // latin lookalikes
+        'ɑ' => 'a',
+        'ɡ' => 'g',
+        'ı' => 'i',
+        'ɩ' => 'i',
+        'ȷ' => 'j',
+        'ǀ' => 'l',
+        'ⅼ' => 'l',
+        'ʋ' => 'v',
+        _ => return None,
+    };
+
+    Some(ascii)
+}
+
+/// The labels of the host with the punycode encoded labels decoded, or `None`
+/// if the host is not a valid internationalized domain name.
+fn decode_labels(host: &str) -> Option<Vec<String>> {
+    let (unicode, result) = idna::domain_to_unicode(host);
+    result.ok()?;
+
+    Some(unicode.split('.').map(str::to_string).collect())
+}
+
+fn is_allowed_mix(scripts: &BTreeSet<Script>) -> bool {
+    scripts.len() <= 1
+        || ALLOWED_MIXES
+            .iter()
+            .any(|allowed| scripts.iter().all(|script| allowed.contains(script)))
+}
+
+fn skeleton(label: &str) -> String {
+    label.chars().map(|c| confusable(c).unwrap_or(c)).collect()
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+    utoipa::ToSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct Homograph {
+    /// The host with the internationalized labels decoded.
+    pub unicode_host: String,
+    /// The ascii host the domain can be mistaken for.
+    pub lookalike: String,
+}
+
+/// Check if the (punycode encoded) host is a likely homograph of another domain.
+pub fn detect(host: &str) -> Option<Homograph> {
+    let host = host.to_lowercase();
+
+    if !host.split('.').any(|label| label.starts_with(ACE_PREFIX)) {
+        return None;
+    }
+
+    let labels = decode_labels(&host)?;
+
+    // domains under an internationalized tld are expected to use its script
+    let has_idn_tld = labels.last().map(|tld| !tld.is_ascii()).unwrap_or(false);
+
+    let mut suspicious = false;
+
+    for label in labels.iter().filter(|label| !label.is_ascii()) {
+        let scripts: BTreeSet<Script> = label.chars().filter_map(Script::of).collect();
+
+        if !is_allowed_mix(&scripts) {
+            suspicious = true;
+        }
+
+        let label_skeleton = skeleton(label);
+        if !has_idn_tld && label_skeleton.is_ascii() {
+            suspicious = true;
+        }
+    }
+
+    if !suspicious {
+        return None;
+    }
+
+    Some(Homograph {
+        unicode_host: labels.join("."),
+        lookalike: labels
+            .iter()
+            .map(|label| skeleton(label))
+            .collect::<Vec<_>>()
+            .join("."),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_punycode() {
+        assert_eq!(
+            decode_labels("xn--bcher-kva.example"),
+            Some(vec!["bücher".to_string(), "example".to_string()])
+        );
+        assert_eq!(
+            decode_labels("xn--mnchen-3ya.de"),
+            Some(vec!["münchen".to_string(), "de".to_string()])
+        );
+        assert_eq!(
+            decode_labels("xn--80ak6aa92e.com"),
+            Some(vec!["аррӏе".to_string(), "com".to_string()])
+        );
+        assert_eq!(decode_labels("xn--!!!.com"), None);
+    }
+
+    #[test]
+    fn whole_script_confusable() {
+        let homograph = detect("xn--80ak6aa92e.com").unwrap();
+
+        assert_eq!(homograph.unicode_host, "аррӏе.com");
+        assert_eq!(homograph.lookalike, "apple.com");
+    }
+
+    #[test]
+    fn mixed_script() {
+        // cyrillic "а" followed by latin "pple"
+        let homograph = detect("xn--pple-43d.com").unwrap();
+        assert_eq!(homograph.unicode_host, "аpple.com");
+        assert_eq!(homograph.lookalike, "apple.com");
+
+        // latin "g" and "gle" with two cyrillic "о"
+        let homograph = detect("xn--ggle-55da.com").unwrap();
+        assert_eq!(homograph.lookalike, "google.com");
+    }
+
+    #[test]
+    fn legitimate_domains() {
+        assert_eq!(detect("example.com"), None);
+        assert_eq!(detect("xn--mnchen-3ya.de"), None); // münchen.de
+        assert_eq!(detect("xn--bcher-kva.example"), None); // bücher.example
+    }
+
+    #[test]
+    fn allowed_mixes() {
+        assert!(is_allowed_mix(
+            &[Script::Latin, Script::Han, Script::Katakana]
+                .into_iter()
+                .collect()
+        ));
+        assert!(!is_allowed_mix(
+            &[Script::Latin, Script::Cyrillic].into_iter().collect()
+        ));
+        assert!(!is_allowed_mix(
+            &[Script::Greek, Script::Cyrillic].into_iter().collect()
+        ));
+    }
+}
@@ -0,0 +1,214 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The breadcrumb is the path shown below the title of a search result.
+//! We prefer the breadcrumb the site itself has annotated using a schema.org `BreadcrumbList`
+//! and fall back to the segments of the url path.
+
+use url::Url;
+
+use crate::webpage::schema_org::Item;
+
+const MAX_CRUMBS: usize = 4;
+const MAX_CRUMB_CHARS: usize = 32;
+
+const FILE_EXTENSIONS: [&str; 6] = [".html", ".htm", ".php", ".asp", ".aspx", ".jsp"];
+
+fn truncate(crumb: &str) -> String {
+    let crumb = crumb.trim();
+
+    if crumb.chars().count() > MAX_CRUMB_CHARS {
+        let mut res: String = crumb.chars().take(MAX_CRUMB_CHARS - 1).collect();
+        res = res.trim_end().to_string();
+        res.push('…');
+        res
+    } else {
+        crumb.to_string()
+    }
+}
+
+fn string_of(item: &Item, key: &str) -> Option<String> {
+    item.properties
+        .get(key)
+        .and_then(|p| p.clone().one())
+        .and_then(|p| p.try_into_string())
+}
+
+fn list_item_name(item: &Item) -> Option<String> {
+    string_of(item, "name").or_else(|| {
+        item.properties
+            .get("item")
+            .and_then(|p| p.clone().one())
+            .and_then(|p| p.try_into_item())
+            .and_then(|item| string_of(&item, "name"))
+    })
+}
+
+fn from_schema(schemas: &[Item]) -> Option<Vec<String>> {
+    let list = schemas
+        .iter()
+        .find(|item| item.types_contains("BreadcrumbList"))?;
+
+    let mut elements: Vec<(usize, String)> = list
+        .properties
+        .get("itemListElement")?
+        .clone()
+        .many()
+        .into_iter()
+        .filter_map(|p| p.try_into_item())
+        .enumerate()
+        .filter_map(|(i, item)| {
+            let position = string_of(&item, "position")
+                .and_then(|p| p.trim().parse().ok())
+                .unwrap_or(i);
+
+            list_item_name(&item).map(|name| (position, name))
+        })
+        .collect();
+
+    elements.sort_by_key(|(position, _)| *position);
+
+    let crumbs: Vec<_> = elements
+        .into_iter()
+        .map(|(_, name)| name)
+        .filter(|name| !name.trim().is_empty())
+        .skip_while(|name| name.trim().eq_ignore_ascii_case("home"))
+        .map(|name| truncate(&name))
+        .take(MAX_CRUMBS)
+        .collect();
+
+    if crumbs.is_empty() {
+        None
+    } else {
+        Some(crumbs)
+    }
+}
+
+fn from_url(url: &Url) -> Vec<String> {
+    let Some(segments) = url.path_segments() else {
+        return Vec::new();
+    };
+
+    segments
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            let segment = urlencoding::decode(segment)
+                .map(|s| s.into_owned())
+                .unwrap_or_else(|_| segment.to_string());
+
+            let lowercase = segment.to_ascii_lowercase();
+            match FILE_EXTENSIONS.iter().find(|ext| lowercase.ends_with(*ext)) {
+                Some(ext) => segment[..segment.len() - ext.len()].to_string(),
+                None => segment,
+            }
+        })
+        .filter(|segment| !segment.is_empty() && segment != "index")
+        .map(|segment| truncate(&segment))
+        .take(MAX_CRUMBS)
+        .collect()
+}
+
+pub fn breadcrumb(schemas: &[Item], url: &Url) -> Vec<String> {
+    from_schema(schemas).unwrap_or_else(|| from_url(url))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::webpage::Html;
+
+    use super::*;
+
+    #[test]
+    fn schema_breadcrumb() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head>
+                    <script type="application/ld+json">
+                    {
+                        "@context": "https://schema.org",
+                        "@type": "BreadcrumbList",
+                        "itemListElement": [
+                            {
+                                "@type": "ListItem",
+                                "position": 3,
+                                "name": "The Fifth Season",
+                                "item": "https://example.com/books/scifi/fifth-season"
+                            },
+                            {
+                                "@type": "ListItem",
+                                "position": 1,
+                                "name": "Home",
+                                "item": "https://example.com/"
+                            },
+                            {
+                                "@type": "ListItem",
+                                "position": 2,
+                                "item": {
+                                    "@type": "Thing",
+                                    "@id": "https://example.com/books/scifi",
+                                    "name": "Science Fiction"
+                                }
+                            }
+                        ]
+                    }
+                    </script>
+                </head>
+                <body></body>
+            </html>
+            "#,
+            "https://example.com/p/123",
+        )
+        .unwrap();
+
+        assert_eq!(
+            breadcrumb(&html.schema_org(), html.url()),
+            vec![
+                "Science Fiction".to_string(),
+                "The Fifth Season".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn url_breadcrumb() {
+        let url =
+            Url::parse("https://example.com/books/science%20fiction/index.html?id=1").unwrap();
+        assert_eq!(
+            breadcrumb(&[], &url),
+            vec!["books".to_string(), "science fiction".to_string()]
+        );
+
+        let url = Url::parse("https://example.com/").unwrap();
+        assert!(breadcrumb(&[], &url).is_empty());
+
+        let url = Url::parse("https://example.com/a/b/c/d/e/f").unwrap();
+        assert_eq!(breadcrumb(&[], &url).len(), MAX_CRUMBS);
+    }
+
+    #[test]
+    fn long_crumbs_are_truncated() {
+        let url = Url::parse(
+            "https://example.com/this-is-a-very-long-slug-that-should-be-truncated-somewhere",
+        )
+        .unwrap();
+
+        let crumbs = breadcrumb(&[], &url);
+        assert_eq!(crumbs.len(), 1);
+        assert!(crumbs[0].ends_with('…'));
+        assert!(crumbs[0].chars().count() <= MAX_CRUMB_CHARS);
+    }
+}
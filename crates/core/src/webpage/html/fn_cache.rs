@@ -17,7 +17,7 @@
 use crate::{webpage::schema_org, Result};
 use tantivy::tokenizer::PreTokenizedString;
 
-use super::{find_recipe_first_ingredient_tag_id, Html};
+use super::{breadcrumb::breadcrumb, find_recipe_first_ingredient_tag_id, Html};
 
 macro_rules! cache {
     ($($fn:ident -> $res:ty),*$(,)?) => {
@@ -30,6 +30,7 @@ macro_rules! cache {
         pub struct FnCache<'a> {
             html: &'a Html,
             first_ingredient_tag_id: Option<String>,
+            breadcrumb: Option<Vec<String>>,
             schema_json: Option<String>,
             pretokenized_schema_json: Option<PreTokenizedString>,
             $($fn: Option<$res>,)*
@@ -41,6 +42,7 @@ macro_rules! cache {
                 Self {
                     html,
                     first_ingredient_tag_id: None,
+                    breadcrumb: None,
                     schema_json: None,
                     pretokenized_schema_json: None,
                     $($fn: None,)*
@@ -95,6 +97,15 @@ impl<'a> FnCache<'a> {
         self.first_ingredient_tag_id.as_ref()
     }
 
+    pub fn breadcrumb(&mut self) -> &Vec<String> {
+        if self.breadcrumb.is_none() {
+            let url = self.html.url().clone();
+            self.breadcrumb = Some(breadcrumb(self.schema_org().as_slice(), &url));
+        }
+
+        self.breadcrumb.as_ref().unwrap()
+    }
+
     pub fn schema_json(&mut self) -> &String {
         if self.schema_json.is_none() {
             self.schema_json = Some(serde_json::to_string(self.schema_org()).unwrap());
@@ -16,9 +16,13 @@
 
 use bitflags::bitflags;
 use kuchiki::{iter::NodeEdge, Attributes};
+use std::cell::RefCell;
 use url::Url;
 
-use crate::webpage::{url_ext::UrlExt, Link};
+use crate::{
+    webgraph::{EdgeMetadata, LinkBlock},
+    webpage::{url_ext::UrlExt, Link},
+};
 
 use super::Html;
 
@@ -69,6 +73,7 @@ impl RelFlags {
                     "tag" => res |= RelFlags::TAG,
                     "terms-of-service" => res |= RelFlags::TERMS_OF_SERVICE,
                     "sponsored" => res |= RelFlags::SPONSORED,
+                    "ugc" => res |= RelFlags::UGC,
                     _ => {}
                 }
             }
@@ -121,6 +126,9 @@ bitflags! {
         const LINK_TAG = 1 << 18;
         const SCRIPT_TAG = 1 << 19;
         const META_TAG = 1 << 20;
+        const UGC = 1 << 21;
+        /// The link is on a parked domain.
+        const PARKED = 1 << 22;
     }
 }
 
@@ -164,6 +172,42 @@ bitflags! {
     }
 }
 
+/// An anchor element whose end tag has not been reached yet.
+struct OpenLink {
+    text: String,
+    attributes: RefCell<Attributes>,
+    dom_depth: u16,
+    heading_level: u8,
+    block: LinkBlock,
+    /// Length of the text that comes before the link.
+    text_before: usize,
+}
+
+impl OpenLink {
+    fn metadata(&self) -> EdgeMetadata {
+        EdgeMetadata {
+            dom_depth: self.dom_depth,
+            position: 0,
+            heading_level: self.heading_level,
+            block: self.block,
+            text_offset: self.text_before.min(u32::MAX as usize) as u32,
+            num_collapsed: 0,
+        }
+    }
+}
+
+fn heading(name: &str) -> Option<u8> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
 impl Html {
     pub fn favicon(&self) -> Option<FaviconLink> {
         for node in self.root.select("link").unwrap() {
@@ -246,32 +290,65 @@ impl Html {
     }
 
     pub fn anchor_links(&self) -> Vec<Link> {
+        self.anchor_links_with_metadata()
+            .into_iter()
+            .map(|(link, _)| link)
+            .collect()
+    }
+
+    /// Anchor links together with where on the page they were found.
+    pub fn anchor_links_with_metadata(&self) -> Vec<(Link, EdgeMetadata)> {
         if self.is_no_follow() {
             return Vec::new();
         }
 
         let mut links = Vec::new();
-        let mut open_links = Vec::new();
+        let mut open_links: Vec<OpenLink> = Vec::new();
         let mut location = Location::empty();
+        let mut depth: u16 = 0;
+        let mut heading_level = 0;
+        let mut blocks: Vec<LinkBlock> = Vec::new();
+        let mut text_len = 0;
 
         for edge in self.root.traverse() {
             match edge {
                 NodeEdge::Start(node) => {
                     if let Some(element) = node.as_element() {
+                        depth = depth.saturating_add(1);
+
+                        if let Some(block) = LinkBlock::from_element(&element.name.local) {
+                            blocks.push(block);
+                        }
+
                         if &element.name.local == "a" {
-                            open_links.push((String::new(), element.attributes.clone()));
+                            open_links.push(OpenLink {
+                                text: String::new(),
+                                attributes: element.attributes.clone(),
+                                dom_depth: depth,
+                                heading_level,
+                                block: blocks.last().copied().unwrap_or_default(),
+                                text_before: text_len,
+                            });
                         } else if &element.name.local == "footer" {
                             location |= Location::FOOTER;
                         } else if &element.name.local == "nav" {
                             location |= Location::NAVIGATION;
+                        } else if let Some(level) = heading(&element.name.local) {
+                            heading_level = level;
                         }
                     }
                 }
                 NodeEdge::End(node) => {
                     if let Some(element) = node.as_element() {
+                        depth = depth.saturating_sub(1);
+
+                        if LinkBlock::from_element(&element.name.local).is_some() {
+                            blocks.pop();
+                        }
+
                         if &element.name.local == "a" {
-                            if let Some((text, attributes)) = open_links.pop() {
-                                if let Some(dest) = attributes.borrow().get("href") {
+                            if let Some(open) = open_links.pop() {
+                                if let Some(dest) = open.attributes.borrow().get("href") {
                                     if dest.starts_with("mailto:") || dest.starts_with("tel:") {
                                         continue;
                                     }
@@ -279,16 +356,20 @@ impl Html {
                                     if let Ok(dest) =
                                         Url::parse(dest).or_else(|_| self.url().join(dest))
                                     {
-                                        links.push(Link {
-                                            source: self.url().clone(),
-                                            text: text.trim().to_string(),
-                                            rel: RelFlags::from_html(
-                                                &dest,
-                                                &attributes.borrow(),
-                                                &location,
-                                            ),
-                                            destination: dest,
-                                        });
+                                        links.push((
+                                            Link {
+                                                source: self.url().clone(),
+                                                text: open.text.trim().to_string(),
+                                                rel: RelFlags::from_html(
+                                                    &dest,
+                                                    &open.attributes.borrow(),
+                                                    &location,
+                                                ),
+                                                destination: dest,
+                                            },
+                                            open.metadata(),
+                                            open.text_before,
+                                        ));
                                     }
                                 }
                             }
@@ -304,9 +385,11 @@ impl Html {
                         let text = raw_text.trim();
 
                         if !text.is_empty() {
-                            for (link_text, _) in &mut open_links {
-                                link_text.push('\n');
-                                link_text.push_str(text);
+                            text_len += text.len();
+
+                            for open in &mut open_links {
+                                open.text.push('\n');
+                                open.text.push_str(text);
                             }
                         }
                     }
@@ -314,30 +397,41 @@ impl Html {
             }
         }
 
-        while let Some((text, attributes)) = open_links.pop() {
-            if let Some(rel) = attributes.borrow().get("rel") {
+        while let Some(open) = open_links.pop() {
+            if let Some(rel) = open.attributes.borrow().get("rel") {
                 if rel.contains("nofollow") || rel.contains("sponsored") || rel.contains("ugc") {
                     continue;
                 }
             }
 
-            if let Some(dest) = attributes.borrow().get("href") {
+            if let Some(dest) = open.attributes.borrow().get("href") {
                 if dest.starts_with("mailto:") || dest.starts_with("tel:") {
                     continue;
                 }
 
                 if let Ok(dest) = Url::parse(dest).or_else(|_| self.url().join(dest)) {
-                    links.push(Link {
-                        source: self.url().clone(),
-                        rel: RelFlags::from_html(&dest, &attributes.borrow(), &location),
-                        destination: dest,
-                        text: text.trim().to_string(),
-                    });
+                    links.push((
+                        Link {
+                            source: self.url().clone(),
+                            rel: RelFlags::from_html(&dest, &open.attributes.borrow(), &location),
+                            destination: dest,
+                            text: open.text.trim().to_string(),
+                        },
+                        open.metadata(),
+                        open.text_before,
+                    ));
                 }
             }
         }
 
         links
+            .into_iter()
+            .map(|(link, mut metadata, text_before)| {
+                metadata.position = (text_before * u16::MAX as usize / text_len.max(1))
+                    .min(u16::MAX as usize) as u16;
+                (link, metadata)
+            })
+            .collect()
     }
 
     fn links_tag(&self) -> Vec<Link> {
@@ -703,4 +797,52 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn anchor_metadata() {
+        let raw = r#"
+            <html>
+                <body>
+                    <a href="https://example.com/top">Top</a>
+                    <h2>Section</h2>
+                    <p>Some text before the <a href="https://example.com/inline">inline</a> link</p>
+                    <div>
+                        <div>
+                            <a href="https://example.com/nested">Nested</a>
+                        </div>
+                    </div>
+                    <ul>
+                        <li><a href="https://example.com/listed">Listed</a></li>
+                    </ul>
+                </body>
+            </html>
+        "#;
+
+        let webpage = Html::parse(raw, "https://www.example.com/whatever").unwrap();
+        let links = webpage.anchor_links_with_metadata();
+
+        assert_eq!(links.len(), 4);
+
+        let (top, inline, nested, listed) = (links[0].1, links[1].1, links[2].1, links[3].1);
+
+        assert_eq!(top.heading_level, 0);
+        assert_eq!(top.position, 0);
+        assert_eq!(inline.heading_level, 2);
+        assert_eq!(inline.dom_depth, top.dom_depth + 1);
+        assert_eq!(nested.dom_depth, top.dom_depth + 2);
+        assert!(top.position < inline.position);
+        assert!(inline.position < nested.position);
+        assert!(top.prominence() > nested.prominence());
+
+        assert_eq!(top.block, LinkBlock::Unknown);
+        assert_eq!(inline.block, LinkBlock::Paragraph);
+        assert_eq!(nested.block, LinkBlock::Unknown);
+        assert_eq!(listed.block, LinkBlock::ListItem);
+        assert_eq!(top.text_offset, 0);
+        assert_eq!(
+            inline.text_offset as usize,
+            "Top".len() + "Section".len() + "Some text before the".len()
+        );
+        assert!(nested.text_offset < listed.text_offset);
+    }
 }
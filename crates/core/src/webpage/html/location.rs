@@ -0,0 +1,280 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Inference of the regions a page is relevant to from its content alone, so
+//! local-intent queries can prefer pages from the region the user selected
+//! without ever geolocating the user. A page is tagged with a region when
+//! - it is served from the country code top-level domain of the region.
+//! - its language tag or the `hreflang` alternate pointing to the page itself
+//!   has the region subtag, e.g. `da-DK`.
+//! - it lists a phone number with the calling code of the region in a `tel:` link,
+//!   in schema.org or in the text.
+//! - it lists a postal address in the region in schema.org or an `<address>` element.
+
+use regex::Regex;
+use url::Url;
+
+use crate::webpage::{
+    region::{Region, RegionTags},
+    schema_org::{Item, Property},
+    url_ext::UrlExt,
+};
+
+use super::Html;
+
+/// International phone numbers with one of the calling codes in [`Region::from_calling_code`].
+static PHONE_REGEX: once_cell::sync::Lazy<Regex> =
+    once_cell::sync::Lazy::new(|| Regex::new(r"\+(1|33|34|45|49)(?:[\s.\-()/]*\d){6,}").unwrap());
+
+/// The region subtag of a language tag like `da-DK` or `zh-Hant-TW`.
+fn region_subtag(lang: &str) -> Option<Region> {
+    lang.split(['-', '_'])
+        .skip(1)
+        .find(|subtag| subtag.len() == 2)
+        .and_then(Region::from_country)
+}
+
+fn calling_code_regions(text: &str) -> impl Iterator<Item = Region> + '_ {
+    PHONE_REGEX
+        .captures_iter(text)
+        .filter_map(|cap| Region::from_calling_code(cap.get(1)?.as_str()))
+}
+
+fn is_same_page(a: &Url, b: &Url) -> bool {
+    a.host_str() == b.host_str() && a.path().trim_end_matches('/') == b.path().trim_end_matches('/')
+}
+
+/// All the schema.org items of the page, including the items nested in other items.
+fn nested_items(items: Vec<Item>) -> Vec<Item> {
+    let mut res = Vec::new();
+    let mut stack = items;
+
+    while let Some(item) = stack.pop() {
+        for property in item.properties.values() {
+            for property in property.clone().many() {
+                if let Property::Item(nested) = property {
+                    stack.push(nested);
+                }
+            }
+        }
+
+        res.push(item);
+    }
+
+    res
+}
+
+fn strings(item: &Item, key: &str) -> Vec<String> {
+    item.properties
+        .get(key)
+        .map(|p| {
+            p.clone()
+                .many()
+                .into_iter()
+                .filter_map(|p| match p {
+                    Property::String(s) => Some(s),
+                    // countries are sometimes given as a `Country` item with a name
+                    Property::Item(item) => strings(&item, "name").into_iter().next(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Html {
+    fn lang_regions(&self) -> Vec<Region> {
+        let mut regions = Vec::new();
+
+        if let Ok(html) = self.root.select_first("html") {
+            if let Some(lang) = html.attributes.borrow().get("lang") {
+                regions.extend(region_subtag(lang));
+            }
+        }
+
+        for node in self.root.select("link[hreflang]").unwrap() {
+            let attributes = node.attributes.borrow();
+
+            let Some(lang) = attributes.get("hreflang") else {
+                continue;
+            };
+
+            let points_to_page = attributes
+                .get("href")
+                .and_then(|href| self.url().join(href).ok())
+                .is_some_and(|href| is_same_page(&href, self.url()));
+
+            if points_to_page {
+                regions.extend(region_subtag(lang));
+            }
+        }
+
+        regions
+    }
+
+    fn phone_regions(&self, items: &[Item]) -> Vec<Region> {
+        let mut regions = Vec::new();
+
+        for node in self.root.select("a[href^='tel:']").unwrap() {
+            if let Some(href) = node.attributes.borrow().get("href") {
+                regions.extend(calling_code_regions(href));
+            }
+        }
+
+        for item in items {
+            for phone in strings(item, "telephone") {
+                regions.extend(calling_code_regions(&phone));
+            }
+        }
+
+        if let Some(text) = self.all_text() {
+            regions.extend(calling_code_regions(&text));
+        }
+
+        regions
+    }
+
+    fn address_regions(&self, items: &[Item]) -> Vec<Region> {
+        let mut regions = Vec::new();
+
+        for item in items
+            .iter()
+            .filter(|item| item.types_contains("PostalAddress"))
+        {
+            regions.extend(
+                strings(item, "addressCountry")
+                    .iter()
+                    .filter_map(|country| Region::from_country(country)),
+            );
+        }
+
+        for node in self.root.select("address").unwrap() {
+            regions.extend(Region::mentioned_in(&node.text_contents()));
+        }
+
+        regions
+    }
+
+    /// The regions the page is relevant to, inferred from the content of the page.
+    pub fn region_tags(&self) -> RegionTags {
+        let items = nested_items(self.schema_org());
+
+        self.url()
+            .tld()
+            .and_then(Region::from_country)
+            .into_iter()
+            .chain(self.lang_regions())
+            .chain(self.phone_regions(&items))
+            .chain(self.address_regions(&items))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(html: &str, url: &str) -> Vec<Region> {
+        Html::parse(html, url)
+            .unwrap()
+            .region_tags()
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn cctld() {
+        assert_eq!(
+            tags("<html><body>hej</body></html>", "https://example.dk/"),
+            vec![Region::Denmark]
+        );
+        assert!(tags("<html><body>hello</body></html>", "https://example.com/").is_empty());
+    }
+
+    #[test]
+    fn lang() {
+        assert_eq!(
+            tags(
+                r#"<html lang="de-DE"><body>hallo</body></html>"#,
+                "https://example.com/"
+            ),
+            vec![Region::Germany]
+        );
+
+        // only the alternate for the page itself says where the page is relevant
+        assert_eq!(
+            tags(
+                r#"<html lang="en"><head>
+                <link rel="alternate" hreflang="es-ES" href="https://example.com/es/" />
+                <link rel="alternate" hreflang="fr-FR" href="https://example.com/fr/" />
+                </head><body>bonjour</body></html>"#,
+                "https://example.com/fr/"
+            ),
+            vec![Region::France]
+        );
+    }
+
+    #[test]
+    fn phone() {
+        assert_eq!(
+            tags(
+                r#"<html><body><a href="tel:+4512345678">call us</a></body></html>"#,
+                "https://example.com/"
+            ),
+            vec![Region::Denmark]
+        );
+        assert_eq!(
+            tags(
+                "<html><body>Call us at +1 (555) 123-4567</body></html>",
+                "https://example.com/"
+            ),
+            vec![Region::US]
+        );
+        assert!(tags(
+            "<html><body>The score went +12 to 45</body></html>",
+            "https://example.com/"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn address() {
+        assert_eq!(
+            tags(
+                r#"<html><head><script type="application/ld+json">
+                {
+                    "@context": "https://schema.org",
+                    "@type": "Restaurant",
+                    "name": "Casa",
+                    "address": {
+                        "@type": "PostalAddress",
+                        "addressLocality": "Madrid",
+                        "addressCountry": "ES"
+                    }
+                }
+                </script></head><body>hola</body></html>"#,
+                "https://example.com/"
+            ),
+            vec![Region::Spain]
+        );
+        assert_eq!(
+            tags(
+                "<html><body><address>1 Main St, Springfield, USA</address></body></html>",
+                "https://example.com/"
+            ),
+            vec![Region::US]
+        );
+    }
+}
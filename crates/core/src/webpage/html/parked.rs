@@ -0,0 +1,268 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Detection of parked domains and "domain for sale" pages.
+//! These pages have no content of their own and usually only show ads
+//! or a link to a domain marketplace.
+//!
+//! Each of the signals below also appears on regular pages, e.g. a blog post
+//! about selling a domain or a short page linking to a registrar, so a page is
+//! only considered parked when it is the homepage of the host and shows several
+//! of the signals.
+
+use url::Url;
+
+use crate::webpage::url_ext::UrlExt;
+
+use super::Html;
+
+/// Pages with more words than this are never classified as parked.
+const MAX_WORDS: usize = 500;
+
+/// Number of signals a page must show to be classified as parked.
+const MIN_SIGNALS: usize = 2;
+
+/// Services that serve the ads or landers of parked domains.
+const PARKING_SERVICES: [&str; 8] = [
+    "sedoparking.com",
+    "parkingcrew.net",
+    "bodis.com",
+    "above.com",
+    "parklogic.com",
+    "domainsponsor.com",
+    "parked.com",
+    "smartname.com",
+];
+
+const MARKETPLACES: [&str; 9] = [
+    "dan.com",
+    "sedo.com",
+    "afternic.com",
+    "hugedomains.com",
+    "undeveloped.com",
+    "uniregistry.com",
+    "domainmarket.com",
+    "buydomains.com",
+    "atom.com",
+];
+
+const PHRASES: [&str; 10] = [
+    "domain is for sale",
+    "domain may be for sale",
+    "domain name is for sale",
+    "buy this domain",
+    "make an offer on this domain",
+    "inquire about this domain",
+    "this domain is parked",
+    "parked free",
+    "domain has been registered",
+    "domain is registered and parked",
+];
+
+fn is_one_of(url: &Url, domains: &[&str]) -> bool {
+    url.root_domain()
+        .map(|domain| domains.contains(&domain))
+        .unwrap_or(false)
+}
+
+impl Html {
+    fn external_urls(&self, selector: &str, attr: &str) -> Vec<Url> {
+        self.root
+            .select(selector)
+            .unwrap()
+            .filter_map(|node| {
+                node.attributes
+                    .borrow()
+                    .get(attr)
+                    .and_then(|url| self.url().join(url).ok())
+            })
+            .filter(|url| url.root_domain() != self.url().root_domain())
+            .collect()
+    }
+
+    /// Whether the page is a parked domain or a page that only
+    /// advertises the domain for sale.
+    pub fn is_parked(&self) -> bool {
+        if !self.is_homepage() {
+            return false;
+        }
+
+        let body = self
+            .root
+            .select_first("body")
+            .map(|body| body.text_contents())
+            .unwrap_or_default();
+
+        if body.split_whitespace().count() > MAX_WORDS {
+            return false;
+        }
+
+        let uses_parking_service = self
+            .external_urls("script", "src")
+            .into_iter()
+            .chain(self.external_urls("iframe", "src"))
+            .chain(self.external_urls("a", "href"))
+            .chain(self.external_urls("form", "action"))
+            .any(|url| is_one_of(&url, &PARKING_SERVICES));
+
+        let links_to_marketplace = self
+            .external_urls("a", "href")
+            .into_iter()
+            .any(|url| is_one_of(&url, &MARKETPLACES));
+
+        let text = (self.title().unwrap_or_default() + " " + &body).to_lowercase();
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        // landers often simply state "example.com is for sale"
+        let has_sale_phrase = PHRASES.iter().any(|phrase| text.contains(phrase))
+            || self.url().host_str().is_some_and(|host| {
+                text.contains(&format!("{} is for sale", host.trim_start_matches("www.")))
+            });
+
+        [uses_parking_service, links_to_marketplace, has_sale_phrase]
+            .into_iter()
+            .filter(|signal| *signal)
+            .count()
+            >= MIN_SIGNALS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_sale_page() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head><title>example.com</title></head>
+                <body>
+                    <h1>The domain example.com is for sale!</h1>
+                    <a href="https://dan.com/buy-domain/example.com">Make an offer today.</a>
+                </body>
+            </html>
+            "#,
+            "https://example.com/",
+        )
+        .unwrap();
+
+        assert!(html.is_parked());
+    }
+
+    #[test]
+    fn parking_service() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head>
+                    <title>example.com</title>
+                    <script src="https://www.parkingcrew.net/lander.js"></script>
+                </head>
+                <body>This domain is parked. Related searches</body>
+            </html>
+            "#,
+            "https://example.com/",
+        )
+        .unwrap();
+
+        assert!(html.is_parked());
+    }
+
+    #[test]
+    fn single_signal() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head><title>example.com</title></head>
+                <body><a href="https://dan.com/buy-domain/example.com">Get this domain</a></body>
+            </html>
+            "#,
+            "https://example.com/",
+        )
+        .unwrap();
+
+        assert!(!html.is_parked());
+
+        let html = Html::parse(
+            r#"
+            <html>
+                <head><title>Coming soon</title></head>
+                <body>The domain has been registered. Our new website is coming soon.</body>
+            </html>
+            "#,
+            "https://example.com/",
+        )
+        .unwrap();
+
+        assert!(!html.is_parked());
+    }
+
+    #[test]
+    fn not_homepage() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head><title>example.com</title></head>
+                <body>
+                    <h1>The domain example.com is for sale!</h1>
+                    <a href="https://dan.com/buy-domain/example.com">Make an offer today.</a>
+                </body>
+            </html>
+            "#,
+            "https://example.com/blog/for-sale",
+        )
+        .unwrap();
+
+        assert!(!html.is_parked());
+    }
+
+    #[test]
+    fn regular_page() {
+        let html = Html::parse(
+            &format!(
+                r#"
+                <html>
+                    <head><title>How we sold our domain</title></head>
+                    <body>
+                        <p>Last year we decided that the domain is for sale.</p>
+                        <a href="https://dan.com/">We used a marketplace</a>
+                        <p>{}</p>
+                    </body>
+                </html>
+                "#,
+                "Lorem ipsum dolor sit amet. ".repeat(200)
+            ),
+            "https://example.com/blog/selling-our-domain",
+        )
+        .unwrap();
+
+        assert!(!html.is_parked());
+
+        let html = Html::parse(
+            r#"
+            <html>
+                <head><title>Contact</title></head>
+                <body><a href="https://dan.com/">Our broker</a> can be reached by email.</body>
+            </html>
+            "#,
+            "https://example.com/contact",
+        )
+        .unwrap();
+
+        assert!(!html.is_parked());
+    }
+}
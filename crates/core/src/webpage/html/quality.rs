@@ -0,0 +1,382 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A simple model of the content quality of a page. The model combines a handful
+//! of heuristics computed from the html into a single score between 0 and 1,
+//! where a higher score means the page is more likely to contain substantial content.
+
+use url::Url;
+use whatlang::Lang;
+
+use crate::webpage::{adservers::AD_SERVERS, just_text::JustText, url_ext::UrlExt};
+
+use super::Html;
+
+/// Pages with a text/markup ratio at or above this get the full text score.
+const GOOD_TEXT_MARKUP_RATIO: f64 = 0.25;
+
+/// Pages with this many words in the main content get the full length score.
+const GOOD_NUM_WORDS: f64 = 1000.0;
+
+/// Paragraphs with at least this many words are considered part of the main content.
+const MIN_PARAGRAPH_WORDS: usize = 20;
+
+/// Flesch reading ease scores in this range are considered normal prose.
+const READING_EASE_RANGE: (f64, f64) = (30.0, 80.0);
+
+/// Class and id tokens that usually mark an ad slot.
+const AD_MARKERS: [&str; 7] = [
+    "ad",
+    "ads",
+    "advert",
+    "advertisement",
+    "adsbygoogle",
+    "sponsored",
+    "banner",
+];
+
+const TEXT_MARKUP_WEIGHT: f64 = 0.2;
+const AD_WEIGHT: f64 = 0.2;
+const READING_LEVEL_WEIGHT: f64 = 0.15;
+const LENGTH_WEIGHT: f64 = 0.25;
+const PARAGRAPH_WEIGHT: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentQuality {
+    /// Bytes of visible text per byte of html.
+    pub text_markup_ratio: f64,
+    /// Number of ad elements per 100 words of content.
+    pub ad_density: f64,
+    /// Flesch reading ease of the main content. The syllables are counted with
+    /// english rules, so it is only computed for english pages.
+    pub reading_ease: Option<f64>,
+    /// Number of words in the main content.
+    pub num_words: usize,
+    /// Ratio of the words on the page that are in paragraphs of
+    /// at least [`MIN_PARAGRAPH_WORDS`] words.
+    pub long_paragraph_ratio: f64,
+}
+
+impl ContentQuality {
+    fn text_markup_score(&self) -> f64 {
+        (self.text_markup_ratio / GOOD_TEXT_MARKUP_RATIO).min(1.0)
+    }
+
+    fn ad_score(&self) -> f64 {
+        1.0 / (1.0 + self.ad_density)
+    }
+
+    fn reading_level_score(&self) -> Option<f64> {
+        let reading_ease = self.reading_ease?;
+
+        if self.num_words == 0 {
+            return Some(0.0);
+        }
+
+        let (low, high) = READING_EASE_RANGE;
+        let distance = if reading_ease < low {
+            low - reading_ease
+        } else if reading_ease > high {
+            reading_ease - high
+        } else {
+            0.0
+        };
+
+        Some((1.0 - distance / (high - low)).max(0.0))
+    }
+
+    fn length_score(&self) -> f64 {
+        ((1.0 + self.num_words as f64).ln() / (1.0 + GOOD_NUM_WORDS).ln()).min(1.0)
+    }
+
+    /// Weighted combination of the individual heuristics. Always between 0 and 1.
+    /// Without a reading level, the other heuristics make up the whole score.
+    pub fn score(&self) -> f64 {
+        let mut score = TEXT_MARKUP_WEIGHT * self.text_markup_score()
+            + AD_WEIGHT * self.ad_score()
+            + LENGTH_WEIGHT * self.length_score()
+            + PARAGRAPH_WEIGHT * self.long_paragraph_ratio;
+        let mut total_weight = TEXT_MARKUP_WEIGHT + AD_WEIGHT + LENGTH_WEIGHT + PARAGRAPH_WEIGHT;
+
+        if let Some(reading_level) = self.reading_level_score() {
+            score += READING_LEVEL_WEIGHT * reading_level;
+            total_weight += READING_LEVEL_WEIGHT;
+        }
+
+        score / total_weight
+    }
+}
+
+fn num_syllables(word: &str) -> usize {
+    let mut count = 0;
+    let mut last_was_vowel = false;
+
+    for c in word.chars().flat_map(|c| c.to_lowercase()) {
+        let is_vowel = matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+
+        if is_vowel && !last_was_vowel {
+            count += 1;
+        }
+
+        last_was_vowel = is_vowel;
+    }
+
+    count.max(1)
+}
+
+/// The Flesch reading ease of the text. Higher scores are easier to read.
+fn reading_ease(text: &str) -> f64 {
+    let words: Vec<_> = text
+        .split_whitespace()
+        .filter(|w| w.chars().any(|c| c.is_alphabetic()))
+        .collect();
+
+    if words.is_empty() {
+        return 0.0;
+    }
+
+    let num_sentences = text
+        .split(['.', '!', '?'])
+        .filter(|s| s.chars().any(|c| c.is_alphabetic()))
+        .count()
+        .max(1);
+    let num_syllables: usize = words.iter().map(|w| num_syllables(w)).sum();
+
+    let words_per_sentence = words.len() as f64 / num_sentences as f64;
+    let syllables_per_word = num_syllables as f64 / words.len() as f64;
+
+    206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word
+}
+
+impl Html {
+    fn is_ad_url(&self, url: &str) -> bool {
+        let Ok(url) = Url::parse(url) else {
+            return false;
+        };
+
+        if url.root_domain() == self.url().root_domain() {
+            return false;
+        }
+
+        url.root_domain()
+            .map(|domain| AD_SERVERS.is_adserver(domain))
+            .unwrap_or(false)
+            || url
+                .host_str()
+                .map(|host| AD_SERVERS.is_adserver(host))
+                .unwrap_or(false)
+    }
+
+    fn num_ad_elements(&self) -> usize {
+        self.root
+            .select("script, iframe, ins, div, aside")
+            .unwrap()
+            .filter(|node| {
+                let attributes = node.attributes.borrow();
+
+                if attributes
+                    .get("src")
+                    .map(|src| self.is_ad_url(src))
+                    .unwrap_or(false)
+                {
+                    return true;
+                }
+
+                attributes
+                    .get("class")
+                    .into_iter()
+                    .chain(attributes.get("id"))
+                    .flat_map(|value| value.split(|c: char| !c.is_ascii_alphanumeric()))
+                    .any(|token| AD_MARKERS.contains(&token.to_ascii_lowercase().as_str()))
+            })
+            .count()
+    }
+
+    pub fn content_quality_features(&self) -> ContentQuality {
+        let markup_len = self.root.to_string().len();
+        let text_len = self
+            .root
+            .select_first("body")
+            .map(|body| {
+                body.text_contents()
+                    .split_whitespace()
+                    .map(|w| w.len() + 1)
+                    .sum::<usize>()
+            })
+            .unwrap_or_default();
+
+        let text_markup_ratio = if markup_len == 0 {
+            0.0
+        } else {
+            text_len as f64 / markup_len as f64
+        };
+
+        let clean_text = self.clean_text().map(|s| s.as_str()).unwrap_or_default();
+        let num_words = clean_text.split_whitespace().count();
+
+        let ad_density = self.num_ad_elements() as f64 * 100.0 / num_words.max(100) as f64;
+
+        let paragraph_words: Vec<_> = JustText::paragraphs(self.root.clone())
+            .iter()
+            .map(|paragraph| paragraph.text.split_whitespace().count())
+            .collect();
+        let total_words: usize = paragraph_words.iter().sum();
+        let long_paragraph_ratio = if total_words == 0 {
+            0.0
+        } else {
+            paragraph_words
+                .iter()
+                .filter(|n| **n >= MIN_PARAGRAPH_WORDS)
+                .sum::<usize>() as f64
+                / total_words as f64
+        };
+
+        ContentQuality {
+            text_markup_ratio,
+            ad_density,
+            // pages without a detected language are treated as english, like when stemming
+            reading_ease: matches!(self.lang(), None | Some(Lang::Eng))
+                .then(|| reading_ease(clean_text)),
+            num_words,
+            long_paragraph_ratio,
+        }
+    }
+
+    /// Estimated quality of the content on the page between 0 and 1.
+    pub fn content_quality(&self) -> f64 {
+        self.content_quality_features().score()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = "The river runs through the old town and past the mill. \
+        People have lived along its banks for many centuries. \
+        In the spring the water rises and the fields turn green. \
+        Farmers still bring their goods to the market by the bridge. ";
+
+    #[test]
+    fn syllables() {
+        assert_eq!(num_syllables("cat"), 1);
+        assert_eq!(num_syllables("river"), 2);
+        assert_eq!(num_syllables("generation"), 4);
+        assert_eq!(num_syllables("xyz"), 1);
+        assert_eq!(num_syllables("b"), 1);
+    }
+
+    #[test]
+    fn reading_ease_of_simple_text() {
+        assert_eq!(reading_ease(""), 0.0);
+
+        let simple = reading_ease("The cat sat on the mat. The dog ran.");
+        let hard = reading_ease(
+            "Institutional considerations necessitate comprehensive reorganization \
+            of administrative responsibilities notwithstanding organizational opposition.",
+        );
+
+        assert!(simple > READING_EASE_RANGE.1);
+        assert!(hard < READING_EASE_RANGE.0);
+    }
+
+    #[test]
+    fn article_scores_higher_than_ad_page() {
+        let article = Html::parse(
+            &format!(
+                r#"
+                <html>
+                    <head><title>The river</title></head>
+                    <body>
+                        <article>
+                            <p>{}</p>
+                            <p>{}</p>
+                        </article>
+                    </body>
+                </html>
+                "#,
+                ARTICLE.repeat(5),
+                ARTICLE.repeat(5)
+            ),
+            "https://example.com/river",
+        )
+        .unwrap();
+
+        let ads = Html::parse(
+            r#"
+            <html>
+                <head>
+                    <title>Best deals</title>
+                    <script src="https://pagead2.googlesyndication.com/pagead/js/adsbygoogle.js"></script>
+                </head>
+                <body>
+                    <div class="ad banner"></div>
+                    <ins class="adsbygoogle"></ins>
+                    <div id="sponsored"><a href="/deal">Deal</a></div>
+                    <p>Click here!</p>
+                    <div class="ad"></div>
+                </body>
+            </html>
+            "#,
+            "https://example.com/deals",
+        )
+        .unwrap();
+
+        let article_quality = article.content_quality_features();
+        let ads_quality = ads.content_quality_features();
+
+        assert!(article_quality.long_paragraph_ratio > 0.9);
+        assert!(ads_quality.ad_density > article_quality.ad_density);
+        assert!(ads_quality.num_words < article_quality.num_words);
+
+        assert!(article.content_quality() > ads.content_quality());
+        assert!(article.content_quality() <= 1.0);
+        assert!(ads.content_quality() >= 0.0);
+    }
+
+    #[test]
+    fn empty_page() {
+        let html = Html::parse("<html><body></body></html>", "https://example.com/").unwrap();
+        let quality = html.content_quality_features();
+
+        assert_eq!(quality.num_words, 0);
+        assert_eq!(quality.long_paragraph_ratio, 0.0);
+        assert!(html.content_quality() <= AD_WEIGHT + 1e-6);
+    }
+
+    #[test]
+    fn no_reading_ease_for_other_languages() {
+        let paragraph = "Der Fluss fließt durch die alte Stadt und an der Mühle vorbei. \
+            Seit vielen Jahrhunderten leben Menschen an seinen Ufern. \
+            Im Frühling steigt das Wasser und die Felder werden grün. \
+            Die Bauern bringen ihre Waren noch immer zum Markt an der Brücke. ";
+
+        let html = Html::parse(
+            &format!(
+                "<html><body><article><p>{}</p></article></body></html>",
+                paragraph.repeat(5)
+            ),
+            "https://example.de/fluss",
+        )
+        .unwrap();
+
+        assert_eq!(html.lang(), Some(&Lang::Deu));
+
+        let quality = html.content_quality_features();
+        assert_eq!(quality.reading_ease, None);
+        assert!(quality.score() <= 1.0);
+    }
+}
@@ -0,0 +1,350 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Detection of scholarly pages (papers, preprints etc.).
+//!
+//! Most publishers and repositories annotate their article pages with the
+//! `citation_*` meta tags used by Google Scholar (and sometimes Dublin Core `dc.*` tags).
+//! We convert these into a schema.org `ScholarlyArticle` item so the rest of the
+//! pipeline (optics, structured data, rich snippets) can treat them like any other
+//! structured data found on the page.
+
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::{
+    webpage::schema_org::{Item, Property},
+    OneOrMany,
+};
+
+use super::Html;
+
+/// Only keep this many references per page to avoid bloating the stored schema.
+const MAX_REFERENCES: usize = 64;
+
+pub static DOI_REGEX: once_cell::sync::Lazy<Regex> =
+    once_cell::sync::Lazy::new(|| Regex::new(r"\b(10\.\d{4,9}/[-._;()/:a-zA-Z0-9]+)").unwrap());
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScholarlyMetadata {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub year: Option<u32>,
+    pub venue: Option<String>,
+    pub doi: Option<String>,
+    pub references: Vec<String>,
+}
+
+impl ScholarlyMetadata {
+    fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.authors.is_empty()
+            && self.year.is_none()
+            && self.venue.is_none()
+            && self.doi.is_none()
+            && self.references.is_empty()
+    }
+}
+
+fn parse_year(date: &str) -> Option<u32> {
+    // dates are given in a wide range of formats ("2021/03/01", "2021-03", "March 2021" etc.)
+    // but they all contain the year as the first group of 4 consecutive digits.
+    let mut digits = String::new();
+
+    for c in date.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+
+            if digits.len() == 4 {
+                return digits.parse().ok().filter(|year| *year > 1000);
+            }
+        } else {
+            digits.clear();
+        }
+    }
+
+    None
+}
+
+pub fn extract_doi(text: &str) -> Option<String> {
+    DOI_REGEX
+        .captures(text)
+        .and_then(|caps| caps.get(1))
+        .map(|doi| doi.as_str().trim_end_matches(['.', ',', ';']).to_string())
+}
+
+fn string_prop(s: String) -> OneOrMany<Property> {
+    OneOrMany::One(Property::String(s))
+}
+
+fn typed_item(itemtype: &str, name: String) -> Property {
+    let mut properties = HashMap::new();
+    properties.insert("name".to_string(), string_prop(name));
+
+    Property::Item(Item {
+        itemtype: Some(OneOrMany::One(itemtype.to_string())),
+        properties,
+    })
+}
+
+impl From<ScholarlyMetadata> for Item {
+    fn from(meta: ScholarlyMetadata) -> Self {
+        let mut properties = HashMap::new();
+
+        if let Some(title) = meta.title {
+            properties.insert("name".to_string(), string_prop(title));
+        }
+
+        if !meta.authors.is_empty() {
+            properties.insert(
+                "author".to_string(),
+                OneOrMany::Many(
+                    meta.authors
+                        .into_iter()
+                        .map(|author| typed_item("Person", author))
+                        .collect(),
+                ),
+            );
+        }
+
+        if let Some(year) = meta.year {
+            properties.insert("datePublished".to_string(), string_prop(year.to_string()));
+        }
+
+        if let Some(venue) = meta.venue {
+            properties.insert(
+                "isPartOf".to_string(),
+                OneOrMany::One(typed_item("Periodical", venue)),
+            );
+        }
+
+        if let Some(doi) = meta.doi {
+            properties.insert(
+                "sameAs".to_string(),
+                string_prop(format!("https://doi.org/{doi}")),
+            );
+            properties.insert("identifier".to_string(), string_prop(doi));
+        }
+
+        if !meta.references.is_empty() {
+            properties.insert(
+                "citation".to_string(),
+                OneOrMany::Many(meta.references.into_iter().map(Property::String).collect()),
+            );
+        }
+
+        Item {
+            itemtype: Some(OneOrMany::One("ScholarlyArticle".to_string())),
+            properties,
+        }
+    }
+}
+
+impl Html {
+    /// Extract metadata about the paper if the page looks like a scholarly article.
+    ///
+    /// A page is considered scholarly if it has any of the `citation_*` meta tags
+    /// or if its url contains a DOI.
+    pub fn scholarly_metadata(&self) -> Option<ScholarlyMetadata> {
+        let mut meta = ScholarlyMetadata::default();
+        let mut dublin_core = ScholarlyMetadata::default();
+
+        for tag in self.metadata() {
+            let (Some(name), Some(content)) = (tag.get("name"), tag.get("content")) else {
+                continue;
+            };
+
+            let content = content.trim();
+            if content.is_empty() {
+                continue;
+            }
+
+            match name.to_ascii_lowercase().as_str() {
+                "citation_title" => meta.title = Some(content.to_string()),
+                "citation_author" => meta.authors.push(content.to_string()),
+                "citation_publication_date" | "citation_date" | "citation_online_date" => {
+                    meta.year = meta.year.or_else(|| parse_year(content))
+                }
+                "citation_journal_title"
+                | "citation_conference_title"
+                | "citation_inbook_title"
+                | "citation_dissertation_institution"
+                | "citation_technical_report_institution" => {
+                    meta.venue.get_or_insert_with(|| content.to_string());
+                }
+                "citation_doi" => meta.doi = extract_doi(content),
+                "citation_reference" => {
+                    if meta.references.len() < MAX_REFERENCES {
+                        meta.references.push(content.to_string());
+                    }
+                }
+                "dc.title" => dublin_core.title = Some(content.to_string()),
+                "dc.creator" => dublin_core.authors.push(content.to_string()),
+                "dc.date" => dublin_core.year = dublin_core.year.or_else(|| parse_year(content)),
+                "dc.identifier" => {
+                    if dublin_core.doi.is_none() {
+                        dublin_core.doi = extract_doi(content);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let doi_in_url = extract_doi(&urlencoding::decode(self.url().path()).unwrap_or_default());
+
+        if meta.is_empty() {
+            // dublin core tags are used by a lot of non-scholarly CMSs,
+            // so we only trust them if the url also points to a paper.
+            doi_in_url.as_ref()?;
+            meta = dublin_core;
+        }
+
+        if meta.doi.is_none() {
+            meta.doi = doi_in_url;
+        }
+
+        if meta.title.is_none() {
+            meta.title = self.title();
+        }
+
+        Some(meta)
+    }
+
+    pub fn is_scholarly(&self) -> bool {
+        self.scholarly_metadata().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn citation_meta_tags() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head>
+                    <title>Attention is all you need | Some Journal</title>
+                    <meta name="citation_title" content="Attention Is All You Need">
+                    <meta name="citation_author" content="Vaswani, Ashish">
+                    <meta name="citation_author" content="Shazeer, Noam">
+                    <meta name="citation_publication_date" content="2017/06/12">
+                    <meta name="citation_conference_title" content="Advances in Neural Information Processing Systems">
+                    <meta name="citation_doi" content="doi:10.48550/arXiv.1706.03762">
+                    <meta name="citation_reference" content="citation_title=Neural machine translation by jointly learning to align and translate">
+                </head>
+                <body>
+                    The dominant sequence transduction models are based on complex recurrent or convolutional neural networks.
+                </body>
+            </html>
+            "#,
+            "https://www.example.com/paper/1706.03762",
+        )
+        .unwrap();
+
+        let meta = html.scholarly_metadata().unwrap();
+
+        assert_eq!(meta.title, Some("Attention Is All You Need".to_string()));
+        assert_eq!(
+            meta.authors,
+            vec!["Vaswani, Ashish".to_string(), "Shazeer, Noam".to_string()]
+        );
+        assert_eq!(meta.year, Some(2017));
+        assert_eq!(
+            meta.venue,
+            Some("Advances in Neural Information Processing Systems".to_string())
+        );
+        assert_eq!(meta.doi, Some("10.48550/arXiv.1706.03762".to_string()));
+        assert_eq!(meta.references.len(), 1);
+
+        let item = html
+            .schema_org()
+            .into_iter()
+            .find(|item| item.types_contains("ScholarlyArticle"))
+            .unwrap();
+
+        assert!(item.properties.contains_key("author"));
+        assert!(item.properties.contains_key("citation"));
+        assert_eq!(
+            item.properties
+                .get("identifier")
+                .cloned()
+                .and_then(|p| p.one())
+                .and_then(|p| p.try_into_string()),
+            Some("10.48550/arXiv.1706.03762".to_string())
+        );
+    }
+
+    #[test]
+    fn doi_in_url() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head>
+                    <title>Some paper</title>
+                    <meta name="dc.creator" content="Jane Doe">
+                </head>
+                <body>
+                    test
+                </body>
+            </html>
+            "#,
+            "https://dl.acm.org/doi/10.1145/3397271.3401075",
+        )
+        .unwrap();
+
+        let meta = html.scholarly_metadata().unwrap();
+
+        assert_eq!(meta.doi, Some("10.1145/3397271.3401075".to_string()));
+        assert_eq!(meta.authors, vec!["Jane Doe".to_string()]);
+        assert_eq!(meta.title, Some("Some paper".to_string()));
+    }
+
+    #[test]
+    fn not_scholarly() {
+        let html = Html::parse(
+            r#"
+            <html>
+                <head>
+                    <title>My blog</title>
+                    <meta name="dc.creator" content="Jane Doe">
+                </head>
+                <body>
+                    I liked the paper with doi 10.1145/3397271.3401075
+                </body>
+            </html>
+            "#,
+            "https://www.example.com/blog",
+        )
+        .unwrap();
+
+        assert!(!html.is_scholarly());
+        assert!(!html
+            .schema_org()
+            .iter()
+            .any(|item| item.types_contains("ScholarlyArticle")));
+    }
+
+    #[test]
+    fn year() {
+        assert_eq!(parse_year("2017/06/12"), Some(2017));
+        assert_eq!(parse_year("March 2021"), Some(2021));
+        assert_eq!(parse_year("12-03-1999"), Some(1999));
+        assert_eq!(parse_year("no year"), None);
+    }
+}
@@ -15,6 +15,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
+    outlink_profile::OutlinkFlags,
     schema::{fast_field::FastField, text_field::TextField, Field},
     webgraph::NodeID,
     Result,
@@ -27,16 +28,18 @@ use tantivy::TantivyDocument;
 use url::Url;
 
 mod adservers;
+pub mod homograph;
 pub mod html;
 mod just_text;
 pub mod region;
 pub mod safety_classifier;
 pub mod schema_org;
+pub mod url_cleaner;
 pub mod url_ext;
 use self::html::links::RelFlags;
 pub use self::html::Html;
 
-pub use region::Region;
+pub use region::{Region, RegionTags};
 
 #[derive(Debug)]
 pub struct Webpage {
@@ -44,6 +47,17 @@ pub struct Webpage {
     pub backlink_labels: Vec<String>,
     pub host_centrality: f64,
     pub host_centrality_rank: u64,
+    /// Ratio of the audited outlinks from the host that are broken.
+    pub host_broken_outlink_ratio: f64,
+    /// The profiles the outgoing links of the host have been classified as.
+    pub host_outlink_flags: OutlinkFlags,
+    /// Whether the host has been flagged for serving different content to our crawler.
+    pub host_is_cloaking: bool,
+    /// Ratio of the about descriptors (title, description, organization, contact and imprint)
+    /// found on the homepage of the host.
+    pub host_about_completeness: f64,
+    /// Cluster of related hosts the host belongs to.
+    pub host_cluster: Option<NodeID>,
     pub page_centrality: f64,
     pub page_centrality_rank: u64,
     pub fetch_time_ms: u64,
@@ -65,6 +79,11 @@ impl Default for Webpage {
             backlink_labels: Default::default(),
             host_centrality: Default::default(),
             host_centrality_rank: u64::MAX,
+            host_broken_outlink_ratio: Default::default(),
+            host_outlink_flags: Default::default(),
+            host_is_cloaking: Default::default(),
+            host_about_completeness: Default::default(),
+            host_cluster: Default::default(),
             page_centrality: Default::default(),
             page_centrality_rank: u64::MAX,
             fetch_time_ms: Default::default(),
@@ -87,6 +106,11 @@ impl From<Html> for Webpage {
             backlink_labels: Default::default(),
             host_centrality: Default::default(),
             host_centrality_rank: u64::MAX,
+            host_broken_outlink_ratio: Default::default(),
+            host_outlink_flags: Default::default(),
+            host_is_cloaking: Default::default(),
+            host_about_completeness: Default::default(),
+            host_cluster: Default::default(),
             page_centrality: Default::default(),
             page_centrality_rank: u64::MAX,
             fetch_time_ms: Default::default(),
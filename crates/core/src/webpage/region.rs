@@ -18,7 +18,7 @@ use std::{collections::HashMap, fs::File, io::Write, path::Path};
 
 use utoipa::ToSchema;
 
-use crate::{Error, Result};
+use crate::{localization::Language, Error, Result};
 
 use super::Webpage;
 
@@ -55,22 +55,76 @@ pub const ALL_REGIONS: [Region; 6] = [
     Region::US,
 ];
 
+/// Country codes and names of the regions, in lowercase. The codes are ISO 3166-1 alpha-2
+/// codes, which are also the country code top-level domains.
+static COUNTRIES: [(&str, Region); 16] = [
+    ("dk", Region::Denmark),
+    ("denmark", Region::Denmark),
+    ("danmark", Region::Denmark),
+    ("fr", Region::France),
+    ("france", Region::France),
+    ("de", Region::Germany),
+    ("germany", Region::Germany),
+    ("deutschland", Region::Germany),
+    ("es", Region::Spain),
+    ("spain", Region::Spain),
+    ("españa", Region::Spain),
+    ("espana", Region::Spain),
+    ("us", Region::US),
+    ("usa", Region::US),
+    ("united states", Region::US),
+    ("united states of america", Region::US),
+];
+
 impl Region {
     pub fn name(&self) -> String {
-        // TODO: Translate these strings.
+        self.localized_name(Language::English)
+    }
 
+    pub fn localized_name(&self, language: Language) -> String {
         // The names are used in the UI. In the future we should
         // actually use region, but right now we only detect region
         // from the language of the page, so it makes more sense to
         // just use the language name.
-        match self {
-            Region::All => "All Languages".to_string(),
-            Region::Denmark => "Danish".to_string(),
-            Region::France => "French".to_string(),
-            Region::Germany => "German".to_string(),
-            Region::Spain => "Spainish".to_string(),
-            Region::US => "English".to_string(),
-        }
+        let name = match (self, language) {
+            (Region::All, Language::English) => "All Languages",
+            (Region::All, Language::Danish) => "Alle sprog",
+            (Region::All, Language::French) => "Toutes les langues",
+            (Region::All, Language::German) => "Alle Sprachen",
+            (Region::All, Language::Spanish) => "Todos los idiomas",
+
+            (Region::Denmark, Language::English) => "Danish",
+            (Region::Denmark, Language::Danish) => "Dansk",
+            (Region::Denmark, Language::French) => "Danois",
+            (Region::Denmark, Language::German) => "Dänisch",
+            (Region::Denmark, Language::Spanish) => "Danés",
+
+            (Region::France, Language::English) => "French",
+            (Region::France, Language::Danish) => "Fransk",
+            (Region::France, Language::French) => "Français",
+            (Region::France, Language::German) => "Französisch",
+            (Region::France, Language::Spanish) => "Francés",
+
+            (Region::Germany, Language::English) => "German",
+            (Region::Germany, Language::Danish) => "Tysk",
+            (Region::Germany, Language::French) => "Allemand",
+            (Region::Germany, Language::German) => "Deutsch",
+            (Region::Germany, Language::Spanish) => "Alemán",
+
+            (Region::Spain, Language::English) => "Spanish",
+            (Region::Spain, Language::Danish) => "Spansk",
+            (Region::Spain, Language::French) => "Espagnol",
+            (Region::Spain, Language::German) => "Spanisch",
+            (Region::Spain, Language::Spanish) => "Español",
+
+            (Region::US, Language::English) => "English",
+            (Region::US, Language::Danish) => "Engelsk",
+            (Region::US, Language::French) => "Anglais",
+            (Region::US, Language::German) => "Englisch",
+            (Region::US, Language::Spanish) => "Inglés",
+        };
+
+        name.to_string()
     }
 
     pub fn gl(&self) -> String {
@@ -119,6 +173,47 @@ impl Region {
         }
     }
 
+    /// The region of a country code or country name.
+    pub fn from_country(country: &str) -> Option<Self> {
+        let country = country.trim().to_lowercase();
+
+        COUNTRIES
+            .iter()
+            .find(|(name, _)| *name == country)
+            .map(|(_, region)| *region)
+    }
+
+    /// The regions of the country names mentioned in the text. Country codes are
+    /// not matched, as they are also common words (e.g. "us" and "de").
+    pub fn mentioned_in(text: &str) -> impl Iterator<Item = Self> {
+        let text = format!(
+            " {} ",
+            text.to_lowercase()
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+
+        COUNTRIES
+            .iter()
+            .filter(|(name, _)| name.len() > 2)
+            .filter(move |(name, _)| text.contains(&format!(" {name} ")))
+            .map(|(_, region)| *region)
+    }
+
+    /// The region of an international calling code, without the leading `+`.
+    pub fn from_calling_code(code: &str) -> Option<Self> {
+        match code {
+            "45" => Some(Region::Denmark),
+            "33" => Some(Region::France),
+            "49" => Some(Region::Germany),
+            "34" => Some(Region::Spain),
+            "1" => Some(Region::US),
+            _ => None,
+        }
+    }
+
     pub fn from_id(doc: u64) -> Self {
         ALL_REGIONS[doc as usize]
     }
@@ -135,6 +230,67 @@ impl Region {
     }
 }
 
+/// The set of regions a page is relevant to, e.g. because it lists an address or
+/// phone number in the region. Unlike [`Region::guess_from`], a page can have any
+/// number of tags and the tags are not inferred from the language of the page.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    serde::Serialize,
+    serde::Deserialize,
+    bincode::Encode,
+    bincode::Decode,
+)]
+pub struct RegionTags(u64);
+
+impl RegionTags {
+    pub fn insert(&mut self, region: Region) {
+        if region != Region::All {
+            self.0 |= 1 << region.id();
+        }
+    }
+
+    pub fn contains(&self, region: Region) -> bool {
+        region != Region::All && self.0 & (1 << region.id()) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Region> + '_ {
+        ALL_REGIONS
+            .into_iter()
+            .filter(move |region| self.contains(*region))
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<u64> for RegionTags {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+impl FromIterator<Region> for RegionTags {
+    fn from_iter<T: IntoIterator<Item = Region>>(iter: T) -> Self {
+        let mut tags = Self::default();
+
+        for region in iter {
+            tags.insert(region);
+        }
+
+        tags
+    }
+}
+
 #[derive(
     serde::Serialize, serde::Deserialize, bincode::Encode, bincode::Decode, Default, Clone,
 )]
@@ -255,4 +411,36 @@ mod tests {
         assert_eq!(a.score(&Region::Denmark), 0.4);
         assert_eq!(a.score(&Region::France), 0.0);
     }
+
+    #[test]
+    fn tags() {
+        let tags: RegionTags = [Region::Denmark, Region::US, Region::All]
+            .into_iter()
+            .collect();
+
+        assert!(tags.contains(Region::Denmark));
+        assert!(tags.contains(Region::US));
+        assert!(!tags.contains(Region::France));
+        assert!(!tags.contains(Region::All));
+        assert_eq!(
+            tags.iter().collect::<Vec<_>>(),
+            vec![Region::Denmark, Region::US]
+        );
+        assert_eq!(RegionTags::from(tags.as_u64()), tags);
+        assert!(RegionTags::default().is_empty());
+    }
+
+    #[test]
+    fn countries() {
+        assert_eq!(Region::from_country("DK"), Some(Region::Denmark));
+        assert_eq!(Region::from_country("Deutschland"), Some(Region::Germany));
+        assert_eq!(Region::from_country("uk"), None);
+        assert_eq!(Region::from_calling_code("34"), Some(Region::Spain));
+
+        assert_eq!(
+            Region::mentioned_in("Rue de Rivoli, 75001 Paris, France").collect::<Vec<_>>(),
+            vec![Region::France]
+        );
+        assert_eq!(Region::mentioned_in("Contact us").count(), 0);
+    }
 }
@@ -23,7 +23,7 @@ pub fn convert_all_to_strings(json: &str) -> Result<String, serde_json::Error> {
 
     fn convert_recursively(json: &mut Value) {
         match json {
-            Value::Number(n) if n.is_u64() || n.is_i64() => {
+            Value::Number(n) => {
                 *json = Value::String(n.to_string());
             }
             Value::Bool(b) => {
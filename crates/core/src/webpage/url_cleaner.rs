@@ -0,0 +1,273 @@
+// Stract is an open source web search engine.
+// Copyright (C) 2024 Stract ApS
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Removes tracking parameters from urls so the same page doesn't end up
+//! as several nodes in the webgraph or several documents in the index.
+//!
+//! The rules use the same format as the ClearURLs project. Each provider
+//! has a url pattern and a list of rules that are applied to matching urls:
+//! - `rules` and `referralMarketing` are regexes for query parameter names to remove.
+//! - `rawRules` are regexes that are removed from the full url.
+//! - `redirections` are regexes where the first capture group is the url the link redirects to.
+//! - `exceptions` are regexes for urls the provider should not be applied to.
+//!
+//! A default set of rules is bundled, but they can be replaced with [`load_rules`].
+
+use std::{
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use regex::Regex;
+use url::Url;
+
+use crate::Result;
+
+const DEFAULT_RULES: &str = include_str!("url_rules.json");
+
+static URL_CLEANER: once_cell::sync::Lazy<RwLock<Arc<UrlCleaner>>> =
+    once_cell::sync::Lazy::new(|| {
+        RwLock::new(Arc::new(
+            UrlCleaner::from_json(DEFAULT_RULES).expect("Failed to parse default url rules"),
+        ))
+    });
+
+/// The url cleaner used when urls are normalized.
+pub fn url_cleaner() -> Arc<UrlCleaner> {
+    URL_CLEANER.read().unwrap().clone()
+}
+
+/// Replace the rules used for url normalization with the rules in the file.
+pub fn load_rules<P: AsRef<Path>>(path: P) -> Result<()> {
+    let cleaner = UrlCleaner::from_json(&std::fs::read_to_string(path)?)?;
+    *URL_CLEANER.write().unwrap() = Arc::new(cleaner);
+
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawProvider {
+    url_pattern: String,
+    #[serde(default)]
+    rules: Vec<String>,
+    #[serde(default)]
+    referral_marketing: Vec<String>,
+    #[serde(default)]
+    raw_rules: Vec<String>,
+    #[serde(default)]
+    exceptions: Vec<String>,
+    #[serde(default)]
+    redirections: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct RawRules {
+    providers: std::collections::BTreeMap<String, RawProvider>,
+}
+
+fn case_insensitive(pattern: &str) -> Result<Regex> {
+    Ok(Regex::new(&format!("(?i){pattern}"))?)
+}
+
+struct Provider {
+    url_pattern: Regex,
+    params: Vec<Regex>,
+    raw_rules: Vec<Regex>,
+    exceptions: Vec<Regex>,
+    redirections: Vec<Regex>,
+}
+
+impl Provider {
+    fn new(raw: RawProvider) -> Result<Self> {
+        let many = |patterns: Vec<String>| -> Result<Vec<Regex>> {
+            patterns.iter().map(|p| case_insensitive(p)).collect()
+        };
+
+        Ok(Self {
+            url_pattern: case_insensitive(&raw.url_pattern)?,
+            params: raw
+                .rules
+                .iter()
+                .chain(raw.referral_marketing.iter())
+                .map(|rule| case_insensitive(&format!("^(?:{rule})$")))
+                .collect::<Result<_>>()?,
+            raw_rules: many(raw.raw_rules)?,
+            exceptions: many(raw.exceptions)?,
+            redirections: many(raw.redirections)?,
+        })
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        self.url_pattern.is_match(url) && !self.exceptions.iter().any(|e| e.is_match(url))
+    }
+
+    fn redirection(&self, url: &str) -> Option<Url> {
+        self.redirections.iter().find_map(|redirection| {
+            let target = redirection.captures(url)?.get(1)?.as_str();
+            let target = urlencoding::decode(target).ok()?;
+
+            Url::parse(&target).ok()
+        })
+    }
+
+    fn is_tracking_param(&self, key: &str) -> bool {
+        self.params.iter().any(|param| param.is_match(key))
+    }
+}
+
+pub struct UrlCleaner {
+    providers: Vec<Provider>,
+}
+
+impl UrlCleaner {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let raw: RawRules = serde_json::from_str(json)?;
+
+        Ok(Self {
+            providers: raw
+                .providers
+                .into_values()
+                .map(Provider::new)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Follow known redirection links and remove tracking parameters from the url.
+    /// The query is only re-serialized if a parameter was removed.
+    pub fn clean(&self, url: &mut Url) {
+        for provider in &self.providers {
+            if provider.matches(url.as_str()) {
+                if let Some(target) = provider.redirection(url.as_str()) {
+                    *url = target;
+                }
+            }
+        }
+
+        let providers: Vec<_> = self
+            .providers
+            .iter()
+            .filter(|provider| provider.matches(url.as_str()))
+            .collect();
+
+        for raw_rule in providers.iter().flat_map(|provider| &provider.raw_rules) {
+            if raw_rule.is_match(url.as_str()) {
+                if let Ok(cleaned) = Url::parse(&raw_rule.replace_all(url.as_str(), "")) {
+                    *url = cleaned;
+                }
+            }
+        }
+
+        let num_params = url.query_pairs().count();
+        let params: Vec<_> = url
+            .query_pairs()
+            .filter(|(key, _)| !providers.iter().any(|p| p.is_tracking_param(key)))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        if params.len() == num_params {
+            return;
+        }
+
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(params);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(url: &str) -> String {
+        let cleaner = UrlCleaner::from_json(DEFAULT_RULES).unwrap();
+        let mut url = Url::parse(url).unwrap();
+        cleaner.clean(&mut url);
+        url.to_string()
+    }
+
+    #[test]
+    fn global_rules() {
+        assert_eq!(
+            clean("https://example.com/page?utm_source=news&utm_medium=email&id=1"),
+            "https://example.com/page?id=1"
+        );
+        assert_eq!(
+            clean("https://example.com/?fbclid=abc&GCLID=def"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            clean("https://example.com/?q=utm_source"),
+            "https://example.com/?q=utm_source"
+        );
+        assert_eq!(
+            clean("https://example.com/?a=b%20c"),
+            "https://example.com/?a=b%20c"
+        );
+    }
+
+    #[test]
+    fn provider_rules() {
+        assert_eq!(
+            clean("https://www.amazon.com/Some-Book/dp/B000123/ref=sr_1_1?qid=123&sr=8-1&keywords=book"),
+            "https://www.amazon.com/Some-Book/dp/B000123?keywords=book"
+        );
+        assert_eq!(
+            clean("https://twitter.com/user/status/1?s=20&t=abc"),
+            "https://twitter.com/user/status/1"
+        );
+        assert_eq!(
+            clean("https://example.com/?s=20"),
+            "https://example.com/?s=20"
+        );
+    }
+
+    #[test]
+    fn redirections() {
+        assert_eq!(
+            clean("https://www.google.com/url?sa=t&url=https%3A%2F%2Fexample.com%2Fpage%3Futm_source%3Dgoogle&ved=123"),
+            "https://example.com/page"
+        );
+    }
+
+    #[test]
+    fn custom_rules() {
+        let cleaner = UrlCleaner::from_json(
+            r#"{
+                "providers": {
+                    "example": {
+                        "urlPattern": "^https?://example\\.com",
+                        "rules": ["session"],
+                        "exceptions": ["^https?://example\\.com/login"]
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let mut url = Url::parse("https://example.com/?session=1&utm_source=a").unwrap();
+        cleaner.clean(&mut url);
+        assert_eq!(url.as_str(), "https://example.com/?utm_source=a");
+
+        let mut url = Url::parse("https://example.com/login?session=1").unwrap();
+        cleaner.clean(&mut url);
+        assert_eq!(url.as_str(), "https://example.com/login?session=1");
+
+        assert!(UrlCleaner::from_json("{}").is_err());
+    }
+}
@@ -16,6 +16,8 @@
 
 use publicsuffix::Psl;
 
+use super::url_cleaner::url_cleaner;
+
 static PUBLIC_SUFFIX_LIST: once_cell::sync::Lazy<publicsuffix::List> =
     once_cell::sync::Lazy::new(|| {
         include_str!("../../public_suffix_list.dat")
@@ -58,15 +60,10 @@ impl UrlExt for url::Url {
 
     fn normalize(&mut self) {
         self.set_fragment(None); // remove fragment (e.g. #comments
+        url_cleaner().clean(self); // remove tracking parameters (e.g. utm_source)
 
         let queries: Vec<_> = self
             .query_pairs()
-            .filter(|(key, _)| {
-                !key.starts_with("utm_")
-                    && !key.starts_with("fbclid")
-                    && !key.starts_with("gclid")
-                    && !key.starts_with("msclkid")
-            })
             .map(|(key, value)| (key.to_string(), value.to_string()))
             .collect();
 
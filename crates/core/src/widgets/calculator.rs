@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::localization::Language;
 use crate::widgets::Error;
 use anyhow::{anyhow, Result};
 use hashbrown::HashMap;
@@ -21,6 +22,7 @@ use rand::Rng;
 
 use std::{
     fmt::Debug,
+    path::{Path, PathBuf},
     sync::{atomic::AtomicUsize, Arc, Mutex},
 };
 use utoipa::ToSchema;
@@ -37,18 +39,23 @@ pub struct Calculation {
     pub result: String,
 }
 
-async fn get_rates() -> Result<CurrencyExchange> {
+async fn fetch_rates() -> Result<String> {
     let client = reqwest::Client::new();
     let xml = client
         .get("https://www.ecb.europa.eu/stats/eurofxref/eurofxref-daily.xml")
         .send()
         .await?
+        .error_for_status()?
         .text()
         .await?;
 
+    Ok(xml)
+}
+
+fn parse_rates(xml: &str) -> Result<CurrencyExchange> {
     let mut rates = HashMap::new();
     let mut buf = Vec::new();
-    let mut reader = quick_xml::Reader::from_str(&xml);
+    let mut reader = quick_xml::Reader::from_str(xml);
 
     // read all `Cube` nodes that has the `currency` attribute
     // and insert them into the `rates` map
@@ -102,10 +109,26 @@ impl CurrencyExchange {
     }
 }
 
+/// Rates stored from an earlier fetch, in the xml format published by the ECB.
+fn read_stored_rates(path: &Path) -> Option<CurrencyExchange> {
+    let xml = std::fs::read_to_string(path).ok()?;
+
+    match parse_rates(&xml) {
+        Ok(rates) => Some(rates),
+        Err(err) => {
+            tracing::warn!("failed to parse stored exchange rates: {}", err);
+            None
+        }
+    }
+}
+
 pub enum ExchangeUpdate {
-    #[allow(dead_code)] // used in tests
     None,
-    AsyncTokio,
+    /// Only use the rates stored at the path. They are never updated.
+    Stored(PathBuf),
+    /// Fetch new rates every day. If a path is given, the fetched rates are stored
+    /// there and the stored rates are used until the first fetch succeeds.
+    AsyncTokio(Option<PathBuf>),
 }
 
 struct MaxIterations {
@@ -139,15 +162,32 @@ impl Calculator {
 
         match exchange_update {
             ExchangeUpdate::None => {}
-            ExchangeUpdate::AsyncTokio => {
+            ExchangeUpdate::Stored(path) => {
+                if let Some(rates) = read_stored_rates(&path) {
+                    *exchange.lock().unwrap() = Arc::new(rates);
+                }
+            }
+            ExchangeUpdate::AsyncTokio(path) => {
+                if let Some(rates) = path.as_deref().and_then(read_stored_rates) {
+                    *exchange.lock().unwrap() = Arc::new(rates);
+                }
+
                 let exchange_clone = exchange.clone();
                 tokio::spawn(async move {
                     let mut interval =
                         tokio::time::interval(std::time::Duration::from_secs(60 * 60 * 24));
 
                     loop {
-                        if let Ok(rates) = get_rates().await {
-                            *exchange_clone.lock().unwrap() = Arc::new(rates);
+                        if let Ok(xml) = fetch_rates().await {
+                            if let Ok(rates) = parse_rates(&xml) {
+                                *exchange_clone.lock().unwrap() = Arc::new(rates);
+
+                                if let Some(path) = &path {
+                                    if let Err(err) = std::fs::write(path, &xml) {
+                                        tracing::warn!("failed to store exchange rates: {}", err);
+                                    }
+                                }
+                            }
                         }
 
                         interval.tick().await;
@@ -159,7 +199,7 @@ impl Calculator {
         Self { exchange }
     }
 
-    pub fn try_calculate(&self, expr: &str) -> Result<Calculation, Error> {
+    pub fn try_calculate(&self, expr: &str, language: Language) -> Result<Calculation, Error> {
         let expr = expr.replace(['"', '\''], "");
         // check if expr has at least one digit
         if !expr.chars().any(|c| c.is_ascii_digit()) {
@@ -199,12 +239,7 @@ impl Calculator {
             match span.kind() {
                 fend_core::SpanKind::Number => {
                     let num = span.string().parse::<f64>()?;
-
-                    if num.fract() > 0.01 {
-                        result.push_str(format!("{:.2}", num).as_str());
-                    } else {
-                        result.push_str(num.to_string().as_str());
-                    }
+                    result.push_str(&language.format_number(num));
                 }
                 _ => result.push_str(span.string()),
             }
@@ -224,10 +259,22 @@ mod tests {
     #[test]
     fn it_calculates_simple_expressions() {
         let calc = Calculator::new(ExchangeUpdate::None);
-        assert_eq!(calc.try_calculate("2+2").unwrap().result, 4.0.to_string());
-        assert_eq!(calc.try_calculate("2*2").unwrap().result, 4.0.to_string());
-        assert_eq!(calc.try_calculate("2*3").unwrap().result, 6.0.to_string());
-        assert_eq!(calc.try_calculate("6/2").unwrap().result, 3.0.to_string());
+        assert_eq!(
+            calc.try_calculate("2+2", Language::English).unwrap().result,
+            4.0.to_string()
+        );
+        assert_eq!(
+            calc.try_calculate("2*2", Language::English).unwrap().result,
+            4.0.to_string()
+        );
+        assert_eq!(
+            calc.try_calculate("2*3", Language::English).unwrap().result,
+            6.0.to_string()
+        );
+        assert_eq!(
+            calc.try_calculate("6/2", Language::English).unwrap().result,
+            3.0.to_string()
+        );
     }
 
     #[test]
@@ -235,12 +282,47 @@ mod tests {
         let calc = Calculator::new(ExchangeUpdate::None);
 
         assert_eq!(
-            calc.try_calculate("2+2*6").unwrap().result,
+            calc.try_calculate("2+2*6", Language::English)
+                .unwrap()
+                .result,
             14.0.to_string()
         );
         assert_eq!(
-            calc.try_calculate("(2+2)*6").unwrap().result,
+            calc.try_calculate("(2+2)*6", Language::English)
+                .unwrap()
+                .result,
             24.0.to_string()
         );
     }
+
+    #[test]
+    fn it_formats_numbers_for_language() {
+        let calc = Calculator::new(ExchangeUpdate::None);
+
+        assert_eq!(
+            calc.try_calculate("1/4", Language::English).unwrap().result,
+            "0.25"
+        );
+        assert_eq!(
+            calc.try_calculate("1/4", Language::Danish).unwrap().result,
+            "0,25"
+        );
+    }
+
+    #[test]
+    fn it_uses_stored_exchange_rates() {
+        let path = crate::gen_temp_path().join("rates.xml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &path,
+            r#"<gesmes:Envelope><Cube><Cube time="2024-03-01"><Cube currency="USD" rate="1.0803"/><Cube currency="DKK" rate="7.4536"/></Cube></Cube></gesmes:Envelope>"#,
+        )
+        .unwrap();
+
+        let calc = Calculator::new(ExchangeUpdate::Stored(path));
+        let exchange = calc.exchange.lock().unwrap().clone();
+
+        assert_eq!(exchange.rates.get("USD"), Some(&1.0803));
+        assert_eq!(exchange.rates.get("EUR"), Some(&1.0));
+    }
 }
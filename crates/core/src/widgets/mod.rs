@@ -14,11 +14,13 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::path::PathBuf;
+
 use thiserror::Error;
 use utoipa::ToSchema;
 
 use self::thesaurus::ThesaurusWidget;
-use crate::config::WidgetsConfig;
+use crate::{config::WidgetsConfig, localization::Language};
 
 use self::calculator::{Calculation, Calculator};
 use anyhow::{anyhow, Result};
@@ -52,10 +54,12 @@ impl Widgets {
             None
         };
 
-        let exchange_update = if config.calculator_fetch_currencies_exchange {
-            calculator::ExchangeUpdate::AsyncTokio
-        } else {
-            calculator::ExchangeUpdate::None
+        let stored_rates = config.calculator_exchange_rates_path.map(PathBuf::from);
+
+        let exchange_update = match (config.calculator_fetch_currencies_exchange, stored_rates) {
+            (true, stored_rates) => calculator::ExchangeUpdate::AsyncTokio(stored_rates),
+            (false, Some(path)) => calculator::ExchangeUpdate::Stored(path),
+            (false, None) => calculator::ExchangeUpdate::None,
         };
 
         Ok(Self {
@@ -64,11 +68,11 @@ impl Widgets {
         })
     }
 
-    pub fn widget(&self, query: &str) -> Option<Widget> {
+    pub fn widget(&self, query: &str, language: Language) -> Option<Widget> {
         let query = query.to_lowercase();
 
         self.calculator
-            .try_calculate(&query)
+            .try_calculate(&query, language)
             .ok()
             .map(Widget::Calculator)
             .or_else(|| {
@@ -25,7 +25,7 @@
 //! If the item type `T` implements `ConstSerializable`, then the `ConstIterableStoreWriter` can be
 //! used to write items to the file without intermediate headers as the size of the serialied item is known upfront.
 
-use crate::{owned_bytes::OwnedBytes, ConstSerializable, Result};
+use crate::{owned_bytes::OwnedBytes, Advice, ConstSerializable, Result};
 use std::{
     cmp::Reverse,
     io::{self, Write},
@@ -162,6 +162,35 @@ impl<T> IterableStoreReader<T> {
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// The byte range of each item in the reader, without decoding the items.
+    /// A range can be passed to [`IterableStoreReader::slice`] to read the item.
+    pub fn item_ranges(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let mut offset = self.offset;
+
+        std::iter::from_fn(move || {
+            let header_end = offset + IterableHeader::serialized_size();
+            if header_end > self.data.len() {
+                return None;
+            }
+
+            let header = IterableHeader::deserialize(&self.data[offset..header_end]).ok()?;
+            let range = offset..header_end + header.num_upcoming_bytes as usize;
+            offset = range.end;
+
+            Some(range)
+        })
+    }
+
+    /// See [`OwnedBytes::advise`].
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        self.data.advise(advice)
+    }
+
+    /// See [`OwnedBytes::lock`].
+    pub fn lock(&self) -> io::Result<()> {
+        self.data.lock()
+    }
 }
 
 impl<T> Iterator for IterableStoreReader<T>
@@ -332,6 +361,16 @@ impl<T> ConstIterableStoreReader<T> {
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// See [`OwnedBytes::advise`].
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        self.data.advise(advice)
+    }
+
+    /// See [`OwnedBytes::lock`].
+    pub fn lock(&self) -> io::Result<()> {
+        self.data.lock()
+    }
 }
 
 impl<T> Iterator for ConstIterableStoreReader<T>
@@ -385,6 +424,12 @@ mod tests {
 
         let reader = IterableStoreReader::from_bytes(writer);
 
+        let items: Vec<i32> = reader
+            .item_ranges()
+            .map(|range| reader.slice(range).next().unwrap())
+            .collect();
+        assert_eq!(items, vec![1, 2, 3]);
+
         let items: Vec<i32> = reader.collect();
         assert_eq!(items, vec![1, 2, 3]);
     }
@@ -25,4 +25,5 @@ pub mod peekable;
 pub mod random_lookup;
 
 pub use const_serializable::ConstSerializable;
+pub use memmap2::Advice;
 pub use peekable::Peekable;
@@ -17,6 +17,7 @@
 //! This is taken from https://docs.rs/ownedbytes/latest/ownedbytes/
 //! to avoid having to pull in another dependency.
 
+use memmap2::{Advice, Mmap};
 use stable_deref_trait::StableDeref;
 use std::{
     fmt, io,
@@ -28,14 +29,21 @@ use std::{
 pub struct OwnedBytes {
     data: &'static [u8],
     box_stable_deref: Arc<dyn Deref<Target = [u8]> + Sync + Send>,
+    /// The memory map the bytes are read from, if any.
+    mmap: Option<Arc<Mmap>>,
 }
 
 impl OwnedBytes {
     pub fn mmap_from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let path = path.as_ref();
-        let mmap = unsafe { memmap2::Mmap::map(&std::fs::File::open(path)?)? };
+        let mmap = Arc::new(unsafe { Mmap::map(&std::fs::File::open(path)?)? });
+        let data = unsafe { &*(mmap.deref().deref() as *const [u8]) };
 
-        Ok(Self::new(mmap))
+        Ok(Self {
+            data,
+            box_stable_deref: mmap.clone(),
+            mmap: Some(mmap),
+        })
     }
 
     pub fn empty() -> Self {
@@ -51,6 +59,7 @@ impl OwnedBytes {
         Self {
             data,
             box_stable_deref,
+            mmap: None,
         }
     }
 
@@ -64,6 +73,28 @@ impl OwnedBytes {
         Self {
             data: &self.data[range],
             box_stable_deref: self.box_stable_deref.clone(),
+            mmap: self.mmap.clone(),
+        }
+    }
+
+    /// Advise the kernel how the bytes will be accessed. Only the pages of this slice of
+    /// the memory map are affected. Does nothing if the bytes are not memory mapped.
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        match &self.mmap {
+            Some(mmap) if !self.data.is_empty() => {
+                let offset = self.data.as_ptr() as usize - mmap.as_ptr() as usize;
+                mmap.advise_range(advice, offset, self.data.len())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Lock the whole underlying memory map in memory, so reads never wait for the disk.
+    /// Does nothing if the bytes are not memory mapped.
+    pub fn lock(&self) -> io::Result<()> {
+        match &self.mmap {
+            Some(mmap) if !mmap.is_empty() => mmap.lock(),
+            _ => Ok(()),
         }
     }
 }
@@ -103,6 +134,7 @@ impl Clone for OwnedBytes {
         OwnedBytes {
             data: self.data,
             box_stable_deref: self.box_stable_deref.clone(),
+            mmap: self.mmap.clone(),
         }
     }
 }
@@ -139,6 +171,28 @@ mod tests {
         assert_eq!(bytes.as_slice(), &[]);
     }
 
+    #[test]
+    fn test_advise_mmap() {
+        let path = std::env::temp_dir().join(format!("owned-bytes-{}", std::process::id()));
+        std::fs::write(&path, vec![7; 3 * 4096]).unwrap();
+
+        let bytes = OwnedBytes::mmap_from_path(&path).unwrap();
+        let slice = bytes.slice(5000..9000);
+
+        slice.advise(Advice::Random).unwrap();
+        slice.advise(Advice::WillNeed).unwrap();
+        bytes.advise(Advice::Sequential).unwrap();
+        assert_eq!(slice.as_slice(), &[7; 4000][..]);
+
+        // not memory mapped
+        OwnedBytes::new(vec![1, 2, 3])
+            .advise(Advice::Random)
+            .unwrap();
+        OwnedBytes::empty().lock().unwrap();
+
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn test_read() {
         let mut bytes = OwnedBytes::new(vec![1, 2, 3, 4, 5]);
@@ -19,7 +19,7 @@ use std::{
     path::Path,
 };
 
-use crate::{owned_bytes::OwnedBytes, ConstSerializable};
+use crate::{owned_bytes::OwnedBytes, Advice, ConstSerializable};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ItemId(u64);
@@ -114,6 +114,16 @@ where
             _phantom: std::marker::PhantomData,
         }
     }
+
+    /// See [`OwnedBytes::advise`].
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        self.data.advise(advice)
+    }
+
+    /// See [`OwnedBytes::lock`].
+    pub fn lock(&self) -> io::Result<()> {
+        self.data.lock()
+    }
 }
 
 impl<V> From<OwnedBytes> for RandomLookup<V> {
@@ -157,6 +157,22 @@ pub struct Matching {
     pub location: MatchLocation,
 }
 
+impl Matching {
+    /// The site if the matching is on the form `Site("|...|")` or `Domain("|...|")`.
+    fn as_exact_site(&self) -> Option<String> {
+        if !matches!(self.location, MatchLocation::Site | MatchLocation::Domain) {
+            return None;
+        }
+
+        match self.pattern.as_slice() {
+            [PatternPart::Anchor, PatternPart::Raw(site), PatternPart::Anchor] => {
+                Some(site.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
 impl Display for Matching {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self.location {
@@ -371,6 +387,34 @@ impl Optic {
     pub fn parse(optic: &str) -> Result<Self> {
         parse(optic)
     }
+
+    /// If the optic discards every page that is not on one of a fixed set of sites or domains,
+    /// return those sites. This is the case when the optic uses `DiscardNonMatching` and every
+    /// rule that does not discard pages requires a `Site("|...|")` or `Domain("|...|")` match.
+    /// Returns `None` if the optic can match pages on any site.
+    pub fn required_sites(&self) -> Option<Vec<String>> {
+        if !self.discard_non_matching {
+            return None;
+        }
+
+        let mut res = Vec::new();
+
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| !matches!(rule.action, Action::Discard))
+        {
+            for matching in &rule.matches {
+                res.push(matching.iter().find_map(Matching::as_exact_site)?);
+            }
+        }
+
+        if res.is_empty() {
+            None
+        } else {
+            Some(res)
+        }
+    }
 }
 
 impl Display for Optic {
@@ -627,4 +671,65 @@ mod tests {
 
         assert_eq!(optic, parsed);
     }
+
+    #[test]
+    fn required_sites() {
+        let optic = Optic::parse(
+            r#"
+            DiscardNonMatching;
+            Rule {
+                Matches {
+                    Site("|a.com|"),
+                    Title("test")
+                },
+                Matches {
+                    Domain("|b.com|")
+                }
+            };
+            Rule {
+                Matches {
+                    Url("spam")
+                },
+                Action(Discard)
+            };
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            optic.required_sites(),
+            Some(vec!["a.com".to_string(), "b.com".to_string()])
+        );
+
+        // pages on any site can match the second rule
+        let optic = Optic::parse(
+            r#"
+            DiscardNonMatching;
+            Rule {
+                Matches {
+                    Site("|a.com|")
+                }
+            };
+            Rule {
+                Matches {
+                    Site("a.com")
+                }
+            };
+            "#,
+        )
+        .unwrap();
+        assert_eq!(optic.required_sites(), None);
+
+        let optic = Optic::parse(
+            r#"
+            Rule {
+                Matches {
+                    Site("|a.com|")
+                }
+            };
+            "#,
+        )
+        .unwrap();
+        assert_eq!(optic.required_sites(), None);
+    }
 }
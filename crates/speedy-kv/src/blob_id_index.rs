@@ -17,6 +17,7 @@
 use fst::{IntoStreamer, Streamer};
 
 use crate::Result;
+use file_store::Advice;
 use std::{
     io::{BufWriter, Write},
     ops::RangeBounds,
@@ -49,6 +50,16 @@ impl<K> BlobIdIndex<K> {
         format!("{}.ids", uuid)
     }
 
+    pub fn advise(&self, advice: Advice) -> Result<()> {
+        self.fst.as_fst().as_inner().advise(advice)?;
+        Ok(())
+    }
+
+    pub fn lock(&self) -> Result<()> {
+        self.fst.as_fst().as_inner().lock()?;
+        Ok(())
+    }
+
     pub fn get(&self, key: &[u8]) -> Option<BlobId> {
         let mut stream = self
             .fst
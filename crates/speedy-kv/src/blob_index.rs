@@ -15,6 +15,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
 use crate::Result;
+use file_store::Advice;
 use std::{
     io::Write,
     path::{Path, PathBuf},
@@ -55,6 +56,16 @@ impl BlobIndex {
     pub fn path(&self) -> &Path {
         &self.path
     }
+
+    pub fn advise(&self, advice: Advice) -> Result<()> {
+        self.data.advise(advice)?;
+        Ok(())
+    }
+
+    pub fn lock(&self) -> Result<()> {
+        self.data.lock()?;
+        Ok(())
+    }
 }
 
 pub struct BlobIndexWriter<W>
@@ -15,6 +15,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>
 
 use crate::Result;
+use file_store::Advice;
 use std::{
     io::{BufWriter, Write},
     path::{Path, PathBuf},
@@ -55,6 +56,22 @@ impl<K, V> BlobStore<K, V> {
         &self.path
     }
 
+    pub fn advise(&self, advice: Advice) -> Result<()> {
+        if let Some(bytes) = self.bytes.as_ref().filter(|bytes| !bytes.is_empty()) {
+            bytes.advise(advice)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn lock(&self) -> Result<()> {
+        if let Some(bytes) = self.bytes.as_ref().filter(|bytes| !bytes.is_empty()) {
+            bytes.lock()?;
+        }
+
+        Ok(())
+    }
+
     fn get_bytes<T>(&self, range: &std::ops::Range<u64>) -> SerializedRef<'_, T> {
         let bytes = match self.bytes.as_ref() {
             Some(bytes) => bytes.as_ref(),
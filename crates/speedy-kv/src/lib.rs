@@ -47,7 +47,7 @@ mod blob_store;
 mod segment;
 mod serialized;
 
-use file_store::{ConstSerializable, Peekable};
+use file_store::{Advice, ConstSerializable, Peekable};
 use segment::SortedSegments;
 pub use serialized::{Serialized, SerializedRef};
 
@@ -284,6 +284,25 @@ impl<K, V> Db<K, V> {
         self.live_segment.len()
     }
 
+    /// Advise the kernel how the committed segments will be accessed.
+    pub fn advise(&self, advice: Advice) -> Result<()> {
+        for segment in &self.segments {
+            segment.advise(advice)?;
+        }
+
+        Ok(())
+    }
+
+    /// Lock the committed segments in memory. Segments that are committed or
+    /// merged later are not locked.
+    pub fn lock(&self) -> Result<()> {
+        for segment in &self.segments {
+            segment.lock()?;
+        }
+
+        Ok(())
+    }
+
     fn save_meta(&self) -> Result<()> {
         let meta_path = self.folder.join("meta.json");
         let meta = serde_json::to_string_pretty(&self.meta)?;
@@ -347,6 +366,32 @@ impl<K, V> Db<K, V> {
             .find_map(|segment| segment.get_raw(key.as_bytes()).ok().flatten())
     }
 
+    /// The values of the keys, in the order of the keys. Each segment is visited once
+    /// for the keys that were not found in a newer segment, and the keys are looked up
+    /// in sorted order so the lookups move forward through the files of the segment.
+    pub fn get_many_raw<'a>(&'a self, keys: &[&[u8]]) -> Vec<Option<SerializedRef<'a, V>>> {
+        let mut res = vec![None; keys.len()];
+
+        let mut remaining: Vec<usize> = (0..keys.len()).collect();
+        remaining.sort_by_key(|&i| keys[i]);
+
+        for segment in self.segments.iter().rev() {
+            if remaining.is_empty() {
+                break;
+            }
+
+            remaining.retain(|&i| match segment.get_raw(keys[i]).ok().flatten() {
+                Some(value) => {
+                    res[i] = Some(value);
+                    false
+                }
+                None => true,
+            });
+        }
+
+        res
+    }
+
     pub fn search_raw<'a, A>(
         &'a self,
         query: A,
@@ -373,6 +418,23 @@ impl<K, V> Db<K, V> {
             .flat_map(move |segment| segment.range_raw(range.clone()))
     }
 
+    /// The keys in the range in sorted order, like [`Db::sorted_iter_raw`]. Each segment
+    /// is only read from the start of the range.
+    pub fn sorted_range_raw<'a, R>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = (SerializedRef<'a, K>, SerializedRef<'a, V>)> + 'a
+    where
+        R: RangeBounds<SerializedRef<'a, K>> + Clone + 'a,
+    {
+        SortedSegments::new(
+            self.segments
+                .iter()
+                .map(|s| Peekable::new(s.range_raw(range.clone())))
+                .collect(),
+        )
+    }
+
     pub fn commit(&mut self) -> Result<()> {
         if self.live_segment.db.is_empty() {
             return Ok(());
@@ -456,6 +518,27 @@ where
             None => Ok(None),
         }
     }
+
+    /// See [`Db::get_many_raw`].
+    pub fn get_many(&self, keys: &[K]) -> Result<Vec<Option<V>>> {
+        let keys = keys
+            .iter()
+            .map(|key| bincode::encode_to_vec(key, bincode::config::standard()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let keys: Vec<_> = keys.iter().map(|key| key.as_slice()).collect();
+
+        self.get_many_raw(&keys)
+            .into_iter()
+            .map(|value| match value {
+                Some(v) => {
+                    let (v, _) =
+                        bincode::decode_from_slice(v.as_bytes(), bincode::config::standard())?;
+                    Ok(Some(v))
+                }
+                None => Ok(None),
+            })
+            .collect()
+    }
 }
 impl<K, V> Db<K, V>
 where
@@ -577,6 +660,47 @@ mod tests {
         assert_eq!(db.get(&4).unwrap(), Some(5));
     }
 
+    #[test]
+    fn test_get_many() {
+        let mut db = Db::open_or_create(gen_temp_path()).unwrap();
+
+        db.insert(1, 2).unwrap();
+        db.insert(2, 3).unwrap();
+        db.commit().unwrap();
+
+        db.insert(2, 4).unwrap();
+        db.insert(300, 5).unwrap();
+        db.commit().unwrap();
+
+        assert_eq!(
+            db.get_many(&[300, 7, 2, 1, 2]).unwrap(),
+            vec![Some(5), None, Some(4), Some(2), Some(4)]
+        );
+        assert_eq!(db.get_many(&[]).unwrap(), Vec::<Option<u64>>::new());
+    }
+
+    #[test]
+    fn test_sorted_range() {
+        let mut db: Db<Vec<u8>, Vec<u8>> = Db::open_or_create(gen_temp_path()).unwrap();
+
+        db.insert_raw(b"a".to_vec(), b"1".to_vec());
+        db.insert_raw(b"c".to_vec(), b"2".to_vec());
+        db.commit().unwrap();
+
+        db.insert_raw(b"b".to_vec(), b"3".to_vec());
+        db.insert_raw(b"d".to_vec(), b"4".to_vec());
+        db.commit().unwrap();
+
+        let start: &[u8] = b"b";
+        let end: &[u8] = b"d";
+        let keys: Vec<_> = db
+            .sorted_range_raw(SerializedRef::from(start)..SerializedRef::from(end))
+            .map(|(key, _)| key.as_bytes().to_vec())
+            .collect();
+
+        assert_eq!(keys, vec![b"b".to_vec(), b"c".to_vec()]);
+    }
+
     #[test]
     fn test_segment_merge() {
         let mut db = Db::open_or_create(gen_temp_path()).unwrap();
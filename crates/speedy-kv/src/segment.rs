@@ -21,7 +21,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use file_store::Peekable;
+use file_store::{Advice, Peekable};
 
 use super::{
     blob_id_index::{BlobIdIndex, BlobIdIndexWriter},
@@ -265,6 +265,18 @@ impl<K, V> Segment<K, V> {
         self.id_index.is_empty()
     }
 
+    pub fn advise(&self, advice: Advice) -> Result<()> {
+        self.id_index.advise(advice)?;
+        self.blob_index.advise(advice)?;
+        self.store.advise(advice)
+    }
+
+    pub fn lock(&self) -> Result<()> {
+        self.id_index.lock()?;
+        self.blob_index.lock()?;
+        self.store.lock()
+    }
+
     fn bloom_path(&self) -> PathBuf {
         self.folder.join(Self::bloom_file_name(self.uuid))
     }
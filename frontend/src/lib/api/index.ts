@@ -102,6 +102,8 @@ export const api = {
   autosuggest: (
     params: {
       q: string;
+      safeSearch?: string;
+      debug?: string;
     },
     options?: ApiOptions,
   ) =>
@@ -110,18 +112,45 @@ export const api = {
       `/beta/api/autosuggest?${new URLSearchParams(params)}`,
       options,
     ),
+  autosuggestWithPreview: (
+    params: {
+      q: string;
+      safeSearch?: string;
+      debug?: string;
+    },
+    options?: ApiOptions,
+  ) =>
+    requestJson<AutosuggestWithPreview>(
+      'POST',
+      `/beta/api/autosuggest?${new URLSearchParams({ ...params, preview: 'true' })}`,
+      options,
+    ),
   exploreExport: (body: ExploreExportOpticParams, options?: ApiOptions) =>
     requestPlain('POST', `/beta/api/explore/export`, body, options),
   hostsExport: (body: HostsExportOpticParams, options?: ApiOptions) =>
     requestPlain('POST', `/beta/api/hosts/export`, body, options),
   search: (body: ApiSearchQuery, options?: ApiOptions) =>
     requestJson<ApiSearchResult>('POST', `/beta/api/search`, body, options),
+  searchBatch: (body: ApiBatchSearchQuery, options?: ApiOptions) =>
+    requestJson<ApiBatchSearchResult[]>('POST', `/beta/api/search/batch`, body, options),
   searchSidebar: (body: SidebarQuery, options?: ApiOptions) =>
     requestJson<DisplayedSidebar>('POST', `/beta/api/search/sidebar`, body, options),
   searchSpellcheck: (body: SpellcheckQuery, options?: ApiOptions) =>
     requestJson<HighlightedSpellCorrection>('POST', `/beta/api/search/spellcheck`, body, options),
   searchWidget: (body: WidgetQuery, options?: ApiOptions) =>
     requestJson<Widget>('POST', `/beta/api/search/widget`, body, options),
+  similarSites: (
+    query: {
+      host: string;
+      page?: string;
+    },
+    options?: ApiOptions,
+  ) =>
+    requestJson<SimilarSitesResponse>(
+      'GET',
+      `/api/v1/similar_sites?${new URLSearchParams(query)}`,
+      options,
+    ),
   summarize: (
     query: {
       query: string;
@@ -188,9 +217,27 @@ export const api = {
     ),
 };
 
+export type ApiBatchSearchQuery = {
+  queries: ApiSearchQuery[];
+};
+export type ApiBatchSearchResult =
+  | (WebsitesResult & {
+      _type: 'websites';
+    })
+  | (BangHit & {
+      _type: 'bang';
+    })
+  | {
+      _type: 'error';
+      message: string;
+    };
 export type ApiSearchQuery = {
   countResultsExact?: boolean;
+  disableRewrites?: boolean;
+  excludeHosts?: string[];
   flattenResponse?: boolean;
+  gl?: string;
+  hl?: string;
   hostRankings?: HostRankings;
   numResults?: number;
   optic?: string;
@@ -201,6 +248,8 @@ export type ApiSearchQuery = {
   safeSearch?: boolean;
   selectedRegion?: Region;
   signalCoefficients?: {};
+  snapshot?: string;
+  withinResults?: string;
 };
 export type ApiSearchResult =
   | (WebsitesResult & {
@@ -209,6 +258,10 @@ export type ApiSearchResult =
   | (BangHit & {
       _type: 'bang';
     });
+export type AutosuggestWithPreview = {
+  preview?: ResultPreview;
+  suggestions: Suggestion[];
+};
 export type Bang = {
   c?: string;
   d?: string;
@@ -274,12 +327,14 @@ export type DisplayedSidebar =
     };
 export type DisplayedWebpage = {
   domain: string;
+  homographWarning?: Homograph;
   likelyHasAds: boolean;
   likelyHasPaywall: boolean;
   prettyUrl: string;
   rankingSignals?: {};
   richSnippet?: RichSnippet;
   score?: number;
+  securityThreat?: Threat;
   site: string;
   snippet: Snippet;
   structuredData?: StructuredData[];
@@ -305,6 +360,7 @@ export type ExploreExportOpticParams = {
   similarHosts: string[];
 };
 export type FullEdge = {
+  cursor: string;
   from: Node;
   label: string;
   to: Node;
@@ -319,6 +375,10 @@ export type HighlightedSpellCorrection = {
   highlighted: HighlightedFragment[];
   raw: string;
 };
+export type Homograph = {
+  lookalike: string;
+  unicodeHost: string;
+};
 export type HostRankings = {
   blocked: string[];
   disliked: string[];
@@ -354,8 +414,23 @@ export type PartOfSpeechMeaning = {
   pos: PartOfSpeech;
 };
 export type Property = string | StructuredData;
+export type QueryRewrite = {
+  from: string;
+  kind: QueryRewriteKind;
+  to: string;
+};
+export type QueryRewriteKind = 'operatorNormalization' | 'spellCorrection';
+export const QUERY_REWRITE_KINDS = [
+  'operatorNormalization',
+  'spellCorrection',
+] satisfies QueryRewriteKind[];
 export type Region = 'All' | 'Denmark' | 'France' | 'Germany' | 'Spain' | 'US';
 export const REGIONS = ['All', 'Denmark', 'France', 'Germany', 'Spain', 'US'] satisfies Region[];
+export type ResultPreview = {
+  favicon: string;
+  title: string;
+  url: string;
+};
 export type ReturnBody =
   | {
       _type: 'all';
@@ -416,6 +491,16 @@ export type SignalEnumDiscriminants =
   | 'url_digits'
   | 'url_slashes'
   | 'link_density'
+  | 'host_broken_outlink_ratio'
+  | 'host_cloaking'
+  | 'host_about'
+  | 'content_quality'
+  | 'is_https'
+  | 'host_affiliate_links'
+  | 'host_link_rotator'
+  | 'host_pbn'
+  | 'local_region'
+  | 'parked_domain'
   | 'title_embedding_similarity'
   | 'keyword_embedding_similarity';
 export const SIGNAL_ENUM_DISCRIMINANTS = [
@@ -457,6 +542,16 @@ export const SIGNAL_ENUM_DISCRIMINANTS = [
   'url_digits',
   'url_slashes',
   'link_density',
+  'host_broken_outlink_ratio',
+  'host_cloaking',
+  'host_about',
+  'content_quality',
+  'is_https',
+  'host_affiliate_links',
+  'host_link_rotator',
+  'host_pbn',
+  'local_region',
+  'parked_domain',
   'title_embedding_similarity',
   'keyword_embedding_similarity',
 ] satisfies SignalEnumDiscriminants[];
@@ -468,6 +563,20 @@ export type SimilarHostsParams = {
   hosts: string[];
   topN: number;
 };
+export type SimilarSite = {
+  explanation: SimilarityExplanation;
+  host: string;
+  score: number;
+};
+export type SimilarSitesResponse = {
+  nextPage?: number;
+  sites: SimilarSite[];
+};
+export type SimilarityExplanation = {
+  hostBacklinks: number;
+  sharedBacklinks: number;
+  siteBacklinks: number;
+};
 export type Snippet = {
   date?: string;
   text: TextSnippet;
@@ -491,6 +600,13 @@ export type StructuredData = {
 export type Suggestion = {
   highlighted: HighlightedFragment[];
   raw: string;
+  score?: SuggestionScore;
+};
+export type SuggestionScore = {
+  frequency: number;
+  prefixMatch: number;
+  total: number;
+  trend: number;
 };
 export type TextSnippet = {
   fragments: HighlightedFragment[];
@@ -499,10 +615,13 @@ export type ThesaurusWidget = {
   meanings: PartOfSpeechMeaning[];
   term: Lemma;
 };
+export type Threat = 'malware' | 'phishing';
 export type UrlWrapper = string;
 export type WebsitesResult = {
   hasMoreResults: boolean;
   numHits: Count;
+  resultSet?: string;
+  rewrites: QueryRewrite[];
   searchDurationMs: number;
   webpages: DisplayedWebpage[];
 };
@@ -516,6 +635,7 @@ export type Widget =
       value: ThesaurusWidget;
     };
 export type WidgetQuery = {
+  hl?: string;
   query: string;
 };
 export type WordMeaning = {
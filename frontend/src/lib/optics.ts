@@ -1,3 +1,5 @@
+import { env } from '$env/dynamic/public';
+
 export type OpticOption = {
   name: string;
   url: string;
@@ -74,8 +76,12 @@ export const DEFAULT_OPTICS = [
  * Fetces the given `opticUrl` if allowed. The rules for which are allowed
  * should consider potentially malicious URLs such as `file://` or
  * internal/local IP addresses.
+ *
+ * Nothing is fetched if `PUBLIC_DISABLE_OUTBOUND_REQUESTS` is set, e.g. when
+ * the instance is served as an onion service.
  */
 export const fetchRemoteOptic = async (opts: { opticUrl: string; fetch?: typeof fetch }) => {
+  if (env.PUBLIC_DISABLE_OUTBOUND_REQUESTS === 'true') return void 0;
   if (opts.opticUrl.startsWith('file://')) return void 0;
   const response = await (opts.fetch ?? fetch)(opts.opticUrl);
   return await response.text();